@@ -7,15 +7,83 @@
 use {
     crate::{
         control::ControlParagraph,
-        dependency::{DependencyList, PackageDependencyFields},
+        dependency::{
+            DependencyList, DependencyVersionConstraint, PackageDependencyFields, SingleDependency,
+            VersionRelationship,
+        },
         error::{DebianError, Result},
         io::ContentDigest,
         package_version::PackageVersion,
         repository::{builder::DebPackageReference, release::ChecksumType},
     },
-    std::ops::{Deref, DerefMut},
+    std::{
+        fmt::{Display, Formatter},
+        ops::{Deref, DerefMut},
+        str::FromStr,
+    },
 };
 
+/// Value of the `Multi-Arch` field.
+///
+/// See <https://wiki.debian.org/Multiarch/HOWTO>.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MultiArch {
+    /// The package can't be installed alongside itself for another architecture.
+    No,
+    /// The package is co-installable with itself for other architectures.
+    Same,
+    /// The package can satisfy dependencies of packages of other architectures.
+    Foreign,
+    /// Dependents can choose to be satisfied by the package of any architecture
+    /// via `package:any`.
+    Allowed,
+}
+
+impl FromStr for MultiArch {
+    type Err = DebianError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(Self::No),
+            "same" => Ok(Self::Same),
+            "foreign" => Ok(Self::Foreign),
+            "allowed" => Ok(Self::Allowed),
+            _ => Err(DebianError::MultiArchUnknown(s.to_string())),
+        }
+    }
+}
+
+impl Display for MultiArch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::No => "no",
+            Self::Same => "same",
+            Self::Foreign => "foreign",
+            Self::Allowed => "allowed",
+        })
+    }
+}
+
+/// The size and content digests of a binary package file.
+///
+/// This represents the `Size`, `SHA256`, and `MD5sum` fields of entries in `Packages` files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageDigest {
+    /// The size of the `.deb` file in bytes.
+    pub size: u64,
+    /// The SHA-256 digest of the `.deb` file.
+    pub sha256: Option<ContentDigest>,
+    /// The MD5 digest of the `.deb` file.
+    pub md5: Option<ContentDigest>,
+}
+
+impl PackageDigest {
+    /// Obtain the strongest available content digest.
+    pub fn preferred_digest(&self) -> Option<&ContentDigest> {
+        self.sha256.as_ref().or(self.md5.as_ref())
+    }
+}
+
 /// A Debian binary package control file/paragraph.
 ///
 /// See <https://www.debian.org/doc/debian-policy/ch-controlfields.html#binary-package-control-files-debian-control>.
@@ -160,6 +228,73 @@ impl<'a> BinaryPackageControlFile<'a> {
         self.field_dependency_list("Pre-Depends")
     }
 
+    /// The `Breaks` field, parsed to a [DependencyList].
+    pub fn breaks(&self) -> Option<Result<DependencyList>> {
+        self.field_dependency_list("Breaks")
+    }
+
+    /// The `Conflicts` field, parsed to a [DependencyList].
+    pub fn conflicts(&self) -> Option<Result<DependencyList>> {
+        self.field_dependency_list("Conflicts")
+    }
+
+    /// The `Replaces` field, parsed to a [DependencyList].
+    pub fn replaces(&self) -> Option<Result<DependencyList>> {
+        self.field_dependency_list("Replaces")
+    }
+
+    /// The `Provides` field, parsed to a [DependencyList].
+    pub fn provides(&self) -> Option<Result<DependencyList>> {
+        self.field_dependency_list("Provides")
+    }
+
+    /// The `Multi-Arch` field, parsed to a [MultiArch].
+    pub fn multi_arch(&self) -> Option<Result<MultiArch>> {
+        self.field_str("Multi-Arch").map(MultiArch::from_str)
+    }
+
+    /// The `Tag` field, as an iterator of debtags.
+    ///
+    /// e.g. `implemented-in::c, role::program`.
+    pub fn tags(&self) -> Option<Box<(dyn Iterator<Item = &str> + '_)>> {
+        self.iter_field_comma_delimited("Tag")
+    }
+
+    /// The `Size`, `SHA256`, and `MD5sum` fields, parsed to a [PackageDigest].
+    ///
+    /// Errors if the `Size` field is missing or a field can't be parsed.
+    pub fn package_digest(&self) -> Result<PackageDigest> {
+        let size = self
+            .size()
+            .ok_or_else(|| DebianError::ControlRequiredFieldMissing("Size".to_string()))??;
+
+        let digest = |checksum: ChecksumType| {
+            self.field_str(checksum.field_name())
+                .map(|digest| ContentDigest::from_hex_digest(checksum, digest))
+                .transpose()
+        };
+
+        Ok(PackageDigest {
+            size,
+            sha256: digest(ChecksumType::Sha256)?,
+            md5: digest(ChecksumType::Md5)?,
+        })
+    }
+
+    /// Obtain a [SingleDependency] on exactly this package and version.
+    ///
+    /// e.g. `libc6 (= 2.31-13)`.
+    pub fn to_dependency(&self) -> Result<SingleDependency> {
+        Ok(SingleDependency {
+            package: self.package()?.to_string(),
+            version_constraint: Some(DependencyVersionConstraint {
+                relationship: VersionRelationship::ExactlyEqual,
+                version: self.version()?,
+            }),
+            architectures: None,
+        })
+    }
+
     /// Obtain parsed values of all fields defining dependencies.
     pub fn package_dependency_fields(&self) -> Result<PackageDependencyFields> {
         PackageDependencyFields::from_paragraph(self)
@@ -199,3 +334,79 @@ impl<'cf, 'a: 'cf> DebPackageReference<'cf> for BinaryPackageControlFile<'a> {
         Ok(self.clone())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraphReader, indoc::indoc, std::io::Cursor};
+
+    const ZSH: &str = indoc! {"
+        Package: zsh
+        Version: 5.8-6+b2
+        Architecture: amd64
+        Multi-Arch: foreign
+        Breaks: zsh-beta (<< 5.3.1-1)
+        Conflicts: zsh-beta
+        Replaces: zsh-beta (<< 5.3.1-1)
+        Provides: zsh-static
+        Tag: implemented-in::c, interface::shell, role::program,
+         works-with::file
+        Size: 810248
+        MD5sum: 4e5e01c9bba2c5a6d1df9ad1c1d0b3f0
+        SHA256: 3a0fe3ce3ac3a8c6c4f5e9b9c4bd5a0c95a1f1a0f0a4bfe1e14d7b0de7c6e2f1
+    "};
+
+    fn zsh() -> Result<BinaryPackageControlFile<'static>> {
+        Ok(ControlParagraphReader::new(Cursor::new(ZSH.as_bytes()))
+            .next()
+            .unwrap()?
+            .into())
+    }
+
+    #[test]
+    fn typed_accessors() -> Result<()> {
+        let cf = zsh()?;
+
+        assert_eq!(cf.multi_arch().unwrap()?, MultiArch::Foreign);
+        assert_eq!(cf.breaks().unwrap()?.to_string(), "zsh-beta (<< 5.3.1-1)");
+        assert_eq!(cf.conflicts().unwrap()?.to_string(), "zsh-beta");
+        assert_eq!(cf.replaces().unwrap()?.to_string(), "zsh-beta (<< 5.3.1-1)");
+        assert_eq!(cf.provides().unwrap()?.to_string(), "zsh-static");
+        assert_eq!(
+            cf.tags().unwrap().collect::<Vec<_>>(),
+            vec![
+                "implemented-in::c",
+                "interface::shell",
+                "role::program",
+                "works-with::file"
+            ]
+        );
+
+        let digest = cf.package_digest()?;
+        assert_eq!(digest.size, 810248);
+        assert_eq!(
+            digest.preferred_digest(),
+            Some(&ContentDigest::sha256_hex(
+                "3a0fe3ce3ac3a8c6c4f5e9b9c4bd5a0c95a1f1a0f0a4bfe1e14d7b0de7c6e2f1"
+            )?)
+        );
+        assert_eq!(
+            digest.md5,
+            Some(ContentDigest::md5_hex("4e5e01c9bba2c5a6d1df9ad1c1d0b3f0")?)
+        );
+
+        assert_eq!(cf.to_dependency()?.to_string(), "zsh (= 5.8-6+b2)");
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_arch_values() -> Result<()> {
+        for value in ["no", "same", "foreign", "allowed"] {
+            assert_eq!(MultiArch::from_str(value)?.to_string(), value);
+        }
+
+        assert!(MultiArch::from_str("bogus").is_err());
+
+        Ok(())
+    }
+}
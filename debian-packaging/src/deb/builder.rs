@@ -34,7 +34,7 @@ impl<'control> DebBuilder<'control> {
         Self {
             control_builder: ControlTarBuilder::new(control_file),
             compression: DebCompression::Gzip,
-            install_files: FileManifest::default(),
+            install_files: FileManifest::new_with_links(),
             mtime: None,
         }
     }
@@ -86,6 +86,9 @@ impl<'control> DebBuilder<'control> {
     ///
     /// The file content will be added to the `data.tar` archive and registered with
     /// the `control.tar` archive so its checksum is computed.
+    ///
+    /// Symlinks and hard links are stored as links in the `data.tar` archive and
+    /// aren't registered in the checksums.
     pub fn install_file(
         mut self,
         path: impl AsRef<Path> + Clone,
@@ -93,11 +96,13 @@ impl<'control> DebBuilder<'control> {
     ) -> Result<Self> {
         let entry = entry.into();
 
-        let data = entry.resolve_content()?;
-        let mut cursor = Cursor::new(&data);
-        self.control_builder = self
-            .control_builder
-            .add_data_file(path.clone(), &mut cursor)?;
+        if !entry.is_link() {
+            let data = entry.resolve_content()?;
+            let mut cursor = Cursor::new(&data);
+            self.control_builder = self
+                .control_builder
+                .add_data_file(path.clone(), &mut cursor)?;
+        }
 
         self.install_files.add_file_entry(path, entry)?;
 
@@ -170,6 +175,54 @@ fn new_tar_header(mtime: u64) -> Result<tar::Header> {
     Ok(header)
 }
 
+/// Emit a GNU long name entry holding a value that doesn't fit in a header field.
+///
+/// `entry_type` is `L` for paths and `K` for link names.
+fn append_gnu_long_name(
+    builder: &mut tar::Builder<impl Write>,
+    value_bytes: &[u8],
+    entry_type: u8,
+) -> Result<()> {
+    let mut header2 = tar::Header::new_gnu();
+    let name = b"././@LongLink";
+    header2.as_gnu_mut().unwrap().name[..name.len()].clone_from_slice(&name[..]);
+    header2.set_mode(0o644);
+    header2.set_uid(0);
+    header2.set_gid(0);
+    header2.set_mtime(0);
+    header2.set_size(value_bytes.len() as u64 + 1);
+    header2.set_entry_type(tar::EntryType::new(entry_type));
+    header2.set_cksum();
+    let mut data = value_bytes.chain(std::io::repeat(0).take(1));
+    builder.append(&header2, &mut data)?;
+
+    Ok(())
+}
+
+fn set_header_link_name(
+    builder: &mut tar::Builder<impl Write>,
+    header: &mut tar::Header,
+    value: &str,
+) -> Result<()> {
+    // Like paths, we set the field directly so hard link targets can retain
+    // their `./` prefix.
+    assert!(header.as_ustar().is_none());
+
+    let value_bytes = value.as_bytes();
+    let link_buffer = &mut header.as_old_mut().linkname;
+
+    if value_bytes.len() <= link_buffer.len() {
+        link_buffer[0..value_bytes.len()].copy_from_slice(value_bytes);
+    } else {
+        append_gnu_long_name(builder, value_bytes, b'K')?;
+
+        let truncated_bytes = &value_bytes[0..link_buffer.len()];
+        link_buffer[0..truncated_bytes.len()].copy_from_slice(truncated_bytes);
+    }
+
+    Ok(())
+}
+
 fn set_header_path(
     builder: &mut tar::Builder<impl Write>,
     header: &mut tar::Header,
@@ -200,18 +253,7 @@ fn set_header_path(
     } else {
         // Else we emit a special entry to extend the filename. Who knew tar
         // files were this jank.
-        let mut header2 = tar::Header::new_gnu();
-        let name = b"././@LongLink";
-        header2.as_gnu_mut().unwrap().name[..name.len()].clone_from_slice(&name[..]);
-        header2.set_mode(0o644);
-        header2.set_uid(0);
-        header2.set_gid(0);
-        header2.set_mtime(0);
-        header2.set_size(value_bytes.len() as u64 + 1);
-        header2.set_entry_type(tar::EntryType::new(b'L'));
-        header2.set_cksum();
-        let mut data = value_bytes.chain(std::io::repeat(0).take(1));
-        builder.append(&header2, &mut data)?;
+        append_gnu_long_name(builder, value_bytes, b'L')?;
 
         let truncated_bytes = &value_bytes[0..name_buffer.len()];
         name_buffer[0..truncated_bytes.len()].copy_from_slice(truncated_bytes);
@@ -350,19 +392,49 @@ pub fn write_deb_tar<W: Write>(writer: W, files: &FileManifest, mtime: u64) -> R
     }
 
     // FileManifest is backed by a BTreeMap, so iteration is deterministic.
+    // Hard links are emitted last so their targets precede them in the archive.
     for (rel_path, content) in files.iter_entries() {
-        let data = content.resolve_content()?;
+        if content.hardlink_target().is_some() {
+            continue;
+        }
 
         let mut header = new_tar_header(mtime)?;
         set_header_path(&mut builder, &mut header, rel_path, false)?;
-        header.set_mode(if content.is_executable() {
-            0o755
+
+        if let Some(target) = content.link_target() {
+            header.set_entry_type(tar::EntryType::Symlink);
+            set_header_link_name(&mut builder, &mut header, &format!("{}", target.display()))?;
+            header.set_mode(0o777);
+            header.set_size(0);
+            header.set_cksum();
+            builder.append(&header, &*vec![])?;
         } else {
-            0o644
-        });
-        header.set_size(data.len() as _);
-        header.set_cksum();
-        builder.append(&header, &*data)?;
+            let data = content.resolve_content()?;
+
+            header.set_mode(content.mode());
+            header.set_size(data.len() as _);
+            header.set_cksum();
+            builder.append(&header, &*data)?;
+        }
+    }
+
+    for (rel_path, content) in files.iter_entries() {
+        if let Some(target) = content.hardlink_target() {
+            let target_entry = files.hardlink_target_entry(target)?;
+
+            let mut header = new_tar_header(mtime)?;
+            set_header_path(&mut builder, &mut header, rel_path, false)?;
+            header.set_entry_type(tar::EntryType::Link);
+            set_header_link_name(
+                &mut builder,
+                &mut header,
+                &format!("./{}", target.display()),
+            )?;
+            header.set_mode(target_entry.mode());
+            header.set_size(0);
+            header.set_cksum();
+            builder.append(&header, &*vec![])?;
+        }
     }
 
     builder.finish()?;
@@ -436,6 +508,55 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_data_tar_links() -> Result<()> {
+        let mut file = FileEntry::new_from_data(vec![42], false);
+        file.set_mode(0o4750);
+
+        let mut manifest = FileManifest::new_with_links();
+        manifest.add_file_entry("usr/lib/libfoo.so.1.0", file)?;
+        manifest.add_symlink("usr/lib/libfoo.so.1", "libfoo.so.1.0")?;
+        manifest.add_hardlink("usr/lib/libfoo-copy.so", "usr/lib/libfoo.so.1.0")?;
+
+        let mut buffer = vec![];
+        write_deb_tar(&mut buffer, &manifest, 2)?;
+
+        let mut archive = tar::Archive::new(std::io::Cursor::new(buffer));
+
+        for (i, entry) in archive.entries()?.enumerate() {
+            let entry = entry?;
+            let header = entry.header();
+
+            match i {
+                0 | 1 | 2 => {}
+                3 => {
+                    assert_eq!(entry.path()?, Path::new("./usr/lib/libfoo.so.1"));
+                    assert_eq!(header.entry_type(), tar::EntryType::Symlink);
+                    assert_eq!(
+                        entry.link_name()?.as_deref(),
+                        Some(Path::new("libfoo.so.1.0"))
+                    );
+                }
+                4 => {
+                    assert_eq!(entry.path()?, Path::new("./usr/lib/libfoo.so.1.0"));
+                    assert_eq!(header.entry_type(), tar::EntryType::Regular);
+                    assert_eq!(header.mode()?, 0o4750);
+                }
+                5 => {
+                    assert_eq!(entry.path()?, Path::new("./usr/lib/libfoo-copy.so"));
+                    assert_eq!(header.entry_type(), tar::EntryType::Link);
+                    assert_eq!(
+                        entry.link_name()?.as_deref(),
+                        Some(Path::new("./usr/lib/libfoo.so.1.0"))
+                    );
+                }
+                _ => panic!("unexpected archive entry"),
+            }
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_write_data_tar_long_path() -> Result<()> {
         let long_path = PathBuf::from(format!("f{}.txt", "u".repeat(200)));
@@ -11,11 +11,15 @@ use {
         error::{DebianError, Result},
     },
     std::{
+        collections::BTreeMap,
         io::{Cursor, Read},
         ops::{Deref, DerefMut},
     },
 };
 
+/// Names of maintainer scripts in `control.tar` archives.
+pub const MAINTAINER_SCRIPTS: &[&str] = &["preinst", "postinst", "prerm", "postrm", "config"];
+
 fn reader_from_filename(extension: &str, data: std::io::Cursor<Vec<u8>>) -> Result<Box<dyn Read>> {
     match extension {
         "" => Ok(Box::new(data)),
@@ -310,3 +314,222 @@ pub fn resolve_control_file(reader: impl Read) -> Result<BinaryPackageControlFil
 
     Err(DebianError::ControlFileNotFound)
 }
+
+/// A high-level reader of .deb files.
+///
+/// Unlike [BinaryPackageReader], which exposes the raw members of the ar archive,
+/// this type reads the `control.tar` archive eagerly, making the package metadata
+/// available for inspection, and keeps the `data.tar` archive for streaming its
+/// entries.
+pub struct DebReader {
+    format_version: String,
+    control: BinaryPackageControlFile<'static>,
+    control_files: BTreeMap<String, Vec<u8>>,
+    data: DataTarReader,
+}
+
+impl DebReader {
+    /// Construct a new instance by reading a .deb file.
+    ///
+    /// The `data.tar` archive is held in memory but not decompressed.
+    pub fn new(reader: impl Read) -> Result<Self> {
+        let mut reader = BinaryPackageReader::new(reader)?;
+
+        let mut format_version = None;
+        let mut control_files = BTreeMap::new();
+        let mut data = None;
+
+        while let Some(entry) = reader.next_entry() {
+            match entry? {
+                BinaryPackageEntry::DebianBinary(cursor) => {
+                    format_version =
+                        Some(String::from_utf8_lossy(cursor.get_ref()).trim().to_string());
+                }
+                BinaryPackageEntry::Control(mut control) => {
+                    for entry in control.entries()? {
+                        let mut entry = entry?;
+
+                        if entry.header().entry_type() != tar::EntryType::Regular {
+                            continue;
+                        }
+
+                        let path = String::from_utf8_lossy(&entry.path_bytes())
+                            .trim_start_matches("./")
+                            .to_string();
+
+                        let mut content = vec![];
+                        entry.read_to_end(&mut content)?;
+
+                        control_files.insert(path, content);
+                    }
+                }
+                BinaryPackageEntry::Data(reader) => {
+                    data = Some(reader);
+                }
+            }
+        }
+
+        let control = control_files
+            .get("control")
+            .ok_or(DebianError::ControlFileNotFound)?;
+        let paragraph = ControlParagraphReader::new(Cursor::new(control))
+            .next()
+            .ok_or(DebianError::ControlFileNoParagraph)??;
+
+        Ok(Self {
+            format_version: format_version.ok_or(DebianError::DebMissingEntry("debian-binary"))?,
+            control: BinaryPackageControlFile::from(paragraph),
+            control_files,
+            data: data.ok_or(DebianError::DebMissingEntry("data.tar"))?,
+        })
+    }
+
+    /// The version of the binary package format, from the `debian-binary` file.
+    ///
+    /// e.g. `2.0`.
+    pub fn format_version(&self) -> &str {
+        &self.format_version
+    }
+
+    /// The parsed `control` file.
+    pub fn control_file(&self) -> &BinaryPackageControlFile<'static> {
+        &self.control
+    }
+
+    /// Obtain the content of a file in the `control.tar` archive.
+    ///
+    /// Paths don't have a leading `./`. e.g. `md5sums`.
+    pub fn control_tar_file(&self, path: &str) -> Option<&[u8]> {
+        self.control_files.get(path).map(|x| x.as_slice())
+    }
+
+    /// Obtain an iterator of paths and content of files in the `control.tar` archive.
+    pub fn control_tar_files(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.control_files
+            .iter()
+            .map(|(path, data)| (path.as_str(), data.as_slice()))
+    }
+
+    /// Obtain an iterator of paths of configuration files, from the `conffiles` file.
+    ///
+    /// Flags preceding paths, like `remove-on-upgrade`, are ignored.
+    pub fn conffiles(&self) -> impl Iterator<Item = &str> {
+        self.control_files
+            .get("conffiles")
+            .and_then(|data| std::str::from_utf8(data).ok())
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.split_ascii_whitespace().last())
+    }
+
+    /// Obtain an iterator of names and content of maintainer scripts present in the package.
+    pub fn maintainer_scripts(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        MAINTAINER_SCRIPTS.iter().filter_map(|name| {
+            self.control_files
+                .get(*name)
+                .map(|data| (*name, data.as_slice()))
+        })
+    }
+
+    /// Obtain the reader for the `data.tar` archive.
+    pub fn into_data_tar(self) -> DataTarReader {
+        self.data
+    }
+
+    /// Obtain a stream of entries in the `data.tar` archive.
+    pub fn into_data_entries(
+        self,
+    ) -> Result<async_tar::Entries<Box<dyn futures::io::AsyncRead + Unpin>>> {
+        Ok(self.data.into_inner().entries()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            control::{ControlFile, ControlParagraph},
+            deb::{builder::DebBuilder, DebCompression},
+        },
+        futures::StreamExt,
+        std::time::SystemTime,
+        tugger_file_manifest::FileEntry,
+    };
+
+    fn deb(compression: DebCompression) -> Result<Vec<u8>> {
+        let mut control_para = ControlParagraph::default();
+        control_para.set_field_from_string("Package".into(), "mypackage".into());
+        control_para.set_field_from_string("Architecture".into(), "amd64".into());
+
+        let mut control = ControlFile::default();
+        control.add_paragraph(control_para);
+
+        let builder = DebBuilder::new(control)
+            .set_compression(compression)
+            .set_mtime(Some(SystemTime::UNIX_EPOCH))
+            .extra_control_tar_file(
+                "conffiles",
+                FileEntry::new_from_data(
+                    b"/etc/myapp.conf\nremove-on-upgrade /etc/old.conf\n".to_vec(),
+                    false,
+                ),
+            )?
+            .extra_control_tar_file(
+                "postinst",
+                FileEntry::new_from_data(b"#!/bin/sh\n".to_vec(), true),
+            )?
+            .install_file("usr/bin/myapp", FileEntry::new_from_data(vec![42], true))?
+            .install_file(
+                "etc/myapp.conf",
+                FileEntry::new_from_data(b"key=value\n".to_vec(), false),
+            )?;
+
+        let mut buffer = vec![];
+        builder.write(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    #[test]
+    fn read_deb() -> Result<()> {
+        for compression in [
+            DebCompression::Gzip,
+            DebCompression::Xz(6),
+            DebCompression::Zstandard(3),
+        ] {
+            let reader = DebReader::new(Cursor::new(deb(compression)?))?;
+
+            assert_eq!(reader.format_version(), "2.0");
+            assert_eq!(reader.control_file().package()?, "mypackage");
+            assert_eq!(
+                reader.conffiles().collect::<Vec<_>>(),
+                vec!["/etc/myapp.conf", "/etc/old.conf"]
+            );
+            assert_eq!(
+                reader.maintainer_scripts().collect::<Vec<_>>(),
+                vec![("postinst", b"#!/bin/sh\n".as_ref())]
+            );
+            assert!(reader.control_tar_file("md5sums").is_some());
+
+            let paths = futures::executor::block_on(async {
+                let mut entries = reader.into_data_entries()?;
+                let mut paths = vec![];
+
+                while let Some(entry) = entries.next().await {
+                    let entry = entry?;
+
+                    if entry.header().entry_type() == async_tar::EntryType::Regular {
+                        paths.push(entry.path()?.to_string_lossy().to_string());
+                    }
+                }
+
+                Result::Ok(paths)
+            })?;
+
+            assert_eq!(paths, vec!["./etc/myapp.conf", "./usr/bin/myapp"]);
+        }
+
+        Ok(())
+    }
+}
@@ -62,6 +62,9 @@ pub enum DebianError {
     #[error("unknown entry in binary package archive: {0}")]
     DebUnknownBinaryPackageEntry(String),
 
+    #[error("missing {0} entry in binary package archive")]
+    DebMissingEntry(&'static str),
+
     #[error("unknown compression in deb archive file: {0}")]
     DebUnknownCompression(String),
 
@@ -83,6 +86,9 @@ pub enum DebianError {
     #[error("could not find Sources indices entry in Release file")]
     RepositoryReadSourcesIndicesEntryNotFound,
 
+    #[error("could not find Translation indices entry in Release file")]
+    RepositoryReadTranslationIndicesEntryNotFound,
+
     #[error("could not determine content digest of binary package")]
     RepositoryReadCouldNotDeterminePackageDigest,
 
@@ -131,6 +137,24 @@ pub enum DebianError {
     #[error("indices files not found in Release file")]
     ReleaseNoIndicesFiles,
 
+    #[error("malformed entry in pdiff Index file: {0}")]
+    PdiffIndexBadEntry(String),
+
+    #[error("pdiff ed script parse error: {0}")]
+    PdiffEdScriptParse(String),
+
+    #[error("unable to apply pdiff ed script: {0}")]
+    PdiffEdScriptApply(String),
+
+    #[error("pdiff patch {0} does not match its expected content digest")]
+    PdiffPatchDigestMismatch(String),
+
+    #[error("patched index file {0} does not match its expected content digest")]
+    PdiffResultDigestMismatch(String),
+
+    #[error("unknown Multi-Arch value: {0}")]
+    MultiArchUnknown(String),
+
     #[error("failed to parse dependency expression: {0}")]
     DependencyParse(String),
 
@@ -146,6 +170,15 @@ pub enum DebianError {
     #[error("debian_revision component has illegal character: {0}")]
     DebianRevisionIllegalChar(String),
 
+    #[error("package not found for sysroot: {0}")]
+    SysrootPackageNotFound(String),
+
+    #[error("package {0} has unsatisfiable dependency: {1}")]
+    SysrootUnsatisfiedDependency(String, String),
+
+    #[error("conflicting versions of package {0} required: {1} and {2}")]
+    SysrootConflictingVersions(String, String, String),
+
     #[error("unknown S3 region: {0}")]
     S3BadRegion(String),
 
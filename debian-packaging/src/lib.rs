@@ -48,7 +48,9 @@ Read operations like parsing control files or version strings should be able to
 
 A `.deb` file defines a Debian package. Readers and writers of `.deb` files exist in the
 [deb] module. To read the contents of a `.deb` defining a binary package, use
-[deb::reader::BinaryPackageReader]. To create new `.deb` files, use [deb::builder::DebBuilder].
+[deb::reader::BinaryPackageReader]. [deb::reader::DebReader] provides a higher-level
+interface exposing the control file, maintainer scripts, and conffiles and streaming
+the installed files. To create new `.deb` files, use [deb::builder::DebBuilder].
 
 A common primitive within Debian packaging is *control files*. These consist of *paragraphs*
 of key-value metadata. Low-level control file primitives are defined in the [control] module.
@@ -85,7 +87,8 @@ provides an interface for reading the root directory of a repository and
 to a repository. Repository interaction involves many support primitives.
 [repository::release::ReleaseFile] represents an `[In]Release` file. Support for verifying
 PGP signatures is provided. [repository::contents::ContentsFile] represents a `Contents`
-file.
+file. [repository::translation::TranslationFile] represents a `Translation` file, which
+holds the extended package descriptions omitted from modern `Packages` files.
 
 Concrete implementations of repository interaction exist. [repository::http::HttpRepositoryClient]
 enables reading from an HTTP-hosted repository (e.g. `http://archive.canonical.com/ubuntu`).
@@ -105,6 +108,10 @@ popular Linux distributions. [signing_key::signing_secret_key_params_builder()]
 [signing_key::create_self_signed_key()] enable easily creating signing keys for Debian
 repositories.
 
+The [sysroot] module assembles sysroots for cross-compilation from binary packages in
+a repository. [sysroot::create_sysroot()] installs a dependency-closed set of packages
+into a directory.
+
 Various other modules provide miscellaneous functionality. [io] defines I/O helpers, including
 stream adapters for validating content digests on read and computing content digests on write.
 
@@ -129,3 +136,4 @@ pub mod package_version;
 pub mod repository;
 pub mod signing_key;
 pub mod source_package_control;
+pub mod sysroot;
@@ -20,7 +20,7 @@ use {
         error::{DebianError, Result},
         io::{read_compressed, ContentDigest, DataResolver, MultiContentDigest, MultiDigester},
         repository::{
-            release::{ChecksumType, ReleaseFile, DATE_FORMAT},
+            release::{ChecksumType, ReleaseFile, ReleaseFileBuilder, DATE_FORMAT},
             Compression, PublishEvent, RepositoryPathVerificationState, RepositoryWriter,
         },
     },
@@ -30,7 +30,7 @@ use {
     pgp_cleartext::cleartext_sign,
     std::{
         borrow::Cow,
-        collections::{BTreeMap, BTreeSet, HashMap},
+        collections::{BTreeMap, BTreeSet},
         pin::Pin,
     },
 };
@@ -766,26 +766,33 @@ impl<'cf> RepositoryBuilder<'cf> {
         digester.update(&buf);
         let digests = digester.finish();
 
-        if self.acquire_by_hash == Some(true) {
-            Ok(Box::new(self.checksums.iter().map(move |checksum| {
-                ExpandedIndexFile {
-                    canonical_path: ifr.canonical_path(),
-                    write_path: ifr.by_hash_path(digests.digest_from_checksum(*checksum)),
+        // The canonical path is always written, for clients not supporting `by-hash`.
+        let by_hash_paths = if self.acquire_by_hash == Some(true) {
+            self.checksums
+                .iter()
+                .map(|checksum| ifr.by_hash_path(digests.digest_from_checksum(*checksum)))
+                .collect::<Vec<_>>()
+        } else {
+            vec![]
+        };
+
+        let canonical_path = ifr.canonical_path();
+
+        Ok(Box::new(
+            std::iter::once(canonical_path.clone())
+                .chain(by_hash_paths)
+                .map(move |write_path| ExpandedIndexFile {
+                    canonical_path: canonical_path.clone(),
+                    write_path,
                     digests: digests.clone(),
                     data: buf.clone(),
-                }
-            })))
-        } else {
-            Ok(Box::new(std::iter::once(ExpandedIndexFile {
-                canonical_path: ifr.canonical_path(),
-                write_path: ifr.canonical_path(),
-                digests,
-                data: buf,
-            })))
-        }
+                }),
+        ))
     }
 
     /// Derive fields for `Release` files that aren't related to indices lists.
+    ///
+    /// `Acquire-By-Hash` is emitted by [ReleaseFileBuilder].
     fn static_release_fields(&self) -> impl Iterator<Item = ControlField<'_>> {
         let mut fields: BTreeMap<Cow<'_, str>, Cow<'_, str>> = BTreeMap::new();
 
@@ -839,13 +846,6 @@ impl<'cf> RepositoryBuilder<'cf> {
         if let Some(version) = &self.version {
             fields.insert("Version".into(), version.into());
         }
-        if let Some(acquire_by_hash) = self.acquire_by_hash {
-            fields.insert(
-                "Acquire-By-Hash".into(),
-                if acquire_by_hash { "yes" } else { "no" }.into(),
-            );
-        }
-
         fields.into_iter().map(|(k, v)| ControlField::new(k, v))
     }
 
@@ -857,55 +857,31 @@ impl<'cf> RepositoryBuilder<'cf> {
         &self,
         indices: impl Iterator<Item = (String, (u64, MultiContentDigest))>,
     ) -> Result<ReleaseFile<'_>> {
-        let mut para = ControlParagraph::default();
+        Ok(self.release_file_builder(indices).to_release_file())
+    }
+
+    /// Derive a [ReleaseFileBuilder] for the `Release` file.
+    ///
+    /// This takes an iterable describing indices files, like [Self::create_release_file()].
+    pub fn release_file_builder(
+        &self,
+        indices: impl Iterator<Item = (String, (u64, MultiContentDigest))>,
+    ) -> ReleaseFileBuilder {
+        let mut builder = ReleaseFileBuilder::new(self.checksums.iter().copied());
 
         for field in self.static_release_fields() {
-            para.set_field(field);
+            builder.set_field(field.name(), field.value_str());
         }
 
-        let mut digests_by_field = HashMap::new();
-
-        for (path, (size, digests)) in indices {
-            for digest in digests.iter_digests() {
-                digests_by_field
-                    .entry(digest.release_field_name())
-                    .or_insert_with(BTreeMap::new)
-                    .insert(path.clone(), (size, digest.digest_hex()));
-            }
+        if let Some(acquire_by_hash) = self.acquire_by_hash {
+            builder.set_acquire_by_hash(acquire_by_hash);
         }
 
-        for checksum in self.checksums.iter() {
-            let entries = digests_by_field
-                .get(checksum.field_name())
-                .expect("digest field should be populated");
-
-            let longest_path = entries.keys().map(|x| x.len()).max().unwrap_or_default();
-            let longest_size = entries
-                .values()
-                .map(|(size, _)| format!("{}", size).len())
-                .max()
-                .unwrap_or_default();
-
-            para.set_field(ControlField::new(
-                checksum.field_name().into(),
-                std::iter::once("".to_string())
-                    .chain(entries.iter().map(|(path, (size, digest))| {
-                        format!(
-                            " {:<path_width$} {:>size_width$} {}",
-                            path,
-                            size,
-                            digest,
-                            path_width = longest_path,
-                            size_width = longest_size
-                        )
-                    }))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-                    .into(),
-            ));
+        for (path, (size, digests)) in indices {
+            builder.add_index_file(path, size, digests);
         }
 
-        Ok(para.into())
+        builder
     }
 
     /// Publish index files.
@@ -1118,7 +1094,7 @@ mod test {
         },
         async_trait::async_trait,
         futures::AsyncReadExt,
-        std::borrow::Cow,
+        std::{borrow::Cow, collections::HashMap},
     };
 
     const BULLSEYE_URL: &str = "http://snapshot.debian.org/archive/debian/20211120T085721Z";
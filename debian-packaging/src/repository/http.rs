@@ -17,21 +17,96 @@ use {
     async_trait::async_trait,
     futures::{stream::TryStreamExt, AsyncRead},
     reqwest::{Client, ClientBuilder, IntoUrl, StatusCode, Url},
-    std::pin::Pin,
+    std::{pin::Pin, time::Duration},
 };
 
 /// Default HTTP user agent string.
 pub const USER_AGENT: &str =
     "debian-packaging Rust crate (https://crates.io/crates/debian-packaging)";
 
+/// Describes how failed HTTP requests are retried.
+///
+/// Requests failing to connect or receiving a 5xx, 408 or 429 response are
+/// retried with exponential backoff.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// How many times to retry a request.
+    pub retries: u32,
+
+    /// Delay before the first retry. Doubles with each retry.
+    pub backoff: Duration,
+
+    /// Upper bound for the delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Obtain the delay before a retry.
+    ///
+    /// `attempt` is the 0-based index of the retry.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether a response status indicates a transient failure worth retrying.
+///
+/// `tugger-common` has a copy of this and [RetryPolicy::delay()] for its
+/// blocking client. Tests of both use the same cases.
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
 async fn fetch_url(
     client: &Client,
     root_url: &Url,
     path: &str,
+    retry: &RetryPolicy,
 ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
     let request_url = root_url.join(path)?;
 
-    let res = client.get(request_url.clone()).send().await.map_err(|e| {
+    let mut attempt = 0;
+
+    let res = loop {
+        let res = client.get(request_url.clone()).send().await;
+
+        let retryable = match &res {
+            Ok(res) => is_retryable_status(res.status()),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        };
+
+        if !retryable || attempt >= retry.retries {
+            break res;
+        }
+
+        async_std::task::sleep(retry.delay(attempt)).await;
+        attempt += 1;
+    };
+
+    let res = res.map_err(|e| {
         DebianError::RepositoryIoPath(
             path.to_string(),
             std::io::Error::new(
@@ -83,10 +158,17 @@ pub struct HttpRepositoryClient {
     ///
     /// Contains both distributions and the files pool.
     root_url: Url,
+
+    /// How failed requests are retried.
+    retry: RetryPolicy,
 }
 
 impl HttpRepositoryClient {
     /// Construct an instance bound to the specified URL.
+    ///
+    /// The HTTP client honors the `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY`
+    /// environment variables. Use [Self::new_client()] to use a custom client,
+    /// e.g. one trusting additional root certificates or sending credentials.
     pub fn new(url: impl IntoUrl) -> Result<Self> {
         let builder = ClientBuilder::new().user_agent(USER_AGENT);
 
@@ -109,14 +191,25 @@ impl HttpRepositoryClient {
             root_url.set_path(&format!("{}/", root_url.path()));
         }
 
-        Ok(Self { client, root_url })
+        Ok(Self {
+            client,
+            root_url,
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// Set how failed requests are retried.
+    ///
+    /// Release readers obtained from this instance inherit the policy.
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
     }
 }
 
 #[async_trait]
 impl DataResolver for HttpRepositoryClient {
     async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
-        fetch_url(&self.client, &self.root_url, path).await
+        fetch_url(&self.client, &self.root_url, path, &self.retry).await
     }
 }
 
@@ -149,6 +242,7 @@ impl RepositoryRootReader for HttpRepositoryClient {
         Ok(Box::new(HttpReleaseClient {
             client: self.client.clone(),
             root_url,
+            retry: self.retry,
             relative_path: distribution_path,
             release,
             fetch_compression,
@@ -164,6 +258,7 @@ fn join_path(a: &str, b: &str) -> String {
 pub struct HttpReleaseClient {
     client: Client,
     root_url: Url,
+    retry: RetryPolicy,
     relative_path: String,
     release: ReleaseFile<'static>,
     fetch_compression: Compression,
@@ -172,7 +267,7 @@ pub struct HttpReleaseClient {
 #[async_trait]
 impl DataResolver for HttpReleaseClient {
     async fn get_path(&self, path: &str) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
-        fetch_url(&self.client, &self.root_url, path).await
+        fetch_url(&self.client, &self.root_url, path, &self.retry).await
     }
 }
 
@@ -211,6 +306,32 @@ mod test {
 
     const BULLSEYE_URL: &str = "http://snapshot.debian.org/archive/debian/20211120T085721Z";
 
+    // Keep in sync with the retry tests of tugger-common's HTTP client.
+    #[test]
+    fn retry_delay() {
+        let policy = RetryPolicy {
+            retries: 5,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(3), Duration::from_millis(800));
+        assert_eq!(policy.delay(4), Duration::from_secs(1));
+        assert_eq!(policy.delay(64), Duration::from_secs(1));
+        assert_eq!(RetryPolicy::none().retries, 0);
+    }
+
+    #[test]
+    fn retryable_status() {
+        for status in [500, 502, 503, 408, 429] {
+            assert!(is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 301, 403, 404] {
+            assert!(!is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+
     #[tokio::test]
     async fn bullseye_release() -> Result<()> {
         let root = HttpRepositoryClient::new(BULLSEYE_URL)?;
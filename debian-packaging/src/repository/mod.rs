@@ -58,8 +58,9 @@ that will send its content to a black hole. It can be used for testing writing w
 actually performing writes. [proxy_writer::ProxyWriter] proxies an inner writer and
 can override behavior on certain I/O operations.
 
-Modules like [contents] and [release] define primitives encountered in
-repositories, such as `[In]Release` files.
+Modules like [contents], [release], and [translation] define primitives
+encountered in repositories, such as `[In]Release` files. [pdiff] implements
+incremental updates of index files.
 
 The [builder] module contains functionality for creating/publishing
 repositories.
@@ -75,13 +76,15 @@ use {
         debian_source_control::{DebianSourceControlFile, DebianSourceControlFileFetch},
         debian_source_package_list::DebianSourcePackageList,
         error::{DebianError, Result},
-        io::{drain_reader, Compression, ContentDigest, DataResolver},
+        io::{drain_reader, Compression, ContentDigest, DataResolver, MultiDigester},
         repository::{
             contents::{ContentsFile, ContentsFileAsyncReader},
+            pdiff::{content_matches, EdScript, PdiffIndex},
             release::{
                 ChecksumType, ClassifiedReleaseFileEntry, ContentsFileEntry, PackagesFileEntry,
-                ReleaseFile, SourcesFileEntry,
+                ReleaseFile, SourcesFileEntry, TranslationFileEntry,
             },
+            translation::{TranslationFile, TranslationFileAsyncReader},
         },
     },
     async_trait::async_trait,
@@ -95,11 +98,13 @@ pub mod copier;
 pub mod filesystem;
 #[cfg(feature = "http")]
 pub mod http;
+pub mod pdiff;
 pub mod proxy_writer;
 pub mod release;
 #[cfg(feature = "s3")]
 pub mod s3;
 pub mod sink_writer;
+pub mod translation;
 
 /// Describes how to fetch a binary package from a repository.
 #[derive(Clone, Debug)]
@@ -473,6 +478,115 @@ pub trait ReleaseReader: DataResolver + Sync {
         self.resolve_packages_from_entry(&entry).await
     }
 
+    /// Fetch the decompressed content of a `Packages` file, updating cached content if possible.
+    ///
+    /// `cached` is previously fetched content of the `Packages` file, e.g. as returned by
+    /// an earlier call. If the repository publishes a `Packages.diff/Index` for the file
+    /// and it describes the cached version, only the patches transforming it into the
+    /// current version are fetched. Otherwise the full `Packages` file is fetched.
+    ///
+    /// The returned content can be parsed with [ControlParagraphReader].
+    ///
+    /// [ControlParagraphReader]: crate::control::ControlParagraphReader
+    async fn fetch_packages_data(
+        &self,
+        component: &str,
+        arch: &str,
+        is_installer: bool,
+        cached: Option<&[u8]>,
+    ) -> Result<Vec<u8>> {
+        let release = self.release_file();
+        let checksum = self.retrieve_checksum()?;
+        let entry = self.packages_entry(component, arch, is_installer)?;
+
+        let base_path = entry
+            .path
+            .strip_suffix(entry.compression.extension())
+            .unwrap_or(entry.path);
+        let diff_path = format!("{}.diff/Index", base_path);
+
+        let index_entry = if let Some(mut entries) = release.iter_index_files(checksum) {
+            entries.find_map(|entry| match entry {
+                Ok(entry) if entry.path == diff_path => Some(entry),
+                _ => None,
+            })
+        } else {
+            None
+        };
+
+        if let (Some(cached), Some(index_entry)) = (cached, index_entry) {
+            let path = if release.acquire_by_hash().unwrap_or_default() {
+                index_entry.by_hash_path()
+            } else {
+                index_entry.path.to_string()
+            };
+
+            let mut reader = self
+                .get_path_with_digest_verification(
+                    &path,
+                    index_entry.size,
+                    index_entry.digest.clone(),
+                )
+                .await?;
+
+            let mut data = vec![];
+            reader.read_to_end(&mut data).await?;
+
+            let index = PdiffIndex::from_reader(std::io::Cursor::new(data), checksum)?;
+
+            let mut digester = MultiDigester::default();
+            digester.update(cached);
+            let digest = digester.finish().digest_from_checksum(checksum).clone();
+
+            if let Some(patches) = index.patches_for_digest(&digest) {
+                let mut content = cached.to_vec();
+
+                for patch in patches {
+                    let path = format!("{}.diff/{}.gz", base_path, patch.name);
+
+                    let mut reader = self.get_path_decoded(&path, Compression::Gzip).await?;
+
+                    let mut script = vec![];
+                    reader.read_to_end(&mut script).await?;
+
+                    if !content_matches(&script, patch.size, &patch.digest) {
+                        return Err(DebianError::PdiffPatchDigestMismatch(path));
+                    }
+
+                    content = EdScript::parse(&script)?.apply(&content)?;
+                }
+
+                if !content_matches(&content, index.current_size(), index.current_digest()) {
+                    return Err(DebianError::PdiffResultDigestMismatch(
+                        base_path.to_string(),
+                    ));
+                }
+
+                return Ok(content);
+            }
+        }
+
+        let path = if release.acquire_by_hash().unwrap_or_default() {
+            entry.by_hash_path()
+        } else {
+            entry.path.to_string()
+        };
+
+        let mut reader = self
+            .get_path_decoded_with_digest_verification(
+                &path,
+                entry.compression,
+                entry.size,
+                entry.digest.clone(),
+            )
+            .await?;
+
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+
+        Ok(data)
+    }
+
     /// Retrieve fetch instructions for binary packages.
     ///
     /// The caller can specify a filter function to choose which packages to retrieve.
@@ -720,6 +834,130 @@ pub trait ReleaseReader: DataResolver + Sync {
 
         Ok(contents)
     }
+
+    /// Resolve indices for `Translation` files.
+    ///
+    /// Only entries for the checksum as defined by [Self::retrieve_checksum()] are returned.
+    ///
+    /// Multiple entries for the same logical file with varying compression formats may be
+    /// returned.
+    fn translations_indices_entries(&self) -> Result<Vec<TranslationFileEntry<'_>>> {
+        Ok(
+            if let Some(entries) = self
+                .release_file()
+                .iter_translations_indices(self.retrieve_checksum()?)
+            {
+                entries.collect::<Result<Vec<_>>>()?
+            } else {
+                vec![]
+            },
+        )
+    }
+
+    /// Resolve the [TranslationFileEntry] for a given component and locale.
+    ///
+    /// This returns the entry variant that is preferred given digest and compression
+    /// settings. If no entry is found, [DebianError::RepositoryReadTranslationIndicesEntryNotFound]
+    /// is returned.
+    fn translation_entry(&self, component: &str, locale: &str) -> Result<TranslationFileEntry> {
+        let entries = self
+            .translations_indices_entries()?
+            .into_iter()
+            .filter(|entry| entry.component == component && entry.locale == locale)
+            .collect::<Vec<_>>();
+
+        if let Some(entry) = entries
+            .iter()
+            .find(|entry| entry.compression == self.preferred_compression())
+        {
+            Ok(entry.clone())
+        } else {
+            for compression in Compression::default_preferred_order() {
+                if let Some(entry) = entries
+                    .iter()
+                    .find(|entry| entry.compression == compression)
+                {
+                    return Ok(entry.clone());
+                }
+            }
+
+            Err(DebianError::RepositoryReadTranslationIndicesEntryNotFound)
+        }
+    }
+
+    /// Fetch a `Translation` file and parse the translated descriptions inside.
+    ///
+    /// The file to fetch is specified from a [TranslationFileEntry] describing it.
+    async fn resolve_translation_from_entry<'entry, 'slf: 'entry>(
+        &'slf self,
+        entry: &'entry TranslationFileEntry<'slf>,
+    ) -> Result<TranslationFile> {
+        let release = self.release_file();
+
+        let path = if release.acquire_by_hash().unwrap_or_default() {
+            entry.by_hash_path()
+        } else {
+            entry.path.to_string()
+        };
+
+        let mut reader = TranslationFileAsyncReader::new(
+            futures::io::BufReader::new(
+                self.get_path_decoded_with_digest_verification(
+                    &path,
+                    entry.compression,
+                    entry.size,
+                    entry.digest.clone(),
+                )
+                .await?,
+            ),
+            &entry.locale,
+        );
+        reader.read_all().await?;
+
+        let (translation, reader) = reader.consume();
+
+        drain_reader(reader)
+            .await
+            .map_err(|e| DebianError::RepositoryIoPath(path, e))?;
+
+        Ok(translation)
+    }
+
+    /// Fetch and parse the `Translation` file for the given component and locale.
+    async fn resolve_translation(&self, component: &str, locale: &str) -> Result<TranslationFile> {
+        let entry = self.translation_entry(component, locale)?;
+
+        self.resolve_translation_from_entry(&entry).await
+    }
+
+    /// Resolve packages like [Self::resolve_packages()] and merge in translated descriptions.
+    ///
+    /// The `Description` of each package having an entry in the `Translation-<locale>`
+    /// file of the component is replaced by the translated, full description. Use a
+    /// `locale` of `en` to obtain the extended descriptions omitted from `Packages`
+    /// files of modern Debian repositories.
+    ///
+    /// Packages are returned untranslated if the release has no `Translation` file
+    /// for the component and locale.
+    async fn resolve_packages_with_translation(
+        &self,
+        component: &str,
+        arch: &str,
+        is_installer: bool,
+        locale: &str,
+    ) -> Result<BinaryPackageList<'static>> {
+        let mut packages = self.resolve_packages(component, arch, is_installer).await?;
+
+        match self.resolve_translation(component, locale).await {
+            Ok(translation) => {
+                translation.merge_into_list(&mut packages)?;
+            }
+            Err(DebianError::RepositoryReadTranslationIndicesEntryNotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(packages)
+    }
 }
 
 /// Describes a repository path verification state.
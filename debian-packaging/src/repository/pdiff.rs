@@ -0,0 +1,485 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Incremental index file updates via `pdiff`.
+
+Repositories can publish the differences between successive versions of an
+index file, so clients holding an older version only need to fetch the
+(much smaller) differences instead of the full file. For a `Packages` file
+at `main/binary-amd64/Packages`, these live under `main/binary-amd64/Packages.diff/`.
+
+The `Index` file in that directory is a control paragraph describing the digest of the
+current version of the index file (`SHA256-Current`), the digests of previous versions
+(`SHA256-History`), and the digests of patches (`SHA256-Patches`), one for each previous
+version. Patches are gzip compressed `ed` scripts, as produced by `diff --ed`, stored
+as `<name>.gz`.
+
+By default, the patch for a previous version transforms it into the next version
+and clients apply all patches from the version they have to the most recent one.
+If the `Index` has `X-Patch-Precedence: merged`, the patch for each previous version
+transforms it directly into the current version.
+
+See <https://wiki.debian.org/DebianRepository/Format#Diffs> for more.
+*/
+
+use {
+    crate::{
+        control::{ControlParagraph, ControlParagraphReader},
+        error::{DebianError, Result},
+        io::{ContentDigest, MultiDigester},
+        repository::release::ChecksumType,
+    },
+    std::{borrow::Cow, io::BufRead, str::FromStr},
+};
+
+/// Whether content has the given size and content digest.
+pub fn content_matches(data: &[u8], size: u64, digest: &ContentDigest) -> bool {
+    let mut digester = MultiDigester::default();
+    digester.update(data);
+
+    data.len() as u64 == size && digester.finish().matches_digest(digest)
+}
+
+/// A command in an `ed` script.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum EdCommand {
+    /// Insert lines after the given line.
+    Append { line: usize, text: Vec<Vec<u8>> },
+    /// Replace an inclusive range of lines.
+    Change {
+        start: usize,
+        end: usize,
+        text: Vec<Vec<u8>>,
+    },
+    /// Delete an inclusive range of lines.
+    Delete { start: usize, end: usize },
+    /// Remove the first character of the current line (`s/.//`).
+    ///
+    /// This is used to insert lines consisting of a single `.`, which
+    /// would otherwise terminate the inserted text.
+    StripDot,
+}
+
+/// A patch in the `ed` script format.
+///
+/// Only the subset of `ed` emitted by `diff --ed` is supported.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EdScript {
+    commands: Vec<EdCommand>,
+}
+
+impl EdScript {
+    /// Parse an `ed` script.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut commands = vec![];
+        let mut lines = data.split_inclusive(|b| *b == b'\n');
+
+        while let Some(line) = lines.next() {
+            let command = std::str::from_utf8(line)
+                .map_err(|_| DebianError::PdiffEdScriptParse("non UTF-8 command".into()))?
+                .trim_end_matches('\n');
+
+            if command.is_empty() {
+                continue;
+            }
+
+            if command == "s/.//" {
+                commands.push(EdCommand::StripDot);
+                continue;
+            }
+
+            let bad_command = || DebianError::PdiffEdScriptParse(command.to_string());
+
+            if !command.is_ascii() {
+                return Err(bad_command());
+            }
+
+            let (range, action) = command.split_at(command.len() - 1);
+            let (start, end) = if let Some((start, end)) = range.split_once(',') {
+                (start, end)
+            } else {
+                (range, range)
+            };
+            let start = usize::from_str(start).map_err(|_| bad_command())?;
+            let end = usize::from_str(end).map_err(|_| bad_command())?;
+
+            let mut read_text = || -> Result<Vec<Vec<u8>>> {
+                let mut text = vec![];
+
+                loop {
+                    let line = lines.next().ok_or_else(|| {
+                        DebianError::PdiffEdScriptParse(format!(
+                            "unterminated text for command {}",
+                            command
+                        ))
+                    })?;
+
+                    if line == b".\n" || line == b"." {
+                        return Ok(text);
+                    }
+
+                    let mut line = line.to_vec();
+                    if !line.ends_with(b"\n") {
+                        line.push(b'\n');
+                    }
+                    text.push(line);
+                }
+            };
+
+            commands.push(match action {
+                "a" if start == end => EdCommand::Append {
+                    line: start,
+                    text: read_text()?,
+                },
+                "c" => EdCommand::Change {
+                    start,
+                    end,
+                    text: read_text()?,
+                },
+                "d" => EdCommand::Delete { start, end },
+                _ => return Err(bad_command()),
+            });
+        }
+
+        Ok(Self { commands })
+    }
+
+    /// Apply the script to content, returning the patched content.
+    pub fn apply(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut lines = data
+            .split_inclusive(|b| *b == b'\n')
+            .map(Cow::Borrowed)
+            .collect::<Vec<_>>();
+
+        let check_range = |start: usize, end: usize, len: usize| {
+            if start == 0 || start > end || end > len {
+                Err(DebianError::PdiffEdScriptApply(format!(
+                    "line range {},{} out of bounds of {} lines",
+                    start, end, len
+                )))
+            } else {
+                Ok(())
+            }
+        };
+
+        // 1-based index of the line `s/.//` operates on.
+        let mut current = 0;
+
+        for command in &self.commands {
+            match command {
+                EdCommand::Append { line, text } => {
+                    if *line > lines.len() {
+                        return Err(DebianError::PdiffEdScriptApply(format!(
+                            "line {} out of bounds of {} lines",
+                            line,
+                            lines.len()
+                        )));
+                    }
+
+                    lines.splice(*line..*line, text.iter().map(|l| Cow::Owned(l.clone())));
+                    current = line + text.len();
+                }
+                EdCommand::Change { start, end, text } => {
+                    check_range(*start, *end, lines.len())?;
+
+                    lines.splice(start - 1..*end, text.iter().map(|l| Cow::Owned(l.clone())));
+                    current = start - 1 + text.len();
+                }
+                EdCommand::Delete { start, end } => {
+                    check_range(*start, *end, lines.len())?;
+
+                    lines.drain(start - 1..*end);
+                    current = (*start).min(lines.len());
+                }
+                EdCommand::StripDot => {
+                    if current == 0
+                        || current > lines.len()
+                        || !lines[current - 1].starts_with(b".")
+                    {
+                        return Err(DebianError::PdiffEdScriptApply(
+                            "s/.// applied to line not starting with .".into(),
+                        ));
+                    }
+
+                    lines[current - 1].to_mut().remove(0);
+                }
+            }
+        }
+
+        Ok(lines.concat())
+    }
+}
+
+/// An entry in a pdiff `Index` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PdiffIndexEntry {
+    /// The content digest.
+    pub digest: ContentDigest,
+    /// The size in bytes.
+    pub size: u64,
+    /// The name of the patch.
+    pub name: String,
+}
+
+impl PdiffIndexEntry {
+    fn parse_lines(
+        paragraph: &ControlParagraph,
+        field: &str,
+        checksum: ChecksumType,
+    ) -> Result<Vec<Self>> {
+        if let Some(lines) = paragraph.iter_field_lines(field) {
+            lines
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    let parts = line.split_ascii_whitespace().collect::<Vec<_>>();
+
+                    if let [digest, size, name] = parts[..] {
+                        Ok(Self {
+                            digest: ContentDigest::from_hex_digest(checksum, digest)?,
+                            size: u64::from_str(size)?,
+                            name: name.to_string(),
+                        })
+                    } else {
+                        Err(DebianError::PdiffIndexBadEntry(line.to_string()))
+                    }
+                })
+                .collect()
+        } else {
+            Ok(vec![])
+        }
+    }
+}
+
+/// A parsed pdiff `Index` file.
+#[derive(Clone, Debug)]
+pub struct PdiffIndex {
+    current_digest: ContentDigest,
+    current_size: u64,
+    history: Vec<PdiffIndexEntry>,
+    patches: Vec<PdiffIndexEntry>,
+    merged: bool,
+}
+
+impl PdiffIndex {
+    /// Construct an instance from a control paragraph.
+    ///
+    /// Digests of the given [ChecksumType] are used. e.g. [ChecksumType::Sha256] reads
+    /// the `SHA256-Current`, `SHA256-History`, and `SHA256-Patches` fields.
+    pub fn from_paragraph(paragraph: &ControlParagraph, checksum: ChecksumType) -> Result<Self> {
+        let field = |suffix: &str| format!("{}-{}", checksum.field_name(), suffix);
+
+        let current_field = field("Current");
+        let current = paragraph.required_field_str(&current_field)?;
+        let (current_digest, current_size) = current
+            .split_once(' ')
+            .ok_or_else(|| DebianError::PdiffIndexBadEntry(current.to_string()))?;
+
+        Ok(Self {
+            current_digest: ContentDigest::from_hex_digest(checksum, current_digest.trim())?,
+            current_size: u64::from_str(current_size.trim())?,
+            history: PdiffIndexEntry::parse_lines(paragraph, &field("History"), checksum)?,
+            patches: PdiffIndexEntry::parse_lines(paragraph, &field("Patches"), checksum)?,
+            merged: paragraph.field_str("X-Patch-Precedence") == Some("merged"),
+        })
+    }
+
+    /// Construct an instance by parsing an `Index` file from a reader.
+    pub fn from_reader(reader: impl BufRead, checksum: ChecksumType) -> Result<Self> {
+        let paragraph = ControlParagraphReader::new(reader)
+            .next()
+            .ok_or(DebianError::ControlFileNoParagraph)??;
+
+        Self::from_paragraph(&paragraph, checksum)
+    }
+
+    /// The content digest of the current version of the index file.
+    pub fn current_digest(&self) -> &ContentDigest {
+        &self.current_digest
+    }
+
+    /// The size of the current version of the index file.
+    pub fn current_size(&self) -> u64 {
+        self.current_size
+    }
+
+    /// Previous versions of the index file, from oldest to newest.
+    ///
+    /// The name of each entry is the name of the patch to apply to that version.
+    pub fn history(&self) -> &[PdiffIndexEntry] {
+        &self.history
+    }
+
+    /// Patches, from oldest to newest.
+    ///
+    /// Digests and sizes are of the decompressed patch.
+    pub fn patches(&self) -> &[PdiffIndexEntry] {
+        &self.patches
+    }
+
+    /// Whether each patch transforms its version directly into the current version.
+    pub fn is_merged(&self) -> bool {
+        self.merged
+    }
+
+    /// Resolve the patches to apply, in order, to the version of the index file having a digest.
+    ///
+    /// Returns an empty list if the digest is of the current version and [None] if the
+    /// version isn't known, in which case the full index file needs to be fetched.
+    pub fn patches_for_digest(&self, digest: &ContentDigest) -> Option<Vec<&PdiffIndexEntry>> {
+        if digest == &self.current_digest {
+            return Some(vec![]);
+        }
+
+        let name = &self
+            .history
+            .iter()
+            .rev()
+            .find(|entry| &entry.digest == digest)?
+            .name;
+
+        let position = self.patches.iter().position(|entry| &entry.name == name)?;
+
+        Some(if self.merged {
+            vec![&self.patches[position]]
+        } else {
+            self.patches[position..].iter().collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        indoc::{formatdoc, indoc},
+    };
+
+    const OLD: &str = "a\nb\nc\nd\ne\n";
+
+    #[test]
+    fn ed_script_apply() -> Result<()> {
+        // As produced by `diff --ed`: commands in descending line order.
+        let script = EdScript::parse(
+            indoc! {"
+                5a
+                f
+                g
+                .
+                3,4c
+                C
+                .
+                1d
+            "}
+            .as_bytes(),
+        )?;
+
+        assert_eq!(script.apply(OLD.as_bytes())?, b"b\nC\ne\nf\ng\n");
+
+        let script = EdScript::parse(b"0a\nz\n.\n")?;
+        assert_eq!(script.apply(OLD.as_bytes())?, b"z\na\nb\nc\nd\ne\n");
+
+        let script = EdScript::parse(b"2,5d\n")?;
+        assert_eq!(script.apply(OLD.as_bytes())?, b"a\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn ed_script_strip_dot() -> Result<()> {
+        // Inserting a line consisting of `.`.
+        let script = EdScript::parse(b"1a\n..\n.\ns/.//\n")?;
+        assert_eq!(script.apply(b"a\nb\n")?, b"a\n.\nb\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn ed_script_errors() {
+        assert!(EdScript::parse(b"1x\n").is_err());
+        assert!(EdScript::parse(b"1a\nfoo\n").is_err());
+        assert!(EdScript::parse(b"1,2a\nfoo\n.\n").is_err());
+
+        let script = EdScript::parse(b"7d\n").unwrap();
+        assert!(script.apply(OLD.as_bytes()).is_err());
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        let mut digester = MultiDigester::default();
+        digester.update(data);
+        digester.finish().sha256.digest_hex()
+    }
+
+    fn index(merged: bool) -> Result<PdiffIndex> {
+        let mut s = formatdoc! {"
+            SHA256-Current: {} 10
+            SHA256-History:
+             {} 10 T-2021-12-01-0200.00-F-2021-12-01-0200.00
+             {} 10 T-2021-12-01-0800.00-F-2021-12-01-0800.00
+            SHA256-Patches:
+             {} 20 T-2021-12-01-0200.00-F-2021-12-01-0200.00
+             {} 21 T-2021-12-01-0800.00-F-2021-12-01-0800.00
+        ",
+            sha256_hex(b"current"),
+            sha256_hex(b"oldest"),
+            sha256_hex(b"older"),
+            sha256_hex(b"patch1"),
+            sha256_hex(b"patch2"),
+        };
+
+        if merged {
+            s.push_str("X-Patch-Precedence: merged\n");
+        }
+
+        PdiffIndex::from_reader(std::io::Cursor::new(s), ChecksumType::Sha256)
+    }
+
+    #[test]
+    fn parse_index() -> Result<()> {
+        let index = index(false)?;
+
+        assert_eq!(index.current_digest().digest_hex(), sha256_hex(b"current"));
+        assert_eq!(index.current_size(), 10);
+        assert_eq!(index.history().len(), 2);
+        assert_eq!(index.patches().len(), 2);
+        assert_eq!(index.patches()[1].size, 21);
+        assert!(!index.is_merged());
+        assert!(self::index(true)?.is_merged());
+
+        Ok(())
+    }
+
+    #[test]
+    fn patches_for_digest() -> Result<()> {
+        let digest = |data: &[u8]| ContentDigest::sha256_hex(&sha256_hex(data)).unwrap();
+
+        let index = index(false)?;
+        assert_eq!(index.patches_for_digest(&digest(b"current")), Some(vec![]));
+        assert_eq!(index.patches_for_digest(&digest(b"unknown")), None);
+        assert_eq!(
+            index.patches_for_digest(&digest(b"oldest")).unwrap().len(),
+            2
+        );
+        assert_eq!(
+            index.patches_for_digest(&digest(b"older")).unwrap(),
+            vec![&index.patches()[1]]
+        );
+
+        let index = self::index(true)?;
+        assert_eq!(
+            index.patches_for_digest(&digest(b"oldest")).unwrap(),
+            vec![&index.patches()[0]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn content_digest_match() {
+        let digest = ContentDigest::sha256_hex(&sha256_hex(b"foo")).unwrap();
+
+        assert!(content_matches(b"foo", 3, &digest));
+        assert!(!content_matches(b"foo", 4, &digest));
+        assert!(!content_matches(b"bar", 3, &digest));
+    }
+}
@@ -22,19 +22,24 @@ Additional types describe more strongly typed indices file variants:
 
 The [ClassifiedReleaseFileEntry] enum wraps all these types and attempts to
 classify each entry as the strongest type possible.
+
+[ReleaseFileBuilder] constructs `Release` and `InRelease` files describing
+index files, along with the paths index files are published at.
 */
 
 use {
     crate::{
-        control::{ControlParagraph, ControlParagraphReader},
+        control::{ControlField, ControlParagraph, ControlParagraphReader},
         error::{DebianError, Result},
-        io::ContentDigest,
+        io::{ContentDigest, MultiContentDigest, MultiDigester},
         repository::Compression,
     },
     chrono::{DateTime, Utc},
-    pgp_cleartext::CleartextHasher,
+    pgp::{crypto::HashAlgorithm, types::SecretKeyTrait},
+    pgp_cleartext::{cleartext_sign, CleartextHasher},
     std::{
         borrow::Cow,
+        collections::{BTreeMap, BTreeSet},
         io::BufRead,
         ops::{Deref, DerefMut},
         str::FromStr,
@@ -106,20 +111,28 @@ pub struct ReleaseFileEntry<'a> {
 impl<'a> ReleaseFileEntry<'a> {
     /// Obtain the `by-hash` path variant for this entry.
     pub fn by_hash_path(&self) -> String {
-        if let Some((prefix, _)) = self.path.rsplit_once('/') {
-            format!(
-                "{}/by-hash/{}/{}",
-                prefix,
-                self.digest.release_field_name(),
-                self.digest.digest_hex()
-            )
-        } else {
-            format!(
-                "by-hash/{}/{}",
-                self.digest.release_field_name(),
-                self.digest.digest_hex()
-            )
-        }
+        by_hash_path(self.path, &self.digest)
+    }
+}
+
+/// Obtain the `by-hash` path of an index file given its path and a [ContentDigest].
+///
+/// The `by-hash` directory is a sibling of the index file. e.g. the `SHA256` `by-hash`
+/// path of `main/binary-amd64/Packages.xz` is `main/binary-amd64/by-hash/SHA256/<digest>`.
+pub fn by_hash_path(path: &str, digest: &ContentDigest) -> String {
+    if let Some((prefix, _)) = path.rsplit_once('/') {
+        format!(
+            "{}/by-hash/{}/{}",
+            prefix,
+            digest.release_field_name(),
+            digest.digest_hex()
+        )
+    } else {
+        format!(
+            "by-hash/{}/{}",
+            digest.release_field_name(),
+            digest.digest_hex()
+        )
     }
 }
 
@@ -1240,6 +1253,191 @@ impl<'a> ReleaseFile<'a> {
             None
         }
     }
+
+    /// Obtain `Translation` indices entries given a checksum flavor.
+    ///
+    /// This essentially looks for `i18n/Translation-*` files in the file lists.
+    pub fn iter_translations_indices(
+        &self,
+        checksum: ChecksumType,
+    ) -> Option<Box<(dyn Iterator<Item = Result<TranslationFileEntry<'_>>> + '_)>> {
+        if let Some(iter) = self.iter_index_files(checksum) {
+            Some(Box::new(iter.filter_map(|entry| match entry {
+                Ok(entry) => match TranslationFileEntry::try_from(entry) {
+                    Ok(v) => Some(Ok(v)),
+                    Err(DebianError::ReleaseIndicesEntryWrongType) => None,
+                    Err(e) => Some(Err(e)),
+                },
+                Err(e) => Some(Err(e)),
+            })))
+        } else {
+            None
+        }
+    }
+}
+
+/// Builds `Release` files.
+///
+/// Metadata fields are registered via [Self::set_field()] and index files via
+/// [Self::add_index_file()] or [Self::add_index_file_data()]. [Self::to_release_file()]
+/// then emits a [ReleaseFile] with a checksums field for each registered [ChecksumType],
+/// listing the digest, size, and path of every index file.
+///
+/// If `Acquire-By-Hash` is enabled, clients fetch index files from `by-hash` paths named
+/// after their digest, so index files can be updated without clients fetching
+/// index files not matching the `Release` file they fetched. [Self::index_file_paths()]
+/// returns the paths each index file needs to be published at.
+#[derive(Clone, Debug)]
+pub struct ReleaseFileBuilder {
+    fields: BTreeMap<String, String>,
+    checksums: BTreeSet<ChecksumType>,
+    acquire_by_hash: Option<bool>,
+    index_files: BTreeMap<String, (u64, MultiContentDigest)>,
+}
+
+impl Default for ReleaseFileBuilder {
+    fn default() -> Self {
+        Self::new([ChecksumType::Md5, ChecksumType::Sha256])
+    }
+}
+
+impl ReleaseFileBuilder {
+    /// Create a new instance emitting the given checksum types.
+    ///
+    /// [ChecksumType::Sha256] should always be used. Adding [ChecksumType::Md5] is
+    /// recommended for compatibility with old clients.
+    pub fn new(checksums: impl IntoIterator<Item = ChecksumType>) -> Self {
+        Self {
+            fields: BTreeMap::new(),
+            checksums: checksums.into_iter().collect(),
+            acquire_by_hash: None,
+            index_files: BTreeMap::new(),
+        }
+    }
+
+    /// Set the value of a metadata field.
+    ///
+    /// e.g. `Suite`, `Codename`, `Components`, or `Date`. Checksums fields and
+    /// `Acquire-By-Hash` are derived from the state of the builder and shouldn't be set.
+    pub fn set_field(&mut self, name: impl ToString, value: impl ToString) {
+        self.fields.insert(name.to_string(), value.to_string());
+    }
+
+    /// Set the value of `Acquire-By-Hash`.
+    ///
+    /// If not set, the field isn't emitted and index files aren't published at
+    /// `by-hash` paths.
+    pub fn set_acquire_by_hash(&mut self, value: bool) {
+        self.acquire_by_hash = Some(value);
+    }
+
+    /// Register an index file given its size and digests.
+    ///
+    /// `path` is relative to the directory of the `Release` file. e.g.
+    /// `main/binary-amd64/Packages.xz`.
+    pub fn add_index_file(&mut self, path: impl ToString, size: u64, digests: MultiContentDigest) {
+        self.index_files.insert(path.to_string(), (size, digests));
+    }
+
+    /// Register an index file given its content.
+    pub fn add_index_file_data(&mut self, path: impl ToString, data: &[u8]) {
+        let mut digester = MultiDigester::default();
+        digester.update(data);
+
+        self.add_index_file(path, data.len() as u64, digester.finish());
+    }
+
+    /// Obtain the paths a registered index file needs to be published at.
+    ///
+    /// The canonical path is always emitted, for clients not supporting `Acquire-By-Hash`.
+    /// If `Acquire-By-Hash` is enabled, the `by-hash` path for each checksum type follows.
+    /// Returns [None] if the index file isn't registered.
+    pub fn index_file_paths(&self, path: &str) -> Option<Vec<String>> {
+        let (_, digests) = self.index_files.get(path)?;
+
+        let mut paths = vec![path.to_string()];
+
+        if self.acquire_by_hash == Some(true) {
+            paths.extend(
+                self.checksums
+                    .iter()
+                    .map(|checksum| by_hash_path(path, digests.digest_from_checksum(*checksum))),
+            );
+        }
+
+        Some(paths)
+    }
+
+    /// Emit a [ReleaseFile] describing registered metadata and index files.
+    pub fn to_release_file(&self) -> ReleaseFile<'static> {
+        let mut para = ControlParagraph::default();
+
+        for (name, value) in &self.fields {
+            para.set_field(ControlField::new(name.clone().into(), value.clone().into()));
+        }
+
+        if let Some(acquire_by_hash) = self.acquire_by_hash {
+            para.set_field_from_string(
+                "Acquire-By-Hash".into(),
+                if acquire_by_hash { "yes" } else { "no" }.into(),
+            );
+        }
+
+        for checksum in &self.checksums {
+            let entries = self
+                .index_files
+                .iter()
+                .map(|(path, (size, digests))| {
+                    (
+                        path,
+                        size,
+                        digests.digest_from_checksum(*checksum).digest_hex(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let longest_size = entries
+                .iter()
+                .map(|(_, size, _)| format!("{}", size).len())
+                .max()
+                .unwrap_or_default();
+
+            // Lines are of the form ` <digest> <size> <path>`, with sizes right aligned.
+            para.set_field(ControlField::from_lines(
+                checksum.field_name().into(),
+                std::iter::once("".to_string()).chain(entries.into_iter().map(
+                    |(path, size, digest)| {
+                        format!(
+                            "{} {:>size_width$} {}",
+                            digest,
+                            size,
+                            path,
+                            size_width = longest_size
+                        )
+                    },
+                )),
+            ));
+        }
+
+        para.into()
+    }
+
+    /// Emit the content of an `InRelease` file, signing the `Release` file with a key.
+    pub fn to_inrelease_string<PW>(
+        &self,
+        signing_key: &impl SecretKeyTrait,
+        password: PW,
+    ) -> Result<String>
+    where
+        PW: FnOnce() -> String,
+    {
+        Ok(cleartext_sign(
+            signing_key,
+            password,
+            HashAlgorithm::SHA2_256,
+            std::io::Cursor::new(self.to_release_file().to_string().as_bytes()),
+        )?)
+    }
 }
 
 #[cfg(test)]
@@ -1627,6 +1825,37 @@ mod test {
             }
         );
 
+        let translations = release
+            .iter_translations_indices(ChecksumType::Sha256)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(translations.len(), EXPECTED_TRANSLATION);
+
+        let entry = translations
+            .into_iter()
+            .find(|entry| {
+                entry.component == "main"
+                    && entry.locale == "en"
+                    && entry.compression == Compression::Bzip2
+            })
+            .unwrap();
+        assert_eq!(
+            entry,
+            TranslationFileEntry {
+                entry: ReleaseFileEntry {
+                    path: "main/i18n/Translation-en.bz2",
+                    digest: ContentDigest::sha256_hex(
+                        "a10a6afaed08d5920ca1f25470447ebe309d6ec6e1dccd7984dbf80f3794e9e5"
+                    )
+                    .unwrap(),
+                    size: 6241447,
+                },
+                component: "main".into(),
+                locale: "en".into(),
+                compression: Compression::Bzip2
+            }
+        );
+
         Ok(())
     }
 
@@ -1663,4 +1892,90 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn release_file_builder() -> Result<()> {
+        let mut builder = ReleaseFileBuilder::default();
+        builder.set_field("Suite", "stable");
+        builder.set_field("Components", "main");
+        builder.set_acquire_by_hash(true);
+        builder.add_index_file_data("main/binary-amd64/Packages", b"Package: foo\n");
+        builder.add_index_file_data("main/binary-amd64/Packages.xz", b"");
+
+        let release = builder.to_release_file();
+        assert_eq!(release.suite(), Some("stable"));
+        assert_eq!(release.acquire_by_hash(), Some(true));
+
+        // Round trip through the serialized form to verify checksum blocks parse.
+        let release = ReleaseFile::from_reader(std::io::Cursor::new(release.to_string()))?;
+        assert!(release.iter_index_files(ChecksumType::Sha1).is_none());
+
+        let entries = release
+            .iter_index_files(ChecksumType::Md5)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "main/binary-amd64/Packages");
+        assert_eq!(entries[0].size, 13);
+        assert_eq!(entries[1].path, "main/binary-amd64/Packages.xz");
+        assert_eq!(entries[1].size, 0);
+        assert_eq!(
+            entries[1].digest.digest_hex(),
+            "d41d8cd98f00b204e9800998ecf8427e"
+        );
+
+        let entries = release
+            .iter_index_files(ChecksumType::Sha256)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            entries[1].digest.digest_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        assert_eq!(
+            builder.index_file_paths("main/binary-amd64/Packages.xz"),
+            Some(vec![
+                "main/binary-amd64/Packages.xz".to_string(),
+                "main/binary-amd64/by-hash/MD5Sum/d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                "main/binary-amd64/by-hash/SHA256/e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string(),
+            ])
+        );
+        assert!(builder
+            .index_file_paths("main/binary-i386/Packages")
+            .is_none());
+
+        builder.set_acquire_by_hash(false);
+        assert_eq!(
+            builder.index_file_paths("main/binary-amd64/Packages.xz"),
+            Some(vec!["main/binary-amd64/Packages.xz".to_string()])
+        );
+        assert_eq!(builder.to_release_file().acquire_by_hash(), Some(false));
+
+        Ok(())
+    }
+
+    #[test]
+    fn release_file_builder_inrelease() -> Result<()> {
+        let passwd_fn = String::new;
+        let key = crate::signing_key::create_self_signed_key(
+            crate::signing_key::signing_secret_key_params_builder("Me <someone@example.com>")
+                .build()
+                .unwrap(),
+            passwd_fn,
+        )?
+        .0;
+
+        let mut builder = ReleaseFileBuilder::default();
+        builder.set_field("Suite", "stable");
+        builder.add_index_file_data("main/binary-amd64/Packages", b"");
+
+        let inrelease = builder.to_inrelease_string(&key, passwd_fn)?;
+        let release = ReleaseFile::from_armored_reader(std::io::Cursor::new(inrelease))?;
+
+        assert_eq!(release.suite(), Some("stable"));
+        release.signatures().unwrap().verify(&key)?;
+
+        Ok(())
+    }
 }
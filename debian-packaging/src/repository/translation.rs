@@ -0,0 +1,294 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `Translation` index file handling.
+
+`Translation-<locale>` files live under `<component>/i18n/` and hold the
+descriptions of binary packages. Modern Debian repositories omit extended
+descriptions from `Packages` files and only retain the synopsis plus a
+`Description-md5` field. The full description is then looked up in the
+`Translation-en` file by package name and that digest.
+*/
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        control::{ControlField, ControlParagraph, ControlParagraphAsyncReader},
+        error::Result,
+    },
+    futures::AsyncBufRead,
+    md5::Digest,
+    std::collections::HashMap,
+};
+
+/// Compute the value of the `Description-md5` field for a description.
+///
+/// The digest covers the full untranslated description as written in the
+/// control file, including a trailing newline.
+pub fn description_md5(description: &str) -> String {
+    let mut h = md5::Md5::new();
+    h.update(description.as_bytes());
+    h.update(b"\n");
+
+    hex::encode(h.finalize())
+}
+
+/// Represents a `Translation-<locale>` file.
+///
+/// Translated descriptions are indexed by package name and the digest of the
+/// untranslated description they translate.
+#[derive(Clone, Debug, Default)]
+pub struct TranslationFile {
+    locale: String,
+    descriptions: HashMap<(String, String), String>,
+}
+
+impl TranslationFile {
+    /// Construct an empty instance for the given locale.
+    pub fn new(locale: impl ToString) -> Self {
+        Self {
+            locale: locale.to_string(),
+            descriptions: HashMap::new(),
+        }
+    }
+
+    /// The locale descriptions are translated to.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// The number of translated descriptions.
+    pub fn len(&self) -> usize {
+        self.descriptions.len()
+    }
+
+    /// Whether there are no translated descriptions.
+    pub fn is_empty(&self) -> bool {
+        self.descriptions.is_empty()
+    }
+
+    /// Register a translated description.
+    pub fn add_description(
+        &mut self,
+        package: impl ToString,
+        description_md5: impl ToString,
+        description: impl ToString,
+    ) {
+        self.descriptions.insert(
+            (package.to_string(), description_md5.to_string()),
+            description.to_string(),
+        );
+    }
+
+    /// Register the translated description defined by a paragraph of a `Translation` file.
+    ///
+    /// The paragraph must have `Package`, `Description-md5` and `Description-<locale>` fields.
+    pub fn add_paragraph(&mut self, paragraph: &ControlParagraph) -> Result<()> {
+        let field = format!("Description-{}", self.locale);
+
+        let package = paragraph.required_field_str("Package")?;
+        let md5 = paragraph.required_field_str("Description-md5")?;
+        let description = paragraph.required_field_str(&field)?;
+
+        self.add_description(package, md5, description);
+
+        Ok(())
+    }
+
+    /// Obtain the translated description of a package.
+    ///
+    /// `description_md5` is the digest of the untranslated description, as
+    /// defined by the `Description-md5` field.
+    pub fn description(&self, package: &str, description_md5: &str) -> Option<&str> {
+        self.descriptions
+            .get(&(package.to_string(), description_md5.to_string()))
+            .map(|x| x.as_str())
+    }
+
+    /// Obtain the translated description for a binary package control file.
+    ///
+    /// The `Description-md5` field is used if present. Otherwise the digest is computed
+    /// from the `Description` field.
+    pub fn package_description(&self, cf: &BinaryPackageControlFile) -> Result<Option<&str>> {
+        let package = cf.package()?;
+
+        let md5 = if let Some(md5) = cf.field_str("Description-md5") {
+            md5.to_string()
+        } else {
+            description_md5(cf.description()?)
+        };
+
+        Ok(self.description(package, &md5))
+    }
+
+    /// Replace the `Description` of a binary package with its translation.
+    ///
+    /// Returns whether a translation was found. The control file is left
+    /// untouched otherwise.
+    pub fn merge_into(&self, cf: &mut BinaryPackageControlFile) -> Result<bool> {
+        if let Some(description) = self.package_description(cf)? {
+            let description = description.to_string();
+
+            cf.set_field(ControlField::new("Description".into(), description.into()));
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Replace the `Description` of all binary packages having a translation.
+    ///
+    /// Returns the number of packages that were translated.
+    pub fn merge_into_list(&self, packages: &mut BinaryPackageList) -> Result<usize> {
+        let mut count = 0;
+
+        for cf in packages.iter_mut() {
+            if self.merge_into(cf)? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// A reader for [TranslationFile].
+pub struct TranslationFileAsyncReader<R> {
+    reader: ControlParagraphAsyncReader<R>,
+    translation: TranslationFile,
+}
+
+impl<R> TranslationFileAsyncReader<R>
+where
+    R: AsyncBufRead + Unpin,
+{
+    /// Create a new instance bound to a reader of a `Translation-<locale>` file.
+    pub fn new(reader: R, locale: impl ToString) -> Self {
+        Self {
+            reader: ControlParagraphAsyncReader::new(reader),
+            translation: TranslationFile::new(locale),
+        }
+    }
+
+    /// Read all paragraphs of the file.
+    pub async fn read_all(&mut self) -> Result<()> {
+        while let Some(paragraph) = self.reader.read_paragraph().await? {
+            self.translation.add_paragraph(&paragraph)?;
+        }
+
+        Ok(())
+    }
+
+    /// Consume self, returning the parsed [TranslationFile] and the inner reader.
+    pub fn consume(self) -> (TranslationFile, R) {
+        (self.translation, self.reader.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraphReader, indoc::indoc, std::io::Cursor};
+
+    const PACKAGES: &str = indoc! {"
+        Package: zsh
+        Version: 5.8-6
+        Architecture: amd64
+        Description: shell with lots of features
+        Description-md5: a1852edbd2b7e64f50fb13acf4a8f6b1
+
+        Package: bash
+        Version: 5.1-2
+        Architecture: amd64
+        Description: GNU Bourne Again SHell
+        Description-md5: 3522aa7b4374048d6450e348a5bb45d9
+    "};
+
+    const TRANSLATION: &str = indoc! {"
+        Package: zsh
+        Description-md5: a1852edbd2b7e64f50fb13acf4a8f6b1
+        Description-en: shell with lots of features
+         Zsh is a UNIX command interpreter (shell) usable as an
+         interactive login shell and as a shell script command
+         processor.
+
+        Package: zsh
+        Description-md5: 00000000000000000000000000000000
+        Description-en: shell with lots of features (outdated)
+    "};
+
+    #[test]
+    fn description_digest() {
+        assert_eq!(description_md5("a"), "60b725f10c9c85c70d97880dfe8191b3");
+    }
+
+    #[test]
+    fn parse_and_merge() -> Result<()> {
+        let mut reader = TranslationFileAsyncReader::new(
+            futures::io::BufReader::new(futures::io::Cursor::new(TRANSLATION.as_bytes())),
+            "en",
+        );
+        futures::executor::block_on(reader.read_all())?;
+        let (translation, _) = reader.consume();
+
+        assert_eq!(translation.locale(), "en");
+        assert_eq!(translation.len(), 2);
+
+        let mut packages = BinaryPackageList::default();
+        for paragraph in ControlParagraphReader::new(Cursor::new(PACKAGES.as_bytes())) {
+            packages.push(BinaryPackageControlFile::from(paragraph?));
+        }
+
+        assert_eq!(translation.merge_into_list(&mut packages)?, 1);
+
+        let zsh = &packages[0];
+        assert_eq!(
+            zsh.field("Description")
+                .unwrap()
+                .iter_lines()
+                .collect::<Vec<_>>(),
+            vec![
+                "shell with lots of features",
+                "Zsh is a UNIX command interpreter (shell) usable as an",
+                "interactive login shell and as a shell script command",
+                "processor."
+            ]
+        );
+        assert_eq!(packages[1].description()?, "GNU Bourne Again SHell");
+
+        Ok(())
+    }
+
+    #[test]
+    fn computed_digest() -> Result<()> {
+        let mut translation = TranslationFile::new("en");
+        translation.add_description(
+            "foo",
+            description_md5("foo synopsis"),
+            "foo synopsis\n extended",
+        );
+
+        let mut cf = BinaryPackageControlFile::from(ControlParagraph::default());
+        cf.set_field_from_string("Package".into(), "foo".into());
+        cf.set_field_from_string("Description".into(), "foo synopsis".into());
+
+        assert!(translation.merge_into(&mut cf)?);
+        assert_eq!(cf.description()?, "foo synopsis\n extended");
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_locale_field() {
+        let mut translation = TranslationFile::new("de");
+
+        let paragraph = ControlParagraphReader::new(Cursor::new(TRANSLATION.as_bytes()))
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert!(translation.add_paragraph(&paragraph).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Assembly of sysroots from binary packages.
+
+A sysroot is a directory tree mirroring the root filesystem of a target
+machine. Compilers and linkers targeting a foreign architecture use it to find
+the headers and libraries of that machine. e.g. `clang --sysroot`.
+
+[create_sysroot()] resolves the dependency-closed set of binary packages for a set
+of requested packages from a repository and extracts their `data.tar` archives into
+a directory. Since packages are extracted without running maintainer scripts, the
+resulting tree is only suitable for build time consumption.
+
+Absolute symlinks are rewritten to relative ones, so they resolve within the sysroot
+instead of against the root filesystem of the build machine.
+*/
+
+use {
+    crate::{
+        binary_package_control::BinaryPackageControlFile,
+        binary_package_list::BinaryPackageList,
+        deb::reader::DebReader,
+        dependency::BinaryDependency,
+        dependency_resolution::DependencyResolver,
+        error::{DebianError, Result},
+        repository::{
+            builder::DebPackageReference, BinaryPackageFetch, ReleaseReader, RepositoryRootReader,
+        },
+    },
+    futures::AsyncReadExt,
+    std::{
+        collections::{HashMap, VecDeque},
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Select the newest package from a list of candidates.
+fn newest<'file, 'data>(
+    candidates: impl Iterator<Item = &'file BinaryPackageControlFile<'data>>,
+) -> Result<Option<&'file BinaryPackageControlFile<'data>>> {
+    let mut best: Option<&BinaryPackageControlFile> = None;
+
+    for cf in candidates {
+        best = match best {
+            Some(existing) if existing.version()? >= cf.version()? => Some(existing),
+            _ => Some(cf),
+        };
+    }
+
+    Ok(best)
+}
+
+/// Packages selected for installation, indexed by name.
+#[derive(Default)]
+struct Selection<'file, 'data: 'file> {
+    packages: HashMap<&'file str, &'file BinaryPackageControlFile<'data>>,
+    order: Vec<&'file BinaryPackageControlFile<'data>>,
+    remaining: VecDeque<&'file BinaryPackageControlFile<'data>>,
+}
+
+impl<'file, 'data: 'file> Selection<'file, 'data> {
+    /// Whether a package with the same name and version is selected.
+    fn contains(&self, cf: &BinaryPackageControlFile) -> bool {
+        match (cf.package(), cf.version_str()) {
+            (Ok(name), Ok(version)) => matches!(
+                self.packages.get(name),
+                Some(existing) if existing.version_str().ok() == Some(version)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Select a package, queuing it for dependency resolution.
+    ///
+    /// Errors if another version of the package is already selected.
+    fn select(&mut self, cf: &'file BinaryPackageControlFile<'data>) -> Result<()> {
+        if self.contains(cf) {
+            return Ok(());
+        }
+
+        let name = cf.package()?;
+
+        if let Some(existing) = self.packages.get(name) {
+            return Err(DebianError::SysrootConflictingVersions(
+                name.to_string(),
+                existing.version_str()?.to_string(),
+                cf.version_str()?.to_string(),
+            ));
+        }
+
+        self.packages.insert(name, cf);
+        self.order.push(cf);
+        self.remaining.push_back(cf);
+
+        Ok(())
+    }
+}
+
+/// Resolve the dependency-closed set of packages needed to install packages.
+///
+/// `Depends` and `Pre-Depends` are followed. For each dependency not yet satisfied by an
+/// already selected package, the newest package satisfying the first satisfiable
+/// alternative is selected.
+///
+/// Packages are returned in the order they were selected, starting with the requested
+/// packages.
+pub fn resolve_sysroot_packages<'file, 'data: 'file>(
+    packages: &'file BinaryPackageList<'data>,
+    names: impl Iterator<Item = impl AsRef<str>>,
+) -> Result<Vec<&'file BinaryPackageControlFile<'data>>> {
+    let mut resolver = DependencyResolver::default();
+    resolver.load_binary_packages(packages.iter())?;
+
+    let mut selection = Selection::default();
+
+    for name in names {
+        let name = name.as_ref();
+
+        let cf = newest(packages.find_packages_with_name(name.to_string()))?
+            .ok_or_else(|| DebianError::SysrootPackageNotFound(name.to_string()))?;
+
+        selection.select(cf)?;
+    }
+
+    while let Some(cf) = selection.remaining.pop_front() {
+        for field in [BinaryDependency::PreDepends, BinaryDependency::Depends] {
+            let deps = resolver.find_direct_binary_package_dependencies(cf, field)?;
+
+            for requirement in &deps.parts {
+                if requirement
+                    .packages()
+                    .any(|candidate| selection.contains(candidate))
+                {
+                    continue;
+                }
+
+                let candidate = requirement
+                    .alternatives
+                    .iter()
+                    .find(|alt| !alt.is_empty())
+                    .map(|alt| newest(alt.packages()))
+                    .transpose()?
+                    .flatten()
+                    .ok_or_else(|| {
+                        DebianError::SysrootUnsatisfiedDependency(
+                            cf.package().unwrap_or_default().to_string(),
+                            requirement
+                                .alternative_constraints()
+                                .map(|x| x.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" | "),
+                        )
+                    })?;
+
+                selection.select(candidate)?;
+            }
+        }
+    }
+
+    Ok(selection.order)
+}
+
+/// Compute the relative target of a symlink pointing to an absolute path within a sysroot.
+///
+/// `link` is the path of the symlink relative to the sysroot.
+fn relative_symlink_target(link: &Path, target: &Path) -> PathBuf {
+    let depth = link
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .count()
+        })
+        .unwrap_or_default();
+
+    let mut res = PathBuf::new();
+    for _ in 0..depth {
+        res.push("..");
+    }
+
+    res.join(target.strip_prefix("/").unwrap_or(target))
+}
+
+/// Rewrite absolute symlinks under a sysroot to relative ones.
+///
+/// Returns the number of rewritten symlinks.
+#[cfg(unix)]
+fn fixup_absolute_symlinks(root: &Path) -> Result<usize> {
+    let mut count = 0;
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let metadata = std::fs::symlink_metadata(&path)?;
+
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if metadata.file_type().is_symlink() {
+                let target = std::fs::read_link(&path)?;
+
+                if target.is_absolute() {
+                    let link = path
+                        .strip_prefix(root)
+                        .expect("path should be under sysroot");
+
+                    std::fs::remove_file(&path)?;
+                    std::os::unix::fs::symlink(relative_symlink_target(link, &target), &path)?;
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(not(unix))]
+fn fixup_absolute_symlinks(_root: &Path) -> Result<usize> {
+    Ok(0)
+}
+
+/// Create a sysroot from binary packages in a repository.
+///
+/// `packages` are the names of the packages to install. They and their dependencies,
+/// as resolved by [resolve_sysroot_packages()], are looked up in the `Packages` indices
+/// of all components of `release` for architecture `arch` and `all`. The `.deb` of each
+/// resolved package is then fetched from `root` and extracted into `dest`.
+///
+/// Returns the control files of the installed packages.
+pub async fn create_sysroot(
+    root: &dyn RepositoryRootReader,
+    release: &dyn ReleaseReader,
+    packages: &[&str],
+    arch: &str,
+    dest: &Path,
+) -> Result<Vec<BinaryPackageControlFile<'static>>> {
+    let mut available = BinaryPackageList::default();
+
+    for entry in release.packages_indices_entries_preferred_compression()? {
+        if !entry.is_installer && (entry.architecture == arch || entry.architecture == "all") {
+            available.extend(release.resolve_packages_from_entry(&entry).await?);
+        }
+    }
+
+    let installed = resolve_sysroot_packages(&available, packages.iter())?
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    std::fs::create_dir_all(dest)?;
+
+    let checksum = release.retrieve_checksum()?;
+
+    for cf in &installed {
+        let fetch = BinaryPackageFetch {
+            control_file: cf.clone(),
+            path: cf.required_field_str("Filename")?.to_string(),
+            size: cf.deb_size_bytes()?,
+            digest: cf.deb_digest(checksum)?,
+        };
+
+        let mut reader = root.fetch_binary_package_generic(fetch).await?;
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+
+        DebReader::new(std::io::Cursor::new(data))?
+            .into_data_tar()
+            .into_inner()
+            .unpack(dest)
+            .await?;
+    }
+
+    fixup_absolute_symlinks(dest)?;
+
+    Ok(installed)
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::control::ControlParagraphReader, indoc::indoc, std::io::Cursor};
+
+    const PACKAGES: &str = indoc! {"
+        Package: libc6
+        Version: 2.31-13
+        Architecture: arm64
+
+        Package: libc6
+        Version: 2.32-4
+        Architecture: arm64
+
+        Package: libssl1.1
+        Version: 1.1.1k-1
+        Architecture: arm64
+        Depends: libc6 (>= 2.28), debconf (>= 0.5) | debconf-2.0
+
+        Package: cdebconf
+        Version: 0.260
+        Architecture: arm64
+        Provides: debconf-2.0
+
+        Package: libssl-dev
+        Version: 1.1.1k-1
+        Architecture: arm64
+        Depends: libssl1.1 (= 1.1.1k-1)
+
+        Package: broken
+        Version: 1.0
+        Architecture: arm64
+        Depends: missing
+    "};
+
+    fn packages() -> Result<BinaryPackageList<'static>> {
+        let mut res = BinaryPackageList::default();
+
+        for paragraph in ControlParagraphReader::new(Cursor::new(PACKAGES.as_bytes())) {
+            res.push(BinaryPackageControlFile::from(paragraph?));
+        }
+
+        Ok(res)
+    }
+
+    #[test]
+    fn resolve_packages() -> Result<()> {
+        let packages = packages()?;
+
+        let resolved = resolve_sysroot_packages(&packages, ["libssl-dev"].iter())?
+            .into_iter()
+            .map(|cf| format!("{}={}", cf.package().unwrap(), cf.version_str().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            resolved,
+            vec![
+                "libssl-dev=1.1.1k-1",
+                "libssl1.1=1.1.1k-1",
+                "libc6=2.32-4",
+                "cdebconf=0.260"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_errors() -> Result<()> {
+        let packages = packages()?;
+
+        assert!(matches!(
+            resolve_sysroot_packages(&packages, ["nope"].iter()),
+            Err(DebianError::SysrootPackageNotFound(_))
+        ));
+        assert!(matches!(
+            resolve_sysroot_packages(&packages, ["broken"].iter()),
+            Err(DebianError::SysrootUnsatisfiedDependency(_, _))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn symlink_target() {
+        assert_eq!(
+            relative_symlink_target(
+                Path::new("usr/lib/aarch64-linux-gnu/libssl.so"),
+                Path::new("/lib/aarch64-linux-gnu/libssl.so.1.1")
+            ),
+            PathBuf::from("../../../lib/aarch64-linux-gnu/libssl.so.1.1")
+        );
+        assert_eq!(
+            relative_symlink_target(Path::new("./lib64"), Path::new("/usr/lib64")),
+            PathBuf::from("usr/lib64")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fixup_symlinks() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+
+        std::fs::create_dir_all(root.join("usr/lib"))?;
+        std::os::unix::fs::symlink("/lib/libfoo.so.1", root.join("usr/lib/libfoo.so"))?;
+        std::os::unix::fs::symlink("libfoo.so", root.join("usr/lib/libfoo-dev.so"))?;
+
+        assert_eq!(fixup_absolute_symlinks(root)?, 1);
+        assert_eq!(
+            std::fs::read_link(root.join("usr/lib/libfoo.so"))?,
+            PathBuf::from("../../lib/libfoo.so.1")
+        );
+        assert_eq!(
+            std::fs::read_link(root.join("usr/lib/libfoo-dev.so"))?,
+            PathBuf::from("libfoo.so")
+        );
+
+        Ok(())
+    }
+}
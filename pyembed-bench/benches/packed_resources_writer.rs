@@ -0,0 +1,66 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    criterion::{criterion_group, criterion_main, Criterion},
+    pyembed_bench::{synthetic::SyntheticResourcesShape, *},
+    python_packed_resources::{write_packed_resources_v3, BlobInteriorPadding},
+};
+
+pub fn bench_packed_resources_writer(c: &mut Criterion) {
+    let paddings = [
+        ("padding_none", BlobInteriorPadding::None),
+        ("padding_null", BlobInteriorPadding::Null),
+    ];
+
+    for modules in [100, 1000, 10000] {
+        let resources = SyntheticResourcesShape {
+            packages: modules / 100,
+            package_depth: 0,
+            modules_per_package: 99,
+            resources_per_package: 10,
+            ..Default::default()
+        }
+        .resources();
+
+        for (label, padding) in paddings {
+            c.bench_function(
+                &format!(
+                    "python-packed-resources.write.synthetic_{}_modules.{}",
+                    modules, label
+                ),
+                |b| {
+                    b.iter(|| {
+                        let mut buffer = Vec::<u8>::new();
+                        write_packed_resources_v3(&resources, &mut buffer, Some(padding))
+                            .expect("failed to write packed resources");
+                    })
+                },
+            );
+        }
+    }
+
+    let (packed_resources, _) =
+        resolve_packed_resources().expect("failed to resolve packed resources");
+    let resources = python_packed_resources::load_resources(&packed_resources)
+        .expect("failed to load packed resources")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("failed to parse packed resources");
+
+    for (label, padding) in paddings {
+        c.bench_function(
+            &format!("python-packed-resources.write.stdlib.{}", label),
+            |b| {
+                b.iter(|| {
+                    let mut buffer = Vec::<u8>::new();
+                    write_packed_resources_v3(&resources, &mut buffer, Some(padding))
+                        .expect("failed to write packed resources");
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(benches, bench_packed_resources_writer);
+criterion_main!(benches);
@@ -0,0 +1,270 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Startup latency and memory usage of Python processes.
+
+Each variant runs a fresh process importing the `app` module and exiting,
+which is what users of a packaged application experience. We measure the
+wall time and peak resident set size of the process and print a table
+comparing the variants:
+
+* A virtualenv created from the system Python, with `app` installed in its
+  `site-packages`. The system Python is `python3` or the interpreter named
+  by `PYEMBED_BENCH_SYSTEM_PYTHON`.
+* An embedded interpreter importing the standard library and `app` from
+  memory via `OxidizedFinder`.
+* An embedded interpreter importing them from files relative to the
+  executable via `OxidizedFinder`.
+
+Embedded interpreters run in processes executing this benchmark binary
+again, as the interpreter can only be initialized once per process.
+
+The number of processes run per variant defaults to 20 and can be changed
+with `PYEMBED_BENCH_STARTUP_ITERATIONS`. Peak memory usage is only measured
+on UNIX.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    pyembed::{MainPythonInterpreter, PackedResourcesSource},
+    pyembed_bench::*,
+    python_packaging::location::ConcreteResourceLocation,
+    std::{
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+        time::{Duration, Instant},
+    },
+};
+
+/// Environment variable holding the origin of the embedded interpreter to run.
+///
+/// When set, this process is a child running the interpreter.
+const CHILD_ORIGIN_ENV: &str = "PYEMBED_BENCH_STARTUP_ORIGIN";
+
+/// Filename of packed resources data in the origin of embedded interpreters.
+const PACKED_RESOURCES_FILENAME: &str = "packed-resources";
+
+/// Result of running a process.
+struct Measurement {
+    wall_time: Duration,
+    /// Peak resident set size, in bytes.
+    max_rss: Option<u64>,
+}
+
+/// Run a process to completion, measuring its wall time and peak memory usage.
+#[cfg(unix)]
+fn measure(command: &mut Command) -> Result<Measurement> {
+    let start = Instant::now();
+    let child = command.stdout(Stdio::null()).spawn()?;
+
+    let mut status = 0;
+    // Safe because rusage is plain data that wait4() fills in.
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    let res = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) };
+    let wall_time = start.elapsed();
+
+    if res == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    if !libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0 {
+        return Err(anyhow!("process {:?} failed", command));
+    }
+
+    // ru_maxrss is in bytes on macOS and in kilobytes elsewhere.
+    let max_rss = if cfg!(target_os = "macos") {
+        usage.ru_maxrss as u64
+    } else {
+        usage.ru_maxrss as u64 * 1024
+    };
+
+    Ok(Measurement {
+        wall_time,
+        max_rss: Some(max_rss),
+    })
+}
+
+/// Run a process to completion, measuring its wall time.
+#[cfg(not(unix))]
+fn measure(command: &mut Command) -> Result<Measurement> {
+    let start = Instant::now();
+    let status = command.stdout(Stdio::null()).status()?;
+    let wall_time = start.elapsed();
+
+    if !status.success() {
+        return Err(anyhow!("process {:?} failed", command));
+    }
+
+    Ok(Measurement {
+        wall_time,
+        max_rss: None,
+    })
+}
+
+/// Run an embedded interpreter importing `app` from resources in an origin directory.
+fn run_child(origin: PathBuf) -> Result<i32> {
+    let mut config = default_interpreter_config();
+    config.oxidized_importer = true;
+    config
+        .packed_resources
+        .push(PackedResourcesSource::MemoryMappedPath(
+            origin.join(PACKED_RESOURCES_FILENAME),
+        ));
+    config.origin = Some(origin);
+    config.interpreter_config.run_command = Some("import app".to_string());
+
+    let interp = MainPythonInterpreter::new(config)
+        .map_err(|e| anyhow!("error creating new interpreter: {}", e.to_string()))?;
+
+    Ok(interp.run())
+}
+
+/// Create a virtualenv with `app` installed, returning the path to its `python`.
+fn create_virtualenv(system_python: &str, dest_dir: &Path) -> Result<PathBuf> {
+    let status = Command::new(system_python)
+        .args(["-m", "venv"])
+        .arg(dest_dir)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("error creating virtualenv with {}", system_python));
+    }
+
+    let python = if cfg!(windows) {
+        dest_dir.join("Scripts").join("python.exe")
+    } else {
+        dest_dir.join("bin").join("python")
+    };
+
+    let output = Command::new(&python)
+        .args([
+            "-c",
+            "import sysconfig; print(sysconfig.get_paths()['purelib'])",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!("error resolving site-packages of virtualenv"));
+    }
+
+    let site_packages = PathBuf::from(String::from_utf8(output.stdout)?.trim());
+    std::fs::write(site_packages.join("app.py"), STARTUP_APP_SOURCE)?;
+
+    Ok(python)
+}
+
+/// Write packed resources for an embedded interpreter to an origin directory.
+fn write_origin(location: &ConcreteResourceLocation, origin: &Path) -> Result<()> {
+    std::fs::create_dir_all(origin)?;
+    let data = resolve_startup_packed_resources(location, origin)?;
+    std::fs::write(origin.join(PACKED_RESOURCES_FILENAME), data)?;
+
+    Ok(())
+}
+
+/// Run a command repeatedly, after a warm-up run.
+///
+/// The warm-up run writes bytecode caches and populates the page cache, so
+/// all runs measure the same thing.
+fn run_variant(mut command: Command, iterations: usize) -> Result<Vec<Measurement>> {
+    measure(&mut command)?;
+
+    (0..iterations).map(|_| measure(&mut command)).collect()
+}
+
+fn print_table(results: &[(&str, Vec<Measurement>)]) {
+    let median = |measurements: &[Measurement]| {
+        let mut times = measurements.iter().map(|m| m.wall_time).collect::<Vec<_>>();
+        times.sort();
+        times[times.len() / 2]
+    };
+
+    let baseline = median(&results[0].1);
+
+    println!(
+        "{:<30} {:>12} {:>12} {:>12} {:>15}",
+        "variant", "median (ms)", "min (ms)", "relative", "peak RSS (MiB)"
+    );
+
+    for (name, measurements) in results {
+        let median = median(measurements);
+        let min = measurements.iter().map(|m| m.wall_time).min().unwrap();
+        let max_rss = measurements
+            .iter()
+            .filter_map(|m| m.max_rss)
+            .max()
+            .map(|rss| format!("{:.1}", rss as f64 / 1048576.0))
+            .unwrap_or_else(|| "n/a".to_string());
+
+        println!(
+            "{:<30} {:>12.2} {:>12.2} {:>11.2}x {:>15}",
+            name,
+            median.as_secs_f64() * 1000.0,
+            min.as_secs_f64() * 1000.0,
+            median.as_secs_f64() / baseline.as_secs_f64(),
+            max_rss
+        );
+    }
+}
+
+fn run_benchmarks() -> Result<()> {
+    let iterations = match std::env::var("PYEMBED_BENCH_STARTUP_ITERATIONS") {
+        Ok(value) => value.parse::<usize>()?.max(1),
+        Err(_) => 20,
+    };
+    let system_python =
+        std::env::var("PYEMBED_BENCH_SYSTEM_PYTHON").unwrap_or_else(|_| "python3".to_string());
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("pyembed-bench-startup-")
+        .tempdir()?;
+
+    let venv_python = create_virtualenv(&system_python, &temp_dir.path().join("venv"))?;
+
+    let in_memory_origin = temp_dir.path().join("in-memory");
+    write_origin(&ConcreteResourceLocation::InMemory, &in_memory_origin)?;
+
+    let filesystem_origin = temp_dir.path().join("filesystem-relative");
+    write_origin(
+        &ConcreteResourceLocation::RelativePath("lib".to_string()),
+        &filesystem_origin,
+    )?;
+
+    let exe = std::env::current_exe()?;
+
+    let mut results = vec![];
+
+    let mut command = Command::new(&venv_python);
+    command.args(["-c", "import app"]);
+    results.push(("system python + venv", run_variant(command, iterations)?));
+
+    for (name, origin) in [
+        ("oxidized in-memory", &in_memory_origin),
+        ("oxidized filesystem-relative", &filesystem_origin),
+    ] {
+        let mut command = Command::new(&exe);
+        command.env(CHILD_ORIGIN_ENV, origin);
+        results.push((name, run_variant(command, iterations)?));
+    }
+
+    println!(
+        "startup of process importing app and exiting; {} runs per variant",
+        iterations
+    );
+    print_table(&results);
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    if let Some(origin) = std::env::var_os(CHILD_ORIGIN_ENV) {
+        std::process::exit(run_child(PathBuf::from(origin))?);
+    }
+
+    // `cargo test` runs benchmarks with --bench to check they work. The
+    // startup benchmarks are too slow for that, so only run them via
+    // `cargo bench`.
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return Ok(());
+    }
+
+    run_benchmarks()
+}
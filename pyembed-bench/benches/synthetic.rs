@@ -0,0 +1,125 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    anyhow::{anyhow, Result},
+    criterion::{criterion_group, criterion_main, Criterion},
+    pyembed::PythonResourcesState,
+    pyembed_bench::{synthetic::SyntheticResourcesShape, *},
+    python_packaging::resource::BytecodeOptimizationLevel,
+};
+
+fn python_resources_state_index(data: &[u8]) -> Result<()> {
+    let mut state = PythonResourcesState::new_from_env()
+        .map_err(|e| anyhow!("error obtaining PythonResourcesState: {}", e))?;
+
+    state
+        .index_data(data)
+        .map_err(|e| anyhow!("error indexing data: {}", e))?;
+
+    Ok(())
+}
+
+pub fn bench_synthetic(c: &mut Criterion) {
+    let shapes = [
+        (
+            "small_modules",
+            SyntheticResourcesShape {
+                packages: 100,
+                package_depth: 0,
+                modules_per_package: 100,
+                module_size: 256,
+                ..Default::default()
+            },
+        ),
+        (
+            "large_modules",
+            SyntheticResourcesShape {
+                packages: 10,
+                package_depth: 0,
+                modules_per_package: 10,
+                module_size: 1048576,
+                ..Default::default()
+            },
+        ),
+        (
+            "deep_packages",
+            SyntheticResourcesShape {
+                packages: 10,
+                package_depth: 20,
+                modules_per_package: 5,
+                ..Default::default()
+            },
+        ),
+        (
+            "package_resources",
+            SyntheticResourcesShape {
+                packages: 100,
+                resources_per_package: 100,
+                ..Default::default()
+            },
+        ),
+    ];
+
+    for (label, shape) in shapes {
+        let (packed_resources, names) = shape
+            .packed_resources()
+            .expect("failed to generate packed resources");
+
+        c.bench_function(
+            &format!("synthetic.{}.PythonResourcesState.index_data", label),
+            |b| {
+                b.iter(|| {
+                    python_resources_state_index(&packed_resources).expect("failed to index data")
+                })
+            },
+        );
+
+        let mut resources_state =
+            PythonResourcesState::new_from_env().expect("failed to create resources state");
+        resources_state
+            .index_data(&packed_resources)
+            .expect("failed to index resources data");
+
+        c.bench_function(
+            &format!("synthetic.{}.PythonResourcesState.resolve_modules", label),
+            |b| {
+                b.iter(|| {
+                    for name in &names {
+                        resources_state
+                            .resolve_importable_module(name, BytecodeOptimizationLevel::Zero)
+                            .expect("failed to retrieve module");
+                    }
+                })
+            },
+        );
+
+        c.bench_function(
+            &format!("synthetic.{}.OxidizedFinder.find_spec.all_modules", label),
+            |b| {
+                b.iter_with_setup(
+                    || {
+                        get_interpreter_and_oxidized_finder(&packed_resources)
+                            .expect("failed to obtain OxidizedFinder")
+                    },
+                    |(interp, finder)| {
+                        interp.with_gil(|py| {
+                            let finder = finder.as_ref(py);
+
+                            for name in &names {
+                                finder
+                                    .call_method("find_spec", (name, py.None()), None)
+                                    .expect("find_spec call failed");
+                            }
+                        });
+                        std::mem::drop(interp);
+                    },
+                )
+            },
+        );
+    }
+}
+
+criterion_group!(benches, bench_synthetic);
+criterion_main!(benches);
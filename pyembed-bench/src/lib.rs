@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+pub mod synthetic;
+
 use {
     anyhow::{anyhow, Result},
     once_cell::sync::Lazy,
@@ -19,13 +21,28 @@ use {
     python_packaging::{
         bytecode::{BytecodeCompiler, CompileMode},
         location::{AbstractResourceLocation, ConcreteResourceLocation},
-        resource::{BytecodeOptimizationLevel, PythonResource},
+        resource::{BytecodeOptimizationLevel, PythonModuleSource, PythonResource},
         resource_collection::PythonResourceCollector,
     },
     slog::{Drain, Logger},
     std::{path::Path, sync::Arc},
+    tugger_file_manifest::FileData,
 };
 
+/// Source code of the `app` module imported by startup benchmarks.
+///
+/// It imports a representative set of standard library modules.
+pub const STARTUP_APP_SOURCE: &str = "\
+import argparse
+import collections
+import email.message
+import http.client
+import json
+import logging
+import pathlib
+import urllib.parse
+";
+
 static ENVIRONMENT: Lazy<Environment> =
     Lazy::new(|| Environment::new().expect("error spawning global environment"));
 
@@ -150,27 +167,65 @@ pub fn get_interpreter_and_oxidized_finder<'interpreter, 'resources>(
 }
 
 pub fn resolve_packed_resources() -> Result<(Vec<u8>, Vec<String>)> {
+    resolve_packed_resources_with_location(&ConcreteResourceLocation::InMemory, false, None)
+}
+
+/// Resolve packed resources data for the standard library and the `app` module.
+///
+/// Resources are placed in `location`. Files backing filesystem-relative
+/// resources are written to `origin`, which must be the origin of the
+/// interpreter loading the resources.
+pub fn resolve_startup_packed_resources(
+    location: &ConcreteResourceLocation,
+    origin: &Path,
+) -> Result<Vec<u8>> {
+    let (data, _) = resolve_packed_resources_with_location(location, true, Some(origin))?;
+
+    Ok(data)
+}
+
+fn resolve_packed_resources_with_location(
+    location: &ConcreteResourceLocation,
+    add_app: bool,
+    origin: Option<&Path>,
+) -> Result<(Vec<u8>, Vec<String>)> {
     let dist = get_python_distribution()?;
 
     let mut collector = PythonResourceCollector::new(
-        vec![AbstractResourceLocation::InMemory],
+        vec![location.into()],
         vec![AbstractResourceLocation::InMemory],
         false,
         true,
     );
 
-    for resource in dist.python_resources().into_iter() {
-        if let PythonResource::ModuleSource(source) = resource {
-            if source.name.contains("test") {
-                continue;
+    let mut sources = dist
+        .python_resources()
+        .into_iter()
+        .filter_map(|resource| match resource {
+            PythonResource::ModuleSource(source) if !source.name.contains("test") => {
+                Some(source.into_owned())
             }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if add_app {
+        sources.push(PythonModuleSource {
+            name: "app".to_string(),
+            source: FileData::Memory(STARTUP_APP_SOURCE.as_bytes().to_vec()),
+            is_package: false,
+            cache_tag: dist.cache_tag().to_string(),
+            is_stdlib: false,
+            is_test: false,
+        });
+    }
 
-            collector.add_python_module_source(&source, &ConcreteResourceLocation::InMemory)?;
-            collector.add_python_module_bytecode_from_source(
-                &source.as_bytecode_module(BytecodeOptimizationLevel::Zero),
-                &ConcreteResourceLocation::InMemory,
-            )?;
-        }
+    for source in sources {
+        collector.add_python_module_source(&source, location)?;
+        collector.add_python_module_bytecode_from_source(
+            &source.as_bytecode_module(BytecodeOptimizationLevel::Zero),
+            location,
+        )?;
     }
 
     let temp_dir = tempfile::Builder::new()
@@ -180,6 +235,19 @@ pub fn resolve_packed_resources() -> Result<(Vec<u8>, Vec<String>)> {
 
     let compiled = collector.compile_resources(&mut compiler)?;
 
+    if let Some(origin) = origin {
+        for (path, data, _) in &compiled.extra_files {
+            let path = origin.join(path);
+            let parent = path
+                .parent()
+                .ok_or_else(|| anyhow!("unable to resolve parent path"))?;
+            std::fs::create_dir_all(parent)?;
+            std::fs::write(&path, data.resolve_content()?)?;
+        }
+    } else if !compiled.extra_files.is_empty() {
+        return Err(anyhow!("resources require files to be installed"));
+    }
+
     let mut buffer = Vec::<u8>::new();
     compiled.write_packed_resources(&mut buffer)?;
 
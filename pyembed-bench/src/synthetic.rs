@@ -0,0 +1,199 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Synthetic packed resources data.
+
+Resources derived from a Python distribution depend on which distribution
+is used. Synthetic resources have a shape defined by the benchmark, so
+indexing and importing can be measured for e.g. many small modules or
+deeply nested packages, and compared across runs on any machine.
+
+Modules only have source code, which is valid Python, so they can be
+imported without a Python distribution to compile bytecode with.
+*/
+
+use {
+    anyhow::Result,
+    python_packed_resources::Resource,
+    std::{borrow::Cow, collections::HashMap},
+};
+
+/// Describes the shape of synthetic packed resources data.
+#[derive(Clone, Debug)]
+pub struct SyntheticResourcesShape {
+    /// Number of top-level packages.
+    pub packages: usize,
+
+    /// Number of packages nested below each top-level package.
+    ///
+    /// Each nested package is a sub-package of the previous one. e.g. a
+    /// depth of 2 yields `pkg0`, `pkg0.sub1` and `pkg0.sub1.sub2`.
+    pub package_depth: usize,
+
+    /// Number of modules in each package, excluding the package itself.
+    pub modules_per_package: usize,
+
+    /// Size in bytes of the source code of each module.
+    ///
+    /// Source code is padded with comments to reach this size.
+    pub module_size: usize,
+
+    /// Number of resource files in each package.
+    pub resources_per_package: usize,
+
+    /// Size in bytes of each resource file.
+    pub resource_size: usize,
+}
+
+impl Default for SyntheticResourcesShape {
+    fn default() -> Self {
+        Self {
+            packages: 10,
+            package_depth: 2,
+            modules_per_package: 10,
+            module_size: 4096,
+            resources_per_package: 0,
+            resource_size: 1024,
+        }
+    }
+}
+
+impl SyntheticResourcesShape {
+    /// Names of all packages, top-level packages first.
+    fn package_names(&self) -> Vec<String> {
+        let mut names = vec![];
+
+        for depth in 0..=self.package_depth {
+            for package in 0..self.packages {
+                let mut name = format!("pkg{}", package);
+                for level in 1..=depth {
+                    name.push_str(&format!(".sub{}", level));
+                }
+                names.push(name);
+            }
+        }
+
+        names
+    }
+
+    /// Generate Python source code for a module.
+    fn module_source(&self, name: &str) -> Vec<u8> {
+        let mut source = format!("NAME = {:?}\n", name);
+
+        while source.len() < self.module_size {
+            let remaining = self.module_size - source.len();
+            let line = "# synthetic module padding\n";
+            if remaining >= line.len() {
+                source.push_str(line);
+            } else {
+                source.push_str(&"#".repeat(remaining - 1));
+                source.push('\n');
+            }
+        }
+
+        source.into_bytes()
+    }
+
+    /// Generate resources of this shape.
+    pub fn resources(&self) -> Vec<Resource<'static, u8>> {
+        let mut resources = vec![];
+
+        for package in self.package_names() {
+            let package_resources = if self.resources_per_package > 0 {
+                Some(
+                    (0..self.resources_per_package)
+                        .map(|i| {
+                            (
+                                Cow::Owned(format!("data{}.bin", i)),
+                                Cow::Owned(vec![b'x'; self.resource_size]),
+                            )
+                        })
+                        .collect::<HashMap<_, _>>(),
+                )
+            } else {
+                None
+            };
+
+            resources.push(Resource {
+                name: Cow::Owned(package.clone()),
+                is_python_module: true,
+                is_python_package: true,
+                in_memory_source: Some(Cow::Owned(self.module_source(&package))),
+                in_memory_package_resources: package_resources,
+                ..Resource::default()
+            });
+
+            for module in 0..self.modules_per_package {
+                let name = format!("{}.mod{}", package, module);
+
+                resources.push(Resource {
+                    in_memory_source: Some(Cow::Owned(self.module_source(&name))),
+                    name: Cow::Owned(name),
+                    is_python_module: true,
+                    ..Resource::default()
+                });
+            }
+        }
+
+        resources
+    }
+
+    /// Generate packed resources data of this shape.
+    ///
+    /// Returns the data and the names of all modules in it.
+    pub fn packed_resources(&self) -> Result<(Vec<u8>, Vec<String>)> {
+        let resources = self.resources();
+
+        let mut buffer = Vec::<u8>::new();
+        python_packed_resources::write_packed_resources_v4(&resources, &mut buffer, None)?;
+
+        let names = resources
+            .into_iter()
+            .map(|resource| resource.name.into_owned())
+            .collect::<Vec<_>>();
+
+        Ok((buffer, names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_resources() -> Result<()> {
+        let shape = SyntheticResourcesShape {
+            packages: 2,
+            package_depth: 1,
+            modules_per_package: 3,
+            module_size: 100,
+            resources_per_package: 2,
+            resource_size: 10,
+        };
+
+        let (data, names) = shape.packed_resources()?;
+
+        // 2 top-level packages + 2 sub-packages, each with 3 modules.
+        assert_eq!(names.len(), 16);
+        assert!(names.contains(&"pkg1.sub1.mod2".to_string()));
+
+        let resources = python_packed_resources::load_resources(&data)
+            .map_err(anyhow::Error::msg)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(anyhow::Error::msg)?;
+        assert_eq!(resources.len(), 16);
+
+        for resource in resources {
+            assert_eq!(resource.in_memory_source.unwrap().len(), 100);
+
+            if resource.is_python_package {
+                let package_resources = resource.in_memory_package_resources.unwrap();
+                assert_eq!(package_resources.len(), 2);
+                assert!(package_resources.values().all(|data| data.len() == 10));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Typed Rust facades over Python functions.
+
+Calling Python functions from Rust involves importing their module,
+converting arguments to Python objects, calling the function and converting
+the result back, handling Python exceptions at each step. The
+[crate::export_api] macro generates this code from Rust signatures of the
+functions:
+
+```rust,no_run
+pyembed::export_api! {
+    /// Functions of the `myapp.api` Python module.
+    pub struct MyAppApi("myapp.api") {
+        /// Compute the total price of items.
+        fn total(prices: Vec<f64>, discount: f64) -> f64;
+        fn greet(name: &str) -> String;
+        fn reset();
+    }
+}
+
+# fn example(interp: &pyembed::MainPythonInterpreter<'_, '_>) -> Result<(), pyembed::ApiError> {
+interp.with_gil(|py| {
+    let api = MyAppApi::import(py)?;
+    let total = api.total(vec![1.0, 2.5], 0.1)?;
+    api.reset()
+})
+# }
+```
+
+Arguments must implement `pyo3::IntoPy<pyo3::PyObject>` and return types
+`pyo3::FromPyObject`. Functions without a return type discard the value
+returned by Python. Failures are reported as [ApiError].
+*/
+
+use {
+    pyo3::{
+        prelude::*,
+        types::{PyModule, PyTuple},
+    },
+    std::fmt::{Display, Formatter},
+};
+
+#[doc(hidden)]
+pub use pyo3 as __pyo3;
+
+/// Error calling a Python function via a facade generated by [crate::export_api].
+#[derive(Clone, Debug)]
+pub enum ApiError {
+    /// The Python module couldn't be imported.
+    Import {
+        /// Name of the module.
+        module: String,
+        /// Description of the exception raised by the import.
+        error: String,
+    },
+
+    /// The Python function raised an exception.
+    Exception {
+        /// Name of the function.
+        function: String,
+        /// Type name of the exception, e.g. `ValueError`.
+        exception: String,
+        /// Message of the exception.
+        message: String,
+        /// Formatted traceback of the exception, if available.
+        traceback: Option<String>,
+    },
+
+    /// The value returned by the Python function couldn't be converted.
+    Conversion {
+        /// Name of the function.
+        function: String,
+        /// Description of the conversion error.
+        error: String,
+    },
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Import { module, error } => write!(f, "unable to import {}: {}", module, error),
+            Self::Exception {
+                function,
+                exception,
+                message,
+                ..
+            } => write!(f, "{} raised {}: {}", function, exception, message),
+            Self::Conversion { function, error } => {
+                write!(
+                    f,
+                    "unable to convert value returned by {}: {}",
+                    function, error
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Describe an exception as its type name and message.
+fn describe_exception(py: Python, err: &PyErr) -> (String, String) {
+    let exception = err
+        .ptype(py)
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|_| "exception".to_string());
+
+    (exception, err.pvalue(py).to_string())
+}
+
+/// Format the traceback of an exception.
+fn format_traceback(py: Python, err: &PyErr) -> Option<String> {
+    let traceback = err.ptraceback(py)?;
+
+    py.import("traceback")
+        .and_then(|module| module.call_method1("format_tb", (traceback,)))
+        .and_then(|lines| lines.extract::<Vec<String>>())
+        .map(|lines| lines.concat())
+        .ok()
+}
+
+#[doc(hidden)]
+pub fn import_module<'py>(py: Python<'py>, module: &str) -> Result<&'py PyModule, ApiError> {
+    py.import(module).map_err(|e| {
+        let (exception, message) = describe_exception(py, &e);
+
+        ApiError::Import {
+            module: module.to_string(),
+            error: format!("{}: {}", exception, message),
+        }
+    })
+}
+
+#[doc(hidden)]
+pub fn call_function<'py>(
+    module: &'py PyModule,
+    function: &str,
+    args: Vec<PyObject>,
+) -> Result<&'py PyAny, ApiError> {
+    let py = module.py();
+
+    module
+        .getattr(function)
+        .and_then(|callable| callable.call1(PyTuple::new(py, args)))
+        .map_err(|e| {
+            let (exception, message) = describe_exception(py, &e);
+
+            ApiError::Exception {
+                function: function.to_string(),
+                exception,
+                message,
+                traceback: format_traceback(py, &e),
+            }
+        })
+}
+
+#[doc(hidden)]
+pub fn extract_result<'py, T: FromPyObject<'py>>(
+    function: &str,
+    value: &'py PyAny,
+) -> Result<T, ApiError> {
+    value.extract().map_err(|e| ApiError::Conversion {
+        function: function.to_string(),
+        error: e.pvalue(value.py()).to_string(),
+    })
+}
+
+/// Generate a typed Rust facade over functions of a Python module.
+///
+/// The macro defines a struct wrapping the module, with an `import()`
+/// constructor and a method calling each function. Methods return
+/// `Result<T, ApiError>`. See the [crate::api] module for an example.
+#[macro_export]
+macro_rules! export_api {
+    (@return) => { () };
+    (@return $ret:ty) => { $ret };
+    (@extract $function:expr, $result:ident) => {{
+        let _ = $result;
+        Ok(())
+    }};
+    (@extract $function:expr, $result:ident, $ret:ty) => {
+        $crate::api::extract_result::<$ret>($function, $result)
+    };
+    (
+        $(#[$struct_meta:meta])*
+        $vis:vis struct $name:ident($module:literal) {
+            $(
+                $(#[$fn_meta:meta])*
+                fn $function:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$struct_meta])*
+        $vis struct $name<'py> {
+            module: &'py $crate::api::__pyo3::types::PyModule,
+        }
+
+        impl<'py> $name<'py> {
+            /// Import the Python module providing the functions.
+            $vis fn import(
+                py: $crate::api::__pyo3::Python<'py>,
+            ) -> Result<Self, $crate::ApiError> {
+                Ok(Self {
+                    module: $crate::api::import_module(py, $module)?,
+                })
+            }
+
+            $(
+                $(#[$fn_meta])*
+                $vis fn $function(
+                    &self,
+                    $($arg: $arg_ty),*
+                ) -> Result<$crate::export_api!(@return $($ret)?), $crate::ApiError> {
+                    #[allow(unused_imports)]
+                    use $crate::api::__pyo3::IntoPy;
+
+                    #[allow(unused_variables)]
+                    let py = self.module.py();
+                    let args: Vec<$crate::api::__pyo3::PyObject> = vec![$($arg.into_py(py)),*];
+                    let result =
+                        $crate::api::call_function(self.module, stringify!($function), args)?;
+
+                    $crate::export_api!(@extract stringify!($function), result $(, $ret)?)
+                }
+            )*
+        }
+    };
+}
@@ -0,0 +1,313 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Running Python coroutines alongside a tokio runtime.
+
+[AsyncioBridge] runs an asyncio event loop in a dedicated thread, so Rust
+services built on tokio and Python code built on asyncio can await each
+other:
+
+* [AsyncioBridge::run_coroutine] schedules a Python coroutine on the event
+  loop and returns a Rust future resolving to its result.
+* [AsyncioBridge::register] makes a Rust async function awaitable from
+  Python via `oxidized_runtime.asyncio.call()`. It runs on the tokio
+  runtime passed to [AsyncioBridge::start].
+
+Neither side polls the other. Completion of a Python future wakes the Rust
+task awaiting it and completion of a Rust future schedules the resolution of
+the asyncio future on the event loop.
+
+e.g.
+
+```rust,no_run
+use pyembed::{asyncio_bridge::AsyncioBridge, MainPythonInterpreter};
+use pyo3::prelude::*;
+
+# async fn example(interp: &MainPythonInterpreter<'_, '_>) -> PyResult<()> {
+let bridge = interp.with_gil(|py| {
+    AsyncioBridge::start(py, tokio::runtime::Handle::current())
+})?;
+
+bridge.register("fetch", |args| async move {
+    Python::with_gil(|py| Ok(args.as_ref(py).len().into_py(py)))
+});
+
+let result = interp
+    .with_gil(|py| {
+        let module = py.import("myapp")?;
+        bridge.run_coroutine(py, module.call_method0("main")?)
+    })?
+    .await?;
+# Ok(())
+# }
+```
+
+```python
+import oxidized_runtime.asyncio
+
+async def main():
+    return await oxidized_runtime.asyncio.call("fetch", "https://example.com")
+```
+*/
+
+use {
+    pyo3::{
+        exceptions::{PyKeyError, PyRuntimeError},
+        prelude::*,
+        types::{PyModule, PyTuple},
+    },
+    std::{
+        collections::HashMap,
+        future::Future,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+    },
+    tokio::{runtime::Handle, sync::oneshot},
+};
+
+/// Python code of the `oxidized_runtime.asyncio` module.
+const ASYNCIO_CODE: &str = "\
+import asyncio
+
+_bridge = None
+
+
+def call(name, *args):
+    '''Call a Rust async function registered by the application.
+
+    Returns an awaitable resolving to the result of the function.'''
+    if _bridge is None:
+        raise RuntimeError('asyncio bridge is not running')
+    loop = asyncio.get_running_loop()
+    future = loop.create_future()
+    _bridge.spawn(name, args, loop, future)
+    return future
+
+
+def _resolve(future, result, error):
+    if future.cancelled():
+        return
+    if error is not None:
+        future.set_exception(error)
+    else:
+        future.set_result(result)
+
+
+def _run(loop):
+    asyncio.set_event_loop(loop)
+    try:
+        loop.run_forever()
+    finally:
+        try:
+            loop.run_until_complete(loop.shutdown_asyncgens())
+        finally:
+            loop.close()
+";
+
+type AsyncFunction = Arc<
+    dyn Fn(Py<PyTuple>) -> Pin<Box<dyn Future<Output = PyResult<PyObject>> + Send>> + Send + Sync,
+>;
+
+/// State shared with the `oxidized_runtime.asyncio` module.
+#[pyclass(module = "oxidized_runtime.asyncio")]
+struct BridgeState {
+    runtime: Handle,
+    functions: Arc<Mutex<HashMap<String, AsyncFunction>>>,
+}
+
+#[pymethods]
+impl BridgeState {
+    /// Run a registered function on the tokio runtime and resolve `future` with its result.
+    fn spawn(
+        &self,
+        py: Python,
+        name: &str,
+        args: Py<PyTuple>,
+        event_loop: PyObject,
+        future: PyObject,
+    ) -> PyResult<()> {
+        let function = self
+            .functions
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| PyKeyError::new_err(format!("no Rust function named {}", name)))?;
+
+        let resolve: PyObject = py
+            .import("oxidized_runtime.asyncio")?
+            .getattr("_resolve")?
+            .into();
+        let task = function(args);
+
+        self.runtime.spawn(async move {
+            let result = task.await;
+
+            Python::with_gil(|py| {
+                let (value, error) = match result {
+                    Ok(value) => (value, py.None()),
+                    Err(e) => (py.None(), e.into_py(py)),
+                };
+
+                // Fails if the event loop was closed, in which case nobody
+                // awaits the result anymore.
+                let _ = event_loop.call_method1(
+                    py,
+                    "call_soon_threadsafe",
+                    (resolve, future, value, error),
+                );
+            });
+        });
+
+        Ok(())
+    }
+}
+
+/// Completes a Rust future once a `concurrent.futures.Future` is done.
+#[pyclass(module = "oxidized_runtime.asyncio")]
+struct FutureWaker {
+    sender: Option<oneshot::Sender<PyResult<PyObject>>>,
+}
+
+#[pymethods]
+impl FutureWaker {
+    fn done(&mut self, future: &PyAny) {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(future.call_method0("result").map(|value| value.into()));
+        }
+    }
+}
+
+/// An asyncio event loop running in a dedicated thread.
+///
+/// Only one bridge should exist per interpreter, as the
+/// `oxidized_runtime.asyncio` module refers to the last one started.
+/// Dropping the bridge stops the event loop and waits for its thread.
+pub struct AsyncioBridge {
+    event_loop: PyObject,
+    functions: Arc<Mutex<HashMap<String, AsyncFunction>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncioBridge {
+    /// Start an event loop in a new thread.
+    ///
+    /// Rust functions called from Python are spawned on `runtime`.
+    pub fn start(py: Python, runtime: Handle) -> PyResult<Self> {
+        let module = PyModule::from_code(
+            py,
+            ASYNCIO_CODE,
+            "oxidized_runtime/asyncio.py",
+            "oxidized_runtime.asyncio",
+        )?;
+        if let Ok(parent) = py.import("oxidized_runtime") {
+            parent.setattr("asyncio", module)?;
+        }
+
+        let functions = Arc::new(Mutex::new(HashMap::new()));
+        let state = Py::new(
+            py,
+            BridgeState {
+                runtime,
+                functions: functions.clone(),
+            },
+        )?;
+        module.setattr("_bridge", state)?;
+
+        let event_loop: PyObject = py.import("asyncio")?.call_method0("new_event_loop")?.into();
+        let run: PyObject = module.getattr("_run")?.into();
+        let thread_loop = event_loop.clone_ref(py);
+
+        let thread = std::thread::Builder::new()
+            .name("asyncio".to_string())
+            .spawn(move || {
+                Python::with_gil(|py| {
+                    if let Err(e) = run.call1(py, (thread_loop,)) {
+                        e.print(py);
+                    }
+                })
+            })
+            .map_err(|e| {
+                PyRuntimeError::new_err(format!("error starting asyncio thread: {}", e))
+            })?;
+
+        Ok(Self {
+            event_loop,
+            functions,
+            thread: Some(thread),
+        })
+    }
+
+    /// The event loop running Python coroutines.
+    pub fn event_loop(&self) -> &PyObject {
+        &self.event_loop
+    }
+
+    /// Make an async function callable from Python.
+    ///
+    /// Python code calls it with `await oxidized_runtime.asyncio.call(name, *args)`.
+    /// The function receives the arguments as a tuple.
+    pub fn register<F, Fut>(&self, name: &str, function: F)
+    where
+        F: Fn(Py<PyTuple>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PyResult<PyObject>> + Send + 'static,
+    {
+        let function: AsyncFunction = Arc::new(move |args| Box::pin(function(args)));
+
+        self.functions
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), function);
+    }
+
+    /// Schedule a coroutine on the event loop.
+    ///
+    /// The returned future resolves to the result of the coroutine. It
+    /// doesn't hold the GIL while it is pending.
+    pub fn run_coroutine(
+        &self,
+        py: Python,
+        coroutine: &PyAny,
+    ) -> PyResult<impl Future<Output = PyResult<PyObject>>> {
+        let (sender, receiver) = oneshot::channel();
+
+        let future = py.import("asyncio")?.call_method1(
+            "run_coroutine_threadsafe",
+            (coroutine, self.event_loop.as_ref(py)),
+        )?;
+        let waker = Py::new(
+            py,
+            FutureWaker {
+                sender: Some(sender),
+            },
+        )?;
+        future.call_method1("add_done_callback", (waker.getattr(py, "done")?,))?;
+
+        Ok(async move {
+            receiver
+                .await
+                .unwrap_or_else(|_| Err(PyRuntimeError::new_err("asyncio event loop stopped")))
+        })
+    }
+}
+
+impl Drop for AsyncioBridge {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            Python::with_gil(|py| {
+                let stopped = self.event_loop.getattr(py, "stop").and_then(|stop| {
+                    self.event_loop
+                        .call_method1(py, "call_soon_threadsafe", (stop,))
+                });
+
+                if stopped.is_ok() {
+                    py.allow_threads(|| {
+                        let _ = thread.join();
+                    });
+                }
+            });
+        }
+    }
+}
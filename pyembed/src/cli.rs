@@ -0,0 +1,122 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Emulation of the `python` command line interface.
+
+Libraries often run Python code in new processes by executing
+`sys.executable` with `-c <code>` or `-m <module>`. In an executable
+embedding Python, `sys.executable` is the executable, which would run the
+application instead.
+
+Arguments are parsed like `python` would: interpreter flags, optionally
+combined like `-Es`, followed by `-c <code>` or `-m <module>`. Interpreter
+flags are accepted but ignored: the interpreter configuration of the
+executable applies. Any other argument means the process wasn't invoked like
+`python`.
+*/
+
+use std::ffi::OsString;
+
+/// Code or module to run, as given to `python -c` or `python -m`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum PythonCliTarget {
+    Command(String),
+    Module(String),
+}
+
+/// Interpreter flags not taking a value.
+const FLAGS: &str = "bBdEIOqsSuvx";
+
+/// Parse process arguments like `python` would.
+///
+/// Returns the code or module to run and the arguments following it if
+/// arguments are `[interpreter flags] -c <code> [args]` or
+/// `[interpreter flags] -m <module> [args]`.
+pub(crate) fn parse_python_cli_args(argv: &[OsString]) -> Option<(PythonCliTarget, Vec<OsString>)> {
+    let mut args = argv.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let flags = arg
+            .to_str()?
+            .strip_prefix('-')
+            .filter(|flags| !flags.is_empty() && !flags.starts_with('-'))?;
+
+        for (i, flag) in flags.char_indices() {
+            match flag {
+                'c' | 'm' | 'W' | 'X' => {
+                    // The value is the remainder of the argument or the next argument.
+                    let rest = &flags[i + flag.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        args.next()?.to_str()?.to_string()
+                    } else {
+                        rest.to_string()
+                    };
+
+                    let target = match flag {
+                        'c' => PythonCliTarget::Command(value),
+                        'm' => PythonCliTarget::Module(value),
+                        _ => break,
+                    };
+
+                    return Some((target, args.cloned().collect()));
+                }
+                flag if FLAGS.contains(flag) => {}
+                _ => return None,
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<OsString> {
+        values.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_parse_python_cli_args() {
+        assert_eq!(
+            parse_python_cli_args(&args(&["myapp", "-c", "import os", "foo"])),
+            Some((
+                PythonCliTarget::Command("import os".to_string()),
+                args(&["foo"])
+            ))
+        );
+        assert_eq!(
+            parse_python_cli_args(&args(&["myapp", "-cimport os"])),
+            Some((PythonCliTarget::Command("import os".to_string()), vec![]))
+        );
+        assert_eq!(
+            parse_python_cli_args(&args(&["myapp", "-m", "pip", "-c", "foo"])),
+            Some((
+                PythonCliTarget::Module("pip".to_string()),
+                args(&["-c", "foo"])
+            ))
+        );
+        assert_eq!(
+            parse_python_cli_args(&args(&[
+                "myapp", "-Es", "-W", "ignore", "-Xdev", "-Bm", "pip"
+            ])),
+            Some((PythonCliTarget::Module("pip".to_string()), vec![]))
+        );
+        assert_eq!(parse_python_cli_args(&args(&["myapp"])), None);
+        assert_eq!(parse_python_cli_args(&args(&["myapp", "-E"])), None);
+        assert_eq!(parse_python_cli_args(&args(&["myapp", "-c"])), None);
+        assert_eq!(parse_python_cli_args(&args(&["myapp", "foo.py"])), None);
+        assert_eq!(parse_python_cli_args(&args(&["myapp", "-h"])), None);
+        assert_eq!(parse_python_cli_args(&args(&["myapp", "-"])), None);
+        assert_eq!(
+            parse_python_cli_args(&args(&["myapp", "--multiprocessing-fork"])),
+            None
+        );
+        assert_eq!(
+            parse_python_cli_args(&args(&["myapp", "--", "-c", "foo"])),
+            None
+        );
+    }
+}
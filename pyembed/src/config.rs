@@ -5,17 +5,27 @@
 //! Data structures for configuring a Python interpreter.
 
 use {
-    crate::NewInterpreterError,
+    crate::{
+        cli::{parse_python_cli_args, PythonCliTarget},
+        osutils::resolve_user_data_dir,
+        NewInterpreterError,
+    },
     oxidized_importer::{PackedResourcesSource, PythonResourcesState},
     pyo3::ffi as pyffi,
-    python_packaging::interpreter::{
-        MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
-        PythonInterpreterProfile, TerminfoResolution,
+    python_packaging::{
+        interpreter::{
+            EnvironmentMutation, MemoryAllocatorBackend, MultiprocessingStartMethod,
+            PythonInterpreterConfig, PythonInterpreterProfile, TerminfoResolution,
+            WorkingDirectoryPolicy,
+        },
+        resource::BytecodeOptimizationLevel,
     },
     std::{
         ffi::{CString, OsString},
         ops::Deref,
-        path::PathBuf,
+        path::{Component, Path, PathBuf},
+        sync::Arc,
+        time::Duration,
     },
 };
 
@@ -35,6 +45,57 @@ pub struct ExtensionModule {
     pub init_func: unsafe extern "C" fn() -> *mut pyffi::PyObject,
 }
 
+/// Function receiving output written to a captured standard stream.
+///
+/// The function is called from a background thread with chunks of output,
+/// which aren't necessarily split at line boundaries.
+pub type OutputCallback = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+/// Destination of output written to a standard stream.
+#[derive(Clone)]
+pub enum OutputCapture {
+    /// Pass output to a function.
+    Callback(OutputCallback),
+
+    /// Append output to a file.
+    File {
+        /// Path of the file.
+        ///
+        /// Relative paths are resolved against the per-user log directory of
+        /// the application. Parent directories are created if missing.
+        path: PathBuf,
+
+        /// Size in bytes after which the file is rotated.
+        ///
+        /// If [None], the file is never rotated.
+        max_size: Option<u64>,
+
+        /// Number of rotated files to keep.
+        ///
+        /// Rotated files are named after the file with a `.1`, `.2`, etc
+        /// suffix, `.1` being the most recent.
+        backups: usize,
+    },
+}
+
+impl std::fmt::Debug for OutputCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Callback(_) => f.write_str("Callback(..)"),
+            Self::File {
+                path,
+                max_size,
+                backups,
+            } => f
+                .debug_struct("File")
+                .field("path", path)
+                .field("max_size", max_size)
+                .field("backups", backups)
+                .finish(),
+        }
+    }
+}
+
 /// Configuration for a Python interpreter.
 ///
 /// This type is used to create a [crate::MainPythonInterpreter], which manages
@@ -269,7 +330,10 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     ///
     /// [Self::resolve()] behavior: [PackedResourcesSource::MemoryMappedPath] members
     /// have the special string `$ORIGIN` expanded to the string value that
-    /// [Self::origin] resolves to.
+    /// [Self::origin] resolves to. `.` and `..` components of paths beginning
+    /// with `$ORIGIN` are then resolved lexically. This allows multiple
+    /// executables in different directories to reference a shared file,
+    /// e.g. `$ORIGIN/../lib/packed-resources`.
     #[cfg_attr(feature = "serialization", serde(skip))]
     pub packed_resources: Vec<PackedResourcesSource<'a>>,
 
@@ -314,7 +378,9 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// If set, [crate::MainPythonInterpreter::run()] will detect when the invoked
     /// interpreter looks like it is supposed to be a `multiprocessing` worker and
     /// will automatically call into the `multiprocessing` module instead of running
-    /// the configured code.
+    /// the configured code. Likewise, helper processes `multiprocessing` runs via
+    /// `-c <code>`, like the resource tracker, are run when the code is exactly
+    /// what `multiprocessing` generates.
     ///
     /// Default value: [true]
     pub multiprocessing_auto_dispatch: bool,
@@ -374,6 +440,20 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     /// variable will be set for the current process.
     pub tcl_library: Option<PathBuf>,
 
+    /// Path to use to define the `TK_LIBRARY` environment variable.
+    ///
+    /// This directory should contain a `tk.tcl` file. It is commonly
+    /// a directory named `tkX.Y`. e.g. `tk8.6`.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, the `TK_LIBRARY` environment
+    /// variable will be set for the current process.
+    pub tk_library: Option<PathBuf>,
+
     /// Environment variable holding the directory to write a loaded modules file.
     ///
     /// If this value is set and the environment it refers to is set,
@@ -383,6 +463,473 @@ pub struct OxidizedPythonInterpreterConfig<'a> {
     ///
     /// Default value: [None]
     pub write_modules_directory_env: Option<String>,
+
+    /// Whether to honor runtime overrides of `sys.path` in development builds.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: if [true] and this crate is built
+    /// with debug assertions, as it is by Cargo's `dev` profile, paths in the
+    /// environment variable named by
+    /// [ResolvedOxidizedPythonInterpreterConfig::runtime_path_overrides_env()]
+    /// are prepended to `sys.path`. A finder searching these paths is installed
+    /// at the front of `sys.meta_path`, so modules in these paths take precedence
+    /// over packed resources. Builds without debug assertions ignore the
+    /// environment variable.
+    pub allow_runtime_path_overrides: bool,
+
+    /// File `faulthandler` writes tracebacks to.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, `faulthandler` is enabled
+    /// for all threads, appending tracebacks to this file instead of writing
+    /// them to `sys.stderr`. This takes effect regardless of
+    /// [PythonInterpreterConfig::fault_handler] and of environment variables.
+    pub fault_handler_path: Option<PathBuf>,
+
+    /// Number of frames `tracemalloc` stores in a traceback.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if set, `PyConfig.tracemalloc`
+    /// is set to this value, overriding [PythonInterpreterConfig::tracemalloc].
+    /// `0` disables `tracemalloc`. This is equivalent to `-X tracemalloc=N`.
+    pub tracemalloc_frames: Option<u32>,
+
+    /// Directory to write crash reports to.
+    ///
+    /// Requires the `crash-reporting` feature.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, handlers for fatal signals
+    /// (on Unix) or unhandled exceptions (on Windows) are installed and Python's
+    /// `faulthandler` is enabled. The directory is created if missing. When the
+    /// process crashes, the raw instruction pointers of the crashing thread and the
+    /// Python stacks are written to the directory. The next time the executable
+    /// starts, they are symbolized into a JSON report.
+    pub crash_reports_directory: Option<PathBuf>,
+
+    /// URL to upload crash reports to.
+    ///
+    /// Only used if [Self::crash_reports_directory] is set.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if set, crash reports from
+    /// previous runs are uploaded from a background thread via HTTP `POST`
+    /// requests. Uploaded reports are deleted.
+    pub crash_reports_upload_url: Option<String>,
+
+    /// URL of a JSON feed to check for updates of the executable.
+    ///
+    /// Requires the `updater` feature.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if set, a background thread
+    /// fetches the feed. If it lists a newer release of [Self::update_channel]
+    /// for the running platform, the release is downloaded, its signature is
+    /// verified against [Self::update_public_key] and it replaces the
+    /// executable, taking effect the next time the application starts.
+    /// Failures are ignored. See the `updater` module for the feed format.
+    pub update_feed_url: Option<String>,
+
+    /// Name of the channel in the update feed to follow.
+    ///
+    /// Only used if [Self::update_feed_url] is set.
+    ///
+    /// Default value: [None], which follows the `stable` channel.
+    pub update_channel: Option<String>,
+
+    /// Hex encoded Ed25519 public key updates must be signed with.
+    ///
+    /// Must be set if [Self::update_feed_url] is set.
+    ///
+    /// Default value: [None]
+    pub update_public_key: Option<String>,
+
+    /// Version of the running executable, for finding newer releases.
+    ///
+    /// Must be set if [Self::update_feed_url] is set. Versions are of the
+    /// form `major.minor.patch[-prerelease]`.
+    ///
+    /// Default value: [None]
+    pub update_current_version: Option<String>,
+
+    /// Whether to run as a daemon.
+    ///
+    /// Requires the `daemon` feature. Only supported on Unix.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: if [true], the process forks twice
+    /// before the interpreter is initialized, detaching from its terminal and
+    /// session. The original process exits once the daemon has written
+    /// [Self::pid_file] and redirected its standard streams. The daemon's
+    /// working directory is `/` and standard input is read from `/dev/null`.
+    /// Standard output and error are written to `/dev/null` unless
+    /// [Self::stdout_path] or [Self::stderr_path] are set.
+    pub daemonize: bool,
+
+    /// File to write the process ID to.
+    ///
+    /// Requires the `daemon` feature. Only supported on Unix.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, the file is locked and the
+    /// process ID is written to it before the interpreter is initialized.
+    /// Initialization fails if another process holds the lock. The file is
+    /// removed when the interpreter is dropped.
+    pub pid_file: Option<PathBuf>,
+
+    /// File standard output is appended to.
+    ///
+    /// Requires the `daemon` feature. Only supported on Unix.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, the standard output file
+    /// descriptor is redirected to this file before the interpreter is
+    /// initialized, so output of Python and native code is captured.
+    pub stdout_path: Option<PathBuf>,
+
+    /// File standard error is appended to.
+    ///
+    /// Behaves like [Self::stdout_path]. Both streams can be written to the
+    /// same file.
+    pub stderr_path: Option<PathBuf>,
+
+    /// Directory packages are installed to at run-time.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` is expanded to the
+    /// resolved value of [Self::origin]. Relative paths are resolved against
+    /// the directory holding per-user application data: `%APPDATA%` on
+    /// Windows, `~/Library/Application Support` on macOS, and
+    /// `$XDG_DATA_HOME` or `~/.local/share` elsewhere.
+    ///
+    /// Interpreter initialization behavior: if set and [Self::oxidized_importer]
+    /// is enabled, `oxidized_importer.install_to_user_site()` installs packages
+    /// to this directory. If the directory exists, its content is indexed by
+    /// `OxidizedFinder`, after packed resources, which take precedence.
+    pub user_site_packages: Option<PathBuf>,
+
+    /// Whether to run `pytest` instead of the configured code.
+    ///
+    /// Default value: [false]
+    ///
+    /// If set, [crate::MainPythonInterpreter::run()] calls
+    /// [crate::MainPythonInterpreter::run_pytest()], which runs `pytest`
+    /// against test modules importable via `OxidizedFinder`. `pytest` must be
+    /// importable. Requires [Self::oxidized_importer].
+    pub run_pytest: bool,
+
+    /// Whether to import modules in a way compatible with `coverage.py`.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: if set and [Self::oxidized_importer]
+    /// is enabled, `OxidizedFinder` compiles modules having source from source
+    /// instead of using packed bytecode. The filename of code objects is the path
+    /// of the source file. Modules whose source is in memory get a path beneath
+    /// the current executable, e.g. `/path/to/myapp/foo/bar.py`, as `__file__`.
+    pub coverage_mode: bool,
+
+    /// Whether to support debuggers like `pdb` and `debugpy`.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: if set and [Self::oxidized_importer]
+    /// is enabled, modules are compiled from source like with
+    /// [Self::coverage_mode]. In addition, `OxidizedFinder` registers the source
+    /// of modules imported from memory with `linecache`, keyed by their synthetic
+    /// paths. So debuggers can show source and set breakpoints by path.
+    pub debugger_support: bool,
+
+    /// Whether to behave like `python` when invoked with `-c` or `-m`.
+    ///
+    /// Default value: [false]
+    ///
+    /// Interpreter initialization behavior: if set and process arguments are
+    /// `[interpreter flags] -c <code> [args]` or
+    /// `[interpreter flags] -m <module> [args]`, the code or module is run
+    /// instead of the configured code, with `sys.argv` set like `python` would.
+    /// Interpreter flags are ignored. This allows libraries invoking
+    /// `sys.executable` to run Python code in new processes to work.
+    pub emulate_python_cli: bool,
+
+    /// Directories holding shared libraries packaged with the application.
+    ///
+    /// Default value: `vec![]`
+    ///
+    /// The special string `$ORIGIN` in paths is expanded to the value of
+    /// [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if non-empty,
+    /// `ctypes.util.find_library()` searches these directories before system
+    /// locations. On Windows, the directories are also registered via
+    /// `os.add_dll_directory()`.
+    pub shared_library_search_paths: Vec<PathBuf>,
+
+    /// Packages whose in-memory modules get a synthetic `__file__`.
+    ///
+    /// Default value: `vec![]`
+    ///
+    /// Interpreter initialization behavior: if [Self::oxidized_importer] is
+    /// enabled, `OxidizedFinder` gives in-memory modules of these packages and
+    /// their sub-packages a `__file__` in [Self::dunder_file_root]. e.g.
+    /// `<root>/foo/bar.py`. When a module of a package is imported, in-memory
+    /// resources of the package are extracted to the directory, so paths
+    /// derived from `__file__` resolve to them.
+    pub dunder_file_packages: Vec<String>,
+
+    /// Directory to extract data of [Self::dunder_file_packages] to.
+    ///
+    /// Default value: [None]
+    ///
+    /// The special string `$ORIGIN` is expanded to the value of [Self::origin].
+    /// Relative paths are resolved against the directory holding per-user
+    /// application data, like [Self::user_site_packages]. If not set, a
+    /// `package-data` directory in a directory named after the executable in
+    /// the directory holding per-user application data is used.
+    pub dunder_file_root: Option<PathBuf>,
+
+    /// Python code to run after modules are imported.
+    ///
+    /// Default value: `vec![]`
+    ///
+    /// Pairs of module names and Python code.
+    ///
+    /// Interpreter initialization behavior: if non-empty, a meta path finder
+    /// is installed which runs the code after the named module is executed,
+    /// with the module available as `module`. Code for modules imported
+    /// during interpreter initialization runs immediately.
+    pub runtime_hooks: Vec<(String, String)>,
+
+    /// Environment variable overriding the bytecode optimization level.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: if set and the environment variable it
+    /// names is set to `0`, `1` or `2`, [PythonInterpreterConfig::optimization_level]
+    /// is set to that level. Other non-empty values are an error.
+    ///
+    /// Bytecode of the optimization level is imported if it is packaged.
+    /// This allows a single binary to run with asserts enabled or optimized
+    /// without rebuilding it.
+    pub optimization_level_env: Option<String>,
+
+    /// Zstandard compressed tar archive of files to extract to the filesystem.
+    ///
+    /// Requires the `self-extracting` feature.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: if set, the archive is extracted to
+    /// [Self::extract_directory] unless that directory exists, and
+    /// [Self::origin] is set to that directory. `$ORIGIN` in other fields and
+    /// filesystem-relative resources then refer to extracted files. This
+    /// allows files that can't be loaded from memory, such as extension
+    /// modules and shared libraries, to be distributed in a single executable.
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub extract_archive: Option<&'a [u8]>,
+
+    /// Directory to extract [Self::extract_archive] to.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the special string `$ORIGIN` is expanded to
+    /// the directory holding the executable. Relative paths are resolved
+    /// against the directory holding per-user cached data. If not set, a
+    /// directory named after a digest of the archive in a directory named
+    /// after the executable in that directory is used. The value is replaced
+    /// by the resolved path.
+    ///
+    /// The directory should be unique to the content of the archive, as
+    /// existing directories are used as is. Directories of other versions of
+    /// the archive aren't removed.
+    pub extract_directory: Option<PathBuf>,
+
+    /// Changes to environment variables.
+    ///
+    /// Default value: `vec![]`
+    ///
+    /// [Self::resolve()] behavior: the special string `$ORIGIN` in values is
+    /// expanded to the resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: changes are applied in order
+    /// before the interpreter is initialized, so they are seen by Python, its
+    /// initialization and child processes. Paths prepended or appended to a
+    /// path list variable aren't added again if the variable already contains
+    /// them, e.g. when inherited by a child process running the executable.
+    pub environment: Vec<EnvironmentMutation>,
+
+    /// Working directory of the process.
+    ///
+    /// Default value: [WorkingDirectoryPolicy::Unchanged]
+    ///
+    /// Interpreter initialization behavior: the working directory is changed
+    /// before the interpreter is initialized. For
+    /// [WorkingDirectoryPolicy::UserDataDirectory], the directory is created
+    /// if it doesn't exist. The directory holding per-user application data is
+    /// `%APPDATA%` on Windows, `~/Library/Application Support` on macOS, and
+    /// `$XDG_DATA_HOME` or `~/.local/share` elsewhere.
+    pub working_directory: WorkingDirectoryPolicy,
+
+    /// Name of the application.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: per-user directories exposed by
+    /// the `oxidized_runtime.dirs` Python module are named after it. If not
+    /// set, the file stem of the executable is used.
+    pub app_name: Option<String>,
+
+    /// Organization publishing the application.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: on Windows, per-user directories
+    /// exposed by the `oxidized_runtime.dirs` Python module are in a directory
+    /// named after it.
+    pub app_author: Option<String>,
+
+    /// Name identifying instances of the application allowed to run once.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if set, the interpreter fails to
+    /// initialize if another process with the same name runs for the current
+    /// user, unless [Self::single_instance_forward_args] is set. Multiprocessing
+    /// worker processes aren't affected. The running instance receives
+    /// arguments of later instances via the `oxidized_runtime.single_instance`
+    /// Python module.
+    ///
+    /// This requires the `single-instance` feature of this crate.
+    pub single_instance: Option<String>,
+
+    /// Whether later instances forward their arguments to the running instance.
+    ///
+    /// Default value: `false`
+    ///
+    /// Interpreter initialization behavior: if [Self::single_instance] is set
+    /// and another instance is running, the arguments and working directory
+    /// of the process are sent to it and the process exits with code 0.
+    pub single_instance_forward_args: bool,
+
+    /// BMP image to show in a splash screen while the interpreter initializes.
+    ///
+    /// Default value: [None]
+    ///
+    /// Interpreter initialization behavior: if set, a borderless window
+    /// showing the image is displayed before the interpreter is initialized,
+    /// on Windows and macOS. Other platforms ignore it. The window stays
+    /// until the `oxidized_runtime.splash.close()` Python function is called,
+    /// [Self::splash_screen_timeout] expires or the interpreter is dropped.
+    /// Multiprocessing worker processes don't show it.
+    ///
+    /// Images must be uncompressed BMP files with 24 or 32 bits per pixel.
+    ///
+    /// This requires the `splash-screen` feature of this crate.
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub splash_screen_image: Option<&'a [u8]>,
+
+    /// Duration after which the splash screen closes itself.
+    ///
+    /// Default value: [None]
+    ///
+    /// If [None], the splash screen stays until it is closed by Python code
+    /// or the interpreter is dropped.
+    pub splash_screen_timeout: Option<Duration>,
+
+    /// Destination of output written to standard output.
+    ///
+    /// Requires the `output-capture` feature.
+    ///
+    /// Default value: [None]
+    ///
+    /// [Self::resolve()] behavior: the token `$ORIGIN` in the path of
+    /// [OutputCapture::File] is expanded to the resolved value of [Self::origin].
+    ///
+    /// Interpreter initialization behavior: if set, the standard output file
+    /// descriptor, and the standard output handle on Windows, are replaced
+    /// by a pipe before the interpreter is initialized. A background thread
+    /// passes everything written to it, by Python or native code, to the
+    /// destination. This gives Windows applications without a console a
+    /// valid `sys.stdout`. The original stream is restored and remaining
+    /// output is delivered when the interpreter is dropped.
+    ///
+    /// Python buffers writes to pipes. Set
+    /// [PythonInterpreterConfig::buffered_stdio] to `Some(false)` to
+    /// deliver output as it is written.
+    ///
+    /// Conflicts with [Self::stdout_path].
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub stdout_capture: Option<OutputCapture>,
+
+    /// Destination of output written to standard error.
+    ///
+    /// Behaves like [Self::stdout_capture]. Both streams can be written to the
+    /// same file.
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub stderr_capture: Option<OutputCapture>,
+
+    /// Whether to forward Python log records to `tracing`.
+    ///
+    /// Requires the `logging-bridge` feature.
+    ///
+    /// Default value: `false`
+    ///
+    /// Interpreter initialization behavior: if set, a handler emitting records
+    /// as `tracing` events is added to the root logger of Python's `logging`
+    /// module, and the level of the root logger is set to the most verbose
+    /// level enabled by the current `tracing` subscriber. The subscriber
+    /// should therefore be installed before the interpreter is initialized.
+    /// Python code can also emit events with structured fields via the
+    /// `oxidized_runtime.logging.emit()` function.
+    pub logging_bridge: bool,
+
+    /// Settings a runtime configuration file can override.
+    ///
+    /// Requires the `runtime-config` feature.
+    ///
+    /// Default value: `vec![]`
+    ///
+    /// [Self::resolve()] behavior: if not empty and a `<name>.runtime.toml`
+    /// file exists next to the `<name>` executable, settings in the file
+    /// replace the values of this config before anything else is resolved.
+    /// Files setting other settings are an error. Supported settings are
+    /// `allocator_backend`, `allocator_debug`, `development_mode`,
+    /// `fault_handler`, `fault_handler_path`, `filesystem_importer`,
+    /// `module_search_paths`, `optimization_level`, `tracemalloc_frames`,
+    /// `verbose` and `warn_options`, named like the fields they set.
+    pub runtime_config_overrides: Vec<String>,
+
+    /// Key/value metadata describing the application, e.g. its version.
+    ///
+    /// Default value: `vec![]`
+    ///
+    /// Interpreter initialization behavior: the metadata is exposed to
+    /// Python code as the read-only `oxidized_runtime.metadata.values`
+    /// mapping. Rust code can read it with
+    /// [crate::MainPythonInterpreter::runtime_metadata()].
+    pub runtime_metadata: Vec<(String, String)>,
 }
 
 impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
@@ -415,7 +962,46 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
             sys_meipass: false,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: None,
+            tk_library: None,
             write_modules_directory_env: None,
+            allow_runtime_path_overrides: false,
+            fault_handler_path: None,
+            tracemalloc_frames: None,
+            crash_reports_directory: None,
+            crash_reports_upload_url: None,
+            update_feed_url: None,
+            update_channel: None,
+            update_public_key: None,
+            update_current_version: None,
+            daemonize: false,
+            pid_file: None,
+            stdout_path: None,
+            stderr_path: None,
+            user_site_packages: None,
+            run_pytest: false,
+            coverage_mode: false,
+            debugger_support: false,
+            emulate_python_cli: false,
+            shared_library_search_paths: vec![],
+            dunder_file_packages: vec![],
+            dunder_file_root: None,
+            runtime_hooks: vec![],
+            optimization_level_env: None,
+            extract_archive: None,
+            extract_directory: None,
+            environment: vec![],
+            working_directory: WorkingDirectoryPolicy::Unchanged,
+            app_name: None,
+            app_author: None,
+            single_instance: None,
+            single_instance_forward_args: false,
+            splash_screen_image: None,
+            splash_screen_timeout: None,
+            stdout_capture: None,
+            stderr_capture: None,
+            logging_bridge: false,
+            runtime_config_overrides: vec![],
+            runtime_metadata: vec![],
         }
     }
 }
@@ -423,17 +1009,9 @@ impl<'a> Default for OxidizedPythonInterpreterConfig<'a> {
 impl<'a> OxidizedPythonInterpreterConfig<'a> {
     /// Create a new type with all values resolved.
     pub fn resolve(
-        self,
+        mut self,
     ) -> Result<ResolvedOxidizedPythonInterpreterConfig<'a>, NewInterpreterError> {
-        let argv = if let Some(args) = self.argv {
-            Some(args)
-        } else if self.interpreter_config.argv.is_some() {
-            None
-        } else {
-            Some(std::env::args_os().collect::<Vec<_>>())
-        };
-
-        let exe = if let Some(exe) = self.exe {
+        let exe = if let Some(exe) = self.exe.clone() {
             exe
         } else {
             std::env::current_exe()
@@ -446,6 +1024,18 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
         let exe = dunce::canonicalize(exe)
             .map_err(|_| NewInterpreterError::Simple("could not obtain current executable path"))?;
 
+        if !self.runtime_config_overrides.is_empty() {
+            apply_runtime_config(&mut self, &exe)?;
+        }
+
+        let mut argv = if let Some(args) = self.argv {
+            Some(args)
+        } else if self.interpreter_config.argv.is_some() {
+            None
+        } else {
+            Some(std::env::args_os().collect::<Vec<_>>())
+        };
+
         let origin = if let Some(origin) = self.origin {
             origin
         } else {
@@ -456,6 +1046,18 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
                 .to_path_buf()
         };
 
+        let (origin, extract_directory) = if let Some(archive) = self.extract_archive {
+            let directory = self
+                .extract_directory
+                .as_ref()
+                .map(|path| resolve_origin_path(path, &origin));
+            let directory = extract_embedded_archive(archive, directory.as_deref(), &exe)?;
+
+            (directory.clone(), Some(directory))
+        } else {
+            (origin, self.extract_directory.clone())
+        };
+
         let origin_string = origin.display().to_string();
 
         let packed_resources = self
@@ -464,9 +1066,7 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
             .map(|entry| match entry {
                 PackedResourcesSource::Memory(_) => entry,
                 PackedResourcesSource::MemoryMappedPath(p) => {
-                    PackedResourcesSource::MemoryMappedPath(PathBuf::from(
-                        p.display().to_string().replace("$ORIGIN", &origin_string),
-                    ))
+                    PackedResourcesSource::MemoryMappedPath(resolve_origin_path(&p, &origin))
                 }
             })
             .collect::<Vec<_>>();
@@ -483,28 +1083,298 @@ impl<'a> OxidizedPythonInterpreterConfig<'a> {
                     .collect::<Vec<_>>()
             });
 
+        let shared_library_search_paths = self
+            .shared_library_search_paths
+            .iter()
+            .map(|p| resolve_origin_path(p, &origin))
+            .collect::<Vec<_>>();
+
         let tcl_library = self
             .tcl_library
             .as_ref()
             .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
 
+        let tk_library = self
+            .tk_library
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let fault_handler_path = self
+            .fault_handler_path
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let crash_reports_directory = self
+            .crash_reports_directory
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let pid_file = self
+            .pid_file
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let stdout_path = self
+            .stdout_path
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let stderr_path = self
+            .stderr_path
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)));
+
+        let resolve_capture = |capture: Option<OutputCapture>| match capture {
+            Some(OutputCapture::File {
+                path,
+                max_size,
+                backups,
+            }) => Some(OutputCapture::File {
+                path: resolve_origin_path(&path, &origin),
+                max_size,
+                backups,
+            }),
+            capture => capture,
+        };
+
+        let stdout_capture = resolve_capture(self.stdout_capture.clone());
+        let stderr_capture = resolve_capture(self.stderr_capture.clone());
+
+        let user_site_packages = self
+            .user_site_packages
+            .as_ref()
+            .map(|x| PathBuf::from(x.display().to_string().replace("$ORIGIN", &origin_string)))
+            .map(|path| {
+                if path.is_absolute() {
+                    Ok(path)
+                } else {
+                    resolve_user_data_dir().map(|dir| dir.join(path)).ok_or(
+                        NewInterpreterError::Simple(
+                            "unable to resolve user data directory for user_site_packages",
+                        ),
+                    )
+                }
+            })
+            .transpose()?;
+
+        let environment = self
+            .environment
+            .iter()
+            .map(|mutation| {
+                let expand = |value: &str| value.replace("$ORIGIN", &origin_string);
+
+                match mutation {
+                    EnvironmentMutation::Set(name, value) => {
+                        EnvironmentMutation::Set(name.clone(), expand(value))
+                    }
+                    EnvironmentMutation::Unset(name) => EnvironmentMutation::Unset(name.clone()),
+                    EnvironmentMutation::Prepend(name, value) => {
+                        EnvironmentMutation::Prepend(name.clone(), expand(value))
+                    }
+                    EnvironmentMutation::Append(name, value) => {
+                        EnvironmentMutation::Append(name.clone(), expand(value))
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let dunder_file_root = if self.dunder_file_packages.is_empty() {
+            self.dunder_file_root.clone()
+        } else {
+            let path = match &self.dunder_file_root {
+                Some(path) => PathBuf::from(
+                    path.display()
+                        .to_string()
+                        .replace("$ORIGIN", &origin_string),
+                ),
+                None => PathBuf::from(exe.file_stem().unwrap_or_default()).join("package-data"),
+            };
+
+            if path.is_absolute() {
+                Some(path)
+            } else {
+                Some(resolve_user_data_dir().map(|dir| dir.join(path)).ok_or(
+                    NewInterpreterError::Simple(
+                        "unable to resolve user data directory for dunder_file_root",
+                    ),
+                )?)
+            }
+        };
+
+        let optimization_level = match self
+            .optimization_level_env
+            .as_ref()
+            .and_then(std::env::var_os)
+            .filter(|value| !value.is_empty())
+        {
+            Some(value) => Some(match value.to_str() {
+                Some("0") => Ok(BytecodeOptimizationLevel::Zero),
+                Some("1") => Ok(BytecodeOptimizationLevel::One),
+                Some("2") => Ok(BytecodeOptimizationLevel::Two),
+                _ => Err(NewInterpreterError::Dynamic(format!(
+                    "invalid bytecode optimization level in {}: {}; expected 0, 1 or 2",
+                    self.optimization_level_env.as_deref().unwrap_or_default(),
+                    value.to_string_lossy()
+                ))),
+            }?),
+            None => self.interpreter_config.optimization_level,
+        };
+
+        let mut interpreter_config = PythonInterpreterConfig {
+            module_search_paths,
+            optimization_level,
+            ..self.interpreter_config
+        };
+        let mut run_pytest = self.run_pytest;
+
+        // Code given like to `python -c` or `python -m` replaces the code to run.
+        if self.emulate_python_cli {
+            if let Some((target, args)) = parse_python_cli_args(
+                argv.as_deref()
+                    .or(interpreter_config.argv.as_deref())
+                    .unwrap_or_default(),
+            ) {
+                let (flag, run_command, run_module) = match target {
+                    PythonCliTarget::Command(code) => ("-c", Some(code), None),
+                    PythonCliTarget::Module(name) => ("-m", None, Some(name)),
+                };
+
+                // `sys.argv[0]` is `-c` or `-m` like with `python`. `runpy`
+                // replaces the latter with the path of the module. Arguments
+                // are already parsed.
+                argv = Some(std::iter::once(OsString::from(flag)).chain(args).collect());
+                interpreter_config = PythonInterpreterConfig {
+                    parse_argv: Some(false),
+                    argv: None,
+                    run_command,
+                    run_module,
+                    run_filename: None,
+                    ..interpreter_config
+                };
+                run_pytest = false;
+            }
+        }
+
         Ok(ResolvedOxidizedPythonInterpreterConfig {
             inner: Self {
                 exe: Some(exe),
                 origin: Some(origin),
-                interpreter_config: PythonInterpreterConfig {
-                    module_search_paths,
-                    ..self.interpreter_config
-                },
+                interpreter_config,
                 argv,
                 packed_resources,
                 tcl_library,
+                tk_library,
+                fault_handler_path,
+                crash_reports_directory,
+                pid_file,
+                stdout_path,
+                stderr_path,
+                stdout_capture,
+                stderr_capture,
+                user_site_packages,
+                run_pytest,
+                shared_library_search_paths,
+                dunder_file_root,
+                extract_directory,
+                environment,
                 ..self
             },
         })
     }
 }
 
+/// Extract an embedded archive, returning the directory it is extracted to.
+#[cfg(feature = "self-extracting")]
+fn extract_embedded_archive(
+    archive: &[u8],
+    directory: Option<&Path>,
+    exe: &Path,
+) -> Result<PathBuf, NewInterpreterError> {
+    let directory = crate::extract::resolve_extract_directory(
+        archive,
+        directory,
+        exe,
+        crate::osutils::resolve_user_cache_dir(),
+    )?;
+
+    crate::extract::extract_archive(archive, &directory)?;
+
+    // Paths derived from the origin are compared against paths of loaded
+    // modules. So canonicalize it like the executable.
+    dunce::canonicalize(&directory).map_err(|e| {
+        NewInterpreterError::Dynamic(format!(
+            "unable to canonicalize {}: {}",
+            directory.display(),
+            e
+        ))
+    })
+}
+
+#[cfg(not(feature = "self-extracting"))]
+fn extract_embedded_archive(
+    _archive: &[u8],
+    _directory: Option<&Path>,
+    _exe: &Path,
+) -> Result<PathBuf, NewInterpreterError> {
+    Err(NewInterpreterError::Simple(
+        "self-extracting executables require the self-extracting feature of pyembed",
+    ))
+}
+
+/// Apply settings of the runtime configuration file next to an executable.
+#[cfg(feature = "runtime-config")]
+fn apply_runtime_config(
+    config: &mut OxidizedPythonInterpreterConfig,
+    exe: &Path,
+) -> Result<(), NewInterpreterError> {
+    crate::runtime_config::apply_runtime_config(
+        config,
+        &crate::runtime_config::runtime_config_path(exe),
+    )
+}
+
+#[cfg(not(feature = "runtime-config"))]
+fn apply_runtime_config(
+    _config: &mut OxidizedPythonInterpreterConfig,
+    _exe: &Path,
+) -> Result<(), NewInterpreterError> {
+    Err(NewInterpreterError::Simple(
+        "runtime configuration files require the runtime-config feature of pyembed",
+    ))
+}
+
+/// Expand `$ORIGIN` in a path.
+///
+/// If the path begins with `$ORIGIN`, `.` and `..` components following it
+/// are resolved lexically. Since the origin is canonicalized, this is
+/// equivalent to the operating system's resolution.
+fn resolve_origin_path(path: &Path, origin: &Path) -> PathBuf {
+    let path_string = path.display().to_string();
+
+    if let Some(relative) = path_string
+        .strip_prefix("$ORIGIN")
+        .filter(|rest| rest.is_empty() || rest.starts_with(&['/', '\\'][..]))
+    {
+        let mut resolved = origin.to_path_buf();
+
+        for component in
+            Path::new(relative.trim_start_matches(|c| c == '/' || c == '\\')).components()
+        {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                component => resolved.push(component),
+            }
+        }
+
+        resolved
+    } else {
+        PathBuf::from(path_string.replace("$ORIGIN", &origin.display().to_string()))
+    }
+}
+
 /// An `OxidizedPythonInterpreterConfig` that has fields resolved.
 pub struct ResolvedOxidizedPythonInterpreterConfig<'a> {
     inner: OxidizedPythonInterpreterConfig<'a>,
@@ -542,6 +1412,47 @@ impl<'a> ResolvedOxidizedPythonInterpreterConfig<'a> {
             .expect("origin should have a value")
     }
 
+    /// Obtain the name of the application.
+    ///
+    /// This is [OxidizedPythonInterpreterConfig::app_name] if set or the file
+    /// stem of the current executable.
+    pub fn app_name(&self) -> String {
+        match &self.inner.app_name {
+            Some(name) => name.clone(),
+            None => self
+                .exe()
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Obtain the name of the environment variable holding runtime `sys.path` overrides.
+    ///
+    /// This is the upper-cased file stem of the current executable with characters
+    /// other than ASCII letters and digits replaced by `_`, followed by `_PYTHONPATH`.
+    /// e.g. `MYAPP_PYTHONPATH` for `myapp` or `myapp.exe`.
+    pub fn runtime_path_overrides_env(&self) -> String {
+        let stem = self
+            .exe()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+
+        let name = stem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        format!("{}_PYTHONPATH", name)
+    }
+
     /// Resolve the effective value of `sys.argv`.
     pub fn resolve_sys_argv(&self) -> &[OsString] {
         if let Some(args) = &self.inner.argv {
@@ -577,6 +1488,10 @@ impl<'a, 'config: 'a> TryFrom<&ResolvedOxidizedPythonInterpreterConfig<'config>>
         state.set_current_exe(config.exe().to_path_buf());
         state.set_origin(config.origin().to_path_buf());
 
+        if let Some(root) = &config.dunder_file_root {
+            state.set_dunder_file_packages(root.clone(), config.dunder_file_packages.clone());
+        }
+
         for source in &config.packed_resources {
             match source {
                 PackedResourcesSource::Memory(data) => {
@@ -625,6 +1540,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_packed_resources_parent_of_origin() -> Result<()> {
+        let mut config = OxidizedPythonInterpreterConfig {
+            origin: Some(PathBuf::from("/other/origin/bin")),
+            ..Default::default()
+        };
+
+        config
+            .packed_resources
+            .push(PackedResourcesSource::MemoryMappedPath(PathBuf::from(
+                "$ORIGIN/../lib/./packed-resources",
+            )));
+
+        let resolved = config.resolve()?;
+
+        assert_eq!(
+            resolved.packed_resources,
+            vec![PackedResourcesSource::MemoryMappedPath(
+                PathBuf::from("/other/origin")
+                    .join("lib")
+                    .join("packed-resources")
+            )]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_packed_resources_explicit_origin() -> Result<()> {
         let mut config = OxidizedPythonInterpreterConfig {
@@ -649,4 +1591,205 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_emulate_python_cli() -> Result<()> {
+        let config = OxidizedPythonInterpreterConfig {
+            argv: Some(vec![
+                "myapp".into(),
+                "-E".into(),
+                "-m".into(),
+                "pip".into(),
+                "list".into(),
+            ]),
+            emulate_python_cli: true,
+            ..Default::default()
+        };
+
+        let resolved = config.resolve()?;
+
+        assert_eq!(
+            resolved.interpreter_config.run_module,
+            Some("pip".to_string())
+        );
+        assert_eq!(resolved.interpreter_config.parse_argv, Some(false));
+        assert_eq!(
+            resolved.resolve_sys_argv(),
+            &[OsString::from("-m"), OsString::from("list")]
+        );
+
+        let config = OxidizedPythonInterpreterConfig {
+            argv: Some(vec!["myapp".into(), "-m".into(), "pip".into()]),
+            ..Default::default()
+        };
+
+        let resolved = config.resolve()?;
+
+        assert_eq!(resolved.interpreter_config.run_module, None);
+        assert_eq!(resolved.resolve_sys_argv().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dunder_file_root() -> Result<()> {
+        let config = OxidizedPythonInterpreterConfig {
+            origin: Some(PathBuf::from("/other/origin")),
+            dunder_file_packages: vec!["foo".to_string()],
+            dunder_file_root: Some(PathBuf::from("$ORIGIN/data")),
+            ..Default::default()
+        };
+
+        let resolved = config.resolve()?;
+
+        assert_eq!(
+            resolved.dunder_file_root,
+            Some(PathBuf::from("/other/origin/data"))
+        );
+
+        // The root is only resolved if packages are given.
+        let resolved = OxidizedPythonInterpreterConfig::default().resolve()?;
+        assert_eq!(resolved.dunder_file_root, None);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "self-extracting")]
+    #[test]
+    fn test_extract_archive() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+
+        let mut builder = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, "lib/foo.py", b"foo".as_slice())?;
+        let archive = zstd::stream::encode_all(builder.into_inner()?.as_slice(), 3)?;
+
+        let config = OxidizedPythonInterpreterConfig {
+            origin: Some(temp_dir.path().to_path_buf()),
+            extract_archive: Some(&archive),
+            extract_directory: Some(PathBuf::from("$ORIGIN/extracted")),
+            interpreter_config: PythonInterpreterConfig {
+                module_search_paths: Some(vec![PathBuf::from("$ORIGIN/lib")]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let resolved = config.resolve()?;
+
+        let directory = dunce::canonicalize(temp_dir.path().join("extracted"))?;
+        assert_eq!(resolved.origin(), &directory);
+        assert_eq!(resolved.extract_directory, Some(directory.clone()));
+        assert_eq!(
+            resolved.interpreter_config.module_search_paths,
+            Some(vec![directory.join("lib")])
+        );
+        assert!(directory.join("lib").join("foo.py").exists());
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "self-extracting"))]
+    #[test]
+    fn test_extract_archive_unsupported() {
+        let config = OxidizedPythonInterpreterConfig {
+            extract_archive: Some(b"archive"),
+            ..Default::default()
+        };
+
+        assert!(config.resolve().is_err());
+    }
+
+    #[test]
+    fn test_optimization_level_env() -> Result<()> {
+        let config = || OxidizedPythonInterpreterConfig {
+            interpreter_config: PythonInterpreterConfig {
+                optimization_level: Some(BytecodeOptimizationLevel::Zero),
+                ..Default::default()
+            },
+            optimization_level_env: Some("PYEMBED_TEST_OPTIMIZATION_LEVEL".to_string()),
+            ..Default::default()
+        };
+
+        // The configured level applies if the variable isn't set.
+        let resolved = config().resolve()?;
+        assert_eq!(
+            resolved.interpreter_config.optimization_level,
+            Some(BytecodeOptimizationLevel::Zero)
+        );
+
+        std::env::set_var("PYEMBED_TEST_OPTIMIZATION_LEVEL", "2");
+        let resolved = config().resolve();
+        std::env::set_var("PYEMBED_TEST_OPTIMIZATION_LEVEL", "3");
+        let invalid = config().resolve();
+        std::env::remove_var("PYEMBED_TEST_OPTIMIZATION_LEVEL");
+
+        assert_eq!(
+            resolved?.interpreter_config.optimization_level,
+            Some(BytecodeOptimizationLevel::Two)
+        );
+        assert!(invalid.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "runtime-config")]
+    #[test]
+    fn test_runtime_config() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let temp_dir = dunce::canonicalize(temp_dir.path())?;
+        let exe = temp_dir.join("myapp.exe");
+        std::fs::write(&exe, b"")?;
+        std::fs::write(
+            temp_dir.join("myapp.runtime.toml"),
+            "optimization_level = 2\nmodule_search_paths = ['$ORIGIN/debug']\n",
+        )?;
+
+        let config = || OxidizedPythonInterpreterConfig {
+            exe: Some(exe.clone()),
+            runtime_config_overrides: vec![
+                "module_search_paths".to_string(),
+                "optimization_level".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let resolved = config().resolve()?;
+        assert_eq!(
+            resolved.interpreter_config.optimization_level,
+            Some(BytecodeOptimizationLevel::Two)
+        );
+        assert_eq!(
+            resolved.interpreter_config.module_search_paths,
+            Some(vec![temp_dir.join("debug")])
+        );
+
+        // Only whitelisted settings can be overridden.
+        let mut config = config();
+        config.runtime_config_overrides.pop();
+        assert!(config.resolve().is_err());
+
+        // The file is ignored unless settings can be overridden.
+        let resolved = OxidizedPythonInterpreterConfig {
+            exe: Some(exe),
+            ..Default::default()
+        }
+        .resolve()?;
+        assert_eq!(resolved.interpreter_config.optimization_level, None);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "runtime-config"))]
+    #[test]
+    fn test_runtime_config_unsupported() {
+        let config = OxidizedPythonInterpreterConfig {
+            runtime_config_overrides: vec!["verbose".to_string()],
+            ..Default::default()
+        };
+
+        assert!(config.resolve().is_err());
+    }
 }
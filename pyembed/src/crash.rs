@@ -0,0 +1,1031 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Crash reporting.
+
+When enabled, handlers for fatal signals (on Unix) or unhandled exceptions
+(on Windows) are installed and Python's `faulthandler` is enabled to write
+the Python stacks of all threads to a file in the reports directory.
+
+The process is in an undefined state when crashing. So our handlers don't
+allocate, format, symbolize or open files. They write the signal number or
+exception code and the raw instruction pointers of the crashing thread to a
+raw report opened when crash reporting is enabled. On Linux, they also copy
+`/proc/self/maps` to the raw report so instruction pointers can be mapped to
+modules. On Unix, handlers are installed with `sigaction()` and run on an
+alternate signal stack, which is set up for the thread enabling crash
+reporting, so stack overflows of that thread can be reported.
+
+Our handlers are installed before `faulthandler`'s, which calls the handler
+it replaced after writing Python stacks.
+
+Raw reports are locked by the process writing them. The next time the
+application starts, raw reports of processes that are no longer running are
+symbolized and converted to JSON reports. Uploading reports from a crashing
+process isn't reliable either, so JSON reports are then uploaded from a
+background thread. Frames are only symbolized if their module is loaded in
+the new process and the executable didn't change. Other frames hold their
+module and offset.
+*/
+
+use {
+    crate::{error::NewInterpreterError, interpreter::run_code_in_namespace},
+    once_cell::sync::OnceCell,
+    pyo3::prelude::*,
+    std::{
+        fs::{File, OpenOptions},
+        io::{Read, Write},
+        path::{Path, PathBuf},
+        time::UNIX_EPOCH,
+    },
+};
+
+/// Python code enabling `faulthandler`.
+///
+/// `faulthandler` keeps a reference to the file object, keeping it open.
+const ENABLE_FAULTHANDLER_CODE: &str = "\
+import faulthandler
+faulthandler.enable(open(python_stacks_path, 'w'), all_threads=True)
+";
+
+/// Python code uploading reports of previous crashes in a background thread.
+///
+/// Uploaded reports are deleted. Failed uploads are retried on next start.
+const UPLOAD_CODE: &str = "\
+import glob, os, threading, urllib.request
+
+def upload(directory, url):
+    for path in sorted(glob.glob(os.path.join(directory, 'crash-*.json'))):
+        try:
+            with open(path, 'rb') as fh:
+                data = fh.read()
+            request = urllib.request.Request(
+                url, data=data, headers={'Content-Type': 'application/json'}
+            )
+            urllib.request.urlopen(request, timeout=30).close()
+            os.unlink(path)
+        except Exception:
+            pass
+
+threading.Thread(target=upload, args=(directory, url), daemon=True).start()
+";
+
+/// Maximum number of native frames written to a raw report.
+const MAX_FRAMES: usize = 128;
+
+/// Raw report of the current process, written to by the crash handlers.
+struct ProcessRawReport {
+    file: File,
+    /// Directory holding the raw report.
+    directory: PathBuf,
+}
+
+/// Raw report of the current process.
+///
+/// Only the first configuration of a process is used by the crash handlers.
+/// The raw report is kept for the lifetime of the process. If the process
+/// doesn't crash, it is deleted the next time the application starts.
+static RAW_REPORT: OnceCell<ProcessRawReport> = OnceCell::new();
+
+/// Quote a string for inclusion in a JSON document.
+fn json_string(value: &str) -> String {
+    let mut res = String::with_capacity(value.len() + 2);
+    res.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+
+    res.push('"');
+    res
+}
+
+/// Render a crash report as JSON.
+fn render_report(
+    exe: &Path,
+    pid: u32,
+    time: u64,
+    reason: &str,
+    native_stack: &[String],
+    python_stacks: &str,
+) -> String {
+    format!(
+        "{{\n  \
+         \"version\": 1,\n  \
+         \"executable\": {},\n  \
+         \"pid\": {},\n  \
+         \"time\": {},\n  \
+         \"reason\": {},\n  \
+         \"native_stack\": [{}],\n  \
+         \"python_stacks\": {}\n\
+         }}\n",
+        json_string(&exe.display().to_string()),
+        pid,
+        time,
+        json_string(reason),
+        native_stack
+            .iter()
+            .map(|frame| json_string(frame))
+            .collect::<Vec<_>>()
+            .join(", "),
+        json_string(python_stacks),
+    )
+}
+
+/// Format a number into the end of `buffer` without allocating.
+fn format_number(mut value: usize, radix: usize, buffer: &mut [u8; 24]) -> &[u8] {
+    let mut start = buffer.len();
+
+    loop {
+        start -= 1;
+        buffer[start] = b"0123456789abcdef"[value % radix];
+        value /= radix;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    &buffer[start..]
+}
+
+/// Write bytes to the raw report.
+///
+/// This is a plain `write(2)` call and is safe to call from a signal handler.
+#[cfg(unix)]
+fn write_raw(mut data: &[u8]) {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = match RAW_REPORT.get() {
+        Some(raw_report) => raw_report.file.as_raw_fd(),
+        None => return,
+    };
+
+    while !data.is_empty() {
+        let written = unsafe { libc::write(fd, data.as_ptr() as *const libc::c_void, data.len()) };
+        if written <= 0 {
+            break;
+        }
+        data = &data[written as usize..];
+    }
+}
+
+/// Write bytes to the raw report.
+#[cfg(not(unix))]
+fn write_raw(data: &[u8]) {
+    if let Some(raw_report) = RAW_REPORT.get() {
+        let _ = (&raw_report.file).write_all(data);
+    }
+}
+
+/// Write a `<key> <value>` line to the raw report without allocating.
+fn write_raw_line(key: &[u8], value: usize, radix: usize) {
+    let mut buffer = [0; 24];
+
+    write_raw(key);
+    write_raw(if radix == 16 { b" 0x" } else { b" " });
+    write_raw(format_number(value, radix, &mut buffer));
+    write_raw(b"\n");
+}
+
+/// Write the instruction pointers of the current thread to the raw report.
+///
+/// The stack is walked without resolving symbols, which doesn't allocate.
+fn write_raw_frames() {
+    let mut count = 0;
+
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            write_raw_line(b"ip", frame.ip() as usize, 16);
+            count += 1;
+            count < MAX_FRAMES
+        });
+    }
+}
+
+/// Copy the memory mappings of the process to the raw report.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn write_raw_maps() {
+    unsafe {
+        let fd = libc::open(
+            b"/proc/self/maps\0".as_ptr() as *const libc::c_char,
+            libc::O_RDONLY,
+        );
+        if fd < 0 {
+            return;
+        }
+
+        write_raw(b"maps\n");
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            let count = libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len());
+            if count <= 0 {
+                break;
+            }
+            write_raw(&buffer[..count as usize]);
+        }
+
+        libc::close(fd);
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn write_raw_maps() {}
+
+/// Create the raw report of the current process and lock it.
+fn create_raw_report(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    // Other processes can read the raw report but not open it for writing,
+    // which is how they tell whether we're still running.
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        options.share_mode(FILE_SHARE_READ);
+    }
+
+    let file = options.open(path)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        // The lock is released by the kernel when the process exits.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    Ok(file)
+}
+
+/// Open a raw report unless the process writing it is still running.
+fn open_unlocked_raw_report(path: &Path) -> Option<File> {
+    let file = OpenOptions::new().read(true).write(true).open(path).ok()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return None;
+        }
+    }
+
+    Some(file)
+}
+
+/// Modification time of a file in seconds since the epoch.
+fn modified_time(path: &Path) -> Option<u64> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
+/// Write the header of a raw report.
+///
+/// The header records the executable and where the module holding this code
+/// is loaded, which are needed to symbolize the report later.
+fn write_raw_header(file: &mut File, exe: &Path) -> std::io::Result<()> {
+    if let Some(modified) = modified_time(exe) {
+        writeln!(file, "exe_modified {}", modified)?;
+    }
+    if let (Some(base), Ok(metadata)) = (current_module_base(), std::fs::metadata(exe)) {
+        writeln!(file, "module {:#x} {:#x}", base, metadata.len())?;
+    }
+
+    Ok(())
+}
+
+/// A memory mapping of a file, as listed in `/proc/<pid>/maps`.
+#[derive(Clone, Debug, PartialEq)]
+struct Mapping {
+    start: usize,
+    end: usize,
+    offset: usize,
+    path: String,
+}
+
+/// Parse file mappings in the format of `/proc/<pid>/maps`.
+fn parse_maps(data: &str) -> Vec<Mapping> {
+    data.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (start, end) = fields.next()?.split_once('-')?;
+            let offset = fields.nth(1)?;
+            let path = fields.skip(2).collect::<Vec<_>>().join(" ");
+
+            if path.is_empty() || path.starts_with('[') {
+                return None;
+            }
+
+            Some(Mapping {
+                start: usize::from_str_radix(start, 16).ok()?,
+                end: usize::from_str_radix(end, 16).ok()?,
+                offset: usize::from_str_radix(offset, 16).ok()?,
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Modules of the process symbolizing raw reports.
+struct CurrentModules {
+    exe: PathBuf,
+    exe_modified: Option<u64>,
+    base: Option<usize>,
+    maps: Vec<Mapping>,
+}
+
+impl CurrentModules {
+    fn new(exe: &Path) -> Self {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let maps = parse_maps(&std::fs::read_to_string("/proc/self/maps").unwrap_or_default());
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let maps = vec![];
+
+        Self {
+            exe: exe.canonicalize().unwrap_or_else(|_| exe.to_path_buf()),
+            exe_modified: modified_time(exe),
+            base: current_module_base(),
+            maps,
+        }
+    }
+}
+
+/// Resolve the symbol at an address of the current process.
+fn resolve_symbol(address: usize) -> Option<String> {
+    let mut res = None;
+
+    backtrace::resolve(address as *mut std::ffi::c_void, |symbol| {
+        if res.is_some() {
+            return;
+        }
+
+        if let Some(name) = symbol.name() {
+            let mut line = name.to_string();
+            if let (Some(filename), Some(lineno)) = (symbol.filename(), symbol.lineno()) {
+                line.push_str(&format!(" ({}:{})", filename.display(), lineno));
+            }
+            res = Some(line);
+        }
+    });
+
+    res
+}
+
+/// A report written by the crash handlers.
+#[derive(Debug, Default, PartialEq)]
+struct RawReport {
+    /// Modification time of the executable when crash reporting was enabled.
+    exe_modified: Option<u64>,
+
+    /// Load address and size of the module holding the crash handlers.
+    module: Option<(usize, usize)>,
+
+    signal: Option<usize>,
+    exception: Option<usize>,
+
+    /// Instruction pointers of the crashing thread.
+    ips: Vec<usize>,
+
+    /// File mappings of the crashed process.
+    maps: Vec<Mapping>,
+}
+
+impl RawReport {
+    /// Parse a raw report.
+    ///
+    /// Returns `None` if the report doesn't record a crash.
+    fn parse(data: &str) -> Option<Self> {
+        let mut res = Self::default();
+
+        let parse_hex = |value: &str| usize::from_str_radix(value.trim_start_matches("0x"), 16);
+
+        let mut lines = data.split_inclusive('\n');
+        for line in lines.by_ref() {
+            let line = line.trim_end();
+
+            if line == "maps" {
+                break;
+            }
+
+            let (key, value) = match line.split_once(' ') {
+                Some(v) => v,
+                None => continue,
+            };
+
+            match key {
+                "exe_modified" => res.exe_modified = value.parse().ok(),
+                "module" => {
+                    if let Some((base, size)) = value.split_once(' ') {
+                        if let (Ok(base), Ok(size)) = (parse_hex(base), parse_hex(size)) {
+                            res.module = Some((base, size));
+                        }
+                    }
+                }
+                "signal" => res.signal = value.parse().ok(),
+                "exception" => res.exception = parse_hex(value).ok(),
+                "ip" => res.ips.extend(parse_hex(value)),
+                _ => {}
+            }
+        }
+
+        res.maps = parse_maps(&lines.collect::<String>());
+
+        if res.signal.is_some() || res.exception.is_some() {
+            Some(res)
+        } else {
+            None
+        }
+    }
+
+    /// Describe why the process crashed.
+    fn reason(&self) -> String {
+        match (self.signal, self.exception) {
+            (Some(signum), _) => format!("fatal signal {}", signal_name(signum)),
+            (None, Some(code)) => format!("unhandled exception {:#010x}", code),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+
+    /// Find the module and offset of an instruction pointer.
+    ///
+    /// Also returns the address of the instruction in the current process, if
+    /// its module is loaded and unchanged.
+    fn locate(
+        &self,
+        ip: usize,
+        current: &CurrentModules,
+    ) -> Option<(String, usize, Option<usize>)> {
+        if let Some(mapping) = self.maps.iter().find(|m| m.start <= ip && ip < m.end) {
+            let offset = ip - mapping.start + mapping.offset;
+
+            let unchanged = Path::new(&mapping.path) != current.exe
+                || self.exe_modified == current.exe_modified;
+            let address = current
+                .maps
+                .iter()
+                .filter(|_| unchanged)
+                .find(|m| {
+                    m.path == mapping.path
+                        && m.offset <= offset
+                        && offset < m.offset + m.end - m.start
+                })
+                .map(|m| m.start + offset - m.offset);
+
+            return Some((mapping.path.clone(), offset, address));
+        }
+
+        let (base, size) = self.module?;
+        if ip < base || ip - base >= size {
+            return None;
+        }
+
+        let offset = ip - base;
+        let address = current
+            .base
+            .filter(|_| self.exe_modified == current.exe_modified)
+            .map(|base| base + offset);
+
+        Some((current.exe.display().to_string(), offset, address))
+    }
+
+    /// Describe the frames of the crashing thread.
+    fn symbolize(&self, current: &CurrentModules) -> Vec<String> {
+        self.ips
+            .iter()
+            .map(|ip| {
+                let mut line = format!("{:#x}", ip);
+
+                if let Some((module, offset, address)) = self.locate(*ip, current) {
+                    line.push_str(&format!(" {}+{:#x}", module, offset));
+
+                    if let Some(symbol) = address.and_then(resolve_symbol) {
+                        line.push_str(&format!(" {}", symbol));
+                    }
+                }
+
+                line
+            })
+            .collect()
+    }
+}
+
+/// Parse the process ID of a raw report from its file name.
+fn raw_report_pid(path: &Path) -> Option<u32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(".crash-")?
+        .strip_suffix(".raw")?
+        .parse()
+        .ok()
+}
+
+/// Convert raw reports of crashed processes in `directory` to JSON reports.
+///
+/// Raw reports of processes that exited without crashing are deleted.
+fn convert_raw_reports(exe: &Path, directory: &Path) {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let current = CurrentModules::new(exe);
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        let pid = match raw_report_pid(&path) {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        let mut file = match open_unlocked_raw_report(&path) {
+            Some(file) => file,
+            None => continue,
+        };
+
+        let mut data = vec![];
+        if file.read_to_end(&mut data).is_err() {
+            continue;
+        }
+        drop(file);
+
+        let python_stacks_path = directory.join(format!(".python-stacks-{}", pid));
+
+        if let Some(raw) = RawReport::parse(&String::from_utf8_lossy(&data)) {
+            let time = modified_time(&path).unwrap_or_default();
+            let python_stacks = std::fs::read_to_string(&python_stacks_path).unwrap_or_default();
+
+            let report = render_report(
+                exe,
+                pid,
+                time,
+                &raw.reason(),
+                &raw.symbolize(&current),
+                &python_stacks,
+            );
+
+            if std::fs::write(
+                directory.join(format!("crash-{}-{}.json", time, pid)),
+                report,
+            )
+            .is_err()
+            {
+                continue;
+            }
+        }
+
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&python_stacks_path);
+    }
+}
+
+#[cfg(unix)]
+const FATAL_SIGNALS: &[(libc::c_int, &str)] = &[
+    (libc::SIGABRT, "SIGABRT"),
+    (libc::SIGBUS, "SIGBUS"),
+    (libc::SIGFPE, "SIGFPE"),
+    (libc::SIGILL, "SIGILL"),
+    (libc::SIGSEGV, "SIGSEGV"),
+];
+
+#[cfg(unix)]
+fn signal_name(signum: usize) -> String {
+    FATAL_SIGNALS
+        .iter()
+        .find(|(value, _)| *value as usize == signum)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| signum.to_string())
+}
+
+#[cfg(not(unix))]
+fn signal_name(signum: usize) -> String {
+    signum.to_string()
+}
+
+/// Size of the alternate signal stack.
+#[cfg(unix)]
+const SIGNAL_STACK_SIZE: usize = 64 * 1024;
+
+#[cfg(unix)]
+type SignalHandler = extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void);
+
+/// Whether a crash handler is running.
+#[cfg(unix)]
+static HANDLING_CRASH: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_signal(
+    signum: libc::c_int,
+    _info: *mut libc::siginfo_t,
+    _context: *mut libc::c_void,
+) {
+    // Only the first crashing thread writes to the raw report.
+    if !HANDLING_CRASH.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        write_raw_line(b"signal", signum as usize, 10);
+        write_raw_frames();
+        write_raw_maps();
+    }
+
+    // Restore the default disposition and raise the signal again so the
+    // process terminates like it otherwise would have.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = libc::SIG_DFL;
+        libc::sigaction(signum, &action, std::ptr::null_mut());
+        libc::raise(signum);
+    }
+}
+
+/// Load address of the module holding this code.
+#[cfg(unix)]
+fn current_module_base() -> Option<usize> {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::dladdr(current_module_base as *const libc::c_void, &mut info) } != 0 {
+        Some(info.dli_fbase as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn install_native_handlers() {
+    unsafe {
+        // The stack is never freed: handlers may run until the process exits.
+        let size = SIGNAL_STACK_SIZE.max(libc::SIGSTKSZ);
+        let stack = Box::leak(vec![0u8; size].into_boxed_slice());
+
+        let mut alternate_stack: libc::stack_t = std::mem::zeroed();
+        alternate_stack.ss_sp = stack.as_mut_ptr() as *mut libc::c_void;
+        alternate_stack.ss_size = size;
+        libc::sigaltstack(&alternate_stack, std::ptr::null_mut());
+
+        for (signum, _) in FATAL_SIGNALS {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_signal as SignalHandler as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(*signum, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Leading fields of `EXCEPTION_RECORD`.
+#[cfg(windows)]
+#[repr(C)]
+struct ExceptionRecord {
+    exception_code: u32,
+}
+
+/// `EXCEPTION_POINTERS`.
+#[cfg(windows)]
+#[repr(C)]
+struct ExceptionPointers {
+    exception_record: *const ExceptionRecord,
+    context_record: *const std::ffi::c_void,
+}
+
+#[cfg(windows)]
+type TopLevelExceptionFilter = unsafe extern "system" fn(*const ExceptionPointers) -> i32;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetUnhandledExceptionFilter(
+        filter: Option<TopLevelExceptionFilter>,
+    ) -> Option<TopLevelExceptionFilter>;
+
+    fn GetModuleHandleW(module_name: *const u16) -> *mut std::ffi::c_void;
+}
+
+/// Let Windows continue handling the exception and terminate the process.
+#[cfg(windows)]
+const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+
+/// Allow other processes to read a file we have open.
+#[cfg(windows)]
+const FILE_SHARE_READ: u32 = 0x1;
+
+#[cfg(windows)]
+unsafe extern "system" fn handle_exception(pointers: *const ExceptionPointers) -> i32 {
+    let code = pointers
+        .as_ref()
+        .and_then(|pointers| pointers.exception_record.as_ref())
+        .map(|record| record.exception_code)
+        .unwrap_or_default();
+
+    write_raw_line(b"exception", code as usize, 16);
+    write_raw_frames();
+
+    EXCEPTION_CONTINUE_SEARCH
+}
+
+/// Load address of the executable.
+#[cfg(windows)]
+fn current_module_base() -> Option<usize> {
+    let base = unsafe { GetModuleHandleW(std::ptr::null()) };
+
+    if base.is_null() {
+        None
+    } else {
+        Some(base as usize)
+    }
+}
+
+#[cfg(windows)]
+fn install_native_handlers() {
+    unsafe {
+        SetUnhandledExceptionFilter(Some(handle_exception));
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn current_module_base() -> Option<usize> {
+    None
+}
+
+#[cfg(not(any(unix, windows)))]
+fn install_native_handlers() {}
+
+/// Crash reporting for the current process.
+///
+/// Dropping the instance removes the file holding Python stacks, which is
+/// only needed if the interpreter crashes.
+pub(crate) struct CrashReporter {
+    python_stacks_path: PathBuf,
+}
+
+impl CrashReporter {
+    /// Enable crash reporting, writing reports to `directory`.
+    ///
+    /// Reports of previous crashes are converted to JSON reports. If
+    /// `upload_url` is set, they are uploaded to it.
+    ///
+    /// The interpreter must be initialized. Only the first configuration of
+    /// a process is used by the crash handlers.
+    pub(crate) fn install(
+        py: Python,
+        exe: &Path,
+        directory: &Path,
+        upload_url: Option<&str>,
+    ) -> Result<Self, NewInterpreterError> {
+        std::fs::create_dir_all(directory).map_err(|e| {
+            NewInterpreterError::Dynamic(format!(
+                "error creating crash reports directory {}: {}",
+                directory.display(),
+                e
+            ))
+        })?;
+
+        // Reports of previous crashes are handled before this process could add one.
+        convert_raw_reports(exe, directory);
+
+        if let Some(url) = upload_url {
+            run_code_in_namespace(
+                py,
+                UPLOAD_CODE,
+                &[
+                    ("directory", directory.display().to_string().to_object(py)),
+                    ("url", url.to_object(py)),
+                ],
+                "uploading crash reports",
+            )?;
+        }
+
+        let pid = std::process::id();
+
+        if RAW_REPORT.get().is_none() {
+            let raw_report_path = directory.join(format!(".crash-{}.raw", pid));
+            let raw_report = create_raw_report(&raw_report_path)
+                .and_then(|mut file| write_raw_header(&mut file, exe).map(|_| file))
+                .map_err(|e| {
+                    NewInterpreterError::Dynamic(format!(
+                        "error creating raw crash report {}: {}",
+                        raw_report_path.display(),
+                        e
+                    ))
+                })?;
+
+            // Walk the stack once so the unwinder initializes outside of the
+            // crash handlers.
+            backtrace::trace(|_| true);
+
+            let _ = RAW_REPORT.set(ProcessRawReport {
+                file: raw_report,
+                directory: directory.to_path_buf(),
+            });
+            install_native_handlers();
+        }
+
+        // Python stacks must be next to the raw report to be found when it
+        // is converted.
+        let python_stacks_path = RAW_REPORT
+            .get()
+            .map(|raw_report| raw_report.directory.as_path())
+            .unwrap_or(directory)
+            .join(format!(".python-stacks-{}", pid));
+
+        run_code_in_namespace(
+            py,
+            ENABLE_FAULTHANDLER_CODE,
+            &[(
+                "python_stacks_path",
+                python_stacks_path.display().to_string().to_object(py),
+            )],
+            "enabling faulthandler",
+        )?;
+
+        Ok(Self { python_stacks_path })
+    }
+}
+
+impl Drop for CrashReporter {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.python_stacks_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("foo"), "\"foo\"");
+        assert_eq!(
+            json_string("a \"b\"\\c\nd\u{1}"),
+            "\"a \\\"b\\\"\\\\c\\nd\\u0001\""
+        );
+    }
+
+    #[test]
+    fn test_render_report() {
+        assert_eq!(
+            render_report(
+                Path::new("/app/myapp"),
+                42,
+                1600000000,
+                "fatal signal SIGSEGV",
+                &["0x1 main".to_string(), "0x2".to_string()],
+                "Fatal Python error: Segmentation fault\n",
+            ),
+            "{\n  \
+             \"version\": 1,\n  \
+             \"executable\": \"/app/myapp\",\n  \
+             \"pid\": 42,\n  \
+             \"time\": 1600000000,\n  \
+             \"reason\": \"fatal signal SIGSEGV\",\n  \
+             \"native_stack\": [\"0x1 main\", \"0x2\"],\n  \
+             \"python_stacks\": \"Fatal Python error: Segmentation fault\\n\"\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_format_number() {
+        let mut buffer = [0; 24];
+        assert_eq!(format_number(0, 10, &mut buffer), b"0");
+        assert_eq!(format_number(11, 10, &mut buffer), b"11");
+        assert_eq!(format_number(0x7f3a00, 16, &mut buffer), b"7f3a00");
+        assert_eq!(
+            format_number(usize::MAX, 10, &mut buffer),
+            usize::MAX.to_string().as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_parse_maps() {
+        assert_eq!(
+            parse_maps(
+                "55d0c000-55d0d000 r--p 00000000 08:01 1234    /app/my app\n\
+                 55d0d000-55d0f000 r-xp 00001000 08:01 1234    /app/my app\n\
+                 7ffc1000-7ffc2000 rw-p 00000000 00:00 0       [stack]\n\
+                 7ffc3000-7ffc4000 rw-p 00000000 00:00 0\n"
+            ),
+            vec![
+                Mapping {
+                    start: 0x55d0c000,
+                    end: 0x55d0d000,
+                    offset: 0,
+                    path: "/app/my app".to_string(),
+                },
+                Mapping {
+                    start: 0x55d0d000,
+                    end: 0x55d0f000,
+                    offset: 0x1000,
+                    path: "/app/my app".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_report() {
+        assert_eq!(
+            RawReport::parse("exe_modified 5\nmodule 0x1000 0x100\n"),
+            None
+        );
+
+        let raw = RawReport::parse(
+            "exe_modified 5\n\
+             module 0x1000 0x100\n\
+             signal 11\n\
+             ip 0x1010\n\
+             ip 0x3008\n\
+             ip 0x9000\n\
+             maps\n\
+             3000-4000 r-xp 00002000 08:01 1 /lib/libfoo.so\n",
+        )
+        .unwrap();
+
+        assert_eq!(raw.exe_modified, Some(5));
+        assert_eq!(raw.module, Some((0x1000, 0x100)));
+        assert_eq!(raw.ips, vec![0x1010, 0x3008, 0x9000]);
+        assert_eq!(raw.maps.len(), 1);
+        #[cfg(unix)]
+        assert_eq!(raw.reason(), "fatal signal SIGSEGV");
+
+        // Nothing is loaded in the current process, so frames aren't resolved.
+        let current = CurrentModules {
+            exe: PathBuf::from("/app/myapp"),
+            exe_modified: Some(5),
+            base: None,
+            maps: vec![],
+        };
+        assert_eq!(
+            raw.symbolize(&current),
+            vec![
+                "0x1010 /app/myapp+0x10",
+                "0x3008 /lib/libfoo.so+0x2008",
+                "0x9000"
+            ]
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+        );
+
+        let raw = RawReport::parse(
+            "exe_modified 5\nmodule 0x1000 0x100\nexception 0xc0000005\nip 0x1010\n",
+        )
+        .unwrap();
+        assert_eq!(raw.reason(), "unhandled exception 0xc0000005");
+        assert_eq!(
+            raw.symbolize(&current),
+            vec!["0x1010 /app/myapp+0x10".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_convert_raw_reports() -> std::io::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let exe = Path::new("/app/myapp");
+
+        std::fs::write(
+            temp_dir.path().join(".crash-42.raw"),
+            "signal 6\nip 0x1010\n",
+        )?;
+        std::fs::write(
+            temp_dir.path().join(".python-stacks-42"),
+            "Fatal Python error: Aborted\n",
+        )?;
+        // A process that exited without crashing.
+        std::fs::write(
+            temp_dir.path().join(".crash-43.raw"),
+            "module 0x1000 0x100\n",
+        )?;
+        // A running process.
+        let _live = create_raw_report(&temp_dir.path().join(".crash-44.raw"))?;
+
+        convert_raw_reports(exe, temp_dir.path());
+
+        let mut names = std::fs::read_dir(temp_dir.path())?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        names.sort();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0], ".crash-44.raw");
+        assert!(names[1].starts_with("crash-") && names[1].ends_with("-42.json"));
+
+        let report = std::fs::read_to_string(temp_dir.path().join(&names[1]))?;
+        assert!(report.contains("\"native_stack\": [\"0x1010\"]"));
+        assert!(report.contains("\"python_stacks\": \"Fatal Python error: Aborted\\n\""));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Running as a Unix daemon.
+
+Forking isn't safe once the Python interpreter started threads, so this is
+done before the interpreter is initialized.
+
+Daemonizing follows the traditional double fork: the process forks, the
+child becomes the leader of a new session and forks again, so the daemon
+can't acquire a controlling terminal. The daemon changes its working
+directory to `/` and reads standard input from `/dev/null`. The original
+process waits for the daemon to finish setting up, so its exit code reflects
+whether the PID file was written, as expected by service managers like
+systemd for services of `Type=forking`.
+
+PID files are locked for as long as the process runs, so a second instance
+can't take over the PID file of a running one. They are removed when the
+interpreter is dropped.
+*/
+
+use {
+    crate::{config::ResolvedOxidizedPythonInterpreterConfig, error::NewInterpreterError},
+    std::{
+        fs::File,
+        path::{Path, PathBuf},
+    },
+};
+
+/// A PID file locked by the running process.
+pub(crate) struct PidFile {
+    path: PathBuf,
+    // The lock is held for as long as the file is open.
+    _file: File,
+    pid: u32,
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Forked children inherit the guard but not the ownership of the file.
+        if std::process::id() == self.pid {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Resolve a path relative to the current directory.
+///
+/// Paths are resolved before daemonizing changes the current directory.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn absolute_path(path: &Path, cwd: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        cwd.join(path)
+    }
+}
+
+#[cfg(unix)]
+impl PidFile {
+    /// Lock a PID file and write the PID of the current process to it.
+    fn acquire(path: &Path) -> Result<Self, NewInterpreterError> {
+        use std::{
+            io::{Read, Seek, SeekFrom, Write},
+            os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+        };
+
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .mode(0o644)
+            .open(path)
+            .map_err(|e| {
+                NewInterpreterError::Dynamic(format!(
+                    "unable to open PID file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let mut pid = String::new();
+            let _ = file.read_to_string(&mut pid);
+
+            return Err(NewInterpreterError::Dynamic(format!(
+                "PID file {} is locked by running process {}",
+                path.display(),
+                pid.trim()
+            )));
+        }
+
+        let pid = std::process::id();
+
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| writeln!(file, "{}", pid))
+            .and_then(|_| file.flush())
+            .map_err(|e| {
+                NewInterpreterError::Dynamic(format!(
+                    "unable to write PID file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            _file: file,
+            pid,
+        })
+    }
+}
+
+/// Replace the standard streams of the process.
+///
+/// `stdin` is read from `/dev/null` if `null_stdin` is set. `stdout` and
+/// `stderr` are appended to the given files. If `null_output` is set, streams
+/// without a file are written to `/dev/null`.
+#[cfg(unix)]
+fn redirect_stdio(
+    null_stdin: bool,
+    null_output: bool,
+    stdout_path: Option<&Path>,
+    stderr_path: Option<&Path>,
+) -> Result<(), NewInterpreterError> {
+    use std::os::unix::io::AsRawFd;
+
+    let open = |path: &Path, write: bool| {
+        std::fs::OpenOptions::new()
+            .read(!write)
+            .append(write)
+            .create(write)
+            .open(path)
+            .map_err(|e| {
+                NewInterpreterError::Dynamic(format!("unable to open {}: {}", path.display(), e))
+            })
+    };
+
+    let dup = |file: &File, fd: libc::c_int| {
+        if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+            Err(NewInterpreterError::Dynamic(format!(
+                "unable to redirect file descriptor {}: {}",
+                fd,
+                std::io::Error::last_os_error()
+            )))
+        } else {
+            Ok(())
+        }
+    };
+
+    let dev_null = Path::new("/dev/null");
+
+    if null_stdin {
+        dup(&open(dev_null, false)?, libc::STDIN_FILENO)?;
+    }
+
+    let stdout = match stdout_path {
+        Some(path) => Some(open(path, true)?),
+        None if null_output => Some(open(dev_null, true)?),
+        None => None,
+    };
+
+    // Both streams share a file description if they are written to the same
+    // file, so their writes don't overwrite each other.
+    let stderr = match stderr_path {
+        Some(path) if Some(path) == stdout_path => None,
+        Some(path) => Some(open(path, true)?),
+        None if null_output => Some(open(dev_null, true)?),
+        None => None,
+    };
+
+    if let Some(file) = &stdout {
+        dup(file, libc::STDOUT_FILENO)?;
+    }
+
+    match (&stderr, &stdout) {
+        (Some(file), _) => dup(file, libc::STDERR_FILENO)?,
+        (None, Some(file)) if stderr_path.is_some() => dup(file, libc::STDERR_FILENO)?,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Fork the process into a daemon.
+///
+/// Returns a pipe to write a status byte to once the daemon is set up. The
+/// original process exits with that status.
+#[cfg(unix)]
+fn daemonize() -> Result<File, NewInterpreterError> {
+    use std::{io::Read, os::unix::io::FromRawFd};
+
+    let os_error = |context: &str| {
+        NewInterpreterError::Dynamic(format!(
+            "unable to {}: {}",
+            context,
+            std::io::Error::last_os_error()
+        ))
+    };
+
+    let mut fds = [0 as libc::c_int; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(os_error("create pipe"));
+    }
+    let (mut reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+    match unsafe { libc::fork() } {
+        -1 => return Err(os_error("fork")),
+        0 => {}
+        _ => {
+            drop(writer);
+
+            // The pipe is closed without a status if the daemon failed.
+            let mut status = [1u8];
+            let _ = reader.read(&mut status);
+
+            unsafe { libc::_exit(status[0] as libc::c_int) };
+        }
+    }
+
+    drop(reader);
+
+    if unsafe { libc::setsid() } < 0 {
+        return Err(os_error("create session"));
+    }
+
+    match unsafe { libc::fork() } {
+        -1 => return Err(os_error("fork")),
+        0 => {}
+        _ => unsafe { libc::_exit(0) },
+    }
+
+    std::env::set_current_dir("/")
+        .map_err(|e| NewInterpreterError::Dynamic(format!("unable to change directory: {}", e)))?;
+
+    Ok(writer)
+}
+
+/// Daemonize, write the PID file and redirect standard streams as configured.
+///
+/// Returns the PID file, which is removed when dropped.
+#[cfg(unix)]
+pub(crate) fn prepare_process(
+    config: &ResolvedOxidizedPythonInterpreterConfig,
+) -> Result<Option<PidFile>, NewInterpreterError> {
+    use std::io::Write;
+
+    let cwd = std::env::current_dir().map_err(|e| {
+        NewInterpreterError::Dynamic(format!("unable to resolve current directory: {}", e))
+    })?;
+
+    let pid_file = config.pid_file.as_ref().map(|x| absolute_path(x, &cwd));
+    let stdout_path = config.stdout_path.as_ref().map(|x| absolute_path(x, &cwd));
+    let stderr_path = config.stderr_path.as_ref().map(|x| absolute_path(x, &cwd));
+
+    let status = if config.daemonize {
+        Some(daemonize()?)
+    } else {
+        None
+    };
+
+    // Errors are reported on the original standard streams.
+    let pid_file = pid_file.map(|path| PidFile::acquire(&path)).transpose()?;
+
+    redirect_stdio(
+        config.daemonize,
+        config.daemonize,
+        stdout_path.as_deref(),
+        stderr_path.as_deref(),
+    )?;
+
+    if let Some(mut status) = status {
+        let _ = status.write_all(&[0]);
+    }
+
+    Ok(pid_file)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn prepare_process(
+    config: &ResolvedOxidizedPythonInterpreterConfig,
+) -> Result<Option<PidFile>, NewInterpreterError> {
+    if config.daemonize
+        || config.pid_file.is_some()
+        || config.stdout_path.is_some()
+        || config.stderr_path.is_some()
+    {
+        Err(NewInterpreterError::Simple(
+            "daemonization is only supported on Unix",
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute_path() {
+        let cwd = Path::new("/srv/app");
+
+        assert_eq!(
+            absolute_path(Path::new("/run/app.pid"), cwd),
+            PathBuf::from("/run/app.pid")
+        );
+        assert_eq!(
+            absolute_path(Path::new("app.pid"), cwd),
+            PathBuf::from("/srv/app/app.pid")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pid_file() -> Result<(), NewInterpreterError> {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("app.pid");
+
+        std::fs::write(&path, "stale\n").unwrap();
+
+        let pid_file = PidFile::acquire(&path)?;
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n", std::process::id())
+        );
+
+        // Locks are per open file, so a second instance in this process conflicts.
+        let err = PidFile::acquire(&path).err().unwrap();
+        assert!(err.to_string().contains("is locked by running process"));
+
+        drop(pid_file);
+        assert!(!path.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Per-user application directories.
+
+Applications shouldn't write files next to their executable, as it may be
+installed in a read-only location and is shared by all users. Instead,
+platforms define per-user directories for settings, data, caches and logs.
+The `oxidized_runtime.dirs` Python module exposes them, so packaged
+applications don't have to know the conventions of each platform.
+*/
+
+use {
+    crate::{
+        error::NewInterpreterError,
+        osutils::{OsVariant, TARGET_OS},
+    },
+    pyo3::{prelude::*, types::PyModule},
+    std::{ffi::OsString, path::PathBuf},
+};
+
+/// Per-user directories of an application.
+///
+/// Directories may not exist. Fields are [None] when the environment doesn't
+/// define the base directory, e.g. when `HOME` isn't set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AppDirectories {
+    /// Directory holding settings.
+    pub config: Option<PathBuf>,
+    /// Directory holding data.
+    pub data: Option<PathBuf>,
+    /// Directory holding cached data, which can be deleted.
+    pub cache: Option<PathBuf>,
+    /// Directory holding log files.
+    pub log: Option<PathBuf>,
+}
+
+impl AppDirectories {
+    /// Resolve the directories of an application for the current platform.
+    ///
+    /// `author` is the organization publishing the application. It is only
+    /// part of paths on Windows, where directories are grouped by it.
+    pub fn resolve(name: &str, author: Option<&str>) -> Self {
+        Self::resolve_for(&TARGET_OS, |key| std::env::var_os(key), name, author)
+    }
+
+    fn resolve_for(
+        os: &OsVariant,
+        env: impl Fn(&str) -> Option<OsString>,
+        name: &str,
+        author: Option<&str>,
+    ) -> Self {
+        let dir = |key: &str| {
+            env(key)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        let home = |path: &str| dir("HOME").map(|home| home.join(path));
+
+        match os {
+            OsVariant::Windows => {
+                let app = |base: PathBuf| match author {
+                    Some(author) => base.join(author).join(name),
+                    None => base.join(name),
+                };
+
+                let roaming = dir("APPDATA").map(app);
+                let local = dir("LOCALAPPDATA").map(app);
+
+                Self {
+                    config: roaming.clone(),
+                    data: roaming,
+                    cache: local.as_ref().map(|path| path.join("Cache")),
+                    log: local.map(|path| path.join("Logs")),
+                }
+            }
+            OsVariant::MacOs => {
+                let support = home("Library/Application Support").map(|path| path.join(name));
+
+                Self {
+                    config: support.clone(),
+                    data: support,
+                    cache: home("Library/Caches").map(|path| path.join(name)),
+                    log: home("Library/Logs").map(|path| path.join(name)),
+                }
+            }
+            OsVariant::Linux | OsVariant::Other => {
+                let xdg = |key: &str, default: &str| {
+                    dir(key)
+                        .or_else(|| home(default))
+                        .map(|path| path.join(name))
+                };
+
+                Self {
+                    config: xdg("XDG_CONFIG_HOME", ".config"),
+                    data: xdg("XDG_DATA_HOME", ".local/share"),
+                    cache: xdg("XDG_CACHE_HOME", ".cache"),
+                    log: xdg("XDG_STATE_HOME", ".local/state").map(|path| path.join("log")),
+                }
+            }
+        }
+    }
+}
+
+/// Register the `oxidized_runtime` module, exposing directories as `oxidized_runtime.dirs`.
+pub(crate) fn install_dirs_module(
+    py: Python,
+    name: &str,
+    author: Option<&str>,
+) -> Result<(), NewInterpreterError> {
+    let dirs = AppDirectories::resolve(name, author);
+    let path = |path: Option<PathBuf>| path.map(|path| path.display().to_string());
+
+    let install = || -> PyResult<()> {
+        let runtime = PyModule::new(py, "oxidized_runtime")?;
+        let module = PyModule::new(py, "oxidized_runtime.dirs")?;
+
+        module.add("app_name", name)?;
+        module.add("app_author", author)?;
+        module.add("config_dir", path(dirs.config))?;
+        module.add("data_dir", path(dirs.data))?;
+        module.add("cache_dir", path(dirs.cache))?;
+        module.add("log_dir", path(dirs.log))?;
+        runtime.add("dirs", module)?;
+
+        let modules = py.import("sys")?.getattr("modules")?;
+        modules.set_item("oxidized_runtime", runtime)?;
+        modules.set_item("oxidized_runtime.dirs", module)?;
+
+        Ok(())
+    };
+
+    install().map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "installing oxidized_runtime"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::HashMap};
+
+    fn resolve(os: OsVariant, vars: &[(&str, &str)], author: Option<&str>) -> AppDirectories {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), OsString::from(v)))
+            .collect::<HashMap<_, _>>();
+
+        AppDirectories::resolve_for(&os, |key| vars.get(key).cloned(), "myapp", author)
+    }
+
+    #[test]
+    fn test_linux() {
+        let dirs = resolve(OsVariant::Linux, &[("HOME", "/home/user")], None);
+        assert_eq!(dirs.config, Some(PathBuf::from("/home/user/.config/myapp")));
+        assert_eq!(
+            dirs.data,
+            Some(PathBuf::from("/home/user/.local/share/myapp"))
+        );
+        assert_eq!(dirs.cache, Some(PathBuf::from("/home/user/.cache/myapp")));
+        assert_eq!(
+            dirs.log,
+            Some(PathBuf::from("/home/user/.local/state/myapp/log"))
+        );
+
+        let dirs = resolve(
+            OsVariant::Linux,
+            &[("HOME", "/home/user"), ("XDG_CONFIG_HOME", "/config")],
+            None,
+        );
+        assert_eq!(dirs.config, Some(PathBuf::from("/config/myapp")));
+
+        assert_eq!(
+            resolve(OsVariant::Linux, &[("HOME", "")], None),
+            Default::default()
+        );
+    }
+
+    #[test]
+    fn test_macos() {
+        let dirs = resolve(OsVariant::MacOs, &[("HOME", "/Users/user")], Some("ACME"));
+        assert_eq!(
+            dirs.config,
+            Some(PathBuf::from(
+                "/Users/user/Library/Application Support/myapp"
+            ))
+        );
+        assert_eq!(dirs.data, dirs.config);
+        assert_eq!(
+            dirs.cache,
+            Some(PathBuf::from("/Users/user/Library/Caches/myapp"))
+        );
+        assert_eq!(
+            dirs.log,
+            Some(PathBuf::from("/Users/user/Library/Logs/myapp"))
+        );
+    }
+
+    #[test]
+    fn test_windows() {
+        let vars = [("APPDATA", "roaming"), ("LOCALAPPDATA", "local")];
+
+        let dirs = resolve(OsVariant::Windows, &vars, Some("ACME"));
+        let roaming = PathBuf::from("roaming").join("ACME").join("myapp");
+        let local = PathBuf::from("local").join("ACME").join("myapp");
+        assert_eq!(dirs.config, Some(roaming.clone()));
+        assert_eq!(dirs.data, Some(roaming));
+        assert_eq!(dirs.cache, Some(local.join("Cache")));
+        assert_eq!(dirs.log, Some(local.join("Logs")));
+
+        let dirs = resolve(OsVariant::Windows, &vars, None);
+        assert_eq!(dirs.config, Some(PathBuf::from("roaming").join("myapp")));
+    }
+}
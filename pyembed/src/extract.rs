@@ -0,0 +1,178 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Extraction of files embedded in self-extracting executables. */
+
+use {
+    crate::NewInterpreterError,
+    sha2::Digest,
+    std::path::{Path, PathBuf},
+};
+
+/// Name of the directory an archive is extracted to by default.
+///
+/// The name is derived from the archive content, so each build of an
+/// application gets its own directory.
+pub fn default_extract_directory_name(archive: &[u8]) -> String {
+    sha2::Sha256::digest(archive)
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Extract a zstd compressed tar archive to a directory.
+///
+/// Nothing is done if the directory exists. Otherwise the archive is
+/// extracted to a sibling directory which is then renamed to `dest`, so
+/// processes starting concurrently never see partially extracted files.
+pub fn extract_archive(archive: &[u8], dest: &Path) -> Result<(), NewInterpreterError> {
+    if dest.is_dir() {
+        return Ok(());
+    }
+
+    let error = |action: &str, path: &Path, e: std::io::Error| {
+        NewInterpreterError::Dynamic(format!("{} {}: {}", action, path.display(), e))
+    };
+
+    let parent = dest.parent().ok_or_else(|| {
+        NewInterpreterError::Dynamic(format!(
+            "unable to resolve parent directory of {}",
+            dest.display()
+        ))
+    })?;
+    std::fs::create_dir_all(parent).map_err(|e| error("creating", parent, e))?;
+
+    let temp_dir = parent.join(format!(
+        ".{}.{}",
+        dest.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id()
+    ));
+
+    // Left over by a process with the same ID that was interrupted.
+    if temp_dir.exists() {
+        std::fs::remove_dir_all(&temp_dir).map_err(|e| error("removing", &temp_dir, e))?;
+    }
+
+    let decoder = zstd::stream::Decoder::new(archive)
+        .map_err(|e| error("decompressing archive to", dest, e))?;
+    if let Err(e) = tar::Archive::new(decoder).unpack(&temp_dir) {
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        return Err(error("extracting archive to", &temp_dir, e));
+    }
+
+    match std::fs::rename(&temp_dir, dest) {
+        Ok(()) => Ok(()),
+        // Another process extracted the archive first.
+        Err(_) if dest.is_dir() => {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            Err(error("renaming extracted files to", dest, e))
+        }
+    }
+}
+
+/// Resolve the directory an archive is extracted to.
+///
+/// `directory` is the configured directory, with `$ORIGIN` already expanded.
+/// Relative paths are resolved against `cache_dir`.
+pub fn resolve_extract_directory(
+    archive: &[u8],
+    directory: Option<&Path>,
+    exe: &Path,
+    cache_dir: Option<PathBuf>,
+) -> Result<PathBuf, NewInterpreterError> {
+    let path = match directory {
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(exe.file_stem().unwrap_or_default())
+            .join(default_extract_directory_name(archive)),
+    };
+
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        cache_dir
+            .map(|dir| dir.join(path))
+            .ok_or(NewInterpreterError::Simple(
+                "unable to resolve user cache directory for extract_directory",
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    fn create_archive() -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(vec![]);
+
+        let data = b"print('hello')";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, "lib/foo.py", data.as_slice())?;
+
+        Ok(zstd::stream::encode_all(
+            builder.into_inner()?.as_slice(),
+            3,
+        )?)
+    }
+
+    #[test]
+    fn test_extract_archive() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let archive = create_archive()?;
+        let dest = temp_dir.path().join("myapp").join("0123");
+
+        extract_archive(&archive, &dest)?;
+        assert_eq!(
+            std::fs::read(dest.join("lib").join("foo.py"))?,
+            b"print('hello')"
+        );
+        assert_eq!(std::fs::read_dir(dest.parent().unwrap())?.count(), 1);
+
+        // Existing directories are reused.
+        std::fs::remove_file(dest.join("lib").join("foo.py"))?;
+        extract_archive(&archive, &dest)?;
+        assert!(!dest.join("lib").join("foo.py").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_invalid_archive() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let dest = temp_dir.path().join("0123");
+
+        assert!(extract_archive(b"garbage", &dest).is_err());
+        assert!(!dest.exists());
+        assert_eq!(std::fs::read_dir(temp_dir.path())?.count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_extract_directory() -> Result<()> {
+        let exe = Path::new("/usr/bin/myapp");
+        let cache_dir = Some(PathBuf::from("/home/user/.cache"));
+
+        let path = resolve_extract_directory(b"data", None, exe, cache_dir.clone())?;
+        assert_eq!(
+            path,
+            PathBuf::from("/home/user/.cache/myapp").join(default_extract_directory_name(b"data"))
+        );
+        assert_eq!(default_extract_directory_name(b"data").len(), 16);
+
+        let path =
+            resolve_extract_directory(b"data", Some(Path::new("myapp/1.0")), exe, cache_dir)?;
+        assert_eq!(path, PathBuf::from("/home/user/.cache/myapp/1.0"));
+
+        assert!(resolve_extract_directory(b"data", None, exe, None).is_err());
+
+        Ok(())
+    }
+}
@@ -8,9 +8,13 @@ use {
     crate::{
         config::{OxidizedPythonInterpreterConfig, ResolvedOxidizedPythonInterpreterConfig},
         conversion::osstring_to_bytes,
+        dirs::install_dirs_module,
         error::NewInterpreterError,
-        osutils::resolve_terminfo_dirs,
+        osutils::{resolve_terminfo_dirs, resolve_user_data_dir},
         pyalloc::PythonMemoryAllocator,
+        pytest::run_pytest,
+        runtime_hooks::install_runtime_hooks,
+        shared_libraries::install_shared_library_hooks,
     },
     once_cell::sync::Lazy,
     oxidized_importer::{
@@ -22,15 +26,158 @@ use {
         exceptions::PyRuntimeError, ffi as pyffi, prelude::*, types::PyDict, PyTypeInfo,
         ToBorrowedObject,
     },
-    python_packaging::interpreter::{MultiprocessingStartMethod, TerminfoResolution},
+    python_packaging::interpreter::{
+        EnvironmentMutation, MultiprocessingStartMethod, TerminfoResolution, WorkingDirectoryPolicy,
+    },
     std::{
         collections::BTreeSet,
-        env, fs,
+        env,
+        ffi::OsString,
+        fs,
         io::Write,
         path::{Path, PathBuf},
     },
 };
 
+/// Python code installing runtime `sys.path` overrides.
+///
+/// Only modules and regular packages are found. Submodules are only found
+/// if their package was found in an override path.
+const RUNTIME_PATH_OVERRIDES_CODE: &str = "\
+import importlib.machinery, os, sys
+
+LOADERS = [
+    (importlib.machinery.ExtensionFileLoader, importlib.machinery.EXTENSION_SUFFIXES),
+    (importlib.machinery.SourceFileLoader, importlib.machinery.SOURCE_SUFFIXES),
+    (importlib.machinery.SourcelessFileLoader, importlib.machinery.BYTECODE_SUFFIXES),
+]
+
+class RuntimePathOverridesFinder:
+    def __init__(self, paths):
+        self.paths = paths
+
+    def find_spec(self, fullname, path=None, target=None):
+        for entry in self.paths if path is None else path:
+            if not os.path.isdir(entry):
+                continue
+            spec = importlib.machinery.FileFinder(entry, *LOADERS).find_spec(fullname, target)
+            if spec is not None and spec.loader is not None:
+                return spec
+        return None
+
+    def invalidate_caches(self):
+        pass
+
+sys.path[0:0] = paths
+sys.meta_path.insert(0, RuntimePathOverridesFinder(paths))
+";
+
+/// A helper process `multiprocessing` runs via `python -c <code>`.
+///
+/// On POSIX, the `spawn` and `forkserver` start methods run a resource tracker
+/// and the `forkserver` start method runs a fork server.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MultiprocessingHelper<'a> {
+    /// `from multiprocessing.resource_tracker import main;main(<fd>)`
+    ResourceTracker { fd: i32 },
+    /// `from multiprocessing.forkserver import main; main(<fd>, <fd>, <preload>, **<kwargs>)`
+    ///
+    /// `preload` and `kwargs` are the `repr()` of a list and a dict.
+    ForkServer {
+        listener_fd: i32,
+        alive_fd: i32,
+        preload: &'a str,
+        kwargs: &'a str,
+    },
+}
+
+/// Parse a file descriptor formatted with `%d`.
+fn parse_fd(value: &str) -> Option<i32> {
+    if !value.is_empty() && value.bytes().all(|c| c.is_ascii_digit()) {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+impl<'a> MultiprocessingHelper<'a> {
+    /// Parse the code of a helper.
+    ///
+    /// Only the exact code `multiprocessing` generates is recognized.
+    fn parse(code: &'a str) -> Option<Self> {
+        if let Some(args) = code
+            .strip_prefix("from multiprocessing.resource_tracker import main;main(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            return Some(Self::ResourceTracker {
+                fd: parse_fd(args)?,
+            });
+        }
+
+        let args = code
+            .strip_prefix("from multiprocessing.forkserver import main; main(")
+            .and_then(|s| s.strip_suffix(')'))?;
+
+        let (listener_fd, args) = args.split_once(", ")?;
+        let (alive_fd, args) = args.split_once(", ")?;
+        // Module names can't contain `]`, so the first match ends the preload list.
+        let split = args.find("], **{")?;
+        let (preload, kwargs) = (&args[..split + 1], &args[split + 5..]);
+
+        if !preload.starts_with('[') || !kwargs.ends_with('}') {
+            return None;
+        }
+
+        Some(Self::ForkServer {
+            listener_fd: parse_fd(listener_fd)?,
+            alive_fd: parse_fd(alive_fd)?,
+            preload,
+            kwargs,
+        })
+    }
+
+    /// Resolve the helper to run if process arguments denote a `multiprocessing` helper.
+    ///
+    /// Helpers are invoked with arguments `[interpreter flags] -c <code>`.
+    /// Interpreter flags are ignored.
+    fn from_argv(argv: &'a [OsString]) -> Option<Self> {
+        match argv {
+            [_, .., flag, code] if flag == "-c" => Self::parse(code.to_str()?),
+            _ => None,
+        }
+    }
+
+    /// Run the helper.
+    ///
+    /// The helper's `main()` is called directly. `repr()` values are evaluated
+    /// with `ast.literal_eval()`, so no code from the arguments is executed.
+    fn run(&self, py: Python) -> PyResult<()> {
+        match self {
+            Self::ResourceTracker { fd } => {
+                py.import("multiprocessing.resource_tracker")?
+                    .getattr("main")?
+                    .call1((*fd,))?;
+            }
+            Self::ForkServer {
+                listener_fd,
+                alive_fd,
+                preload,
+                kwargs,
+            } => {
+                let literal_eval = py.import("ast")?.getattr("literal_eval")?;
+                let preload = literal_eval.call1((*preload,))?;
+                let kwargs = literal_eval.call1((*kwargs,))?.downcast::<PyDict>()?;
+
+                py.import("multiprocessing.forkserver")?
+                    .getattr("main")?
+                    .call((*listener_fd, *alive_fd, preload), Some(kwargs))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 static GLOBAL_INTERPRETER_GUARD: Lazy<std::sync::Mutex<()>> =
     Lazy::new(|| std::sync::Mutex::new(()));
 
@@ -91,6 +238,18 @@ pub struct MainPythonInterpreter<'interpreter, 'resources: 'interpreter> {
     pub(crate) allocator: Option<PythonMemoryAllocator>,
     /// File to write containing list of modules when the interpreter finalizes.
     write_modules_path: Option<PathBuf>,
+    /// Crash reporting state, cleaned up after the interpreter finalizes.
+    #[cfg(feature = "crash-reporting")]
+    crash_reporter: Option<crate::crash::CrashReporter>,
+    /// PID file, removed after the interpreter finalizes.
+    #[cfg(feature = "daemon")]
+    pid_file: Option<crate::daemon::PidFile>,
+    /// Lock held by the single running instance of the application.
+    #[cfg(feature = "single-instance")]
+    single_instance: Option<crate::single_instance::SingleInstance>,
+    /// Captured standard streams, restored after the interpreter finalizes.
+    #[cfg(feature = "output-capture")]
+    output_captures: Option<crate::output::OutputCaptures>,
 }
 
 impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
@@ -119,6 +278,14 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             interpreter_guard: None,
             allocator: None,
             write_modules_path: None,
+            #[cfg(feature = "crash-reporting")]
+            crash_reporter: None,
+            #[cfg(feature = "daemon")]
+            pid_file: None,
+            #[cfg(feature = "single-instance")]
+            single_instance: None,
+            #[cfg(feature = "output-capture")]
+            output_captures: None,
         };
 
         res.init()?;
@@ -148,6 +315,143 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             std::env::set_var("TCL_LIBRARY", tcl_library);
         }
 
+        if let Some(tk_library) = &self.config.tk_library {
+            std::env::set_var("TK_LIBRARY", tk_library);
+        }
+
+        #[cfg(not(feature = "crash-reporting"))]
+        if self.config.crash_reports_directory.is_some() {
+            return Err(NewInterpreterError::Simple(
+                "crash reporting requires the crash-reporting feature of pyembed",
+            ));
+        }
+
+        #[cfg(not(feature = "updater"))]
+        if self.config.update_feed_url.is_some() {
+            return Err(NewInterpreterError::Simple(
+                "update checks require the updater feature of pyembed",
+            ));
+        }
+
+        #[cfg(not(feature = "daemon"))]
+        if self.config.daemonize
+            || self.config.pid_file.is_some()
+            || self.config.stdout_path.is_some()
+            || self.config.stderr_path.is_some()
+        {
+            return Err(NewInterpreterError::Simple(
+                "daemonization requires the daemon feature of pyembed",
+            ));
+        }
+
+        #[cfg(not(feature = "output-capture"))]
+        if self.config.stdout_capture.is_some() || self.config.stderr_capture.is_some() {
+            return Err(NewInterpreterError::Simple(
+                "output capture requires the output-capture feature of pyembed",
+            ));
+        }
+
+        if (self.config.stdout_path.is_some() && self.config.stdout_capture.is_some())
+            || (self.config.stderr_path.is_some() && self.config.stderr_capture.is_some())
+        {
+            return Err(NewInterpreterError::Simple(
+                "standard streams can't be both redirected to a path and captured",
+            ));
+        }
+
+        // Forking is only safe before the interpreter starts threads.
+        // Multiprocessing workers belong to a process that is set up already.
+        #[cfg(feature = "daemon")]
+        if !self.is_multiprocessing() {
+            self.pid_file = crate::daemon::prepare_process(&self.config)?;
+        }
+
+        // Captured after daemonization, which replaces standard streams.
+        #[cfg(feature = "output-capture")]
+        if self.config.stdout_capture.is_some() || self.config.stderr_capture.is_some() {
+            let log_dir = crate::dirs::AppDirectories::resolve(
+                &self.config.app_name(),
+                self.config.app_author.as_deref(),
+            )
+            .log;
+
+            self.output_captures = Some(crate::output::OutputCaptures::start(
+                self.config.stdout_capture.as_ref(),
+                self.config.stderr_capture.as_ref(),
+                log_dir,
+            )?);
+        }
+
+        #[cfg(not(feature = "single-instance"))]
+        if self.config.single_instance.is_some() {
+            return Err(NewInterpreterError::Simple(
+                "single instance enforcement requires the single-instance feature of pyembed",
+            ));
+        }
+
+        // Workers of the running instance are started by running the executable.
+        #[cfg(feature = "single-instance")]
+        let single_instance_listener = match &self.config.single_instance {
+            Some(name) if !self.is_multiprocessing() => {
+                match crate::single_instance::acquire(name)? {
+                    Some((instance, listener)) => {
+                        self.single_instance = Some(instance);
+                        Some(listener)
+                    }
+                    None if self.config.single_instance_forward_args => {
+                        let cwd = env::current_dir().map_err(|e| {
+                            NewInterpreterError::Dynamic(format!(
+                                "unable to resolve current directory: {}",
+                                e
+                            ))
+                        })?;
+
+                        crate::single_instance::forward_args(
+                            name,
+                            &cwd,
+                            self.config.resolve_sys_argv().get(1..).unwrap_or_default(),
+                        )?;
+
+                        std::process::exit(0);
+                    }
+                    None => {
+                        return Err(NewInterpreterError::Dynamic(format!(
+                            "another instance of {} is already running",
+                            name
+                        )));
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        #[cfg(not(feature = "logging-bridge"))]
+        if self.config.logging_bridge {
+            return Err(NewInterpreterError::Simple(
+                "forwarding log records requires the logging-bridge feature of pyembed",
+            ));
+        }
+
+        #[cfg(not(feature = "splash-screen"))]
+        if self.config.splash_screen_image.is_some() {
+            return Err(NewInterpreterError::Simple(
+                "splash screens require the splash-screen feature of pyembed",
+            ));
+        }
+
+        #[cfg(feature = "splash-screen")]
+        if let Some(image) = self.config.splash_screen_image {
+            if !self.is_multiprocessing() {
+                crate::splash::show(image, self.config.splash_screen_timeout)?;
+            }
+        }
+
+        for mutation in &self.config.environment {
+            apply_environment_mutation(mutation)?;
+        }
+
+        apply_working_directory_policy(self.config.working_directory, self.config.exe())?;
+
         set_pyimport_inittab(&self.config);
 
         // Pre-configure Python.
@@ -248,6 +552,38 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
         self.write_modules_path =
             self.with_gil(|py| self.init_post_main(py, oxidized_finder_loaded))?;
 
+        #[cfg(feature = "crash-reporting")]
+        if let Some(directory) = &self.config.crash_reports_directory {
+            self.crash_reporter = Some(self.with_gil(|py| {
+                crate::crash::CrashReporter::install(
+                    py,
+                    self.config.exe(),
+                    directory,
+                    self.config.crash_reports_upload_url.as_deref(),
+                )
+            })?);
+        }
+
+        #[cfg(feature = "updater")]
+        if self.config.update_feed_url.is_some() {
+            self.with_gil(|py| crate::updater::start_background_update(py, &self.config))?;
+        }
+
+        #[cfg(feature = "single-instance")]
+        if let Some(listener) = single_instance_listener {
+            self.with_gil(|py| listener.install(py))?;
+        }
+
+        #[cfg(feature = "splash-screen")]
+        if self.config.splash_screen_image.is_some() {
+            self.with_gil(crate::splash::install_splash_module)?;
+        }
+
+        #[cfg(feature = "logging-bridge")]
+        if self.config.logging_bridge {
+            self.with_gil(crate::logging_bridge::install_logging_module)?;
+        }
+
         debug_assert_eq!(unsafe { pyffi::PyGILState_Check() }, 0);
 
         Ok(())
@@ -271,30 +607,35 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             NewInterpreterError::new_from_pyerr(py, err, "import of oxidized importer module")
         })?;
 
-        let cb = |importer_state: &mut ImporterState| match self.config.multiprocessing_start_method
-        {
-            MultiprocessingStartMethod::None => {}
-            MultiprocessingStartMethod::Fork
-            | MultiprocessingStartMethod::ForkServer
-            | MultiprocessingStartMethod::Spawn => {
-                importer_state.set_multiprocessing_set_start_method(Some(
-                    self.config.multiprocessing_start_method.to_string(),
-                ));
+        let cb = |importer_state: &mut ImporterState| {
+            match self.config.multiprocessing_start_method {
+                MultiprocessingStartMethod::None => {}
+                MultiprocessingStartMethod::Fork
+                | MultiprocessingStartMethod::ForkServer
+                | MultiprocessingStartMethod::Spawn => {
+                    importer_state.set_multiprocessing_set_start_method(Some(
+                        self.config.multiprocessing_start_method.to_string(),
+                    ));
+                }
+                MultiprocessingStartMethod::Auto => {
+                    // Windows uses "spawn" because "fork" isn't available.
+                    // Everywhere else uses "fork." The default on macOS is "spawn." This
+                    // is due to https://bugs.python.org/issue33725, which only affects
+                    // Python framework builds. Our assumption is we aren't using a Python
+                    // framework, so "spawn" is safe.
+                    let method = if cfg!(target_family = "windows") {
+                        "spawn"
+                    } else {
+                        "fork"
+                    };
+
+                    importer_state.set_multiprocessing_set_start_method(Some(method.to_string()));
+                }
             }
-            MultiprocessingStartMethod::Auto => {
-                // Windows uses "spawn" because "fork" isn't available.
-                // Everywhere else uses "fork." The default on macOS is "spawn." This
-                // is due to https://bugs.python.org/issue33725, which only affects
-                // Python framework builds. Our assumption is we aren't using a Python
-                // framework, so "spawn" is safe.
-                let method = if cfg!(target_family = "windows") {
-                    "spawn"
-                } else {
-                    "fork"
-                };
 
-                importer_state.set_multiprocessing_set_start_method(Some(method.to_string()));
-            }
+            importer_state.set_user_site_packages(self.config.user_site_packages.clone());
+            importer_state.set_coverage_mode(self.config.coverage_mode);
+            importer_state.set_debugger_support(self.config.debugger_support);
         };
 
         // Ownership of the resources state is transferred into the importer, where the Box
@@ -377,6 +718,49 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
                     "installing OxidizedFinder in sys.path_hooks",
                 )
             })?;
+
+            // Packages installed by install_to_user_site() during previous runs.
+            if let Some(path) = &self.config.user_site_packages {
+                if path.is_dir() {
+                    finder
+                        .call_method1("index_directory", (path.display().to_string(),))
+                        .map_err(|err| {
+                            NewInterpreterError::new_from_pyerr(
+                                py,
+                                err,
+                                "indexing user site-packages directory",
+                            )
+                        })?;
+                }
+            }
+        }
+
+        if !self.config.shared_library_search_paths.is_empty() {
+            install_shared_library_hooks(py, &self.config.shared_library_search_paths)?;
+        }
+
+        if !self.config.runtime_hooks.is_empty() {
+            install_runtime_hooks(py, &self.config.runtime_hooks)?;
+        }
+
+        install_dirs_module(
+            py,
+            &self.config.app_name(),
+            self.config.app_author.as_deref(),
+        )?;
+        install_metadata_module(py, &self.config.runtime_metadata)?;
+
+        if self.config.allow_runtime_path_overrides && cfg!(debug_assertions) {
+            if let Some(value) = env::var_os(self.config.runtime_path_overrides_env()) {
+                let paths = env::split_paths(&value)
+                    .filter(|path| !path.as_os_str().is_empty())
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>();
+
+                if !paths.is_empty() {
+                    install_runtime_path_overrides(py, paths)?;
+                }
+            }
         }
 
         if self.config.argvb {
@@ -436,6 +820,10 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
             }
         }
 
+        if let Some(path) = &self.config.fault_handler_path {
+            enable_fault_handler(py, path)?;
+        }
+
         let write_modules_path = if let Some(key) = &self.config.write_modules_directory_env {
             if let Ok(path) = std::env::var(key) {
                 let path = PathBuf::from(path);
@@ -513,6 +901,10 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
     /// This should be called when `sys.argv[1] == "--multiprocessing-fork"`. It
     /// will parse arguments for the worker from `sys.argv` and call into the
     /// `multiprocessing` module to perform work.
+    ///
+    /// It should also be called when the process was invoked to run a
+    /// `multiprocessing` helper, like the resource tracker, via `-c <code>`.
+    /// The helper is run if the code is exactly what `multiprocessing` generates.
     pub fn run_multiprocessing(&self) -> PyResult<i32> {
         // This code effectively reimplements multiprocessing.spawn.freeze_support(),
         // except entirely in the Rust domain. This function effectively verifies
@@ -523,6 +915,14 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
 
         let argv = self.config.resolve_sys_argv().to_vec();
 
+        if let Some(helper) = MultiprocessingHelper::from_argv(&argv) {
+            return self.with_gil(|py| {
+                helper.run(py)?;
+
+                Ok(0)
+            });
+        }
+
         if argv.len() < 2 {
             panic!("run_multiprocessing() called prematurely; sys.argv does not indicate multiprocessing mode");
         }
@@ -568,12 +968,44 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
     /// Whether the Python interpreter is in "multiprocessing worker" mode.
     ///
     /// The `multiprocessing` module can work by spawning new processes
-    /// with arguments `--multiprocessing-fork [key=value] ...`. This function
+    /// with arguments `--multiprocessing-fork [key=value] ...`. Helper processes
+    /// are spawned with arguments `[interpreter flags] -c <code>`. This function
     /// detects if the current Python interpreter is configured for said execution.
     pub fn is_multiprocessing(&self) -> bool {
         let argv = self.config.resolve_sys_argv();
 
-        argv.len() >= 2 && argv[1] == "--multiprocessing-fork"
+        (argv.len() >= 2 && argv[1] == "--multiprocessing-fork")
+            || MultiprocessingHelper::from_argv(argv).is_some()
+    }
+
+    /// Obtain a value of the metadata embedded in the application.
+    ///
+    /// See [crate::OxidizedPythonInterpreterConfig::runtime_metadata].
+    pub fn runtime_metadata(&self, key: &str) -> Option<&str> {
+        self.config
+            .runtime_metadata
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Run `pytest` against test modules importable via `OxidizedFinder`.
+    ///
+    /// Arguments after `sys.argv[0]` are passed to `pytest`. Positional
+    /// arguments name packages or modules to collect tests from instead of
+    /// paths. All test modules are collected if none are given.
+    ///
+    /// Returns the exit code of `pytest`.
+    pub fn run_pytest(&self) -> PyResult<i32> {
+        let args = self
+            .config
+            .resolve_sys_argv()
+            .iter()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+
+        self.with_gil(|py| run_pytest(py, self.config.origin(), args))
     }
 
     /// Runs the Python interpreter.
@@ -582,21 +1014,27 @@ impl<'interpreter, 'resources> MainPythonInterpreter<'interpreter, 'resources> {
     /// current process invocation appears to be a spawned multiprocessing worker
     /// and dispatch to multiprocessing accordingly.
     ///
+    /// If `pytest` is enabled, this delegates to [Self::run_pytest].
+    ///
     /// Otherwise, this delegates to [Self::py_runmain].
     pub fn run(self) -> i32 {
-        if self.config.multiprocessing_auto_dispatch && self.is_multiprocessing() {
-            match self.run_multiprocessing() {
-                Ok(code) => code,
-                Err(e) => {
-                    self.with_gil(|py| {
-                        e.print(py);
-                    });
-
-                    1
-                }
-            }
+        let res = if self.config.multiprocessing_auto_dispatch && self.is_multiprocessing() {
+            self.run_multiprocessing()
+        } else if self.config.run_pytest {
+            self.run_pytest()
         } else {
-            self.py_runmain()
+            return self.py_runmain();
+        };
+
+        match res {
+            Ok(code) => code,
+            Err(e) => {
+                self.with_gil(|py| {
+                    e.print(py);
+                });
+
+                1
+            }
         }
     }
 }
@@ -674,6 +1112,182 @@ fn set_pyimport_inittab(config: &OxidizedPythonInterpreterConfig) {
     }
 }
 
+/// Run Python code in a new namespace holding `values`.
+///
+/// Code runs in its own namespace so functions it defines can resolve its
+/// imports. Returns the namespace, which holds what the code defined.
+pub(crate) fn run_code_in_new_namespace<'p>(
+    py: Python<'p>,
+    code: &str,
+    values: &[(&str, PyObject)],
+) -> PyResult<&'p PyDict> {
+    let globals = PyDict::new(py);
+    globals.set_item("__builtins__", py.import("builtins")?)?;
+    for (name, value) in values {
+        globals.set_item(name, value)?;
+    }
+
+    py.run(code, Some(globals), None)?;
+
+    Ok(globals)
+}
+
+/// Like [run_code_in_new_namespace], describing errors with `context`.
+pub(crate) fn run_code_in_namespace<'p>(
+    py: Python<'p>,
+    code: &str,
+    values: &[(&str, PyObject)],
+    context: &str,
+) -> Result<&'p PyDict, NewInterpreterError> {
+    run_code_in_new_namespace(py, code, values)
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, context))
+}
+
+/// Search `paths` for modules before any other importer.
+fn install_runtime_path_overrides(
+    py: Python,
+    paths: Vec<String>,
+) -> Result<(), NewInterpreterError> {
+    run_code_in_namespace(
+        py,
+        RUNTIME_PATH_OVERRIDES_CODE,
+        &[("paths", paths.to_object(py))],
+        "installing runtime path overrides",
+    )?;
+
+    Ok(())
+}
+
+/// Register the `oxidized_runtime.metadata` module.
+fn install_metadata_module(
+    py: Python,
+    metadata: &[(String, String)],
+) -> Result<(), NewInterpreterError> {
+    let install = || -> PyResult<()> {
+        let values = PyDict::new(py);
+        for (key, value) in metadata {
+            values.set_item(key, value)?;
+        }
+        let values = py
+            .import("types")?
+            .getattr("MappingProxyType")?
+            .call1((values,))?;
+
+        let module = PyModule::new(py, "oxidized_runtime.metadata")?;
+        module.add("values", values)?;
+        module.add("get", values.getattr("get")?)?;
+
+        py.import("oxidized_runtime")?.setattr("metadata", module)?;
+        py.import("sys")?
+            .getattr("modules")?
+            .set_item("oxidized_runtime.metadata", module)?;
+
+        Ok(())
+    };
+
+    install().map_err(|e| {
+        NewInterpreterError::new_from_pyerr(py, e, "installing oxidized_runtime.metadata")
+    })
+}
+
+/// Apply a change to an environment variable of the current process.
+fn apply_environment_mutation(mutation: &EnvironmentMutation) -> Result<(), NewInterpreterError> {
+    let (name, path, prepend) = match mutation {
+        EnvironmentMutation::Set(name, value) => {
+            env::set_var(name, value);
+            return Ok(());
+        }
+        EnvironmentMutation::Unset(name) => {
+            env::remove_var(name);
+            return Ok(());
+        }
+        EnvironmentMutation::Prepend(name, value) => (name, PathBuf::from(value), true),
+        EnvironmentMutation::Append(name, value) => (name, PathBuf::from(value), false),
+    };
+
+    let mut paths = env::var_os(name)
+        .map(|value| {
+            env::split_paths(&value)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if !paths.contains(&path) {
+        if prepend {
+            paths.insert(0, path);
+        } else {
+            paths.push(path);
+        }
+    }
+
+    let value = env::join_paths(paths)
+        .map_err(|e| NewInterpreterError::Dynamic(format!("unable to set {}: {}", name, e)))?;
+    env::set_var(name, value);
+
+    Ok(())
+}
+
+/// Change the working directory of the current process according to a policy.
+fn apply_working_directory_policy(
+    policy: WorkingDirectoryPolicy,
+    exe: &Path,
+) -> Result<(), NewInterpreterError> {
+    let path = match policy {
+        WorkingDirectoryPolicy::Unchanged => return Ok(()),
+        WorkingDirectoryPolicy::ExecutableDirectory => exe
+            .parent()
+            .ok_or(NewInterpreterError::Simple(
+                "unable to obtain current executable parent directory",
+            ))?
+            .to_path_buf(),
+        WorkingDirectoryPolicy::UserDataDirectory => {
+            let path = resolve_user_data_dir()
+                .ok_or(NewInterpreterError::Simple(
+                    "unable to resolve user data directory for working_directory",
+                ))?
+                .join(exe.file_stem().unwrap_or_default());
+
+            fs::create_dir_all(&path).map_err(|e| {
+                NewInterpreterError::Dynamic(format!("creating {}: {}", path.display(), e))
+            })?;
+
+            path
+        }
+    };
+
+    env::set_current_dir(&path).map_err(|e| {
+        NewInterpreterError::Dynamic(format!(
+            "changing working directory to {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Enable `faulthandler` for all threads, appending tracebacks to a file.
+fn enable_fault_handler(py: Python, path: &Path) -> Result<(), NewInterpreterError> {
+    let file = py
+        .import("io")
+        .and_then(|io| io.getattr("open"))
+        .and_then(|open| open.call1((path.display().to_string(), "a")))
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "opening fault handler file"))?;
+
+    let kwargs = PyDict::new(py);
+    kwargs
+        .set_item("file", file)
+        .and_then(|_| kwargs.set_item("all_threads", true))
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "building kwargs"))?;
+
+    // faulthandler keeps a reference to the file, keeping it open.
+    py.import("faulthandler")
+        .and_then(|faulthandler| faulthandler.getattr("enable"))
+        .and_then(|enable| enable.call((), Some(kwargs)))
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "enabling faulthandler"))?;
+
+    Ok(())
+}
+
 /// Write loaded Python modules to a directory.
 ///
 /// Given a Python interpreter and a path to a directory, this will create a
@@ -713,6 +1327,9 @@ fn write_modules_to_path(py: Python, path: &Path) -> Result<(), &'static str> {
 
 impl<'interpreter, 'resources> Drop for MainPythonInterpreter<'interpreter, 'resources> {
     fn drop(&mut self) {
+        #[cfg(feature = "splash-screen")]
+        crate::splash::close_window();
+
         // Interpreter may have been finalized already. Possibly through our invocation
         // of Py_RunMain(). Possibly something out-of-band beyond our control. We don't
         // muck with the interpreter after finalization because this will likely result
@@ -736,3 +1353,79 @@ impl<'interpreter, 'resources> Drop for MainPythonInterpreter<'interpreter, 'res
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<OsString> {
+        values.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_multiprocessing_helper_argv() {
+        let code = "from multiprocessing.resource_tracker import main;main(5)";
+        let tracker = Some(MultiprocessingHelper::ResourceTracker { fd: 5 });
+
+        assert_eq!(
+            MultiprocessingHelper::from_argv(&args(&["myapp", "-c", code])),
+            tracker
+        );
+        assert_eq!(
+            MultiprocessingHelper::from_argv(&args(&[
+                "myapp", "-E", "-s", "-W", "ignore", "-c", code
+            ])),
+            tracker
+        );
+        assert_eq!(
+            MultiprocessingHelper::from_argv(&args(&["myapp", "-c", "import os"])),
+            None
+        );
+        assert_eq!(MultiprocessingHelper::from_argv(&args(&["-c", code])), None);
+        assert_eq!(
+            MultiprocessingHelper::from_argv(&args(&["myapp", "--multiprocessing-fork"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_multiprocessing_helper_parse() {
+        assert_eq!(
+            MultiprocessingHelper::parse(
+                "from multiprocessing.forkserver import main; \
+                 main(7, 8, ['__main__'], **{'sys_path': ['/app', '/lib']})"
+            ),
+            Some(MultiprocessingHelper::ForkServer {
+                listener_fd: 7,
+                alive_fd: 8,
+                preload: "['__main__']",
+                kwargs: "{'sys_path': ['/app', '/lib']}",
+            })
+        );
+        assert_eq!(
+            MultiprocessingHelper::parse(
+                "from multiprocessing.forkserver import main; main(7, 8, [], **{})"
+            ),
+            Some(MultiprocessingHelper::ForkServer {
+                listener_fd: 7,
+                alive_fd: 8,
+                preload: "[]",
+                kwargs: "{}",
+            })
+        );
+
+        for code in [
+            "from multiprocessing.resource_tracker import main;main(5); import os",
+            "from multiprocessing.resource_tracker import main;main(os.system('id'))",
+            "from multiprocessing.resource_tracker import main;main(-5)",
+            "from multiprocessing.resource_tracker import main;main()",
+            "from multiprocessing.forkserver import main; import os",
+            "from multiprocessing.forkserver import main; main(7, 8, [], **{}); import os",
+            "from multiprocessing.forkserver import main; main(7, 8, [], {})",
+            "from multiprocessing.forkserver import main; main(7, x, [], **{})",
+            "from multiprocessing.forkserver import main; main(7, 8, f(), **{})",
+        ] {
+            assert_eq!(MultiprocessingHelper::parse(code), None, "{}", code);
+        }
+    }
+}
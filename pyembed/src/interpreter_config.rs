@@ -591,6 +591,12 @@ impl<'a> TryInto<pyffi::PyConfig> for &'a ResolvedOxidizedPythonInterpreterConfi
             set_argv(&mut config, argv)?;
         }
 
+        if let Some(frames) = self.tracemalloc_frames {
+            config.tracemalloc = frames
+                .try_into()
+                .map_err(|_| NewInterpreterError::Simple("tracemalloc_frames is too large"))?;
+        }
+
         if self.exe.is_none() {
             return Err(NewInterpreterError::Simple(
                 "current executable not set; must call ensure_origin() 1st",
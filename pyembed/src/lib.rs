@@ -48,40 +48,151 @@ The optional `allocator-snmalloc` feature controls support for using
 [snmalloc](https://github.com/microsoft/snmalloc) as Python's memory allocator.
 The feature behaves similarly to `jemalloc`, which is documented above.
 
+The optional `asyncio-bridge` feature provides the [asyncio_bridge] module for
+running an asyncio event loop alongside a tokio runtime, so Rust and Python
+code can await each other.
+
+The optional `crash-reporting` feature controls support for writing crash
+reports when the process crashes. Crash reporting is enabled at run-time by
+setting [OxidizedPythonInterpreterConfig::crash_reports_directory].
+
+The optional `daemon` feature controls support for running as a Unix daemon,
+writing a PID file and redirecting standard streams to files before the
+interpreter is initialized. These are enabled at run-time by setting
+[OxidizedPythonInterpreterConfig::daemonize],
+[OxidizedPythonInterpreterConfig::pid_file],
+[OxidizedPythonInterpreterConfig::stdout_path] and
+[OxidizedPythonInterpreterConfig::stderr_path].
+
+The optional `logging-bridge` feature controls support for forwarding records
+of Python's `logging` module to the `tracing` crate, so they reach the
+subscriber installed by the application. This is enabled at run-time by
+setting [OxidizedPythonInterpreterConfig::logging_bridge].
+
+The optional `output-capture` feature controls support for capturing output
+written to standard output and error into a function or a rotated file. This
+is enabled at run-time by setting
+[OxidizedPythonInterpreterConfig::stdout_capture] and
+[OxidizedPythonInterpreterConfig::stderr_capture].
+
+The optional `runtime-config` feature controls support for overriding settings
+from a `<name>.runtime.toml` file next to the executable, which eases
+debugging of shipped binaries. The settings that can be overridden are chosen
+at build time by setting
+[OxidizedPythonInterpreterConfig::runtime_config_overrides].
+
+The optional `self-extracting` feature controls support for extracting an
+archive of files embedded in the executable to a per-user cache directory
+before the interpreter is initialized. Extraction is enabled at run-time by
+setting [OxidizedPythonInterpreterConfig::extract_archive].
+
+The optional `single-instance` feature controls support for allowing a single
+running instance of an application, with later instances exiting or forwarding
+their arguments to the running instance before the interpreter is initialized.
+This is enabled at run-time by setting
+[OxidizedPythonInterpreterConfig::single_instance].
+
+The optional `splash-screen` feature controls support for showing an image
+in a window on Windows and macOS while the interpreter initializes. This is
+enabled at run-time by setting
+[OxidizedPythonInterpreterConfig::splash_screen_image].
+
 The optional `serialization` feature controls whether configuration types
 (such as [OxidizedPythonInterpreterConfig]) implement `Serialize` and
 `Deserialize`.
+
+The optional `update-patches` feature provides the [update_patch] module for
+applying update patches produced by `pyoxidizer diff-build`.
+
+The optional `updater` feature provides the [updater] module for checking for
+and installing signed updates of the running executable. Background update
+checks are enabled at run-time by setting
+[OxidizedPythonInterpreterConfig::update_feed_url].
+
+The optional `windows-service` feature provides the [windows_service] module
+for running the application as a Windows service, dispatching service control
+events to Python.
+
+# Auto-Updates
+
+Applications distributed with the [Squirrel](https://github.com/Squirrel/Squirrel.Windows)
+auto-update framework can use [SquirrelApplication] to check for and apply
+updates from a release feed.
+
+Applications shipping their own updates can instead download the binary
+patches produced by `pyoxidizer diff-build` and apply them with
+[update_patch::apply_update_patch], which requires the `update-patches`
+feature.
+
+# Calling Python Functions
+
+The [export_api] macro generates typed Rust wrappers around functions of a
+Python module, converting arguments and results and reporting failures as
+[ApiError]. See the [api] module.
 */
 
+pub mod api;
+#[cfg(feature = "asyncio-bridge")]
+pub mod asyncio_bridge;
+mod cli;
 #[allow(unused)]
 mod config;
 mod conversion;
+#[cfg(feature = "crash-reporting")]
+mod crash;
+#[cfg(feature = "daemon")]
+mod daemon;
+mod dirs;
 mod error;
+#[cfg(feature = "self-extracting")]
+mod extract;
 mod interpreter;
 mod interpreter_config;
+#[cfg(feature = "logging-bridge")]
+mod logging_bridge;
 mod osutils;
+#[cfg(feature = "output-capture")]
+mod output;
 mod pyalloc;
+mod pytest;
+#[cfg(feature = "runtime-config")]
+mod runtime_config;
+mod runtime_hooks;
+mod shared_libraries;
+#[cfg(feature = "single-instance")]
+mod single_instance;
+#[cfg(feature = "splash-screen")]
+mod splash;
+mod squirrel;
 pub mod technotes;
 #[cfg(test)]
 mod test;
+#[cfg(feature = "update-patches")]
+pub mod update_patch;
+#[cfg(feature = "updater")]
+pub mod updater;
+#[cfg(feature = "windows-service")]
+pub mod windows_service;
 
 #[allow(unused_imports)]
 pub use {
     crate::{
+        api::ApiError,
         config::{
-            ExtensionModule, OxidizedPythonInterpreterConfig,
+            ExtensionModule, OutputCallback, OutputCapture, OxidizedPythonInterpreterConfig,
             ResolvedOxidizedPythonInterpreterConfig,
         },
         error::NewInterpreterError,
         interpreter::MainPythonInterpreter,
         pyalloc::PythonMemoryAllocator,
+        squirrel::{SquirrelApplication, SquirrelRelease},
     },
     oxidized_importer::{PackedResourcesSource, PythonResourcesState},
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-            TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, EnvironmentMutation,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
+            PythonInterpreterProfile, TerminfoResolution, WorkingDirectoryPolicy,
         },
         resource::BytecodeOptimizationLevel,
     },
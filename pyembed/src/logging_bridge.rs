@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Forwarding of Python log records to `tracing`.
+
+Applications embedding Python usually log from both Rust and Python. The
+`oxidized_runtime.logging` Python module provides a `logging.Handler`
+emitting records as `tracing` events, so the subscriber installed by the
+application receives a single stream of events.
+
+Events have the `python` target. The name of the Python logger is recorded
+in the `logger` field, along with the `file` and `line` the record was
+emitted from.
+*/
+
+use {
+    crate::error::NewInterpreterError,
+    pyo3::{prelude::*, types::PyModule, wrap_pyfunction},
+    tracing::{level_filters::LevelFilter, Level},
+};
+
+/// Python code of the `oxidized_runtime.logging` module.
+const LOGGING_CODE: &str = "\
+import logging, sys
+
+
+class Handler(logging.Handler):
+    '''Forwards log records to the tracing subscriber of the application.'''
+
+    def emit(self, record):
+        try:
+            _emit(record.levelno, record.name, self.format(record),
+                  record.pathname, record.lineno, '')
+        except Exception:
+            self.handleError(record)
+
+
+def emit(level, msg, *args, logger='python', **fields):
+    '''Emit an event to the tracing subscriber of the application.
+
+    ``level`` is a logging level, e.g. ``logging.INFO`` or ``'info'``.
+    Keyword arguments are recorded as fields of the event.'''
+    if isinstance(level, str):
+        level = logging.getLevelName(level.upper())
+    if args:
+        msg = msg % args
+    frame = sys._getframe(1)
+    _emit(level, logger, str(msg), frame.f_code.co_filename, frame.f_lineno,
+          ' '.join('%s=%r' % item for item in fields.items()))
+";
+
+/// Convert a Python logging level to a `tracing` level.
+fn tracing_level(level: i64) -> Level {
+    match level {
+        level if level >= 40 => Level::ERROR,
+        level if level >= 30 => Level::WARN,
+        level if level >= 20 => Level::INFO,
+        level if level >= 10 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Convert the most verbose enabled `tracing` level to a Python logging level.
+fn python_level(filter: LevelFilter) -> i64 {
+    match filter.into_level() {
+        Some(Level::ERROR) => 40,
+        Some(Level::WARN) => 30,
+        Some(Level::INFO) => 20,
+        Some(Level::DEBUG) => 10,
+        Some(Level::TRACE) => 0,
+        // Above CRITICAL.
+        None => 100,
+    }
+}
+
+/// Emit a `tracing` event.
+#[pyfunction]
+#[pyo3(name = "_emit")]
+fn emit(level: i64, logger: &str, message: &str, file: &str, line: u32, fields: &str) {
+    // Levels of events must be constants.
+    macro_rules! event {
+        ($level:expr) => {
+            if fields.is_empty() {
+                tracing::event!(target: "python", $level, logger, file, line, "{}", message)
+            } else {
+                tracing::event!(target: "python", $level, logger, file, line, fields, "{}", message)
+            }
+        };
+    }
+
+    match tracing_level(level) {
+        Level::ERROR => event!(Level::ERROR),
+        Level::WARN => event!(Level::WARN),
+        Level::INFO => event!(Level::INFO),
+        Level::DEBUG => event!(Level::DEBUG),
+        Level::TRACE => event!(Level::TRACE),
+    }
+}
+
+/// Register the `oxidized_runtime.logging` module and forward records of the root logger.
+///
+/// The level of the root logger is set to the most verbose level enabled
+/// by the current `tracing` subscriber, so records it discards aren't created.
+pub(crate) fn install_logging_module(py: Python) -> Result<(), NewInterpreterError> {
+    let install = || -> PyResult<()> {
+        let module = PyModule::from_code(
+            py,
+            LOGGING_CODE,
+            "oxidized_runtime/logging.py",
+            "oxidized_runtime.logging",
+        )?;
+        module.add_function(wrap_pyfunction!(emit, module)?)?;
+        py.import("oxidized_runtime")?.setattr("logging", module)?;
+
+        let root = py.import("logging")?.call_method0("getLogger")?;
+        root.call_method1("addHandler", (module.getattr("Handler")?.call0()?,))?;
+        root.call_method1("setLevel", (python_level(LevelFilter::current()),))?;
+
+        Ok(())
+    };
+
+    install().map_err(|e| {
+        NewInterpreterError::new_from_pyerr(py, e, "installing oxidized_runtime.logging")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(tracing_level(50), Level::ERROR);
+        assert_eq!(tracing_level(30), Level::WARN);
+        assert_eq!(tracing_level(25), Level::INFO);
+        assert_eq!(tracing_level(10), Level::DEBUG);
+        assert_eq!(tracing_level(5), Level::TRACE);
+
+        assert_eq!(python_level(LevelFilter::OFF), 100);
+        assert_eq!(python_level(LevelFilter::WARN), 30);
+        assert_eq!(python_level(LevelFilter::TRACE), 0);
+    }
+}
@@ -36,7 +36,7 @@ static TERMINFO_DIRS_COMMON: Lazy<Vec<PathBuf>> = Lazy::new(|| {
 });
 
 #[derive(Clone)]
-enum OsVariant {
+pub(crate) enum OsVariant {
     Linux,
     MacOs,
     Windows,
@@ -49,7 +49,7 @@ enum LinuxDistroVariant {
     Unknown,
 }
 
-static TARGET_OS: Lazy<OsVariant> = Lazy::new(|| {
+pub(crate) static TARGET_OS: Lazy<OsVariant> = Lazy::new(|| {
     if cfg!(target_os = "linux") {
         OsVariant::Linux
     } else if cfg!(target_os = "macos") {
@@ -141,3 +141,40 @@ pub fn resolve_terminfo_dirs() -> Option<String> {
         OsVariant::Other => None,
     }
 }
+
+/// Resolve the directory holding per-user application data.
+///
+/// This is `%APPDATA%` on Windows, `~/Library/Application Support` on macOS,
+/// and `$XDG_DATA_HOME` or `~/.local/share` elsewhere.
+pub fn resolve_user_data_dir() -> Option<PathBuf> {
+    let non_empty = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    match *TARGET_OS {
+        OsVariant::Windows => non_empty("APPDATA").map(PathBuf::from),
+        OsVariant::MacOs => {
+            non_empty("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+        }
+        OsVariant::Linux | OsVariant::Other => non_empty("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".local/share"))),
+    }
+}
+
+/// Resolve the directory holding per-user cached data.
+///
+/// This is `%LOCALAPPDATA%` on Windows, `~/Library/Caches` on macOS,
+/// and `$XDG_CACHE_HOME` or `~/.cache` elsewhere.
+#[cfg(feature = "self-extracting")]
+pub fn resolve_user_cache_dir() -> Option<PathBuf> {
+    let non_empty = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    match *TARGET_OS {
+        OsVariant::Windows => non_empty("LOCALAPPDATA").map(PathBuf::from),
+        OsVariant::MacOs => {
+            non_empty("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+        }
+        OsVariant::Linux | OsVariant::Other => non_empty("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".cache"))),
+    }
+}
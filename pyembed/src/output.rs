@@ -0,0 +1,410 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Capture of output written to standard streams.
+
+Standard streams are replaced by pipes at the file descriptor level, so
+output of Python and native code is captured alike. Background threads read
+the pipes and pass output to its destination.
+
+Windows applications built for the GUI subsystem start without standard
+streams. Python then sets `sys.stdout` and `sys.stderr` to `None` and writes
+to them fail. Capturing gives them valid streams.
+*/
+
+use {
+    crate::{
+        config::{OutputCallback, OutputCapture},
+        error::NewInterpreterError,
+    },
+    std::{
+        fs::File,
+        io::Write,
+        path::{Path, PathBuf},
+        sync::{mpsc, Arc, Mutex},
+        time::Duration,
+    },
+};
+
+/// Appends to a file, rotating it once it grows past a size.
+struct RotatingFileWriter {
+    path: PathBuf,
+    max_size: Option<u64>,
+    backups: usize,
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingFileWriter {
+    fn open(path: &Path, max_size: Option<u64>, backups: usize) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            backups,
+            file: Some(file),
+            size,
+        })
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        // Windows can't rename open files.
+        self.file = None;
+
+        if self.backups == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.backups).rev() {
+                let path = self.backup_path(index);
+                if path.exists() {
+                    std::fs::rename(&path, self.backup_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.backup_path(1))?;
+        }
+
+        self.file = Some(File::create(&self.path)?);
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if let Some(max_size) = self.max_size {
+            if self.size > 0 && self.size + data.len() as u64 > max_size {
+                self.rotate()?;
+            }
+        }
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            // A previous rotation failed.
+            None => self.file.insert(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            ),
+        };
+        file.write_all(data)?;
+        self.size += data.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Destination of output read from a pipe.
+#[derive(Clone)]
+enum Sink {
+    Callback(OutputCallback),
+    File(Arc<Mutex<RotatingFileWriter>>),
+}
+
+impl Sink {
+    fn write(&self, data: &[u8]) {
+        match self {
+            Self::Callback(callback) => callback(data),
+            // There is nowhere left to report errors to.
+            Self::File(writer) => {
+                if let Ok(mut writer) = writer.lock() {
+                    let _ = writer.write(data);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetStdHandle(std_handle: u32) -> isize;
+    fn SetStdHandle(std_handle: u32, handle: isize) -> i32;
+}
+
+/// A standard stream replaced by a pipe.
+struct CapturedStream {
+    fd: libc::c_int,
+    /// Duplicate of the original file descriptor, or -1 if it wasn't valid.
+    original: libc::c_int,
+    #[cfg(windows)]
+    original_handle: isize,
+    done: mpsc::Receiver<()>,
+}
+
+impl CapturedStream {
+    fn start(fd: libc::c_int, sink: Sink) -> Result<Self, NewInterpreterError> {
+        let error = |action: &str| {
+            NewInterpreterError::Dynamic(format!(
+                "unable to {} for file descriptor {}: {}",
+                action,
+                fd,
+                std::io::Error::last_os_error()
+            ))
+        };
+
+        let mut fds = [0; 2];
+
+        // The read end must not leak into child processes, or the pipe is
+        // never closed while they run.
+        #[cfg(unix)]
+        unsafe {
+            if libc::pipe(fds.as_mut_ptr()) != 0 {
+                return Err(error("create pipe"));
+            }
+            libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        #[cfg(windows)]
+        unsafe {
+            if libc::pipe(fds.as_mut_ptr(), 65536, libc::O_BINARY | libc::O_NOINHERIT) != 0 {
+                return Err(error("create pipe"));
+            }
+        }
+
+        let [read_fd, write_fd] = fds;
+
+        #[cfg(windows)]
+        let original_handle = unsafe { GetStdHandle(std_handle(fd)) };
+
+        let original = unsafe { libc::dup(fd) };
+
+        if unsafe { libc::dup2(write_fd, fd) } < 0 {
+            let e = error("redirect output");
+            unsafe {
+                libc::close(read_fd);
+                libc::close(write_fd);
+                if original >= 0 {
+                    libc::close(original);
+                }
+            }
+            return Err(e);
+        }
+        unsafe {
+            libc::close(write_fd);
+        }
+
+        // Native code on Windows writes to standard handles rather than to
+        // file descriptors of the C runtime.
+        #[cfg(windows)]
+        unsafe {
+            SetStdHandle(std_handle(fd), libc::get_osfhandle(fd));
+        }
+
+        let (sender, done) = mpsc::channel();
+
+        let thread = std::thread::Builder::new()
+            .name(format!("output-capture-{}", fd))
+            .spawn(move || {
+                let mut buffer = [0u8; 8192];
+
+                loop {
+                    let count = unsafe {
+                        libc::read(read_fd, buffer.as_mut_ptr() as *mut _, buffer.len() as _)
+                    };
+
+                    if count > 0 {
+                        sink.write(&buffer[..count as usize]);
+                    } else if count < 0
+                        && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+                    {
+                        continue;
+                    } else {
+                        break;
+                    }
+                }
+
+                unsafe {
+                    libc::close(read_fd);
+                }
+                let _ = sender.send(());
+            });
+
+        let stream = Self {
+            fd,
+            original,
+            #[cfg(windows)]
+            original_handle,
+            done,
+        };
+
+        // Dropping the stream restores the original stream.
+        thread.map_err(|e| {
+            NewInterpreterError::Dynamic(format!("error starting output capture thread: {}", e))
+        })?;
+
+        Ok(stream)
+    }
+}
+
+#[cfg(windows)]
+fn std_handle(fd: libc::c_int) -> u32 {
+    // STD_OUTPUT_HANDLE and STD_ERROR_HANDLE.
+    if fd == 1 {
+        -11i32 as u32
+    } else {
+        -12i32 as u32
+    }
+}
+
+impl Drop for CapturedStream {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+
+        // Replacing the file descriptor closes the write end of the pipe,
+        // which ends the thread once it read everything.
+        unsafe {
+            if self.original >= 0 {
+                libc::dup2(self.original, self.fd);
+                libc::close(self.original);
+            } else {
+                libc::close(self.fd);
+            }
+        }
+
+        #[cfg(windows)]
+        unsafe {
+            SetStdHandle(std_handle(self.fd), self.original_handle);
+        }
+
+        // Child processes inheriting the pipe keep it open. Don't wait for
+        // them.
+        let _ = self.done.recv_timeout(Duration::from_secs(1));
+    }
+}
+
+/// Standard streams captured for the lifetime of an interpreter.
+pub(crate) struct OutputCaptures {
+    // Restored in reverse order, so errors about stdout can still be seen.
+    _stderr: Option<CapturedStream>,
+    _stdout: Option<CapturedStream>,
+}
+
+impl OutputCaptures {
+    /// Start capturing standard streams.
+    ///
+    /// Relative file paths are resolved against `log_dir`.
+    pub(crate) fn start(
+        stdout: Option<&OutputCapture>,
+        stderr: Option<&OutputCapture>,
+        log_dir: Option<PathBuf>,
+    ) -> Result<Self, NewInterpreterError> {
+        let mut files: Vec<(PathBuf, Arc<Mutex<RotatingFileWriter>>)> = vec![];
+
+        let mut sink = |capture: &OutputCapture| -> Result<Sink, NewInterpreterError> {
+            match capture {
+                OutputCapture::Callback(callback) => Ok(Sink::Callback(callback.clone())),
+                OutputCapture::File {
+                    path,
+                    max_size,
+                    backups,
+                } => {
+                    let path = if path.is_absolute() {
+                        path.clone()
+                    } else {
+                        log_dir
+                            .as_ref()
+                            .ok_or(NewInterpreterError::Simple(
+                                "unable to resolve user log directory for output capture",
+                            ))?
+                            .join(path)
+                    };
+
+                    // Streams written to the same file share a writer, so
+                    // rotation doesn't race.
+                    if let Some((_, writer)) = files.iter().find(|(p, _)| p == &path) {
+                        return Ok(Sink::File(writer.clone()));
+                    }
+
+                    let writer =
+                        RotatingFileWriter::open(&path, *max_size, *backups).map_err(|e| {
+                            NewInterpreterError::Dynamic(format!(
+                                "unable to open {}: {}",
+                                path.display(),
+                                e
+                            ))
+                        })?;
+                    let writer = Arc::new(Mutex::new(writer));
+                    files.push((path, writer.clone()));
+
+                    Ok(Sink::File(writer))
+                }
+            }
+        };
+
+        let stdout = stdout.map(&mut sink).transpose()?;
+        let stderr = stderr.map(&mut sink).transpose()?;
+
+        let stdout = stdout
+            .map(|sink| CapturedStream::start(1, sink))
+            .transpose()?;
+        let stderr = stderr
+            .map(|sink| CapturedStream::start(2, sink))
+            .transpose()?;
+
+        Ok(Self {
+            _stderr: stderr,
+            _stdout: stdout,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    #[test]
+    fn test_rotating_file() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("logs").join("myapp.log");
+
+        let mut writer = RotatingFileWriter::open(&path, Some(10), 2)?;
+        writer.write(b"12345")?;
+        writer.write(b"67890")?;
+        assert_eq!(std::fs::read(&path)?, b"1234567890");
+
+        writer.write(b"abc")?;
+        writer.write(b"defghijklmno")?;
+        writer.write(b"xyz")?;
+        assert_eq!(std::fs::read(&path)?, b"xyz");
+        assert_eq!(std::fs::read(writer.backup_path(1))?, b"defghijklmno");
+        assert_eq!(std::fs::read(writer.backup_path(2))?, b"abc");
+        assert!(!writer.backup_path(3).exists());
+
+        // Reopened files are appended to.
+        drop(writer);
+        let mut writer = RotatingFileWriter::open(&path, None, 0)?;
+        writer.write(b"123456789012")?;
+        assert_eq!(std::fs::read(&path)?, b"xyz123456789012");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotating_file_without_backups() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("myapp.log");
+
+        let mut writer = RotatingFileWriter::open(&path, Some(4), 0)?;
+        writer.write(b"1234")?;
+        writer.write(b"5678")?;
+        assert_eq!(std::fs::read(&path)?, b"5678");
+        assert_eq!(std::fs::read_dir(temp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Running `pytest` against in-memory modules.
+
+`pytest` collects tests by walking the filesystem. Test modules loaded by
+`OxidizedFinder` may not exist on the filesystem. So a plugin replaces
+collection: test modules are found by enumerating the resources indexed by
+`OxidizedFinder`. Module names are matched against the `python_files`
+option, like file names are during regular collection. `conftest` modules
+of packages containing selected test modules are registered as plugins.
+
+Collection nodes are given synthetic paths beneath the directory of the
+executable, which is also used as `rootdir`. So node IDs look like the ones
+of a source checkout: e.g. `myapp/tests/test_foo.py::test_bar`.
+
+`pytest` rewrites `assert` statements when importing test modules from the
+filesystem. The plugin installs a meta path finder which does the same for
+test and `conftest` modules whose source is available from `OxidizedFinder`.
+*/
+
+use {crate::interpreter::run_code_in_new_namespace, pyo3::prelude::*, std::path::Path};
+
+/// Python code defining the `pytest` plugin.
+///
+/// `main(rootdir, args)` runs `pytest` and returns its exit code.
+const PYTEST_PLUGIN_CODE: &str = "\
+import ast, fnmatch, importlib, os, pathlib, sys
+
+import pytest
+from _pytest.assertion.rewrite import rewrite_asserts
+
+
+def oxidized_finder():
+    for finder in sys.meta_path:
+        if type(finder).__name__ == 'OxidizedFinder':
+            return finder
+    raise RuntimeError('OxidizedFinder not found on sys.meta_path')
+
+
+class AssertionRewriter:
+    def __init__(self, finder, config, names):
+        self.finder = finder
+        self.config = config
+        self.names = set(names)
+
+    def find_spec(self, fullname, path=None, target=None):
+        if fullname not in self.names or self.finder.get_source(fullname) is None:
+            return None
+        spec = self.finder.find_spec(fullname, path, target)
+        if spec is not None:
+            spec.loader = self
+        return spec
+
+    def create_module(self, spec):
+        return None
+
+    def exec_module(self, module):
+        name = module.__spec__.name
+        source = self.finder.get_source(name)
+        filename = module.__spec__.origin or name
+        tree = ast.parse(source, filename)
+        rewrite_asserts(tree, source.encode('utf-8'), filename, self.config)
+        exec(compile(tree, filename, 'exec', dont_inherit=True), module.__dict__)
+
+    def invalidate_caches(self):
+        pass
+
+
+class OxidizedModule(pytest.Module):
+    def _getobj(self):
+        return importlib.import_module(self.module_name)
+
+
+class OxidizedPlugin:
+    def __init__(self, rootdir):
+        self.rootdir = rootdir
+
+    def selected(self, name, targets):
+        return not targets or any(
+            name == target or name.startswith(target + '.') for target in targets
+        )
+
+    def module_path(self, name):
+        parts = name.split('.')
+        parts[-1] += '.py'
+        return pathlib.Path(os.path.join(self.rootdir, *parts))
+
+    @pytest.hookimpl(tryfirst=True)
+    def pytest_collection(self, session):
+        config = session.config
+        finder = oxidized_finder()
+        targets = config.option.file_or_dir
+        patterns = config.getini('python_files')
+
+        modules = {}
+        for resource in finder.indexed_resources():
+            if resource.is_module and self.selected(resource.name, targets):
+                modules[resource.name] = resource.is_package
+
+        names = sorted(
+            name
+            for name, is_package in modules.items()
+            if not is_package
+            and any(
+                fnmatch.fnmatch(name.rpartition('.')[2] + '.py', pattern)
+                for pattern in patterns
+            )
+        )
+
+        packages = {name.rpartition('.')[0] for name in names}
+        conftests = sorted(
+            package + '.conftest'
+            for package in packages
+            if package and package + '.conftest' in modules
+        )
+
+        if config.getoption('assertmode') == 'rewrite':
+            sys.meta_path.insert(0, AssertionRewriter(finder, config, names + conftests))
+
+        for name in conftests:
+            config.pluginmanager.register(importlib.import_module(name), name)
+
+        items = []
+        session.items = items
+        for name in names:
+            module = OxidizedModule.from_parent(session, path=self.module_path(name))
+            module.module_name = name
+            items.extend(session.genitems(module))
+
+        hook = config.hook
+        hook.pytest_collection_modifyitems(session=session, config=config, items=items)
+        hook.pytest_collection_finish(session=session)
+        session.testscollected = len(items)
+
+        return True
+
+
+def main(rootdir, args):
+    if not any(arg == '--rootdir' or arg.startswith('--rootdir=') for arg in args):
+        args = ['--rootdir', rootdir] + args
+
+    args = ['-p', 'no:cacheprovider'] + args
+
+    return int(pytest.main(args, plugins=[OxidizedPlugin(rootdir)]))
+";
+
+/// Run `pytest` with command line arguments `args`.
+///
+/// Positional arguments name packages or modules to collect tests from. All
+/// test modules are collected if none are given. Other arguments are
+/// interpreted by `pytest`.
+///
+/// Returns the exit code of `pytest`.
+pub(crate) fn run_pytest(py: Python, rootdir: &Path, args: Vec<String>) -> PyResult<i32> {
+    let globals = run_code_in_new_namespace(
+        py,
+        PYTEST_PLUGIN_CODE,
+        &[("__name__", "oxidized_pytest".to_object(py))],
+    )?;
+
+    globals
+        .get_item("main")
+        .expect("main() should be defined")
+        .call1((rootdir.display().to_string(), args))?
+        .extract::<i32>()
+}
@@ -0,0 +1,277 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Overriding settings from a TOML file next to the executable.
+
+Shipped binaries can't be rebuilt to investigate a problem. Applications
+allowing it read `<name>.runtime.toml` next to the `<name>` executable, e.g.
+
+```toml
+optimization_level = 0
+verbose = true
+allocator_backend = "default"
+module_search_paths = ["$ORIGIN/debug"]
+```
+
+Only settings named in
+[crate::OxidizedPythonInterpreterConfig::runtime_config_overrides] can be set
+by the file. Other settings, unknown settings and values of the wrong type are
+errors, so mistakes in the file aren't silently ignored.
+*/
+
+use {
+    crate::{config::OxidizedPythonInterpreterConfig, error::NewInterpreterError},
+    python_packaging::{interpreter::MemoryAllocatorBackend, resource::BytecodeOptimizationLevel},
+    std::{
+        convert::TryFrom,
+        path::{Path, PathBuf},
+    },
+    toml::{value::Table, Value},
+};
+
+/// Settings that can be overridden by a runtime configuration file.
+pub(crate) const RUNTIME_CONFIG_SETTINGS: &[&str] = &[
+    "allocator_backend",
+    "allocator_debug",
+    "development_mode",
+    "fault_handler",
+    "fault_handler_path",
+    "filesystem_importer",
+    "module_search_paths",
+    "optimization_level",
+    "tracemalloc_frames",
+    "verbose",
+    "warn_options",
+];
+
+/// Path of the runtime configuration file of an executable.
+pub(crate) fn runtime_config_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_stem().unwrap_or_default().to_os_string();
+    name.push(".runtime.toml");
+
+    exe.with_file_name(name)
+}
+
+fn invalid(path: &Path, key: &str, expected: &str) -> NewInterpreterError {
+    NewInterpreterError::Dynamic(format!(
+        "invalid value of {} in {}: expected {}",
+        key,
+        path.display(),
+        expected
+    ))
+}
+
+fn as_bool(path: &Path, key: &str, value: &Value) -> Result<bool, NewInterpreterError> {
+    value
+        .as_bool()
+        .ok_or_else(|| invalid(path, key, "a boolean"))
+}
+
+fn as_str<'v>(path: &Path, key: &str, value: &'v Value) -> Result<&'v str, NewInterpreterError> {
+    value.as_str().ok_or_else(|| invalid(path, key, "a string"))
+}
+
+fn as_strings(path: &Path, key: &str, value: &Value) -> Result<Vec<String>, NewInterpreterError> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(|s| s.to_string()))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| invalid(path, key, "an array of strings"))
+}
+
+/// Apply settings of a runtime configuration file to a config.
+///
+/// Nothing is done if the file doesn't exist.
+pub(crate) fn apply_runtime_config(
+    config: &mut OxidizedPythonInterpreterConfig,
+    path: &Path,
+) -> Result<(), NewInterpreterError> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(NewInterpreterError::Dynamic(format!(
+                "unable to read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+
+    let table = toml::from_str::<Table>(&data).map_err(|e| {
+        NewInterpreterError::Dynamic(format!("unable to parse {}: {}", path.display(), e))
+    })?;
+
+    for (key, value) in &table {
+        if !RUNTIME_CONFIG_SETTINGS.contains(&key.as_str()) {
+            return Err(NewInterpreterError::Dynamic(format!(
+                "unknown setting in {}: {}",
+                path.display(),
+                key
+            )));
+        }
+        if !config.runtime_config_overrides.contains(key) {
+            return Err(NewInterpreterError::Dynamic(format!(
+                "setting {} in {} can't be overridden by this application",
+                key,
+                path.display()
+            )));
+        }
+
+        let key = key.as_str();
+
+        match key {
+            "allocator_backend" => {
+                config.allocator_backend =
+                    MemoryAllocatorBackend::try_from(as_str(path, key, value)?)
+                        .map_err(|_| invalid(path, key, "an allocator backend"))?;
+            }
+            "allocator_debug" => {
+                config.allocator_debug = as_bool(path, key, value)?;
+            }
+            "development_mode" => {
+                config.interpreter_config.development_mode = Some(as_bool(path, key, value)?);
+            }
+            "fault_handler" => {
+                config.interpreter_config.fault_handler = Some(as_bool(path, key, value)?);
+            }
+            "fault_handler_path" => {
+                config.fault_handler_path = Some(PathBuf::from(as_str(path, key, value)?));
+            }
+            "filesystem_importer" => {
+                config.filesystem_importer = as_bool(path, key, value)?;
+            }
+            "module_search_paths" => {
+                config.interpreter_config.module_search_paths = Some(
+                    as_strings(path, key, value)?
+                        .into_iter()
+                        .map(PathBuf::from)
+                        .collect(),
+                );
+            }
+            "optimization_level" => {
+                config.interpreter_config.optimization_level = Some(
+                    value
+                        .as_integer()
+                        .and_then(|level| i32::try_from(level).ok())
+                        .and_then(|level| BytecodeOptimizationLevel::try_from(level).ok())
+                        .ok_or_else(|| invalid(path, key, "0, 1 or 2"))?,
+                );
+            }
+            "tracemalloc_frames" => {
+                config.tracemalloc_frames = Some(
+                    value
+                        .as_integer()
+                        .and_then(|frames| u32::try_from(frames).ok())
+                        .ok_or_else(|| invalid(path, key, "a number of frames"))?,
+                );
+            }
+            "verbose" => {
+                config.interpreter_config.verbose = Some(as_bool(path, key, value)?);
+            }
+            "warn_options" => {
+                config.interpreter_config.warn_options = Some(as_strings(path, key, value)?);
+            }
+            _ => unreachable!("unhandled runtime setting {}", key),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    fn apply(overrides: &[&str], data: &str) -> Result<OxidizedPythonInterpreterConfig<'static>> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = runtime_config_path(&temp_dir.path().join("myapp.exe"));
+        std::fs::write(&path, data)?;
+
+        let mut config = OxidizedPythonInterpreterConfig {
+            runtime_config_overrides: overrides.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        apply_runtime_config(&mut config, &path)?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn test_runtime_config_path() {
+        assert_eq!(
+            runtime_config_path(Path::new("/opt/myapp/myapp")),
+            PathBuf::from("/opt/myapp/myapp.runtime.toml")
+        );
+        assert_eq!(
+            runtime_config_path(Path::new("/opt/myapp/myapp.exe")),
+            PathBuf::from("/opt/myapp/myapp.runtime.toml")
+        );
+    }
+
+    #[test]
+    fn test_missing_file() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+
+        let mut config = OxidizedPythonInterpreterConfig::default();
+        apply_runtime_config(&mut config, &temp_dir.path().join("myapp.runtime.toml"))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply() -> Result<()> {
+        let config = apply(
+            RUNTIME_CONFIG_SETTINGS,
+            "allocator_backend = 'rust'\n\
+             allocator_debug = true\n\
+             fault_handler_path = '$ORIGIN/faults.log'\n\
+             module_search_paths = ['$ORIGIN/debug', '/src']\n\
+             optimization_level = 2\n\
+             tracemalloc_frames = 10\n\
+             verbose = true\n\
+             warn_options = ['error']\n",
+        )?;
+
+        assert_eq!(config.allocator_backend, MemoryAllocatorBackend::Rust);
+        assert!(config.allocator_debug);
+        assert_eq!(
+            config.fault_handler_path,
+            Some(PathBuf::from("$ORIGIN/faults.log"))
+        );
+        assert_eq!(
+            config.interpreter_config.module_search_paths,
+            Some(vec![PathBuf::from("$ORIGIN/debug"), PathBuf::from("/src")])
+        );
+        assert_eq!(
+            config.interpreter_config.optimization_level,
+            Some(BytecodeOptimizationLevel::Two)
+        );
+        assert_eq!(config.tracemalloc_frames, Some(10));
+        assert_eq!(config.interpreter_config.verbose, Some(true));
+        assert_eq!(
+            config.interpreter_config.warn_options,
+            Some(vec!["error".to_string()])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |overrides: &[&str], data: &str| apply(overrides, data).unwrap_err().to_string();
+
+        assert!(err(&["verbose"], "optimization_level = 1").contains("can't be overridden"));
+        assert!(err(&["verbose"], "color = true").contains("unknown setting"));
+        assert!(err(&["verbose"], "verbose = 'yes'").contains("expected a boolean"));
+        assert!(
+            err(&["optimization_level"], "optimization_level = 3").contains("expected 0, 1 or 2")
+        );
+        assert!(err(&["verbose"], "verbose = ").contains("unable to parse"));
+    }
+}
@@ -0,0 +1,98 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Python code patching modules after they are imported.
+
+Some packages need patching to work when packaged, e.g. because they expect
+files to exist on the filesystem. A meta path finder wraps the loaders of
+hooked modules so hook code runs after the module is executed, before the
+importer gets the module back. So names other modules import from it are
+already patched.
+
+Hook code runs in its own namespace, with the imported module as `module`.
+*/
+
+use {
+    crate::{error::NewInterpreterError, interpreter::run_code_in_namespace},
+    pyo3::prelude::*,
+    std::collections::BTreeMap,
+};
+
+/// Python code installing the runtime hooks.
+const RUNTIME_HOOKS_CODE: &str = "\
+import importlib.util, sys
+
+
+def run_hooks(module):
+    for code in hooks[module.__name__]:
+        exec(code, {'__name__': '__oxidized_runtime_hook__', 'module': module})
+
+
+class RuntimeHookLoader:
+    def __init__(self, loader):
+        self.loader = loader
+
+    def __getattr__(self, name):
+        return getattr(self.loader, name)
+
+    def create_module(self, spec):
+        return self.loader.create_module(spec)
+
+    def exec_module(self, module):
+        self.loader.exec_module(module)
+        run_hooks(module)
+
+
+class RuntimeHookFinder:
+    def __init__(self):
+        self.resolving = set()
+
+    def find_spec(self, fullname, path=None, target=None):
+        if fullname not in hooks or fullname in self.resolving:
+            return None
+        self.resolving.add(fullname)
+        try:
+            spec = importlib.util.find_spec(fullname)
+        finally:
+            self.resolving.discard(fullname)
+        if spec is not None and spec.loader is not None:
+            spec.loader = RuntimeHookLoader(spec.loader)
+        return spec
+
+    def invalidate_caches(self):
+        pass
+
+
+for name in hooks:
+    if name in sys.modules:
+        run_hooks(sys.modules[name])
+
+sys.meta_path.insert(0, RuntimeHookFinder())
+";
+
+/// Run Python code after modules are imported.
+///
+/// `hooks` are pairs of module names and code to run after the module is
+/// imported. Code for the same module runs in order.
+pub(crate) fn install_runtime_hooks(
+    py: Python,
+    hooks: &[(String, String)],
+) -> Result<(), NewInterpreterError> {
+    let mut by_module = BTreeMap::<&str, Vec<&str>>::new();
+    for (module, code) in hooks {
+        by_module
+            .entry(module.as_str())
+            .or_default()
+            .push(code.as_str());
+    }
+
+    run_code_in_namespace(
+        py,
+        RUNTIME_HOOKS_CODE,
+        &[("hooks", by_module.to_object(py))],
+        "installing runtime hooks",
+    )?;
+
+    Ok(())
+}
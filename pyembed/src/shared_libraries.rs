@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Resolution of shared libraries packaged with the application.
+
+Packages loading shared libraries via `ctypes` commonly locate them with
+`ctypes.util.find_library()`, which searches system locations. Shared
+libraries installed next to the executable aren't found there. So
+`find_library()` is wrapped to search the directories holding packaged
+shared libraries first.
+
+`ctypes.util` is patched when it is imported, so `ctypes` isn't loaded at
+startup. On Windows, the directories are also registered via
+`os.add_dll_directory()`, so DLL dependencies of extension modules and
+libraries loaded by `ctypes` are found.
+*/
+
+use {
+    crate::{error::NewInterpreterError, interpreter::run_code_in_namespace},
+    pyo3::prelude::*,
+    std::path::PathBuf,
+};
+
+/// Python code installing the shared library resolution hooks.
+const SHARED_LIBRARY_HOOKS_CODE: &str = "\
+import glob, importlib.util, os, sys
+
+if sys.platform == 'win32':
+    PATTERNS = ['{name}.dll', 'lib{name}.dll', '{name}-*.dll', 'lib{name}-*.dll']
+elif sys.platform == 'darwin':
+    PATTERNS = [
+        'lib{name}.dylib', '{name}.dylib', 'lib{name}.*.dylib', '{name}.framework/{name}'
+    ]
+else:
+    PATTERNS = ['lib{name}.so', 'lib{name}.so.*', 'lib{name}-*.so*']
+
+
+def find_packaged_library(name):
+    for directory in paths:
+        for pattern in PATTERNS:
+            pattern = pattern.format(name=glob.escape(name))
+            matches = sorted(glob.glob(os.path.join(glob.escape(directory), pattern)))
+            if matches:
+                return matches[0]
+    return None
+
+
+def patch_find_library(module):
+    find_library = module.find_library
+
+    def wrapper(name):
+        return find_packaged_library(name) or find_library(name)
+
+    module.find_library = wrapper
+
+
+class CtypesUtilLoader:
+    def __init__(self, loader):
+        self.loader = loader
+
+    def __getattr__(self, name):
+        return getattr(self.loader, name)
+
+    def create_module(self, spec):
+        return self.loader.create_module(spec)
+
+    def exec_module(self, module):
+        self.loader.exec_module(module)
+        patch_find_library(module)
+
+
+class CtypesUtilFinder:
+    def find_spec(self, fullname, path=None, target=None):
+        if fullname != 'ctypes.util':
+            return None
+        sys.meta_path.remove(self)
+        spec = importlib.util.find_spec(fullname)
+        if spec is not None and spec.loader is not None:
+            spec.loader = CtypesUtilLoader(spec.loader)
+        return spec
+
+    def invalidate_caches(self):
+        pass
+
+
+if sys.platform == 'win32':
+    dll_directories = [os.add_dll_directory(p) for p in paths if os.path.isdir(p)]
+
+if 'ctypes.util' in sys.modules:
+    patch_find_library(sys.modules['ctypes.util'])
+else:
+    sys.meta_path.insert(0, CtypesUtilFinder())
+";
+
+/// Search `paths` for shared libraries before system locations.
+pub(crate) fn install_shared_library_hooks(
+    py: Python,
+    paths: &[PathBuf],
+) -> Result<(), NewInterpreterError> {
+    let paths = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+
+    run_code_in_namespace(
+        py,
+        SHARED_LIBRARY_HOOKS_CODE,
+        &[("paths", paths.to_object(py))],
+        "installing shared library hooks",
+    )?;
+
+    Ok(())
+}
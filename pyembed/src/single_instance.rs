@@ -0,0 +1,494 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Allowing a single running instance of an application.
+
+Desktop applications often must not run more than once per user, e.g.
+because they own a window or a database. Opening a document from a file
+manager then starts a second process, which should hand the document to the
+running instance instead. This is decided before the interpreter is
+initialized, so later instances exit without paying for Python startup.
+
+The first instance locks a file named after the instance name in a per-user
+directory. On Unix, this is `$XDG_RUNTIME_DIR` or a directory only accessible
+by the current user in the temporary directory. Files in it not owned by the
+current user are rejected, so other users can't impersonate the running
+instance. The operating system releases the lock when the process exits,
+including when it crashes, so stale locks don't prevent applications from
+starting.
+
+The first instance also accepts connections on the loopback interface. The
+port and a random token authenticating clients are written to a second file
+next to the lock file. Later instances failing to acquire the lock either
+exit with an error or send their arguments and working directory to the
+running instance and exit successfully.
+
+Python code receives forwarded arguments by registering a handler with
+`oxidized_runtime.single_instance.set_handler()`. It is called from a
+background thread with the list of arguments, without the executable, and
+the working directory of the instance that forwarded them. Arguments
+received before a handler is registered are delivered when it is.
+*/
+
+use {
+    crate::error::NewInterpreterError,
+    pyo3::{prelude::*, types::PyModule},
+    std::{
+        ffi::OsString,
+        fs::File,
+        io::{Read, Write},
+        net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+};
+
+/// Python code receiving forwarded arguments.
+const SINGLE_INSTANCE_CODE: &str = "\
+import hmac, socket, sys, threading
+
+_lock = threading.Lock()
+_handler = None
+_pending = []
+
+
+def set_handler(handler):
+    '''Register a function called with the arguments and working directory
+    of instances started later.'''
+    global _handler
+    with _lock:
+        _handler = handler
+        pending = _pending[:]
+        del _pending[:]
+    for args, cwd in pending:
+        handler(args, cwd)
+
+
+def _deliver(args, cwd):
+    with _lock:
+        handler = _handler
+        if handler is None:
+            _pending.append((args, cwd))
+            return
+    handler(args, cwd)
+
+
+def _receive(conn, token):
+    data = b''
+    while True:
+        chunk = conn.recv(65536)
+        if not chunk:
+            break
+        data += chunk
+    header, _, payload = data.partition(b'\\n')
+    if not hmac.compare_digest(header, token):
+        return None
+    conn.sendall(b'ok')
+    cwd, *args = payload.decode('utf-8', 'replace').split('\\0')
+    return args, cwd
+
+
+def _serve(server, token):
+    while True:
+        conn, _ = server.accept()
+        try:
+            with conn:
+                conn.settimeout(10)
+                message = _receive(conn, token)
+        except OSError:
+            continue
+        if message is not None:
+            try:
+                _deliver(*message)
+            except Exception:
+                sys.excepthook(*sys.exc_info())
+
+
+def _start(fileno, token):
+    server = socket.socket(fileno=fileno)
+    threading.Thread(
+        target=_serve, args=(server, token.encode()), name='single-instance', daemon=True
+    ).start()
+";
+
+/// How long later instances try to reach the running instance.
+const FORWARD_ATTEMPTS: usize = 50;
+const FORWARD_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// A lock held by the first instance for as long as it runs.
+pub(crate) struct SingleInstance {
+    // The lock is held for as long as the file is open.
+    _file: File,
+}
+
+/// Accepts arguments forwarded by later instances.
+pub(crate) struct SingleInstanceListener {
+    listener: TcpListener,
+    token: String,
+}
+
+/// Ensure a file or directory is owned by the current user and isn't a symlink.
+#[cfg(unix)]
+fn ensure_owned(path: &Path, metadata: &std::fs::Metadata) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    if metadata.file_type().is_symlink() || metadata.uid() != unsafe { libc::getuid() } {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is not owned by the current user", path.display()),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Resolve a directory only accessible by the current user.
+#[cfg(unix)]
+fn private_dir() -> std::io::Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    // $XDG_RUNTIME_DIR is owned by the user and not accessible by others.
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|value| !value.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    // The temporary directory is shared by all users. Create a directory
+    // other users can't create files in.
+    let dir = std::env::temp_dir().join(format!("pyembed-{}", unsafe { libc::getuid() }));
+
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+
+    let metadata = std::fs::symlink_metadata(&dir)?;
+    ensure_owned(&dir, &metadata)?;
+
+    if !metadata.is_dir() || metadata.permissions().mode() & 0o077 != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is accessible by other users", dir.display()),
+        ));
+    }
+
+    Ok(dir)
+}
+
+/// Resolve the paths of the lock file and of the file describing the listener.
+fn instance_paths(name: &str) -> std::io::Result<(PathBuf, PathBuf)> {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    #[cfg(unix)]
+    let dir = private_dir()?;
+    #[cfg(not(unix))]
+    let dir = std::env::temp_dir();
+
+    Ok((
+        dir.join(format!("{}.lock", name)),
+        dir.join(format!("{}.instance", name)),
+    ))
+}
+
+/// Generate a random token authenticating clients.
+fn random_token() -> std::io::Result<String> {
+    let mut data = [0u8; 16];
+    getrandom::getrandom(&mut data)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+
+    Ok(data.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Lock a file, returning [None] if another process holds the lock.
+#[cfg(unix)]
+fn lock_file(path: &Path) -> std::io::Result<Option<File>> {
+    use std::os::unix::{fs::OpenOptionsExt, io::AsRawFd};
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?;
+    ensure_owned(path, &file.metadata()?)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        Ok(Some(file))
+    } else {
+        let e = std::io::Error::last_os_error();
+
+        if e.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            Ok(None)
+        } else {
+            Err(e)
+        }
+    }
+}
+
+/// Lock a file, returning [None] if another process holds the lock.
+#[cfg(windows)]
+fn lock_file(path: &Path) -> std::io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    // Files opened without sharing can't be opened again until closed.
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(0)
+        .open(path)
+    {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Write the file describing the listener of the running instance.
+///
+/// The file is replaced atomically, so later instances never read a partial
+/// file.
+fn write_instance_file(path: &Path, port: u16, token: &str) -> std::io::Result<()> {
+    let temp_path = path.with_extension(format!("instance.{}", std::process::id()));
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(&temp_path)?;
+    writeln!(file, "{} {}", port, token)?;
+    drop(file);
+
+    std::fs::rename(&temp_path, path)
+}
+
+/// Attempt to become the running instance of an application.
+///
+/// Returns [None] if another instance is running.
+pub(crate) fn acquire(
+    name: &str,
+) -> Result<Option<(SingleInstance, SingleInstanceListener)>, NewInterpreterError> {
+    let (lock_path, instance_path) = instance_paths(name).map_err(|e| {
+        NewInterpreterError::Dynamic(format!("resolving single instance paths: {}", e))
+    })?;
+
+    let file = match lock_file(&lock_path).map_err(|e| {
+        NewInterpreterError::Dynamic(format!("locking {}: {}", lock_path.display(), e))
+    })? {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+
+    let (listener, token) = random_token()
+        .and_then(|token| {
+            let listener = TcpListener::bind(("127.0.0.1", 0))?;
+            let port = listener.local_addr()?.port();
+            write_instance_file(&instance_path, port, &token)?;
+            Ok((listener, token))
+        })
+        .map_err(|e| {
+            NewInterpreterError::Dynamic(format!("accepting arguments of later instances: {}", e))
+        })?;
+
+    Ok(Some((
+        SingleInstance { _file: file },
+        SingleInstanceListener { listener, token },
+    )))
+}
+
+/// Send arguments to the running instance once.
+fn send_args(instance_path: &Path, payload: &[u8]) -> std::io::Result<()> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let mut file = std::fs::File::open(instance_path)?;
+    #[cfg(unix)]
+    ensure_owned(instance_path, &std::fs::symlink_metadata(instance_path)?)?;
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let (port, token) = content
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| invalid("malformed instance file"))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| invalid("malformed instance file"))?;
+
+    let mut stream = TcpStream::connect_timeout(
+        &SocketAddr::from(([127, 0, 0, 1], port)),
+        Duration::from_secs(5),
+    )?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    stream.write_all(token.as_bytes())?;
+    stream.write_all(b"\n")?;
+    stream.write_all(payload)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response)?;
+
+    if response == b"ok" {
+        Ok(())
+    } else {
+        Err(invalid("arguments rejected"))
+    }
+}
+
+/// Send arguments and the working directory to the running instance.
+///
+/// The running instance may still be starting, so sending is retried for a
+/// few seconds.
+pub(crate) fn forward_args(
+    name: &str,
+    cwd: &Path,
+    args: &[OsString],
+) -> Result<(), NewInterpreterError> {
+    let (_, instance_path) = instance_paths(name).map_err(|e| {
+        NewInterpreterError::Dynamic(format!("resolving single instance paths: {}", e))
+    })?;
+
+    let payload = std::iter::once(cwd.as_os_str())
+        .chain(args.iter().map(|arg| arg.as_os_str()))
+        .map(|value| value.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\0");
+
+    let mut error = None;
+
+    for _ in 0..FORWARD_ATTEMPTS {
+        match send_args(&instance_path, payload.as_bytes()) {
+            Ok(()) => return Ok(()),
+            Err(e) => error = Some(e),
+        }
+
+        std::thread::sleep(FORWARD_RETRY_DELAY);
+    }
+
+    Err(NewInterpreterError::Dynamic(format!(
+        "unable to forward arguments to the running instance of {}: {}",
+        name,
+        error.map(|e| e.to_string()).unwrap_or_default()
+    )))
+}
+
+impl SingleInstanceListener {
+    /// Register the `oxidized_runtime.single_instance` module and start receiving arguments.
+    pub(crate) fn install(self, py: Python) -> Result<(), NewInterpreterError> {
+        #[cfg(unix)]
+        let fileno = std::os::unix::io::IntoRawFd::into_raw_fd(self.listener);
+        #[cfg(windows)]
+        let fileno = std::os::windows::io::IntoRawSocket::into_raw_socket(self.listener);
+
+        let install = || -> PyResult<()> {
+            let module = PyModule::from_code(
+                py,
+                SINGLE_INSTANCE_CODE,
+                "oxidized_runtime/single_instance.py",
+                "oxidized_runtime.single_instance",
+            )?;
+            module
+                .getattr("_start")?
+                .call1((fileno, self.token.as_str()))?;
+
+            py.import("oxidized_runtime")?
+                .setattr("single_instance", module)?;
+
+            Ok(())
+        };
+
+        install().map_err(|e| {
+            NewInterpreterError::new_from_pyerr(
+                py,
+                e,
+                "installing oxidized_runtime.single_instance",
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::BufRead};
+
+    #[test]
+    fn test_acquire() -> Result<(), NewInterpreterError> {
+        let name = format!("pyembed-test-{}", random_token().unwrap());
+
+        let (lock, listener) = acquire(&name)?.unwrap();
+        assert!(acquire(&name)?.is_none());
+
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream);
+
+            let mut token = String::new();
+            reader.read_line(&mut token).unwrap();
+            assert_eq!(token.trim_end(), listener.token);
+
+            let mut payload = String::new();
+            reader.read_to_string(&mut payload).unwrap();
+            reader.get_mut().write_all(b"ok").unwrap();
+
+            payload
+        });
+
+        forward_args(
+            &name,
+            Path::new("/home/user"),
+            &[OsString::from("foo.txt"), OsString::from("--flag")],
+        )?;
+        assert_eq!(server.join().unwrap(), "/home/user\0foo.txt\0--flag");
+
+        drop(lock);
+        assert!(acquire(&name)?.is_some());
+
+        let (lock_path, instance_path) = instance_paths(&name).unwrap();
+        let _ = std::fs::remove_file(lock_path);
+        let _ = std::fs::remove_file(instance_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_instance_paths() -> std::io::Result<()> {
+        let (lock_path, instance_path) = instance_paths("my app/1")?;
+
+        let lock_name = lock_path.file_name().unwrap().to_string_lossy();
+        assert!(lock_name.starts_with("my_app_1"));
+        assert!(lock_name.ends_with(".lock"));
+        assert_eq!(lock_path.parent(), instance_path.parent());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_file_symlink() -> std::io::Result<()> {
+        let (lock_path, _) = instance_paths(&format!("pyembed-test-{}", random_token()?))?;
+        let target = lock_path.with_extension("target");
+        std::fs::write(&target, b"")?;
+        std::os::unix::fs::symlink(&target, &lock_path)?;
+
+        assert!(lock_file(&lock_path).is_err());
+
+        std::fs::remove_file(&lock_path)?;
+        std::fs::remove_file(&target)?;
+
+        Ok(())
+    }
+}
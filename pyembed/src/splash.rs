@@ -0,0 +1,663 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Splash screens shown while the interpreter starts.
+
+Initializing Python and importing a GUI toolkit can take seconds, during
+which users see nothing. A splash screen showing an image is displayed
+before the interpreter is initialized and stays visible until Python code
+calls `oxidized_runtime.splash.close()`, a timeout expires or the
+interpreter is finalized.
+
+Images are BMP files, which both platforms can display without image
+decoding libraries.
+
+On Windows, the window is owned by a dedicated thread running its own
+message loop, so it is painted while the main thread initializes Python.
+
+On macOS, AppKit windows can only be used from the main thread, which is
+busy initializing Python. So the window is drawn once when shown. Timeouts
+and closing from other threads take effect once the application runs the
+main event loop, which GUI toolkits do.
+
+Splash screens aren't supported on other platforms, where they aren't shown.
+*/
+
+// Window handling is only compiled on Windows and macOS.
+#![cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+
+use {
+    crate::error::NewInterpreterError,
+    pyo3::{prelude::*, types::PyModule, wrap_pyfunction},
+    std::time::Duration,
+};
+
+/// Dimensions and data offsets of an uncompressed BMP image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Bitmap {
+    /// Width in pixels.
+    pub width: i32,
+    /// Height in pixels. Negative for images stored top-down.
+    pub height: i32,
+    /// Offset of the pixel data.
+    pub bits_offset: usize,
+}
+
+/// Offset of the `BITMAPINFOHEADER` structure in BMP files.
+const BITMAP_INFO_OFFSET: usize = 14;
+
+impl Bitmap {
+    /// Parse the headers of a BMP file.
+    ///
+    /// Only 24 and 32 bits per pixel images without compression are supported.
+    pub fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        let u16_at = |offset: usize| {
+            data.get(offset..offset + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        };
+        let u32_at = |offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+
+        if !data.starts_with(b"BM") || data.len() < BITMAP_INFO_OFFSET + 40 {
+            return Err("splash screen image is not a BMP file");
+        }
+
+        let bits_offset = u32_at(10).unwrap_or_default() as usize;
+        let width = u32_at(18).unwrap_or_default() as i32;
+        let height = u32_at(22).unwrap_or_default() as i32;
+        let bit_count = u16_at(28).unwrap_or_default();
+        // BI_RGB or BI_BITFIELDS.
+        let compression = u32_at(30).unwrap_or_default();
+
+        if (bit_count != 24 && bit_count != 32) || (compression != 0 && compression != 3) {
+            return Err(
+                "splash screen images must be uncompressed BMP files with 24 or 32 bits per pixel",
+            );
+        }
+
+        // Rows are padded to 4 bytes.
+        let end = (width > 0 && height != 0)
+            .then(|| (width as usize).checked_mul(bit_count as usize))
+            .flatten()
+            .map(|bits| (bits + 31) / 32 * 4)
+            .and_then(|row_size| row_size.checked_mul(height.unsigned_abs() as usize))
+            .and_then(|size| size.checked_add(bits_offset));
+
+        if end.map_or(true, |end| end > data.len()) {
+            return Err("splash screen image is truncated or has invalid dimensions");
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bits_offset,
+        })
+    }
+}
+
+/// Show a splash screen with an image.
+///
+/// The splash screen closes itself once `timeout` expires.
+pub(crate) fn show(image: &[u8], timeout: Option<Duration>) -> Result<(), NewInterpreterError> {
+    let bitmap = Bitmap::parse(image).map_err(NewInterpreterError::Simple)?;
+
+    #[cfg(any(windows, target_os = "macos"))]
+    platform::show(image, bitmap, timeout).map_err(NewInterpreterError::Dynamic)?;
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let _ = (bitmap, timeout);
+
+    Ok(())
+}
+
+/// Close the splash screen, if shown.
+pub(crate) fn close_window() {
+    #[cfg(any(windows, target_os = "macos"))]
+    platform::close();
+}
+
+/// Close the splash screen.
+#[pyfunction]
+fn close() {
+    close_window();
+}
+
+/// Register the `oxidized_runtime.splash` module.
+pub(crate) fn install_splash_module(py: Python) -> Result<(), NewInterpreterError> {
+    let install = || -> PyResult<()> {
+        let module = PyModule::new(py, "oxidized_runtime.splash")?;
+        module.add_function(wrap_pyfunction!(close, module)?)?;
+
+        py.import("oxidized_runtime")?.setattr("splash", module)?;
+        py.import("sys")?
+            .getattr("modules")?
+            .set_item("oxidized_runtime.splash", module)?;
+
+        Ok(())
+    };
+
+    install().map_err(|e| {
+        NewInterpreterError::new_from_pyerr(py, e, "installing oxidized_runtime.splash")
+    })
+}
+
+#[cfg(windows)]
+mod platform {
+    use {
+        super::{Bitmap, BITMAP_INFO_OFFSET},
+        std::{
+            cell::RefCell,
+            ffi::c_void,
+            ptr::null_mut,
+            sync::atomic::{AtomicIsize, Ordering},
+            time::Duration,
+        },
+    };
+
+    const WS_POPUP: u32 = 0x8000_0000;
+    const WS_VISIBLE: u32 = 0x1000_0000;
+    const WS_EX_TOPMOST: u32 = 0x8;
+    const WS_EX_TOOLWINDOW: u32 = 0x80;
+    const SM_CXSCREEN: i32 = 0;
+    const SM_CYSCREEN: i32 = 1;
+    const WM_DESTROY: u32 = 0x2;
+    const WM_PAINT: u32 = 0xf;
+    const WM_CLOSE: u32 = 0x10;
+    const WM_TIMER: u32 = 0x113;
+    const IDC_APPSTARTING: usize = 32650;
+    const DIB_RGB_COLORS: u32 = 0;
+
+    type WindowProc = unsafe extern "system" fn(isize, u32, usize, isize) -> isize;
+
+    #[repr(C)]
+    struct WindowClass {
+        style: u32,
+        window_proc: WindowProc,
+        class_extra: i32,
+        window_extra: i32,
+        instance: isize,
+        icon: isize,
+        cursor: isize,
+        background: isize,
+        menu_name: *const u16,
+        class_name: *const u16,
+    }
+
+    #[repr(C)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[repr(C)]
+    struct Message {
+        window: isize,
+        message: u32,
+        wparam: usize,
+        lparam: isize,
+        time: u32,
+        point: Point,
+    }
+
+    #[repr(C)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    #[repr(C)]
+    struct PaintStruct {
+        hdc: isize,
+        erase: i32,
+        paint: Rect,
+        restore: i32,
+        inc_update: i32,
+        reserved: [u8; 32],
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn BeginPaint(window: isize, paint: *mut PaintStruct) -> isize;
+        fn CreateWindowExW(
+            ex_style: u32,
+            class_name: *const u16,
+            window_name: *const u16,
+            style: u32,
+            x: i32,
+            y: i32,
+            width: i32,
+            height: i32,
+            parent: isize,
+            menu: isize,
+            instance: isize,
+            param: *mut c_void,
+        ) -> isize;
+        fn DefWindowProcW(window: isize, message: u32, wparam: usize, lparam: isize) -> isize;
+        fn DestroyWindow(window: isize) -> i32;
+        fn DispatchMessageW(message: *const Message) -> isize;
+        fn EndPaint(window: isize, paint: *const PaintStruct) -> i32;
+        fn GetMessageW(message: *mut Message, window: isize, min: u32, max: u32) -> i32;
+        fn GetSystemMetrics(index: i32) -> i32;
+        fn LoadCursorW(instance: isize, name: *const u16) -> isize;
+        fn PostMessageW(window: isize, message: u32, wparam: usize, lparam: isize) -> i32;
+        fn PostQuitMessage(exit_code: i32);
+        fn RegisterClassW(class: *const WindowClass) -> u16;
+        fn SetTimer(window: isize, id: usize, elapse: u32, func: *const c_void) -> usize;
+        fn TranslateMessage(message: *const Message) -> i32;
+    }
+
+    #[link(name = "gdi32")]
+    extern "system" {
+        fn SetDIBitsToDevice(
+            hdc: isize,
+            x_dest: i32,
+            y_dest: i32,
+            width: u32,
+            height: u32,
+            x_src: i32,
+            y_src: i32,
+            start_scan: u32,
+            lines: u32,
+            bits: *const c_void,
+            info: *const c_void,
+            color_use: u32,
+        ) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleW(name: *const u16) -> isize;
+        fn GetLastError() -> u32;
+    }
+
+    /// Handle of the splash screen window, or 0.
+    static WINDOW: AtomicIsize = AtomicIsize::new(0);
+
+    thread_local! {
+        /// Image painted by the splash screen thread.
+        static IMAGE: RefCell<Option<(Vec<u8>, Bitmap)>> = RefCell::new(None);
+    }
+
+    unsafe fn paint(window: isize) {
+        let mut paint = std::mem::zeroed::<PaintStruct>();
+        let hdc = BeginPaint(window, &mut paint);
+
+        IMAGE.with(|image| {
+            if let Some((data, bitmap)) = &*image.borrow() {
+                let lines = bitmap.height.unsigned_abs();
+
+                SetDIBitsToDevice(
+                    hdc,
+                    0,
+                    0,
+                    bitmap.width as u32,
+                    lines,
+                    0,
+                    0,
+                    0,
+                    lines,
+                    data[bitmap.bits_offset..].as_ptr() as *const c_void,
+                    data[BITMAP_INFO_OFFSET..].as_ptr() as *const c_void,
+                    DIB_RGB_COLORS,
+                );
+            }
+        });
+
+        EndPaint(window, &paint);
+    }
+
+    unsafe extern "system" fn window_proc(
+        window: isize,
+        message: u32,
+        wparam: usize,
+        lparam: isize,
+    ) -> isize {
+        match message {
+            WM_PAINT => {
+                paint(window);
+                0
+            }
+            WM_TIMER => {
+                DestroyWindow(window);
+                0
+            }
+            WM_DESTROY => {
+                let _ = WINDOW.compare_exchange(window, 0, Ordering::SeqCst, Ordering::SeqCst);
+                PostQuitMessage(0);
+                0
+            }
+            _ => DefWindowProcW(window, message, wparam, lparam),
+        }
+    }
+
+    unsafe fn create_window(bitmap: &Bitmap, timeout: Option<Duration>) -> Result<isize, String> {
+        let class_name = "PyOxidizerSplashScreen\0"
+            .encode_utf16()
+            .collect::<Vec<_>>();
+        let instance = GetModuleHandleW(null_mut());
+
+        let class = WindowClass {
+            style: 0,
+            window_proc,
+            class_extra: 0,
+            window_extra: 0,
+            instance,
+            icon: 0,
+            cursor: LoadCursorW(0, IDC_APPSTARTING as *const u16),
+            background: 0,
+            menu_name: null_mut(),
+            class_name: class_name.as_ptr(),
+        };
+
+        if RegisterClassW(&class) == 0 {
+            return Err(format!(
+                "error registering splash screen window class: {}",
+                GetLastError()
+            ));
+        }
+
+        let height = bitmap.height.abs();
+        let window = CreateWindowExW(
+            WS_EX_TOPMOST | WS_EX_TOOLWINDOW,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            WS_POPUP | WS_VISIBLE,
+            (GetSystemMetrics(SM_CXSCREEN) - bitmap.width) / 2,
+            (GetSystemMetrics(SM_CYSCREEN) - height) / 2,
+            bitmap.width,
+            height,
+            0,
+            0,
+            instance,
+            null_mut(),
+        );
+
+        if window == 0 {
+            return Err(format!(
+                "error creating splash screen window: {}",
+                GetLastError()
+            ));
+        }
+
+        if let Some(timeout) = timeout {
+            SetTimer(
+                window,
+                1,
+                timeout.as_millis().min(u32::MAX as u128) as u32,
+                null_mut(),
+            );
+        }
+
+        Ok(window)
+    }
+
+    pub(super) fn show(
+        image: &[u8],
+        bitmap: Bitmap,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let image = image.to_vec();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("splash-screen".to_string())
+            .spawn(move || {
+                IMAGE.with(|value| *value.borrow_mut() = Some((image, bitmap)));
+
+                let window = match unsafe { create_window(&bitmap, timeout) } {
+                    Ok(window) => window,
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                        return;
+                    }
+                };
+
+                WINDOW.store(window, Ordering::SeqCst);
+                let _ = sender.send(Ok(()));
+
+                unsafe {
+                    let mut message = std::mem::zeroed::<Message>();
+
+                    while GetMessageW(&mut message, 0, 0, 0) > 0 {
+                        TranslateMessage(&message);
+                        DispatchMessageW(&message);
+                    }
+                }
+            })
+            .map_err(|e| format!("error starting splash screen thread: {}", e))?;
+
+        // Wait for the window, so closing it right away works.
+        receiver
+            .recv()
+            .map_err(|_| "splash screen thread exited".to_string())?
+    }
+
+    pub(super) fn close() {
+        let window = WINDOW.swap(0, Ordering::SeqCst);
+
+        if window != 0 {
+            unsafe {
+                PostMessageW(window, WM_CLOSE, 0, 0);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use {
+        super::Bitmap,
+        std::{
+            ffi::c_void,
+            os::raw::{c_char, c_long},
+            ptr::null_mut,
+            sync::atomic::{AtomicPtr, Ordering},
+            time::Duration,
+        },
+    };
+
+    type Id = *mut c_void;
+    type Sel = *mut c_void;
+
+    const NS_WINDOW_STYLE_MASK_BORDERLESS: c_long = 0;
+    const NS_BACKING_STORE_BUFFERED: c_long = 2;
+    const NS_FLOATING_WINDOW_LEVEL: c_long = 3;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct NsRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    extern "C" {
+        fn pthread_main_np() -> i32;
+    }
+
+    /// The splash screen window, or null.
+    static WINDOW: AtomicPtr<c_void> = AtomicPtr::new(null_mut());
+
+    /// Obtain a class or selector from a NUL terminated name.
+    unsafe fn class(name: &str) -> Id {
+        objc_getClass(name.as_ptr() as *const c_char)
+    }
+
+    unsafe fn sel(name: &str) -> Sel {
+        sel_registerName(name.as_ptr() as *const c_char)
+    }
+
+    /// Obtain `objc_msgSend()` with the signature of a method.
+    macro_rules! msg_send_fn {
+        ($($arg:ty),* => $ret:ty) => {{
+            type Method = unsafe extern "C" fn(Id, Sel $(, $arg)*) -> $ret;
+            std::mem::transmute::<unsafe extern "C" fn(), Method>(objc_msgSend)
+        }};
+    }
+
+    unsafe fn send(receiver: Id, selector: &str) -> Id {
+        msg_send_fn!(=> Id)(receiver, sel(selector))
+    }
+
+    unsafe fn send_id(receiver: Id, selector: &str, arg: Id) -> Id {
+        msg_send_fn!(Id => Id)(receiver, sel(selector), arg)
+    }
+
+    pub(super) fn show(
+        image: &[u8],
+        bitmap: Bitmap,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        if unsafe { pthread_main_np() } == 0 {
+            return Err("splash screens can only be shown from the main thread".to_string());
+        }
+
+        unsafe {
+            let pool = send(send(class("NSAutoreleasePool\0"), "alloc\0"), "init\0");
+
+            let app = send(class("NSApplication\0"), "sharedApplication\0");
+
+            let data = msg_send_fn!(*const c_void, usize => Id)(
+                class("NSData\0"),
+                sel("dataWithBytes:length:\0"),
+                image.as_ptr() as *const c_void,
+                image.len(),
+            );
+            let ns_image = send_id(send(class("NSImage\0"), "alloc\0"), "initWithData:\0", data);
+
+            if ns_image.is_null() {
+                send(pool, "drain\0");
+                return Err("unable to load splash screen image".to_string());
+            }
+
+            let window = msg_send_fn!(NsRect, c_long, c_long, i8 => Id)(
+                send(class("NSWindow\0"), "alloc\0"),
+                sel("initWithContentRect:styleMask:backing:defer:\0"),
+                NsRect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: bitmap.width as f64,
+                    height: bitmap.height.abs() as f64,
+                },
+                NS_WINDOW_STYLE_MASK_BORDERLESS,
+                NS_BACKING_STORE_BUFFERED,
+                0,
+            );
+
+            // The window is kept around after it is closed, so closing it
+            // again is harmless.
+            msg_send_fn!(i8 => ())(window, sel("setReleasedWhenClosed:\0"), 0);
+            send_id(
+                window,
+                "setContentView:\0",
+                send_id(class("NSImageView\0"), "imageViewWithImage:\0", ns_image),
+            );
+            msg_send_fn!(c_long => ())(window, sel("setLevel:\0"), NS_FLOATING_WINDOW_LEVEL);
+            send(window, "center\0");
+            send(window, "orderFrontRegardless\0");
+            send(window, "display\0");
+            msg_send_fn!(i8 => ())(app, sel("activateIgnoringOtherApps:\0"), 1);
+
+            if let Some(timeout) = timeout {
+                msg_send_fn!(Sel, Id, f64 => ())(
+                    window,
+                    sel("performSelector:withObject:afterDelay:\0"),
+                    sel("close\0"),
+                    null_mut(),
+                    timeout.as_secs_f64(),
+                );
+            }
+
+            WINDOW.store(window, Ordering::SeqCst);
+            send(pool, "drain\0");
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn close() {
+        let window = WINDOW.swap(null_mut(), Ordering::SeqCst);
+
+        if window.is_null() {
+            return;
+        }
+
+        unsafe {
+            if pthread_main_np() != 0 {
+                send(window, "close\0");
+            } else {
+                msg_send_fn!(Sel, Id, i8 => ())(
+                    window,
+                    sel("performSelectorOnMainThread:withObject:waitUntilDone:\0"),
+                    sel("close\0"),
+                    null_mut(),
+                    0,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a BMP file with 24 bits per pixel.
+    fn bitmap(width: u32, height: i32) -> Vec<u8> {
+        let row_size = ((width * 24 + 31) / 32) * 4;
+        let image_size = row_size * height.unsigned_abs();
+
+        let mut data = vec![];
+        data.extend_from_slice(b"BM");
+        data.extend_from_slice(&(54 + image_size).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&54u32.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&24u16.to_le_bytes());
+        data.extend_from_slice(&[0; 24]);
+        data.resize(54 + image_size as usize, 0xff);
+
+        data
+    }
+
+    #[test]
+    fn test_parse_bitmap() {
+        assert_eq!(
+            Bitmap::parse(&bitmap(3, 2)),
+            Ok(Bitmap {
+                width: 3,
+                height: 2,
+                bits_offset: 54
+            })
+        );
+        assert_eq!(Bitmap::parse(&bitmap(3, -2)).unwrap().height, -2);
+
+        let mut data = bitmap(3, 2);
+        data.pop();
+        assert!(Bitmap::parse(&data).is_err());
+
+        let mut data = bitmap(3, 2);
+        data[28] = 8;
+        assert!(Bitmap::parse(&data).is_err());
+
+        assert!(Bitmap::parse(b"\x89PNG\r\n\x1a\n").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Support for applications updated with Squirrel.
+
+[Squirrel](https://github.com/Squirrel/Squirrel.Windows) installs
+applications into a per-user directory holding `Update.exe` and one
+`app-<version>` directory per installed release. Updates are discovered
+by reading a `RELEASES` file from a release feed and applied by running
+`Update.exe`.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::{
+        cmp::Ordering,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Parse a version string into a sortable key.
+///
+/// Versions are of the form `major.minor.patch[-prerelease]`. Releases
+/// sort after pre-releases of the same version.
+fn version_key(version: &str) -> Option<(Vec<u64>, bool, String)> {
+    let (release, prerelease) = match version.split_once('-') {
+        Some((release, prerelease)) => (release, Some(prerelease)),
+        None => (version, None),
+    };
+
+    let parts = release
+        .split('.')
+        .map(|p| p.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if parts.len() != 3 {
+        return None;
+    }
+
+    Some((
+        parts,
+        prerelease.is_none(),
+        prerelease.unwrap_or_default().to_string(),
+    ))
+}
+
+/// Compare two version strings.
+///
+/// Returns `None` if either version can't be parsed.
+pub(crate) fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    Some(version_key(a)?.cmp(&version_key(b)?))
+}
+
+/// A release listed in a Squirrel `RELEASES` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SquirrelRelease {
+    /// Version of the release.
+    pub version: String,
+
+    /// Filename of the release package. e.g. `MyApp-1.0.0-full.nupkg`.
+    pub filename: String,
+
+    /// Upper case hex encoded SHA-1 digest of the package.
+    pub sha1: String,
+
+    /// Size of the package in bytes.
+    pub size: u64,
+}
+
+impl SquirrelRelease {
+    /// Parse a line of a `RELEASES` file.
+    pub fn from_releases_line(line: &str) -> Result<Self> {
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+
+        if parts.len() != 3 {
+            return Err(anyhow!(
+                "RELEASES entry is not of the form <sha1> <filename> <size>: {}",
+                line
+            ));
+        }
+
+        let filename = parts[1];
+        let stem = filename
+            .strip_suffix("-full.nupkg")
+            .or_else(|| filename.strip_suffix("-delta.nupkg"))
+            .ok_or_else(|| anyhow!("{} is not a Squirrel package filename", filename))?;
+
+        // The package id may contain hyphens. The version is the first
+        // hyphen-delimited suffix that parses as a version.
+        let version = stem
+            .match_indices('-')
+            .map(|(i, _)| &stem[i + 1..])
+            .find(|v| version_key(v).is_some())
+            .ok_or_else(|| anyhow!("unable to resolve version of {}", filename))?;
+
+        Ok(Self {
+            version: version.to_string(),
+            filename: filename.to_string(),
+            sha1: parts[0].to_string(),
+            size: parts[2]
+                .parse::<u64>()
+                .with_context(|| format!("parsing size of RELEASES entry: {}", line))?,
+        })
+    }
+
+    /// Whether this release is a delta package.
+    pub fn is_delta(&self) -> bool {
+        self.filename.ends_with("-delta.nupkg")
+    }
+
+    /// Parse the content of a `RELEASES` file.
+    pub fn parse_releases(data: &str) -> Result<Vec<Self>> {
+        data.trim_start_matches('\u{feff}')
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(Self::from_releases_line)
+            .collect()
+    }
+}
+
+/// Describes an application installed by Squirrel.
+#[derive(Clone, Debug)]
+pub struct SquirrelApplication {
+    /// Path to Squirrel's `Update.exe`.
+    update_exe: PathBuf,
+
+    /// Version of the running release.
+    current_version: String,
+}
+
+impl SquirrelApplication {
+    /// Resolve the Squirrel installation of an executable.
+    ///
+    /// Returns `None` if the executable isn't in a Squirrel installation:
+    /// its parent directory must be named `app-<version>` and be next to
+    /// `Update.exe`.
+    pub fn from_exe_path(exe: impl AsRef<Path>) -> Option<Self> {
+        let app_dir = exe.as_ref().parent()?;
+        let current_version = app_dir
+            .file_name()?
+            .to_str()?
+            .strip_prefix("app-")?
+            .to_string();
+
+        version_key(&current_version)?;
+
+        let update_exe = app_dir.parent()?.join("Update.exe");
+
+        if update_exe.is_file() {
+            Some(Self {
+                update_exe,
+                current_version,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Resolve the Squirrel installation of the current executable.
+    pub fn from_current_exe() -> Option<Self> {
+        Self::from_exe_path(std::env::current_exe().ok()?)
+    }
+
+    /// Path to Squirrel's `Update.exe`.
+    pub fn update_exe(&self) -> &Path {
+        &self.update_exe
+    }
+
+    /// Version of the running release.
+    pub fn current_version(&self) -> &str {
+        &self.current_version
+    }
+
+    /// Find the newest release that is newer than the running release.
+    ///
+    /// `releases` is the content of a `RELEASES` file, typically fetched
+    /// from the release feed. Only full packages are considered.
+    pub fn find_update(&self, releases: &str) -> Result<Option<SquirrelRelease>> {
+        let mut candidates = SquirrelRelease::parse_releases(releases)?
+            .into_iter()
+            .filter(|r| !r.is_delta())
+            .filter(|r| {
+                compare_versions(&r.version, &self.current_version) == Some(Ordering::Greater)
+            })
+            .collect::<Vec<_>>();
+
+        candidates
+            .sort_by(|a, b| compare_versions(&a.version, &b.version).unwrap_or(Ordering::Equal));
+
+        Ok(candidates.pop())
+    }
+
+    /// Download and install the latest release from a release feed.
+    ///
+    /// `url` is the URL or path of the directory holding the `RELEASES`
+    /// file. The update is applied by `Update.exe` and takes effect the
+    /// next time the application is started.
+    pub fn update(&self, url: &str) -> Result<()> {
+        let status = std::process::Command::new(&self.update_exe)
+            .arg("--update")
+            .arg(url)
+            .status()
+            .with_context(|| format!("running {}", self.update_exe.display()))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} failed: {}", self.update_exe.display(), status))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELEASES: &str = "\
+        94689FEDE03FED7AB59C24337673A27837F0C3EC my-app-1.0.0-full.nupkg 1004502\n\
+        3A2EADD1C4A5C2F96A0E6E4D7E4F6D1F4B5C6D7E my-app-1.2.0-beta.1-full.nupkg 1004510\n\
+        14DB31B9B8B7D7F8C8E2E9C2D6B1E2F3A4B5C6D7 my-app-1.1.0-delta.nupkg 12345\n\
+        2E8A9B0C1D2E3F4A5B6C7D8E9F0A1B2C3D4E5F6A my-app-1.1.0-full.nupkg 1004508\n";
+
+    #[test]
+    fn test_parse_releases() -> Result<()> {
+        let releases = SquirrelRelease::parse_releases(RELEASES)?;
+        assert_eq!(releases.len(), 4);
+        assert_eq!(releases[0].version, "1.0.0");
+        assert_eq!(releases[1].version, "1.2.0-beta.1");
+        assert!(releases[2].is_delta());
+
+        assert!(SquirrelRelease::from_releases_line("abc foo.zip 42").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Some(Ordering::Greater));
+        assert_eq!(
+            compare_versions("1.0.0-beta", "1.0.0"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare_versions("1.0", "1.0.0"), None);
+    }
+
+    #[test]
+    fn test_find_update() -> Result<()> {
+        let mut app = SquirrelApplication {
+            update_exe: PathBuf::from("Update.exe"),
+            current_version: "1.0.0".to_string(),
+        };
+
+        let release = app.find_update(RELEASES)?.unwrap();
+        assert_eq!(release.filename, "my-app-1.2.0-beta.1-full.nupkg");
+
+        app.current_version = "1.2.0".to_string();
+        assert!(app.find_update(RELEASES)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_exe_path() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("pyembed-squirrel-{}", std::process::id()));
+        let app_dir = root.join("app-1.0.0");
+        std::fs::create_dir_all(&app_dir)?;
+
+        assert!(SquirrelApplication::from_exe_path(app_dir.join("myapp.exe")).is_none());
+
+        std::fs::write(root.join("Update.exe"), b"")?;
+        let app = SquirrelApplication::from_exe_path(app_dir.join("myapp.exe")).unwrap();
+        assert_eq!(app.current_version(), "1.0.0");
+        assert_eq!(app.update_exe(), root.join("Update.exe"));
+
+        std::fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::default_interpreter_config,
+    crate::{export_api, ApiError, MainPythonInterpreter},
+    pyo3::{prelude::*, types::PyModule},
+    rusty_fork::rusty_fork_test,
+};
+
+const MODULE_CODE: &str = "\
+calls = []
+
+def add(a, b):
+    return a + b
+
+def greet(name, excited=False):
+    return 'hello, %s%s' % (name, '!' if excited else '')
+
+def record(value):
+    calls.append(value)
+
+def fail(message):
+    raise ValueError(message)
+
+def wrong_type():
+    return 'not a number'
+";
+
+export_api! {
+    /// Functions of the test module.
+    struct TestApi("pyembed_api_test") {
+        fn add(a: i64, b: i64) -> i64;
+        fn greet(name: &str, excited: bool) -> String;
+        fn record(value: Vec<u32>);
+        fn fail(message: &str);
+        fn wrong_type() -> i64;
+    }
+}
+
+export_api! {
+    struct MissingApi("pyembed_api_missing") {
+        #[allow(dead_code)]
+        fn missing();
+    }
+}
+
+fn install_test_module(py: Python) {
+    let module =
+        PyModule::from_code(py, MODULE_CODE, "pyembed_api_test.py", "pyembed_api_test").unwrap();
+    py.import("sys")
+        .unwrap()
+        .getattr("modules")
+        .unwrap()
+        .set_item("pyembed_api_test", module)
+        .unwrap();
+}
+
+rusty_fork_test! {
+    #[test]
+    fn test_typed_calls() {
+        let interp = MainPythonInterpreter::new(default_interpreter_config()).unwrap();
+
+        interp.with_gil(|py| {
+            install_test_module(py);
+            let api = TestApi::import(py).unwrap();
+
+            assert_eq!(api.add(40, 2).unwrap(), 42);
+            assert_eq!(api.greet("world", true).unwrap(), "hello, world!");
+
+            api.record(vec![1, 2]).unwrap();
+            let calls: Vec<Vec<u32>> = py
+                .import("pyembed_api_test")
+                .unwrap()
+                .getattr("calls")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(calls, vec![vec![1, 2]]);
+        });
+    }
+
+    #[test]
+    fn test_errors() {
+        let interp = MainPythonInterpreter::new(default_interpreter_config()).unwrap();
+
+        interp.with_gil(|py| {
+            install_test_module(py);
+            let api = TestApi::import(py).unwrap();
+
+            match api.fail("bad input") {
+                Err(ApiError::Exception {
+                    function,
+                    exception,
+                    message,
+                    traceback,
+                }) => {
+                    assert_eq!(function, "fail");
+                    assert_eq!(exception, "ValueError");
+                    assert_eq!(message, "bad input");
+                    assert!(traceback.unwrap().contains("pyembed_api_test.py"));
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            let err = api.wrong_type().unwrap_err();
+            assert!(matches!(
+                err,
+                ApiError::Conversion { ref function, .. } if function == "wrong_type"
+            ));
+
+            let err = MissingApi::import(py).err().unwrap();
+            assert!(matches!(err, ApiError::Import { .. }));
+            assert!(err
+                .to_string()
+                .starts_with("unable to import pyembed_api_missing: ModuleNotFoundError"));
+        });
+    }
+}
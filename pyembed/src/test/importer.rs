@@ -104,6 +104,32 @@ rusty_fork_test! {
         run_py_test("test_importer_indexing.py").unwrap()
     }
 
+    /// Packages in the user site-packages directory are indexed.
+    #[test]
+    fn user_site_packages() {
+        let path = std::env::temp_dir().join(format!("pyembed-user-site-{}", std::process::id()));
+        std::fs::create_dir_all(path.join("pyembed_user_site_test")).unwrap();
+        std::fs::write(path.join("pyembed_user_site_test").join("__init__.py"), "VALUE = 42\n").unwrap();
+
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.user_site_packages = Some(path.clone());
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        let importer = get_importer(&interp).unwrap();
+
+        interp.with_gil(|py| {
+            assert_eq!(
+                importer.getattr(py, "user_site_packages").unwrap().extract::<String>(py).unwrap(),
+                path.display().to_string()
+            );
+
+            let module = py.import("pyembed_user_site_test").unwrap();
+            assert_eq!(module.getattr("VALUE").unwrap().extract::<i64>().unwrap(), 42);
+        });
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
     /// Run test_importer_iter_modules.py.
     #[test]
     fn importer_iter_modules_py() {
@@ -8,10 +8,14 @@ use {
     pyo3::{
         ffi as pyffi,
         prelude::*,
+        AsPyPointer,
         types::{PyBytes, PyList, PyString, PyStringData},
     },
     python_packaging::{
-        interpreter::{BytesWarning, MemoryAllocatorBackend, PythonInterpreterProfile},
+        interpreter::{
+            BytesWarning, EnvironmentMutation, MemoryAllocatorBackend, PythonInterpreterProfile,
+            WorkingDirectoryPolicy,
+        },
         resource::BytecodeOptimizationLevel,
     },
     rusty_fork::rusty_fork_test,
@@ -555,6 +559,73 @@ rusty_fork_test! {
         assert_eq!(config.tcl_library, Some(origin.join("lib").join("tcl8.6")));
     }
 
+    #[test]
+    fn test_tk_library_origin() {
+        let mut config = default_interpreter_config();
+        config.tk_library = Some(PathBuf::from("$ORIGIN").join("lib").join("tk8.6"));
+
+        let config = config.resolve().unwrap();
+
+        let origin = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        assert_eq!(config.tk_library, Some(origin.join("lib").join("tk8.6")));
+    }
+
+    #[test]
+    fn test_fault_handler_path_origin() {
+        let mut config = default_interpreter_config();
+        config.fault_handler_path = Some(PathBuf::from("$ORIGIN").join("faults.log"));
+
+        let config = config.resolve().unwrap();
+
+        let origin = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        assert_eq!(config.fault_handler_path, Some(origin.join("faults.log")));
+    }
+
+    #[test]
+    fn test_user_site_packages_resolve() {
+        let mut config = default_interpreter_config();
+        config.user_site_packages = Some(PathBuf::from("$ORIGIN").join("site-packages"));
+
+        let config = config.resolve().unwrap();
+
+        let origin = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        assert_eq!(config.user_site_packages, Some(origin.join("site-packages")));
+
+        std::env::set_var("HOME", "/home/user");
+        std::env::set_var("XDG_DATA_HOME", "/home/user/data");
+        std::env::set_var("APPDATA", "C:\\Users\\user\\AppData\\Roaming");
+
+        let mut config = default_interpreter_config();
+        config.user_site_packages = Some(PathBuf::from("myapp").join("site-packages"));
+
+        let config = config.resolve().unwrap();
+
+        let data_dir = if cfg!(windows) {
+            PathBuf::from("C:\\Users\\user\\AppData\\Roaming")
+        } else if cfg!(target_os = "macos") {
+            PathBuf::from("/home/user/Library/Application Support")
+        } else {
+            PathBuf::from("/home/user/data")
+        };
+
+        assert_eq!(config.user_site_packages, Some(data_dir.join("myapp").join("site-packages")));
+    }
+
     #[test]
     fn test_dev_mode() {
         let mut config = default_interpreter_config();
@@ -779,4 +850,477 @@ rusty_fork_test! {
             assert_eq!(flags.getattr("dont_write_bytecode").unwrap().extract::<i64>().unwrap(), 1);
         });
     }
+
+    #[test]
+    fn test_fault_handler_path() {
+        let path = std::env::temp_dir().join(format!("pyembed-faulthandler-{}", std::process::id()));
+
+        let mut config = default_interpreter_config();
+        config.fault_handler_path = Some(path.clone());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let faulthandler = py.import("faulthandler").unwrap();
+
+            assert!(faulthandler.getattr("is_enabled").unwrap().call0().unwrap().extract::<bool>().unwrap());
+        });
+
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_runtime_path_overrides() {
+        let path = std::env::temp_dir().join(format!("pyembed-overrides-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("pyembed_override_test.py"), "VALUE = 42\n").unwrap();
+
+        let env = default_interpreter_config().resolve().unwrap().runtime_path_overrides_env();
+        std::env::set_var(&env, &path);
+
+        let mut config = default_interpreter_config();
+        config.allow_runtime_path_overrides = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let sys = py.import("sys").unwrap();
+            let sys_path = sys.getattr("path").unwrap().extract::<Vec<String>>().unwrap();
+            assert_eq!(sys_path[0], path.display().to_string());
+
+            let module = py.import("pyembed_override_test").unwrap();
+            assert_eq!(module.getattr("VALUE").unwrap().extract::<i64>().unwrap(), 42);
+        });
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_runtime_path_overrides_disallowed() {
+        let env = default_interpreter_config().resolve().unwrap().runtime_path_overrides_env();
+        std::env::set_var(&env, "/does/not/exist");
+
+        let interp = MainPythonInterpreter::new(default_interpreter_config()).unwrap();
+
+        interp.with_gil(|py| {
+            let sys = py.import("sys").unwrap();
+            let sys_path = sys.getattr("path").unwrap().extract::<Vec<String>>().unwrap();
+            assert!(!sys_path.contains(&"/does/not/exist".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_tracemalloc_frames() {
+        let mut config = default_interpreter_config();
+        config.tracemalloc_frames = Some(5);
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let tracemalloc = py.import("tracemalloc").unwrap();
+
+            assert!(tracemalloc.getattr("is_tracing").unwrap().call0().unwrap().extract::<bool>().unwrap());
+            assert_eq!(tracemalloc.getattr("get_traceback_limit").unwrap().call0().unwrap().extract::<i64>().unwrap(), 5);
+        });
+    }
+
+    #[test]
+    fn test_environment() {
+        let bin_path = std::env::temp_dir().join("pyembed-bin");
+        std::env::set_var("PYEMBED_TEST_PATH", &bin_path);
+        std::env::set_var("PYEMBED_TEST_UNSET", "1");
+
+        let mut config = default_interpreter_config();
+        config.environment = vec![
+            EnvironmentMutation::Set("PYEMBED_TEST_SET".to_string(), "$ORIGIN/data".to_string()),
+            EnvironmentMutation::Unset("PYEMBED_TEST_UNSET".to_string()),
+            EnvironmentMutation::Prepend("PYEMBED_TEST_PATH".to_string(), "$ORIGIN".to_string()),
+            EnvironmentMutation::Append("PYEMBED_TEST_PATH".to_string(), bin_path.display().to_string()),
+        ];
+
+        let origin = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let environ = py.import("os").unwrap().getattr("environ").unwrap();
+
+            assert_eq!(
+                environ.get_item("PYEMBED_TEST_SET").unwrap().extract::<String>().unwrap(),
+                format!("{}/data", origin.display())
+            );
+            assert!(environ.call_method1("get", ("PYEMBED_TEST_UNSET",)).unwrap().is_none());
+
+            // Paths already present aren't added again.
+            let paths = environ.get_item("PYEMBED_TEST_PATH").unwrap().extract::<String>().unwrap();
+            assert_eq!(
+                std::env::split_paths(&paths).collect::<Vec<_>>(),
+                vec![origin.clone(), bin_path.clone()]
+            );
+        });
+    }
+
+    #[test]
+    fn test_working_directory_exe() {
+        let mut config = default_interpreter_config();
+        config.working_directory = WorkingDirectoryPolicy::ExecutableDirectory;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        let origin = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        interp.with_gil(|py| {
+            let cwd = py.import("os").unwrap().getattr("getcwd").unwrap().call0().unwrap().extract::<String>().unwrap();
+            assert_eq!(dunce::canonicalize(cwd).unwrap(), dunce::canonicalize(origin).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_working_directory_user_data() {
+        let data_dir = std::env::temp_dir().join(format!("pyembed-data-{}", std::process::id()));
+        std::env::set_var("XDG_DATA_HOME", &data_dir);
+        std::env::set_var("APPDATA", &data_dir);
+        std::env::set_var("HOME", &data_dir);
+
+        let mut config = default_interpreter_config();
+        config.working_directory = WorkingDirectoryPolicy::UserDataDirectory;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        let exe = std::env::current_exe().unwrap();
+        let expected = if cfg!(target_os = "macos") {
+            data_dir.join("Library/Application Support")
+        } else {
+            data_dir.clone()
+        }
+        .join(exe.file_stem().unwrap());
+
+        assert!(expected.is_dir());
+        assert_eq!(dunce::canonicalize(std::env::current_dir().unwrap()).unwrap(), dunce::canonicalize(&expected).unwrap());
+
+        drop(interp);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[test]
+    fn test_dirs_module() {
+        let mut config = default_interpreter_config();
+        config.app_name = Some("myapp".to_string());
+        config.app_author = Some("ACME".to_string());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let dirs = py.import("oxidized_runtime.dirs").unwrap();
+
+            assert_eq!(dirs.getattr("app_name").unwrap().extract::<String>().unwrap(), "myapp");
+            assert_eq!(dirs.getattr("app_author").unwrap().extract::<String>().unwrap(), "ACME");
+
+            for attr in ["config_dir", "data_dir", "cache_dir", "log_dir"] {
+                let path = dirs.getattr(attr).unwrap().extract::<String>().unwrap();
+                assert!(path.contains("myapp"));
+            }
+
+            let runtime = py.import("oxidized_runtime").unwrap();
+            assert_eq!(runtime.getattr("dirs").unwrap().as_ptr(), dirs.as_ptr());
+        });
+    }
+
+    #[test]
+    fn test_runtime_metadata() {
+        let mut config = default_interpreter_config();
+        config.runtime_metadata = vec![
+            ("version".to_string(), "1.2.3".to_string()),
+            ("channel".to_string(), "beta".to_string()),
+        ];
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        assert_eq!(interp.runtime_metadata("version"), Some("1.2.3"));
+        assert_eq!(interp.runtime_metadata("git_sha"), None);
+
+        interp.with_gil(|py| {
+            let metadata = py.import("oxidized_runtime.metadata").unwrap();
+            let values = metadata.getattr("values").unwrap();
+
+            assert_eq!(values.get_item("channel").unwrap().extract::<String>().unwrap(), "beta");
+            assert!(metadata.call_method1("get", ("git_sha",)).unwrap().is_none());
+
+            // The mapping is read-only.
+            assert!(values.set_item("channel", "stable").is_err());
+        });
+    }
+
+    #[test]
+    #[cfg(not(feature = "single-instance"))]
+    fn test_single_instance_requires_feature() {
+        let mut config = default_interpreter_config();
+        config.single_instance = Some("pyembed-test".to_string());
+
+        assert!(MainPythonInterpreter::new(config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "single-instance")]
+    fn test_single_instance() {
+        let name = format!("pyembed-test-{}", std::process::id());
+
+        let mut config = default_interpreter_config();
+        config.single_instance = Some(name.clone());
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            py.run(
+                "import oxidized_runtime.single_instance\n\
+                received = []\n\
+                oxidized_runtime.single_instance.set_handler(lambda args, cwd: received.append((args, cwd)))",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+
+        crate::single_instance::forward_args(
+            &name,
+            &PathBuf::from("/home/user"),
+            &[OsString::from("foo.txt")],
+        )
+        .unwrap();
+
+        // Arguments are delivered by a background thread.
+        let mut received = vec![];
+        for _ in 0..50 {
+            received = interp.with_gil(|py| {
+                py.eval("received", None, None)
+                    .unwrap()
+                    .extract::<Vec<(Vec<String>, String)>>()
+                    .unwrap()
+            });
+
+            if !received.is_empty() {
+                break;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        assert_eq!(
+            received,
+            vec![(vec!["foo.txt".to_string()], "/home/user".to_string())]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "splash-screen"))]
+    fn test_splash_screen_requires_feature() {
+        let mut config = default_interpreter_config();
+        config.splash_screen_image = Some(b"BM");
+
+        assert!(MainPythonInterpreter::new(config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "splash-screen")]
+    fn test_splash_screen_invalid_image() {
+        let mut config = default_interpreter_config();
+        config.splash_screen_image = Some(b"\x89PNG\r\n\x1a\n");
+
+        assert!(MainPythonInterpreter::new(config).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "output-capture"))]
+    fn test_output_capture_requires_feature() {
+        let mut config = default_interpreter_config();
+        config.stderr_capture = Some(crate::OutputCapture::Callback(std::sync::Arc::new(
+            |_: &[u8]| {},
+        )));
+
+        assert!(MainPythonInterpreter::new(config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "output-capture")]
+    fn test_output_capture() {
+        let captured = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let sink = captured.clone();
+
+        let mut config = default_interpreter_config();
+        config.stderr_capture = Some(crate::OutputCapture::Callback(std::sync::Arc::new(
+            move |data: &[u8]| sink.lock().unwrap().extend_from_slice(data),
+        )));
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        interp.with_gil(|py| {
+            py.run(
+                "import sys; sys.stderr.write('hello\\n'); sys.stderr.flush()",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+
+        // Remaining output is delivered once the stream is restored.
+        drop(interp);
+        assert_eq!(captured.lock().unwrap().as_slice(), b"hello\n");
+    }
+
+    #[test]
+    #[cfg(not(feature = "logging-bridge"))]
+    fn test_logging_bridge_requires_feature() {
+        let mut config = default_interpreter_config();
+        config.logging_bridge = true;
+
+        assert!(MainPythonInterpreter::new(config).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "logging-bridge")]
+    fn test_logging_bridge() {
+        use {
+            std::{
+                collections::BTreeMap,
+                sync::{Arc, Mutex},
+            },
+            tracing::{
+                field::{Field, Visit},
+                span, Event, Metadata, Subscriber,
+            },
+        };
+
+        type Events = Arc<Mutex<Vec<(tracing::Level, BTreeMap<String, String>)>>>;
+
+        struct Recorder(Events);
+
+        struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+        impl<'a> Visit for Fields<'a> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name().to_string(), value.to_string());
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.insert(field.name().to_string(), format!("{:?}", value));
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+                *metadata.level() <= tracing::Level::INFO
+            }
+
+            fn max_level_hint(&self) -> Option<tracing::level_filters::LevelFilter> {
+                Some(tracing::level_filters::LevelFilter::INFO)
+            }
+
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = BTreeMap::new();
+                event.record(&mut Fields(&mut fields));
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((*event.metadata().level(), fields));
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let events = Events::default();
+        tracing::subscriber::set_global_default(Recorder(events.clone())).unwrap();
+
+        let mut config = default_interpreter_config();
+        config.logging_bridge = true;
+
+        let interp = MainPythonInterpreter::new(config).unwrap();
+        interp.with_gil(|py| {
+            py.run(
+                "import logging, oxidized_runtime.logging\n\
+                assert logging.getLogger().level == logging.INFO\n\
+                logging.getLogger('myapp').warning('hello %s', 'world')\n\
+                oxidized_runtime.logging.emit('info', 'done', logger='myapp', count=2)",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+
+        assert_eq!(events[0].0, tracing::Level::WARN);
+        assert_eq!(events[0].1["message"], "hello world");
+        assert_eq!(events[0].1["logger"], "myapp");
+
+        assert_eq!(events[1].0, tracing::Level::INFO);
+        assert_eq!(events[1].1["message"], "done");
+        assert_eq!(events[1].1["fields"], "count=2");
+    }
+
+    #[test]
+    #[cfg(feature = "asyncio-bridge")]
+    fn test_asyncio_bridge() {
+        use crate::asyncio_bridge::AsyncioBridge;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let interp = MainPythonInterpreter::new(default_interpreter_config()).unwrap();
+        let bridge = interp
+            .with_gil(|py| AsyncioBridge::start(py, runtime.handle().clone()))
+            .unwrap();
+
+        bridge.register("double", |args| async move {
+            Python::with_gil(|py| {
+                let (value,) = args.as_ref(py).extract::<(i64,)>()?;
+                Ok((value * 2).into_py(py))
+            })
+        });
+
+        let future = interp
+            .with_gil(|py| {
+                let module = PyModule::from_code(
+                    py,
+                    "import oxidized_runtime.asyncio\n\
+                    async def main(value):\n    \
+                        return await oxidized_runtime.asyncio.call('double', value) + 1\n",
+                    "asyncio_test.py",
+                    "asyncio_test",
+                )?;
+                bridge.run_coroutine(py, module.call_method1("main", (20,))?)
+            })
+            .unwrap();
+
+        let result = runtime.block_on(future).unwrap();
+        assert_eq!(interp.with_gil(|py| result.extract::<i64>(py).unwrap()), 41);
+
+        let error = interp
+            .with_gil(|py| {
+                let module = py.import("asyncio_test")?;
+                let coroutine = module.getattr("main")?.call1(("foo",))?;
+                bridge.run_coroutine(py, coroutine)
+            })
+            .unwrap();
+        assert!(runtime.block_on(error).is_err());
+    }
 }
@@ -39,4 +39,124 @@ rusty_fork_test! {
     fn multiprocessing_py() {
         run_py_test("test_multiprocessing.py").unwrap()
     }
+
+    /// Tests of a package indexed by `OxidizedFinder` are run by `pytest`.
+    #[test]
+    fn run_pytest() {
+        let path = std::env::temp_dir().join(format!("pyembed-pytest-{}", std::process::id()));
+        let package = path.join("pyembed_pytest_test");
+        std::fs::create_dir_all(&package).unwrap();
+        std::fs::write(package.join("__init__.py"), "").unwrap();
+        std::fs::write(
+            package.join("conftest.py"),
+            "import pytest\n\n@pytest.fixture\ndef value():\n    return 42\n",
+        ).unwrap();
+        std::fs::write(
+            package.join("test_example.py"),
+            "import pytest\n\n\
+             def test_fixture(value):\n    assert value == 42\n\n\
+             def test_assertion_rewriting():\n    \
+             with pytest.raises(AssertionError, match='assert 1 == 2'):\n        \
+             assert 1 == 2\n",
+        ).unwrap();
+
+        let mut config = default_interpreter_config();
+        config.oxidized_importer = true;
+        config.user_site_packages = Some(path.clone());
+        config.argv = Some(vec!["prog".into(), "pyembed_pytest_test".into()]);
+        config.run_pytest = true;
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        // pytest isn't a dependency of the Python used by tests.
+        if interp.with_gil(|py| py.import("pytest").is_ok()) {
+            assert_eq!(interp.run_pytest().unwrap(), 0);
+        }
+
+        std::mem::drop(interp);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    /// Code given via `-c` is run with `sys.argv` set like `python` would.
+    #[test]
+    fn emulate_python_cli() {
+        let mut config = default_interpreter_config();
+        config.argv = Some(vec![
+            "prog".into(),
+            "-E".into(),
+            "-c".into(),
+            "import sys; sys.exit(42 if sys.argv == ['-c', 'foo'] else 1)".into(),
+            "foo".into(),
+        ]);
+        config.emulate_python_cli = true;
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        assert_eq!(interp.py_runmain(), 42);
+    }
+
+    /// `ctypes.util.find_library()` finds libraries in shared library search paths.
+    #[test]
+    fn shared_library_search_paths() {
+        let path = std::env::temp_dir().join(format!("pyembed-libs-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+
+        let filename = if cfg!(windows) {
+            "pyembedtest.dll"
+        } else if cfg!(target_os = "macos") {
+            "libpyembedtest.dylib"
+        } else {
+            "libpyembedtest.so"
+        };
+        std::fs::write(path.join(filename), b"").unwrap();
+
+        let mut config = default_interpreter_config();
+        config.shared_library_search_paths = vec![path.clone()];
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let found = py
+                .import("ctypes.util")
+                .unwrap()
+                .getattr("find_library")
+                .unwrap()
+                .call1(("pyembedtest",))
+                .unwrap()
+                .extract::<String>()
+                .unwrap();
+
+            assert_eq!(found, path.join(filename).display().to_string());
+        });
+
+        std::mem::drop(interp);
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn runtime_hooks() {
+        let mut config = default_interpreter_config();
+        config.runtime_hooks = vec![
+            ("textwrap".to_string(), "module.hooked = [1]".to_string()),
+            ("textwrap".to_string(), "module.hooked.append(2)".to_string()),
+            ("sys".to_string(), "module.pyembed_hooked = True".to_string()),
+        ];
+        let interp = MainPythonInterpreter::new(config).unwrap();
+
+        interp.with_gil(|py| {
+            let textwrap = py.import("textwrap").unwrap();
+            assert_eq!(
+                textwrap
+                    .getattr("hooked")
+                    .unwrap()
+                    .extract::<Vec<i64>>()
+                    .unwrap(),
+                vec![1, 2]
+            );
+
+            let sys = py.import("sys").unwrap();
+            assert!(sys
+                .getattr("pyembed_hooked")
+                .unwrap()
+                .extract::<bool>()
+                .unwrap());
+        });
+    }
 }
@@ -8,6 +8,7 @@ use {
     std::path::PathBuf,
 };
 
+mod api;
 mod importer;
 mod interpreter_config;
 mod main_python_interpreter;
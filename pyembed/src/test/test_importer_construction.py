@@ -27,6 +27,13 @@ class TestImporterConstruction(unittest.TestCase):
             f = OxidizedFinder()
             f.index_bytes(b"foo")
 
+    def test_resources_newer_version(self):
+        with self.assertRaisesRegex(
+            ValueError, "resources produced by newer pyoxidizer"
+        ):
+            f = OxidizedFinder()
+            f.index_bytes(b"pyembed\x05" + b"\x00" * 21)
+
     def test_resources_bad_magic(self):
         with self.assertRaisesRegex(ValueError, "unrecognized file format"):
             f = OxidizedFinder()
@@ -38,6 +45,18 @@ class TestImporterConstruction(unittest.TestCase):
             b"pyembed\x03\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
         )
 
+    def test_no_indices_v4(self):
+        f = OxidizedFinder()
+        f.index_bytes(b"pyembed\x04" + b"\x00" * 21)
+
+    def test_bad_blob_data_length_v4(self):
+        with self.assertRaisesRegex(ValueError, "mismatch between blob data length"):
+            f = OxidizedFinder()
+            f.index_bytes(
+                b"pyembed\x04\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
+                + b"\x01\x00\x00\x00\x00\x00\x00\x00"
+            )
+
     def test_multiprocessing_set_start_method(self):
         f = OxidizedFinder()
         self.assertIsNone(f.multiprocessing_set_start_method)
@@ -11,6 +11,7 @@ import unittest
 from oxidized_importer import (
     OxidizedResourceCollector,
     OxidizedFinder,
+    OxidizedResourceDescriptor,
     find_resources_in_path,
 )
 
@@ -81,6 +82,112 @@ class TestImporterConstruction(unittest.TestCase):
         f = OxidizedFinder()
         f.index_file_memory_mapped(path)
 
+    def test_index_directory(self):
+        package = self.td / "myplugin"
+        package.mkdir()
+        (package / "__init__.py").write_text("VALUE = 42\n")
+        (package / "data.txt").write_text("data")
+        dist_info = self.td / "myplugin-1.0.dist-info"
+        dist_info.mkdir()
+        (dist_info / "METADATA").write_text("Name: myplugin\nVersion: 1.0\n")
+
+        f = OxidizedFinder()
+        f.index_directory(self.td)
+
+        resources = {r.name: r for r in f.indexed_resources()}
+        self.assertIn("myplugin", resources)
+
+        resource = resources["myplugin"]
+        self.assertTrue(resource.is_module)
+        self.assertTrue(resource.is_package)
+        self.assertEqual(resource.relative_path_module_source, package / "__init__.py")
+        self.assertEqual(
+            resource.relative_path_package_resources,
+            {"data.txt": package / "data.txt"},
+        )
+        self.assertEqual(
+            resource.relative_path_distribution_resources,
+            {"METADATA": dist_info / "METADATA"},
+        )
+
+        spec = f.find_spec("myplugin", None)
+        self.assertIsNotNone(spec)
+        self.assertEqual(spec.origin, str(package / "__init__.py"))
+
+    def test_index_directory_existing_resources(self):
+        (self.td / "myplugin.py").write_text("VALUE = 42\n")
+
+        f = OxidizedFinder()
+        f.index_bytes(self.get_resources_data())
+        names = {r.name for r in f.indexed_resources()}
+
+        f.index_directory(self.td)
+        self.assertEqual({r.name for r in f.indexed_resources()}, names | {"myplugin"})
+
+    def test_find_resources(self):
+        package = self.td / "myapp" / "plugins"
+        package.mkdir(parents=True)
+        (self.td / "myapp" / "__init__.py").write_text("")
+        (package / "__init__.py").write_text("")
+        (package / "foo.py").write_text("VALUE = 42\n")
+        (package / "data.txt").write_text("data")
+        (self.td / "other.py").write_text("")
+
+        c = OxidizedResourceCollector(allowed_locations=["in-memory"])
+        for resource in find_resources_in_path(self.td):
+            c.add_in_memory(resource)
+
+        f = OxidizedFinder()
+        f.add_resources(c.oxidize()[0])
+
+        found = f.find_resources(package="myapp.plugins")
+        self.assertEqual(
+            [(d.kind, d.name) for d in found],
+            [
+                ("module", "myapp.plugins"),
+                ("package-resource", "data.txt"),
+                ("module", "myapp.plugins.foo"),
+            ],
+        )
+
+        module = found[2]
+        self.assertIsInstance(module, OxidizedResourceDescriptor)
+        self.assertEqual(module.location, "in-memory")
+        self.assertEqual(module.package, "myapp.plugins")
+        self.assertFalse(module.is_package)
+        self.assertIsNone(module.path)
+        self.assertEqual(module.read(), b"VALUE = 42\n")
+        self.assertEqual(found[1].package, "myapp.plugins")
+        self.assertEqual(found[1].read(), b"data")
+
+        self.assertEqual(
+            [d.name for d in f.find_resources(kind="module", location="in-memory")],
+            ["myapp", "myapp.plugins", "myapp.plugins.foo", "other"],
+        )
+        self.assertEqual(f.find_resources(location="filesystem-relative"), [])
+
+        with self.assertRaises(ValueError):
+            f.find_resources(kind="unknown")
+        with self.assertRaises(ValueError):
+            f.find_resources(location="unknown")
+
+    def test_find_resources_filesystem_relative(self):
+        package = self.td / "myplugin"
+        package.mkdir()
+        (package / "__init__.py").write_text("VALUE = 42\n")
+
+        f = OxidizedFinder()
+        f.index_directory(self.td)
+
+        found = f.find_resources(package="myplugin")
+        self.assertEqual(len(found), 1)
+        self.assertEqual(found[0].location, "filesystem-relative")
+        self.assertEqual(found[0].path, package / "__init__.py")
+        self.assertEqual(found[0].read(), b"VALUE = 42\n")
+
+    def test_user_site_packages(self):
+        self.assertIsNone(OxidizedFinder().user_site_packages)
+
 
 if __name__ == "__main__":
     unittest.main()
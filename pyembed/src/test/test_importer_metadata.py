@@ -267,8 +267,93 @@ class TestImporterMetadata(unittest.TestCase):
 
         dist = list(f.find_distributions())[0]
 
-        with self.assertRaises(AttributeError):
-            dist.locate_file("METADATA")
+        path = dist.locate_file("my_package/__init__.py")
+        self.assertIsInstance(path, pathlib.Path)
+        self.assertEqual(path.parts[-2:], ("my_package", "__init__.py"))
+
+    def test_name(self):
+        self._write_metadata()
+        f = self._finder_from_td()
+
+        dist = list(f.find_distributions())[0]
+        self.assertEqual(dist.name, "my_package")
+        self.assertEqual(dist._normalized_name, "my_package")
+
+    def test_files_record(self):
+        self._write_metadata()
+
+        with (self.td / "my_package-1.0.dist-info" / "RECORD").open("w") as fh:
+            fh.write("my_package/__init__.py,sha256=abc,42\n")
+            fh.write("my_package-1.0.dist-info/RECORD,,\n")
+
+        f = self._finder_from_td()
+        dist = list(f.find_distributions())[0]
+
+        files = dist.files
+        self.assertEqual(
+            [str(p) for p in files],
+            ["my_package/__init__.py", "my_package-1.0.dist-info/RECORD"],
+        )
+        self.assertIsInstance(files[0], importlib.metadata.PackagePath)
+        self.assertEqual(files[0].hash.mode, "sha256")
+        self.assertEqual(files[0].hash.value, "abc")
+        self.assertEqual(files[0].size, 42)
+        self.assertIs(files[0].dist, dist)
+        self.assertIsNone(files[1].hash)
+        self.assertIsNone(files[1].size)
+
+    def test_files_synthesized(self):
+        self._write_metadata()
+
+        package_path = self.td / "my_package"
+        package_path.mkdir()
+        with (package_path / "__init__.py").open("wb"):
+            pass
+        with (package_path / "mod.py").open("wb"):
+            pass
+        with (package_path / "data.txt").open("wb"):
+            pass
+
+        f = self._finder_from_td()
+        dist = list(f.find_distributions())[0]
+
+        self.assertEqual(
+            [str(p) for p in dist.files],
+            [
+                "my_package-1.0.dist-info/METADATA",
+                "my_package/__init__.py",
+                "my_package/data.txt",
+                "my_package/mod.py",
+            ],
+        )
+        self.assertIsNone(dist.files[0].hash)
+
+    def test_importlib_metadata_functions(self):
+        self._write_metadata()
+
+        dist_info = self.td / "my_package-1.0.dist-info"
+        with (dist_info / "METADATA").open("a", encoding="utf-8") as fh:
+            fh.write("Requires-Dist: foo\n")
+        with (dist_info / "entry_points.txt").open("w", encoding="utf-8") as fh:
+            fh.write("[console_scripts]\n")
+            fh.write("script = my_package:module\n")
+
+        f = self._finder_from_td()
+
+        sys.meta_path = [f]
+        sys.path = []
+
+        eps = importlib.metadata.entry_points()["console_scripts"]
+        self.assertEqual([ep.name for ep in eps], ["script"])
+        self.assertEqual(importlib.metadata.requires("my_package"), ["foo"])
+        self.assertIn(
+            "my_package-1.0.dist-info/METADATA",
+            [str(p) for p in importlib.metadata.files("my_package")],
+        )
+        self.assertEqual(
+            importlib.metadata.distribution("my_package").read_text("entry_points.txt"),
+            "[console_scripts]\nscript = my_package:module\n",
+        )
 
     def test_distribution_from_name(self):
         self._write_metadata()
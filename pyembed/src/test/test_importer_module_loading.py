@@ -5,6 +5,8 @@
 import importlib.machinery
 import importlib.util
 import importlib
+import inspect
+import linecache
 import marshal
 import os
 import pathlib
@@ -45,12 +47,12 @@ class TestImporterModuleLoading(unittest.TestCase):
 
         return package_path
 
-    def _finder_from_td(self):
+    def _finder_from_td(self, **kwargs):
         collector = OxidizedResourceCollector(allowed_locations=["in-memory"])
         for r in find_resources_in_path(self.td):
             collector.add_in_memory(r)
 
-        f = OxidizedFinder()
+        f = OxidizedFinder(**kwargs)
         f.add_resources(collector.oxidize()[0])
 
         return f
@@ -102,6 +104,98 @@ class TestImporterModuleLoading(unittest.TestCase):
         with self.assertRaises(ImportError):
             f.get_filename("my_package")
 
+    def test_coverage_mode(self):
+        p = self._make_package("my_package")
+
+        with (p / "mod.py").open("wb") as fh:
+            fh.write(b"def func():\n    return 42\n")
+
+        f = self._finder_from_td(coverage_mode=True)
+        self.assertTrue(f.coverage_mode)
+
+        package_dir = os.path.join(f.path_hook_base_str, "my_package")
+        package_path = os.path.join(package_dir, "__init__.py")
+        module_path = os.path.join(package_dir, "mod.py")
+
+        spec = f.find_spec("my_package", None)
+        self.assertEqual(spec.origin, package_path)
+        self.assertTrue(spec.has_location)
+        self.assertEqual(spec.submodule_search_locations, [package_dir])
+
+        self.assertEqual(f.get_filename("my_package.mod"), module_path)
+        self.assertEqual(f.get_code("my_package.mod").co_filename, module_path)
+
+        spec = f.find_spec("my_package.mod", None)
+        m = importlib.util.module_from_spec(spec)
+        sys.modules["my_package.mod"] = m
+        self.addCleanup(sys.modules.pop, "my_package.mod", None)
+        f.exec_module(m)
+
+        self.assertEqual(m.__file__, module_path)
+        self.assertEqual(m.func.__code__.co_filename, module_path)
+        self.assertEqual(inspect.getsource(m.func), "def func():\n    return 42\n")
+
+    def test_debugger_support(self):
+        p = self._make_package("my_package")
+
+        with (p / "mod.py").open("wb") as fh:
+            fh.write(b"def func():\n    return 42\n")
+
+        f = self._finder_from_td(debugger_support=True)
+        self.assertTrue(f.debugger_support)
+        self.assertFalse(f.coverage_mode)
+
+        module_path = os.path.join(f.path_hook_base_str, "my_package", "mod.py")
+        self.addCleanup(linecache.cache.pop, module_path, None)
+
+        spec = f.find_spec("my_package.mod", None)
+        self.assertEqual(spec.origin, module_path)
+
+        m = importlib.util.module_from_spec(spec)
+        f.exec_module(m)
+
+        self.assertEqual(m.func.__code__.co_filename, module_path)
+        # Source is available without module globals, like debuggers request it.
+        self.assertEqual(linecache.getline(module_path, 2), "    return 42\n")
+
+    def test_dunder_file_packages(self):
+        p = self._make_package("my_package")
+
+        with (p / "mod.py").open("wb") as fh:
+            fh.write(
+                b"import os\n"
+                b"path = os.path.join(os.path.dirname(__file__), 'data')\n"
+                b"with open(os.path.join(path, 'values.txt'), 'rb') as fh:\n"
+                b"    VALUES = fh.read()\n"
+            )
+
+        (p / "data").mkdir()
+        with (p / "data" / "values.txt").open("wb") as fh:
+            fh.write(b"42")
+
+        root = tempfile.TemporaryDirectory(prefix="oxidized_importer-test-")
+        self.addCleanup(root.cleanup)
+
+        f = self._finder_from_td(
+            dunder_file_root=root.name, dunder_file_packages=["my_package"]
+        )
+
+        module_path = os.path.join(root.name, "my_package", "mod.py")
+
+        spec = f.find_spec("my_package.mod", None)
+        self.assertEqual(spec.origin, module_path)
+        self.assertTrue(spec.has_location)
+
+        m = importlib.util.module_from_spec(spec)
+        f.exec_module(m)
+
+        self.assertEqual(m.__file__, module_path)
+        self.assertEqual(m.VALUES, b"42")
+
+    def test_dunder_file_packages_requires_root(self):
+        with self.assertRaises(ValueError):
+            OxidizedFinder(dunder_file_packages=["my_package"])
+
     def test_bytecode_package(self):
         p = self._make_package("my_package")
 
@@ -148,6 +242,40 @@ class TestImporterModuleLoading(unittest.TestCase):
         with self.assertRaises(ImportError):
             f.get_filename("my_package")
 
+    def test_namespace_package(self):
+        (self.td / "ns").mkdir()
+        with (self.td / "ns" / "memory.py").open("wb") as fh:
+            fh.write(b"VALUE = 'memory'\n")
+
+        portion = tempfile.TemporaryDirectory(prefix="oxidized_importer-test-")
+        self.addCleanup(portion.cleanup)
+        portion_path = pathlib.Path(portion.name)
+        (portion_path / "ns").mkdir()
+        with (portion_path / "ns" / "disk.py").open("wb") as fh:
+            fh.write(b"VALUE = 'disk'\n")
+
+        f = self._finder_from_td()
+
+        spec = f.find_spec("ns", [portion.name, str(self.td / "missing")])
+        self.assertIsInstance(spec, importlib.machinery.ModuleSpec)
+        self.assertIsInstance(spec.loader, OxidizedFinder)
+        self.assertIsNone(spec.origin)
+        self.assertEqual(
+            spec.submodule_search_locations,
+            [
+                os.path.join(f.path_hook_base_str, "ns"),
+                str(portion_path / "ns"),
+            ],
+        )
+
+        m = importlib.util.module_from_spec(spec)
+        self.assertFalse(hasattr(m, "__file__"))
+        self.assertIsNone(f.exec_module(m))
+
+        disk = importlib.machinery.PathFinder.find_spec("ns.disk", m.__path__)
+        self.assertEqual(disk.origin, str(portion_path / "ns" / "disk.py"))
+        self.assertIsNotNone(f.find_spec("ns.memory", m.__path__))
+
     def test_dot_init(self):
         p = self._make_package("dotinit")
 
@@ -0,0 +1,214 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Apply update patches produced by `pyoxidizer diff-build`.
+
+The layout of update patches is described by
+[python_packaging::update_manifest].
+*/
+
+pub use python_packaging::update_manifest::{
+    parse_update_manifest, render_update_manifest, UpdateEntry, UpdateOperation,
+    UPDATE_MANIFEST_FILENAME, UPDATE_MANIFEST_HEADER,
+};
+
+use {
+    anyhow::{anyhow, Context, Result},
+    sha2::Digest,
+    std::{
+        io::Read,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Largest zstd window log patches may use.
+///
+/// Windows of 1 GiB are the largest supported on 32-bit platforms.
+const MAX_WINDOW_LOG: u32 = 30;
+
+/// Hex SHA-256 of data.
+fn sha256_hex(data: &[u8]) -> String {
+    sha2::Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Decompress a patch using the old content as a dictionary.
+pub fn decode_patch(old: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = zstd::stream::Decoder::with_dictionary(patch, old)?;
+    decoder.window_log_max(MAX_WINDOW_LOG)?;
+
+    let mut data = vec![];
+    decoder
+        .read_to_end(&mut data)
+        .context("decompressing patch")?;
+
+    Ok(data)
+}
+
+/// Apply an update patch to a build, writing the new build to a directory.
+///
+/// `current_dir` holds the old build, such as the directory of the running
+/// application. The new build is materialized in `dest_dir`, which must not
+/// exist, leaving `current_dir` untouched. Callers then replace the old build
+/// with `dest_dir`, e.g. on the next application launch.
+///
+/// Digests of old files are verified before they are used and digests of all
+/// new files are verified before they are written.
+pub fn apply_update_patch(current_dir: &Path, patch_dir: &Path, dest_dir: &Path) -> Result<()> {
+    let manifest_path = patch_dir.join(UPDATE_MANIFEST_FILENAME);
+    let entries = parse_update_manifest(
+        &std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("reading {}", manifest_path.display()))?,
+    )?;
+
+    if dest_dir.exists() {
+        return Err(anyhow!("{} already exists", dest_dir.display()));
+    }
+
+    let read_old = |entry: &UpdateEntry| -> Result<Vec<u8>> {
+        let path = entry.resolve_in(current_dir)?;
+        let data = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+        if entry.old_sha256.as_deref() != Some(sha256_hex(&data).as_str()) {
+            return Err(anyhow!(
+                "{} does not match the build the update patch was produced from",
+                path.display()
+            ));
+        }
+
+        Ok(data)
+    };
+
+    let read_data = |entry: &UpdateEntry, extension: &str| -> Result<Vec<u8>> {
+        let mut path = entry.resolve_in(&patch_dir.join("data"))?.into_os_string();
+        path.push(".");
+        path.push(extension);
+        let path = PathBuf::from(path);
+
+        std::fs::read(&path).with_context(|| format!("reading {}", path.display()))
+    };
+
+    for entry in &entries {
+        let data = match entry.operation {
+            UpdateOperation::Remove => continue,
+            UpdateOperation::Unchanged => read_old(entry)?,
+            UpdateOperation::Add => zstd::stream::decode_all(read_data(entry, "zst")?.as_slice())
+                .with_context(|| format!("decompressing {}", entry.path))?,
+            UpdateOperation::Patch => {
+                decode_patch(&read_old(entry)?, &read_data(entry, "zstpatch")?)
+                    .with_context(|| format!("patching {}", entry.path))?
+            }
+        };
+
+        if entry.new_sha256.as_deref() != Some(sha256_hex(&data).as_str()) {
+            return Err(anyhow!(
+                "digest of updated {} does not match update manifest",
+                entry.path
+            ));
+        }
+
+        let dest_path = entry.resolve_in(dest_dir)?;
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        std::fs::write(&dest_path, &data)
+            .with_context(|| format!("writing {}", dest_path.display()))?;
+
+        #[cfg(unix)]
+        if entry.executable {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(&dest_path, std::fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("making {} executable", dest_path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Write};
+
+    #[test]
+    fn test_apply_update_patch() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let current = temp_dir.path().join("current");
+        let patch = temp_dir.path().join("patch");
+        let dest = temp_dir.path().join("dest");
+
+        let old_exe = b"old executable content ".repeat(100);
+        let new_exe = [old_exe.as_slice(), b"appended"].concat();
+
+        std::fs::create_dir_all(current.join("lib"))?;
+        std::fs::create_dir_all(patch.join("data/lib"))?;
+        std::fs::write(current.join("app"), &old_exe)?;
+        std::fs::write(current.join("lib/same.txt"), b"same")?;
+        std::fs::write(current.join("removed.txt"), b"removed")?;
+
+        let mut encoder = zstd::stream::Encoder::with_dictionary(vec![], 3, &old_exe)?;
+        encoder.write_all(&new_exe)?;
+        std::fs::write(patch.join("data/app.zstpatch"), encoder.finish()?)?;
+        std::fs::write(
+            patch.join("data/lib/new.txt.zst"),
+            zstd::stream::encode_all(b"new".as_slice(), 3)?,
+        )?;
+
+        let entries = vec![
+            UpdateEntry {
+                operation: UpdateOperation::Patch,
+                old_sha256: Some(sha256_hex(&old_exe)),
+                new_sha256: Some(sha256_hex(&new_exe)),
+                new_size: Some(new_exe.len() as u64),
+                executable: true,
+                path: "app".to_string(),
+            },
+            UpdateEntry {
+                operation: UpdateOperation::Unchanged,
+                old_sha256: Some(sha256_hex(b"same")),
+                new_sha256: Some(sha256_hex(b"same")),
+                new_size: Some(4),
+                executable: false,
+                path: "lib/same.txt".to_string(),
+            },
+            UpdateEntry {
+                operation: UpdateOperation::Add,
+                old_sha256: None,
+                new_sha256: Some(sha256_hex(b"new")),
+                new_size: Some(3),
+                executable: false,
+                path: "lib/new.txt".to_string(),
+            },
+            UpdateEntry {
+                operation: UpdateOperation::Remove,
+                old_sha256: Some(sha256_hex(b"removed")),
+                new_sha256: None,
+                new_size: None,
+                executable: false,
+                path: "removed.txt".to_string(),
+            },
+        ];
+
+        std::fs::write(
+            patch.join(UPDATE_MANIFEST_FILENAME),
+            render_update_manifest(&entries),
+        )?;
+
+        apply_update_patch(&current, &patch, &dest)?;
+
+        assert_eq!(std::fs::read(dest.join("app"))?, new_exe);
+        assert_eq!(std::fs::read(dest.join("lib/same.txt"))?, b"same");
+        assert_eq!(std::fs::read(dest.join("lib/new.txt"))?, b"new");
+        assert!(!dest.join("removed.txt").exists());
+
+        // Applying against a different old build fails.
+        std::fs::write(current.join("app"), b"tampered")?;
+        assert!(apply_update_patch(&current, &patch, &temp_dir.path().join("dest2")).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,638 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Application self-updates.
+
+Updates are discovered by reading a static JSON feed, e.g.
+
+```json
+{
+  "channels": {
+    "stable": [
+      {
+        "version": "1.2.0",
+        "platform": "x86_64-linux",
+        "url": "myapp-1.2.0-x86_64-linux",
+        "sha256": "<hex encoded SHA-256 of the executable>",
+        "signature": "<hex encoded Ed25519 signature>"
+      }
+    ]
+  }
+}
+```
+
+Each channel lists releases. The newest release of the configured channel
+for the running platform that is newer than the running version is the
+update. Platforms are of the form `<arch>-<os>` using Rust's names for
+architectures and operating systems, e.g. `aarch64-macos` or
+`x86_64-windows`. `url` may be relative to the feed.
+
+`signature` is an Ed25519 signature from the configured public key of
+`<channel>\n<version>\n<platform>\n<sha256>\n` (see
+[UpdateRelease::signed_payload]). Releases without a valid signature are
+ignored. So the feed can't point a platform at another platform's
+executable, offer an older release as an update or move a release into
+another channel, e.g. a beta release into the stable channel. Downloaded executables must match the signed digest and be
+strictly newer than the running version. They then replace the running
+executable, taking effect the next time the application starts.
+
+Replacement is atomic on Unix, where the new executable is renamed over the
+old one. Windows doesn't allow replacing running executables, so the
+running executable is first renamed to `<name>.old`, which is deleted by a
+later run. Downloads are staged in a file unique to the process, so
+concurrently running instances don't interfere with each other.
+*/
+
+use {
+    crate::{
+        config::ResolvedOxidizedPythonInterpreterConfig, error::NewInterpreterError,
+        interpreter::run_code_in_namespace, squirrel::compare_versions,
+    },
+    anyhow::{anyhow, Context, Result},
+    ed25519_dalek::Verifier,
+    pyo3::{exceptions::PyRuntimeError, prelude::*},
+    serde::Deserialize,
+    sha2::Digest,
+    std::{
+        cmp::Ordering,
+        collections::BTreeMap,
+        io::Write,
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+};
+
+/// Channel used if none is configured.
+pub const DEFAULT_UPDATE_CHANNEL: &str = "stable";
+
+/// Age after which staged downloads are assumed to be abandoned.
+///
+/// Younger staged files may still be written by another running instance.
+const STALE_STAGED_FILE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Python code checking for and installing updates in a background thread.
+///
+/// Failures are ignored. The check is retried on next start.
+const UPDATE_CODE: &str = "\
+import threading, urllib.parse, urllib.request
+
+def update(feed_url, updater):
+    try:
+        with urllib.request.urlopen(feed_url, timeout=30) as fh:
+            feed = fh.read()
+        url = updater.find_update(feed)
+        if url is None:
+            return
+        url = urllib.parse.urljoin(feed_url, url)
+        with urllib.request.urlopen(url, timeout=300) as fh:
+            data = fh.read()
+        updater.install(feed, data)
+    except Exception:
+        pass
+
+threading.Thread(target=update, args=(feed_url, updater), daemon=True).start()
+";
+
+/// A release listed in an update feed.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct UpdateRelease {
+    /// Version of the release.
+    pub version: String,
+
+    /// Platform the release runs on.
+    pub platform: String,
+
+    /// URL of the executable, possibly relative to the feed.
+    pub url: String,
+
+    /// Hex encoded SHA-256 digest of the executable.
+    pub sha256: String,
+
+    /// Hex encoded Ed25519 signature of [Self::signed_payload()] for the
+    /// channel listing the release.
+    pub signature: String,
+}
+
+impl UpdateRelease {
+    /// The data signed by the release's signature.
+    ///
+    /// This binds the channel, version and platform of the release to the
+    /// digest of its executable.
+    pub fn signed_payload(&self, channel: &str) -> Vec<u8> {
+        format!(
+            "{}\n{}\n{}\n{}\n",
+            channel, self.version, self.platform, self.sha256
+        )
+        .into_bytes()
+    }
+}
+
+/// An update feed.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct UpdateFeed {
+    /// Releases by channel name.
+    pub channels: BTreeMap<String, Vec<UpdateRelease>>,
+}
+
+impl UpdateFeed {
+    /// Parse a JSON update feed.
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).context("parsing update feed")
+    }
+}
+
+/// The platform of the running executable, as named in update feeds.
+pub fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Hex SHA-256 of data.
+fn sha256_hex(data: &[u8]) -> String {
+    sha2::Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Decode a hex string.
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    if !value.is_ascii() || value.len() % 2 != 0 {
+        return Err(anyhow!("invalid hex string: {}", value));
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&value[i..i + 2], 16)
+                .map_err(|_| anyhow!("invalid hex string: {}", value))
+        })
+        .collect()
+}
+
+/// Checks for and installs updates of an executable.
+#[derive(Clone, Debug)]
+pub struct Updater {
+    exe: PathBuf,
+    current_version: String,
+    channel: String,
+    platform: String,
+    public_key: ed25519_dalek::PublicKey,
+}
+
+impl Updater {
+    /// Construct an instance updating `exe`.
+    ///
+    /// `public_key` is the hex encoded Ed25519 public key releases must be
+    /// signed with.
+    pub fn new(
+        exe: impl AsRef<Path>,
+        current_version: &str,
+        channel: &str,
+        public_key: &str,
+    ) -> Result<Self> {
+        let public_key = ed25519_dalek::PublicKey::from_bytes(&decode_hex(public_key)?)
+            .map_err(|e| anyhow!("invalid update public key: {}", e))?;
+
+        Ok(Self {
+            exe: exe.as_ref().to_path_buf(),
+            current_version: current_version.to_string(),
+            channel: channel.to_string(),
+            platform: current_platform(),
+            public_key,
+        })
+    }
+
+    /// Construct an instance from interpreter configuration.
+    ///
+    /// Returns `None` if updates aren't configured.
+    pub fn from_config(config: &ResolvedOxidizedPythonInterpreterConfig) -> Result<Option<Self>> {
+        if config.update_feed_url.is_none() {
+            return Ok(None);
+        }
+
+        let current_version = config
+            .update_current_version
+            .as_deref()
+            .ok_or_else(|| anyhow!("update_current_version must be set to check for updates"))?;
+        let public_key = config
+            .update_public_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("update_public_key must be set to check for updates"))?;
+        let channel = config
+            .update_channel
+            .as_deref()
+            .unwrap_or(DEFAULT_UPDATE_CHANNEL);
+
+        Ok(Some(Self::new(
+            config.exe(),
+            current_version,
+            channel,
+            public_key,
+        )?))
+    }
+
+    /// Path of the executable being updated.
+    pub fn exe(&self) -> &Path {
+        &self.exe
+    }
+
+    /// Version of the running release.
+    pub fn current_version(&self) -> &str {
+        &self.current_version
+    }
+
+    /// Whether a release is strictly newer than the running release.
+    fn is_newer(&self, release: &UpdateRelease) -> bool {
+        compare_versions(&release.version, &self.current_version) == Some(Ordering::Greater)
+    }
+
+    /// Find the newest validly signed release newer than the running release.
+    ///
+    /// `feed` is the content of the JSON update feed.
+    pub fn find_update(&self, feed: &[u8]) -> Result<Option<UpdateRelease>> {
+        let feed = UpdateFeed::from_json(feed)?;
+
+        let mut candidates = feed
+            .channels
+            .get(&self.channel)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.platform == self.platform)
+            .filter(|r| self.is_newer(r))
+            .filter(|r| self.verify_release(r).is_ok())
+            .collect::<Vec<_>>();
+
+        candidates
+            .sort_by(|a, b| compare_versions(&a.version, &b.version).unwrap_or(Ordering::Equal));
+
+        Ok(candidates.pop())
+    }
+
+    /// Verify the signature of a release of the configured channel.
+    pub fn verify_release(&self, release: &UpdateRelease) -> Result<()> {
+        let signature =
+            ed25519_dalek::Signature::try_from(decode_hex(&release.signature)?.as_slice())
+                .map_err(|e| anyhow!("invalid signature of release {}: {}", release.version, e))?;
+
+        self.public_key
+            .verify(&release.signed_payload(&self.channel), &signature)
+            .map_err(|_| {
+                anyhow!(
+                    "signature verification of release {} failed",
+                    release.version
+                )
+            })
+    }
+
+    /// Verify that executable data belongs to a validly signed release for this platform.
+    pub fn verify(&self, release: &UpdateRelease, data: &[u8]) -> Result<()> {
+        self.verify_release(release)?;
+
+        if release.platform != self.platform {
+            return Err(anyhow!(
+                "release {} is for platform {}, not {}",
+                release.version,
+                release.platform,
+                self.platform
+            ));
+        }
+
+        if !sha256_hex(data).eq_ignore_ascii_case(&release.sha256) {
+            return Err(anyhow!(
+                "digest of release {} does not match",
+                release.version
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Verify and install a downloaded release.
+    ///
+    /// The release must be strictly newer than the running release.
+    pub fn install(&self, release: &UpdateRelease, data: &[u8]) -> Result<()> {
+        if !self.is_newer(release) {
+            return Err(anyhow!(
+                "release {} is not newer than {}",
+                release.version,
+                self.current_version
+            ));
+        }
+
+        self.verify(release, data)?;
+
+        replace_executable(&self.exe, data)
+    }
+}
+
+/// Atomically replace an executable file with new content.
+///
+/// The replacement is staged next to the executable, in a file unique to
+/// the process, and inherits the executable's permissions.
+pub fn replace_executable(exe: &Path, data: &[u8]) -> Result<()> {
+    let dir = exe
+        .parent()
+        .ok_or_else(|| anyhow!("unable to resolve directory of {}", exe.display()))?;
+    let name = exe
+        .file_name()
+        .ok_or_else(|| anyhow!("unable to resolve filename of {}", exe.display()))?
+        .to_string_lossy();
+    let staged = dir.join(format!(".{}.{}.update", name, std::process::id()));
+
+    let mut fh =
+        std::fs::File::create(&staged).with_context(|| format!("creating {}", staged.display()))?;
+    fh.write_all(data)
+        .and_then(|_| fh.sync_all())
+        .with_context(|| format!("writing {}", staged.display()))?;
+    drop(fh);
+
+    let permissions = std::fs::metadata(exe)
+        .with_context(|| format!("reading metadata of {}", exe.display()))?
+        .permissions();
+    if let Err(e) = std::fs::set_permissions(&staged, permissions) {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e).with_context(|| format!("setting permissions of {}", staged.display()));
+    }
+
+    // Running executables can't be replaced on Windows. But they can be
+    // renamed.
+    #[cfg(windows)]
+    {
+        let old = dir.join(format!("{}.old", name));
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old).with_context(|| format!("renaming {}", exe.display()))?;
+
+        if let Err(e) = std::fs::rename(&staged, exe) {
+            let _ = std::fs::rename(&old, exe);
+            return Err(e).with_context(|| format!("replacing {}", exe.display()));
+        }
+    }
+
+    #[cfg(not(windows))]
+    std::fs::rename(&staged, exe).with_context(|| format!("replacing {}", exe.display()))?;
+
+    Ok(())
+}
+
+/// Remove files left behind by previous updates of an executable.
+///
+/// Staged downloads are only removed once they are older than `max_age`,
+/// as other running instances may still be writing them.
+fn remove_stale_files(exe: &Path, max_age: Duration) {
+    let (dir, name) = match (exe.parent(), exe.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return,
+    };
+
+    let _ = std::fs::remove_file(dir.join(format!("{}.old", name)));
+
+    let prefix = format!(".{}.", name);
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let filename = entry.file_name();
+        let filename = filename.to_string_lossy();
+
+        if !filename.starts_with(&prefix) || !filename.ends_with(".update") {
+            continue;
+        }
+
+        let age = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+
+        if matches!(age, Some(age) if age >= max_age) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Exposes an [Updater] to the Python code checking for updates.
+#[pyclass(module = "pyembed")]
+struct PythonUpdater {
+    updater: Updater,
+}
+
+#[pymethods]
+impl PythonUpdater {
+    fn find_update(&self, feed: &[u8]) -> PyResult<Option<String>> {
+        self.updater
+            .find_update(feed)
+            .map(|release| release.map(|r| r.url))
+            .map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))
+    }
+
+    fn install(&self, feed: &[u8], data: &[u8]) -> PyResult<()> {
+        let release = self
+            .updater
+            .find_update(feed)
+            .and_then(|release| release.ok_or_else(|| anyhow!("no update available")))
+            .map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))?;
+
+        self.updater
+            .install(&release, data)
+            .map_err(|e| PyRuntimeError::new_err(format!("{:#}", e)))
+    }
+}
+
+/// Check for and install updates in a background thread, if configured.
+///
+/// The interpreter must be initialized.
+pub(crate) fn start_background_update(
+    py: Python,
+    config: &ResolvedOxidizedPythonInterpreterConfig,
+) -> Result<(), NewInterpreterError> {
+    let updater = match Updater::from_config(config)
+        .map_err(|e| NewInterpreterError::Dynamic(format!("{:#}", e)))?
+    {
+        Some(updater) => updater,
+        None => return Ok(()),
+    };
+
+    remove_stale_files(updater.exe(), STALE_STAGED_FILE_AGE);
+
+    let updater = Py::new(py, PythonUpdater { updater })
+        .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "creating updater"))?;
+
+    run_code_in_namespace(
+        py,
+        UPDATE_CODE,
+        &[
+            ("feed_url", config.update_feed_url.to_object(py)),
+            ("updater", updater.into_py(py)),
+        ],
+        "starting update check",
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, ed25519_dalek::ExpandedSecretKey};
+
+    const SECRET_KEY: [u8; 32] = [7; 32];
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn public_key() -> String {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&SECRET_KEY).unwrap();
+
+        hex(ed25519_dalek::PublicKey::from(&secret).as_bytes())
+    }
+
+    /// Obtain a release of executable data signed for a channel.
+    fn signed_release(channel: &str, version: &str, platform: &str, data: &[u8]) -> UpdateRelease {
+        let mut release = UpdateRelease {
+            version: version.to_string(),
+            platform: platform.to_string(),
+            url: format!("app-{}", version),
+            sha256: sha256_hex(data),
+            signature: "".to_string(),
+        };
+
+        let secret = ed25519_dalek::SecretKey::from_bytes(&SECRET_KEY).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret);
+        let signature =
+            ExpandedSecretKey::from(&secret).sign(&release.signed_payload(channel), &public);
+        release.signature = hex(&signature.to_bytes());
+
+        release
+    }
+
+    /// Obtain a release of executable data signed for the stable channel.
+    fn release(version: &str, platform: &str, data: &[u8]) -> UpdateRelease {
+        signed_release("stable", version, platform, data)
+    }
+
+    fn feed(releases: &[(&str, &UpdateRelease)]) -> Vec<u8> {
+        let channels = releases
+            .iter()
+            .map(|(channel, r)| {
+                format!(
+                    r#""{}": [{{"version": "{}", "platform": "{}", "url": "{}", "sha256": "{}", "signature": "{}"}}]"#,
+                    channel, r.version, r.platform, r.url, r.sha256, r.signature
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(r#"{{"channels": {{{}}}}}"#, channels).into_bytes()
+    }
+
+    #[test]
+    fn test_decode_hex() -> Result<()> {
+        assert_eq!(decode_hex("00ff10")?, vec![0, 255, 16]);
+        assert!(decode_hex("0").is_err());
+        assert!(decode_hex("zz").is_err());
+        assert!(decode_hex("é").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_update() -> Result<()> {
+        let updater = Updater::new("app", "1.0.0", "stable", &public_key())?;
+        let platform = current_platform();
+
+        let found = updater
+            .find_update(&feed(&[
+                ("stable", &release("1.1.0", &platform, b"new")),
+                (
+                    "beta",
+                    &signed_release("beta", "2.0.0", &platform, b"newer"),
+                ),
+            ]))?
+            .unwrap();
+        assert_eq!(found.version, "1.1.0");
+        assert_eq!(found.url, "app-1.1.0");
+
+        assert!(updater
+            .find_update(&feed(&[("stable", &release("1.0.0", &platform, b"new"))]))?
+            .is_none());
+        assert!(updater
+            .find_update(&feed(&[("stable", &release("1.1.0", "other-os", b"new"))]))?
+            .is_none());
+        assert!(updater.find_update(b"not json").is_err());
+
+        // Signatures bind the version and platform of a release.
+        let mut downgrade = release("0.9.0", &platform, b"old");
+        downgrade.version = "1.1.0".to_string();
+        assert!(updater
+            .find_update(&feed(&[("stable", &downgrade)]))?
+            .is_none());
+
+        let mut other_platform = release("1.1.0", "other-os", b"other");
+        other_platform.platform = platform.clone();
+        assert!(updater
+            .find_update(&feed(&[("stable", &other_platform)]))?
+            .is_none());
+
+        // Signatures bind the channel of a release.
+        let beta = signed_release("beta", "1.1.0", &platform, b"beta");
+        assert!(updater.find_update(&feed(&[("stable", &beta)]))?.is_none());
+        assert!(updater.verify(&beta, b"beta").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_install() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let exe = temp_dir.path().join("app");
+        std::fs::write(&exe, b"old")?;
+
+        let updater = Updater::new(&exe, "1.0.0", "stable", &public_key())?;
+        let found = updater
+            .find_update(&feed(&[(
+                "stable",
+                &release("1.1.0", &current_platform(), b"new"),
+            )]))?
+            .unwrap();
+
+        assert!(updater.install(&found, b"tampered").is_err());
+        assert_eq!(std::fs::read(&exe)?, b"old");
+
+        assert!(updater
+            .install(&release("1.0.0", &current_platform(), b"same"), b"same")
+            .is_err());
+        assert!(updater
+            .install(&release("1.2.0", "other-os", b"other"), b"other")
+            .is_err());
+        assert_eq!(std::fs::read(&exe)?, b"old");
+
+        updater.install(&found, b"new")?;
+        assert_eq!(std::fs::read(&exe)?, b"new");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_stale_files() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let exe = temp_dir.path().join("app");
+        std::fs::write(&exe, b"app")?;
+        std::fs::write(temp_dir.path().join("app.old"), b"old")?;
+        std::fs::write(temp_dir.path().join(".app.42.update"), b"staged")?;
+        std::fs::write(temp_dir.path().join(".other.42.update"), b"staged")?;
+
+        remove_stale_files(&exe, STALE_STAGED_FILE_AGE);
+        assert!(!temp_dir.path().join("app.old").exists());
+        assert!(temp_dir.path().join(".app.42.update").exists());
+
+        remove_stale_files(&exe, Duration::from_secs(0));
+        assert!(!temp_dir.path().join(".app.42.update").exists());
+        assert!(temp_dir.path().join(".other.42.update").exists());
+
+        Ok(())
+    }
+}
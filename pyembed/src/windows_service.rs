@@ -0,0 +1,457 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Running applications as Windows services.
+
+The Windows service control manager starts services by running their
+executable, which must connect to it by calling
+`StartServiceCtrlDispatcherW()`. [run_windows_service] does this and
+dispatches the events of the service to a Python object, so applications
+don't need `pywin32`'s service host.
+
+The Python object is named by a `module:attribute` string. If the attribute
+is a class, it is instantiated without arguments. The object must have the
+following methods:
+
+`run()`
+   Runs the service. The service stops when it returns.
+
+`stop()`
+   Requests the service to stop, e.g. when the service is stopped or the
+   system shuts down. It is called from another thread than `run()` and
+   should return quickly, causing `run()` to return.
+
+If the object also has `pause()` and `resume()` methods, the service can be
+paused and continued. They are called from the same thread as `stop()`.
+
+e.g.
+
+```python
+import threading
+
+class Service:
+    def __init__(self):
+        self.stopped = threading.Event()
+
+    def run(self):
+        while not self.stopped.wait(60):
+            do_work()
+
+    def stop(self):
+        self.stopped.set()
+```
+*/
+
+// Service handling is only compiled on Windows.
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use {
+    crate::{error::NewInterpreterError, interpreter::MainPythonInterpreter},
+    pyo3::{
+        exceptions::{PyTypeError, PyValueError},
+        prelude::*,
+        types::PyType,
+    },
+};
+
+/// Service control requests, as passed to control handlers.
+const SERVICE_CONTROL_STOP: u32 = 0x1;
+const SERVICE_CONTROL_PAUSE: u32 = 0x2;
+const SERVICE_CONTROL_CONTINUE: u32 = 0x3;
+const SERVICE_CONTROL_INTERROGATE: u32 = 0x4;
+const SERVICE_CONTROL_SHUTDOWN: u32 = 0x5;
+
+/// Service states, as reported to the service control manager.
+const SERVICE_STOPPED: u32 = 0x1;
+const SERVICE_START_PENDING: u32 = 0x2;
+const SERVICE_STOP_PENDING: u32 = 0x3;
+const SERVICE_RUNNING: u32 = 0x4;
+const SERVICE_CONTINUE_PENDING: u32 = 0x5;
+const SERVICE_PAUSE_PENDING: u32 = 0x6;
+const SERVICE_PAUSED: u32 = 0x7;
+
+/// Controls a service accepts.
+const SERVICE_ACCEPT_STOP: u32 = 0x1;
+const SERVICE_ACCEPT_PAUSE_CONTINUE: u32 = 0x2;
+const SERVICE_ACCEPT_SHUTDOWN: u32 = 0x4;
+
+/// Resolve the module and attribute of a `module:attribute` handler string.
+fn parse_handler(handler: &str) -> Result<(&str, &str), NewInterpreterError> {
+    match handler.split_once(':') {
+        Some((module, attr)) if !module.is_empty() && !attr.is_empty() => Ok((module, attr)),
+        _ => Err(NewInterpreterError::Dynamic(format!(
+            "invalid Windows service handler {}; expected module:attribute",
+            handler
+        ))),
+    }
+}
+
+/// Controls accepted by a service.
+fn accepted_controls(pausable: bool) -> u32 {
+    let mut controls = SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN;
+
+    if pausable {
+        controls |= SERVICE_ACCEPT_PAUSE_CONTINUE;
+    }
+
+    controls
+}
+
+/// Resolve how a service control request is handled.
+///
+/// Returns the method of the handler to call along with the states to report
+/// before and after calling it. `None` if the control isn't handled.
+fn control_action(control: u32, pausable: bool) -> Option<(&'static str, u32, Option<u32>)> {
+    match control {
+        // Reaching the stopped state is reported once `run()` returns.
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            Some(("stop", SERVICE_STOP_PENDING, None))
+        }
+        SERVICE_CONTROL_PAUSE if pausable => {
+            Some(("pause", SERVICE_PAUSE_PENDING, Some(SERVICE_PAUSED)))
+        }
+        SERVICE_CONTROL_CONTINUE if pausable => {
+            Some(("resume", SERVICE_CONTINUE_PENDING, Some(SERVICE_RUNNING)))
+        }
+        _ => None,
+    }
+}
+
+/// Resolve the Python object handling service events.
+fn resolve_handler(py: Python, handler: &str) -> PyResult<(PyObject, bool)> {
+    let (module, attr) =
+        parse_handler(handler).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    let mut value = py.import(module)?.getattr(attr)?;
+
+    if value.cast_as::<PyType>().is_ok() {
+        value = value.call0()?;
+    }
+
+    for method in ["run", "stop"] {
+        if !value.hasattr(method)? {
+            return Err(PyTypeError::new_err(format!(
+                "Windows service handler {} has no {}() method",
+                handler, method
+            )));
+        }
+    }
+
+    let pausable = value.hasattr("pause")? && value.hasattr("resume")?;
+
+    Ok((value.into_py(py), pausable))
+}
+
+#[cfg(windows)]
+mod ffi {
+    use std::ffi::c_void;
+
+    pub type ServiceMainFunction = unsafe extern "system" fn(u32, *mut *mut u16);
+    pub type HandlerFunctionEx =
+        unsafe extern "system" fn(u32, u32, *mut c_void, *mut c_void) -> u32;
+
+    #[repr(C)]
+    pub struct ServiceTableEntryW {
+        pub service_name: *mut u16,
+        pub service_proc: Option<ServiceMainFunction>,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct ServiceStatus {
+        pub service_type: u32,
+        pub current_state: u32,
+        pub controls_accepted: u32,
+        pub win32_exit_code: u32,
+        pub service_specific_exit_code: u32,
+        pub check_point: u32,
+        pub wait_hint: u32,
+    }
+
+    pub const SERVICE_WIN32_OWN_PROCESS: u32 = 0x10;
+    pub const NO_ERROR: u32 = 0;
+    pub const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+    pub const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn StartServiceCtrlDispatcherW(service_start_table: *const ServiceTableEntryW) -> i32;
+        pub fn RegisterServiceCtrlHandlerExW(
+            service_name: *const u16,
+            handler_proc: Option<HandlerFunctionEx>,
+            context: *mut c_void,
+        ) -> isize;
+        pub fn SetServiceStatus(service_status: isize, status: *mut ServiceStatus) -> i32;
+    }
+}
+
+/// State shared by the service's entry point and control handler.
+#[cfg(windows)]
+struct ServiceState {
+    /// Name of the service, NUL terminated.
+    name: Vec<u16>,
+
+    /// Python object handling service events.
+    handler: PyObject,
+
+    /// Whether the service can be paused.
+    pausable: bool,
+
+    /// Handle of the service's status.
+    status_handle: std::sync::atomic::AtomicIsize,
+
+    /// Whether `run()` raised an exception.
+    failed: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(windows)]
+static SERVICE_STATE: once_cell::sync::OnceCell<ServiceState> = once_cell::sync::OnceCell::new();
+
+#[cfg(windows)]
+fn set_service_status(state: &ServiceState, current_state: u32) {
+    use std::sync::atomic::Ordering;
+
+    let handle = state.status_handle.load(Ordering::SeqCst);
+    if handle == 0 {
+        return;
+    }
+
+    let mut status = ffi::ServiceStatus {
+        service_type: ffi::SERVICE_WIN32_OWN_PROCESS,
+        current_state,
+        controls_accepted: match current_state {
+            SERVICE_START_PENDING | SERVICE_STOP_PENDING | SERVICE_STOPPED => 0,
+            _ => accepted_controls(state.pausable),
+        },
+        ..Default::default()
+    };
+
+    match current_state {
+        SERVICE_STOPPED if state.failed.load(Ordering::SeqCst) => {
+            status.win32_exit_code = ffi::ERROR_SERVICE_SPECIFIC_ERROR;
+            status.service_specific_exit_code = 1;
+        }
+        SERVICE_START_PENDING
+        | SERVICE_STOP_PENDING
+        | SERVICE_PAUSE_PENDING
+        | SERVICE_CONTINUE_PENDING => {
+            status.wait_hint = 30000;
+        }
+        _ => {}
+    }
+
+    unsafe {
+        ffi::SetServiceStatus(handle, &mut status);
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn handle_control(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut std::ffi::c_void,
+    _context: *mut std::ffi::c_void,
+) -> u32 {
+    let state = match SERVICE_STATE.get() {
+        Some(state) => state,
+        None => return ffi::ERROR_CALL_NOT_IMPLEMENTED,
+    };
+
+    if control == SERVICE_CONTROL_INTERROGATE {
+        return ffi::NO_ERROR;
+    }
+
+    let (method, pending, done) = match control_action(control, state.pausable) {
+        Some(action) => action,
+        None => return ffi::ERROR_CALL_NOT_IMPLEMENTED,
+    };
+
+    set_service_status(state, pending);
+
+    let res = Python::with_gil(|py| match state.handler.call_method0(py, method) {
+        Ok(_) => true,
+        Err(e) => {
+            e.print(py);
+            false
+        }
+    });
+
+    if let Some(done) = done {
+        // Stay in the previous state if the transition failed.
+        let current = match (res, done) {
+            (true, done) => done,
+            (false, SERVICE_PAUSED) => SERVICE_RUNNING,
+            (false, _) => SERVICE_PAUSED,
+        };
+        set_service_status(state, current);
+    }
+
+    ffi::NO_ERROR
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut *mut u16) {
+    use std::sync::atomic::Ordering;
+
+    let state = match SERVICE_STATE.get() {
+        Some(state) => state,
+        None => return,
+    };
+
+    let handle = ffi::RegisterServiceCtrlHandlerExW(
+        state.name.as_ptr(),
+        Some(handle_control),
+        std::ptr::null_mut(),
+    );
+    if handle == 0 {
+        return;
+    }
+    state.status_handle.store(handle, Ordering::SeqCst);
+
+    set_service_status(state, SERVICE_START_PENDING);
+    set_service_status(state, SERVICE_RUNNING);
+
+    Python::with_gil(|py| {
+        if let Err(e) = state.handler.call_method0(py, "run") {
+            e.print(py);
+            state.failed.store(true, Ordering::SeqCst);
+        }
+    });
+
+    set_service_status(state, SERVICE_STOPPED);
+}
+
+/// Run the application as a Windows service.
+///
+/// `service_name` is the name the service is installed as and `handler` names
+/// the Python object handling service events as `module:attribute`. See the
+/// [module documentation](self) for the methods the object must have.
+///
+/// This blocks until the service stops. It must be called from a process
+/// started by the service control manager. An error is returned otherwise, or
+/// if `run()` raised an exception.
+pub fn run_windows_service(
+    interpreter: &MainPythonInterpreter,
+    service_name: &str,
+    handler: &str,
+) -> Result<(), NewInterpreterError> {
+    let (handler, pausable) = interpreter.with_gil(|py| {
+        resolve_handler(py, handler)
+            .map_err(|e| NewInterpreterError::new_from_pyerr(py, e, "resolving service handler"))
+    })?;
+
+    run_service_dispatcher(service_name, handler, pausable)
+}
+
+#[cfg(windows)]
+fn run_service_dispatcher(
+    service_name: &str,
+    handler: PyObject,
+    pausable: bool,
+) -> Result<(), NewInterpreterError> {
+    use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+
+    let name = service_name
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .collect::<Vec<_>>();
+
+    SERVICE_STATE
+        .set(ServiceState {
+            name: name.clone(),
+            handler,
+            pausable,
+            status_handle: AtomicIsize::new(0),
+            failed: AtomicBool::new(false),
+        })
+        .map_err(|_| NewInterpreterError::Simple("Windows service already run"))?;
+
+    let mut name = name;
+    let table = [
+        ffi::ServiceTableEntryW {
+            service_name: name.as_mut_ptr(),
+            service_proc: Some(service_main),
+        },
+        ffi::ServiceTableEntryW {
+            service_name: std::ptr::null_mut(),
+            service_proc: None,
+        },
+    ];
+
+    // The dispatcher blocks until the service stops. The GIL isn't held here,
+    // so the service's threads can acquire it.
+    let res = unsafe { ffi::StartServiceCtrlDispatcherW(table.as_ptr()) };
+
+    if res == 0 {
+        return Err(NewInterpreterError::Dynamic(format!(
+            "unable to connect to the service control manager: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+
+    if SERVICE_STATE
+        .get()
+        .map(|state| state.failed.load(Ordering::SeqCst))
+        .unwrap_or_default()
+    {
+        Err(NewInterpreterError::Simple("Windows service failed"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(windows))]
+fn run_service_dispatcher(
+    _service_name: &str,
+    _handler: PyObject,
+    _pausable: bool,
+) -> Result<(), NewInterpreterError> {
+    Err(NewInterpreterError::Simple(
+        "Windows services are only supported on Windows",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_handler() {
+        assert_eq!(
+            parse_handler("myapp.service:Service").unwrap(),
+            ("myapp.service", "Service")
+        );
+        assert!(parse_handler("myapp").is_err());
+        assert!(parse_handler(":Service").is_err());
+        assert!(parse_handler("myapp:").is_err());
+    }
+
+    #[test]
+    fn test_accepted_controls() {
+        assert_eq!(accepted_controls(false), 0x5);
+        assert_eq!(accepted_controls(true), 0x7);
+    }
+
+    #[test]
+    fn test_control_action() {
+        assert_eq!(
+            control_action(SERVICE_CONTROL_STOP, false),
+            Some(("stop", SERVICE_STOP_PENDING, None))
+        );
+        assert_eq!(
+            control_action(SERVICE_CONTROL_SHUTDOWN, true),
+            Some(("stop", SERVICE_STOP_PENDING, None))
+        );
+        assert_eq!(control_action(SERVICE_CONTROL_PAUSE, false), None);
+        assert_eq!(
+            control_action(SERVICE_CONTROL_PAUSE, true),
+            Some(("pause", SERVICE_PAUSE_PENDING, Some(SERVICE_PAUSED)))
+        );
+        assert_eq!(
+            control_action(SERVICE_CONTROL_CONTINUE, true),
+            Some(("resume", SERVICE_CONTINUE_PENDING, Some(SERVICE_RUNNING)))
+        );
+        assert_eq!(control_action(SERVICE_CONTROL_INTERROGATE, true), None);
+    }
+}
@@ -0,0 +1,314 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Generate continuous integration pipelines building PyOxidizer projects.
+
+use {
+    crate::environment::PYOXIDIZER_VERSION,
+    anyhow::{anyhow, Result},
+    std::fmt::Write,
+};
+
+/// Name of the secret holding a base64 encoded PFX code signing certificate.
+pub const SIGNING_CERTIFICATE_SECRET: &str = "PYOXIDIZER_SIGNING_CERTIFICATE";
+
+/// Name of the secret holding the password of the code signing certificate.
+pub const SIGNING_PASSWORD_SECRET: &str = "PYOXIDIZER_SIGNING_PASSWORD";
+
+/// A continuous integration service to generate a pipeline for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CiProvider {
+    /// GitHub Actions.
+    GitHub,
+
+    /// GitLab CI/CD.
+    GitLab,
+}
+
+impl CiProvider {
+    /// Default path of the generated pipeline, relative to the project.
+    pub fn default_path(&self) -> &'static str {
+        match self {
+            Self::GitHub => ".github/workflows/pyoxidizer.yml",
+            Self::GitLab => ".gitlab-ci.yml",
+        }
+    }
+}
+
+/// A platform CI jobs can build on.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CiPlatform {
+    /// Short name of the platform.
+    pub name: &'static str,
+
+    /// Rust target triple built on this platform.
+    pub target_triple: &'static str,
+
+    /// GitHub Actions runner label.
+    pub github_runner: &'static str,
+
+    /// GitLab runner tag of GitLab.com hosted runners.
+    pub gitlab_tag: &'static str,
+
+    /// Whether the platform supports code signing with PFX certificates.
+    pub supports_signing: bool,
+}
+
+/// Platforms that CI pipelines can build on.
+pub static CI_PLATFORMS: &[CiPlatform] = &[
+    CiPlatform {
+        name: "linux",
+        target_triple: "x86_64-unknown-linux-gnu",
+        github_runner: "ubuntu-latest",
+        gitlab_tag: "saas-linux-medium-amd64",
+        supports_signing: false,
+    },
+    CiPlatform {
+        name: "windows",
+        target_triple: "x86_64-pc-windows-msvc",
+        github_runner: "windows-latest",
+        gitlab_tag: "saas-windows-medium-amd64",
+        supports_signing: true,
+    },
+    CiPlatform {
+        name: "macos",
+        target_triple: "aarch64-apple-darwin",
+        github_runner: "macos-latest",
+        gitlab_tag: "saas-macos-medium-m1",
+        supports_signing: true,
+    },
+];
+
+/// Resolve a platform by name.
+pub fn ci_platform(name: &str) -> Result<&'static CiPlatform> {
+    CI_PLATFORMS.iter().find(|p| p.name == name).ok_or_else(|| {
+        anyhow!(
+            "unknown platform {}; valid platforms are {}",
+            name,
+            CI_PLATFORMS
+                .iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// A CI job building targets on a platform.
+#[derive(Clone, Debug)]
+pub struct CiJob {
+    /// Platform the job runs on.
+    pub platform: &'static CiPlatform,
+
+    /// Targets to build.
+    ///
+    /// Each target's build directory is uploaded as an artifact.
+    pub targets: Vec<String>,
+}
+
+impl CiJob {
+    /// Arguments to `pyoxidizer build`.
+    fn build_args(&self, sign: bool) -> String {
+        let mut args = vec!["--release".to_string()];
+
+        if sign && self.platform.supports_signing {
+            args.push("--var signing_certificate signing.pfx".to_string());
+            args.push(format!(
+                "--secret-var-env signing_password {}",
+                SIGNING_PASSWORD_SECRET
+            ));
+        }
+
+        args.extend(self.targets.iter().cloned());
+
+        args.join(" ")
+    }
+
+    /// Path of a target's build directory, relative to the project.
+    fn target_path(&self, target: &str) -> String {
+        format!("build/{}/release/{}", self.platform.target_triple, target)
+    }
+}
+
+/// Command to install PyOxidizer in CI jobs.
+fn install_command() -> String {
+    if PYOXIDIZER_VERSION.contains("-pre") {
+        "pip install pyoxidizer".to_string()
+    } else {
+        format!("pip install pyoxidizer=={}", PYOXIDIZER_VERSION)
+    }
+}
+
+/// Generate a GitHub Actions workflow running the given jobs.
+///
+/// If `sign` is true, jobs on platforms supporting code signing decode a
+/// certificate from repository secrets and pass it to the configuration via
+/// the `signing_certificate` and `signing_password` variables.
+pub fn github_workflow(jobs: &[CiJob], sign: bool) -> String {
+    let mut s = String::new();
+
+    s.push_str("# Generated by `pyoxidizer generate-ci --github`.\n");
+    s.push_str("name: pyoxidizer\n\n");
+    s.push_str("on:\n  push:\n    branches: [main]\n    tags: ['*']\n  pull_request:\n  workflow_dispatch:\n\n");
+    s.push_str("jobs:\n");
+
+    for job in jobs {
+        let signing = sign && job.platform.supports_signing;
+
+        let _ = writeln!(s, "  build-{}:", job.platform.name);
+        let _ = writeln!(s, "    runs-on: {}", job.platform.github_runner);
+        s.push_str("    defaults:\n      run:\n        shell: bash\n");
+        s.push_str("    steps:\n");
+        s.push_str("      - uses: actions/checkout@v4\n");
+        s.push_str("      - uses: actions/setup-python@v5\n        with:\n          python-version: '3.x'\n");
+        let _ = writeln!(
+            s,
+            "      - name: Install PyOxidizer\n        run: {}",
+            install_command()
+        );
+
+        if signing {
+            s.push_str("      - name: Decode signing certificate\n");
+            s.push_str("        env:\n");
+            let _ = writeln!(
+                s,
+                "          {0}: ${{{{ secrets.{0} }}}}",
+                SIGNING_CERTIFICATE_SECRET
+            );
+            let _ = writeln!(
+                s,
+                "        run: echo \"${}\" | base64 --decode > signing.pfx",
+                SIGNING_CERTIFICATE_SECRET
+            );
+        }
+
+        s.push_str("      - name: Build\n");
+        if signing {
+            s.push_str("        env:\n");
+            let _ = writeln!(
+                s,
+                "          {0}: ${{{{ secrets.{0} }}}}",
+                SIGNING_PASSWORD_SECRET
+            );
+        }
+        let _ = writeln!(s, "        run: pyoxidizer build {}", job.build_args(sign));
+
+        for target in &job.targets {
+            let _ = writeln!(s, "      - uses: actions/upload-artifact@v4");
+            s.push_str("        with:\n");
+            let _ = writeln!(s, "          name: {}-{}", target, job.platform.name);
+            let _ = writeln!(s, "          path: {}", job.target_path(target));
+        }
+
+        s.push('\n');
+    }
+
+    s.trim_end().to_string() + "\n"
+}
+
+/// Generate a GitLab CI/CD pipeline running the given jobs.
+///
+/// Signing behaves like [github_workflow], with the certificate and password
+/// coming from CI/CD variables.
+pub fn gitlab_pipeline(jobs: &[CiJob], sign: bool) -> String {
+    let mut s = String::new();
+
+    s.push_str("# Generated by `pyoxidizer generate-ci --gitlab`.\n");
+    s.push_str("stages:\n  - build\n\n");
+
+    for job in jobs {
+        let signing = sign && job.platform.supports_signing;
+
+        let _ = writeln!(s, "build-{}:", job.platform.name);
+        s.push_str("  stage: build\n");
+        if job.platform.name == "linux" {
+            s.push_str("  image: python:3\n");
+        }
+        let _ = writeln!(s, "  tags:\n    - {}", job.platform.gitlab_tag);
+        s.push_str("  script:\n");
+        let _ = writeln!(s, "    - {}", install_command());
+        if signing {
+            let _ = writeln!(
+                s,
+                "    - echo \"${}\" | base64 --decode > signing.pfx",
+                SIGNING_CERTIFICATE_SECRET
+            );
+        }
+        let _ = writeln!(s, "    - pyoxidizer build {}", job.build_args(sign));
+        s.push_str("  artifacts:\n    paths:\n");
+        for target in &job.targets {
+            let _ = writeln!(s, "      - {}", job.target_path(target));
+        }
+
+        s.push('\n');
+    }
+
+    s.trim_end().to_string() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ci_platform() -> Result<()> {
+        assert_eq!(
+            ci_platform("windows")?.target_triple,
+            "x86_64-pc-windows-msvc"
+        );
+        assert!(ci_platform("solaris").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_github_workflow() -> Result<()> {
+        let jobs = vec![
+            CiJob {
+                platform: ci_platform("linux")?,
+                targets: vec!["exe".to_string()],
+            },
+            CiJob {
+                platform: ci_platform("windows")?,
+                targets: vec!["exe".to_string(), "msi".to_string()],
+            },
+        ];
+
+        let workflow = github_workflow(&jobs, true);
+
+        assert!(workflow.contains("  build-linux:\n    runs-on: ubuntu-latest\n"));
+        assert!(workflow.contains("  build-windows:\n    runs-on: windows-latest\n"));
+        assert!(workflow.contains(
+            "          PYOXIDIZER_SIGNING_PASSWORD: ${{ secrets.PYOXIDIZER_SIGNING_PASSWORD }}\n"
+        ));
+        assert!(workflow.contains("        run: pyoxidizer build --release exe\n"));
+        assert!(workflow.contains(
+            "        run: pyoxidizer build --release --var signing_certificate signing.pfx \
+             --secret-var-env signing_password PYOXIDIZER_SIGNING_PASSWORD exe msi\n"
+        ));
+        assert!(workflow.contains("          path: build/x86_64-pc-windows-msvc/release/msi\n"));
+        // Signing is only configured on platforms supporting it.
+        assert_eq!(workflow.matches("Decode signing certificate").count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gitlab_pipeline() -> Result<()> {
+        let jobs = vec![CiJob {
+            platform: ci_platform("macos")?,
+            targets: vec!["exe".to_string()],
+        }];
+
+        let pipeline = gitlab_pipeline(&jobs, false);
+
+        assert!(pipeline
+            .contains("build-macos:\n  stage: build\n  tags:\n    - saas-macos-medium-m1\n"));
+        assert!(pipeline.contains("    - pyoxidizer build --release exe\n"));
+        assert!(pipeline.contains("      - build/aarch64-apple-darwin/release/exe\n"));
+        assert!(!pipeline.contains("signing.pfx"));
+
+        Ok(())
+    }
+}
@@ -4,17 +4,111 @@
 
 use {
     crate::{
+        ci::CiProvider,
         environment::{default_target_triple, PYOXIDIZER_VERSION},
-        logging, project_building, projectmgmt,
+        logging::{self, OutputFormat},
+        project_building, projectmgmt,
     },
     anyhow::{anyhow, Context, Result},
     clap::{App, AppSettings, Arg, ArgMatches},
+    clap_complete::Shell,
     std::{
         collections::HashMap,
+        io::Write,
         path::{Path, PathBuf},
     },
+    tugger_common::secrets::MIN_SECRET_LENGTH,
 };
 
+const ANALYZE_ABOUT: &str = "\
+Analyze a built binary.
+
+The PATH argument is a filesystem path to an executable.
+
+This command prints the build metadata PyOxidizer embeds in binaries
+(PyOxidizer and Python versions, target triple and notable interpreter
+settings) along with an inventory of the packed resources used by the
+binary. Packed resources in files next to the executable are read as
+well.
+
+For ELF binaries, library dependencies and required symbol versions are
+also printed.
+";
+
+const DIFF_BUILD_ABOUT: &str = "\
+Produce an update patch between 2 builds of the same target.
+
+OLD and NEW are build directories, such as copies of a target's build
+directory from consecutive releases. The update patch written to the
+--out directory contains a manifest describing every file and the data
+needed to turn OLD into NEW. Changed files are stored as zstd compressed
+binary patches against their old version when that is smaller than
+storing them in full.
+
+Applications can download update patches and apply them with the
+pyembed crate's update_patch module, which verifies the SHA-256 digests
+of all old and new files.
+";
+
+const DOCTOR_ABOUT: &str = "\
+Diagnose problems with the environment PyOxidizer runs in.
+
+This command checks for tools needed to build for a target triple: the
+Rust toolchain and target, a C compiler and linker (Visual Studio on
+Windows, Xcode on macOS) and the WiX Toolset for Windows installers. It
+also checks that hosts PyOxidizer downloads from are reachable and that
+PyOxidizer's cache directory is usable.
+
+Every problem found is printed with a suggested fix. The command exits
+with an error if any problem will prevent builds from working.
+";
+
+const FETCH_DEPS_ABOUT: &str = "\
+Download everything building a project needs to an archive.
+
+The PATH argument is a filesystem path to a directory containing an
+existing PyOxidizer enabled project.
+
+This command builds the requested targets using an empty cache directory,
+saving the packages pip installs, and writes the content of the cache
+directory to a tar archive at --out. Python distributions, Rust toolchains,
+files fetched by the configuration and Python packages are included.
+
+Extract the archive into the PyOxidizer cache directory of a machine
+without network access to build the project there with --offline.
+Cargo crates are not included; vendor them or populate the Cargo home
+directory on that machine separately.
+";
+
+const OFFLINE_HELP: &str = "\
+Disable network access.
+
+Python distributions, Rust toolchains and other files not already in
+PyOxidizer's cache directory aren't downloaded; using them fails instead.
+pip only installs packages from the wheels directory in the cache
+directory and Cargo runs in offline mode.
+
+`pyoxidizer fetch-deps` produces archives that populate the cache
+directory for offline use.
+";
+
+const EXTRACT_RESOURCES_ABOUT: &str = "\
+Extract packed resources from a built binary.
+
+The PATH argument is a filesystem path to an executable built by
+PyOxidizer.
+
+This command writes the in-memory data of the packed resources used by
+the binary to the directory given by --out. Python modules are laid out
+as on the filesystem: source as .py files and bytecode as .bytecode files
+holding marshalled code objects (without a .pyc header). Package
+resources, shared libraries and file data are written as files.
+
+By default all resources are extracted. --allow restricts extraction to
+the named resource and modules and files beneath it. It can be specified
+multiple times.
+";
+
 const BUILD_ABOUT: &str = "\
 Build a PyOxidizer project.
 
@@ -25,6 +119,95 @@ This command will invoke Rust's build system tool (Cargo) to build
 the project.
 ";
 
+const VERIFY_REPRODUCIBLE_ABOUT: &str = "\
+Verify that a PyOxidizer project builds reproducibly.
+
+The PATH argument is a filesystem path to a directory containing an
+existing PyOxidizer enabled project.
+
+This command builds the requested targets twice in reproducible mode
+(as if `build --reproducible` were used), each time from scratch in a
+separate temporary build directory. The files produced by each target
+are then compared byte for byte and any differences are reported.
+";
+
+const TEST_CONFIG_ABOUT: &str = "\
+Run tests for a PyOxidizer configuration file.
+
+The PATH argument is a filesystem path to a directory containing an
+existing PyOxidizer enabled project.
+
+Tests are functions whose names begin with test_ defined in the file
+given by --tests (pyoxidizer_test.bzl next to the configuration file by
+default). For each test, the configuration file is evaluated without
+resolving any targets, then the tests file is evaluated and the test
+function is called. Nothing is built.
+
+Tests can call target functions and inspect returned values using the
+assert_eq() and assert_true() functions. mock_pip() makes pip_download()
+and pip_install() read packages from a directory instead of running pip.
+";
+
+const COMPLETIONS_ABOUT: &str = "\
+Generate shell completions for pyoxidizer.
+
+The completion script for SHELL is printed to stdout. e.g. for bash:
+
+    pyoxidizer completions bash > /etc/bash_completion.d/pyoxidizer
+
+Completions cover every sub-command and its arguments.
+";
+
+const DEVENV_ABOUT: &str = "\
+Materialize a development environment for a PyOxidizer project.
+
+The PATH argument is a filesystem path to a directory containing an
+existing PyOxidizer enabled project.
+
+A virtualenv is created from the Python distribution used by a
+PythonExecutable target. The executable's resources that aren't part of
+the standard library are installed into the virtualenv's site-packages
+directory and scripts are generated for console_scripts and gui_scripts
+entry points of its packages. Nothing is compiled.
+
+This allows running tests, debuggers, and IDEs against the same
+interpreter and packages that are embedded in the executable.
+
+By default, the first resolved target producing a PythonExecutable is
+used and the environment is written to devenv in the build directory.
+";
+
+const FMT_ABOUT: &str = "\
+Format PyOxidizer configuration files.
+
+Formatting normalizes whitespace: line endings, trailing whitespace,
+tab indentation, and runs of blank lines. The content of string literals
+is never modified. Files that don't parse are rejected.
+
+With --check, files are not modified. Instead, the names of files that
+would be reformatted are printed and the command fails if there are any.
+";
+
+const LSP_ABOUT: &str = "\
+Run a language server for PyOxidizer configuration files.
+
+The server speaks the Language Server Protocol over stdin and stdout and
+is meant to be launched by an editor. It provides completion and hover
+documentation for the types, functions, and attributes of the Starlark
+dialect, diagnostics for syntax errors and unknown attributes, and
+document formatting equivalent to `pyoxidizer fmt`.
+";
+
+const MAN_ABOUT: &str = "\
+Generate man pages for pyoxidizer.
+
+By default, the man page of the pyoxidizer command is printed to stdout.
+
+With --out-dir, pyoxidizer.1 and a pyoxidizer-<command>.1 page for each
+sub-command are written to the given directory, which is created if
+needed. This is intended for packagers of pyoxidizer.
+";
+
 const INIT_RUST_PROJECT_ABOUT: &str = "\
 Create a new Rust project embedding Python.
 
@@ -44,6 +227,24 @@ they were created with.
 On success, instructions on potential next steps are printed.
 ";
 
+const GENERATE_CI_ABOUT: &str = "\
+Generate a CI pipeline building the project on multiple platforms.
+
+The configuration file is evaluated for each platform to determine what to
+build there. By default, each platform builds its default target. If
+TARGET arguments are given, each platform builds the named targets it
+defines. Each built target is uploaded as an artifact.
+
+A GitHub Actions workflow is written to .github/workflows/pyoxidizer.yml
+by default. With --gitlab, a GitLab CI/CD pipeline is written to
+.gitlab-ci.yml.
+
+With --sign, jobs on Windows and macOS decode a base64 encoded PFX
+certificate from the PYOXIDIZER_SIGNING_CERTIFICATE secret and pass its
+path and the PYOXIDIZER_SIGNING_PASSWORD secret to the configuration as
+the `signing_certificate` and `signing_password` variables.
+";
+
 const GENERATE_PYTHON_EMBEDDING_ARTIFACTS_ABOUT: &str = "\
 Generate files useful for embedding Python in a [Rust] binary.
 
@@ -68,6 +269,32 @@ appropriate for your use case, you can always define a configuration file to
 customize them and use `run-build-script` to produce similar output files.
 ";
 
+const GENERATE_RUST_PROJECT_ABOUT: &str = "\
+Write a Rust project building an executable target, without building it.
+
+The PATH argument is a filesystem path to a directory containing an
+existing PyOxidizer enabled project.
+
+The configuration is evaluated and the Rust project `pyoxidizer build` would
+generate for a PythonExecutable target is written to the directory given by
+--out, without invoking `cargo`. The project contains:
+
+* A Cargo.toml whose default features are those needed by the executable.
+* src/main.rs, build.rs and a .cargo/config file.
+* The artifacts the project is built with (an embedded libpython, packed
+  resources, interpreter configuration and PyO3 configuration) in
+  `artifacts`.
+* Files to install next to the built executable in `extra-files`.
+
+The project can be built with `cargo build`, added to an existing Cargo
+workspace or built by other build systems. Builds not using the project's
+.cargo/config file must define the PYOXIDIZER_ARTIFACT_DIR and
+PYO3_CONFIG_FILE environment variables themselves.
+
+If --target isn't given, the first resolved target producing a
+PythonExecutable is used.
+";
+
 const RUN_BUILD_SCRIPT_ABOUT: &str = "\
 Runs a crate build script to generate Python artifacts.
 
@@ -113,6 +340,65 @@ Starlark expression `VARS[\"my_var\"] = \"my_value\"`.
 If a Starlark variable is defined multiple times, an error occurs.
 ";
 
+const CONTAINER_HELP: &str = "\
+Run the build inside a container created from the IMAGE image.
+
+The project directory is mounted at /project and the PyOxidizer cache
+directory is mounted at /pyoxidizer-cache, so downloaded Python
+distributions and Rust toolchains are shared with the host. Build
+artifacts are written to the project's build directory as usual.
+
+`docker` is used if available, otherwise `podman`. Set
+PYOXIDIZER_CONTAINER_ENGINE to the name or path of an alternate engine
+executable.
+
+On Linux, the running pyoxidizer executable is mounted into the
+container. On other hosts, the image must provide `pyoxidizer` on PATH.
+";
+
+const VAR_FILE_HELP: &str = "\
+Defines keys to set in the VARS global dict from a file.
+
+Each line of the file has the form `<name>=<value>`. Empty lines and
+lines beginning with `#` are ignored. Whitespace around names is
+stripped but values are used verbatim.
+
+This argument can be specified multiple times. If a Starlark variable is
+defined multiple times, an error occurs.
+";
+
+const SECRET_VAR_ENV_HELP: &str = "\
+Defines a secret key to set in the VARS global dict from an environment variable.
+
+This is like --var-env except the value is treated as a secret: it is
+replaced with <redacted> in all log output and writing an artifact, such
+as an executable, an installer or installed files, containing the value is
+an error unless --allow-secret-embedding is given. Values must be at least
+8 bytes long.
+
+Secrets are read from the environment rather than from command arguments
+so they don't leak via process listings or shell history.
+";
+
+const SECRET_VAR_FILE_HELP: &str = "\
+Defines a secret key to set in the VARS global dict from the content of a file.
+
+This is like --secret-var-env except the value is the content of the
+<path> file, with a trailing newline removed.
+";
+
+const PROFILE_HELP: &str = "\
+Name of the build profile.
+
+The profile name is exposed to Starlark as the BUILD_PROFILE global, so
+configuration files can vary settings by profile. Each profile has its
+own build directory.
+
+Defaults to `release` if --release is given and `debug` otherwise. The
+`release` profile implies --release. Other custom profiles build Rust code
+in debug mode unless --release is given.
+";
+
 const ENV_VAR_HELP: &str = "\
 Defines a single string key to set in the VARS global dict from an environment variable.
 
@@ -147,6 +433,77 @@ fn add_env_args(app: App) -> App {
             .help("Define an environment variable in Starlark environment")
             .long_help(ENV_VAR_HELP),
     )
+    .arg(
+        Arg::new("var_files")
+            .long("var-file")
+            .value_name("path")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .help("Define variables in Starlark environment from a file")
+            .long_help(VAR_FILE_HELP),
+    )
+    .arg(
+        Arg::new("secret_vars_env")
+            .long("secret-var-env")
+            .value_names(&["name", "env"])
+            .multiple_occurrences(true)
+            .multiple_values(true)
+            .help("Define a secret variable in Starlark environment from an environment variable")
+            .long_help(SECRET_VAR_ENV_HELP),
+    )
+    .arg(
+        Arg::new("secret_vars_file")
+            .long("secret-var-file")
+            .value_names(&["name", "path"])
+            .multiple_occurrences(true)
+            .multiple_values(true)
+            .help("Define a secret variable in Starlark environment from a file")
+            .long_help(SECRET_VAR_FILE_HELP),
+    )
+    .arg(
+        Arg::new("allow_secret_embedding")
+            .long("allow-secret-embedding")
+            .help("Allow secret variable values to be embedded in built artifacts"),
+    )
+}
+
+fn add_profile_args(app: App) -> App {
+    app.arg(
+        Arg::new("profile")
+            .long("profile")
+            .takes_value(true)
+            .value_name("NAME")
+            .help("Name of the build profile, exposed to Starlark as BUILD_PROFILE")
+            .long_help(PROFILE_HELP),
+    )
+}
+
+/// Resolve whether to build in release mode and the name of the build profile.
+fn build_profile(args: &ArgMatches) -> Result<(bool, String)> {
+    let release = args.is_present("release");
+
+    match args.value_of("profile") {
+        None => Ok((
+            release,
+            if release { "release" } else { "debug" }.to_string(),
+        )),
+        Some("release") => Ok((true, "release".to_string())),
+        Some("debug") if release => Err(anyhow!("--profile debug conflicts with --release")),
+        Some(name) => {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(anyhow!(
+                    "invalid profile name {:?}; names consist of letters, digits, - and _",
+                    name
+                ));
+            }
+
+            Ok((release, name.to_string()))
+        }
+    }
 }
 
 fn add_python_distribution_args(app: App) -> App {
@@ -172,41 +529,177 @@ fn add_python_distribution_args(app: App) -> App {
     )
 }
 
-fn starlark_vars(args: &ArgMatches) -> Result<HashMap<String, Option<String>>> {
+/// Parse the content of a `--var-file` file into `(name, value)` pairs.
+fn parse_var_file(data: &str) -> Result<Vec<(String, String)>> {
+    let mut res = vec![];
+
+    for (i, line) in data.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected <name>=<value>", i + 1))?;
+
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("line {}: empty variable name", i + 1));
+        }
+
+        res.push((name.to_string(), value.to_string()));
+    }
+
+    Ok(res)
+}
+
+/// Register the value of a secret variable.
+///
+/// Short values can't be reliably detected in built artifacts, so they are
+/// rejected.
+fn add_secret_var(
+    env: &mut crate::environment::Environment,
+    name: &str,
+    value: &str,
+) -> Result<()> {
+    if value.len() < MIN_SECRET_LENGTH {
+        return Err(anyhow!(
+            "value of secret variable {} must be at least {} bytes long",
+            name,
+            MIN_SECRET_LENGTH
+        ));
+    }
+
+    env.add_secret(value);
+
+    Ok(())
+}
+
+fn starlark_vars(
+    env: &mut crate::environment::Environment,
+    args: &ArgMatches,
+) -> Result<HashMap<String, Option<String>>> {
     let mut res = HashMap::new();
 
+    let mut insert = |name: &str, value: Option<String>| {
+        if res.contains_key(name) {
+            return Err(anyhow!("Starlark variable {} already defined", name));
+        }
+
+        res.insert(name.to_string(), value);
+        Ok(())
+    };
+
     if let Some(mut vars) = args.values_of("vars") {
         while let (Some(name), Some(value)) = (vars.next(), vars.next()) {
-            if res.contains_key(name) {
-                return Err(anyhow!("Starlark variable {} already defined", name));
-            }
-
-            res.insert(name.to_string(), Some(value.to_string()));
+            insert(name, Some(value.to_string()))?;
         }
     }
 
     if let Some(mut vars) = args.values_of("vars_env") {
         while let (Some(name), Some(env)) = (vars.next(), vars.next()) {
-            if res.contains_key(name) {
-                return Err(anyhow!("Starlark variable {} already defined", name));
+            insert(name, std::env::var(env).ok())?;
+        }
+    }
+
+    if let Some(paths) = args.values_of("var_files") {
+        for path in paths {
+            let data = std::fs::read_to_string(path)
+                .with_context(|| format!("reading variables file {}", path))?;
+
+            for (name, value) in
+                parse_var_file(&data).with_context(|| format!("parsing {}", path))?
+            {
+                insert(&name, Some(value))?;
             }
+        }
+    }
+
+    if let Some(mut vars) = args.values_of("secret_vars_env") {
+        while let (Some(name), Some(key)) = (vars.next(), vars.next()) {
+            let value = std::env::var(key).ok();
+
+            if let Some(value) = &value {
+                add_secret_var(env, name, value)?;
+            }
+
+            insert(name, value)?;
+        }
+    }
 
-            res.insert(name.to_string(), std::env::var(env).ok());
+    if let Some(mut vars) = args.values_of("secret_vars_file") {
+        while let (Some(name), Some(path)) = (vars.next(), vars.next()) {
+            let value = std::fs::read_to_string(path)
+                .with_context(|| format!("reading secret variable file {}", path))?;
+            let value = value
+                .strip_suffix('\n')
+                .map(|v| v.strip_suffix('\r').unwrap_or(v))
+                .unwrap_or(&value)
+                .to_string();
+
+            add_secret_var(env, name, &value)?;
+            insert(name, Some(value))?;
         }
     }
 
+    if args.is_present("allow_secret_embedding") {
+        env.allow_secret_embedding();
+    }
+
     Ok(res)
 }
 
-pub fn run_cli() -> Result<()> {
-    let mut env = crate::environment::Environment::new()?;
+/// Write a shell completion script for `pyoxidizer`.
+fn write_completions(app: &mut App, shell: Shell, writer: &mut dyn Write) -> Result<()> {
+    clap_complete::generate(shell, app, "pyoxidizer", writer);
 
-    let version = env.pyoxidizer_source.version_long();
+    Ok(())
+}
+
+/// Write man pages for `pyoxidizer`.
+///
+/// If `out_dir` is defined, pages for `pyoxidizer` and each sub-command are
+/// written to it. Otherwise the page for `pyoxidizer` is printed.
+fn write_man_pages(app: &App, out_dir: Option<&Path>) -> Result<()> {
+    let app = app.clone().name("pyoxidizer");
+
+    let out_dir = if let Some(out_dir) = out_dir {
+        out_dir
+    } else {
+        return clap_mangen::Man::new(app)
+            .render(&mut std::io::stdout())
+            .context("rendering man page");
+    };
 
+    std::fs::create_dir_all(out_dir).with_context(|| format!("creating {}", out_dir.display()))?;
+
+    let mut pages = vec![app.clone()];
+    pages.extend(
+        app.get_subcommands()
+            .map(|sub| sub.clone().name(format!("pyoxidizer-{}", sub.get_name()))),
+    );
+
+    for page in pages {
+        let path = out_dir.join(format!("{}.1", page.get_name()));
+
+        let mut data = vec![];
+        clap_mangen::Man::new(page)
+            .render(&mut data)
+            .with_context(|| format!("rendering {}", path.display()))?;
+        std::fs::write(&path, data).with_context(|| format!("writing {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Construct the `pyoxidizer` command line parser.
+///
+/// `long_version` is the text printed by `--version`.
+pub fn build_app(long_version: &str) -> App {
     let app = App::new("PyOxidizer")
         .setting(AppSettings::ArgRequiredElseHelp)
         .version(PYOXIDIZER_VERSION)
-        .long_version(version.as_str())
+        .long_version(long_version)
         .author("Gregory Szorc <gregory.szorc@gmail.com>")
         .long_about("Build and distribute Python applications")
         .arg(
@@ -215,21 +708,38 @@ pub fn run_cli() -> Result<()> {
                 .global(true)
                 .help("Use a system install of Rust instead of a self-managed Rust installation"),
         )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .global(true)
+                .help("Fail instead of accessing the network")
+                .long_help(OFFLINE_HELP),
+        )
         .arg(
             Arg::new("verbose")
                 .long("verbose")
                 .global(true)
                 .help("Enable verbose output"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Format of output. json emits a stream of JSON objects"),
         );
 
     let app = app.subcommand(
         App::new("analyze")
             .about("Analyze a built binary")
+            .long_about(ANALYZE_ABOUT)
             .setting(AppSettings::ArgRequiredElseHelp)
             .arg(Arg::new("path").help("Path to executable to analyze")),
     );
 
-    let app = app.subcommand(add_env_args(
+    let app = app.subcommand(add_profile_args(add_env_args(
         App::new("build")
             .setting(AppSettings::ArgRequiredElseHelp)
             .about("Build a PyOxidizer enabled project")
@@ -245,6 +755,19 @@ pub fn run_cli() -> Result<()> {
                     .long("release")
                     .help("Build a release binary"),
             )
+            .arg(
+                Arg::new("reproducible")
+                    .long("reproducible")
+                    .help("Produce reproducible build artifacts"),
+            )
+            .arg(
+                Arg::new("container")
+                    .long("container")
+                    .takes_value(true)
+                    .value_name("IMAGE")
+                    .help("Run the build inside a Docker or Podman container using this image")
+                    .long_help(CONTAINER_HELP),
+            )
             .arg(
                 Arg::new("path")
                     .long("path")
@@ -260,48 +783,277 @@ pub fn run_cli() -> Result<()> {
                     .multiple_values(true)
                     .help("Target to resolve"),
             ),
-    ));
+    )));
 
     let app =
         app.subcommand(App::new("cache-clear").about("Clear PyOxidizer's user-specific cache"));
 
+    let app = app.subcommand(
+        App::new("completions")
+            .setting(AppSettings::ArgRequiredElseHelp)
+            .about("Generate shell completions for pyoxidizer")
+            .long_about(COMPLETIONS_ABOUT)
+            .arg(
+                Arg::new("shell")
+                    .required(true)
+                    .possible_values(&["bash", "elvish", "fish", "powershell", "zsh"])
+                    .help("Shell to generate completions for"),
+            ),
+    );
+
+    let app = app.subcommand(add_env_args(
+        App::new("devenv")
+            .about("Materialize a virtualenv with the resources of an executable")
+            .long_about(DEVENV_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to evaluate the configuration for"),
+            )
+            .arg(
+                Arg::new("target")
+                    .long("target")
+                    .takes_value(true)
+                    .help("Target producing the PythonExecutable"),
+            )
+            .arg(
+                Arg::new("out")
+                    .long("out")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .help("Directory to write the environment to"),
+            )
+            .arg(
+                Arg::new("path")
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project"),
+            ),
+    ));
+
+    let app = app.subcommand(
+        App::new("diff-build")
+            .about("Produce an update patch between 2 builds of the same target")
+            .long_about(DIFF_BUILD_ABOUT)
+            .arg(
+                Arg::new("old")
+                    .required(true)
+                    .value_name("OLD")
+                    .help("Directory containing the old build"),
+            )
+            .arg(
+                Arg::new("new")
+                    .required(true)
+                    .value_name("NEW")
+                    .help("Directory containing the new build"),
+            )
+            .arg(
+                Arg::new("out")
+                    .long("out")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("DIR")
+                    .help("Directory to write the update patch to"),
+            ),
+    );
+
+    let app = app.subcommand(
+        App::new("doctor")
+            .about("Diagnose problems with the environment PyOxidizer runs in")
+            .long_about(DOCTOR_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .default_value(default_target_triple())
+                    .help("Rust target triple to check the environment can build for"),
+            ),
+    );
+
+    let app = app.subcommand(add_profile_args(add_env_args(
+        App::new("fetch-deps")
+            .setting(AppSettings::ArgRequiredElseHelp)
+            .about("Download everything building a project needs to an archive")
+            .long_about(FETCH_DEPS_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to build for"),
+            )
+            .arg(
+                Arg::new("release")
+                    .long("release")
+                    .help("Build a release binary"),
+            )
+            .arg(
+                Arg::new("out")
+                    .long("out")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("FILE")
+                    .help("Path of the archive to write"),
+            )
+            .arg(
+                Arg::new("path")
+                    .long("path")
+                    .takes_value(true)
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project to build"),
+            )
+            .arg(
+                Arg::new("targets")
+                    .value_name("TARGET")
+                    .multiple_occurrences(true)
+                    .multiple_values(true)
+                    .help("Target to resolve"),
+            ),
+    )));
+
+    let app = app.subcommand(
+        App::new("extract-resources")
+            .about("Extract packed resources from a built binary")
+            .long_about(EXTRACT_RESOURCES_ABOUT)
+            .setting(AppSettings::ArgRequiredElseHelp)
+            .arg(
+                Arg::new("out")
+                    .long("out")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("DIR")
+                    .help("Directory to write resources to"),
+            )
+            .arg(
+                Arg::new("allow")
+                    .long("allow")
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .number_of_values(1)
+                    .value_name("NAME")
+                    .help("Only extract this resource and resources beneath it"),
+            )
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_name("PATH")
+                    .help("Path to executable to extract resources from"),
+            ),
+    );
+
     let app = app.subcommand(
         App::new("find-resources")
             .about("Find resources in a file or directory")
             .long_about(RESOURCES_SCAN_ABOUT)
             .setting(AppSettings::ArgRequiredElseHelp)
             .arg(
-                Arg::new("distributions_dir")
-                    .long("distributions-dir")
+                Arg::new("distributions_dir")
+                    .long("distributions-dir")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .help("Directory to extract downloaded Python distributions into"),
+            )
+            .arg(
+                Arg::new("scan_distribution")
+                    .long("--scan-distribution")
+                    .help("Scan the Python distribution instead of a path"),
+            )
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .default_value(default_target_triple())
+                    .help("Target triple of Python distribution to use"),
+            )
+            .arg(
+                Arg::new("no_classify_files")
+                    .long("no-classify-files")
+                    .help("Whether to skip classifying files as typed resources"),
+            )
+            .arg(
+                Arg::new("no_emit_files")
+                    .long("no-emit-files")
+                    .help("Whether to skip emitting File resources"),
+            )
+            .arg(Arg::new("path").value_name("PATH").help(
+                "Filesystem path to scan for resources. Must be a directory or Python wheel",
+            )),
+    );
+
+    let app = app.subcommand(
+        App::new("fmt")
+            .about("Format PyOxidizer configuration files")
+            .long_about(FMT_ABOUT)
+            .arg(
+                Arg::new("check")
+                    .long("check")
+                    .help("Only check whether files are formatted"),
+            )
+            .arg(
+                Arg::new("paths")
+                    .multiple_values(true)
+                    .default_value("pyoxidizer.bzl")
+                    .value_name("PATH")
+                    .help("Configuration files to format"),
+            ),
+    );
+
+    let app = app.subcommand(
+        App::new("generate-ci")
+            .about("Generate a CI pipeline building the project on multiple platforms")
+            .long_about(GENERATE_CI_ABOUT)
+            .arg(
+                Arg::new("github")
+                    .long("github")
+                    .conflicts_with("gitlab")
+                    .help("Generate a GitHub Actions workflow (the default)"),
+            )
+            .arg(
+                Arg::new("gitlab")
+                    .long("gitlab")
+                    .help("Generate a GitLab CI/CD pipeline"),
+            )
+            .arg(
+                Arg::new("platforms")
+                    .long("platform")
                     .takes_value(true)
-                    .value_name("PATH")
-                    .help("Directory to extract downloaded Python distributions into"),
+                    .multiple_occurrences(true)
+                    .possible_values(&["linux", "windows", "macos"])
+                    .help("Platform to build on (defaults to all platforms)"),
             )
             .arg(
-                Arg::new("scan_distribution")
-                    .long("--scan-distribution")
-                    .help("Scan the Python distribution instead of a path"),
+                Arg::new("sign")
+                    .long("sign")
+                    .help("Pass a code signing certificate from CI secrets to builds"),
             )
             .arg(
-                Arg::new("target_triple")
-                    .long("target-triple")
+                Arg::new("output")
+                    .long("output")
                     .takes_value(true)
-                    .default_value(default_target_triple())
-                    .help("Target triple of Python distribution to use"),
+                    .value_name("PATH")
+                    .help("Path to write the pipeline to"),
             )
             .arg(
-                Arg::new("no_classify_files")
-                    .long("no-classify-files")
-                    .help("Whether to skip classifying files as typed resources"),
+                Arg::new("force")
+                    .long("force")
+                    .help("Overwrite an existing pipeline file"),
             )
             .arg(
-                Arg::new("no_emit_files")
-                    .long("no-emit-files")
-                    .help("Whether to skip emitting File resources"),
+                Arg::new("path")
+                    .long("path")
+                    .takes_value(true)
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project"),
             )
-            .arg(Arg::new("path").value_name("PATH").help(
-                "Filesystem path to scan for resources. Must be a directory or Python wheel",
-            )),
+            .arg(
+                Arg::new("targets")
+                    .value_name("TARGET")
+                    .multiple_occurrences(true)
+                    .multiple_values(true)
+                    .help("Target to build (defaults to the default target)"),
+            ),
     );
 
     let app = app.subcommand(add_python_distribution_args(
@@ -316,6 +1068,43 @@ pub fn run_cli() -> Result<()> {
             ),
     ));
 
+    let app = app.subcommand(add_env_args(
+        App::new("generate-rust-project")
+            .about("Write the Rust project building an executable without building it")
+            .long_about(GENERATE_RUST_PROJECT_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to generate the project for"),
+            )
+            .arg(
+                Arg::new("release")
+                    .long("release")
+                    .help("Generate artifacts for a release build"),
+            )
+            .arg(
+                Arg::new("target")
+                    .long("target")
+                    .takes_value(true)
+                    .help("Target producing the PythonExecutable"),
+            )
+            .arg(
+                Arg::new("out")
+                    .long("out")
+                    .takes_value(true)
+                    .required(true)
+                    .value_name("DIR")
+                    .help("Directory to write the Rust project to"),
+            )
+            .arg(
+                Arg::new("path")
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project"),
+            ),
+    ));
+
     let app = app.subcommand(
         App::new("init-config-file")
             .setting(AppSettings::ArgRequiredElseHelp)
@@ -368,6 +1157,25 @@ pub fn run_cli() -> Result<()> {
             ),
     );
 
+    let app = app.subcommand(
+        App::new("lsp")
+            .about("Run a language server for PyOxidizer configuration files")
+            .long_about(LSP_ABOUT),
+    );
+
+    let app = app.subcommand(
+        App::new("man")
+            .about("Generate man pages for pyoxidizer")
+            .long_about(MAN_ABOUT)
+            .arg(
+                Arg::new("out_dir")
+                    .long("out-dir")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .help("Directory to write man pages for all sub-commands to"),
+            ),
+    );
+
     let app = app.subcommand(
         App::new("python-distribution-extract")
             .about("Extract a Python distribution archive to a directory")
@@ -430,7 +1238,7 @@ pub fn run_cli() -> Result<()> {
             ),
     ));
 
-    let app = app.subcommand(add_env_args(
+    let app = app.subcommand(add_profile_args(add_env_args(
         App::new("run")
             .setting(AppSettings::TrailingVarArg)
             .about("Run a target in a PyOxidizer configuration file")
@@ -463,8 +1271,77 @@ pub fn run_cli() -> Result<()> {
                     .multiple_occurrences(true)
                     .multiple_values(true),
             ),
+    )));
+
+    let app = app.subcommand(add_env_args(
+        App::new("test-config")
+            .about("Run tests for a PyOxidizer configuration file")
+            .long_about(TEST_CONFIG_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to evaluate the configuration for"),
+            )
+            .arg(
+                Arg::new("tests")
+                    .long("tests")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("File defining tests (default: pyoxidizer_test.bzl next to the config file)"),
+            )
+            .arg(
+                Arg::new("path")
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project to test"),
+            ),
     ));
 
+    let app = app.subcommand(add_profile_args(add_env_args(
+        App::new("verify-reproducible")
+            .setting(AppSettings::ArgRequiredElseHelp)
+            .about("Verify that a PyOxidizer project builds reproducibly")
+            .long_about(VERIFY_REPRODUCIBLE_ABOUT)
+            .arg(
+                Arg::new("target_triple")
+                    .long("target-triple")
+                    .takes_value(true)
+                    .help("Rust target triple to build for"),
+            )
+            .arg(
+                Arg::new("release")
+                    .long("release")
+                    .help("Build a release binary"),
+            )
+            .arg(
+                Arg::new("path")
+                    .long("path")
+                    .takes_value(true)
+                    .default_value(".")
+                    .value_name("PATH")
+                    .help("Directory containing project to build"),
+            )
+            .arg(
+                Arg::new("targets")
+                    .value_name("TARGET")
+                    .multiple_occurrences(true)
+                    .multiple_values(true)
+                    .help("Target to resolve"),
+            ),
+    )));
+
+    app
+}
+
+/// Run the `pyoxidizer` command line interface with the process's arguments.
+pub fn run_cli() -> Result<()> {
+    let mut env = crate::environment::Environment::new()?;
+
+    let version = env.pyoxidizer_source.version_long();
+
+    let app = build_app(version.as_str());
+
     let matches = app.get_matches();
 
     let verbose = matches.is_present("verbose");
@@ -475,12 +1352,19 @@ pub fn run_cli() -> Result<()> {
         slog::Level::Warning
     };
 
-    let logger_context = logging::logger_from_env(log_level);
+    let format = OutputFormat::try_from(matches.value_of("format").unwrap())
+        .map_err(|e| anyhow!("{}", e))?;
+
+    let logger_context = logging::logger_from_env(log_level, format);
 
     if matches.is_present("system_rust") {
         env.unmanage_rust().context("unmanaging Rust")?;
     }
 
+    if matches.is_present("offline") {
+        env.enable_offline();
+    }
+
     let (command, args) = matches
         .subcommand()
         .ok_or_else(|| anyhow!("invalid sub-command"))?;
@@ -489,21 +1373,138 @@ pub fn run_cli() -> Result<()> {
         "analyze" => {
             let path = args.value_of("path").unwrap();
             let path = PathBuf::from(path);
-            tugger_binary_analysis::analyze_file(path);
+
+            let data =
+                std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+            projectmgmt::analyze(&path, &data, format)?;
+
+            // Linkage analysis only supports ELF binaries and isn't available as JSON.
+            if format == OutputFormat::Text && data.starts_with(b"\x7fELF") {
+                println!();
+                tugger_binary_analysis::analyze_data(&data);
+            }
 
             Ok(())
         }
 
         "build" => {
-            let starlark_vars = starlark_vars(args)?;
-            let release = args.is_present("release");
+            if args.is_present("reproducible") {
+                env.enable_reproducible();
+            }
+
+            let starlark_vars = starlark_vars(&mut env, args)?;
+            let (release, profile) = build_profile(args)?;
+            let target_triple = args.value_of("target_triple");
+            let path = args.value_of("path").unwrap();
+            let resolve_targets = args
+                .values_of("targets")
+                .map(|values| values.map(|x| x.to_string()).collect());
+
+            if let Some(image) = args.value_of("container") {
+                projectmgmt::build_in_container(
+                    &env,
+                    &logger_context.logger,
+                    image,
+                    Path::new(path),
+                    target_triple,
+                    resolve_targets,
+                    starlark_vars,
+                    release,
+                    &profile,
+                    verbose,
+                )
+            } else {
+                projectmgmt::build(
+                    &env,
+                    &logger_context.logger,
+                    Path::new(path),
+                    target_triple,
+                    resolve_targets,
+                    starlark_vars,
+                    release,
+                    &profile,
+                    verbose,
+                )
+            }
+        }
+
+        "cache-clear" => projectmgmt::cache_clear(&env),
+
+        "completions" => {
+            let shell = args
+                .value_of("shell")
+                .unwrap()
+                .parse::<Shell>()
+                .map_err(|e| anyhow!("{}", e))?;
+
+            write_completions(
+                &mut build_app(version.as_str()),
+                shell,
+                &mut std::io::stdout(),
+            )
+        }
+
+        "devenv" => {
+            let starlark_vars = starlark_vars(&mut env, args)?;
+            let target_triple = args.value_of("target_triple");
+            let target = args.value_of("target");
+            let out = args.value_of("out").map(Path::new);
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::devenv(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                target,
+                out,
+                starlark_vars,
+                verbose,
+            )
+        }
+
+        "diff-build" => {
+            let old = args.value_of("old").unwrap();
+            let new = args.value_of("new").unwrap();
+            let out = args.value_of("out").unwrap();
+
+            projectmgmt::diff_build(
+                &logger_context.logger,
+                Path::new(old),
+                Path::new(new),
+                Path::new(out),
+            )
+        }
+
+        "doctor" => {
+            let target_triple = args.value_of("target_triple").unwrap();
+
+            projectmgmt::doctor(&env, target_triple)
+        }
+
+        "extract-resources" => {
+            let path = args.value_of("path").unwrap();
+            let out = args.value_of("out").unwrap();
+            let allow = args
+                .values_of("allow")
+                .map(|values| values.map(|x| x.to_string()).collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            projectmgmt::extract_resources(Path::new(path), Path::new(out), &allow)
+        }
+
+        "fetch-deps" => {
+            let starlark_vars = starlark_vars(&mut env, args)?;
+            let (release, profile) = build_profile(args)?;
             let target_triple = args.value_of("target_triple");
             let path = args.value_of("path").unwrap();
+            let out = args.value_of("out").unwrap();
             let resolve_targets = args
                 .values_of("targets")
                 .map(|values| values.map(|x| x.to_string()).collect());
 
-            projectmgmt::build(
+            projectmgmt::fetch_deps(
                 &env,
                 &logger_context.logger,
                 Path::new(path),
@@ -511,12 +1512,12 @@ pub fn run_cli() -> Result<()> {
                 resolve_targets,
                 starlark_vars,
                 release,
+                &profile,
                 verbose,
+                Path::new(out),
             )
         }
 
-        "cache-clear" => projectmgmt::cache_clear(&env),
-
         "find-resources" => {
             let path = args.value_of("path").map(Path::new);
             let distributions_dir = args.value_of("distributions_dir").map(Path::new);
@@ -540,6 +1541,44 @@ pub fn run_cli() -> Result<()> {
             }
         }
 
+        "fmt" => {
+            let check = args.is_present("check");
+            let paths = args
+                .values_of("paths")
+                .unwrap()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+
+            projectmgmt::format_files(&paths, check)
+        }
+
+        "generate-ci" => {
+            let provider = if args.is_present("gitlab") {
+                CiProvider::GitLab
+            } else {
+                CiProvider::GitHub
+            };
+            let platforms = args
+                .values_of("platforms")
+                .map(|values| values.map(|x| x.to_string()).collect());
+            let targets = args
+                .values_of("targets")
+                .map(|values| values.map(|x| x.to_string()).collect());
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::generate_ci(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                provider,
+                platforms,
+                targets,
+                args.is_present("sign"),
+                args.value_of("output").map(Path::new),
+                args.is_present("force"),
+            )
+        }
+
         "generate-python-embedding-artifacts" => {
             let target_triple = args
                 .value_of("target_triple")
@@ -561,6 +1600,26 @@ pub fn run_cli() -> Result<()> {
             )
         }
 
+        "generate-rust-project" => {
+            let starlark_vars = starlark_vars(&mut env, args)?;
+            let target_triple = args.value_of("target_triple");
+            let target = args.value_of("target");
+            let out = args.value_of("out").unwrap();
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::generate_rust_project(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                target,
+                Path::new(out),
+                starlark_vars,
+                args.is_present("release"),
+                verbose,
+            )
+        }
+
         "init-config-file" => {
             let code = args.value_of("python-code");
             let pip_install = if args.is_present("pip-install") {
@@ -587,6 +1646,13 @@ pub fn run_cli() -> Result<()> {
             projectmgmt::init_rust_project(&env, &logger_context.logger, project_path)
         }
 
+        "lsp" => projectmgmt::lsp(),
+
+        "man" => write_man_pages(
+            &build_app(version.as_str()),
+            args.value_of("out_dir").map(Path::new),
+        ),
+
         "python-distribution-extract" => {
             let download_default = args.is_present("download-default");
             let archive_path = args.value_of("archive-path");
@@ -616,7 +1682,7 @@ pub fn run_cli() -> Result<()> {
         }
 
         "run-build-script" => {
-            let starlark_vars = starlark_vars(args)?;
+            let starlark_vars = starlark_vars(&mut env, args)?;
             let build_script = args.value_of("build-script-name").unwrap();
             let target = args.value_of("target");
 
@@ -630,9 +1696,9 @@ pub fn run_cli() -> Result<()> {
         }
 
         "run" => {
-            let starlark_vars = starlark_vars(args)?;
+            let starlark_vars = starlark_vars(&mut env, args)?;
             let target_triple = args.value_of("target_triple");
-            let release = args.is_present("release");
+            let (release, profile) = build_profile(args)?;
             let path = args.value_of("path").unwrap();
             let target = args.value_of("target");
             let extra: Vec<&str> = args.values_of("extra").unwrap_or_default().collect();
@@ -643,6 +1709,7 @@ pub fn run_cli() -> Result<()> {
                 Path::new(path),
                 target_triple,
                 release,
+                &profile,
                 target,
                 starlark_vars,
                 &extra,
@@ -650,6 +1717,90 @@ pub fn run_cli() -> Result<()> {
             )
         }
 
+        "test-config" => {
+            let starlark_vars = starlark_vars(&mut env, args)?;
+            let target_triple = args.value_of("target_triple");
+            let tests = args.value_of("tests").map(Path::new);
+            let path = args.value_of("path").unwrap();
+
+            projectmgmt::test_config(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                tests,
+                target_triple,
+                starlark_vars,
+                verbose,
+            )
+        }
+
+        "verify-reproducible" => {
+            env.enable_reproducible();
+
+            let starlark_vars = starlark_vars(&mut env, args)?;
+            let (release, profile) = build_profile(args)?;
+            let target_triple = args.value_of("target_triple");
+            let path = args.value_of("path").unwrap();
+            let resolve_targets = args
+                .values_of("targets")
+                .map(|values| values.map(|x| x.to_string()).collect());
+
+            projectmgmt::verify_reproducible(
+                &env,
+                &logger_context.logger,
+                Path::new(path),
+                target_triple,
+                resolve_targets,
+                starlark_vars,
+                release,
+                &profile,
+                verbose,
+            )
+        }
+
         _ => Err(anyhow!("invalid sub-command")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_app() {
+        build_app(PYOXIDIZER_VERSION).debug_assert();
+    }
+
+    #[test]
+    fn test_completions() -> Result<()> {
+        let mut data = vec![];
+        write_completions(&mut build_app(PYOXIDIZER_VERSION), Shell::Bash, &mut data)?;
+        let script = String::from_utf8(data)?;
+
+        assert!(script.contains("generate-ci"));
+        assert!(script.contains("--var-file"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_man_pages() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let app = build_app(PYOXIDIZER_VERSION);
+
+        write_man_pages(&app, Some(temp_dir.path()))?;
+
+        assert!(temp_dir.path().join("pyoxidizer.1").exists());
+        for sub in app.get_subcommands() {
+            assert!(temp_dir
+                .path()
+                .join(format!("pyoxidizer-{}.1", sub.get_name()))
+                .exists());
+        }
+
+        let page = std::fs::read_to_string(temp_dir.path().join("pyoxidizer-diff-build.1"))?;
+        assert!(page.contains("Directory containing the old build"));
+
+        Ok(())
+    }
+}
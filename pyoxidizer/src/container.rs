@@ -0,0 +1,243 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Run PyOxidizer inside Docker or Podman containers.
+
+use {
+    crate::environment::Environment,
+    anyhow::{anyhow, Context, Result},
+    duct::cmd,
+    slog::warn,
+    std::{
+        io::{BufRead, BufReader},
+        path::{Path, PathBuf},
+    },
+};
+
+/// Path the project directory is mounted at inside containers.
+pub const CONTAINER_PROJECT_PATH: &str = "/project";
+
+/// Path the PyOxidizer cache directory is mounted at inside containers.
+pub const CONTAINER_CACHE_PATH: &str = "/pyoxidizer-cache";
+
+/// Path the running `pyoxidizer` executable is mounted at inside containers.
+const CONTAINER_PYOXIDIZER_PATH: &str = "/usr/local/bin/pyoxidizer";
+
+/// Find the executable of the container engine to use.
+///
+/// `PYOXIDIZER_CONTAINER_ENGINE` can name an executable to use. Otherwise
+/// `docker` and then `podman` are searched for.
+pub fn find_container_engine(env: &Environment) -> Result<PathBuf> {
+    if let Ok(engine) = std::env::var("PYOXIDIZER_CONTAINER_ENGINE") {
+        return env
+            .find_executable(&engine)?
+            .ok_or_else(|| anyhow!("container engine {} not found", engine));
+    }
+
+    for engine in ["docker", "podman"] {
+        if let Some(path) = env.find_executable(engine)? {
+            return Ok(path);
+        }
+    }
+
+    Err(anyhow!(
+        "unable to find docker or podman; install one or set PYOXIDIZER_CONTAINER_ENGINE"
+    ))
+}
+
+/// Describes an invocation of `pyoxidizer` inside a container.
+#[derive(Clone, Debug, Default)]
+pub struct ContainerInvocation {
+    /// Container image to run.
+    pub image: String,
+
+    /// Host directory to mount at [CONTAINER_PROJECT_PATH].
+    pub project_path: PathBuf,
+
+    /// Host directory to mount at [CONTAINER_CACHE_PATH].
+    pub cache_dir: PathBuf,
+
+    /// Host `pyoxidizer` executable to mount into the container.
+    ///
+    /// If not defined, the image must provide `pyoxidizer` on `PATH`.
+    pub pyoxidizer_exe: Option<PathBuf>,
+
+    /// `uid:gid` to run the container as.
+    pub user: Option<String>,
+
+    /// Environment variables to define in the container.
+    ///
+    /// Values are passed to the container engine process rather than on its
+    /// command line so they don't appear in process listings. Variables
+    /// without a value are not defined in the container.
+    pub env: Vec<(String, Option<String>)>,
+
+    /// Arguments to `pyoxidizer`.
+    pub args: Vec<String>,
+}
+
+impl ContainerInvocation {
+    /// Arguments to pass to the container engine.
+    pub fn engine_args(&self) -> Vec<String> {
+        let mut args = vec!["run".to_string(), "--rm".to_string()];
+
+        args.push("--volume".to_string());
+        args.push(format!(
+            "{}:{}",
+            self.project_path.display(),
+            CONTAINER_PROJECT_PATH
+        ));
+        args.push("--volume".to_string());
+        args.push(format!(
+            "{}:{}",
+            self.cache_dir.display(),
+            CONTAINER_CACHE_PATH
+        ));
+
+        if let Some(exe) = &self.pyoxidizer_exe {
+            args.push("--volume".to_string());
+            args.push(format!(
+                "{}:{}:ro",
+                exe.display(),
+                CONTAINER_PYOXIDIZER_PATH
+            ));
+        }
+
+        if let Some(user) = &self.user {
+            args.push("--user".to_string());
+            args.push(user.clone());
+            // The user likely has no home directory in the image.
+            args.push("--env".to_string());
+            args.push("HOME=/tmp".to_string());
+        }
+
+        args.push("--env".to_string());
+        args.push(format!("PYOXIDIZER_CACHE_DIR={}", CONTAINER_CACHE_PATH));
+
+        for (key, value) in &self.env {
+            if value.is_some() {
+                args.push("--env".to_string());
+                args.push(key.clone());
+            }
+        }
+
+        args.push("--workdir".to_string());
+        args.push(CONTAINER_PROJECT_PATH.to_string());
+        args.push(self.image.clone());
+        args.push("pyoxidizer".to_string());
+        args.extend(self.args.iter().cloned());
+
+        args
+    }
+
+    /// Run the invocation with the given container engine.
+    ///
+    /// Output from the container is forwarded to the logger.
+    pub fn run(&self, logger: &slog::Logger, engine: &Path) -> Result<()> {
+        std::fs::create_dir_all(&self.cache_dir)
+            .with_context(|| format!("creating {}", self.cache_dir.display()))?;
+
+        warn!(
+            logger,
+            "running pyoxidizer in container image {}", self.image
+        );
+
+        let mut command = cmd(engine, self.engine_args());
+        for (key, value) in &self.env {
+            if let Some(value) = value {
+                command = command.env(key, value);
+            }
+        }
+
+        let reader = command
+            .stderr_to_stdout()
+            .unchecked()
+            .reader()
+            .with_context(|| format!("invoking {}", engine.display()))?;
+        {
+            let lines = BufReader::new(&reader);
+            for line in lines.lines() {
+                warn!(logger, "{}", line.context("reading container output")?);
+            }
+        }
+        let output = reader
+            .try_wait()
+            .context("waiting on container process")?
+            .ok_or_else(|| anyhow!("unable to wait on command"))?;
+        if !output.status.success() {
+            return Err(anyhow!("build in container {} failed", self.image));
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolve the `uid:gid` owning a path, for running containers as that user.
+///
+/// Build outputs written through the project mount are then owned by the
+/// project owner rather than root.
+#[cfg(unix)]
+pub fn path_owner(path: &Path) -> Result<Option<String>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("resolving owner of {}", path.display()))?;
+
+    Ok(Some(format!("{}:{}", metadata.uid(), metadata.gid())))
+}
+
+/// Resolve the `uid:gid` owning a path, for running containers as that user.
+#[cfg(not(unix))]
+pub fn path_owner(_path: &Path) -> Result<Option<String>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_args() {
+        let invocation = ContainerInvocation {
+            image: "quay.io/pypa/manylinux2014_x86_64".to_string(),
+            project_path: PathBuf::from("/home/user/app"),
+            cache_dir: PathBuf::from("/home/user/.cache/pyoxidizer"),
+            pyoxidizer_exe: Some(PathBuf::from("/usr/bin/pyoxidizer")),
+            user: Some("1000:1000".to_string()),
+            env: vec![
+                ("SET".to_string(), Some("secret".to_string())),
+                ("UNSET".to_string(), None),
+            ],
+            args: vec!["build".to_string(), "--release".to_string()],
+        };
+
+        assert_eq!(
+            invocation.engine_args(),
+            vec![
+                "run",
+                "--rm",
+                "--volume",
+                "/home/user/app:/project",
+                "--volume",
+                "/home/user/.cache/pyoxidizer:/pyoxidizer-cache",
+                "--volume",
+                "/usr/bin/pyoxidizer:/usr/local/bin/pyoxidizer:ro",
+                "--user",
+                "1000:1000",
+                "--env",
+                "HOME=/tmp",
+                "--env",
+                "PYOXIDIZER_CACHE_DIR=/pyoxidizer-cache",
+                "--env",
+                "SET",
+                "--workdir",
+                "/project",
+                "quay.io/pypa/manylinux2014_x86_64",
+                "pyoxidizer",
+                "build",
+                "--release",
+            ]
+        );
+    }
+}
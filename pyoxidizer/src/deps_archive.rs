@@ -0,0 +1,146 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Archives of content builds download, for building without network access.
+
+A dependencies archive is a tar archive of a PyOxidizer cache directory
+populated by building a project. Extracting it into the cache directory of
+another machine allows that machine to build the project with `--offline`.
+*/
+
+use {
+    anyhow::{Context, Result},
+    std::path::Path,
+};
+
+/// Whether a cache relative path is left out of dependencies archives.
+///
+/// Locks and interrupted downloads are transient. Extracted Python
+/// distributions are recreated from their archives and may be modified
+/// in place by builds.
+fn is_excluded(relative: &Path) -> bool {
+    let filename = relative
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    if filename.ends_with(".lock") || filename.ends_with(".tmp") {
+        return true;
+    }
+
+    let mut components = relative.components();
+
+    matches!(
+        (components.next(), components.next()),
+        (Some(first), Some(second))
+            if first.as_os_str() == "python_distributions"
+                && second.as_os_str().to_string_lossy().starts_with("python.")
+    )
+}
+
+/// Write a dependencies archive of a cache directory to a file.
+///
+/// Returns the number of files in the archive.
+pub fn write_deps_archive(cache_dir: &Path, dest_path: &Path) -> Result<usize> {
+    if let Some(parent) = dest_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+    }
+
+    let fh = std::fs::File::create(dest_path)
+        .with_context(|| format!("creating {}", dest_path.display()))?;
+    let mut builder = tar::Builder::new(std::io::BufWriter::new(fh));
+    builder.follow_symlinks(false);
+
+    let mut count = 0;
+
+    let walker = walkdir::WalkDir::new(cache_dir)
+        .sort_by_file_name()
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry
+                .path()
+                .strip_prefix(cache_dir)
+                .map(|relative| !is_excluded(relative))
+                .unwrap_or(false)
+        });
+
+    for entry in walker {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(cache_dir)?;
+
+        builder
+            .append_path_with_name(entry.path(), relative)
+            .with_context(|| format!("adding {} to archive", entry.path().display()))?;
+
+        if !entry.file_type().is_dir() {
+            count += 1;
+        }
+    }
+
+    builder
+        .into_inner()
+        .and_then(|mut writer| std::io::Write::flush(&mut writer))
+        .with_context(|| format!("writing {}", dest_path.display()))?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded() {
+        assert!(is_excluded(Path::new("rust/1.56.1.lock")));
+        assert!(is_excluded(Path::new("http/foo.tmp")));
+        assert!(is_excluded(Path::new(
+            "python_distributions/python.0123456789ab"
+        )));
+        assert!(!is_excluded(Path::new(
+            "python_distributions/cpython-3.9.7-x86_64-unknown-linux-gnu.tar.zst"
+        )));
+        assert!(!is_excluded(Path::new("wheels/python.whl")));
+    }
+
+    #[test]
+    fn test_write_deps_archive() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache_dir = temp_dir.path().join("cache");
+
+        let dists_dir = cache_dir.join("python_distributions");
+        std::fs::create_dir_all(dists_dir.join("python.0123456789ab"))?;
+        std::fs::write(dists_dir.join("cpython.tar.zst"), b"dist")?;
+        std::fs::write(dists_dir.join("cpython.tar.lock"), b"")?;
+        std::fs::write(dists_dir.join("python.0123456789ab/python.exe"), b"")?;
+        std::fs::create_dir_all(cache_dir.join("wheels"))?;
+        std::fs::write(
+            cache_dir.join("wheels/six-1.16.0-py2.py3-none-any.whl"),
+            b"",
+        )?;
+
+        let archive_path = temp_dir.path().join("deps.tar");
+        assert_eq!(write_deps_archive(&cache_dir, &archive_path)?, 2);
+
+        let mut archive = tar::Archive::new(std::fs::File::open(&archive_path)?);
+        let mut names = vec![];
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if entry.header().entry_type().is_file() {
+                names.push(entry.path()?.to_string_lossy().to_string());
+            }
+        }
+
+        assert_eq!(
+            names,
+            vec![
+                "python_distributions/cpython.tar.zst",
+                "wheels/six-1.16.0-py2.py3-none-any.whl",
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,530 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Diagnose problems with the environment PyOxidizer runs in.
+
+use {
+    crate::{
+        environment::{default_target_triple, Environment, RUST_TOOLCHAIN_VERSION},
+        py_packaging::distribution::PythonDistributionLocation,
+        python_distributions::PYTHON_DISTRIBUTIONS,
+    },
+    std::{collections::BTreeSet, fmt::Write, path::Path, time::Duration},
+};
+
+/// Hosts PyOxidizer fetches content from, besides Python distribution hosts.
+const EXTRA_HOSTS: &[(&str, &str)] = &[
+    ("static.rust-lang.org", "managed Rust toolchains"),
+    ("github.com", "the WiX Toolset"),
+    ("pypi.org", "Python packages installed with pip"),
+];
+
+/// Outcome of a diagnostic check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum CheckStatus {
+    /// Nothing to act on.
+    Ok,
+
+    /// Builds may work but something is degraded or may fail later.
+    Warning,
+
+    /// Builds will fail.
+    Error,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// The result of a diagnostic check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CheckResult {
+    /// Short name of what was checked.
+    pub name: String,
+
+    /// Outcome of the check.
+    pub status: CheckStatus,
+
+    /// What was found.
+    pub message: String,
+
+    /// How to fix the problem.
+    pub remediation: Option<String>,
+}
+
+impl CheckResult {
+    fn ok(name: &str, message: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Ok,
+            message: message.to_string(),
+            remediation: None,
+        }
+    }
+
+    fn problem(
+        name: &str,
+        status: CheckStatus,
+        message: impl ToString,
+        remediation: impl ToString,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: message.to_string(),
+            remediation: Some(remediation.to_string()),
+        }
+    }
+}
+
+/// Check the Rust toolchain used to build for a target.
+fn check_rust(env: &Environment, target_triple: &str) -> CheckResult {
+    const NAME: &str = "Rust toolchain";
+
+    if env.managed_rust() {
+        let install_dir = env.rust_dir().join(format!(
+            "{}-{}",
+            RUST_TOOLCHAIN_VERSION,
+            default_target_triple()
+        ));
+
+        return if install_dir
+            .join(format!("MANIFEST.{}.rust-std", target_triple))
+            .exists()
+        {
+            CheckResult::ok(
+                NAME,
+                format!(
+                    "managed Rust {} for {} is installed in {}",
+                    RUST_TOOLCHAIN_VERSION,
+                    target_triple,
+                    install_dir.display()
+                ),
+            )
+        } else {
+            CheckResult::problem(
+                NAME,
+                CheckStatus::Warning,
+                format!(
+                    "managed Rust {} for {} is not installed",
+                    RUST_TOOLCHAIN_VERSION, target_triple
+                ),
+                "it is downloaded from static.rust-lang.org by the first build; \
+                 pass --system-rust to use an existing Rust installation instead",
+            )
+        };
+    }
+
+    let rust = match env.system_rust_environment() {
+        Ok(rust) => rust,
+        Err(e) => {
+            return CheckResult::problem(
+                NAME,
+                CheckStatus::Error,
+                format!("{:#}", e),
+                "install Rust from https://rustup.rs/ or omit --system-rust to use a \
+                 Rust toolchain managed by PyOxidizer",
+            )
+        }
+    };
+
+    if target_triple == rust.rust_version.host {
+        return CheckResult::ok(
+            NAME,
+            format!(
+                "system Rust {} at {}",
+                rust.rust_version.semver,
+                rust.rustc_exe.display()
+            ),
+        );
+    }
+
+    // Cross-compiling requires the target's standard library. We can only
+    // see which targets are installed if rustup manages the toolchain.
+    let installed = duct::cmd("rustup", &["target", "list", "--installed"])
+        .stderr_null()
+        .read();
+
+    match installed {
+        Ok(targets) if targets.lines().any(|l| l.trim() == target_triple) => CheckResult::ok(
+            NAME,
+            format!(
+                "system Rust {} with {} target installed",
+                rust.rust_version.semver, target_triple
+            ),
+        ),
+        Ok(_) => CheckResult::problem(
+            NAME,
+            CheckStatus::Error,
+            format!("Rust target {} is not installed", target_triple),
+            format!("run `rustup target add {}`", target_triple),
+        ),
+        Err(_) => CheckResult::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!(
+                "unable to determine whether Rust target {} is installed",
+                target_triple
+            ),
+            "ensure the Rust standard library for the target is installed",
+        ),
+    }
+}
+
+/// Check the C toolchain and linker used to build for a target.
+fn check_native_toolchain(env: &Environment, target_triple: &str) -> Option<CheckResult> {
+    if target_triple.contains("-windows-msvc") {
+        const NAME: &str = "MSVC";
+
+        if !cfg!(windows) {
+            return Some(CheckResult::problem(
+                NAME,
+                CheckStatus::Error,
+                "building for Windows requires a Windows machine",
+                "build on Windows, e.g. with `pyoxidizer generate-ci`",
+            ));
+        }
+
+        let vswhere = tugger_windows::find_vswhere().ok();
+
+        return Some(match tugger_windows::find_visual_studio_installation() {
+            Ok(install) => CheckResult::ok(
+                NAME,
+                format!(
+                    "Visual Studio {} at {} (found via {:?}{})",
+                    install.version.as_deref().unwrap_or("(unknown version)"),
+                    install.install_path.display(),
+                    install.discovery,
+                    if vswhere.is_some() {
+                        ""
+                    } else {
+                        "; vswhere.exe not found"
+                    }
+                ),
+            ),
+            Err(_) => CheckResult::problem(
+                NAME,
+                CheckStatus::Error,
+                "no Visual Studio or Visual Studio Build Tools installation found",
+                "install Visual Studio Build Tools with the \"Desktop development with C++\" \
+                 workload from https://visualstudio.microsoft.com/downloads/",
+            ),
+        });
+    }
+
+    if target_triple.contains("-apple-") {
+        const NAME: &str = "Xcode";
+
+        if !cfg!(target_os = "macos") {
+            return Some(CheckResult::problem(
+                NAME,
+                CheckStatus::Error,
+                "building for Apple platforms requires a macOS machine",
+                "build on macOS, e.g. with `pyoxidizer generate-ci`",
+            ));
+        }
+
+        let sdks = tugger_apple::find_default_developer_sdks()
+            .ok()
+            .filter(|sdks| !sdks.is_empty())
+            .or_else(|| tugger_apple::find_command_line_tools_sdks().ok().flatten());
+
+        return Some(match sdks {
+            Some(sdks) if !sdks.is_empty() => CheckResult::ok(
+                NAME,
+                format!(
+                    "found SDKs: {}",
+                    sdks.iter()
+                        .map(|sdk| sdk.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ),
+            _ => CheckResult::problem(
+                NAME,
+                CheckStatus::Error,
+                "no Xcode or Xcode Command Line Tools SDKs found",
+                "run `xcode-select --install` to install the Xcode Command Line Tools",
+            ),
+        });
+    }
+
+    const NAME: &str = "C compiler";
+
+    Some(match env.find_executable("cc") {
+        Ok(Some(path)) => CheckResult::ok(NAME, format!("found {}", path.display())),
+        _ => CheckResult::problem(
+            NAME,
+            CheckStatus::Error,
+            "no cc executable found; Rust needs it to link executables",
+            "install a C compiler, e.g. `apt install build-essential` or \
+             `dnf install gcc`",
+        ),
+    })
+}
+
+/// Check that the WiX Toolset can be obtained when building for Windows.
+fn check_wix(env: &Environment, target_triple: &str) -> Option<CheckResult> {
+    if !target_triple.contains("-windows-") {
+        return None;
+    }
+
+    const NAME: &str = "WiX Toolset";
+
+    Some(match env.find_executable("candle") {
+        Ok(Some(path)) => CheckResult::ok(
+            NAME,
+            format!(
+                "found {}; installers use a WiX Toolset downloaded from github.com",
+                path.display()
+            ),
+        ),
+        _ => CheckResult::ok(NAME, "downloaded from github.com when building installers"),
+    })
+}
+
+/// Resolve hosts Python distributions for a target are downloaded from.
+fn distribution_hosts(target_triple: &str) -> BTreeSet<String> {
+    PYTHON_DISTRIBUTIONS
+        .iter()
+        .filter(|dist| dist.target_triple == target_triple)
+        .filter_map(|dist| match &dist.location {
+            PythonDistributionLocation::Url { url, .. } => url
+                .split_once("://")
+                .and_then(|(_, rest)| rest.split('/').next())
+                .map(|host| host.to_string()),
+            PythonDistributionLocation::Local { .. } => None,
+        })
+        .collect()
+}
+
+/// Check that hosts PyOxidizer downloads from are reachable.
+fn check_network(target_triple: &str) -> Vec<CheckResult> {
+    let mut hosts = distribution_hosts(target_triple)
+        .into_iter()
+        .map(|host| (host, "Python distributions".to_string()))
+        .collect::<Vec<_>>();
+    for (host, purpose) in EXTRA_HOSTS {
+        if let Some(entry) = hosts.iter_mut().find(|(h, _)| h == host) {
+            entry.1 = format!("{} and {}", entry.1, purpose);
+        } else {
+            hosts.push((host.to_string(), purpose.to_string()));
+        }
+    }
+
+    let client = match tugger_common::http::get_http_client() {
+        Ok(client) => client,
+        Err(e) => {
+            return vec![CheckResult::problem(
+                "Network",
+                CheckStatus::Error,
+                format!("unable to construct HTTP client: {}", e),
+                "check the HTTPS_PROXY, HTTP_PROXY and TUGGER_HTTP_* environment variables",
+            )]
+        }
+    };
+
+    hosts
+        .into_iter()
+        .map(|(host, purpose)| {
+            let name = format!("Network access to {}", host);
+            let url = format!("https://{}/", host);
+
+            match client.head(&url).timeout(Duration::from_secs(10)).send() {
+                Ok(_) => CheckResult::ok(&name, format!("reachable; used for {}", purpose)),
+                Err(e) => CheckResult::problem(
+                    &name,
+                    CheckStatus::Warning,
+                    format!("unable to reach {}: {}; it is used for {}", url, e, purpose),
+                    "check network connectivity and proxy settings (HTTPS_PROXY); \
+                     builds work offline once everything they need is cached",
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Total size of files under a directory.
+fn directory_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Check that a cache directory is usable.
+fn check_cache_dir(cache_dir: &Path) -> CheckResult {
+    const NAME: &str = "Cache";
+
+    if let Err(e) = std::fs::create_dir_all(cache_dir) {
+        return CheckResult::problem(
+            NAME,
+            CheckStatus::Error,
+            format!("unable to create {}: {}", cache_dir.display(), e),
+            "set PYOXIDIZER_CACHE_DIR to a writable directory",
+        );
+    }
+
+    let probe = cache_dir.join(format!(".doctor-{}", std::process::id()));
+    if let Err(e) = std::fs::write(&probe, b"") {
+        return CheckResult::problem(
+            NAME,
+            CheckStatus::Error,
+            format!("{} is not writable: {}", cache_dir.display(), e),
+            "fix the directory's permissions or set PYOXIDIZER_CACHE_DIR to a writable \
+             directory",
+        );
+    }
+    let _ = std::fs::remove_file(&probe);
+
+    // Interrupted downloads leave partial files behind.
+    let partial = walkdir::WalkDir::new(cache_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .map(|name| name.ends_with(".tmp") || name.ends_with(".partial"))
+                .unwrap_or(false)
+        })
+        .count();
+
+    let size = directory_size(cache_dir);
+
+    if partial > 0 {
+        CheckResult::problem(
+            NAME,
+            CheckStatus::Warning,
+            format!(
+                "{} holds {} bytes including {} incomplete download(s)",
+                cache_dir.display(),
+                size,
+                partial
+            ),
+            "run `pyoxidizer cache-clear` if builds fail with checksum or archive errors",
+        )
+    } else {
+        CheckResult::ok(
+            NAME,
+            format!(
+                "{} is writable and holds {} bytes",
+                cache_dir.display(),
+                size
+            ),
+        )
+    }
+}
+
+/// Run diagnostic checks for building for a target triple.
+///
+/// Network checks are skipped if network access is disabled.
+pub fn run_checks(env: &Environment, target_triple: &str) -> Vec<CheckResult> {
+    let mut results = vec![check_rust(env, target_triple)];
+    results.extend(check_native_toolchain(env, target_triple));
+    results.extend(check_wix(env, target_triple));
+
+    if PYTHON_DISTRIBUTIONS
+        .all_target_triples()
+        .all(|triple| triple != target_triple)
+    {
+        results.push(CheckResult::problem(
+            "Python distribution",
+            CheckStatus::Error,
+            format!("no default Python distribution targets {}", target_triple),
+            "build for a supported target triple or pass a custom distribution to \
+             default_python_distribution()",
+        ));
+    }
+
+    if !env.offline() {
+        results.extend(check_network(target_triple));
+    }
+
+    results.push(check_cache_dir(env.cache_dir()));
+
+    results
+}
+
+/// Render check results as text.
+pub fn render_report(results: &[CheckResult]) -> String {
+    let mut s = String::new();
+
+    for result in results {
+        let _ = writeln!(
+            s,
+            "[{}] {}: {}",
+            result.status.as_str(),
+            result.name,
+            result.message
+        );
+        if let Some(remediation) = &result.remediation {
+            let _ = writeln!(s, "    fix: {}", remediation);
+        }
+    }
+
+    let problems = results
+        .iter()
+        .filter(|r| r.status != CheckStatus::Ok)
+        .count();
+    if problems == 0 {
+        s.push_str("no problems found\n");
+    } else {
+        let _ = writeln!(s, "{} problem(s) found", problems);
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anyhow::Result};
+
+    #[test]
+    fn test_distribution_hosts() {
+        assert!(distribution_hosts("x86_64-unknown-linux-gnu").contains("github.com"));
+        assert!(distribution_hosts("sparc-sun-solaris").is_empty());
+    }
+
+    #[test]
+    fn test_check_cache_dir() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache_dir = temp_dir.path().join("cache");
+
+        let result = check_cache_dir(&cache_dir);
+        assert_eq!(result.status, CheckStatus::Ok);
+        assert!(cache_dir.is_dir());
+
+        std::fs::write(cache_dir.join("cpython.tar.zst.tmp"), b"partial")?;
+        let result = check_cache_dir(&cache_dir);
+        assert_eq!(result.status, CheckStatus::Warning);
+        assert!(result.message.contains("1 incomplete download(s)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_report() {
+        let report = render_report(&[
+            CheckResult::ok("Cache", "writable"),
+            CheckResult::problem("C compiler", CheckStatus::Error, "missing", "install one"),
+        ]);
+
+        assert_eq!(
+            report,
+            "[ok] Cache: writable\n\
+             [error] C compiler: missing\n    fix: install one\n\
+             1 problem(s) found\n"
+        );
+    }
+}
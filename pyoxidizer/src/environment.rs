@@ -17,6 +17,7 @@ use {
         sync::{Arc, RwLock},
     },
     tugger_apple::{find_command_line_tools_sdks, find_default_developer_sdks, AppleSdk},
+    tugger_common::secrets::Secrets,
     tugger_rust_toolchain::install_rust_toolchain,
 };
 
@@ -32,6 +33,11 @@ const GIT_REPO_URL: &str = env!("GIT_REPO_URL");
 /// Version string of PyOxidizer.
 pub const PYOXIDIZER_VERSION: &str = env!("PYOXIDIZER_VERSION");
 
+/// `SOURCE_DATE_EPOCH` to use for reproducible builds if one isn't defined.
+///
+/// This is 1980-01-01T00:00:00Z, the earliest time representable in zip files.
+const DEFAULT_SOURCE_DATE_EPOCH: &str = "315532800";
+
 /// Filesystem path to Git repository we were built from.
 ///
 /// Will be None if a path is defined in the environment but not present.
@@ -238,10 +244,25 @@ pub struct Environment {
     /// Whether we should use a Rust installation we manage ourselves.
     managed_rust: bool,
 
+    /// Version of the managed Rust toolchain.
+    rust_toolchain_version: String,
+
     /// Rust environment to use.
     ///
     /// Cached because lookups may be expensive.
     rust_environment: Arc<RwLock<Option<RustEnvironment>>>,
+
+    /// Whether build outputs should be reproducible.
+    reproducible: bool,
+
+    /// Whether network access is disabled.
+    offline: bool,
+
+    /// Secret values that must not leak into built artifacts.
+    ///
+    /// Shared with the Tugger Starlark context so artifacts written by Tugger
+    /// are checked too.
+    secrets: Secrets,
 }
 
 impl Environment {
@@ -259,12 +280,26 @@ impl Environment {
 
         let managed_rust = std::env::var("PYOXIDIZER_SYSTEM_RUST").is_err();
 
-        Ok(Self {
+        let mut env = Self {
             pyoxidizer_source,
             cache_dir,
             managed_rust,
+            rust_toolchain_version: RUST_TOOLCHAIN_VERSION.to_string(),
             rust_environment: Arc::new(RwLock::new(None)),
-        })
+            reproducible: false,
+            offline: false,
+            secrets: Secrets::default(),
+        };
+
+        if std::env::var("PYOXIDIZER_REPRODUCIBLE").is_ok() {
+            env.enable_reproducible();
+        }
+
+        if std::env::var("PYOXIDIZER_OFFLINE").is_ok() {
+            env.enable_offline();
+        }
+
+        Ok(env)
     }
 
     /// Cache directory for PyOxidizer to use.
@@ -279,11 +314,38 @@ impl Environment {
         self.cache_dir.join("python_distributions")
     }
 
+    /// Directory to cache content fetched over HTTP by Starlark configs.
+    pub fn http_cache_dir(&self) -> PathBuf {
+        self.cache_dir.join("http")
+    }
+
     /// Directory to hold Rust toolchains.
     pub fn rust_dir(&self) -> PathBuf {
         self.cache_dir.join("rust")
     }
 
+    /// Directory holding Python packages for offline installs.
+    pub fn wheels_dir(&self) -> PathBuf {
+        self.cache_dir.join("wheels")
+    }
+
+    /// Directory holding conda repository metadata and packages.
+    pub fn conda_dir(&self) -> PathBuf {
+        self.cache_dir.join("conda")
+    }
+
+    /// Use a different cache directory.
+    pub fn set_cache_dir(&mut self, path: PathBuf) {
+        self.cache_dir = path;
+        // A managed Rust resolved before lives in the old cache directory.
+        self.rust_environment = Arc::new(RwLock::new(None));
+    }
+
+    /// Whether PyOxidizer manages the Rust toolchain it uses.
+    pub fn managed_rust(&self) -> bool {
+        self.managed_rust
+    }
+
     /// Do not use a managed Rust.
     ///
     /// When called, [self.ensure_rust_toolchain()] will attempt to locate a
@@ -298,6 +360,133 @@ impl Environment {
         Ok(())
     }
 
+    /// Version of the Rust toolchain used when Rust is managed.
+    pub fn rust_toolchain_version(&self) -> &str {
+        &self.rust_toolchain_version
+    }
+
+    /// Pin the version of the managed Rust toolchain.
+    ///
+    /// `version` must be an exact `X.Y.Z` version no older than
+    /// [MINIMUM_RUST_VERSION]. This has no effect if Rust isn't managed.
+    pub fn set_rust_toolchain_version(&mut self, version: &str) -> Result<()> {
+        let parsed = semver::Version::parse(version).map_err(|e| {
+            anyhow!(
+                "invalid Rust toolchain version {}: {} (expected a version like {})",
+                version,
+                e,
+                RUST_TOOLCHAIN_VERSION
+            )
+        })?;
+
+        if !parsed.pre.is_empty() || !parsed.build.is_empty() {
+            return Err(anyhow!(
+                "invalid Rust toolchain version {}: only stable releases are supported",
+                version
+            ));
+        }
+
+        if parsed.lt(&MINIMUM_RUST_VERSION) {
+            return Err(anyhow!(
+                "Rust toolchain version {} is older than the minimum required version {}",
+                version,
+                *MINIMUM_RUST_VERSION
+            ));
+        }
+
+        if self.rust_toolchain_version != version {
+            self.rust_toolchain_version = version.to_string();
+            // Don't reuse a toolchain resolved for another version. The cache
+            // is replaced rather than cleared because it is shared by clones.
+            self.rust_environment = Arc::new(RwLock::new(None));
+        }
+
+        Ok(())
+    }
+
+    /// Whether build outputs should be reproducible.
+    pub fn reproducible(&self) -> bool {
+        self.reproducible
+    }
+
+    /// Enable reproducible builds.
+    ///
+    /// This defines `SOURCE_DATE_EPOCH` (if not already defined) and
+    /// `PYTHONHASHSEED` in the current process so timestamps and hash ordering
+    /// in artifacts produced by us and by processes we invoke are deterministic.
+    /// `PYOXIDIZER_REPRODUCIBLE` is also defined so PyOxidizer processes invoked
+    /// by builds (e.g. from Cargo build scripts) behave the same way.
+    pub fn enable_reproducible(&mut self) {
+        self.reproducible = true;
+
+        if std::env::var_os("SOURCE_DATE_EPOCH").is_none() {
+            std::env::set_var("SOURCE_DATE_EPOCH", DEFAULT_SOURCE_DATE_EPOCH);
+        }
+
+        std::env::set_var("PYTHONHASHSEED", "0");
+        std::env::set_var("PYOXIDIZER_REPRODUCIBLE", "1");
+    }
+
+    /// Whether network access is disabled.
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Disable network access.
+    ///
+    /// Downloads of content not already in the cache fail instead of
+    /// touching the network. This also configures `pip` to only install
+    /// packages from [Self::wheels_dir] and Cargo to not access the network.
+    /// `PYOXIDIZER_OFFLINE` is defined so PyOxidizer processes invoked by
+    /// builds behave the same way.
+    pub fn enable_offline(&mut self) {
+        self.offline = true;
+        tugger_common::http::set_offline(true);
+
+        std::env::set_var("PIP_NO_INDEX", "1");
+        std::env::set_var("PIP_FIND_LINKS", self.wheels_dir());
+        std::env::set_var("CARGO_NET_OFFLINE", "true");
+        std::env::set_var("PYOXIDIZER_OFFLINE", "1");
+    }
+
+    /// Register a secret value.
+    ///
+    /// The value is redacted from log output. Building artifacts containing
+    /// it fails unless [Self::allow_secret_embedding] is called. Values
+    /// shorter than [tugger_common::secrets::MIN_SECRET_LENGTH] are only
+    /// redacted.
+    pub fn add_secret(&mut self, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+
+        crate::logging::add_redaction(value);
+        self.secrets.add(value);
+    }
+
+    /// Secret values registered via [Self::add_secret].
+    pub fn secrets(&self) -> &Secrets {
+        &self.secrets
+    }
+
+    /// Allow secret values to be embedded in built artifacts.
+    pub fn allow_secret_embedding(&mut self) {
+        self.secrets.allow_embedding();
+    }
+
+    /// Whether secret values may be embedded in built artifacts.
+    pub fn secret_embedding_allowed(&self) -> bool {
+        self.secrets.embedding_allowed()
+    }
+
+    /// Ensure artifact data doesn't contain a registered secret value.
+    ///
+    /// `artifact` describes the data in the error message. The secret itself
+    /// is never included in the error.
+    pub fn ensure_no_secrets(&self, artifact: &str, data: &[u8]) -> Result<()> {
+        self.secrets.ensure_not_in(artifact, data)
+    }
+
     /// Find an executable of the given name.
     ///
     /// Resolves to `Some(T)` if an executable was found or `None` if not.
@@ -325,7 +514,7 @@ impl Environment {
         if cached.is_none() {
             warn!(
                 logger,
-                "ensuring Rust toolchain {} is available", RUST_TOOLCHAIN_VERSION,
+                "ensuring Rust toolchain {} is available", self.rust_toolchain_version,
             );
 
             let rust_env = if self.managed_rust {
@@ -335,7 +524,7 @@ impl Environment {
 
                 let toolchain = install_rust_toolchain(
                     logger,
-                    RUST_TOOLCHAIN_VERSION,
+                    &self.rust_toolchain_version,
                     default_target_triple(),
                     &[target_triple],
                     &self.rust_dir(),
@@ -396,7 +585,7 @@ impl Environment {
     /// This attempts to locate a Rust toolchain suitable for use with
     /// PyOxidizer. If a toolchain could not be found or doesn't meet the
     /// requirements, an error occurs.
-    fn system_rust_environment(&self) -> Result<RustEnvironment> {
+    pub fn system_rust_environment(&self) -> Result<RustEnvironment> {
         let cargo_exe = self
             .cargo_exe()
             .context("finding cargo executable")?
@@ -11,14 +11,21 @@ distribution and embedding it in a larger binary, oftentimes an executable.
 This library exposes that functionality to other tools.
 */
 
+pub mod ci;
+pub mod cli;
+pub mod container;
+pub mod deps_archive;
+pub mod doctor;
 pub mod environment;
 pub mod logging;
+pub mod lsp;
 pub mod project_building;
 pub mod project_layout;
 pub mod projectmgmt;
 pub mod py_packaging;
 pub mod python_distributions;
 pub mod starlark;
+pub mod update_patch;
 
 #[cfg(test)]
 mod testutil;
@@ -2,7 +2,69 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use slog::Drain;
+use {
+    once_cell::sync::Lazy,
+    serde_json::{Map, Value},
+    slog::{Drain, Key, Serializer, KV},
+    std::{fmt::Arguments, sync::RwLock},
+};
+
+/// Text replacing redacted values in log output.
+pub const REDACTED: &str = "<redacted>";
+
+/// Values that must not appear in log output.
+///
+/// This is process global so every logger, including ones constructed
+/// before a value is registered, redacts it.
+static REDACTIONS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(vec![]));
+
+/// Register a value to be redacted from all log output.
+pub fn add_redaction(value: &str) {
+    if value.is_empty() {
+        return;
+    }
+
+    let mut redactions = REDACTIONS.write().unwrap();
+
+    if !redactions.iter().any(|v| v == value) {
+        redactions.push(value.to_string());
+        // Replace longer values first so a value containing another is fully redacted.
+        redactions.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    }
+}
+
+/// Replace registered redaction values in a message.
+pub fn redact(message: &str) -> String {
+    REDACTIONS
+        .read()
+        .unwrap()
+        .iter()
+        .fold(message.to_string(), |message, value| {
+            message.replace(value, REDACTED)
+        })
+}
+
+/// Format of output emitted by the CLI.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text.
+    Text,
+
+    /// A stream of JSON objects, one per line.
+    Json,
+}
+
+impl TryFrom<&str> for OutputFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("{} is not a valid output format", value)),
+        }
+    }
+}
 
 /// A slog Drain that uses println!.
 pub struct PrintlnDrain {
@@ -21,7 +83,90 @@ impl slog::Drain for PrintlnDrain {
         _values: &slog::OwnedKVList,
     ) -> Result<Self::Ok, Self::Err> {
         if record.level().is_at_least(self.min_level) {
-            println!("{}", record.msg());
+            println!("{}", redact(&record.msg().to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects slog key-value pairs into a JSON object.
+struct JsonSerializer(Map<String, Value>);
+
+impl Serializer for JsonSerializer {
+    fn emit_arguments(&mut self, key: Key, val: &Arguments) -> slog::Result {
+        self.0
+            .insert(key.to_string(), Value::from(redact(&val.to_string())));
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_usize(&mut self, key: Key, val: usize) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+}
+
+/// Convert a log record to a JSON object.
+///
+/// The record's key-value pairs become fields of the object. Records
+/// describing structured events carry an `event` field.
+fn record_to_json(record: &slog::Record, values: &slog::OwnedKVList) -> Map<String, Value> {
+    let mut serializer = JsonSerializer(Map::new());
+
+    // Serialization into a map is infallible.
+    let _ = values.serialize(record, &mut serializer);
+    let _ = record.kv().serialize(record, &mut serializer);
+
+    let mut object = serializer.0;
+    object.insert(
+        "level".to_string(),
+        Value::from(record.level().as_str().to_lowercase()),
+    );
+    object.insert(
+        "message".to_string(),
+        Value::from(redact(&record.msg().to_string())),
+    );
+
+    object
+}
+
+/// A slog Drain that prints records as JSON objects, one per line.
+///
+/// Records describing structured events are always emitted, regardless of
+/// their level.
+pub struct JsonDrain {
+    /// Minimum logging level that we're emitting.
+    pub min_level: slog::Level,
+}
+
+impl slog::Drain for JsonDrain {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(
+        &self,
+        record: &slog::Record,
+        values: &slog::OwnedKVList,
+    ) -> Result<Self::Ok, Self::Err> {
+        let object = record_to_json(record, values);
+
+        if record.level().is_at_least(self.min_level) || object.contains_key("event") {
+            println!("{}", Value::Object(object));
         }
 
         Ok(())
@@ -34,9 +179,12 @@ pub struct LoggerContext {
 }
 
 /// Construct a slog::Logger from settings in environment.
-pub fn logger_from_env(min_level: slog::Level) -> LoggerContext {
+pub fn logger_from_env(min_level: slog::Level, format: OutputFormat) -> LoggerContext {
     LoggerContext {
-        logger: slog::Logger::root(PrintlnDrain { min_level }.fuse(), slog::o!()),
+        logger: match format {
+            OutputFormat::Text => slog::Logger::root(PrintlnDrain { min_level }.fuse(), slog::o!()),
+            OutputFormat::Json => slog::Logger::root(JsonDrain { min_level }.fuse(), slog::o!()),
+        },
     }
 }
 
@@ -53,3 +201,72 @@ impl Default for LoggerContext {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, slog::warn, std::sync::Mutex};
+
+    /// A drain capturing records as JSON objects.
+    struct CaptureDrain(Mutex<Vec<Map<String, Value>>>);
+
+    impl slog::Drain for CaptureDrain {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(
+            &self,
+            record: &slog::Record,
+            values: &slog::OwnedKVList,
+        ) -> Result<Self::Ok, Self::Err> {
+            self.0.lock().unwrap().push(record_to_json(record, values));
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_to_json() {
+        let drain = std::sync::Arc::new(CaptureDrain(Mutex::new(vec![])));
+        let logger = slog::Logger::root(drain.clone(), slog::o!("command" => "build"));
+
+        warn!(logger, "built {}", "app"; "event" => "artifact", "size" => 42u64);
+
+        let records = drain.0.lock().unwrap();
+        assert_eq!(
+            Value::Object(records[0].clone()),
+            serde_json::json!({
+                "command": "build",
+                "event": "artifact",
+                "level": "warn",
+                "message": "built app",
+                "size": 42,
+            })
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        add_redaction("hunter2");
+        add_redaction("hunter2-long");
+        add_redaction("");
+
+        assert_eq!(redact("password is hunter2"), "password is <redacted>");
+        assert_eq!(redact("token hunter2-long"), "token <redacted>");
+        assert_eq!(redact("nothing to hide"), "nothing to hide");
+
+        let drain = std::sync::Arc::new(CaptureDrain(Mutex::new(vec![])));
+        let logger = slog::Logger::root(drain.clone(), slog::o!());
+
+        warn!(logger, "using {}", "hunter2"; "value" => "x hunter2 x");
+
+        let records = drain.0.lock().unwrap();
+        assert_eq!(records[0]["message"], "using <redacted>");
+        assert_eq!(records[0]["value"], "x <redacted> x");
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(OutputFormat::try_from("json"), Ok(OutputFormat::Json));
+        assert!(OutputFormat::try_from("xml").is_err());
+    }
+}
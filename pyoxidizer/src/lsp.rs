@@ -0,0 +1,594 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+A language server for PyOxidizer configuration files.
+
+The server speaks the [Language Server Protocol](https://microsoft.github.io/language-server-protocol/)
+over stdin and stdout. It offers completion and hover documentation for
+the symbols of the Starlark dialect, diagnostics for syntax errors and
+unknown attributes, and document formatting.
+
+Since values aren't typed until evaluation, attributes are checked
+against the union of attributes of all known types.
+*/
+
+use {
+    crate::starlark::{
+        format::{check_syntax, format_source, segments, SegmentKind},
+        symbols::{
+            dialect_symbols, find_global, find_members, is_known_attribute, Symbol, SymbolKind,
+            STARLARK_BUILTINS, STARLARK_BUILTIN_METHODS,
+        },
+    },
+    anyhow::{anyhow, Context, Result},
+    serde_json::{json, Value},
+    std::{
+        collections::{BTreeMap, HashMap},
+        io::{BufRead, Write},
+    },
+};
+
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+
+const ERROR_METHOD_NOT_FOUND: i64 = -32601;
+
+fn completion_item_kind(kind: SymbolKind) -> u64 {
+    match kind {
+        SymbolKind::Method => 2,
+        SymbolKind::Function => 3,
+        SymbolKind::Attribute => 5,
+        SymbolKind::Type => 7,
+        SymbolKind::Constant => 21,
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Resolve the start of the identifier ending at a byte offset.
+fn identifier_start(source: &str, offset: usize) -> usize {
+    source[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_char(*c))
+        .last()
+        .map_or(offset, |(i, _)| i)
+}
+
+/// Read a message framed by a `Content-Length` header.
+///
+/// Returns `None` when the input is closed.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .context("parsing Content-Length")?,
+                );
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| anyhow!("message has no Content-Length header"))?;
+    let mut data = vec![0; length];
+    reader.read_exact(&mut data)?;
+
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+/// Write a message framed by a `Content-Length` header.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let data = serde_json::to_vec(message)?;
+
+    write!(writer, "Content-Length: {}\r\n\r\n", data.len())?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Convert a byte offset into an LSP position.
+///
+/// Positions count UTF-16 code units, per the protocol.
+fn position_of(source: &str, offset: usize) -> Value {
+    let prefix = &source[..offset];
+    let line_start = prefix.rfind('\n').map_or(0, |p| p + 1);
+
+    json!({
+        "line": prefix.matches('\n').count(),
+        "character": prefix[line_start..].encode_utf16().count(),
+    })
+}
+
+/// Convert an LSP position into a byte offset.
+fn offset_of(source: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+    let line_start = if line == 0 {
+        0
+    } else {
+        source
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(source.len(), |(p, _)| p + 1)
+    };
+    let line_text = source[line_start..].split('\n').next().unwrap_or_default();
+
+    let mut units = 0;
+    for (i, c) in line_text.char_indices() {
+        if units >= character {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+
+    line_start + line_text.len()
+}
+
+/// Find attribute accesses (`.name`) in source code.
+///
+/// Returns byte ranges of attribute names.
+fn attribute_accesses(source: &str) -> Vec<(usize, usize)> {
+    let mut accesses = vec![];
+
+    for (_, range) in segments(source)
+        .into_iter()
+        .filter(|(kind, _)| *kind == SegmentKind::Code)
+    {
+        for (i, _) in source[range.clone()].match_indices('.') {
+            let dot = range.start + i;
+            let start = dot + 1;
+
+            let end = source[start..range.end]
+                .find(|c: char| !is_identifier_char(c))
+                .map_or(range.end, |p| start + p);
+
+            if end == start || source[start..].starts_with(|c: char| c.is_ascii_digit()) {
+                continue;
+            }
+
+            // The receiver must be an expression, not a number like `1.5`.
+            let receiver = &source[..dot];
+            let word = &receiver[identifier_start(source, dot)..];
+
+            if receiver.ends_with(|c: char| c.is_whitespace())
+                || (!word.is_empty() && word.chars().all(|c| c.is_ascii_digit()))
+            {
+                continue;
+            }
+
+            accesses.push((start, end));
+        }
+    }
+
+    accesses
+}
+
+/// Compute diagnostics for a document.
+pub fn diagnostics(source: &str) -> Vec<Value> {
+    let mut diagnostics = vec![];
+
+    if let Err(e) = check_syntax("pyoxidizer.bzl", source) {
+        let ((start_line, start_column), (end_line, end_column)) =
+            e.span.unwrap_or(((0, 0), (0, 0)));
+
+        diagnostics.push(json!({
+            "range": {
+                "start": {"line": start_line, "character": start_column},
+                "end": {"line": end_line, "character": end_column},
+            },
+            "severity": SEVERITY_ERROR,
+            "source": "pyoxidizer",
+            "message": e.message,
+        }));
+    }
+
+    for (start, end) in attribute_accesses(source) {
+        let name = &source[start..end];
+
+        if !is_known_attribute(name) {
+            diagnostics.push(json!({
+                "range": {"start": position_of(source, start), "end": position_of(source, end)},
+                "severity": SEVERITY_WARNING,
+                "source": "pyoxidizer",
+                "message": format!("unknown attribute: {}", name),
+            }));
+        }
+    }
+
+    diagnostics
+}
+
+/// Resolve the identifier at an offset.
+///
+/// Returns the byte range of the identifier and whether it is an attribute.
+fn identifier_at(source: &str, offset: usize) -> (usize, usize, bool) {
+    let start = identifier_start(source, offset);
+    let end = source[offset..]
+        .find(|c: char| !is_identifier_char(c))
+        .map_or(source.len(), |p| offset + p);
+
+    (start, end, source[..start].ends_with('.'))
+}
+
+fn completion_item(label: &str, kind: u64, symbols: &[&Symbol]) -> Value {
+    let mut item = json!({"label": label, "kind": kind});
+
+    if let [symbol] = symbols {
+        item["detail"] = json!(symbol.signature);
+        item["documentation"] = json!({"kind": "markdown", "value": symbol.to_markdown()});
+    } else if !symbols.is_empty() {
+        item["detail"] = json!(symbols
+            .iter()
+            .filter_map(|s| s.owner.as_deref())
+            .collect::<Vec<_>>()
+            .join(", "));
+    }
+
+    item
+}
+
+/// Compute completions at an offset.
+fn completions(source: &str, offset: usize) -> Vec<Value> {
+    let (start, _, is_attribute) = identifier_at(source, offset);
+    let prefix = &source[start..offset];
+
+    let mut symbols: BTreeMap<&str, Vec<&Symbol>> = BTreeMap::new();
+    for symbol in dialect_symbols() {
+        if symbol.owner.is_some() == is_attribute && !symbol.name.ends_with('*') {
+            symbols
+                .entry(symbol.name.as_str())
+                .or_default()
+                .push(symbol);
+        }
+    }
+
+    let builtins = if is_attribute {
+        STARLARK_BUILTIN_METHODS
+    } else {
+        STARLARK_BUILTINS
+    };
+    let builtin_kind = if is_attribute { 2 } else { 3 };
+
+    builtins
+        .iter()
+        .filter(|name| !symbols.contains_key(*name))
+        .map(|name| (*name, completion_item(name, builtin_kind, &[])))
+        .chain(symbols.iter().map(|(name, symbols)| {
+            (
+                *name,
+                completion_item(name, completion_item_kind(symbols[0].kind), symbols),
+            )
+        }))
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Compute hover documentation at an offset.
+fn hover(source: &str, offset: usize) -> Option<Value> {
+    let (start, end, is_attribute) = identifier_at(source, offset);
+    let name = &source[start..end];
+
+    let symbols = if is_attribute {
+        find_members(name)
+    } else {
+        find_global(name)
+    };
+
+    if symbols.is_empty() {
+        return None;
+    }
+
+    Some(json!({
+        "contents": {
+            "kind": "markdown",
+            "value": symbols
+                .iter()
+                .map(|s| s.to_markdown())
+                .collect::<Vec<_>>()
+                .join("\n\n---\n\n"),
+        },
+        "range": {"start": position_of(source, start), "end": position_of(source, end)},
+    }))
+}
+
+/// Compute edits formatting a document.
+fn formatting(source: &str) -> Value {
+    if check_syntax("pyoxidizer.bzl", source).is_err() {
+        return Value::Null;
+    }
+
+    let formatted = format_source(source);
+
+    if formatted == source {
+        json!([])
+    } else {
+        json!([{
+            "range": {"start": position_of(source, 0), "end": position_of(source, source.len())},
+            "newText": formatted,
+        }])
+    }
+}
+
+/// State of a language server.
+#[derive(Default)]
+pub struct Server {
+    /// Content of open documents, keyed by URI.
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let diagnostics = self
+            .documents
+            .get(uri)
+            .map(|source| diagnostics(source))
+            .unwrap_or_default();
+
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        })
+    }
+
+    /// Resolve the document and offset a request refers to.
+    fn document_position(&self, params: &Value) -> Option<(&str, usize)> {
+        let source = self
+            .documents
+            .get(params["textDocument"]["uri"].as_str()?)?;
+
+        Some((source, offset_of(source, &params["position"])))
+    }
+
+    /// Handle a message from the client.
+    ///
+    /// Returns messages to send to the client and whether the server
+    /// should exit.
+    pub fn handle_message(&mut self, message: &Value) -> (Vec<Value>, bool) {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            // Responses to requests we never send.
+            None => return (vec![], false),
+        };
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let mut messages = vec![];
+
+        let result = match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": {"triggerCharacters": ["."]},
+                    "hoverProvider": true,
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": {"name": "pyoxidizer", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "shutdown" => Some(Value::Null),
+            "exit" => return (vec![], true),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                messages.push(self.publish_diagnostics(&uri));
+                None
+            }
+            "textDocument/didChange" => {
+                // We only support full document synchronization.
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                    messages.push(self.publish_diagnostics(&uri));
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                messages.push(self.publish_diagnostics(&uri));
+                None
+            }
+            "textDocument/completion" => Some(
+                self.document_position(params)
+                    .map(|(source, offset)| json!(completions(source, offset)))
+                    .unwrap_or_else(|| json!([])),
+            ),
+            "textDocument/hover" => Some(
+                self.document_position(params)
+                    .and_then(|(source, offset)| hover(source, offset))
+                    .unwrap_or(Value::Null),
+            ),
+            "textDocument/formatting" => Some(
+                self.documents
+                    .get(&uri)
+                    .map(|source| formatting(source))
+                    .unwrap_or(Value::Null),
+            ),
+            _ => None,
+        };
+
+        // Only requests carry an id and expect a response.
+        if let Some(id) = message.get("id") {
+            messages.push(match result {
+                Some(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": ERROR_METHOD_NOT_FOUND,
+                        "message": format!("unsupported method: {}", method),
+                    },
+                }),
+            });
+        }
+
+        (messages, false)
+    }
+}
+
+/// Run a language server until the client asks it to exit.
+pub fn run_server(reader: &mut impl BufRead, writer: &mut impl Write) -> Result<()> {
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(reader)? {
+        let (messages, exit) = server.handle_message(&message);
+
+        for message in messages {
+            write_message(writer, &message)?;
+        }
+
+        if exit {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_framing() -> Result<()> {
+        let mut data = vec![];
+        write_message(&mut data, &json!({"jsonrpc": "2.0", "method": "exit"}))?;
+        assert!(data.starts_with(b"Content-Length: 33\r\n\r\n"));
+
+        let mut reader = std::io::Cursor::new(data);
+        assert_eq!(
+            read_message(&mut reader)?,
+            Some(json!({"jsonrpc": "2.0", "method": "exit"}))
+        );
+        assert_eq!(read_message(&mut reader)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_positions() {
+        let source = "a = 1\nb = 'é'.upper()\n";
+
+        let offset = source.find("upper").unwrap();
+        let position = position_of(source, offset);
+        assert_eq!(position, json!({"line": 1, "character": 8}));
+        assert_eq!(offset_of(source, &position), offset);
+
+        assert_eq!(offset_of(source, &json!({"line": 0, "character": 100})), 5);
+    }
+
+    #[test]
+    fn test_unknown_attributes() {
+        let source = "\
+exe = dist.to_python_executable(name = 'app')
+exe.add_python_resources(exe.pip_install(['foo']))
+exe.windows_subsystem = 'console'
+policy.resources_location_fallback = None
+'a.b'.upper() + 'c' # x.not_checked
+manifest.frobnicate()
+";
+
+        let messages = diagnostics(source)
+            .into_iter()
+            .map(|d| d["message"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(messages, vec!["unknown attribute: frobnicate"]);
+
+        let diagnostics = diagnostics("def foo(:\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["severity"], SEVERITY_ERROR);
+    }
+
+    #[test]
+    fn test_completions() {
+        let labels = |source: &str| {
+            completions(source, source.len())
+                .into_iter()
+                .map(|item| item["label"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(labels("dist = default_python_").contains(&"default_python_distribution".into()));
+        assert!(labels("exe.to_").contains(&"to_file_manifest".into()));
+        assert!(labels("x.up").contains(&"upper".into()));
+        assert!(!labels("exe.to_").contains(&"default_python_distribution".into()));
+    }
+
+    #[test]
+    fn test_hover() {
+        let source = "m = exe.to_file_manifest('.')";
+        let value = hover(source, source.find("file").unwrap()).unwrap();
+        assert!(value["contents"]["value"]
+            .as_str()
+            .unwrap()
+            .contains("PythonExecutable.to_file_manifest(prefix: str)"));
+
+        assert!(hover(source, 0).is_none());
+    }
+
+    #[test]
+    fn test_server() {
+        let mut server = Server::default();
+
+        let (messages, exit) = server.handle_message(
+            &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        );
+        assert!(!exit);
+        assert_eq!(messages[0]["result"]["capabilities"]["hoverProvider"], true);
+
+        let (messages, _) = server.handle_message(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": "file:///pyoxidizer.bzl", "text": "x.bogus\n\n\n\n"}},
+        }));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0]["params"]["diagnostics"][0]["message"],
+            "unknown attribute: bogus"
+        );
+
+        let (messages, _) = server.handle_message(&json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "textDocument/formatting",
+            "params": {"textDocument": {"uri": "file:///pyoxidizer.bzl"}},
+        }));
+        assert_eq!(messages[0]["result"][0]["newText"], "x.bogus\n");
+
+        let (messages, _) = server.handle_message(
+            &json!({"jsonrpc": "2.0", "id": 3, "method": "workspace/symbol", "params": {}}),
+        );
+        assert_eq!(messages[0]["error"]["code"], ERROR_METHOD_NOT_FOUND);
+
+        let (_, exit) = server.handle_message(&json!({"jsonrpc": "2.0", "method": "exit"}));
+        assert!(exit);
+    }
+}
@@ -27,9 +27,14 @@ is implemented in C. Rust is simply a tool to achieve an end goal, albeit
 a rather effective and powerful tool.
 */
 
+mod ci;
 mod cli;
+mod container;
+mod deps_archive;
+mod doctor;
 mod environment;
 mod logging;
+mod lsp;
 mod project_building;
 mod project_layout;
 mod projectmgmt;
@@ -38,6 +43,7 @@ mod python_distributions;
 pub mod starlark;
 #[cfg(test)]
 mod testutil;
+mod update_patch;
 
 fn main() {
     std::process::exit(match cli::run_cli() {
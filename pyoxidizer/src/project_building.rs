@@ -5,11 +5,14 @@
 use {
     crate::{
         environment::{canonicalize_path, Environment, RustEnvironment},
-        project_layout::initialize_project,
+        project_layout::{
+            initialize_project, vendor_project_crates, write_portable_launcher_project,
+        },
         py_packaging::{
             binary::{LibpythonLinkMode, PythonBinaryBuilder},
             distribution::AppleSdkInfo,
             embedding::{EmbeddedPythonContext, DEFAULT_PYTHON_CONFIG_FILENAME},
+            portable::PortableLauncher,
         },
         starlark::eval::{EvaluationContext, EvaluationContextBuilder},
     },
@@ -18,7 +21,7 @@ use {
     slog::warn,
     starlark_dialect_build_targets::ResolvedTarget,
     std::{
-        collections::HashMap,
+        collections::{BTreeMap, BTreeSet, HashMap},
         fs::create_dir_all,
         io::{BufRead, BufReader},
         path::{Path, PathBuf},
@@ -214,6 +217,44 @@ impl BuildEnvironment {
             environment_vars: envs,
         })
     }
+
+    /// Instruct rustc to rewrite a path prefix in the binaries it produces.
+    ///
+    /// Paths to source files are embedded in binaries, e.g. in panic messages.
+    /// Remapping them makes binaries independent of the directory they are
+    /// built in.
+    pub fn remap_path_prefix(&mut self, logger: &slog::Logger, from: &Path, to: &str) {
+        let from = from.display().to_string();
+
+        // RUSTFLAGS is whitespace delimited, so these paths can't be expressed.
+        if from.contains(char::is_whitespace) {
+            warn!(
+                logger,
+                "unable to remap path containing whitespace: {}; build may not be reproducible",
+                from
+            );
+            return;
+        }
+
+        self.add_rust_flags(&[format!("--remap-path-prefix={}={}", from, to)]);
+    }
+
+    /// Append flags to the `RUSTFLAGS` passed to `rustc`.
+    pub fn add_rust_flags(&mut self, flags: &[String]) {
+        if flags.is_empty() {
+            return;
+        }
+
+        let flags = flags.join(" ");
+
+        let value = if let Some(value) = self.environment_vars.get("RUSTFLAGS") {
+            format!("{} {}", value, flags)
+        } else {
+            flags
+        };
+
+        self.environment_vars.insert("RUSTFLAGS".to_string(), value);
+    }
 }
 
 /// Holds results from building an executable.
@@ -231,6 +272,60 @@ pub struct BuiltExecutable<'a> {
     pub binary_data: EmbeddedPythonContext<'a>,
 }
 
+/// Cargo features of a generated Rust project to enable to build an executable.
+///
+/// The project is built with artifacts generated by PyOxidizer. Features
+/// requested by [PythonBinaryBuilder::cargo_build_options] come last.
+pub fn cargo_features(exe: &dyn PythonBinaryBuilder) -> Vec<String> {
+    let mut features = vec!["build-mode-prebuilt-artifacts"];
+
+    if exe.requires_jemalloc() {
+        features.push("global-allocator-jemalloc");
+        features.push("allocator-jemalloc");
+    }
+    if exe.requires_mimalloc() {
+        features.push("global-allocator-mimalloc");
+        features.push("allocator-mimalloc");
+    }
+    if exe.requires_snmalloc() {
+        features.push("global-allocator-snmalloc");
+        features.push("allocator-snmalloc");
+    }
+    if exe.requires_crash_reporting() {
+        features.push("crash-reporting");
+    }
+    if exe.requires_updater() {
+        features.push("updater");
+    }
+    if exe.requires_daemon() {
+        features.push("daemon");
+    }
+    if exe.requires_single_instance() {
+        features.push("single-instance");
+    }
+    if exe.requires_splash_screen() {
+        features.push("splash-screen");
+    }
+    if exe.requires_output_capture() {
+        features.push("output-capture");
+    }
+    if exe.requires_logging_bridge() {
+        features.push("logging-bridge");
+    }
+    if exe.requires_runtime_config() {
+        features.push("runtime-config");
+    }
+    if exe.self_extracting() {
+        features.push("self-extracting");
+    }
+
+    features
+        .into_iter()
+        .map(|x| x.to_string())
+        .chain(exe.cargo_build_options().features.iter().cloned())
+        .collect()
+}
+
 /// Build an executable embedding Python using an existing Rust project.
 ///
 /// The path to the produced executable is returned.
@@ -258,7 +353,7 @@ pub fn build_executable_with_rust_project<'a>(
         .write_files(artifacts_path)
         .context("writing embedded python context files")?;
 
-    let build_env = BuildEnvironment::new(
+    let mut build_env = BuildEnvironment::new(
         env,
         logger,
         exe.target_triple(),
@@ -269,6 +364,20 @@ pub fn build_executable_with_rust_project<'a>(
     )
     .context("resolving build environment")?;
 
+    // These directories are often temporary. So remap them to fixed values to
+    // keep them from leaking into the binary.
+    if env.reproducible() {
+        build_env.remap_path_prefix(logger, project_path, "/pyoxidizer/project");
+        build_env.remap_path_prefix(logger, build_path, "/pyoxidizer/build");
+        build_env.remap_path_prefix(logger, artifacts_path, "/pyoxidizer/artifacts");
+    }
+
+    let cargo_options = exe.cargo_build_options();
+    build_env.add_rust_flags(&cargo_options.rustflags);
+    build_env
+        .environment_vars
+        .extend(cargo_options.environment_variables(target_triple, release));
+
     warn!(
         logger,
         "building with Rust {}", build_env.rust_environment.rust_version.semver
@@ -298,22 +407,7 @@ pub fn build_executable_with_rust_project<'a>(
     }
 
     args.push("--no-default-features");
-    let mut features = vec!["build-mode-prebuilt-artifacts"];
-
-    if exe.requires_jemalloc() {
-        features.push("global-allocator-jemalloc");
-        features.push("allocator-jemalloc");
-    }
-    if exe.requires_mimalloc() {
-        features.push("global-allocator-mimalloc");
-        features.push("allocator-mimalloc");
-    }
-    if exe.requires_snmalloc() {
-        features.push("global-allocator-snmalloc");
-        features.push("allocator-snmalloc");
-    }
-
-    let features = features.join(" ");
+    let features = cargo_features(exe).join(" ");
 
     if !features.is_empty() {
         args.push("--features");
@@ -353,6 +447,29 @@ pub fn build_executable_with_rust_project<'a>(
         return Err(anyhow!("{} does not exist", exe_path.display()));
     }
 
+    if target_triple.contains("linux-musl") {
+        validate_static_executable(&exe_path)?;
+    }
+
+    if let Some(ceiling) = exe.glibc_version_ceiling() {
+        if target_triple.contains("linux-gnu") {
+            validate_glibc_version_ceiling(
+                &exe_path,
+                ceiling,
+                &[
+                    artifacts_path.to_path_buf(),
+                    target_triple_base_path.join("deps"),
+                ],
+            )?;
+        }
+    }
+
+    if let Some(processor) = exe.binary_post_processor() {
+        processor
+            .process_file(logger, &exe_path)
+            .with_context(|| format!("post-processing {}", exe_path.display()))?;
+    }
+
     let exe_data =
         std::fs::read(&exe_path).with_context(|| format!("reading {}", exe_path.display()))?;
     let exe_name = exe_path.file_name().unwrap().to_string_lossy().to_string();
@@ -365,6 +482,136 @@ pub fn build_executable_with_rust_project<'a>(
     })
 }
 
+/// Ensure an ELF executable doesn't require dynamic linking.
+fn validate_static_executable(path: &Path) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let requirements = tugger_binary_analysis::find_elf_dynamic_requirements(&data)
+        .with_context(|| format!("parsing {}", path.display()))?;
+
+    if requirements.is_static() {
+        return Ok(());
+    }
+
+    let mut offenders = vec![];
+    if let Some(interpreter) = &requirements.interpreter {
+        offenders.push(format!("interpreter {}", interpreter));
+    }
+    offenders.extend(
+        requirements
+            .libraries
+            .iter()
+            .map(|lib| format!("shared library {}", lib)),
+    );
+
+    Err(anyhow!(
+        "{} is not statically linked; it requires:\n  {}\n\
+         ensure all libraries linked by extension modules are available as static libraries",
+        path.display(),
+        offenders.join("\n  ")
+    ))
+}
+
+/// Find object files and archives referencing symbols.
+///
+/// Returns a mapping of symbol name to descriptions of objects referencing it.
+/// Files in `search_paths` that aren't ELF objects or archives are ignored.
+fn find_symbol_origins(
+    symbols: &BTreeSet<String>,
+    search_paths: &[PathBuf],
+) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut res: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    for search_path in search_paths.iter().filter(|p| p.is_dir()) {
+        for entry in std::fs::read_dir(search_path)? {
+            let path = entry?.path();
+
+            if !matches!(
+                path.extension().and_then(|x| x.to_str()),
+                Some("a" | "o" | "rlib")
+            ) {
+                continue;
+            }
+
+            let data = std::fs::read(&path)?;
+            let references = match tugger_binary_analysis::find_elf_object_references(&data) {
+                Ok(references) => references,
+                Err(_) => continue,
+            };
+
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+            for reference in references {
+                let origin = match &reference.member {
+                    Some(member) => format!("{}({})", file_name, member),
+                    None => file_name.clone(),
+                };
+
+                for symbol in reference.symbols.intersection(symbols) {
+                    res.entry(symbol.clone())
+                        .or_default()
+                        .insert(origin.clone());
+                }
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+/// Ensure an ELF executable doesn't require glibc symbols newer than a version.
+///
+/// Object files and archives in `search_paths` are scanned to report what
+/// references offending symbols.
+fn validate_glibc_version_ceiling(
+    path: &Path,
+    ceiling: &str,
+    search_paths: &[PathBuf],
+) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let symbols = tugger_binary_analysis::find_elf_symbols_above_version(&data, "GLIBC", ceiling)
+        .with_context(|| format!("analyzing {}", path.display()))?;
+
+    if symbols.is_empty() {
+        return Ok(());
+    }
+
+    let origins = find_symbol_origins(
+        &symbols
+            .iter()
+            .map(|s| s.symbol.clone())
+            .collect::<BTreeSet<_>>(),
+        search_paths,
+    )?;
+
+    let lines = symbols
+        .iter()
+        .map(|symbol| {
+            let line = format!(
+                "{}@{}",
+                symbol.symbol,
+                symbol.version.as_deref().unwrap_or_default()
+            );
+
+            match origins.get(&symbol.symbol) {
+                Some(origins) => format!(
+                    "{} (referenced by {})",
+                    line,
+                    origins.iter().cloned().collect::<Vec<_>>().join(", ")
+                ),
+                None => line,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Err(anyhow!(
+        "{} requires glibc symbols newer than GLIBC_{}:\n  {}\n\
+         build on a machine with an older glibc or avoid the code referencing them",
+        path.display(),
+        ceiling,
+        lines.join("\n  ")
+    ))
+}
+
 /// Build a Python executable using a temporary Rust project.
 ///
 /// Returns the binary data constituting the built executable.
@@ -377,20 +624,61 @@ pub fn build_python_executable<'a>(
     opt_level: &str,
     release: bool,
 ) -> Result<BuiltExecutable<'a>> {
-    let cargo_exe = env
-        .ensure_rust_toolchain(logger, Some(target_triple))
-        .context("resolving Rust toolchain")?
-        .cargo_exe;
-
     let temp_dir = tempfile::Builder::new()
         .prefix("pyoxidizer")
         .tempdir()
         .context("creating temp directory")?;
 
+    let mut build = build_python_executable_in_dir(
+        env,
+        logger,
+        bin_name,
+        exe,
+        temp_dir.path(),
+        target_triple,
+        opt_level,
+        release,
+    )?;
+
+    // Blank out the path since it is in the temporary directory.
+    build.exe_path = None;
+
+    Ok(build)
+}
+
+/// Build a Python executable using a Rust project generated in a directory.
+///
+/// The project is generated in a `<bin_name>` directory of `dir`, replacing
+/// a project generated by a previous build. Build state is written to the
+/// `build` and `artifacts` directories of `dir`.
+///
+/// If the executable vendors crates, they are vendored into the project so
+/// it builds, and can later be rebuilt, without network access.
+#[allow(clippy::too_many_arguments)]
+pub fn build_python_executable_in_dir<'a>(
+    env: &Environment,
+    logger: &slog::Logger,
+    bin_name: &str,
+    exe: &'a (dyn PythonBinaryBuilder + 'a),
+    dir: &Path,
+    target_triple: &str,
+    opt_level: &str,
+    release: bool,
+) -> Result<BuiltExecutable<'a>> {
+    let cargo_exe = env
+        .ensure_rust_toolchain(logger, Some(target_triple))
+        .context("resolving Rust toolchain")?
+        .cargo_exe;
+
     // Directory needs to have name of project.
-    let project_path = temp_dir.path().join(bin_name);
-    let build_path = temp_dir.path().join("build");
-    let artifacts_path = temp_dir.path().join("artifacts");
+    let project_path = dir.join(bin_name);
+    let build_path = dir.join("build");
+    let artifacts_path = dir.join("artifacts");
+
+    if project_path.exists() {
+        std::fs::remove_dir_all(&project_path)
+            .with_context(|| format!("removing {}", project_path.display()))?;
+    }
 
     initialize_project(
         &env.pyoxidizer_source,
@@ -399,10 +687,16 @@ pub fn build_python_executable<'a>(
         None,
         &[],
         exe.windows_subsystem(),
+        exe.windows_resources(),
     )
     .context("initializing project")?;
 
-    let mut build = build_executable_with_rust_project(
+    if exe.vendor_crates() {
+        warn!(logger, "vendoring crates into {}", project_path.display());
+        vendor_project_crates(&project_path, &cargo_exe).context("vendoring crates")?;
+    }
+
+    build_executable_with_rust_project(
         env,
         logger,
         &project_path,
@@ -417,12 +711,96 @@ pub fn build_python_executable<'a>(
         // Rust project we just created.
         true,
     )
-    .context("building executable with Rust project")?;
+    .context("building executable with Rust project")
+}
 
-    // Blank out the path since it is in the temporary directory.
-    build.exe_path = None;
+/// Build the launcher executable of a portable application.
+///
+/// Returns the content of the built executable.
+pub fn build_portable_launcher(
+    env: &Environment,
+    logger: &slog::Logger,
+    launcher: &PortableLauncher,
+    target_triple: &str,
+    release: bool,
+) -> Result<Vec<u8>> {
+    let rust_environment = env
+        .ensure_rust_toolchain(logger, Some(target_triple))
+        .context("resolving Rust toolchain")?;
 
-    Ok(build)
+    let temp_dir = tempfile::Builder::new()
+        .prefix("pyoxidizer-launcher")
+        .tempdir()
+        .context("creating temp directory")?;
+
+    let project_path = temp_dir.path().join("portable-launcher");
+    let target_path = temp_dir.path().join("target");
+
+    write_portable_launcher_project(&project_path, launcher)
+        .context("writing launcher project")?;
+
+    let mut envs = std::env::vars().collect::<HashMap<_, _>>();
+    envs.insert(
+        "RUSTC".to_string(),
+        format!("{}", rust_environment.rustc_exe.display()),
+    );
+
+    // Don't require the Visual C++ Redistributable to be installed, as the
+    // machines portable applications run on may not allow installing it.
+    let mut rust_flags = vec![];
+    if target_triple.contains("-windows-msvc") {
+        rust_flags.push("-C target-feature=+crt-static".to_string());
+    }
+    if env.reproducible() {
+        rust_flags.push(format!(
+            "--remap-path-prefix={}=/pyoxidizer/launcher",
+            temp_dir.path().display()
+        ));
+    }
+    if !rust_flags.is_empty() {
+        if let Some(value) = envs.get("RUSTFLAGS") {
+            rust_flags.push(value.clone());
+        }
+        envs.insert("RUSTFLAGS".to_string(), rust_flags.join(" "));
+    }
+
+    let target_dir = target_path.display().to_string();
+    let mut args = vec![
+        "build",
+        "--target",
+        target_triple,
+        "--target-dir",
+        &target_dir,
+    ];
+    if release {
+        args.push("--release");
+    }
+
+    let output = cmd(&rust_environment.cargo_exe, &args)
+        .dir(&project_path)
+        .full_env(&envs)
+        .stderr_to_stdout()
+        .unchecked()
+        .read()
+        .context("invoking cargo command")?;
+    for line in output.lines() {
+        warn!(logger, "{}", line);
+    }
+
+    let exe_path = target_path
+        .join(target_triple)
+        .join(if release { "release" } else { "debug" })
+        .join(if target_triple.contains("pc-windows") {
+            "portable-launcher.exe"
+        } else {
+            "portable-launcher"
+        });
+
+    if !exe_path.exists() {
+        return Err(anyhow!("cargo build of launcher failed"));
+    }
+
+    std::fs::read(&exe_path).with_context(|| format!("reading {}", exe_path.display()))
 }
 
 /// Build artifacts needed by the pyembed crate.
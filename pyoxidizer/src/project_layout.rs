@@ -5,7 +5,15 @@
 //! Handle file layout of PyOxidizer projects.
 
 use {
-    crate::environment::{PyOxidizerSource, BUILD_GIT_COMMIT, PYOXIDIZER_VERSION},
+    crate::{
+        environment::{PyOxidizerSource, BUILD_GIT_COMMIT, PYOXIDIZER_VERSION},
+        py_packaging::{
+            portable::PortableLauncher,
+            windows_resources::{
+                rc_string_literal, windows_supported_os_id, WindowsManifest, WindowsResources,
+            },
+        },
+    },
     anyhow::{anyhow, Context, Result},
     handlebars::Handlebars,
     once_cell::sync::Lazy,
@@ -54,6 +62,18 @@ static HANDLEBARS: Lazy<Handlebars<'static>> = Lazy::new(|| {
             include_str!("templates/new-pyoxidizer.bzl.hbs"),
         )
         .unwrap();
+    handlebars
+        .register_template_string(
+            "portable-launcher-cargo.toml",
+            include_str!("templates/portable-launcher-cargo.toml.hbs"),
+        )
+        .unwrap();
+    handlebars
+        .register_template_string(
+            "portable-launcher-main.rs",
+            include_str!("templates/portable-launcher-main.rs.hbs"),
+        )
+        .unwrap();
 
     handlebars
 });
@@ -90,6 +110,36 @@ struct TemplateData {
     program_name: Option<String>,
     code: Option<String>,
     pip_install_simple: Vec<String>,
+    default_features: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct VersionString {
+    key: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct VersionInfoTemplateData {
+    file_version: String,
+    product_version: String,
+    strings: Vec<VersionString>,
+}
+
+#[derive(Serialize)]
+struct ManifestTemplateData {
+    execution_level: Option<&'static str>,
+    dpi_aware: Option<&'static str>,
+    dpi_awareness: Option<&'static str>,
+    long_path_aware: bool,
+    supported_os: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct ResourceScriptTemplateData {
+    program_name: String,
+    icon_filename: Option<String>,
+    version_info: Option<VersionInfoTemplateData>,
 }
 
 impl TemplateData {
@@ -105,6 +155,7 @@ impl TemplateData {
             program_name: None,
             code: None,
             pip_install_simple: Vec::new(),
+            default_features: vec!["build-mode-standalone".to_string()],
         }
     }
 }
@@ -152,6 +203,72 @@ pub fn write_new_cargo_config(project_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Render Cargo configuration pointing a build at prebuilt artifacts.
+///
+/// `artifacts_path` and `pyo3_config_path` are relative to the project.
+/// Cargo resolves them and defines them as the `PYOXIDIZER_ARTIFACT_DIR` and
+/// `PYO3_CONFIG_FILE` environment variables of build scripts.
+pub fn render_prebuilt_artifacts_cargo_config(
+    artifacts_path: &str,
+    pyo3_config_path: &str,
+) -> String {
+    format!(
+        "\n# Build with artifacts generated by `pyoxidizer generate-rust-project`.\n\
+         [env]\n\
+         PYOXIDIZER_ARTIFACT_DIR = {{ value = \"{}\", relative = true }}\n\
+         PYO3_CONFIG_FILE = {{ value = \"{}\", relative = true }}\n",
+        artifacts_path, pyo3_config_path
+    )
+}
+
+/// Render the Cargo configuration of a project with vendored crates.
+///
+/// `config` is the existing configuration and `vendor_config` the source
+/// replacement configuration printed by `cargo vendor`. Network access is
+/// disabled so builds fail rather than fetch crates that aren't vendored.
+pub fn render_vendored_cargo_config(config: &str, vendor_config: &str) -> String {
+    format!(
+        "{}\n# Crates are vendored by `cargo vendor`.\n{}\n\n[net]\noffline = true\n",
+        config,
+        vendor_config.trim()
+    )
+}
+
+/// Vendor the crates a project depends on into its `vendor` directory.
+///
+/// The project's Cargo configuration is updated to use the vendored crates,
+/// so the project can be built without network access.
+pub fn vendor_project_crates(project_path: &Path, cargo_exe: &Path) -> Result<()> {
+    let output = std::process::Command::new(cargo_exe)
+        .arg("vendor")
+        .arg("--locked")
+        .arg("--versioned-dirs")
+        .arg("vendor")
+        .current_dir(project_path)
+        .stderr(std::process::Stdio::inherit())
+        .output()
+        .context("invoking cargo vendor")?;
+
+    if !output.status.success() {
+        return Err(anyhow!("cargo vendor failed"));
+    }
+
+    let vendor_config = String::from_utf8(output.stdout).context("decoding cargo vendor output")?;
+
+    let config_path = project_path.join(".cargo").join("config");
+    let config = std::fs::read_to_string(&config_path)
+        .with_context(|| format!("reading {}", config_path.display()))?;
+
+    println!("writing {}", config_path.display());
+    std::fs::write(
+        &config_path,
+        render_vendored_cargo_config(&config, &vendor_config),
+    )
+    .with_context(|| format!("writing {}", config_path.display()))?;
+
+    Ok(())
+}
+
 /// Write a Cargo.lock file for a project path.
 ///
 /// The Cargo.lock content is under version control and is automatically
@@ -278,6 +395,30 @@ pub fn write_new_main_rs(path: &Path, windows_subsystem: &str) -> Result<()> {
     Ok(())
 }
 
+/// Write a Rust project building the launcher of a portable application.
+pub fn write_portable_launcher_project(
+    project_path: &Path,
+    launcher: &PortableLauncher,
+) -> Result<()> {
+    let src_path = project_path.join("src");
+    std::fs::create_dir_all(&src_path)
+        .with_context(|| format!("creating {}", src_path.display()))?;
+
+    let data: BTreeMap<String, String> = BTreeMap::new();
+    std::fs::write(
+        project_path.join("Cargo.toml"),
+        HANDLEBARS.render("portable-launcher-cargo.toml", &data)?,
+    )?;
+
+    let values = launcher.template_values()?;
+    std::fs::write(
+        src_path.join("main.rs"),
+        HANDLEBARS.render("portable-launcher-main.rs", &values)?,
+    )?;
+
+    Ok(())
+}
+
 /// Writes default PyOxidizer config files into a project directory.
 pub fn write_new_pyoxidizer_config_file(
     source: &PyOxidizerSource,
@@ -309,26 +450,95 @@ pub fn write_new_pyoxidizer_config_file(
     Ok(())
 }
 
+/// Render the application manifest of Windows executables.
+fn render_application_manifest(manifest: &WindowsManifest) -> Result<String> {
+    let data = ManifestTemplateData {
+        execution_level: manifest.execution_level.map(|x| x.manifest_value()),
+        dpi_aware: manifest.dpi_awareness.map(|x| x.dpi_aware()),
+        dpi_awareness: manifest.dpi_awareness.map(|x| x.dpi_awareness()),
+        long_path_aware: manifest.long_path_aware,
+        supported_os: manifest
+            .supported_os
+            .iter()
+            .map(|os| windows_supported_os_id(os))
+            .collect::<Result<Vec<_>>>()?,
+    };
+
+    Ok(HANDLEBARS.render("exe.manifest", &data)?)
+}
+
+/// Render the resource script compiled into Windows executables.
+///
+/// `icon_filename` is the name of the icon file in the project directory.
+fn render_resource_script(
+    program_name: &str,
+    windows_resources: &WindowsResources,
+    icon_filename: Option<String>,
+) -> Result<String> {
+    let format_version = |version: [u16; 4]| version.map(|x| x.to_string()).join(",");
+
+    let version_info = windows_resources.version_info(program_name)?;
+    let version_info = version_info.map(|info| VersionInfoTemplateData {
+        file_version: format_version(info.file_version),
+        product_version: format_version(info.product_version),
+        strings: info
+            .strings
+            .iter()
+            .map(|(key, value)| VersionString {
+                key: rc_string_literal(key),
+                value: rc_string_literal(value),
+            })
+            .collect(),
+    });
+
+    Ok(HANDLEBARS.render(
+        "application-manifest.rc",
+        &ResourceScriptTemplateData {
+            program_name: program_name.to_string(),
+            icon_filename,
+            version_info,
+        },
+    )?)
+}
+
 /// Write an application manifest and corresponding resource file.
 ///
-/// This is used on Windows to allow the built executable to use long paths.
+/// By default, the manifest allows the built executable to use long paths.
+/// Its settings and the icon and version information defined by the
+/// resource file come from `windows_resources`. The icon and a custom
+/// manifest are copied into the project directory.
 ///
 /// Windows 10 version 1607 and above enable long paths by default. So we
 /// might be able to remove this someday. It isn't clear if you get long
 /// paths support if using that version of the Windows SDK or if you have
 /// to be running on a modern Windows version as well.
-pub fn write_application_manifest(project_dir: &Path, program_name: &str) -> Result<()> {
-    let mut data = TemplateData::new();
-    data.program_name = Some(program_name.to_string());
-
+pub fn write_application_manifest(
+    project_dir: &Path,
+    program_name: &str,
+    windows_resources: &WindowsResources,
+) -> Result<()> {
     let manifest_path = project_dir.join(format!("{}.exe.manifest", program_name));
-    let manifest_data = HANDLEBARS.render("exe.manifest", &data)?;
+    let manifest_data = if let Some(path) = &windows_resources.manifest.path {
+        std::fs::read(path).with_context(|| format!("reading manifest {}", path.display()))?
+    } else {
+        render_application_manifest(&windows_resources.manifest)?.into_bytes()
+    };
     println!("writing {}", manifest_path.display());
     let mut fh = std::fs::File::create(&manifest_path)?;
-    fh.write_all(manifest_data.as_bytes())?;
+    fh.write_all(&manifest_data)?;
+
+    let icon_filename = if let Some(icon_path) = &windows_resources.icon_path {
+        let icon_filename = format!("{}.ico", program_name);
+        std::fs::copy(icon_path, project_dir.join(&icon_filename))
+            .with_context(|| format!("copying icon {}", icon_path.display()))?;
+
+        Some(icon_filename)
+    } else {
+        None
+    };
 
     let rc_path = project_dir.join(format!("{}-manifest.rc", program_name));
-    let rc_data = HANDLEBARS.render("application-manifest.rc", &data)?;
+    let rc_data = render_resource_script(program_name, windows_resources, icon_filename)?;
     println!("writing {}", rc_path.display());
     let mut fh = std::fs::File::create(&rc_path)?;
     fh.write_all(rc_data.as_bytes())?;
@@ -365,6 +575,39 @@ impl PyembedLocation {
 pub fn update_new_cargo_toml(path: &Path, pyembed_location: &PyembedLocation) -> Result<()> {
     let content = std::fs::read_to_string(path)?;
 
+    let content = add_pyembed_to_cargo_toml(&content, pyembed_location, &TemplateData::new())?;
+
+    std::fs::write(path, content)?;
+
+    Ok(())
+}
+
+/// Render the Cargo.toml of a new Rust project using pyembed.
+///
+/// The content matches that of a project created by `cargo init` and updated
+/// by [update_new_cargo_toml], except `default_features` are the features
+/// enabled by default.
+pub fn render_new_cargo_toml(
+    name: &str,
+    pyembed_location: &PyembedLocation,
+    default_features: &[&str],
+) -> Result<String> {
+    let content = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n",
+        name
+    );
+
+    let mut data = TemplateData::new();
+    data.default_features = default_features.iter().map(|x| x.to_string()).collect();
+
+    add_pyembed_to_cargo_toml(&content, pyembed_location, &data)
+}
+
+fn add_pyembed_to_cargo_toml(
+    content: &str,
+    pyembed_location: &PyembedLocation,
+    data: &TemplateData,
+) -> Result<String> {
     // Insert a `build = build.rs` line after the `version = *\n` line. We key off
     // version because it should always be present.
     let version_start = match content.find("version =") {
@@ -389,16 +632,13 @@ pub fn update_new_cargo_toml(path: &Path, pyembed_location: &PyembedLocation) ->
     ));
     content.push('\n');
 
-    let data = TemplateData::new();
     content.push_str(
         &HANDLEBARS
-            .render("cargo-extra.toml", &data)
+            .render("cargo-extra.toml", data)
             .context("rendering cargo-extra.toml template")?,
     );
 
-    std::fs::write(path, content)?;
-
-    Ok(())
+    Ok(content)
 }
 
 /// Initialize a new Rust project using PyOxidizer.
@@ -407,7 +647,8 @@ pub fn update_new_cargo_toml(path: &Path, pyembed_location: &PyembedLocation) ->
 /// path component.
 ///
 /// `windows_subsystem` is the value of the `windows_subsystem` compiler
-/// attribute.
+/// attribute. `windows_resources` defines resources embedded in Windows
+/// executables.
 pub fn initialize_project(
     source: &PyOxidizerSource,
     project_path: &Path,
@@ -415,6 +656,7 @@ pub fn initialize_project(
     code: Option<&str>,
     pip_install: &[&str],
     windows_subsystem: &str,
+    windows_resources: &WindowsResources,
 ) -> Result<()> {
     let status = std::process::Command::new(cargo_exe)
         .arg("init")
@@ -431,15 +673,183 @@ pub fn initialize_project(
     let name = path.iter().last().unwrap().to_str().unwrap();
     update_new_cargo_toml(&path.join("Cargo.toml"), &source.as_pyembed_location())
         .context("updating Cargo.toml")?;
-    write_new_cargo_config(&path).context("writing cargo config")?;
-    write_new_cargo_lock(&path, name, &source.as_pyembed_location())
-        .context("writing Cargo.lock")?;
-    write_new_build_rs(&path.join("build.rs"), name).context("writing build.rs")?;
-    write_new_main_rs(&path.join("src").join("main.rs"), windows_subsystem)
-        .context("writing main.rs")?;
+    write_new_project_files(source, &path, name, windows_subsystem, windows_resources)?;
     write_new_pyoxidizer_config_file(source, &path, name, code, pip_install)
         .context("writing PyOxidizer config file")?;
-    write_application_manifest(&path, name).context("writing application manifest")?;
 
     Ok(())
 }
+
+/// Write a Rust project using PyOxidizer without invoking Cargo.
+///
+/// The project builds a binary named `name` and enables `default_features`
+/// by default. Unlike [initialize_project], no PyOxidizer configuration file
+/// is written: the project is meant to be built with artifacts generated
+/// out-of-band, e.g. as part of an existing Cargo workspace or build system.
+pub fn write_rust_project(
+    source: &PyOxidizerSource,
+    project_path: &Path,
+    name: &str,
+    default_features: &[&str],
+    windows_subsystem: &str,
+    windows_resources: &WindowsResources,
+) -> Result<()> {
+    let src_path = project_path.join("src");
+    std::fs::create_dir_all(&src_path)
+        .with_context(|| format!("creating {}", src_path.display()))?;
+
+    let cargo_toml_path = project_path.join("Cargo.toml");
+    println!("writing {}", cargo_toml_path.display());
+    std::fs::write(
+        &cargo_toml_path,
+        render_new_cargo_toml(name, &source.as_pyembed_location(), default_features)?,
+    )
+    .context("writing Cargo.toml")?;
+
+    write_new_project_files(
+        source,
+        project_path,
+        name,
+        windows_subsystem,
+        windows_resources,
+    )
+}
+
+/// Write the files of a new Rust project besides Cargo.toml.
+fn write_new_project_files(
+    source: &PyOxidizerSource,
+    project_path: &Path,
+    name: &str,
+    windows_subsystem: &str,
+    windows_resources: &WindowsResources,
+) -> Result<()> {
+    write_new_cargo_config(project_path).context("writing cargo config")?;
+    write_new_cargo_lock(project_path, name, &source.as_pyembed_location())
+        .context("writing Cargo.lock")?;
+    write_new_build_rs(&project_path.join("build.rs"), name).context("writing build.rs")?;
+    write_new_main_rs(&project_path.join("src").join("main.rs"), windows_subsystem)
+        .context("writing main.rs")?;
+    write_application_manifest(project_path, name, windows_resources)
+        .context("writing application manifest")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::py_packaging::windows_resources::{WindowsDpiAwareness, WindowsExecutionLevel},
+    };
+
+    #[test]
+    fn test_render_application_manifest() -> Result<()> {
+        let mut manifest = WindowsManifest::default();
+
+        let xml = render_application_manifest(&manifest)?;
+        assert!(xml.contains("<ws2:longPathAware>true</ws2:longPathAware>"));
+        assert!(!xml.contains("requestedExecutionLevel"));
+        assert!(!xml.contains("dpiAware"));
+        assert!(!xml.contains("supportedOS"));
+
+        manifest.long_path_aware = false;
+        manifest.execution_level = Some(WindowsExecutionLevel::RequireAdministrator);
+        manifest.dpi_awareness = Some(WindowsDpiAwareness::PerMonitorV2);
+        manifest.supported_os = vec!["8.1".to_string(), "10".to_string()];
+
+        let xml = render_application_manifest(&manifest)?;
+        assert!(!xml.contains("longPathAware"));
+        assert!(xml.contains("<requestedExecutionLevel level=\"requireAdministrator\""));
+        assert!(xml.contains("<ws1:dpiAware>true/pm</ws1:dpiAware>"));
+        assert!(xml.contains("<ws2:dpiAwareness>PerMonitorV2,PerMonitor</ws2:dpiAwareness>"));
+        assert!(xml.contains("<supportedOS Id=\"{1f676c76-80e1-4239-95bb-83d0f6d0da78}\"/>"));
+        assert!(xml.contains("<supportedOS Id=\"{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}\"/>"));
+
+        manifest.supported_os = vec!["xp".to_string()];
+        assert!(render_application_manifest(&manifest).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_resource_script() -> Result<()> {
+        let mut resources = WindowsResources::default();
+
+        let rc = render_resource_script("myapp", &resources, None)?;
+        assert!(rc.contains("1 RT_MANIFEST \"myapp.exe.manifest\""));
+        assert!(!rc.contains("ICON"));
+        assert!(!rc.contains("VERSIONINFO"));
+
+        resources.file_version = Some("1.2.3".to_string());
+        resources
+            .version_strings
+            .insert("CompanyName".to_string(), "\"Example\" Inc".to_string());
+
+        let rc = render_resource_script("myapp", &resources, Some("myapp.ico".to_string()))?;
+        assert!(rc.contains("1 ICON \"myapp.ico\""));
+        assert!(rc.contains("FILEVERSION 1,2,3,0\n"));
+        assert!(rc.contains("PRODUCTVERSION 1,2,3,0\n"));
+        assert!(rc.contains("VALUE \"CompanyName\", \"\"\"Example\"\" Inc\"\n"));
+        assert!(rc.contains("VALUE \"OriginalFilename\", \"myapp.exe\"\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_new_cargo_toml() -> Result<()> {
+        let toml = render_new_cargo_toml(
+            "myapp",
+            &PyembedLocation::Version("0.19.0".to_string()),
+            &["build-mode-prebuilt-artifacts", "updater"],
+        )?;
+
+        assert!(toml.starts_with(
+            "[package]\nname = \"myapp\"\nversion = \"0.1.0\"\nbuild = \"build.rs\"\nedition = \"2021\"\n"
+        ));
+        assert!(toml.contains(
+            "[dependencies]\npyembed = { version = \"0.19.0\", default-features = false }\n"
+        ));
+        assert!(toml.contains("default = [\"build-mode-prebuilt-artifacts\", \"updater\"]\n"));
+
+        let toml = render_new_cargo_toml(
+            "myapp",
+            &PyembedLocation::Version("0.19.0".to_string()),
+            &[],
+        )?;
+        assert!(toml.contains("default = []\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_prebuilt_artifacts_cargo_config() {
+        let config = render_prebuilt_artifacts_cargo_config(
+            "artifacts",
+            "artifacts/pyo3-build-config-file.txt",
+        );
+
+        assert!(config.contains(
+            "[env]\n\
+             PYOXIDIZER_ARTIFACT_DIR = { value = \"artifacts\", relative = true }\n\
+             PYO3_CONFIG_FILE = { value = \"artifacts/pyo3-build-config-file.txt\", relative = true }\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_vendored_cargo_config() {
+        let config = render_vendored_cargo_config(
+            "[target.x86_64-unknown-linux-gnu]\nrustflags = []\n",
+            "[source.crates-io]\nreplace-with = \"vendored-sources\"\n\n\
+             [source.vendored-sources]\ndirectory = \"vendor\"\n\n",
+        );
+
+        assert_eq!(
+            config,
+            "[target.x86_64-unknown-linux-gnu]\nrustflags = []\n\n\
+             # Crates are vendored by `cargo vendor`.\n\
+             [source.crates-io]\nreplace-with = \"vendored-sources\"\n\n\
+             [source.vendored-sources]\ndirectory = \"vendor\"\n\n\
+             [net]\noffline = true\n"
+        );
+    }
+}
@@ -6,19 +6,40 @@
 
 use {
     crate::{
+        ci::{ci_platform, github_workflow, gitlab_pipeline, CiJob, CiProvider, CI_PLATFORMS},
+        container::{find_container_engine, path_owner, ContainerInvocation},
+        deps_archive::write_deps_archive,
         environment::{canonicalize_path, default_target_triple, Environment, PyOxidizerSource},
-        project_building::find_pyoxidizer_config_file_env,
-        project_layout::{initialize_project, write_new_pyoxidizer_config_file},
+        logging::OutputFormat,
+        project_building::{cargo_features, find_pyoxidizer_config_file_env},
+        project_layout::{
+            initialize_project, render_prebuilt_artifacts_cargo_config,
+            write_new_pyoxidizer_config_file, write_rust_project,
+        },
         py_packaging::{
+            devenv::materialize_devenv,
             distribution::{
                 default_distribution_location, resolve_distribution,
                 resolve_python_distribution_archive, BinaryLibpythonLinkMode, DistributionCache,
                 DistributionFlavor, PythonDistribution,
             },
+            embedded_metadata::{
+                load_exe_resources, resource_allowed, resource_data_sizes, resources_file_manifest,
+                BuildMetadata,
+            },
+            packaging_tool::SAVE_PACKAGES_DIR_ENV,
             standalone_distribution::StandaloneDistribution,
+            windows_resources::WindowsResources,
         },
         python_distributions::PYTHON_DISTRIBUTIONS,
-        starlark::eval::EvaluationContextBuilder,
+        starlark::{
+            env::PyOxidizerEnvironmentContext,
+            eval::{EvaluationContext, EvaluationContextBuilder},
+            format::{check_syntax, format_source},
+            python_executable::PythonExecutableValue,
+            testing::find_test_functions,
+        },
+        update_patch::create_update_patch,
     },
     anyhow::{anyhow, Context, Result},
     python_packaging::{
@@ -27,11 +48,15 @@ use {
         resource::PythonResource,
         wheel::WheelArchive,
     },
+    sha2::Digest,
+    slog::warn,
+    starlark::values::Value,
     std::{
-        collections::HashMap,
+        collections::{BTreeMap, BTreeSet, HashMap},
         fs::create_dir_all,
         io::{Cursor, Read},
         path::{Path, PathBuf},
+        sync::Arc,
     },
     tugger_file_manifest::{FileData, FileManifest},
     tugger_licensing::LicenseFlavor,
@@ -97,6 +122,116 @@ pub fn list_targets(env: &Environment, logger: &slog::Logger, project_path: &Pat
     Ok(())
 }
 
+/// Generate a CI pipeline building a project on multiple platforms.
+///
+/// The configuration file is evaluated for each platform's target triple to
+/// determine which targets to build there. If `targets` isn't defined, each
+/// platform builds its default target. Otherwise, each platform builds the
+/// requested targets it defines.
+///
+/// The pipeline is written to `output`, or the provider's default location in
+/// the project if not defined.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_ci(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    provider: CiProvider,
+    platforms: Option<Vec<String>>,
+    targets: Option<Vec<String>>,
+    sign: bool,
+    output: Option<&Path>,
+    force: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+
+    let platforms = if let Some(names) = platforms {
+        names
+            .iter()
+            .map(|name| ci_platform(name))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        CI_PLATFORMS.iter().collect::<Vec<_>>()
+    };
+
+    let mut jobs = vec![];
+
+    for platform in platforms {
+        let mut context = EvaluationContextBuilder::new(
+            env,
+            logger.clone(),
+            config_path.clone(),
+            platform.target_triple,
+        )
+        .release(true)
+        .resolve_targets(vec![])
+        .into_context()?;
+
+        context
+            .evaluate_file(&config_path)
+            .with_context(|| format!("evaluating config for {}", platform.target_triple))?;
+
+        let platform_targets = if let Some(targets) = &targets {
+            let names = context.target_names()?;
+            targets
+                .iter()
+                .filter(|t| names.contains(t))
+                .cloned()
+                .collect::<Vec<_>>()
+        } else {
+            context.default_target()?.into_iter().collect::<Vec<_>>()
+        };
+
+        if platform_targets.is_empty() {
+            warn!(
+                logger,
+                "no targets to build on {}; skipping platform", platform.name
+            );
+            continue;
+        }
+
+        jobs.push(CiJob {
+            platform,
+            targets: platform_targets,
+        });
+    }
+
+    if jobs.is_empty() {
+        return Err(anyhow!("no targets to build on any platform"));
+    }
+
+    let content = match provider {
+        CiProvider::GitHub => github_workflow(&jobs, sign),
+        CiProvider::GitLab => gitlab_pipeline(&jobs, sign),
+    };
+
+    let output = output
+        .map(|p| p.to_path_buf())
+        .unwrap_or_else(|| project_path.join(provider.default_path()));
+
+    if output.exists() && !force {
+        return Err(anyhow!(
+            "{} already exists; use --force to overwrite it",
+            output.display()
+        ));
+    }
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+    }
+    std::fs::write(&output, content).with_context(|| format!("writing {}", output.display()))?;
+
+    warn!(logger, "wrote CI pipeline to {}", output.display());
+
+    Ok(())
+}
+
 /// Build a PyOxidizer enabled project.
 ///
 /// This is a glorified wrapper around `cargo build`. Our goal is to get the
@@ -110,6 +245,7 @@ pub fn build(
     resolve_targets: Option<Vec<String>>,
     extra_vars: HashMap<String, Option<String>>,
     release: bool,
+    profile: &str,
     verbose: bool,
 ) -> Result<()> {
     let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
@@ -124,6 +260,7 @@ pub fn build(
         EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
             .extra_vars(extra_vars)
             .release(release)
+            .build_profile(profile)
             .verbose(verbose)
             .resolve_targets_optional(resolve_targets)
             .into_context()?;
@@ -137,6 +274,298 @@ pub fn build(
     Ok(())
 }
 
+/// Download everything building a project needs to a dependencies archive.
+///
+/// The project is built using an empty cache directory so the archive only
+/// contains what the build downloaded.
+#[allow(clippy::too_many_arguments)]
+pub fn fetch_deps(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    resolve_targets: Option<Vec<String>>,
+    extra_vars: HashMap<String, Option<String>>,
+    release: bool,
+    profile: &str,
+    verbose: bool,
+    dest_path: &Path,
+) -> Result<()> {
+    if env.offline() {
+        return Err(anyhow!("fetch-deps requires network access"));
+    }
+
+    let staging_dir = tempfile::Builder::new()
+        .prefix("pyoxidizer-fetch-deps")
+        .tempdir()?;
+
+    let mut fetch_env = env.clone();
+    fetch_env.set_cache_dir(staging_dir.path().to_path_buf());
+
+    std::env::set_var(SAVE_PACKAGES_DIR_ENV, fetch_env.wheels_dir());
+    let res = build(
+        &fetch_env,
+        logger,
+        project_path,
+        target_triple,
+        resolve_targets,
+        extra_vars,
+        release,
+        profile,
+        verbose,
+    );
+    std::env::remove_var(SAVE_PACKAGES_DIR_ENV);
+    res?;
+
+    let count = write_deps_archive(staging_dir.path(), dest_path)?;
+
+    println!(
+        "wrote {} files to {}; extract it into the cache directory of the offline machine",
+        count,
+        dest_path.display()
+    );
+
+    Ok(())
+}
+
+/// Build a project by running `pyoxidizer build` inside a container.
+///
+/// The project directory and the PyOxidizer cache are mounted into the
+/// container. Variables are forwarded to the container through environment
+/// variables, so secret values don't appear on command lines.
+#[allow(clippy::too_many_arguments)]
+pub fn build_in_container(
+    env: &Environment,
+    logger: &slog::Logger,
+    image: &str,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    resolve_targets: Option<Vec<String>>,
+    extra_vars: HashMap<String, Option<String>>,
+    release: bool,
+    profile: &str,
+    verbose: bool,
+) -> Result<()> {
+    let engine = find_container_engine(env)?;
+    let project_path = canonicalize_path(project_path)
+        .with_context(|| format!("resolving {}", project_path.display()))?;
+
+    let mut args = vec!["build".to_string()];
+
+    if verbose {
+        args.push("--verbose".to_string());
+    }
+    if let Some(triple) = target_triple {
+        args.extend(["--target-triple".to_string(), triple.to_string()]);
+    }
+    if release {
+        args.push("--release".to_string());
+    }
+    args.extend(["--profile".to_string(), profile.to_string()]);
+    if env.reproducible() {
+        args.push("--reproducible".to_string());
+    }
+    if env.offline() {
+        args.push("--offline".to_string());
+    }
+    if env.secret_embedding_allowed() {
+        args.push("--allow-secret-embedding".to_string());
+    }
+
+    let mut container_env = vec![];
+
+    // Sort for deterministic invocations.
+    let mut extra_vars = extra_vars.into_iter().collect::<Vec<_>>();
+    extra_vars.sort();
+
+    for (i, (name, value)) in extra_vars.into_iter().enumerate() {
+        let key = format!("PYOXIDIZER_CONTAINER_VAR_{}", i);
+
+        let secret = value
+            .as_ref()
+            .map(|v| env.secrets().values().contains(v))
+            .unwrap_or(false);
+
+        args.extend([
+            if secret {
+                "--secret-var-env"
+            } else {
+                "--var-env"
+            }
+            .to_string(),
+            name,
+            key.clone(),
+        ]);
+        container_env.push((key, value));
+    }
+
+    if let Some(targets) = resolve_targets {
+        args.extend(targets);
+    }
+
+    // Docker runs containers as root by default, which would leave root owned
+    // files in the project. Rootless Podman already maps root to the invoking user.
+    let user = if engine
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().contains("docker"))
+        .unwrap_or(false)
+    {
+        path_owner(&project_path)?
+    } else {
+        None
+    };
+
+    let invocation = ContainerInvocation {
+        image: image.to_string(),
+        project_path,
+        cache_dir: env.cache_dir().to_path_buf(),
+        // The running executable can only work in Linux containers.
+        pyoxidizer_exe: if cfg!(target_os = "linux") {
+            Some(std::env::current_exe().context("resolving current executable")?)
+        } else {
+            None
+        },
+        user,
+        env: container_env,
+        args,
+    };
+
+    invocation.run(logger, &engine)
+}
+
+/// Compute SHA-256 digests of all files in a directory, keyed by relative path.
+///
+/// Symlinks are recorded by their target rather than followed.
+fn directory_digests(path: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut digests = BTreeMap::new();
+
+    if !path.exists() {
+        return Ok(digests);
+    }
+
+    for entry in walkdir::WalkDir::new(path) {
+        let entry = entry?;
+
+        let data = if entry.file_type().is_symlink() {
+            std::fs::read_link(entry.path())?
+                .display()
+                .to_string()
+                .into_bytes()
+        } else if entry.file_type().is_file() {
+            std::fs::read(entry.path())
+                .with_context(|| format!("reading {}", entry.path().display()))?
+        } else {
+            continue;
+        };
+
+        digests.insert(
+            entry.path().strip_prefix(path)?.to_path_buf(),
+            sha2::Sha256::digest(&data).to_vec(),
+        );
+    }
+
+    Ok(digests)
+}
+
+/// Describe the differences between the files produced by 2 builds.
+fn compare_digests(
+    first: &BTreeMap<PathBuf, Vec<u8>>,
+    second: &BTreeMap<PathBuf, Vec<u8>>,
+) -> Vec<String> {
+    first
+        .keys()
+        .chain(second.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|path| match (first.get(path), second.get(path)) {
+            (Some(a), Some(b)) if a == b => None,
+            (Some(_), Some(_)) => Some(format!("{} differs", path.display())),
+            (Some(_), None) => Some(format!("{} only produced by first build", path.display())),
+            (None, _) => Some(format!("{} only produced by second build", path.display())),
+        })
+        .collect()
+}
+
+/// Verify that a PyOxidizer enabled project builds reproducibly.
+///
+/// Targets are built twice, each time from scratch in a separate temporary
+/// build directory, and the files they produce are compared.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_reproducible(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    resolve_targets: Option<Vec<String>>,
+    extra_vars: HashMap<String, Option<String>>,
+    release: bool,
+    profile: &str,
+    verbose: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("pyoxidizer-verify-")
+        .tempdir()
+        .context("creating temp directory")?;
+
+    let mut builds = vec![];
+
+    for name in ["first", "second"] {
+        warn!(logger, "performing {} build", name);
+
+        let mut context = EvaluationContextBuilder::new(
+            env,
+            logger.clone(),
+            config_path.clone(),
+            target_triple.clone(),
+        )
+        .extra_vars(extra_vars.clone())
+        .release(release)
+        .build_profile(profile)
+        .verbose(verbose)
+        .resolve_targets_optional(resolve_targets.clone())
+        .into_context()?;
+
+        context.evaluate_file(&config_path)?;
+        context.set_build_path(&temp_dir.path().join(name))?;
+
+        let mut digests = BTreeMap::new();
+
+        for target in context.targets_to_resolve()? {
+            let resolved = context.build_resolved_target(&target)?;
+
+            for (path, digest) in directory_digests(&resolved.output_path)? {
+                digests.insert(PathBuf::from(&target).join(path), digest);
+            }
+        }
+
+        builds.push(digests);
+    }
+
+    let differences = compare_digests(&builds[0], &builds[1]);
+
+    if differences.is_empty() {
+        warn!(logger, "{} files are reproducible", builds[0].len());
+        Ok(())
+    } else {
+        for difference in &differences {
+            warn!(logger, "{}", difference);
+        }
+
+        Err(anyhow!(
+            "build is not reproducible: {} files differ",
+            differences.len()
+        ))
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn run(
     env: &Environment,
@@ -144,6 +573,7 @@ pub fn run(
     project_path: &Path,
     target_triple: Option<&str>,
     release: bool,
+    profile: &str,
     target: Option<&str>,
     extra_vars: HashMap<String, Option<String>>,
     _extra_args: &[&str],
@@ -161,6 +591,7 @@ pub fn run(
         EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
             .extra_vars(extra_vars)
             .release(release)
+            .build_profile(profile)
             .verbose(verbose)
             .resolve_target_optional(target)
             .into_context()?;
@@ -170,6 +601,267 @@ pub fn run(
     context.run_target(target)
 }
 
+/// Run tests for a PyOxidizer configuration file.
+///
+/// The configuration file is evaluated without resolving targets. Then the
+/// file at `tests_path` is evaluated in the same environment and each of its
+/// `test_*` functions is called.
+#[allow(clippy::too_many_arguments)]
+pub fn test_config(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    tests_path: Option<&Path>,
+    target_triple: Option<&str>,
+    extra_vars: HashMap<String, Option<String>>,
+    verbose: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let tests_path = match tests_path {
+        Some(path) => path.to_path_buf(),
+        None => config_path
+            .parent()
+            .ok_or_else(|| anyhow!("unable to resolve directory of config file"))?
+            .join("pyoxidizer_test.bzl"),
+    };
+
+    let source = std::fs::read_to_string(&tests_path)
+        .with_context(|| format!("reading tests from {}", tests_path.display()))?;
+    let tests = find_test_functions(&source);
+
+    if tests.is_empty() {
+        return Err(anyhow!(
+            "no test_* functions defined in {}",
+            tests_path.display()
+        ));
+    }
+
+    let mut failures = vec![];
+
+    // Distributions are shared between tests since preparing them is slow.
+    let distribution_cache = Arc::new(DistributionCache::new(Some(
+        &env.python_distributions_dir(),
+    )));
+
+    // Each test gets a fresh environment so state doesn't leak between tests.
+    for test in &tests {
+        let mut context =
+            EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), &target_triple)
+                .distribution_cache(distribution_cache.clone())
+                .extra_vars(extra_vars.clone())
+                .verbose(verbose)
+                .resolve_targets(vec![])
+                .test_mode(true)
+                .into_context()?;
+
+        context.evaluate_file(&config_path)?;
+        context.evaluate_file(&tests_path)?;
+
+        match context.eval(&format!("{}()", test)) {
+            Ok(_) => warn!(logger, "{} ... ok", test;
+                "event" => "test", "test" => test, "result" => "ok"),
+            Err(e) => {
+                warn!(logger, "{} ... FAILED\n{}", test, e;
+                    "event" => "test", "test" => test, "result" => "failed");
+                failures.push(test.clone());
+            }
+        }
+    }
+
+    warn!(
+        logger,
+        "{} passed; {} failed",
+        tests.len() - failures.len(),
+        failures.len()
+    );
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("failed tests: {}", failures.join(", ")))
+    }
+}
+
+/// Find the value of a resolved target producing a `PythonExecutable`.
+///
+/// If `target` isn't specified, the first such target is used.
+fn resolve_executable_target(
+    context: &EvaluationContext,
+    target: Option<&str>,
+) -> Result<(String, Value)> {
+    let targets = match target {
+        Some(target) => vec![target.to_string()],
+        None => context.target_names()?,
+    };
+
+    for name in &targets {
+        if let Some(value) = context.resolved_target_value(name)? {
+            if value.get_type() == "PythonExecutable" {
+                return Ok((name.clone(), value));
+            } else if target.is_some() {
+                return Err(anyhow!(
+                    "target {} is a {}, not a PythonExecutable",
+                    name,
+                    value.get_type()
+                ));
+            }
+        }
+    }
+
+    Err(anyhow!("no resolved target produces a PythonExecutable"))
+}
+
+/// Materialize a development environment for an executable target.
+///
+/// If `target` isn't specified, the first resolved target producing a
+/// `PythonExecutable` is used. The environment is written to `devenv` in the
+/// build directory unless `dest_path` is specified.
+#[allow(clippy::too_many_arguments)]
+pub fn devenv(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    target: Option<&str>,
+    dest_path: Option<&Path>,
+    extra_vars: HashMap<String, Option<String>>,
+    verbose: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let mut context =
+        EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
+            .extra_vars(extra_vars)
+            .verbose(verbose)
+            .resolve_target_optional(target)
+            .into_context()?;
+
+    context.evaluate_file(&config_path)?;
+
+    let (name, value) = resolve_executable_target(&context, target)?;
+
+    let dest_path = match dest_path {
+        Some(path) => path.to_path_buf(),
+        None => context
+            .build_path()
+            .map_err(|e| anyhow!("{:?}", e))?
+            .join("devenv"),
+    };
+
+    warn!(logger, "materializing development environment for {}", name);
+
+    let exe = value
+        .downcast_ref::<PythonExecutableValue>()
+        .ok_or_else(|| anyhow!("target {} has incorrect type", name))?;
+    let builder = exe.inner("devenv").map_err(|e| anyhow!("{:?}", e))?;
+
+    materialize_devenv(logger, builder.as_ref(), &dest_path)?;
+
+    let activate = if cfg!(windows) {
+        dest_path.join("Scripts").join("activate.bat")
+    } else {
+        dest_path.join("bin").join("activate")
+    };
+    println!("development environment written to {}", dest_path.display());
+    println!("activate it with: {}", activate.display());
+
+    Ok(())
+}
+
+/// Run a language server for configuration files over stdin and stdout.
+pub fn lsp() -> Result<()> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    crate::lsp::run_server(&mut stdin.lock(), &mut stdout.lock())
+}
+
+/// Write an update patch transforming one build directory into another.
+pub fn diff_build(logger: &slog::Logger, old: &Path, new: &Path, out: &Path) -> Result<()> {
+    let summary = create_update_patch(old, new, out)?;
+
+    warn!(
+        logger,
+        "{} unchanged, {} patched, {} added, {} removed files",
+        summary.unchanged,
+        summary.patched,
+        summary.added,
+        summary.removed
+    );
+    warn!(
+        logger,
+        "wrote {} byte update patch to {} ({:.1}% of {} byte build)",
+        summary.patch_size,
+        out.display(),
+        summary.patch_size as f64 * 100.0 / summary.new_size.max(1) as f64,
+        summary.new_size
+    );
+
+    Ok(())
+}
+
+/// Format Starlark configuration files.
+///
+/// If `check` is true, files aren't modified and an error is returned if
+/// any file isn't formatted.
+pub fn format_files(paths: &[PathBuf], check: bool) -> Result<()> {
+    let mut unformatted = vec![];
+
+    for path in paths {
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+
+        // Refuse to touch files we can't parse, as the formatter relies on
+        // correct tokenization.
+        check_syntax(&path.display().to_string(), &source).map_err(|e| {
+            if let Some(((line, column), _)) = e.span {
+                anyhow!(
+                    "{}:{}:{}: {}",
+                    path.display(),
+                    line + 1,
+                    column + 1,
+                    e.message
+                )
+            } else {
+                anyhow!("{}: {}", path.display(), e.message)
+            }
+        })?;
+
+        let formatted = format_source(&source);
+
+        if formatted != source {
+            if check {
+                println!("{} is not formatted", path.display());
+            } else {
+                println!("formatting {}", path.display());
+                std::fs::write(path, formatted)
+                    .with_context(|| format!("writing {}", path.display()))?;
+            }
+
+            unformatted.push(path);
+        }
+    }
+
+    if check && !unformatted.is_empty() {
+        Err(anyhow!("{} file(s) need formatting", unformatted.len()))
+    } else {
+        Ok(())
+    }
+}
+
 pub fn cache_clear(env: &Environment) -> Result<()> {
     let cache_dir = env.cache_dir();
 
@@ -179,6 +871,25 @@ pub fn cache_clear(env: &Environment) -> Result<()> {
     Ok(())
 }
 
+/// Diagnose problems building for a target triple.
+pub fn doctor(env: &Environment, target_triple: &str) -> Result<()> {
+    let results = crate::doctor::run_checks(env, target_triple);
+
+    print!("{}", crate::doctor::render_report(&results));
+
+    if results
+        .iter()
+        .any(|r| r.status == crate::doctor::CheckStatus::Error)
+    {
+        Err(anyhow!(
+            "environment is unable to build for {}",
+            target_triple
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 /// Find resources given a source path.
 pub fn find_resources(
     logger: &slog::Logger,
@@ -299,6 +1010,159 @@ fn print_resource(r: &PythonResource) {
     }
 }
 
+/// Describe the kind of a packed resource.
+fn packed_resource_kind(resource: &python_packed_resources::Resource<u8>) -> &'static str {
+    if resource.is_python_extension_module {
+        "extension-module"
+    } else if resource.is_python_package {
+        "package"
+    } else if resource.is_python_module {
+        "module"
+    } else if resource.is_shared_library {
+        "shared-library"
+    } else if resource.is_utf8_filename_data {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+/// Print metadata and the resources inventory of a built binary.
+///
+/// `data` is the content of the binary at `path`. With [OutputFormat::Json],
+/// a single JSON document is printed.
+pub fn analyze(path: &Path, data: &[u8], format: OutputFormat) -> Result<()> {
+    let metadata = BuildMetadata::find_in_data(data);
+    let resources = load_exe_resources(path, data)?;
+
+    let mut totals = BTreeMap::new();
+    for resource in &resources {
+        let entry = totals
+            .entry(packed_resource_kind(resource))
+            .or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += resource_data_sizes(resource).values().sum::<usize>();
+    }
+
+    if format == OutputFormat::Json {
+        let document = serde_json::json!({
+            "path": format!("{}", path.display()),
+            "metadata": metadata,
+            "totals": totals
+                .iter()
+                .map(|(kind, (count, size))| {
+                    (kind.to_string(), serde_json::json!({"count": count, "size": size}))
+                })
+                .collect::<serde_json::Map<_, _>>(),
+            "resources": resources
+                .iter()
+                .map(|resource| {
+                    serde_json::json!({
+                        "name": resource.name,
+                        "kind": packed_resource_kind(resource),
+                        "sizes": resource_data_sizes(resource),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        });
+
+        println!("{}", serde_json::to_string(&document)?);
+
+        return Ok(());
+    }
+
+    println!("Build Metadata");
+    println!("==============");
+    println!();
+
+    if let Some(metadata) = &metadata {
+        println!("PyOxidizer version:    {}", metadata.pyoxidizer_version);
+        println!("Target triple:         {}", metadata.target_triple);
+        println!(
+            "Python:                {} {}",
+            metadata.python_implementation, metadata.python_version
+        );
+        println!(
+            "Packed resources:      {}",
+            metadata.packed_resources.join(", ")
+        );
+        println!();
+
+        println!("Configuration");
+        println!("-------------");
+        println!();
+        for (key, value) in &metadata.config {
+            println!("{}: {}", key, value);
+        }
+    } else {
+        println!("(no build metadata found; binary not built by PyOxidizer or predates metadata)");
+    }
+    println!();
+
+    println!("Resources Summary");
+    println!("=================");
+    println!();
+    println!("{} resources", resources.len());
+    for (kind, (count, size)) in totals {
+        println!("{}: {} ({} bytes)", kind, count, size);
+    }
+    println!();
+
+    println!("Resources");
+    println!("=========");
+    println!();
+    for resource in &resources {
+        println!(
+            "{} [{}] {}",
+            resource.name,
+            packed_resource_kind(resource),
+            resource_data_sizes(resource)
+                .into_iter()
+                .map(|(kind, size)| format!("{}={}", kind, size))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the in-memory data of packed resources used by a built binary to a directory.
+///
+/// Only resources selected by `allow` are written. An empty allow-list selects
+/// all resources.
+pub fn extract_resources(path: &Path, dest_path: &Path, allow: &[String]) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+
+    let extension_suffix = match BuildMetadata::find_in_data(&data) {
+        Some(metadata) if metadata.target_triple.contains("-windows-") => ".pyd",
+        _ => ".so",
+    };
+
+    let resources = load_exe_resources(path, &data)?;
+    let manifest = resources_file_manifest(&resources, allow, extension_suffix)?;
+
+    let written = manifest
+        .materialize_files(dest_path)
+        .with_context(|| format!("writing resources to {}", dest_path.display()))?;
+
+    for path in &written {
+        println!("{}", path.display());
+    }
+
+    println!(
+        "wrote {} files from {} resources to {}",
+        written.len(),
+        resources
+            .iter()
+            .filter(|r| resource_allowed(&r.name, allow))
+            .count(),
+        dest_path.display()
+    );
+
+    Ok(())
+}
+
 /// Initialize a PyOxidizer configuration file in a given directory.
 pub fn init_config_file(
     source: &PyOxidizerSource,
@@ -355,6 +1219,7 @@ pub fn init_rust_project(
         None,
         &[],
         "console",
+        &WindowsResources::default(),
     )?;
     println!();
     println!(
@@ -385,6 +1250,129 @@ for configuration changes to take effect.
     Ok(())
 }
 
+/// Write a Rust project building an executable target, without building it.
+///
+/// If `target` isn't specified, the first resolved target producing a
+/// `PythonExecutable` is used. The project is written to `dest_path` along
+/// with the artifacts it is built with, in `artifacts`, and the files to
+/// install next to the built executable, in `extra-files`.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_rust_project(
+    env: &Environment,
+    logger: &slog::Logger,
+    project_path: &Path,
+    target_triple: Option<&str>,
+    target: Option<&str>,
+    dest_path: &Path,
+    extra_vars: HashMap<String, Option<String>>,
+    release: bool,
+    verbose: bool,
+) -> Result<()> {
+    let config_path = find_pyoxidizer_config_file_env(logger, project_path).ok_or_else(|| {
+        anyhow!(
+            "unable to find PyOxidizer config file at {}",
+            project_path.display()
+        )
+    })?;
+    let target_triple = resolve_target(target_triple)?;
+
+    let mut context =
+        EvaluationContextBuilder::new(env, logger.clone(), config_path.clone(), target_triple)
+            .extra_vars(extra_vars)
+            .release(release)
+            .verbose(verbose)
+            .resolve_target_optional(target)
+            .into_context()?;
+
+    context.evaluate_file(&config_path)?;
+
+    let (name, value) = resolve_executable_target(&context, target)?;
+
+    let pyoxidizer_context_value = context
+        .pyoxidizer_context_value()
+        .map_err(|e| anyhow!("{:?}", e))?;
+    let pyoxidizer_context = pyoxidizer_context_value
+        .downcast_ref::<PyOxidizerEnvironmentContext>()
+        .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+    let exe = value
+        .downcast_ref::<PythonExecutableValue>()
+        .ok_or_else(|| anyhow!("target {} has incorrect type", name))?;
+    let builder = exe
+        .inner("generate-rust-project")
+        .map_err(|e| anyhow!("{:?}", e))?;
+
+    warn!(logger, "generating Rust project for {}", name);
+
+    create_dir_all(dest_path)
+        .with_context(|| format!("creating directory {}", dest_path.display()))?;
+    let dest_path = canonicalize_path(dest_path).context("canonicalizing destination directory")?;
+
+    let features = cargo_features(builder.as_ref());
+
+    write_rust_project(
+        &env.pyoxidizer_source,
+        &dest_path,
+        &builder.name(),
+        &features.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+        builder.windows_subsystem(),
+        builder.windows_resources(),
+    )
+    .context("writing Rust project")?;
+
+    let embedded_context = builder
+        .to_embedded_python_context(
+            logger,
+            pyoxidizer_context.env(),
+            &pyoxidizer_context.build_opt_level,
+        )
+        .context("resolving embedded context")?;
+
+    if embedded_context.shared_resources_file.is_some() {
+        return Err(anyhow!(
+            "executables sharing a packed resources file are not supported"
+        ));
+    }
+
+    let artifacts_path = dest_path.join("artifacts");
+    create_dir_all(&artifacts_path)
+        .with_context(|| format!("creating directory {}", artifacts_path.display()))?;
+    embedded_context
+        .write_files(&artifacts_path)
+        .context("writing embedded artifact files")?;
+    embedded_context
+        .extra_files
+        .materialize_files_with_replace(dest_path.join("extra-files"))
+        .context("writing extra files")?;
+
+    let pyo3_config_file = embedded_context
+        .pyo3_config_path(&artifacts_path)
+        .file_name()
+        .ok_or_else(|| anyhow!("PyO3 config path has no file name"))?
+        .to_string_lossy()
+        .to_string();
+
+    let cargo_config_path = dest_path.join(".cargo").join("config");
+    let mut cargo_config = std::fs::read_to_string(&cargo_config_path)
+        .with_context(|| format!("reading {}", cargo_config_path.display()))?;
+    cargo_config.push_str(&render_prebuilt_artifacts_cargo_config(
+        "artifacts",
+        &format!("artifacts/{}", pyo3_config_file),
+    ));
+    std::fs::write(&cargo_config_path, cargo_config)
+        .with_context(|| format!("writing {}", cargo_config_path.display()))?;
+
+    println!();
+    println!(
+        "A Rust project building {} has been written to {}",
+        name,
+        dest_path.display()
+    );
+    println!("Build it with `cargo build` or add it to an existing Cargo workspace.");
+
+    Ok(())
+}
+
 pub fn python_distribution_extract(
     download_default: bool,
     archive_path: Option<&str>,
@@ -678,3 +1666,38 @@ pub fn generate_python_embedding_artifacts(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_digests() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+
+        for name in ["a", "b"] {
+            let path = temp_dir.path().join(name);
+            create_dir_all(path.join("lib"))?;
+            std::fs::write(path.join("lib").join("same.txt"), b"same")?;
+            std::fs::write(path.join("app"), name)?;
+        }
+        std::fs::write(temp_dir.path().join("b").join("extra"), b"extra")?;
+
+        let a = directory_digests(&temp_dir.path().join("a"))?;
+        let b = directory_digests(&temp_dir.path().join("b"))?;
+        assert_eq!(a.len(), 2);
+
+        assert!(compare_digests(&a, &a).is_empty());
+        assert_eq!(
+            compare_digests(&a, &b),
+            vec![
+                "app differs".to_string(),
+                "extra only produced by second build".to_string()
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -9,7 +9,12 @@ Defining and manipulating binaries embedding Python.
 use {
     crate::{
         environment::Environment,
-        py_packaging::{distribution::AppleSdkInfo, embedding::EmbeddedPythonContext},
+        py_packaging::{
+            cargo_options::CargoBuildOptions, conda::CondaPayload, distribution::AppleSdkInfo,
+            embedding::EmbeddedPythonContext, hooks::PackagingHook,
+            packaging_tool::PipIndexSettings, post_processing::BinaryPostProcessor,
+            windows_resources::WindowsResources,
+        },
     },
     anyhow::Result,
     python_packaging::{
@@ -22,7 +27,11 @@ use {
             AddResourceAction, PrePackagedResource, PythonResourceAddCollectionContext,
         },
     },
-    std::{collections::HashMap, path::Path, sync::Arc},
+    std::{
+        collections::{BTreeMap, BTreeSet, HashMap},
+        path::Path,
+        sync::Arc,
+    },
     tugger_file_manifest::File,
     tugger_windows::VcRedistributablePlatform,
 };
@@ -60,6 +69,14 @@ pub enum PackedResourcesLoadMode {
     /// The configuration will reference the file via a relative path using
     /// `$ORIGIN` expansion. Memory mapped I/O will be used to read the file.
     BinaryRelativePathMemoryMapped(String),
+
+    /// Resources data will be serialized to a file shared by multiple binaries.
+    ///
+    /// Like `BinaryRelativePathMemoryMapped` except the path may reference
+    /// parent directories of the built binary. The file isn't part of the
+    /// binary's extra files: whatever installs the binary is responsible for
+    /// writing it once for all binaries referencing it.
+    SharedPathMemoryMapped(String),
 }
 
 impl ToString for PackedResourcesLoadMode {
@@ -70,6 +87,7 @@ impl ToString for PackedResourcesLoadMode {
             Self::BinaryRelativePathMemoryMapped(path) => {
                 format!("binary-relative-memory-mapped:{}", path)
             }
+            Self::SharedPathMemoryMapped(path) => format!("shared-memory-mapped:{}", path),
         }
     }
 }
@@ -98,7 +116,10 @@ impl TryFrom<&str> for PackedResourcesLoadMode {
                     "binary-relative-memory-mapped" => {
                         Ok(Self::BinaryRelativePathMemoryMapped(value.to_string()))
                     }
-                    _ => Err(format!("{} is not a valid prefix; must be 'embedded' or 'binary-relative-memory-mapped'", prefix))
+                    "shared-memory-mapped" => {
+                        Ok(Self::SharedPathMemoryMapped(value.to_string()))
+                    }
+                    _ => Err(format!("{} is not a valid prefix; must be 'embedded', 'binary-relative-memory-mapped', or 'shared-memory-mapped'", prefix))
                 }
             }
         }
@@ -144,6 +165,47 @@ impl TryFrom<&str> for WindowsRuntimeDllsMode {
     }
 }
 
+/// How in-memory modules of a package referencing `__file__` are handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DunderFilePolicy {
+    /// Import the modules from memory without `__file__` and warn about them.
+    Warn,
+
+    /// Extract the package's data files at run-time and give its modules a
+    /// synthetic `__file__` in the directory they are extracted to.
+    Extract,
+
+    /// Fail the build, naming the modules referencing `__file__`.
+    Error,
+}
+
+impl ToString for DunderFilePolicy {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Warn => "warn",
+            Self::Extract => "extract",
+            Self::Error => "error",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<&str> for DunderFilePolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, <Self as TryFrom<&str>>::Error> {
+        match value {
+            "warn" => Ok(Self::Warn),
+            "extract" => Ok(Self::Extract),
+            "error" => Ok(DunderFilePolicy::Error),
+            _ => Err(format!(
+                "{} is not a valid policy; must be 'warn', 'extract', or 'error'",
+                value
+            )),
+        }
+    }
+}
+
 /// A callable that can influence PythonResourceAddCollectionContext.
 pub type ResourceAddCollectionContextCallback<'a> = Box<
     dyn Fn(
@@ -166,9 +228,20 @@ pub trait PythonBinaryBuilder {
     /// Clone self into a Box'ed trait object.
     fn clone_trait(&self) -> Arc<dyn PythonBinaryBuilder>;
 
+    /// Clone self into a new builder which can be modified independently.
+    fn clone_box(&self) -> Box<dyn PythonBinaryBuilder>;
+
     /// The name of the binary.
     fn name(&self) -> String;
 
+    /// Set the name of the binary.
+    fn set_name(&mut self, name: &str);
+
+    /// Set Python code the interpreter runs when the binary starts.
+    ///
+    /// This replaces any module or file the interpreter is configured to run.
+    fn set_run_command(&mut self, code: &str);
+
     /// How the binary will link against libpython.
     fn libpython_link_mode(&self) -> LibpythonLinkMode;
 
@@ -224,6 +297,105 @@ pub trait PythonBinaryBuilder {
     /// Set the value of the `windows_subsystem` Rust attribute for generated Rust projects.
     fn set_windows_subsystem(&mut self, value: &str) -> Result<()>;
 
+    /// Resources to embed in Windows executables.
+    fn windows_resources(&self) -> &WindowsResources;
+
+    /// Set resources to embed in Windows executables.
+    fn set_windows_resources(&mut self, value: WindowsResources);
+
+    /// Operations to perform on the binary after it is built.
+    fn binary_post_processor(&self) -> Option<&BinaryPostProcessor>;
+
+    /// Set operations to perform on the binary after it is built.
+    fn set_binary_post_processor(&mut self, value: Option<BinaryPostProcessor>);
+
+    /// Filesystem relative path that packages referencing `__file__` are moved to.
+    ///
+    /// If `None`, these packages are left in place.
+    fn dunder_file_packages_path(&self) -> &Option<String>;
+
+    /// Set the filesystem relative path that packages referencing `__file__` are moved to.
+    fn set_dunder_file_packages_path(&mut self, value: Option<String>);
+
+    /// Policies for in-memory modules referencing `__file__`, keyed by package name.
+    ///
+    /// Packages without a policy use [DunderFilePolicy::Warn].
+    fn dunder_file_policies(&self) -> &BTreeMap<String, DunderFilePolicy>;
+
+    /// Set the policy for in-memory modules of a package referencing `__file__`.
+    fn set_dunder_file_policy(&mut self, package: String, policy: DunderFilePolicy);
+
+    /// Modules imported in ways that can't be seen, which must be packaged.
+    fn hidden_imports(&self) -> &BTreeSet<String>;
+
+    /// Declare modules imported in ways that can't be seen.
+    ///
+    /// Missing modules are added from the Python distribution when the binary
+    /// is built.
+    fn add_hidden_imports(&mut self, names: Vec<String>);
+
+    /// Key/value metadata embedded in the binary, readable at run-time.
+    fn runtime_metadata(&self) -> &BTreeMap<String, String>;
+
+    /// Set values of the metadata embedded in the binary.
+    ///
+    /// Existing values of other keys are kept.
+    fn set_runtime_metadata(&mut self, values: BTreeMap<String, String>);
+
+    /// Version of the built-in packaging hooks to apply.
+    ///
+    /// Built-in hooks introduced after this version aren't applied.
+    fn packaging_hooks_version(&self) -> u32;
+
+    /// Set the version of the built-in packaging hooks to apply.
+    fn set_packaging_hooks_version(&mut self, version: u32);
+
+    /// Packaging hooks to apply, by package name.
+    ///
+    /// Hooks added via [Self::add_packaging_hook] replace built-in hooks for
+    /// the same package.
+    fn packaging_hooks(&self) -> BTreeMap<String, PackagingHook>;
+
+    /// Add a packaging hook, replacing any existing hook for the same package.
+    fn add_packaging_hook(&mut self, hook: PackagingHook);
+
+    /// Newest glibc version the built executable may require symbols from.
+    ///
+    /// e.g. `2.17`. Only applies to executables targeting glibc.
+    fn glibc_version_ceiling(&self) -> &Option<String>;
+
+    /// Set the newest glibc version the built executable may require symbols from.
+    fn set_glibc_version_ceiling(&mut self, value: Option<String>);
+
+    /// Whether to write a size report next to the built executable.
+    fn write_size_report(&self) -> bool;
+
+    /// Set whether to write a size report next to the built executable.
+    fn set_write_size_report(&mut self, value: bool);
+
+    /// Options controlling the `cargo build` of the Rust project building the executable.
+    fn cargo_build_options(&self) -> &CargoBuildOptions;
+
+    /// Set options controlling the `cargo build` of the Rust project building the executable.
+    fn set_cargo_build_options(&mut self, value: CargoBuildOptions);
+
+    /// Whether crates of the Rust project building the executable are vendored.
+    ///
+    /// Vendored crates are copied into the project, which is built offline.
+    fn vendor_crates(&self) -> bool;
+
+    /// Set whether crates of the Rust project building the executable are vendored.
+    fn set_vendor_crates(&mut self, value: bool);
+
+    /// Whether files installed next to the executable are embedded in it.
+    ///
+    /// Embedded files are extracted to a per-user cache directory on first
+    /// run.
+    fn self_extracting(&self) -> bool;
+
+    /// Set whether files installed next to the executable are embedded in it.
+    fn set_self_extracting(&mut self, value: bool);
+
     /// How packed Python resources will be loaded by the binary.
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode;
 
@@ -257,8 +429,36 @@ pub trait PythonBinaryBuilder {
         logger: &slog::Logger,
         verbose: bool,
         args: &[String],
+        extra_envs: &HashMap<String, String>,
+    ) -> Result<Vec<PythonResource>>;
+
+    /// Resolves and downloads wheels for the target distribution without running pip.
+    ///
+    /// `args` holds requirements as given to `pip download`. Downloaded wheels
+    /// are saved in `wheels_dir`.
+    ///
+    /// Returns resources discovered from the downloaded wheels.
+    fn resolve_wheels(
+        &mut self,
+        logger: &slog::Logger,
+        args: &[String],
+        index_settings: &PipIndexSettings,
+        wheels_dir: &Path,
     ) -> Result<Vec<PythonResource>>;
 
+    /// Obtain resources from conda packages.
+    ///
+    /// `specs` are conda package specifications resolved against `channels`,
+    /// which are channel names or URLs. Channel metadata and packages are
+    /// cached in `cache_dir`.
+    fn conda_packages(
+        &mut self,
+        logger: &slog::Logger,
+        specs: &[String],
+        channels: &[String],
+        cache_dir: &Path,
+    ) -> Result<CondaPayload>;
+
     /// Runs `pip install` using the binary builder's settings.
     ///
     /// Returns resources discovered as part of performing an install.
@@ -285,6 +485,12 @@ pub trait PythonBinaryBuilder {
         path: &Path,
     ) -> Result<Vec<PythonResource>>;
 
+    /// Read resources of the `pip` package bundled with the Python distribution.
+    ///
+    /// `pip` is read from the wheel bundled with `ensurepip`, so no network
+    /// access is required.
+    fn read_bundled_pip(&mut self, logger: &slog::Logger) -> Result<Vec<PythonResource>>;
+
     /// Runs `python setup.py install` using the binary builder's settings.
     ///
     /// Returns resources discovered as part of performing an install.
@@ -394,6 +600,30 @@ pub trait PythonBinaryBuilder {
     /// Whether the binary requires the Snmalloc library.
     fn requires_snmalloc(&self) -> bool;
 
+    /// Whether the binary requires crash reporting support.
+    fn requires_crash_reporting(&self) -> bool;
+
+    /// Whether the binary requires self-update support.
+    fn requires_updater(&self) -> bool;
+
+    /// Whether the binary requires daemonization support.
+    fn requires_daemon(&self) -> bool;
+
+    /// Whether the binary requires single instance enforcement support.
+    fn requires_single_instance(&self) -> bool;
+
+    /// Whether the binary requires splash screen support.
+    fn requires_splash_screen(&self) -> bool;
+
+    /// Whether the binary requires standard stream capture support.
+    fn requires_output_capture(&self) -> bool;
+
+    /// Whether the binary requires forwarding of Python log records to `tracing`.
+    fn requires_logging_bridge(&self) -> bool;
+
+    /// Whether the binary requires support for runtime configuration files.
+    fn requires_runtime_config(&self) -> bool;
+
     /// Obtain an `EmbeddedPythonContext` instance from this one.
     fn to_embedded_python_context(
         &self,
@@ -422,6 +652,10 @@ mod tests {
                 .to_string(),
             "binary-relative-memory-mapped:relative-resources".to_string()
         );
+        assert_eq!(
+            PackedResourcesLoadMode::SharedPathMemoryMapped("../lib/resources".into()).to_string(),
+            "shared-memory-mapped:../lib/resources".to_string()
+        );
     }
 
     #[test]
@@ -438,6 +672,10 @@ mod tests {
             PackedResourcesLoadMode::try_from("binary-relative-memory-mapped:relative").unwrap(),
             PackedResourcesLoadMode::BinaryRelativePathMemoryMapped("relative".into())
         );
+        assert_eq!(
+            PackedResourcesLoadMode::try_from("shared-memory-mapped:../lib/resources").unwrap(),
+            PackedResourcesLoadMode::SharedPathMemoryMapped("../lib/resources".into())
+        );
 
         Ok(())
     }
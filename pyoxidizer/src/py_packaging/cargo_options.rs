@@ -0,0 +1,208 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Options controlling the Cargo build of generated Rust projects.
+
+Options are applied through the command line and environment of the
+`cargo build` invocation, so generated projects don't need to be edited.
+Cargo reads overrides of profile settings from
+`CARGO_PROFILE_<profile>_<setting>` variables and the linker of a target
+triple from `CARGO_TARGET_<triple>_LINKER`.
+*/
+
+use std::collections::BTreeMap;
+
+/// Link time optimization performed when building.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CargoLto {
+    /// No link time optimization, not even across codegen units of a crate.
+    Off,
+
+    /// Fast link time optimization across all crates.
+    Thin,
+
+    /// Full link time optimization across all crates.
+    Fat,
+}
+
+impl ToString for CargoLto {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Off => "off",
+            Self::Thin => "thin",
+            Self::Fat => "fat",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<&str> for CargoLto {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "off" => Ok(Self::Off),
+            "thin" => Ok(Self::Thin),
+            "fat" => Ok(Self::Fat),
+            _ => Err(format!(
+                "{} is not a valid LTO mode; must be 'off', 'thin', or 'fat'",
+                value
+            )),
+        }
+    }
+}
+
+/// What happens when Rust code panics.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CargoPanic {
+    /// Unwind the stack.
+    Unwind,
+
+    /// Abort the process.
+    Abort,
+}
+
+impl ToString for CargoPanic {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Unwind => "unwind",
+            Self::Abort => "abort",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<&str> for CargoPanic {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "unwind" => Ok(Self::Unwind),
+            "abort" => Ok(Self::Abort),
+            _ => Err(format!(
+                "{} is not a valid panic strategy; must be 'unwind' or 'abort'",
+                value
+            )),
+        }
+    }
+}
+
+/// Options controlling the `cargo build` of a generated Rust project.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CargoBuildOptions {
+    /// Features of the project to enable in addition to those PyOxidizer enables.
+    pub features: Vec<String>,
+
+    /// Link time optimization of the built profile.
+    pub lto: Option<CargoLto>,
+
+    /// Number of codegen units of the built profile.
+    pub codegen_units: Option<u32>,
+
+    /// Panic strategy of the built profile.
+    pub panic: Option<CargoPanic>,
+
+    /// Flags to pass to `rustc` when compiling for the target triple.
+    pub rustflags: Vec<String>,
+
+    /// Linker to use for the target triple.
+    pub linker: Option<String>,
+}
+
+impl CargoBuildOptions {
+    /// Environment variables applying profile settings and the linker.
+    ///
+    /// `release` selects the profile being built (`release` or `dev`).
+    /// Features and `rustc` flags aren't applied by environment variables.
+    pub fn environment_variables(
+        &self,
+        target_triple: &str,
+        release: bool,
+    ) -> BTreeMap<String, String> {
+        let profile = if release { "RELEASE" } else { "DEV" };
+
+        let mut envs = BTreeMap::new();
+
+        if let Some(lto) = &self.lto {
+            envs.insert(format!("CARGO_PROFILE_{}_LTO", profile), lto.to_string());
+        }
+        if let Some(codegen_units) = &self.codegen_units {
+            envs.insert(
+                format!("CARGO_PROFILE_{}_CODEGEN_UNITS", profile),
+                codegen_units.to_string(),
+            );
+        }
+        if let Some(panic) = &self.panic {
+            envs.insert(
+                format!("CARGO_PROFILE_{}_PANIC", profile),
+                panic.to_string(),
+            );
+        }
+        if let Some(linker) = &self.linker {
+            envs.insert(
+                format!(
+                    "CARGO_TARGET_{}_LINKER",
+                    target_triple.to_uppercase().replace(&['-', '.'][..], "_")
+                ),
+                linker.clone(),
+            );
+        }
+
+        envs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(CargoLto::try_from("thin"), Ok(CargoLto::Thin));
+        assert!(CargoLto::try_from("true").is_err());
+        assert_eq!(CargoPanic::try_from("abort"), Ok(CargoPanic::Abort));
+        assert!(CargoPanic::try_from("exit").is_err());
+    }
+
+    #[test]
+    fn test_environment_variables() {
+        let mut options = CargoBuildOptions::default();
+        assert!(options
+            .environment_variables("x86_64-unknown-linux-gnu", true)
+            .is_empty());
+
+        options.lto = Some(CargoLto::Fat);
+        options.codegen_units = Some(1);
+        options.panic = Some(CargoPanic::Abort);
+        options.linker = Some("clang".to_string());
+
+        let envs = options.environment_variables("x86_64-unknown-linux-gnu", true);
+        assert_eq!(
+            envs.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    "CARGO_PROFILE_RELEASE_CODEGEN_UNITS".to_string(),
+                    "1".to_string()
+                ),
+                ("CARGO_PROFILE_RELEASE_LTO".to_string(), "fat".to_string()),
+                (
+                    "CARGO_PROFILE_RELEASE_PANIC".to_string(),
+                    "abort".to_string()
+                ),
+                (
+                    "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_LINKER".to_string(),
+                    "clang".to_string()
+                ),
+            ]
+        );
+
+        let envs = options.environment_variables("thumbv7em-none-eabi.custom", false);
+        assert_eq!(
+            envs.get("CARGO_PROFILE_DEV_LTO").map(|x| x.as_str()),
+            Some("fat")
+        );
+        assert!(envs.contains_key("CARGO_TARGET_THUMBV7EM_NONE_EABI_CUSTOM_LINKER"));
+    }
+}
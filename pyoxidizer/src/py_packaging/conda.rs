@@ -0,0 +1,1092 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Obtain Python resources from conda packages.
+
+Packages are selected from channel repository metadata for the conda
+platform (`subdir`) of the target distribution, downloaded, and the Python
+packages they install into `site-packages` are collected as resources.
+Shared libraries packages install are collected separately so they can be
+installed next to the executable.
+
+Dependencies are resolved greedily: each package is pinned to the newest
+build satisfying the requirements seen so far. Packages the Python
+distribution or the operating system provide are not resolved.
+*/
+
+use {
+    super::{distribution::PythonDistribution, packaging_tool::find_resources},
+    anyhow::{anyhow, Context, Result},
+    python_packaging::{policy::PythonPackagingPolicy, resource::PythonResource, version::Version},
+    serde::Deserialize,
+    sha2::Digest,
+    slog::warn,
+    std::{
+        cmp::Ordering,
+        collections::{BTreeMap, HashMap, VecDeque},
+        io::Read,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+    tugger_file_manifest::{File, FileEntry},
+    url::Url,
+};
+
+/// Channel used when no channels are configured.
+pub const DEFAULT_CHANNEL: &str = "conda-forge";
+
+/// Base URL of channels given by name.
+const CHANNEL_BASE_URL: &str = "https://conda.anaconda.org/";
+
+/// Packages provided by the Python distribution or the operating system.
+///
+/// Names starting with `__` are virtual packages and are always skipped.
+const PROVIDED_PACKAGES: &[&str] = &[
+    "_libgcc_mutex",
+    "_openmp_mutex",
+    "libgcc",
+    "libgcc-ng",
+    "libgomp",
+    "libstdcxx-ng",
+    "python",
+    "python_abi",
+    "ucrt",
+    "vc",
+    "vc14_runtime",
+    "vs2015_runtime",
+];
+
+/// Resolve the URL of a channel given by name or URL.
+pub fn channel_url(channel: &str) -> String {
+    let url = if channel.contains("://") {
+        channel.to_string()
+    } else {
+        format!("{}{}", CHANNEL_BASE_URL, channel)
+    };
+
+    url.trim_end_matches('/').to_string()
+}
+
+/// Resolve the conda platform directory for a Rust target triple.
+pub fn conda_subdir(target_triple: &str) -> Result<&'static str> {
+    Ok(match target_triple {
+        "x86_64-unknown-linux-gnu" => "linux-64",
+        "aarch64-unknown-linux-gnu" => "linux-aarch64",
+        "i686-unknown-linux-gnu" => "linux-32",
+        "x86_64-pc-windows-msvc" => "win-64",
+        "i686-pc-windows-msvc" => "win-32",
+        "x86_64-apple-darwin" => "osx-64",
+        "aarch64-apple-darwin" => "osx-arm64",
+        _ => {
+            return Err(anyhow!(
+                "conda packages are not available for target {}",
+                target_triple
+            ))
+        }
+    })
+}
+
+/// A package described by channel repository metadata.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct PackageRecord {
+    pub name: String,
+    pub version: String,
+    pub build: String,
+    #[serde(default)]
+    pub build_number: u64,
+    #[serde(default)]
+    pub depends: Vec<String>,
+    pub sha256: Option<String>,
+
+    /// Filename of the package. Set from the key of the record.
+    #[serde(skip)]
+    pub filename: String,
+
+    /// URL of the package. Set when the record is loaded.
+    #[serde(skip)]
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct RepoData {
+    #[serde(default)]
+    packages: HashMap<String, PackageRecord>,
+    #[serde(default, rename = "packages.conda")]
+    packages_conda: HashMap<String, PackageRecord>,
+}
+
+/// Parse a `repodata.json` document.
+///
+/// `subdir_url` is the URL of the directory holding the document.
+fn parse_repodata(data: &[u8], subdir_url: &str) -> Result<Vec<PackageRecord>> {
+    let repodata: RepoData = serde_json::from_slice(data).context("parsing repodata")?;
+
+    let mut records = repodata
+        .packages
+        .into_iter()
+        .chain(repodata.packages_conda)
+        .map(|(filename, mut record)| {
+            record.url = format!("{}/{}", subdir_url, filename);
+            record.filename = filename;
+            record
+        })
+        .collect::<Vec<_>>();
+    records.sort_by(|a, b| a.filename.cmp(&b.filename));
+
+    Ok(records)
+}
+
+/// Compare conda version strings.
+///
+/// Versions are compared as PEP 440 versions when possible. Otherwise
+/// components separated by `.`, `-` and `_` are compared numerically if both
+/// are numbers and as strings otherwise.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    if let (Ok(a), Ok(b)) = (Version::from_str(a), Version::from_str(b)) {
+        return a.cmp(&b);
+    }
+
+    let split = |s: &str| {
+        s.split(['.', '-', '_'])
+            .map(|c| c.to_lowercase())
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (split(a), split(b));
+
+    for i in 0..a.len().max(b.len()) {
+        let ordering = match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                _ => x.cmp(y),
+            },
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Match a string against a pattern where `*` matches any characters.
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == s,
+        Some((prefix, rest)) => {
+            if !s.starts_with(prefix) {
+                return false;
+            }
+            let s = &s[prefix.len()..];
+
+            (0..=s.len())
+                .filter(|i| s.is_char_boundary(*i))
+                .any(|i| glob_matches(rest, &s[i..]))
+        }
+    }
+}
+
+/// Whether a version equals a version or starts with it followed by `.`.
+fn prefix_matches(prefix: &str, version: &str) -> bool {
+    let prefix = prefix.trim_end_matches('.');
+
+    version == prefix
+        || version
+            .strip_prefix(prefix)
+            .map(|rest| rest.starts_with('.'))
+            .unwrap_or(false)
+        || compare_versions(prefix, version) == Ordering::Equal
+}
+
+/// Whether a version satisfies a conda version specification.
+///
+/// e.g. `>=1.20,<2`, `1.21.*`, `=1.21` or `1.2|1.3`.
+pub fn version_matches(spec: &str, version: &str) -> bool {
+    spec.split('|').any(|alternative| {
+        alternative.split(',').all(|constraint| {
+            let constraint = constraint.trim();
+
+            if constraint.is_empty() || constraint == "*" {
+                return true;
+            }
+
+            for (op, ordering_ok) in [
+                (">=", &[Ordering::Greater, Ordering::Equal][..]),
+                ("<=", &[Ordering::Less, Ordering::Equal][..]),
+                (">", &[Ordering::Greater][..]),
+                ("<", &[Ordering::Less][..]),
+            ] {
+                if let Some(other) = constraint.strip_prefix(op) {
+                    return ordering_ok.contains(&compare_versions(version, other.trim()));
+                }
+            }
+
+            if let Some(other) = constraint.strip_prefix("!=") {
+                return !version_matches(other, version);
+            }
+            if let Some(other) = constraint.strip_prefix("==") {
+                return version_matches(other, version);
+            }
+            if let Some(other) = constraint.strip_prefix("~=") {
+                let other = other.trim();
+                let prefix = other.rsplit_once('.').map(|(p, _)| p).unwrap_or(other);
+                return compare_versions(version, other) != Ordering::Less
+                    && prefix_matches(prefix, version);
+            }
+            if let Some(other) = constraint.strip_prefix('=') {
+                return prefix_matches(other.trim().trim_end_matches('*'), version);
+            }
+
+            if let Some(prefix) = constraint.strip_suffix(".*") {
+                prefix_matches(prefix, version)
+            } else if constraint.contains('*') {
+                glob_matches(constraint, version)
+            } else {
+                compare_versions(constraint, version) == Ordering::Equal
+            }
+        })
+    })
+}
+
+/// A package requirement. e.g. `numpy >=1.20,<2 py39*` or `numpy=1.21`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MatchSpec {
+    pub name: String,
+
+    /// Version specification.
+    pub version: Option<String>,
+
+    /// Pattern matched against build strings.
+    pub build: Option<String>,
+}
+
+impl FromStr for MatchSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        // Strip channel and subdir prefixes. e.g. `conda-forge::numpy`.
+        let s = s.rsplit_once("::").map(|(_, spec)| spec).unwrap_or(s);
+
+        let name_end = s
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(s.len());
+        let name = s[..name_end].to_lowercase();
+        if name.is_empty() {
+            return Err(anyhow!("invalid conda package specification: {}", s));
+        }
+
+        let rest = s[name_end..].trim();
+
+        let (version, build) = if rest.is_empty() {
+            (None, None)
+        } else if rest.starts_with('=') && !rest.starts_with("==") {
+            // `name=version[=build]`. A version without a build is a prefix.
+            let mut parts = rest[1..].splitn(2, '=');
+            let version = parts.next().unwrap_or_default().to_string();
+
+            match parts.next() {
+                Some(build) => (Some(version), Some(build.to_string())),
+                None => (Some(format!("={}", version)), None),
+            }
+        } else {
+            let mut parts = rest.split_whitespace();
+            (
+                parts.next().map(|s| s.to_string()),
+                parts.next().map(|s| s.to_string()),
+            )
+        };
+
+        Ok(Self {
+            name,
+            version,
+            build,
+        })
+    }
+}
+
+impl MatchSpec {
+    /// Whether a package satisfies this specification.
+    pub fn matches(&self, record: &PackageRecord) -> bool {
+        record.name == self.name
+            && self
+                .version
+                .as_ref()
+                .map(|spec| version_matches(spec, &record.version))
+                .unwrap_or(true)
+            && self
+                .build
+                .as_ref()
+                .map(|pattern| glob_matches(pattern, &record.build))
+                .unwrap_or(true)
+    }
+}
+
+/// Whether a package can be used with a Python version.
+///
+/// Packages constrain the Python they're built for through requirements of
+/// the `python` and `python_abi` packages.
+fn python_compatible(record: &PackageRecord, python_version: &str) -> bool {
+    let major_minor = python_version
+        .splitn(3, '.')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(".");
+
+    record.depends.iter().all(|dependency| {
+        let spec = match MatchSpec::from_str(dependency) {
+            Ok(spec) => spec,
+            Err(_) => return true,
+        };
+
+        let version = match spec.name.as_str() {
+            "python" => python_version,
+            "python_abi" => &major_minor,
+            _ => return true,
+        };
+
+        spec.version
+            .as_ref()
+            .map(|spec| version_matches(spec, version))
+            .unwrap_or(true)
+    })
+}
+
+/// A source of conda package metadata and content.
+pub trait CondaChannels {
+    /// Records of packages with a name.
+    fn records(&self, name: &str) -> Result<Vec<PackageRecord>>;
+
+    /// Obtain a local path holding the content of a package.
+    fn fetch(&self, logger: &slog::Logger, record: &PackageRecord) -> Result<PathBuf>;
+}
+
+/// Read a URL, which may be a `file://` URL.
+fn read_url(url: &str) -> Result<Vec<u8>> {
+    let parsed = Url::parse(url).with_context(|| format!("parsing URL {}", url))?;
+
+    if parsed.scheme() == "file" {
+        let path = parsed
+            .to_file_path()
+            .map_err(|_| anyhow!("invalid file URL: {}", url))?;
+        return std::fs::read(&path).with_context(|| format!("reading {}", path.display()));
+    }
+
+    tugger_common::http::fetch_url(url)
+}
+
+/// Conda channels accessed over HTTP or the filesystem, with a local cache.
+///
+/// `current_repodata.json`, which only describes the newest versions of
+/// packages, is consulted first. The complete `repodata.json` is only
+/// fetched if it doesn't satisfy a requirement.
+pub struct RemoteChannels {
+    channels: Vec<String>,
+    subdir: String,
+    cache_dir: PathBuf,
+    full: bool,
+    records: std::cell::RefCell<Option<Vec<PackageRecord>>>,
+}
+
+impl RemoteChannels {
+    /// Construct an instance for channels and a conda platform.
+    ///
+    /// Metadata and packages are cached in `cache_dir`.
+    pub fn new(channels: &[String], subdir: &str, cache_dir: &Path) -> Self {
+        Self {
+            channels: channels.iter().map(|c| channel_url(c)).collect(),
+            subdir: subdir.to_string(),
+            cache_dir: cache_dir.to_path_buf(),
+            full: false,
+            records: std::cell::RefCell::new(None),
+        }
+    }
+
+    /// An instance consulting complete repository metadata.
+    pub fn with_full_repodata(&self) -> Self {
+        Self {
+            channels: self.channels.clone(),
+            subdir: self.subdir.clone(),
+            cache_dir: self.cache_dir.clone(),
+            full: true,
+            records: std::cell::RefCell::new(None),
+        }
+    }
+
+    /// Load repository metadata for a channel directory.
+    ///
+    /// Cached metadata is used if network access is disabled.
+    fn load_repodata(&self, channel: &str, subdir: &str) -> Result<Vec<PackageRecord>> {
+        let filename = if self.full {
+            "repodata.json"
+        } else {
+            "current_repodata.json"
+        };
+
+        let subdir_url = format!("{}/{}", channel, subdir);
+        let url = format!("{}/{}", subdir_url, filename);
+
+        let cache_path = self
+            .cache_dir
+            .join("repodata")
+            .join(hex::encode(sha2::Sha256::digest(subdir_url.as_bytes())))
+            .join(filename);
+
+        let data = if tugger_common::http::is_offline() && !url.starts_with("file:") {
+            std::fs::read(&cache_path).with_context(|| {
+                format!(
+                    "network access is disabled in offline mode and {} is not cached",
+                    url
+                )
+            })?
+        } else {
+            match read_url(&url) {
+                Ok(data) => {
+                    if let Some(parent) = cache_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&cache_path, &data)?;
+                    data
+                }
+                // Channels aren't required to offer `current_repodata.json`.
+                Err(_) if !self.full => return Ok(vec![]),
+                Err(e) => return Err(e),
+            }
+        };
+
+        parse_repodata(&data, &subdir_url)
+    }
+}
+
+impl CondaChannels for RemoteChannels {
+    fn records(&self, name: &str) -> Result<Vec<PackageRecord>> {
+        if self.records.borrow().is_none() {
+            let mut records = vec![];
+            for channel in &self.channels {
+                for subdir in [self.subdir.as_str(), "noarch"] {
+                    records.extend(self.load_repodata(channel, subdir)?);
+                }
+            }
+
+            self.records.replace(Some(records));
+        }
+
+        Ok(self
+            .records
+            .borrow()
+            .as_ref()
+            .expect("records loaded above")
+            .iter()
+            .filter(|record| record.name == name)
+            .cloned()
+            .collect())
+    }
+
+    fn fetch(&self, logger: &slog::Logger, record: &PackageRecord) -> Result<PathBuf> {
+        let dest_dir = self.cache_dir.join("pkgs");
+        let dest_path = dest_dir.join(&record.filename);
+
+        let sha256 = |data: &[u8]| hex::encode(sha2::Sha256::digest(data));
+
+        if dest_path.exists() {
+            match &record.sha256 {
+                Some(expected) if sha256(&std::fs::read(&dest_path)?) == *expected => {
+                    return Ok(dest_path)
+                }
+                None => return Ok(dest_path),
+                _ => {}
+            }
+        }
+
+        warn!(logger, "downloading {}", record.url);
+        let data = read_url(&record.url)?;
+
+        if let Some(expected) = &record.sha256 {
+            let actual = sha256(&data);
+            if actual != *expected {
+                return Err(anyhow!(
+                    "hash mismatch of {}: expected {}, got {}",
+                    record.filename,
+                    expected,
+                    actual
+                ));
+            }
+        }
+
+        std::fs::create_dir_all(&dest_dir)
+            .with_context(|| format!("creating {}", dest_dir.display()))?;
+        let temp_path = dest_dir.join(format!("{}.tmp", record.filename));
+        std::fs::write(&temp_path, &data)
+            .with_context(|| format!("writing {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &dest_path)
+            .with_context(|| format!("renaming {}", temp_path.display()))?;
+
+        Ok(dest_path)
+    }
+}
+
+/// Select the packages to install for requirements.
+///
+/// Returns records ordered by package name.
+pub fn resolve_packages(
+    channels: &dyn CondaChannels,
+    specs: &[MatchSpec],
+    python_version: &str,
+) -> Result<Vec<PackageRecord>> {
+    let mut constraints: BTreeMap<String, Vec<(MatchSpec, String)>> = BTreeMap::new();
+    let mut selected: BTreeMap<String, PackageRecord> = BTreeMap::new();
+
+    let mut queue = specs
+        .iter()
+        .map(|spec| (spec.clone(), "requirements".to_string()))
+        .collect::<VecDeque<_>>();
+
+    while let Some((spec, requested_by)) = queue.pop_front() {
+        if spec.name.starts_with("__") || PROVIDED_PACKAGES.contains(&spec.name.as_str()) {
+            continue;
+        }
+
+        let package_constraints = constraints.entry(spec.name.clone()).or_default();
+        package_constraints.push((spec.clone(), requested_by.clone()));
+
+        if let Some(record) = selected.get(&spec.name) {
+            if !spec.matches(record) {
+                return Err(anyhow!(
+                    "{} {} was selected but {} requires {}; pin a version of {} \
+                     satisfying all requirements",
+                    record.name,
+                    record.version,
+                    requested_by,
+                    format_spec(&spec),
+                    record.name
+                ));
+            }
+
+            continue;
+        }
+
+        let best = channels
+            .records(&spec.name)?
+            .into_iter()
+            .filter(|record| {
+                package_constraints
+                    .iter()
+                    .all(|(spec, _)| spec.matches(record))
+                    && python_compatible(record, python_version)
+            })
+            .max_by(|a, b| {
+                compare_versions(&a.version, &b.version)
+                    .then_with(|| a.build_number.cmp(&b.build_number))
+                    // Prefer the newer package format.
+                    .then_with(|| {
+                        a.filename
+                            .ends_with(".conda")
+                            .cmp(&b.filename.ends_with(".conda"))
+                    })
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "no conda package for Python {} satisfies {}",
+                    python_version,
+                    package_constraints
+                        .iter()
+                        .map(|(spec, requested_by)| format!(
+                            "`{}` (from {})",
+                            format_spec(spec),
+                            requested_by
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+
+        for dependency in &best.depends {
+            queue.push_back((
+                MatchSpec::from_str(dependency)
+                    .with_context(|| format!("parsing dependency of {}", best.filename))?,
+                format!("{} {}", best.name, best.version),
+            ));
+        }
+
+        selected.insert(spec.name.clone(), best);
+    }
+
+    Ok(selected.into_values().collect())
+}
+
+fn format_spec(spec: &MatchSpec) -> String {
+    let mut s = spec.name.clone();
+    if let Some(version) = &spec.version {
+        s.push(' ');
+        s.push_str(version);
+    }
+    if let Some(build) = &spec.build {
+        s.push(' ');
+        s.push_str(build);
+    }
+
+    s
+}
+
+/// Extract the content of a `.conda` or `.tar.bz2` package into a directory.
+///
+/// Only files passing `filter` are extracted.
+pub fn extract_package(path: &Path, dest_dir: &Path, filter: impl Fn(&Path) -> bool) -> Result<()> {
+    let filename = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    std::fs::create_dir_all(dest_dir)
+        .with_context(|| format!("creating {}", dest_dir.display()))?;
+
+    let unpack = |reader: &mut dyn Read| -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.to_path_buf();
+
+            if entry.header().entry_type().is_file() && filter(&entry_path) {
+                entry.unpack_in(dest_dir)?;
+            }
+        }
+
+        Ok(())
+    };
+
+    if filename.ends_with(".tar.bz2") {
+        let fh = std::fs::File::open(path)?;
+        unpack(&mut bzip2::read::BzDecoder::new(std::io::BufReader::new(
+            fh,
+        )))
+        .with_context(|| format!("extracting {}", path.display()))
+    } else if filename.ends_with(".conda") {
+        // `.conda` files are zip archives holding zstd compressed tar
+        // archives of package metadata (`info-*`) and content (`pkg-*`).
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(path)?)
+            .with_context(|| format!("reading {}", path.display()))?;
+
+        let name = zip
+            .file_names()
+            .find(|name| name.starts_with("pkg-") && name.ends_with(".tar.zst"))
+            .map(|name| name.to_string())
+            .ok_or_else(|| anyhow!("{} does not contain package content", path.display()))?;
+
+        let mut decoder = zstd::stream::Decoder::new(zip.by_name(&name)?)?;
+        unpack(&mut decoder).with_context(|| format!("extracting {}", path.display()))
+    } else {
+        Err(anyhow!("unsupported conda package format: {}", filename))
+    }
+}
+
+/// Directories relative to the installation prefix holding Python packages.
+fn python_package_roots(python_major_minor: &str) -> Vec<PathBuf> {
+    vec![
+        // noarch: python packages.
+        PathBuf::from("site-packages"),
+        PathBuf::from(format!("lib/python{}/site-packages", python_major_minor)),
+        PathBuf::from("Lib/site-packages"),
+    ]
+}
+
+/// Whether a path relative to the installation prefix is a shared library.
+fn is_shared_library(path: &Path) -> bool {
+    let filename = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+
+    (parent == Path::new("lib")
+        && (filename.ends_with(".dylib") || filename.ends_with(".so") || filename.contains(".so.")))
+        || (parent == Path::new("Library/bin") && filename.to_lowercase().ends_with(".dll"))
+}
+
+/// Resources obtained from conda packages.
+#[derive(Debug, Default)]
+pub struct CondaPayload<'a> {
+    /// Resources of Python packages.
+    pub resources: Vec<PythonResource<'a>>,
+
+    /// Shared libraries, with paths relative to the directory they should be
+    /// installed in.
+    pub shared_libraries: Vec<File>,
+}
+
+/// Download conda packages for a distribution and collect their resources.
+///
+/// `specs` are package specifications. e.g. `numpy>=1.21`. Channels are
+/// names, like `conda-forge`, or URLs.
+pub fn conda_packages<'a>(
+    logger: &slog::Logger,
+    dist: &dyn PythonDistribution,
+    policy: &PythonPackagingPolicy,
+    specs: &[String],
+    channels: &[String],
+    cache_dir: &Path,
+) -> Result<CondaPayload<'a>> {
+    let specs = specs
+        .iter()
+        .map(|s| MatchSpec::from_str(s))
+        .collect::<Result<Vec<_>>>()?;
+
+    let subdir = conda_subdir(dist.target_triple())?;
+    let remote = RemoteChannels::new(channels, subdir, cache_dir);
+
+    let records = match resolve_packages(&remote, &specs, dist.python_version()) {
+        Ok(records) => records,
+        Err(e) => {
+            warn!(
+                logger,
+                "unable to resolve from current repodata ({}); retrying with complete repodata", e
+            );
+            resolve_packages(&remote.with_full_repodata(), &specs, dist.python_version())?
+        }
+    };
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("pyoxidizer-conda")
+        .tempdir()?;
+
+    let python_roots = python_package_roots(&dist.python_major_minor_version());
+
+    let mut payload = CondaPayload::default();
+
+    for record in &records {
+        warn!(logger, "using conda package {}", record.filename);
+        let path = remote.fetch(logger, record)?;
+
+        let prefix = temp_dir.path().join(&record.name);
+        extract_package(&path, &prefix, |p| {
+            python_roots.iter().any(|root| p.starts_with(root)) || is_shared_library(p)
+        })?;
+
+        for root in &python_roots {
+            let root = prefix.join(root);
+            if root.is_dir() {
+                payload
+                    .resources
+                    .extend(find_resources(dist, policy, &root, None)?);
+            }
+        }
+
+        for dir in [prefix.join("lib"), prefix.join("Library").join("bin")] {
+            if !dir.is_dir() {
+                continue;
+            }
+
+            let mut entries = std::fs::read_dir(&dir)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?;
+            entries.sort();
+
+            for path in entries {
+                if !path.is_file() {
+                    continue;
+                }
+
+                let relative = path.strip_prefix(&prefix)?;
+                if is_shared_library(relative) {
+                    payload.shared_libraries.push(File::new(
+                        path.file_name().expect("files have names"),
+                        FileEntry::new_from_data(std::fs::read(&path)?, true),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::cell::RefCell, std::io::Write};
+
+    fn record(name: &str, version: &str, build: &str, depends: &[&str]) -> PackageRecord {
+        PackageRecord {
+            name: name.to_string(),
+            version: version.to_string(),
+            build: build.to_string(),
+            build_number: 0,
+            depends: depends.iter().map(|s| s.to_string()).collect(),
+            sha256: None,
+            filename: format!("{}-{}-{}.tar.bz2", name, version, build),
+            url: String::new(),
+        }
+    }
+
+    struct TestChannels(Vec<PackageRecord>, RefCell<usize>);
+
+    impl CondaChannels for TestChannels {
+        fn records(&self, name: &str) -> Result<Vec<PackageRecord>> {
+            *self.1.borrow_mut() += 1;
+            Ok(self.0.iter().filter(|r| r.name == name).cloned().collect())
+        }
+
+        fn fetch(&self, _logger: &slog::Logger, _record: &PackageRecord) -> Result<PathBuf> {
+            unimplemented!()
+        }
+    }
+
+    fn resolve(channels: &TestChannels, specs: &[&str]) -> Result<Vec<String>> {
+        let specs = specs
+            .iter()
+            .map(|s| MatchSpec::from_str(s))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(resolve_packages(channels, &specs, "3.9.7")?
+            .into_iter()
+            .map(|r| r.filename)
+            .collect())
+    }
+
+    #[test]
+    fn test_channel_url() {
+        assert_eq!(
+            channel_url("conda-forge"),
+            "https://conda.anaconda.org/conda-forge"
+        );
+        assert_eq!(
+            channel_url("https://repo.example.com/channel/"),
+            "https://repo.example.com/channel"
+        );
+    }
+
+    #[test]
+    fn test_parse_match_spec() -> Result<()> {
+        assert_eq!(
+            MatchSpec::from_str("numpy")?,
+            MatchSpec {
+                name: "numpy".to_string(),
+                version: None,
+                build: None
+            }
+        );
+        assert_eq!(
+            MatchSpec::from_str("conda-forge::NumPy >=1.20,<2 py39*")?,
+            MatchSpec {
+                name: "numpy".to_string(),
+                version: Some(">=1.20,<2".to_string()),
+                build: Some("py39*".to_string())
+            }
+        );
+        assert_eq!(
+            MatchSpec::from_str("numpy=1.21")?.version.as_deref(),
+            Some("=1.21")
+        );
+        let spec = MatchSpec::from_str("numpy=1.21.2=py39h20f2e39_0")?;
+        assert_eq!(spec.version.as_deref(), Some("1.21.2"));
+        assert_eq!(spec.build.as_deref(), Some("py39h20f2e39_0"));
+
+        assert!(MatchSpec::from_str(">=1.0").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_matches() {
+        assert!(version_matches(">=3.9,<3.10.0a0", "3.9.7"));
+        assert!(!version_matches(">=3.9,<3.10.0a0", "3.10.0"));
+        assert!(version_matches("3.9.*", "3.9.7"));
+        assert!(!version_matches("3.9.*", "3.10.1"));
+        assert!(version_matches("=1.21", "1.21.2"));
+        assert!(!version_matches("=1.2", "1.21"));
+        assert!(version_matches("1.21", "1.21.0"));
+        assert!(version_matches("1.20|1.21", "1.21"));
+        assert!(version_matches("!=1.0", "1.1"));
+        assert!(version_matches("~=1.4.5", "1.4.9"));
+        assert!(!version_matches("~=1.4.5", "1.5.0"));
+        assert!(version_matches("1.1.1*", "1.1.1l"));
+        assert!(version_matches(">=1.1.1k", "1.1.1l"));
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(compare_versions("1.21.2", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.1.1l", "1.1.1k"), Ordering::Greater);
+        assert_eq!(compare_versions("2021.10", "2021.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_resolve_packages() -> Result<()> {
+        let channels = TestChannels(
+            vec![
+                record(
+                    "app",
+                    "1.0",
+                    "pyhd8ed1ab_0",
+                    &["python >=3.6", "numpy >=1.20"],
+                ),
+                record(
+                    "numpy",
+                    "1.21.2",
+                    "py39h20f2e39_0",
+                    &[
+                        "libopenblas >=0.3.17",
+                        "python >=3.9,<3.10.0a0",
+                        "python_abi 3.9.* *_cp39",
+                        "libgcc-ng >=9.4.0",
+                        "__glibc >=2.17",
+                    ],
+                ),
+                record(
+                    "numpy",
+                    "1.21.2",
+                    "py310h20f2e39_0",
+                    &["libopenblas >=0.3.17", "python >=3.10,<3.11.0a0"],
+                ),
+                record(
+                    "numpy",
+                    "1.22.0",
+                    "py310h20f2e39_0",
+                    &["python >=3.10,<3.11.0a0"],
+                ),
+                record(
+                    "numpy",
+                    "1.19.5",
+                    "py39h20f2e39_0",
+                    &["python >=3.9,<3.10.0a0"],
+                ),
+                record("libopenblas", "0.3.18", "pthreads_h8fe5266_0", &[]),
+            ],
+            RefCell::new(0),
+        );
+
+        assert_eq!(
+            resolve(&channels, &["app"])?,
+            vec![
+                "app-1.0-pyhd8ed1ab_0.tar.bz2",
+                "libopenblas-0.3.18-pthreads_h8fe5266_0.tar.bz2",
+                "numpy-1.21.2-py39h20f2e39_0.tar.bz2",
+            ]
+        );
+
+        let err = resolve(&channels, &["numpy=1.19", "app"]).unwrap_err();
+        assert!(err.to_string().contains("numpy 1.19.5 was selected"));
+
+        let err = resolve(&channels, &["numpy >=1.22"]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("no conda package for Python 3.9.7 satisfies"));
+
+        Ok(())
+    }
+
+    fn tar_data(files: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *data)?;
+        }
+
+        Ok(builder.into_inner()?)
+    }
+
+    #[test]
+    fn test_extract_package() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let files: &[(&str, &[u8])] = &[
+            ("info/index.json", b"{}"),
+            (
+                "lib/python3.9/site-packages/foo/__init__.py",
+                b"print('hi')",
+            ),
+            ("lib/libfoo.so.1", b"\x7fELF"),
+            ("bin/foo", b""),
+        ];
+        let filter = |p: &Path| {
+            python_package_roots("3.9")
+                .iter()
+                .any(|root| p.starts_with(root))
+                || is_shared_library(p)
+        };
+
+        // .tar.bz2 packages.
+        let bz2_path = temp_dir.path().join("foo-1.0-0.tar.bz2");
+        let mut encoder = bzip2::write::BzEncoder::new(
+            std::fs::File::create(&bz2_path)?,
+            bzip2::Compression::fast(),
+        );
+        encoder.write_all(&tar_data(files)?)?;
+        encoder.finish()?;
+
+        let dest = temp_dir.path().join("bz2");
+        extract_package(&bz2_path, &dest, filter)?;
+        assert!(dest
+            .join("lib/python3.9/site-packages/foo/__init__.py")
+            .is_file());
+        assert!(dest.join("lib/libfoo.so.1").is_file());
+        assert!(!dest.join("info/index.json").exists());
+        assert!(!dest.join("bin/foo").exists());
+
+        // .conda packages.
+        let conda_path = temp_dir.path().join("foo-1.0-0.conda");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&conda_path)?);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("metadata.json", options)?;
+        zip.write_all(b"{\"conda_pkg_format_version\": 2}")?;
+        zip.start_file("pkg-foo-1.0-0.tar.zst", options)?;
+        zip.write_all(&zstd::stream::encode_all(tar_data(files)?.as_slice(), 3)?)?;
+        zip.finish()?;
+
+        let dest = temp_dir.path().join("conda");
+        extract_package(&conda_path, &dest, filter)?;
+        assert!(dest
+            .join("lib/python3.9/site-packages/foo/__init__.py")
+            .is_file());
+        assert!(dest.join("lib/libfoo.so.1").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_repodata() -> Result<()> {
+        let data = br#"{
+            "info": {"subdir": "linux-64"},
+            "packages": {
+                "six-1.16.0-pyh6c4a22f_0.tar.bz2": {
+                    "name": "six", "version": "1.16.0", "build": "pyh6c4a22f_0",
+                    "build_number": 0, "depends": ["python"], "sha256": "abcd"
+                }
+            },
+            "packages.conda": {
+                "six-1.16.0-pyh6c4a22f_0.conda": {
+                    "name": "six", "version": "1.16.0", "build": "pyh6c4a22f_0"
+                }
+            }
+        }"#;
+
+        let records = parse_repodata(data, "file:///channel/noarch")?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].filename, "six-1.16.0-pyh6c4a22f_0.conda");
+        assert_eq!(
+            records[1].url,
+            "file:///channel/noarch/six-1.16.0-pyh6c4a22f_0.tar.bz2"
+        );
+        assert_eq!(records[1].depends, vec!["python"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_shared_library() {
+        assert!(is_shared_library(Path::new("lib/libopenblas.so.0")));
+        assert!(is_shared_library(Path::new("lib/libz.dylib")));
+        assert!(is_shared_library(Path::new("Library/bin/openblas.DLL")));
+        assert!(!is_shared_library(Path::new("lib/python3.9/foo.so")));
+        assert!(!is_shared_library(Path::new("lib/libfoo.a")));
+    }
+}
@@ -7,22 +7,40 @@ Configuring a Python interpreter.
 */
 
 use {
+    crate::py_packaging::embedded_metadata::BuildMetadata,
     anyhow::Result,
     itertools::Itertools,
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterConfig, PythonInterpreterProfile,
-            TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, EnvironmentMutation,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterConfig,
+            PythonInterpreterProfile, TerminfoResolution, WorkingDirectoryPolicy,
         },
         resource::BytecodeOptimizationLevel,
     },
     std::{
+        collections::BTreeMap,
         io::Write,
         path::{Path, PathBuf},
+        time::Duration,
     },
 };
 
+/// Settings of `pyembed::OxidizedPythonInterpreterConfig` a runtime configuration file can override.
+pub const RUNTIME_CONFIG_SETTINGS: &[&str] = &[
+    "allocator_backend",
+    "allocator_debug",
+    "development_mode",
+    "fault_handler",
+    "fault_handler_path",
+    "filesystem_importer",
+    "module_search_paths",
+    "optimization_level",
+    "tracemalloc_frames",
+    "verbose",
+    "warn_options",
+];
+
 /// Determine the default memory allocator for a target triple.
 pub fn default_memory_allocator(target_triple: &str) -> MemoryAllocatorBackend {
     // Jemalloc doesn't work on Windows.
@@ -134,7 +152,51 @@ pub struct PyembedPythonInterpreterConfig {
     pub sys_meipass: bool,
     pub terminfo_resolution: TerminfoResolution,
     pub tcl_library: Option<PathBuf>,
+    pub tk_library: Option<PathBuf>,
     pub write_modules_directory_env: Option<String>,
+    pub allow_runtime_path_overrides: bool,
+    pub fault_handler_path: Option<PathBuf>,
+    pub tracemalloc_frames: Option<u32>,
+    pub crash_reports_directory: Option<PathBuf>,
+    pub crash_reports_upload_url: Option<String>,
+    pub update_feed_url: Option<String>,
+    pub update_channel: Option<String>,
+    pub update_public_key: Option<String>,
+    pub update_current_version: Option<String>,
+    pub daemonize: bool,
+    pub pid_file: Option<PathBuf>,
+    pub stdout_path: Option<PathBuf>,
+    pub stderr_path: Option<PathBuf>,
+    pub user_site_packages: Option<PathBuf>,
+    pub run_pytest: bool,
+    pub coverage_mode: bool,
+    pub debugger_support: bool,
+    pub emulate_python_cli: bool,
+    pub shared_library_search_paths: Vec<PathBuf>,
+    pub dunder_file_packages: Vec<String>,
+    pub dunder_file_root: Option<PathBuf>,
+    pub runtime_hooks: Vec<(String, String)>,
+    pub optimization_level_env: Option<String>,
+    /// Path of a file holding the archive to extract, embedded via `include_bytes!`.
+    pub extract_archive: Option<PathBuf>,
+    pub extract_directory: Option<PathBuf>,
+    pub environment: Vec<EnvironmentMutation>,
+    pub working_directory: WorkingDirectoryPolicy,
+    pub app_name: Option<String>,
+    pub app_author: Option<String>,
+    pub single_instance: Option<String>,
+    pub single_instance_forward_args: bool,
+    /// Path of a BMP file shown as splash screen, embedded via `include_bytes!`.
+    pub splash_screen_image: Option<PathBuf>,
+    pub splash_screen_timeout: Option<Duration>,
+    /// Files standard streams are captured to, as `pyembed::OutputCapture::File`.
+    pub stdout_capture_path: Option<PathBuf>,
+    pub stderr_capture_path: Option<PathBuf>,
+    pub capture_max_size: Option<u64>,
+    pub capture_backups: usize,
+    pub logging_bridge: bool,
+    pub runtime_config_overrides: Vec<String>,
+    pub runtime_metadata: BTreeMap<String, String>,
 }
 
 impl Default for PyembedPythonInterpreterConfig {
@@ -169,12 +231,134 @@ impl Default for PyembedPythonInterpreterConfig {
             sys_meipass: false,
             terminfo_resolution: TerminfoResolution::None,
             tcl_library: None,
+            tk_library: None,
             write_modules_directory_env: None,
+            allow_runtime_path_overrides: false,
+            fault_handler_path: None,
+            tracemalloc_frames: None,
+            crash_reports_directory: None,
+            crash_reports_upload_url: None,
+            update_feed_url: None,
+            update_channel: None,
+            update_public_key: None,
+            update_current_version: None,
+            daemonize: false,
+            pid_file: None,
+            stdout_path: None,
+            stderr_path: None,
+            user_site_packages: None,
+            run_pytest: false,
+            coverage_mode: false,
+            debugger_support: false,
+            emulate_python_cli: false,
+            shared_library_search_paths: vec![],
+            dunder_file_packages: vec![],
+            dunder_file_root: None,
+            runtime_hooks: vec![],
+            optimization_level_env: None,
+            extract_archive: None,
+            extract_directory: None,
+            environment: vec![],
+            working_directory: WorkingDirectoryPolicy::Unchanged,
+            app_name: None,
+            app_author: None,
+            single_instance: None,
+            single_instance_forward_args: false,
+            splash_screen_image: None,
+            splash_screen_timeout: None,
+            stdout_capture_path: None,
+            stderr_capture_path: None,
+            capture_max_size: None,
+            capture_backups: 0,
+            logging_bridge: false,
+            runtime_config_overrides: vec![],
+            runtime_metadata: BTreeMap::new(),
         }
     }
 }
 
 impl PyembedPythonInterpreterConfig {
+    /// Whether standard streams are captured to files.
+    pub fn requires_output_capture(&self) -> bool {
+        self.stdout_capture_path.is_some() || self.stderr_capture_path.is_some()
+    }
+
+    fn output_capture_to_string(&self, path: &Option<PathBuf>) -> String {
+        match path {
+            Some(path) => format!(
+                "Some(pyembed::OutputCapture::File {{ path: {}, max_size: {}, backups: {} }})",
+                path_to_string(path),
+                match self.capture_max_size {
+                    Some(size) => format!("Some({})", size),
+                    None => "None".to_string(),
+                },
+                self.capture_backups
+            ),
+            None => "None".to_string(),
+        }
+    }
+
+    /// Obtain notable settings as strings, for recording in build metadata.
+    pub fn metadata_flags(&self) -> BTreeMap<String, String> {
+        [
+            ("profile", self.config.profile.to_string()),
+            ("allocator_backend", self.allocator_backend.to_string()),
+            ("oxidized_importer", self.oxidized_importer.to_string()),
+            ("filesystem_importer", self.filesystem_importer.to_string()),
+            ("sys_frozen", self.sys_frozen.to_string()),
+            ("sys_meipass", self.sys_meipass.to_string()),
+            (
+                "multiprocessing_start_method",
+                self.multiprocessing_start_method.to_string(),
+            ),
+            ("terminfo_resolution", self.terminfo_resolution.to_string()),
+            (
+                "allow_runtime_path_overrides",
+                self.allow_runtime_path_overrides.to_string(),
+            ),
+            (
+                "crash_reporting",
+                self.crash_reports_directory.is_some().to_string(),
+            ),
+            ("updater", self.update_feed_url.is_some().to_string()),
+            ("daemonize", self.daemonize.to_string()),
+            (
+                "user_site_packages",
+                self.user_site_packages.is_some().to_string(),
+            ),
+            ("run_pytest", self.run_pytest.to_string()),
+            ("coverage_mode", self.coverage_mode.to_string()),
+            ("debugger_support", self.debugger_support.to_string()),
+            ("emulate_python_cli", self.emulate_python_cli.to_string()),
+            (
+                "self_extracting",
+                self.extract_archive.is_some().to_string(),
+            ),
+            ("working_directory", self.working_directory.to_string()),
+            (
+                "single_instance",
+                self.single_instance.is_some().to_string(),
+            ),
+            (
+                "splash_screen",
+                self.splash_screen_image.is_some().to_string(),
+            ),
+            ("output_capture", self.requires_output_capture().to_string()),
+            ("logging_bridge", self.logging_bridge.to_string()),
+            (
+                "runtime_config",
+                (!self.runtime_config_overrides.is_empty()).to_string(),
+            ),
+            (
+                "write_modules_directory_env",
+                self.write_modules_directory_env.clone().unwrap_or_default(),
+            ),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+    }
+
     /// Convert the instance to Rust code that constructs a `pyembed::OxidizedPythonInterpreterConfig`.
     pub fn to_oxidized_python_interpreter_config_rs(&self) -> Result<String> {
         // This code is complicated enough. Let's not worry about format! in format!.
@@ -260,7 +444,46 @@ impl PyembedPythonInterpreterConfig {
             sys_meipass: {},\n    \
             terminfo_resolution: {},\n    \
             tcl_library: {},\n    \
+            tk_library: {},\n    \
             write_modules_directory_env: {},\n    \
+            allow_runtime_path_overrides: {},\n    \
+            fault_handler_path: {},\n    \
+            tracemalloc_frames: {},\n    \
+            crash_reports_directory: {},\n    \
+            crash_reports_upload_url: {},\n    \
+            update_feed_url: {},\n    \
+            update_channel: {},\n    \
+            update_public_key: {},\n    \
+            update_current_version: {},\n    \
+            daemonize: {},\n    \
+            pid_file: {},\n    \
+            stdout_path: {},\n    \
+            stderr_path: {},\n    \
+            user_site_packages: {},\n    \
+            run_pytest: {},\n    \
+            coverage_mode: {},\n    \
+            debugger_support: {},\n    \
+            emulate_python_cli: {},\n    \
+            shared_library_search_paths: {},\n    \
+            dunder_file_packages: {},\n    \
+            dunder_file_root: {},\n    \
+            runtime_hooks: {},\n    \
+            optimization_level_env: {},\n    \
+            extract_archive: {},\n    \
+            extract_directory: {},\n    \
+            environment: {},\n    \
+            working_directory: {},\n    \
+            app_name: {},\n    \
+            app_author: {},\n    \
+            single_instance: {},\n    \
+            single_instance_forward_args: {},\n    \
+            splash_screen_image: {},\n    \
+            splash_screen_timeout: {},\n    \
+            stdout_capture: {},\n    \
+            stderr_capture: {},\n    \
+            logging_bridge: {},\n    \
+            runtime_config_overrides: {},\n    \
+            runtime_metadata: {},\n    \
             }}\n\
             ",
             match self.config.profile {
@@ -413,14 +636,148 @@ impl PyembedPythonInterpreterConfig {
                 }
             },
             optional_pathbuf_to_string(&self.tcl_library),
+            optional_pathbuf_to_string(&self.tk_library),
             optional_string_to_string(&self.write_modules_directory_env),
+            self.allow_runtime_path_overrides,
+            optional_pathbuf_to_string(&self.fault_handler_path),
+            match &self.tracemalloc_frames {
+                Some(value) => format!("Some({})", value),
+                None => "None".to_string(),
+            },
+            optional_pathbuf_to_string(&self.crash_reports_directory),
+            optional_string_to_string(&self.crash_reports_upload_url),
+            optional_string_to_string(&self.update_feed_url),
+            optional_string_to_string(&self.update_channel),
+            optional_string_to_string(&self.update_public_key),
+            optional_string_to_string(&self.update_current_version),
+            self.daemonize,
+            optional_pathbuf_to_string(&self.pid_file),
+            optional_pathbuf_to_string(&self.stdout_path),
+            optional_pathbuf_to_string(&self.stderr_path),
+            optional_pathbuf_to_string(&self.user_site_packages),
+            self.run_pytest,
+            self.coverage_mode,
+            self.debugger_support,
+            self.emulate_python_cli,
+            format!(
+                "vec![{}]",
+                self.shared_library_search_paths
+                    .iter()
+                    .map(|p| path_to_string(p.as_path()))
+                    .join(", ")
+            ),
+            format!(
+                "vec![{}]",
+                self.dunder_file_packages
+                    .iter()
+                    .map(|x| format!("\"{}\".to_string()", x.escape_default()))
+                    .join(", ")
+            ),
+            optional_pathbuf_to_string(&self.dunder_file_root),
+            format!(
+                "vec![{}]",
+                self.runtime_hooks
+                    .iter()
+                    .map(|(module, code)| format!(
+                        "(\"{}\".to_string(), \"{}\".to_string())",
+                        module.escape_default(),
+                        code.escape_default()
+                    ))
+                    .join(", ")
+            ),
+            optional_string_to_string(&self.optimization_level_env),
+            match &self.extract_archive {
+                Some(path) => format!("Some(include_bytes!(r#\"{}\"#))", path.display()),
+                None => "None".to_string(),
+            },
+            optional_pathbuf_to_string(&self.extract_directory),
+            format!(
+                "vec![{}]",
+                self.environment
+                    .iter()
+                    .map(|mutation| {
+                        let string = |s: &str| format!("\"{}\".to_string()", s.escape_default());
+
+                        match mutation {
+                            EnvironmentMutation::Set(name, value) => format!(
+                                "pyembed::EnvironmentMutation::Set({}, {})",
+                                string(name),
+                                string(value)
+                            ),
+                            EnvironmentMutation::Unset(name) => {
+                                format!("pyembed::EnvironmentMutation::Unset({})", string(name))
+                            }
+                            EnvironmentMutation::Prepend(name, value) => format!(
+                                "pyembed::EnvironmentMutation::Prepend({}, {})",
+                                string(name),
+                                string(value)
+                            ),
+                            EnvironmentMutation::Append(name, value) => format!(
+                                "pyembed::EnvironmentMutation::Append({}, {})",
+                                string(name),
+                                string(value)
+                            ),
+                        }
+                    })
+                    .join(", ")
+            ),
+            match self.working_directory {
+                WorkingDirectoryPolicy::Unchanged => "pyembed::WorkingDirectoryPolicy::Unchanged",
+                WorkingDirectoryPolicy::ExecutableDirectory =>
+                    "pyembed::WorkingDirectoryPolicy::ExecutableDirectory",
+                WorkingDirectoryPolicy::UserDataDirectory =>
+                    "pyembed::WorkingDirectoryPolicy::UserDataDirectory",
+            },
+            optional_string_to_string(&self.app_name),
+            optional_string_to_string(&self.app_author),
+            optional_string_to_string(&self.single_instance),
+            self.single_instance_forward_args,
+            match &self.splash_screen_image {
+                Some(path) => format!("Some(include_bytes!(r#\"{}\"#))", path.display()),
+                None => "None".to_string(),
+            },
+            match &self.splash_screen_timeout {
+                Some(timeout) => format!(
+                    "Some(std::time::Duration::from_millis({}))",
+                    timeout.as_millis()
+                ),
+                None => "None".to_string(),
+            },
+            self.output_capture_to_string(&self.stdout_capture_path),
+            self.output_capture_to_string(&self.stderr_capture_path),
+            self.logging_bridge,
+            format!(
+                "vec![{}]",
+                self.runtime_config_overrides
+                    .iter()
+                    .map(|x| format!("\"{}\".to_string()", x.escape_default()))
+                    .join(", ")
+            ),
+            format!(
+                "vec![{}]",
+                self.runtime_metadata
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "(\"{}\".to_string(), \"{}\".to_string())",
+                        key.escape_default(),
+                        value.escape_default()
+                    ))
+                    .join(", ")
+            ),
         );
 
         Ok(code)
     }
 
     /// Write a Rust file containing a function for obtaining the default `OxidizedPythonInterpreterConfig`.
-    pub fn write_default_python_config_rs(&self, path: impl AsRef<Path>) -> Result<()> {
+    ///
+    /// `metadata` is embedded in the binary so it can be inspected by
+    /// `pyoxidizer analyze`.
+    pub fn write_default_python_config_rs(
+        &self,
+        path: impl AsRef<Path>,
+        metadata: &BuildMetadata,
+    ) -> Result<()> {
         let mut f = std::fs::File::create(path.as_ref())?;
 
         let indented = self
@@ -429,13 +786,23 @@ impl PyembedPythonInterpreterConfig {
             .map(|line| "    ".to_string() + line)
             .join("\n");
 
+        f.write_all(
+            metadata
+                .to_rust_static("PYOXIDIZER_BUILD_METADATA")?
+                .as_bytes(),
+        )?;
+
         f.write_fmt(format_args!(
-            "/// Obtain the default Python configuration\n\
+            "\n\
+             /// Obtain the default Python configuration\n\
              ///\n\
              /// The crate is compiled with a default Python configuration embedded\n\
              /// in the crate. This function will return an instance of that\n\
              /// configuration.\n\
-             pub fn default_python_config<'a>() -> pyembed::OxidizedPythonInterpreterConfig<'a> {{\n{}\n}}\n",
+             pub fn default_python_config<'a>() -> pyembed::OxidizedPythonInterpreterConfig<'a> {{\n\
+             \x20   // Reference the build metadata so the linker retains it.\n\
+             \x20   let _ = unsafe {{ std::ptr::read_volatile(&PYOXIDIZER_BUILD_METADATA) }};\n\n\
+             {}\n}}\n",
             indented
         ))?;
 
@@ -515,6 +882,337 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_serialize_diagnostics() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            fault_handler_path: Some(PathBuf::from("$ORIGIN/faults.log")),
+            tracemalloc_frames: Some(10),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "fault_handler_path: Some(std::path::PathBuf::from(\"$ORIGIN/faults.log\")),",
+        )?;
+        assert_contains(&code, "tracemalloc_frames: Some(10),")
+    }
+
+    #[test]
+    fn test_serialize_crash_reports() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            crash_reports_directory: Some(PathBuf::from("$ORIGIN/crashes")),
+            crash_reports_upload_url: Some("https://example.com/crashes".to_string()),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "crash_reports_directory: Some(std::path::PathBuf::from(\"$ORIGIN/crashes\")),",
+        )?;
+        assert_contains(
+            &code,
+            "crash_reports_upload_url: Some(\"https://example.com/crashes\".to_string()),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_updater() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            update_feed_url: Some("https://example.com/updates.json".to_string()),
+            update_channel: Some("beta".to_string()),
+            update_current_version: Some("1.0.0".to_string()),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "update_feed_url: Some(\"https://example.com/updates.json\".to_string()),",
+        )?;
+        assert_contains(&code, "update_channel: Some(\"beta\".to_string()),")?;
+        assert_contains(&code, "update_public_key: None,")?;
+        assert_contains(
+            &code,
+            "update_current_version: Some(\"1.0.0\".to_string()),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_daemon() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            daemonize: true,
+            pid_file: Some(PathBuf::from("/run/myapp.pid")),
+            stderr_path: Some(PathBuf::from("$ORIGIN/myapp.log")),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "daemonize: true,")?;
+        assert_contains(
+            &code,
+            "pid_file: Some(std::path::PathBuf::from(\"/run/myapp.pid\")),",
+        )?;
+        assert_contains(&code, "stdout_path: None,")?;
+        assert_contains(
+            &code,
+            "stderr_path: Some(std::path::PathBuf::from(\"$ORIGIN/myapp.log\")),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_user_site_packages() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            user_site_packages: Some(PathBuf::from("myapp/site-packages")),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "user_site_packages: Some(std::path::PathBuf::from(\"myapp/site-packages\")),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_run_pytest() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            run_pytest: true,
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "run_pytest: true,")?;
+        assert_eq!(
+            config.metadata_flags().get("run_pytest"),
+            Some(&"true".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_coverage_mode() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            coverage_mode: true,
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "coverage_mode: true,")
+    }
+
+    #[test]
+    fn test_serialize_debugger_support() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            debugger_support: true,
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "debugger_support: true,")
+    }
+
+    #[test]
+    fn test_serialize_emulate_python_cli() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            emulate_python_cli: true,
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "emulate_python_cli: true,")
+    }
+
+    #[test]
+    fn test_serialize_shared_library_search_paths() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            shared_library_search_paths: vec!["$ORIGIN/lib".into()],
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "shared_library_search_paths: vec![std::path::PathBuf::from(\"$ORIGIN/lib\")],",
+        )
+    }
+
+    #[test]
+    fn test_serialize_dunder_file_packages() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            dunder_file_packages: vec!["foo".to_string()],
+            dunder_file_root: Some("$ORIGIN/data".into()),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "dunder_file_packages: vec![\"foo\".to_string()],")?;
+        assert_contains(
+            &code,
+            "dunder_file_root: Some(std::path::PathBuf::from(\"$ORIGIN/data\")),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_runtime_hooks() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            runtime_hooks: vec![("foo".to_string(), "module.x = \"1\"\n".to_string())],
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            r#"runtime_hooks: vec![("foo".to_string(), "module.x = \"1\"\n".to_string())],"#,
+        )
+    }
+
+    #[test]
+    fn test_serialize_runtime_config_overrides() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            runtime_config_overrides: vec!["verbose".to_string()],
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "runtime_config_overrides: vec![\"verbose\".to_string()],",
+        )
+    }
+
+    #[test]
+    fn test_serialize_runtime_metadata() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            runtime_metadata: [
+                ("version".to_string(), "1.0".to_string()),
+                ("channel".to_string(), "beta\n".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            r#"runtime_metadata: vec![("channel".to_string(), "beta\n".to_string()), ("version".to_string(), "1.0".to_string())],"#,
+        )
+    }
+
+    #[test]
+    fn test_serialize_optimization_level_env() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            optimization_level_env: Some("MYAPP_OPTIMIZE".to_string()),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "optimization_level_env: Some(\"MYAPP_OPTIMIZE\".to_string()),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_extract_archive() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            extract_archive: Some("extract-archive".into()),
+            extract_directory: Some("myapp/0123".into()),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "extract_archive: Some(include_bytes!(r#\"extract-archive\"#)),",
+        )?;
+        assert_contains(
+            &code,
+            "extract_directory: Some(std::path::PathBuf::from(\"myapp/0123\")),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_splash_screen() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            splash_screen_image: Some("splash-screen.bmp".into()),
+            splash_screen_timeout: Some(Duration::from_secs(3)),
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            "splash_screen_image: Some(include_bytes!(r#\"splash-screen.bmp\"#)),",
+        )?;
+        assert_contains(
+            &code,
+            "splash_screen_timeout: Some(std::time::Duration::from_millis(3000)),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_output_capture() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            stderr_capture_path: Some("myapp.log".into()),
+            capture_max_size: Some(1024),
+            capture_backups: 2,
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(&code, "stdout_capture: None,")?;
+        assert_contains(
+            &code,
+            "stderr_capture: Some(pyembed::OutputCapture::File { path: std::path::PathBuf::from(\"myapp.log\"), max_size: Some(1024), backups: 2 }),",
+        )
+    }
+
+    #[test]
+    fn test_serialize_environment() -> Result<()> {
+        let config = PyembedPythonInterpreterConfig {
+            environment: vec![
+                EnvironmentMutation::Set("FOO".to_string(), "\"bar\"".to_string()),
+                EnvironmentMutation::Unset("BAZ".to_string()),
+                EnvironmentMutation::Prepend("PATH".to_string(), "$ORIGIN/bin".to_string()),
+            ],
+            working_directory: WorkingDirectoryPolicy::UserDataDirectory,
+            ..Default::default()
+        };
+
+        let code = config.to_oxidized_python_interpreter_config_rs()?;
+
+        assert_contains(
+            &code,
+            r#"environment: vec![pyembed::EnvironmentMutation::Set("FOO".to_string(), "\"bar\"".to_string()), pyembed::EnvironmentMutation::Unset("BAZ".to_string()), pyembed::EnvironmentMutation::Prepend("PATH".to_string(), "$ORIGIN/bin".to_string())],"#,
+        )?;
+        assert_contains(
+            &code,
+            "working_directory: pyembed::WorkingDirectoryPolicy::UserDataDirectory,",
+        )
+    }
+
     // TODO enable once CI has a linkable Python.
     #[test]
     #[ignore]
@@ -603,7 +1301,53 @@ mod tests {
             sys_meipass: true,
             terminfo_resolution: TerminfoResolution::Dynamic,
             tcl_library: Some("path".into()),
+            tk_library: Some("tk-path".into()),
             write_modules_directory_env: Some("env".into()),
+            allow_runtime_path_overrides: true,
+            fault_handler_path: Some("$ORIGIN/faults.log".into()),
+            tracemalloc_frames: Some(10),
+            crash_reports_directory: Some("$ORIGIN/crashes".into()),
+            crash_reports_upload_url: Some("https://example.com/crashes".into()),
+            update_feed_url: Some("https://example.com/updates.json".into()),
+            update_channel: Some("beta".into()),
+            update_public_key: Some("00".repeat(32)),
+            update_current_version: Some("1.0.0".into()),
+            daemonize: true,
+            pid_file: Some("$ORIGIN/myapp.pid".into()),
+            stdout_path: Some("$ORIGIN/myapp.log".into()),
+            stderr_path: Some("$ORIGIN/myapp.log".into()),
+            user_site_packages: Some("myapp/site-packages".into()),
+            run_pytest: true,
+            coverage_mode: true,
+            debugger_support: true,
+            emulate_python_cli: true,
+            shared_library_search_paths: vec!["$ORIGIN/lib".into()],
+            dunder_file_packages: vec!["foo".to_string()],
+            dunder_file_root: Some("$ORIGIN/data".into()),
+            runtime_hooks: vec![("foo".to_string(), "module.x = 1".to_string())],
+            optimization_level_env: Some("MYAPP_OPTIMIZE".to_string()),
+            extract_archive: None,
+            extract_directory: Some("myapp/extracted".into()),
+            environment: vec![EnvironmentMutation::Prepend(
+                "PATH".to_string(),
+                "$ORIGIN/bin".to_string(),
+            )],
+            working_directory: WorkingDirectoryPolicy::ExecutableDirectory,
+            app_name: Some("myapp".to_string()),
+            app_author: Some("ACME".to_string()),
+            single_instance: Some("myapp".to_string()),
+            single_instance_forward_args: true,
+            splash_screen_image: None,
+            splash_screen_timeout: Some(Duration::from_secs(5)),
+            stdout_capture_path: Some("myapp.log".into()),
+            stderr_capture_path: Some("myapp.log".into()),
+            capture_max_size: Some(1048576),
+            capture_backups: 3,
+            logging_bridge: true,
+            runtime_config_overrides: vec!["optimization_level".to_string()],
+            runtime_metadata: [("version".to_string(), "1.0".to_string())]
+                .into_iter()
+                .collect(),
             multiprocessing_auto_dispatch: false,
             multiprocessing_start_method: MultiprocessingStartMethod::Spawn,
         };
@@ -0,0 +1,385 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Materialize the resources of an executable as a virtualenv.
+
+Embedded resources are loaded from memory and aren't visible to tools
+like test runners and IDEs. A development environment is a virtualenv
+created from the Python distribution the executable embeds, with the
+executable's non-standard library resources installed in its
+`site-packages` directory and scripts for its console entry points. So
+developers can iterate against the same interpreter and packages using
+standard tools.
+*/
+
+use {
+    super::binary::PythonBinaryBuilder,
+    anyhow::{Context, Result},
+    duct::cmd,
+    python_packaging::{package_metadata::EntryPoint, resource_collection::PrePackagedResource},
+    serde::Deserialize,
+    slog::warn,
+    std::{
+        collections::BTreeSet,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::{FileData, FileEntry, FileManifest},
+};
+
+/// Python code printing information about the interpreter of a virtualenv.
+///
+/// It must run before anything is installed into the virtualenv so
+/// `modules` only contains modules provided by the distribution.
+const INTERPRETER_INFO_CODE: &str = "\
+import importlib.machinery, json, pkgutil, sys, sysconfig
+paths = sysconfig.get_paths()
+print(json.dumps({
+    'purelib': paths['purelib'],
+    'scripts': paths['scripts'],
+    'extension_suffix': importlib.machinery.EXTENSION_SUFFIXES[0],
+    'modules': sorted({m.name for m in pkgutil.iter_modules()} | set(sys.builtin_module_names)),
+}))
+";
+
+/// Entry point groups that scripts are generated for.
+const SCRIPT_GROUPS: &[&str] = &["console_scripts", "gui_scripts"];
+
+#[derive(Deserialize)]
+struct InterpreterInfo {
+    purelib: PathBuf,
+    scripts: PathBuf,
+    extension_suffix: String,
+    modules: BTreeSet<String>,
+}
+
+/// Resolve files to install into `site-packages` for resources.
+///
+/// Resources belonging to top-level packages in `stdlib` are skipped, as
+/// are resources which aren't Python modules or package data.
+pub fn site_packages_manifest<'a>(
+    resources: impl Iterator<Item = &'a PrePackagedResource>,
+    stdlib: &BTreeSet<String>,
+    extension_suffix: &str,
+) -> Result<FileManifest> {
+    let mut manifest = FileManifest::default();
+
+    for resource in resources {
+        let top_level = resource.name.split('.').next().unwrap_or_default();
+
+        if !resource.is_python_resource()
+            || resource.is_builtin_extension_module
+            || resource.is_frozen_module
+            || stdlib.contains(top_level)
+        {
+            continue;
+        }
+
+        let package_path = resource.name.replace('.', "/");
+        let module_path = if resource.is_package {
+            format!("{}/__init__", package_path)
+        } else {
+            package_path.clone()
+        };
+
+        let mut files: Vec<(String, &FileData)> = vec![];
+
+        if let Some(data) = resource.in_memory_source.as_ref().or_else(|| {
+            resource
+                .relative_path_module_source
+                .as_ref()
+                .map(|(_, data)| data)
+        }) {
+            files.push((format!("{}.py", module_path), data));
+        }
+
+        if let Some(data) = resource
+            .in_memory_extension_module_shared_library
+            .as_ref()
+            .or_else(|| {
+                resource
+                    .relative_path_extension_module_shared_library
+                    .as_ref()
+                    .map(|(_, data)| data)
+            })
+        {
+            files.push((format!("{}{}", package_path, extension_suffix), data));
+        }
+
+        for (key, data) in resource.in_memory_resources.iter().flatten() {
+            files.push((format!("{}/{}", package_path, key), data));
+        }
+
+        for (key, (_, data)) in resource.relative_path_package_resources.iter().flatten() {
+            files.push((format!("{}/{}", package_path, key), data));
+        }
+
+        // The version of in-memory distributions isn't known. Fortunately
+        // `importlib.metadata` finds `<name>.dist-info` directories too.
+        let dist_info = format!(
+            "{}.dist-info",
+            resource.name.to_lowercase().replace('-', "_")
+        );
+
+        for (key, data) in resource.in_memory_distribution_resources.iter().flatten() {
+            files.push((format!("{}/{}", dist_info, key), data));
+        }
+
+        for (key, (path, data)) in resource
+            .relative_path_distribution_resources
+            .iter()
+            .flatten()
+        {
+            let dir = path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|| dist_info.clone());
+
+            files.push((format!("{}/{}", dir, key), data));
+        }
+
+        for (path, data) in files {
+            manifest
+                .add_file_entry(&path, FileEntry::new_from_data(data.clone(), false))
+                .with_context(|| format!("adding {} from resource {}", path, resource.name))?;
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Source code of a script calling an entry point.
+pub fn entry_point_script(python_exe: &Path, entry_point: &EntryPoint) -> Result<String> {
+    Ok(format!(
+        "#!{}\n\
+         # -*- coding: utf-8 -*-\n\
+         import re\n\
+         import sys\n\
+         sys.argv[0] = re.sub(r\"(-script\\.pyw|\\.exe)?$\", \"\", sys.argv[0])\n\
+         {}",
+        python_exe.display(),
+        entry_point.run_code()?
+    ))
+}
+
+/// Create a virtualenv holding the resources of an executable.
+///
+/// Any existing content of `dest_path` is removed.
+pub fn materialize_devenv(
+    logger: &slog::Logger,
+    builder: &dyn PythonBinaryBuilder,
+    dest_path: &Path,
+) -> Result<()> {
+    let python_exe = builder.host_python_exe_path();
+
+    if builder.target_triple() != crate::environment::default_target_triple() {
+        warn!(
+            logger,
+            "executable targets {}; extension modules may not load on this machine",
+            builder.target_triple()
+        );
+    }
+
+    warn!(
+        logger,
+        "creating virtualenv in {} from {}",
+        dest_path.display(),
+        python_exe.display()
+    );
+    cmd(
+        python_exe,
+        &[
+            "-m".into(),
+            "venv".into(),
+            "--clear".into(),
+            "--without-pip".into(),
+            dest_path.as_os_str().to_owned(),
+        ],
+    )
+    .stderr_to_stdout()
+    .read()
+    .context("creating virtualenv")?;
+
+    let dest_path = std::fs::canonicalize(dest_path)?;
+    let venv_python = if cfg!(windows) {
+        dest_path.join("Scripts").join("python.exe")
+    } else {
+        dest_path.join("bin").join("python")
+    };
+
+    let info: InterpreterInfo = serde_json::from_str(
+        &cmd(&venv_python, &["-c", INTERPRETER_INFO_CODE])
+            .read()
+            .context("querying virtualenv interpreter")?,
+    )
+    .context("parsing virtualenv interpreter info")?;
+
+    let manifest = site_packages_manifest(
+        builder.iter_resources().map(|(_, r)| r),
+        &info.modules,
+        &info.extension_suffix,
+    )?;
+    manifest
+        .materialize_files(&info.purelib)
+        .with_context(|| format!("installing resources to {}", info.purelib.display()))?;
+    warn!(
+        logger,
+        "installed {} files to {}",
+        manifest.iter_files().count(),
+        info.purelib.display()
+    );
+
+    for (name, _) in builder
+        .iter_resources()
+        .filter(|(_, r)| r.is_shared_library)
+    {
+        warn!(
+            logger,
+            "shared library {} is not installed; extension modules using it may fail to load", name
+        );
+    }
+
+    let mut scripts = FileManifest::default();
+
+    for (_, resource) in builder.iter_resources() {
+        for entry_point in resource
+            .entry_points()?
+            .into_iter()
+            .filter(|ep| SCRIPT_GROUPS.contains(&ep.group.as_str()))
+        {
+            let source = entry_point_script(&venv_python, &entry_point)?;
+
+            if cfg!(windows) {
+                scripts.add_file_entry(
+                    format!("{}-script.py", entry_point.name),
+                    FileEntry::new_from_data(source.into_bytes(), false),
+                )?;
+                scripts.add_file_entry(
+                    format!("{}.cmd", entry_point.name),
+                    FileEntry::new_from_data(
+                        format!(
+                            "@\"%~dp0python.exe\" \"%~dp0{}-script.py\" %*\r\n",
+                            entry_point.name
+                        )
+                        .into_bytes(),
+                        false,
+                    ),
+                )?;
+            } else {
+                scripts.add_file_entry(
+                    &entry_point.name,
+                    FileEntry::new_from_data(source.into_bytes(), true),
+                )?;
+            }
+
+            warn!(logger, "installed script {}", entry_point.name);
+        }
+    }
+
+    scripts
+        .materialize_files(&info.scripts)
+        .with_context(|| format!("installing scripts to {}", info.scripts.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::BTreeMap};
+
+    fn data(s: &str) -> FileData {
+        s.as_bytes().to_vec().into()
+    }
+
+    #[test]
+    fn test_site_packages_manifest() -> Result<()> {
+        let resources = vec![
+            PrePackagedResource {
+                name: "myapp".to_string(),
+                is_module: true,
+                is_package: true,
+                in_memory_source: Some(data("import json")),
+                in_memory_resources: Some(BTreeMap::from([(
+                    "data/config.json".to_string(),
+                    data("{}"),
+                )])),
+                in_memory_distribution_resources: Some(BTreeMap::from([(
+                    "METADATA".to_string(),
+                    data("Name: myapp"),
+                )])),
+                ..PrePackagedResource::default()
+            },
+            PrePackagedResource {
+                name: "myapp.cli".to_string(),
+                is_module: true,
+                relative_path_module_source: Some(("lib".to_string(), data("def main(): pass"))),
+                ..PrePackagedResource::default()
+            },
+            PrePackagedResource {
+                name: "myapp._speedups".to_string(),
+                is_module: true,
+                is_extension_module: true,
+                in_memory_extension_module_shared_library: Some(data("ELF")),
+                ..PrePackagedResource::default()
+            },
+            PrePackagedResource {
+                name: "json".to_string(),
+                is_module: true,
+                is_package: true,
+                in_memory_source: Some(data("")),
+                ..PrePackagedResource::default()
+            },
+            PrePackagedResource {
+                name: "libfoo".to_string(),
+                is_shared_library: true,
+                in_memory_shared_library: Some(data("ELF")),
+                ..PrePackagedResource::default()
+            },
+        ];
+
+        let stdlib = BTreeSet::from(["json".to_string()]);
+        let manifest = site_packages_manifest(resources.iter(), &stdlib, ".so")?;
+
+        assert_eq!(
+            manifest
+                .iter_files()
+                .map(|f| f.path_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "myapp/__init__.py",
+                "myapp/_speedups.so",
+                "myapp/cli.py",
+                "myapp/data/config.json",
+                "myapp.dist-info/METADATA",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_point_script() -> Result<()> {
+        let resource = PrePackagedResource {
+            name: "myapp".to_string(),
+            in_memory_distribution_resources: Some(BTreeMap::from([(
+                "entry_points.txt".to_string(),
+                data("[console_scripts]\nmyapp = myapp.cli:Main.run\n"),
+            )])),
+            ..PrePackagedResource::default()
+        };
+
+        let entry_points = resource.entry_points()?;
+        assert_eq!(entry_points.len(), 1);
+
+        let script = entry_point_script(Path::new("/venv/bin/python"), &entry_points[0])?;
+        assert!(script.starts_with("#!/venv/bin/python\n"));
+        assert!(script.ends_with("\nfrom myapp.cli import Main\nsys.exit(Main.run())\n"));
+
+        let entry_point = EntryPoint::from_value("console_scripts", "x", "x")?;
+        assert!(entry_point_script(Path::new("python"), &entry_point).is_err());
+
+        Ok(())
+    }
+}
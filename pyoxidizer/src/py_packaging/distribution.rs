@@ -30,7 +30,7 @@ use {
         path::{Path, PathBuf},
         sync::{Arc, Mutex},
     },
-    tugger_common::http::get_http_client,
+    tugger_common::http::{ensure_online, fetch_url},
     tugger_file_manifest::FileEntry,
     url::Url,
     uuid::Uuid,
@@ -235,11 +235,15 @@ pub trait PythonDistribution {
     /// The returned list of files contains relative file names and the locations
     /// of file content. If the files are installed in a new directory, it should
     /// be possible to use that directory joined with `tcl_library_path_directory`
-    /// as the value of `TCL_LIBRARY`.
+    /// as the value of `TCL_LIBRARY` and joined with `tk_library_path_directory`
+    /// as the value of `TK_LIBRARY`.
     fn tcl_files(&self) -> Result<Vec<(PathBuf, FileEntry)>>;
 
     /// The name of the directory to use for `TCL_LIBRARY`
     fn tcl_library_path_directory(&self) -> Option<String>;
+
+    /// The name of the directory to use for `TK_LIBRARY`
+    fn tk_library_path_directory(&self) -> Option<String>;
 }
 
 /// Multiple threads or processes could race to extract the archive.
@@ -316,12 +320,10 @@ pub fn download_distribution(url: &str, sha256: &str, cache_dir: &Path) -> Resul
         }
     }
 
-    let mut data: Vec<u8> = Vec::new();
+    ensure_online(url)?;
 
     println!("downloading {}", u);
-    let client = get_http_client()?;
-    let mut response = client.get(u.as_str()).send()?;
-    response.read_to_end(&mut data)?;
+    let data = fetch_url(u.as_str())?;
 
     let mut hasher = Sha256::new();
     hasher.update(&data);
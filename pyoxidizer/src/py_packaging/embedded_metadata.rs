@@ -0,0 +1,484 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Metadata embedded in built binaries.
+
+Binaries built by PyOxidizer embed a small JSON document describing how
+they were built, delimited by marker strings so it can be found by scanning
+the binary. This module defines that document and functionality for locating
+it and packed resources data in existing binaries.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packed_resources::{
+        load_resources, packed_resources_length, Resource, HEADER_V3, HEADER_V4,
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Marks the start of embedded build metadata.
+const METADATA_START: &[u8] = b"PYOXIDIZER_BUILD_METADATA_V1:";
+
+/// Marks the end of embedded build metadata.
+const METADATA_END: &[u8] = b":END_PYOXIDIZER_BUILD_METADATA";
+
+/// Describes how a binary was built.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BuildMetadata {
+    /// Version of PyOxidizer that produced the binary.
+    pub pyoxidizer_version: String,
+
+    /// Rust target triple the binary was built for.
+    pub target_triple: String,
+
+    /// Name of the Python implementation. e.g. `CPython`.
+    pub python_implementation: String,
+
+    /// `X.Y` version of the embedded Python interpreter.
+    pub python_version: String,
+
+    /// Sources of packed resources data.
+    ///
+    /// `memory` denotes data embedded in the binary. Other values are paths,
+    /// which may contain `$ORIGIN`.
+    pub packed_resources: Vec<String>,
+
+    /// Notable interpreter configuration settings.
+    pub config: BTreeMap<String, String>,
+}
+
+impl BuildMetadata {
+    /// Serialize to bytes suitable for embedding in a binary.
+    pub fn to_embedded_bytes(&self) -> Result<Vec<u8>> {
+        let mut data = METADATA_START.to_vec();
+        data.extend(serde_json::to_vec(self).context("serializing build metadata")?);
+        data.extend(METADATA_END);
+
+        Ok(data)
+    }
+
+    /// Obtain Rust source code defining a static holding this metadata.
+    pub fn to_rust_static(&self, name: &str) -> Result<String> {
+        let escaped = self
+            .to_embedded_bytes()?
+            .iter()
+            .map(|b| format!("\\x{:02x}", b))
+            .collect::<String>();
+
+        Ok(format!("static {}: &[u8] = b\"{}\";\n", name, escaped))
+    }
+
+    /// Find build metadata embedded in binary data.
+    ///
+    /// Returns `None` if no metadata is present.
+    pub fn find_in_data(data: &[u8]) -> Option<Self> {
+        find_all(data, METADATA_START).find_map(|start| {
+            let payload = &data[start + METADATA_START.len()..];
+            let end = find_all(payload, METADATA_END).next()?;
+
+            serde_json::from_slice(&payload[..end]).ok()
+        })
+    }
+}
+
+/// Find offsets of all occurrences of `needle` in `data`.
+fn find_all<'a>(data: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    data.windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(|(offset, _)| offset)
+}
+
+/// Whether data holds a well-formed packed resources data structure.
+fn is_packed_resources(data: &[u8]) -> bool {
+    let resources = match load_resources(data) {
+        Ok(resources) => resources,
+        Err(_) => return false,
+    };
+
+    let expected = resources.expected_resources_count();
+    let mut count = 0;
+
+    for resource in resources {
+        if resource.is_err() {
+            return false;
+        }
+
+        count += 1;
+    }
+
+    expected > 0 && count == expected
+}
+
+/// Find packed resources data embedded in binary data.
+///
+/// Returns slices beginning at the header of each packed resources data
+/// structure that parses successfully. Version 4 data declares its length, so
+/// its slices are exact. Version 3 slices extend to the end of `data`, which
+/// the parser tolerates.
+pub fn find_packed_resources(data: &[u8]) -> Vec<&[u8]> {
+    let mut offsets = find_all(data, HEADER_V3)
+        .chain(find_all(data, HEADER_V4))
+        .collect::<Vec<_>>();
+    offsets.sort_unstable();
+
+    offsets
+        .into_iter()
+        .map(|offset| {
+            let candidate = &data[offset..];
+
+            packed_resources_length(candidate)
+                .ok()
+                .and_then(|length| candidate.get(..length))
+                .unwrap_or(candidate)
+        })
+        .filter(|candidate| is_packed_resources(candidate))
+        .collect()
+}
+
+/// Resolve the path to a packed resources file referenced by an executable.
+///
+/// `$ORIGIN` is expanded to the directory of the executable.
+pub fn resolve_packed_resources_path(exe: &Path, path: &str) -> PathBuf {
+    let origin = exe.parent().unwrap_or_else(|| Path::new("."));
+
+    PathBuf::from(path.replace("$ORIGIN", &format!("{}", origin.display())))
+}
+
+/// Load packed resources used by an executable.
+///
+/// Resources embedded in the executable are loaded along with resources
+/// in external files referenced by its build metadata.
+pub fn load_exe_resources(exe: &Path, data: &[u8]) -> Result<Vec<Resource<'static, u8>>> {
+    let mut sources = find_packed_resources(data)
+        .into_iter()
+        .map(|data| data.to_vec())
+        .collect::<Vec<_>>();
+
+    if let Some(metadata) = BuildMetadata::find_in_data(data) {
+        for source in metadata.packed_resources.iter().filter(|s| *s != "memory") {
+            let path = resolve_packed_resources_path(exe, source);
+
+            sources.push(
+                std::fs::read(&path)
+                    .with_context(|| format!("reading packed resources {}", path.display()))?,
+            );
+        }
+    }
+
+    let mut resources = vec![];
+
+    for source in sources {
+        for resource in load_resources(&source).map_err(|e| anyhow!("{}", e))? {
+            resources.push(resource.map_err(|e| anyhow!("{}", e))?.to_owned());
+        }
+    }
+
+    Ok(resources)
+}
+
+/// Obtain the in-memory sizes of data in a resource, keyed by kind.
+pub fn resource_data_sizes(resource: &Resource<u8>) -> BTreeMap<&'static str, usize> {
+    let mut sizes = BTreeMap::new();
+
+    for (kind, data) in [
+        ("source", &resource.in_memory_source),
+        ("bytecode", &resource.in_memory_bytecode),
+        ("bytecode-opt1", &resource.in_memory_bytecode_opt1),
+        ("bytecode-opt2", &resource.in_memory_bytecode_opt2),
+        (
+            "extension-module",
+            &resource.in_memory_extension_module_shared_library,
+        ),
+        ("shared-library", &resource.in_memory_shared_library),
+        ("file-data", &resource.file_data_embedded),
+    ] {
+        if let Some(data) = data {
+            sizes.insert(kind, data.len());
+        }
+    }
+
+    for (kind, data) in [
+        ("package-resources", &resource.in_memory_package_resources),
+        (
+            "distribution-resources",
+            &resource.in_memory_distribution_resources,
+        ),
+    ] {
+        if let Some(data) = data {
+            sizes.insert(kind, data.values().map(|v| v.len()).sum());
+        }
+    }
+
+    sizes
+}
+
+/// Whether a resource is selected by an allow-list of names.
+///
+/// An empty allow-list selects all resources. Entries select resources having
+/// the same name as well as modules and files beneath them.
+pub fn resource_allowed(name: &str, allow: &[String]) -> bool {
+    allow.is_empty()
+        || allow.iter().any(|allowed| {
+            name.strip_prefix(allowed.as_str()).map_or(false, |rest| {
+                rest.is_empty() || rest.starts_with('.') || rest.starts_with('/')
+            })
+        })
+}
+
+/// Obtain a [FileManifest] holding the in-memory data of resources.
+///
+/// Modules are laid out as on the filesystem. Bytecode is written next to
+/// source as `.bytecode` files holding marshalled code objects without a
+/// `.pyc` header. `extension_suffix` is the filename suffix for extension
+/// modules. Only resources selected by `allow` are added.
+pub fn resources_file_manifest(
+    resources: &[Resource<u8>],
+    allow: &[String],
+    extension_suffix: &str,
+) -> Result<FileManifest> {
+    let mut manifest = FileManifest::default();
+
+    for resource in resources
+        .iter()
+        .filter(|r| resource_allowed(&r.name, allow))
+    {
+        let module_path = if resource.is_python_package {
+            format!("{}/__init__", resource.name.replace('.', "/"))
+        } else {
+            resource.name.replace('.', "/")
+        };
+        let package_path = resource.name.replace('.', "/");
+
+        let mut files = vec![];
+
+        for (suffix, data) in [
+            (".py", &resource.in_memory_source),
+            (".bytecode", &resource.in_memory_bytecode),
+            (".opt-1.bytecode", &resource.in_memory_bytecode_opt1),
+            (".opt-2.bytecode", &resource.in_memory_bytecode_opt2),
+            (
+                extension_suffix,
+                &resource.in_memory_extension_module_shared_library,
+            ),
+        ] {
+            if let Some(data) = data {
+                files.push((format!("{}{}", module_path, suffix), data, false));
+            }
+        }
+
+        if let Some(data) = &resource.in_memory_shared_library {
+            files.push((resource.name.to_string(), data, true));
+        }
+
+        if let Some(data) = &resource.file_data_embedded {
+            files.push((resource.name.to_string(), data, resource.file_executable));
+        }
+
+        for (key, data) in resource.in_memory_package_resources.iter().flatten() {
+            files.push((format!("{}/{}", package_path, key), data, false));
+        }
+
+        for (key, data) in resource.in_memory_distribution_resources.iter().flatten() {
+            files.push((format!("{}.dist-info/{}", resource.name, key), data, false));
+        }
+
+        for (path, data, executable) in files {
+            manifest
+                .add_file_entry(&path, FileEntry::new_from_data(data.to_vec(), executable))
+                .with_context(|| format!("adding {} from resource {}", path, resource.name))?;
+        }
+    }
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        python_packed_resources::{write_packed_resources_v3, write_packed_resources_v4},
+        std::borrow::Cow,
+    };
+
+    fn metadata() -> BuildMetadata {
+        BuildMetadata {
+            pyoxidizer_version: "0.1.0".to_string(),
+            target_triple: "x86_64-unknown-linux-gnu".to_string(),
+            python_implementation: "CPython".to_string(),
+            python_version: "3.9".to_string(),
+            packed_resources: vec!["memory".to_string()],
+            config: [("sys_frozen".to_string(), "true".to_string())]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() -> Result<()> {
+        let metadata = metadata();
+
+        let mut data = b"\x7fELF garbage".to_vec();
+        data.extend(METADATA_START);
+        data.extend(b"not json");
+        data.extend(METADATA_END);
+        data.extend(metadata.to_embedded_bytes()?);
+        data.extend(b"trailing garbage");
+
+        assert_eq!(BuildMetadata::find_in_data(&data), Some(metadata.clone()));
+        assert_eq!(BuildMetadata::find_in_data(b"no metadata"), None);
+
+        let code = metadata.to_rust_static("METADATA")?;
+        assert!(code.starts_with("static METADATA: &[u8] = b\"\\x50\\x59"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_packed_resources() -> Result<()> {
+        let resources = vec![Resource {
+            name: Cow::from("foo"),
+            is_python_module: true,
+            in_memory_source: Some(Cow::Borrowed(b"import io".as_ref())),
+            ..Resource::default()
+        }];
+
+        let mut packed = vec![];
+        write_packed_resources_v3(&resources, &mut packed, None)?;
+
+        // A bare header, as found in the code parsing resources, is ignored.
+        let mut data = b"leading".to_vec();
+        data.extend(HEADER_V3);
+        data.extend(b"\x00\x00\x00\x00garbage");
+        data.extend(&packed);
+        data.extend(b"trailing");
+
+        let found = find_packed_resources(&data);
+        assert_eq!(found.len(), 1);
+
+        let parsed = load_resources(found[0])
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(parsed, resources);
+        assert_eq!(
+            resource_data_sizes(&parsed[0])
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![("source", 9)]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_packed_resources_v4() -> Result<()> {
+        let resources = vec![Resource {
+            name: Cow::from("foo"),
+            is_python_module: true,
+            in_memory_source: Some(Cow::Borrowed(b"import io".as_ref())),
+            ..Resource::default()
+        }];
+
+        let mut packed = vec![];
+        write_packed_resources_v4(&resources, &mut packed, None)?;
+
+        let mut data = b"leading".to_vec();
+        data.extend(HEADER_V4);
+        data.extend(b"garbage");
+        data.extend(&packed);
+        data.extend(b"trailing");
+
+        // Version 4 data declares its length, so trailing data isn't included.
+        let found = find_packed_resources(&data);
+        assert_eq!(found, vec![packed.as_slice()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_allowed() {
+        let allow = vec!["foo".to_string(), "data/config.json".to_string()];
+
+        assert!(resource_allowed("anything", &[]));
+        assert!(resource_allowed("foo", &allow));
+        assert!(resource_allowed("foo.bar", &allow));
+        assert!(!resource_allowed("foobar", &allow));
+        assert!(resource_allowed("data/config.json", &allow));
+        assert!(!resource_allowed("data/other.json", &allow));
+    }
+
+    #[test]
+    fn test_resources_file_manifest() -> Result<()> {
+        let resources = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_package: true,
+                in_memory_source: Some(Cow::Borrowed(b"import io".as_ref())),
+                in_memory_bytecode: Some(Cow::Borrowed(b"bytecode".as_ref())),
+                in_memory_package_resources: Some(
+                    [(Cow::from("data.txt"), Cow::Borrowed(b"data".as_ref()))]
+                        .into_iter()
+                        .collect(),
+                ),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("foo.ext"),
+                is_python_extension_module: true,
+                in_memory_extension_module_shared_library: Some(Cow::Borrowed(b"elf".as_ref())),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("bar"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::Borrowed(b"".as_ref())),
+                ..Resource::default()
+            },
+        ];
+
+        let manifest = resources_file_manifest(&resources, &[], ".so")?;
+        assert_eq!(
+            manifest
+                .iter_files()
+                .map(|f| f.path_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "bar.py",
+                "foo/__init__.bytecode",
+                "foo/__init__.py",
+                "foo/data.txt",
+                "foo/ext.so"
+            ]
+        );
+
+        let manifest = resources_file_manifest(&resources, &["bar".to_string()], ".so")?;
+        assert_eq!(manifest.iter_files().count(), 1);
+
+        let malicious = vec![Resource {
+            name: Cow::from("../escape"),
+            is_utf8_filename_data: true,
+            file_data_embedded: Some(Cow::Borrowed(b"".as_ref())),
+            ..Resource::default()
+        }];
+        assert!(resources_file_manifest(&malicious, &[], ".so").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_packed_resources_path() {
+        assert_eq!(
+            resolve_packed_resources_path(Path::new("/opt/app/myapp"), "$ORIGIN/packed-resources"),
+            PathBuf::from("/opt/app/packed-resources")
+        );
+    }
+}
@@ -5,7 +5,13 @@
 /*! Functionality for embedding Python in a binary. */
 
 use {
-    crate::py_packaging::config::PyembedPythonInterpreterConfig,
+    crate::{
+        environment::PYOXIDIZER_VERSION,
+        py_packaging::{
+            config::{PyembedPackedResourcesSource, PyembedPythonInterpreterConfig},
+            embedded_metadata::BuildMetadata,
+        },
+    },
     anyhow::{anyhow, Context, Result},
     pyo3_build_config::{
         BuildFlags, InterpreterConfig as PyO3InterpreterConfig, PythonImplementation, PythonVersion,
@@ -224,6 +230,12 @@ impl From<LinkStaticLibraryData> for LibpythonLinkSettings {
 /// Filename of artifact containing the default PythonInterpreterConfig.
 pub const DEFAULT_PYTHON_CONFIG_FILENAME: &str = "default_python_config.rs";
 
+/// Filename of artifact containing files extracted by self-extracting binaries.
+pub const EXTRACT_ARCHIVE_FILENAME: &str = "extract-archive.tar.zst";
+
+/// Filename of artifact containing the splash screen image.
+pub const SPLASH_SCREEN_IMAGE_FILENAME: &str = "splash-screen.bmp";
+
 /// Holds context necessary to embed Python in a binary.
 pub struct EmbeddedPythonContext<'a> {
     /// The configuration for the embedded interpreter.
@@ -238,6 +250,22 @@ pub struct EmbeddedPythonContext<'a> {
     /// Extra files to install next to produced binary.
     pub extra_files: FileManifest,
 
+    /// Packed resources data to write to a file shared by multiple binaries.
+    ///
+    /// The path is relative to the directory of the produced binary and may
+    /// reference parent directories.
+    pub shared_resources_file: Option<(PathBuf, Vec<u8>)>,
+
+    /// Archive of files extracted by the binary at run-time.
+    ///
+    /// The path is relative to the directory build artifacts are written to.
+    pub extract_archive: Option<(PathBuf, Vec<u8>)>,
+
+    /// Image shown by the binary while the interpreter initializes.
+    ///
+    /// The path is relative to the directory build artifacts are written to.
+    pub splash_screen_image: Option<(PathBuf, Vec<u8>)>,
+
     /// Rust target triple for the host we are running on.
     pub host_triple: String,
 
@@ -323,16 +351,67 @@ impl<'a> EmbeddedPythonContext<'a> {
         Ok(())
     }
 
+    /// Ensure the archive of files extracted at run-time is written.
+    pub fn write_extract_archive(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+        if let Some((path, data)) = &self.extract_archive {
+            let dest_path = dest_dir.as_ref().join(path);
+
+            std::fs::write(&dest_path, data)
+                .with_context(|| format!("writing {}", dest_path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Ensure the splash screen image is written.
+    pub fn write_splash_screen_image(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
+        if let Some((path, data)) = &self.splash_screen_image {
+            let dest_path = dest_dir.as_ref().join(path);
+
+            std::fs::write(&dest_path, data)
+                .with_context(|| format!("writing {}", dest_path.display()))?;
+        }
+
+        Ok(())
+    }
+
     /// Ensure files required by libpython are written.
     pub fn write_libpython(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
         self.link_settings
             .write_files(dest_dir.as_ref(), &self.target_triple)
     }
 
+    /// Obtain metadata describing the binary being built.
+    pub fn build_metadata(&self) -> BuildMetadata {
+        BuildMetadata {
+            pyoxidizer_version: PYOXIDIZER_VERSION.to_string(),
+            target_triple: self.target_triple.clone(),
+            python_implementation: format!("{:?}", self.python_implementation),
+            python_version: format!(
+                "{}.{}",
+                self.python_version.major, self.python_version.minor
+            ),
+            packed_resources: self
+                .config
+                .packed_resources
+                .iter()
+                .map(|source| match source {
+                    PyembedPackedResourcesSource::MemoryIncludeBytes(_) => "memory".to_string(),
+                    PyembedPackedResourcesSource::MemoryMappedPath(path) => {
+                        format!("{}", path.display())
+                    }
+                })
+                .collect(),
+            config: self.config.metadata_flags(),
+        }
+    }
+
     /// Write the file containing the default interpreter configuration Rust struct.
     pub fn write_interpreter_config_rs(&self, dest_dir: impl AsRef<Path>) -> Result<()> {
-        self.config
-            .write_default_python_config_rs(self.interpreter_config_rs_path(&dest_dir))?;
+        self.config.write_default_python_config_rs(
+            self.interpreter_config_rs_path(&dest_dir),
+            &self.build_metadata(),
+        )?;
 
         Ok(())
     }
@@ -353,6 +432,10 @@ impl<'a> EmbeddedPythonContext<'a> {
     pub fn write_files(&self, dest_dir: &Path) -> Result<()> {
         self.write_packed_resources(&dest_dir)
             .context("write_packed_resources()")?;
+        self.write_extract_archive(&dest_dir)
+            .context("write_extract_archive()")?;
+        self.write_splash_screen_image(&dest_dir)
+            .context("write_splash_screen_image()")?;
         self.write_libpython(&dest_dir)
             .context("write_libpython()")?;
         self.write_interpreter_config_rs(&dest_dir)
@@ -0,0 +1,200 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Packaging hooks for Python packages known to need special handling.
+
+Some popular packages don't work when imported from memory or rely on
+modules they import in ways that can't be seen. A packaging hook describes
+the adjustments a package needs: installing its resources on the
+filesystem, packaging modules it imports implicitly, Python code
+patching it at run-time, and deploying the Qt files of Qt bindings.
+
+A curated set of hooks is built in. The set is versioned: each built-in hook
+records the version of the set it was introduced in. So configurations can
+pin the version they were written against and packaging doesn't change
+when hooks are added.
+*/
+
+use std::collections::BTreeMap;
+
+/// The newest version of the built-in packaging hooks.
+pub const PACKAGING_HOOKS_VERSION: u32 = 2;
+
+/// Python code making `jinja2.PackageLoader` load templates from memory.
+///
+/// `PackageLoader` requires templates to exist on the filesystem or in a zip
+/// file. If that fails, templates are read via the resource reader of the
+/// package's loader instead.
+const JINJA2_LOADERS_HOOK: &str = "\
+import importlib.util
+import posixpath
+
+
+class PackageLoader(module.PackageLoader):
+    def __init__(self, package_name, package_path='templates', encoding='utf-8'):
+        self._reader = None
+
+        try:
+            super().__init__(package_name, package_path, encoding)
+            return
+        except ValueError:
+            spec = importlib.util.find_spec(package_name)
+            if spec is None or not hasattr(spec.loader, 'get_resource_reader'):
+                raise
+            reader = spec.loader.get_resource_reader(package_name)
+            if reader is None:
+                raise
+
+        self.package_name = package_name
+        self.package_path = posixpath.normpath(package_path).strip('/')
+        if self.package_path == '.':
+            self.package_path = ''
+        self.encoding = encoding
+        self._reader = reader
+
+    def _resource_name(self, template):
+        pieces = module.split_template_path(template)
+        return posixpath.join(self.package_path, *pieces)
+
+    def get_source(self, environment, template):
+        if self._reader is None:
+            return super().get_source(environment, template)
+
+        try:
+            with self._reader.open_resource(self._resource_name(template)) as fh:
+                source = fh.read()
+        except OSError:
+            raise module.TemplateNotFound(template)
+
+        return source.decode(self.encoding), None, lambda: True
+
+    def list_templates(self):
+        if self._reader is None:
+            return super().list_templates()
+
+        prefix = self.package_path + '/' if self.package_path else ''
+        names = self._reader.contents()
+        return sorted(name[len(prefix):] for name in names if name.startswith(prefix))
+
+
+module.PackageLoader = PackageLoader
+";
+
+/// Adjustments to the packaging of a Python package.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PackagingHook {
+    /// Name of the top-level package the hook applies to.
+    pub package: String,
+
+    /// Whether modules and resources of the package are installed on the filesystem.
+    ///
+    /// Packages locating their data via `__file__` need this.
+    pub filesystem_resources: bool,
+
+    /// Modules the package imports in ways that can't be seen.
+    ///
+    /// These are added from the Python distribution if missing.
+    pub hidden_imports: Vec<String>,
+
+    /// Python code to run after a module is imported, keyed by module name.
+    ///
+    /// The imported module is available to the code as `module`.
+    pub runtime_hooks: BTreeMap<String, String>,
+
+    /// Whether the package is a Qt binding whose Qt files are deployed.
+    ///
+    /// Files of Qt modules the application doesn't import are excluded and a
+    /// `qt.conf` locating Qt's plugins and QML imports is written next to
+    /// the executable. See the `qt` module.
+    pub qt_deployment: bool,
+}
+
+/// Obtain the built-in packaging hooks introduced up to and including a version.
+///
+/// Version `0` has no hooks.
+pub fn builtin_packaging_hooks(version: u32) -> Vec<PackagingHook> {
+    [
+        (
+            1,
+            PackagingHook {
+                package: "certifi".to_string(),
+                // certifi.where() returns the path of its CA bundle.
+                filesystem_resources: true,
+                ..Default::default()
+            },
+        ),
+        (
+            1,
+            PackagingHook {
+                package: "jinja2".to_string(),
+                runtime_hooks: [(
+                    "jinja2.loaders".to_string(),
+                    JINJA2_LOADERS_HOOK.to_string(),
+                )]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+        ),
+        (
+            1,
+            PackagingHook {
+                package: "numpy".to_string(),
+                filesystem_resources: true,
+                hidden_imports: vec!["ctypes".to_string(), "secrets".to_string()],
+                ..Default::default()
+            },
+        ),
+        (
+            1,
+            PackagingHook {
+                package: "pandas".to_string(),
+                filesystem_resources: true,
+                hidden_imports: vec!["csv".to_string(), "decimal".to_string()],
+                ..Default::default()
+            },
+        ),
+        (
+            2,
+            PackagingHook {
+                package: "PyQt6".to_string(),
+                // Qt loads plugins and QML imports from the filesystem.
+                filesystem_resources: true,
+                qt_deployment: true,
+                ..Default::default()
+            },
+        ),
+        (
+            2,
+            PackagingHook {
+                package: "PySide6".to_string(),
+                filesystem_resources: true,
+                qt_deployment: true,
+                ..Default::default()
+            },
+        ),
+    ]
+    .into_iter()
+    .filter(|(since, _)| *since <= version)
+    .map(|(_, hook)| hook)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_packaging_hooks() {
+        assert!(builtin_packaging_hooks(0).is_empty());
+        assert_eq!(builtin_packaging_hooks(1).len(), 4);
+
+        let hooks = builtin_packaging_hooks(PACKAGING_HOOKS_VERSION);
+        assert_eq!(
+            hooks.iter().map(|h| h.package.as_str()).collect::<Vec<_>>(),
+            vec!["certifi", "jinja2", "numpy", "pandas", "PyQt6", "PySide6"]
+        );
+    }
+}
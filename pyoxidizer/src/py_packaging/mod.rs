@@ -9,13 +9,25 @@ This module tree holds functionality that is centered around Python.
 */
 
 pub mod binary;
+pub mod cargo_options;
+pub mod conda;
 pub mod config;
+pub mod devenv;
 pub mod distribution;
 pub mod distutils;
+pub mod embedded_metadata;
 pub mod embedding;
 pub mod filtering;
+pub mod hooks;
 pub mod libpython;
 pub mod packaging_tool;
+pub mod portable;
+pub mod post_processing;
+pub mod qt;
 pub mod resource;
+pub mod resource_compilers;
+pub mod size_report;
 pub mod standalone_builder;
 pub mod standalone_distribution;
+pub mod wheel_resolver;
+pub mod windows_resources;
@@ -0,0 +1,222 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Portable application directory layouts. */
+
+use {
+    anyhow::{anyhow, Result},
+    std::{
+        collections::BTreeMap,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Directory a portable application launcher runs the application in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorkingDirectoryPolicy {
+    /// Keep the working directory the launcher was started in.
+    Inherit,
+
+    /// Use the directory of the launcher.
+    Launcher,
+
+    /// Use the directory of the application executable.
+    Application,
+
+    /// Use the data directory of the application.
+    Data,
+}
+
+impl ToString for WorkingDirectoryPolicy {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Inherit => "inherit",
+            Self::Launcher => "launcher",
+            Self::Application => "app",
+            Self::Data => "data",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<&str> for WorkingDirectoryPolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "inherit" => Ok(Self::Inherit),
+            "launcher" => Ok(Self::Launcher),
+            "app" => Ok(Self::Application),
+            "data" => Ok(Self::Data),
+            _ => Err(format!(
+                "{} is not a valid working directory policy; must be 'inherit', 'launcher', 'app' or 'data'",
+                value
+            )),
+        }
+    }
+}
+
+/// Describes a small executable launching an application of a portable layout.
+///
+/// Portable layouts are directories holding everything an application
+/// needs, including its settings, so they can be distributed as zip files
+/// and run without installation.
+#[derive(Clone, Debug)]
+pub struct PortableLauncher {
+    /// Path of the executable to run, relative to the launcher.
+    pub exe_path: PathBuf,
+
+    /// Directory to store per-user settings in, relative to the launcher.
+    ///
+    /// Environment variables defining per-user settings directories, like
+    /// `APPDATA` on Windows and `XDG_CONFIG_HOME` elsewhere, point into it.
+    pub data_dir: Option<PathBuf>,
+
+    /// Environment variables to define.
+    ///
+    /// `$ORIGIN` in values expands to the directory of the launcher.
+    pub environment: BTreeMap<String, String>,
+
+    /// Directory to run the executable in.
+    pub working_directory: WorkingDirectoryPolicy,
+
+    /// Value of the `windows_subsystem` Rust attribute of the launcher.
+    pub windows_subsystem: String,
+}
+
+/// Obtain the components of a relative path as strings.
+fn path_components(path: &Path) -> Result<Vec<String>> {
+    path.components()
+        .map(|c| match c {
+            Component::Normal(c) => Ok(c.to_string_lossy().to_string()),
+            Component::CurDir => Ok(".".to_string()),
+            Component::ParentDir => Ok("..".to_string()),
+            _ => Err(anyhow!("{} is not a relative path", path.display())),
+        })
+        .collect()
+}
+
+/// Render strings as a comma delimited list of Rust string literals.
+fn rust_str_literals<'a>(values: impl Iterator<Item = &'a str>) -> String {
+    values
+        .map(|v| format!("{:?}", v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl PortableLauncher {
+    /// Ensure settings are consistent.
+    pub fn validate(&self) -> Result<()> {
+        if self.working_directory == WorkingDirectoryPolicy::Data && self.data_dir.is_none() {
+            return Err(anyhow!(
+                "working directory policy of 'data' requires a data directory"
+            ));
+        }
+
+        path_components(&self.exe_path)?;
+        if let Some(path) = &self.data_dir {
+            path_components(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Obtain the values of the placeholders of the launcher source template.
+    ///
+    /// Values are Rust expressions.
+    pub fn template_values(&self) -> Result<BTreeMap<&'static str, String>> {
+        self.validate()?;
+
+        let exe_path = path_components(&self.exe_path)?;
+        let data_dir = match &self.data_dir {
+            Some(path) => format!(
+                "Some(&[{}])",
+                rust_str_literals(path_components(path)?.iter().map(|s| s.as_str()))
+            ),
+            None => "None".to_string(),
+        };
+
+        let mut values = BTreeMap::new();
+        values.insert("windows_subsystem", self.windows_subsystem.clone());
+        values.insert(
+            "exe_path",
+            rust_str_literals(exe_path.iter().map(|s| s.as_str())),
+        );
+        values.insert("data_dir", data_dir);
+        values.insert(
+            "environment",
+            self.environment
+                .iter()
+                .map(|(k, v)| format!("({:?}, {:?})", k, v))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+        values.insert(
+            "working_directory",
+            format!("{:?}", self.working_directory.to_string()),
+        );
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launcher() -> PortableLauncher {
+        PortableLauncher {
+            exe_path: PathBuf::from("App").join("myapp.exe"),
+            data_dir: Some(PathBuf::from("Data")),
+            environment: BTreeMap::new(),
+            working_directory: WorkingDirectoryPolicy::Application,
+            windows_subsystem: "console".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_working_directory_policy() {
+        for value in ["inherit", "launcher", "app", "data"] {
+            assert_eq!(
+                WorkingDirectoryPolicy::try_from(value).unwrap().to_string(),
+                value
+            );
+        }
+        assert!(WorkingDirectoryPolicy::try_from("home").is_err());
+    }
+
+    #[test]
+    fn test_template_values() -> Result<()> {
+        let mut launcher = launcher();
+        launcher
+            .environment
+            .insert("MYAPP_HOME".to_string(), "$ORIGIN\\App".to_string());
+
+        let values = launcher.template_values()?;
+        assert_eq!(values["exe_path"], "\"App\", \"myapp.exe\"");
+        assert_eq!(values["data_dir"], "Some(&[\"Data\"])");
+        assert_eq!(
+            values["environment"],
+            "(\"MYAPP_HOME\", \"$ORIGIN\\\\App\")"
+        );
+        assert_eq!(values["working_directory"], "\"app\"");
+
+        launcher.data_dir = None;
+        assert_eq!(launcher.template_values()?["data_dir"], "None");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut launcher = launcher();
+        launcher.data_dir = None;
+        launcher.working_directory = WorkingDirectoryPolicy::Data;
+        assert!(launcher.validate().is_err());
+
+        let mut launcher = self::launcher();
+        launcher.exe_path = PathBuf::from("/opt/myapp");
+        assert!(launcher.validate().is_err());
+    }
+}
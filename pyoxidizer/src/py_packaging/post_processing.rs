@@ -0,0 +1,389 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Post-processing of built binaries.
+
+After an executable is linked, it can have its symbols stripped, PE
+version information and icon set, ELF notes added, and finally be
+compressed with UPX. These operations are performed by external tools
+which must be installed and found in `PATH`: `strip`, `rcedit`, `objcopy`,
+and `upx`. Only the tools required by the requested operations are used.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    duct::cmd,
+    sha2::Digest,
+    slog::warn,
+    std::{
+        collections::BTreeMap,
+        ffi::OsString,
+        io::{BufRead, BufReader},
+        path::{Path, PathBuf},
+    },
+};
+
+/// ELF note type of GNU build IDs.
+const NT_GNU_BUILD_ID: u32 = 3;
+
+/// Length in bytes of generated GNU build IDs.
+const BUILD_ID_LENGTH: usize = 20;
+
+/// Leading bytes of Mach-O binaries, including universal binaries.
+const MACHO_MAGICS: &[[u8; 4]] = &[
+    [0xfe, 0xed, 0xfa, 0xce],
+    [0xfe, 0xed, 0xfa, 0xcf],
+    [0xce, 0xfa, 0xed, 0xfe],
+    [0xcf, 0xfa, 0xed, 0xfe],
+    [0xca, 0xfe, 0xba, 0xbe],
+];
+
+/// The file format of a binary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BinaryFormat {
+    Elf,
+    MachO,
+    Pe,
+    Unknown,
+}
+
+impl BinaryFormat {
+    /// Detect the format of a binary from its leading bytes.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"\x7fELF") {
+            Self::Elf
+        } else if data.starts_with(b"MZ") {
+            Self::Pe
+        } else if MACHO_MAGICS.iter().any(|magic| data.starts_with(magic)) {
+            Self::MachO
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// A note to add to an ELF binary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ElfNote {
+    /// Name of the section holding the note. Must begin with `.note`.
+    pub section: String,
+
+    /// Name of the note's owner. e.g. `GNU`.
+    pub owner: String,
+
+    /// Owner defined type of the note.
+    pub note_type: u32,
+
+    /// Content of the note.
+    pub desc: Vec<u8>,
+}
+
+impl ElfNote {
+    /// Serialize the note.
+    ///
+    /// The name and descriptor are padded to 4 byte alignment. Fields use
+    /// little endian byte order, like all Linux targets we support.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut name = self.owner.as_bytes().to_vec();
+        name.push(0);
+
+        let mut data = vec![];
+        data.extend((name.len() as u32).to_le_bytes());
+        data.extend((self.desc.len() as u32).to_le_bytes());
+        data.extend(self.note_type.to_le_bytes());
+
+        for field in [&name, &self.desc] {
+            data.extend(field.iter());
+            data.resize((data.len() + 3) & !3, 0);
+        }
+
+        data
+    }
+}
+
+/// Obtain a GNU build ID note for binary content.
+///
+/// The build ID is derived from the content, so it is reproducible.
+pub fn gnu_build_id_note(data: &[u8]) -> ElfNote {
+    ElfNote {
+        section: ".note.gnu.build-id".to_string(),
+        owner: "GNU".to_string(),
+        note_type: NT_GNU_BUILD_ID,
+        desc: sha2::Sha256::digest(data)[0..BUILD_ID_LENGTH].to_vec(),
+    }
+}
+
+/// Operations to perform on a built binary.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BinaryPostProcessor {
+    /// Whether to strip symbols from ELF and Mach-O binaries.
+    pub strip: bool,
+
+    /// Whether to compress the binary with UPX.
+    pub upx: bool,
+
+    /// Extra arguments to pass to `upx`.
+    pub upx_args: Vec<String>,
+
+    /// Path to an `.ico` file to use as the icon of PE binaries.
+    pub windows_icon_path: Option<PathBuf>,
+
+    /// File version to set in the version information of PE binaries.
+    pub windows_file_version: Option<String>,
+
+    /// Product version to set in the version information of PE binaries.
+    pub windows_product_version: Option<String>,
+
+    /// Strings to set in the version information of PE binaries.
+    ///
+    /// e.g. `CompanyName` or `FileDescription`.
+    pub windows_version_strings: BTreeMap<String, String>,
+
+    /// Whether to add a GNU build ID note to ELF binaries.
+    ///
+    /// Any existing build ID is replaced.
+    pub elf_build_id: bool,
+
+    /// Notes to add to ELF binaries.
+    pub elf_notes: Vec<ElfNote>,
+}
+
+impl BinaryPostProcessor {
+    fn has_windows_resources(&self) -> bool {
+        self.windows_icon_path.is_some()
+            || self.windows_file_version.is_some()
+            || self.windows_product_version.is_some()
+            || !self.windows_version_strings.is_empty()
+    }
+
+    fn has_elf_notes(&self) -> bool {
+        self.elf_build_id || !self.elf_notes.is_empty()
+    }
+
+    /// Arguments to `rcedit` to set PE resources.
+    fn rcedit_args(&self, path: &Path) -> Vec<OsString> {
+        let mut args = vec![path.as_os_str().to_owned()];
+
+        if let Some(icon) = &self.windows_icon_path {
+            args.push("--set-icon".into());
+            args.push(icon.as_os_str().to_owned());
+        }
+        if let Some(version) = &self.windows_file_version {
+            args.push("--set-file-version".into());
+            args.push(version.into());
+        }
+        if let Some(version) = &self.windows_product_version {
+            args.push("--set-product-version".into());
+            args.push(version.into());
+        }
+        for (key, value) in &self.windows_version_strings {
+            args.push("--set-version-string".into());
+            args.push(key.into());
+            args.push(value.into());
+        }
+
+        args
+    }
+
+    /// Process a binary in place.
+    pub fn process_file(&self, logger: &slog::Logger, path: &Path) -> Result<()> {
+        let format = BinaryFormat::detect(
+            &std::fs::read(path).with_context(|| format!("reading {}", path.display()))?,
+        );
+
+        if self.strip {
+            match format {
+                BinaryFormat::Elf => {
+                    run_tool(logger, &["strip"], vec!["--strip-all".into(), path.into()])?
+                }
+                // Global symbols must be retained for extension modules to link
+                // against libpython.
+                BinaryFormat::MachO => {
+                    run_tool(logger, &["strip"], vec!["-x".into(), path.into()])?
+                }
+                _ => warn!(
+                    logger,
+                    "not stripping {}: only ELF and Mach-O binaries can be stripped",
+                    path.display()
+                ),
+            }
+        }
+
+        if self.has_windows_resources() {
+            if format == BinaryFormat::Pe {
+                run_tool(logger, &["rcedit", "rcedit-x64"], self.rcedit_args(path))?;
+            } else {
+                warn!(
+                    logger,
+                    "not setting Windows resources of {}: not a PE binary",
+                    path.display()
+                );
+            }
+        }
+
+        if self.has_elf_notes() {
+            if format == BinaryFormat::Elf {
+                self.add_elf_notes(logger, path)?;
+            } else {
+                warn!(
+                    logger,
+                    "not adding ELF notes to {}: not an ELF binary",
+                    path.display()
+                );
+            }
+        }
+
+        if self.upx {
+            let mut args = vec![OsString::from("-q")];
+            args.extend(self.upx_args.iter().map(OsString::from));
+            args.push(path.into());
+
+            run_tool(logger, &["upx"], args)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_elf_notes(&self, logger: &slog::Logger, path: &Path) -> Result<()> {
+        let mut notes = self.elf_notes.clone();
+
+        if self.elf_build_id {
+            // The build ID reflects stripping but not notes, which are
+            // derived from configuration.
+            notes.push(gnu_build_id_note(&std::fs::read(path)?));
+        }
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-notes")
+            .tempdir()
+            .context("creating temp directory")?;
+
+        let mut args = vec![];
+        for (i, note) in notes.iter().enumerate() {
+            if !note.section.starts_with(".note") {
+                return Err(anyhow!(
+                    "ELF note section {} does not begin with .note",
+                    note.section
+                ));
+            }
+
+            let note_path = temp_dir.path().join(format!("note{}", i));
+            std::fs::write(&note_path, note.to_bytes())?;
+
+            let mut add = OsString::from(format!("{}=", note.section));
+            add.push(&note_path);
+
+            args.push("--remove-section".into());
+            args.push(note.section.clone().into());
+            args.push("--add-section".into());
+            args.push(add);
+        }
+        args.push(path.into());
+
+        run_tool(logger, &["objcopy"], args)
+    }
+}
+
+/// Run the first of `tools` found in `PATH`, logging its output.
+fn run_tool(logger: &slog::Logger, tools: &[&str], args: Vec<OsString>) -> Result<()> {
+    let exe = tools
+        .iter()
+        .find_map(|tool| which::which(tool).ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "unable to find {} in PATH; it is required to post-process binaries",
+                tools.join(" or ")
+            )
+        })?;
+
+    warn!(logger, "running {} {:?}", exe.display(), args);
+
+    let command = cmd(&exe, &args).stderr_to_stdout().reader()?;
+    {
+        let reader = BufReader::new(&command);
+        for line in reader.lines() {
+            warn!(logger, "{}", line?);
+        }
+    }
+
+    let output = command
+        .try_wait()?
+        .ok_or_else(|| anyhow!("unable to wait on command"))?;
+    if !output.status.success() {
+        return Err(anyhow!("error running {}", exe.display()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(BinaryFormat::detect(b"\x7fELF\x02\x01"), BinaryFormat::Elf);
+        assert_eq!(BinaryFormat::detect(b"MZ\x90\x00"), BinaryFormat::Pe);
+        assert_eq!(
+            BinaryFormat::detect(&[0xcf, 0xfa, 0xed, 0xfe, 0x07]),
+            BinaryFormat::MachO
+        );
+        assert_eq!(BinaryFormat::detect(b"#!/bin/sh"), BinaryFormat::Unknown);
+        assert_eq!(BinaryFormat::detect(b""), BinaryFormat::Unknown);
+    }
+
+    #[test]
+    fn test_elf_note() {
+        let note = ElfNote {
+            section: ".note.test".to_string(),
+            owner: "Test".to_string(),
+            note_type: 1,
+            desc: b"abcde".to_vec(),
+        };
+
+        assert_eq!(
+            note.to_bytes(),
+            b"\x05\x00\x00\x00\x05\x00\x00\x00\x01\x00\x00\x00Test\x00\x00\x00\x00abcde\x00\x00\x00"
+                .to_vec()
+        );
+
+        let build_id = gnu_build_id_note(b"binary");
+        assert_eq!(build_id.desc.len(), BUILD_ID_LENGTH);
+        assert_eq!(build_id, gnu_build_id_note(b"binary"));
+        assert_ne!(build_id.desc, gnu_build_id_note(b"other").desc);
+        assert_eq!(build_id.to_bytes().len(), 12 + 4 + BUILD_ID_LENGTH);
+    }
+
+    #[test]
+    fn test_rcedit_args() {
+        let mut processor = BinaryPostProcessor {
+            windows_icon_path: Some(PathBuf::from("app.ico")),
+            windows_file_version: Some("1.2.3.4".to_string()),
+            ..BinaryPostProcessor::default()
+        };
+        processor
+            .windows_version_strings
+            .insert("CompanyName".to_string(), "Example".to_string());
+
+        assert!(processor.has_windows_resources());
+        assert!(!processor.has_elf_notes());
+        assert_eq!(
+            processor.rcedit_args(Path::new("app.exe")),
+            [
+                "app.exe",
+                "--set-icon",
+                "app.ico",
+                "--set-file-version",
+                "1.2.3.4",
+                "--set-version-string",
+                "CompanyName",
+                "Example"
+            ]
+            .iter()
+            .map(OsString::from)
+            .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,557 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Deployment of Qt bindings for Python.
+
+Python bindings for Qt like PySide6 and PyQt6 ship Qt's libraries, plugins
+and QML imports inside their package. Qt loads plugins and QML imports from
+the filesystem, locating them via a `qt.conf` file next to the executable.
+
+Bindings ship every Qt module, many of which applications don't use. The
+Qt modules an application needs are determined by scanning the imports of
+its modules, and files of other Qt modules, including the plugins they load,
+are excluded.
+*/
+
+use {
+    anyhow::Result,
+    python_packaging::resource_collection::PythonResourceCollector,
+    std::{
+        collections::BTreeSet,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Qt modules whose files are recognized and the Qt modules they depend on.
+const QT_MODULE_DEPENDENCIES: &[(&str, &[&str])] = &[
+    ("QtBluetooth", &["QtCore"]),
+    ("QtCharts", &["QtWidgets"]),
+    ("QtConcurrent", &["QtCore"]),
+    ("QtCore", &[]),
+    ("QtDBus", &["QtCore"]),
+    ("QtDesigner", &["QtWidgets"]),
+    ("QtGui", &["QtCore", "QtDBus"]),
+    ("QtHelp", &["QtSql", "QtWidgets"]),
+    ("QtMultimedia", &["QtGui", "QtNetwork"]),
+    ("QtMultimediaWidgets", &["QtMultimedia", "QtWidgets"]),
+    ("QtNetwork", &["QtCore"]),
+    ("QtNetworkAuth", &["QtNetwork"]),
+    ("QtNfc", &["QtCore"]),
+    ("QtOpenGL", &["QtGui"]),
+    ("QtOpenGLWidgets", &["QtOpenGL", "QtWidgets"]),
+    ("QtPdf", &["QtGui"]),
+    ("QtPdfWidgets", &["QtPdf", "QtWidgets"]),
+    ("QtPositioning", &["QtCore"]),
+    ("QtPrintSupport", &["QtWidgets"]),
+    ("QtQml", &["QtNetwork"]),
+    ("QtQuick", &["QtGui", "QtOpenGL", "QtQml"]),
+    ("QtQuickControls2", &["QtQuick"]),
+    ("QtQuickWidgets", &["QtQuick", "QtWidgets"]),
+    ("QtRemoteObjects", &["QtNetwork"]),
+    ("QtSensors", &["QtCore"]),
+    ("QtSerialPort", &["QtCore"]),
+    ("QtSql", &["QtCore"]),
+    ("QtStateMachine", &["QtCore"]),
+    ("QtSvg", &["QtGui"]),
+    ("QtSvgWidgets", &["QtSvg", "QtWidgets"]),
+    ("QtTest", &["QtCore"]),
+    ("QtTextToSpeech", &["QtCore"]),
+    ("QtUiTools", &["QtWidgets"]),
+    ("QtWebChannel", &["QtCore"]),
+    (
+        "QtWebEngineCore",
+        &["QtNetwork", "QtPositioning", "QtQuick", "QtWebChannel"],
+    ),
+    ("QtWebEngineQuick", &["QtQuick", "QtWebEngineCore"]),
+    (
+        "QtWebEngineWidgets",
+        &["QtPrintSupport", "QtWebEngineCore", "QtWidgets"],
+    ),
+    ("QtWebSockets", &["QtNetwork"]),
+    ("QtWidgets", &["QtGui"]),
+    ("QtXml", &["QtCore"]),
+];
+
+/// Qt plugin directories and the Qt module loading plugins from them.
+///
+/// Directories starting with `wayland-` hold plugins of `QtGui`.
+const QT_PLUGIN_MODULES: &[(&str, &str)] = &[
+    ("accessible", "QtWidgets"),
+    ("audio", "QtMultimedia"),
+    ("bearer", "QtNetwork"),
+    ("designer", "QtDesigner"),
+    ("egldeviceintegrations", "QtGui"),
+    ("generic", "QtGui"),
+    ("iconengines", "QtGui"),
+    ("imageformats", "QtGui"),
+    ("mediaservice", "QtMultimedia"),
+    ("multimedia", "QtMultimedia"),
+    ("networkinformation", "QtNetwork"),
+    ("platforminputcontexts", "QtGui"),
+    ("platforms", "QtGui"),
+    ("platformthemes", "QtGui"),
+    ("playlistformats", "QtMultimedia"),
+    ("position", "QtPositioning"),
+    ("printsupport", "QtPrintSupport"),
+    ("qmltooling", "QtQml"),
+    ("scenegraph", "QtQuick"),
+    ("sensorgestures", "QtSensors"),
+    ("sensors", "QtSensors"),
+    ("sqldrivers", "QtSql"),
+    ("styles", "QtWidgets"),
+    ("texttospeech", "QtTextToSpeech"),
+    ("tls", "QtNetwork"),
+    ("xcbglintegrations", "QtGui"),
+];
+
+fn known_qt_module(name: &str) -> Option<&'static str> {
+    QT_MODULE_DEPENDENCIES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(module, _)| *module)
+}
+
+/// Obtain the Qt modules of a binding needed by a set of imported modules.
+///
+/// Dependencies of imported Qt modules are included. `QtCore` is always
+/// needed.
+pub fn needed_qt_modules<'a>(
+    binding: &str,
+    imports: impl IntoIterator<Item = &'a str>,
+) -> BTreeSet<String> {
+    let mut pending = vec!["QtCore".to_string()];
+
+    for import in imports {
+        if let Some(module) = import
+            .strip_prefix(binding)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .and_then(|rest| rest.split('.').next())
+        {
+            if module.starts_with("Qt") {
+                pending.push(module.to_string());
+            }
+        }
+    }
+
+    let mut needed = BTreeSet::new();
+
+    while let Some(module) = pending.pop() {
+        if !needed.insert(module.clone()) {
+            continue;
+        }
+
+        if let Some((_, dependencies)) = QT_MODULE_DEPENDENCIES
+            .iter()
+            .find(|(name, _)| *name == module)
+        {
+            pending.extend(dependencies.iter().map(|x| x.to_string()));
+        }
+    }
+
+    needed
+}
+
+/// Resolve the Qt module a file of a Qt binding belongs to.
+///
+/// `path` is relative to the directory of the binding package, with `/`
+/// separators. Python modules of the binding are identified by their path
+/// without extension. e.g. `QtCore` for `PySide6.QtCore`.
+///
+/// Plugins belong to the Qt module loading them and QML imports to `QtQml`.
+/// Returns `None` if the file isn't specific to a recognized Qt module.
+pub fn qt_module_for_file(path: &str) -> Option<&'static str> {
+    let components = path.split('/').collect::<Vec<_>>();
+
+    if let Some(index) = components.iter().position(|c| *c == "plugins") {
+        if components.len() > index + 2 {
+            let directory = components[index + 1];
+
+            return if directory.starts_with("wayland-") {
+                Some("QtGui")
+            } else {
+                QT_PLUGIN_MODULES
+                    .iter()
+                    .find(|(name, _)| *name == directory)
+                    .map(|(_, module)| *module)
+            };
+        }
+    }
+
+    if components.iter().rev().skip(1).any(|c| *c == "qml") {
+        return Some("QtQml");
+    }
+
+    // Frameworks on macOS are directories holding the library.
+    let name = components
+        .iter()
+        .rev()
+        .find_map(|c| c.strip_suffix(".framework"))
+        .or_else(|| components.last().copied())?;
+    let name = name.strip_prefix("lib").unwrap_or(name);
+    let name = name.split('.').next().unwrap_or_default();
+
+    if let Some(module) = name
+        .strip_prefix("Qt6")
+        .or_else(|| name.strip_prefix("Qt"))
+        .and_then(|suffix| known_qt_module(&format!("Qt{}", suffix)))
+    {
+        return Some(module);
+    }
+
+    // Helper process and data files of Qt WebEngine.
+    if components
+        .iter()
+        .any(|c| c.to_lowercase().starts_with("qtwebengine"))
+    {
+        return Some("QtWebEngineCore");
+    }
+
+    None
+}
+
+/// Obtain the content of a `qt.conf` file for Qt files installed in a directory.
+///
+/// `prefix` is relative to the directory of the executable.
+pub fn qt_conf(prefix: &Path) -> String {
+    let prefix = prefix.display().to_string().replace('\\', "/");
+
+    format!(
+        "[Paths]\n\
+         Prefix = {}\n\
+         Plugins = plugins\n\
+         QmlImports = qml\n\
+         Qml2Imports = qml\n",
+        prefix
+    )
+}
+
+/// Describes the deployment of a Qt binding.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QtDeployment {
+    /// Qt modules of the binding needed by the application.
+    pub needed_modules: BTreeSet<String>,
+
+    /// Qt modules of the binding whose files were excluded.
+    pub excluded_modules: BTreeSet<String>,
+
+    /// Directory Qt's plugins and QML imports are installed in.
+    ///
+    /// Relative to the directory of the executable. `None` if they aren't
+    /// installed on the filesystem.
+    pub prefix: Option<PathBuf>,
+}
+
+/// Path of a resource relative to the directory of a binding package.
+fn binding_path(binding: &str, package: &str, name: Option<&str>) -> Option<String> {
+    let package_path = if package == binding {
+        String::new()
+    } else {
+        package
+            .strip_prefix(binding)?
+            .strip_prefix('.')?
+            .replace('.', "/")
+    };
+
+    Some(match name {
+        Some(name) if package_path.is_empty() => name.to_string(),
+        Some(name) => format!("{}/{}", package_path, name),
+        None => package_path,
+    })
+}
+
+/// Whether a binding-relative path is within a Qt directory holding files loaded
+/// by Qt, returning the path of that directory relative to the binding.
+fn qt_directory_of_path(path: &str) -> Option<&str> {
+    let mut offset = 0;
+
+    for component in path.split('/') {
+        if component == "plugins" || component == "qml" {
+            return Some(path[..offset].trim_end_matches('/'));
+        }
+
+        offset += component.len() + 1;
+    }
+
+    None
+}
+
+/// Deploy a Qt binding packaged in a resource collector.
+///
+/// Files of Qt modules of the binding not needed by the imported modules are
+/// removed from the collector. Returns where Qt's plugins and QML imports are
+/// installed, so `qt.conf` can point at them.
+pub fn deploy_qt_binding(
+    collector: &mut PythonResourceCollector,
+    binding: &str,
+    imports: &BTreeSet<String>,
+) -> Result<QtDeployment> {
+    let needed_modules = needed_qt_modules(binding, imports.iter().map(|x| x.as_str()));
+
+    let is_needed = |path: &str| match qt_module_for_file(path) {
+        Some(module) => needed_modules.contains(module),
+        None => true,
+    };
+
+    let file_prefix = format!("{}/", binding);
+
+    // Paths of all files of the binding.
+    let mut paths = vec![];
+    for (name, resource) in collector.iter_resources() {
+        if resource.is_utf8_filename_data {
+            if let Some(path) = name.replace('\\', "/").strip_prefix(&file_prefix) {
+                paths.push(path.to_string());
+            }
+
+            continue;
+        }
+
+        if resource.is_python_resource() {
+            if let Some(path) = binding_path(binding, name, None) {
+                paths.push(path);
+            }
+        }
+
+        for key in resource
+            .in_memory_resources
+            .iter()
+            .flat_map(|x| x.keys())
+            .chain(
+                resource
+                    .relative_path_package_resources
+                    .iter()
+                    .flat_map(|x| x.keys()),
+            )
+        {
+            if let Some(path) = binding_path(binding, name, Some(key)) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let excluded_modules = paths
+        .iter()
+        .filter_map(|path| qt_module_for_file(path))
+        .filter(|module| !needed_modules.contains(*module))
+        .map(|module| module.to_string())
+        .collect::<BTreeSet<_>>();
+
+    collector.filter_resources_mut(|resource| {
+        let path = if resource.is_utf8_filename_data {
+            resource
+                .name
+                .replace('\\', "/")
+                .strip_prefix(&file_prefix)
+                .map(|x| x.to_string())
+        } else if resource.is_python_resource() {
+            binding_path(binding, &resource.name, None)
+        } else {
+            None
+        };
+
+        path.map_or(true, |path| path.is_empty() || is_needed(&path))
+    })?;
+
+    collector.filter_package_resources_mut(|package, name| {
+        binding_path(binding, package, Some(name)).map_or(true, |path| is_needed(&path))
+    })?;
+
+    // Find where plugins and QML imports are installed by looking at the
+    // installed paths of files in these directories.
+    let mut prefix = None;
+    for (name, resource) in collector.iter_resources() {
+        let installs =
+            resource
+                .relative_path_package_resources
+                .iter()
+                .flat_map(|x| x.iter())
+                .filter_map(|(key, (install_path, _))| {
+                    binding_path(binding, name, Some(key)).map(|path| (path, install_path))
+                })
+                .chain(resource.file_data_utf8_relative_path.iter().filter_map(
+                    |(install_path, _)| {
+                        name.replace('\\', "/")
+                            .strip_prefix(&file_prefix)
+                            .map(|path| (path.to_string(), install_path))
+                    },
+                ));
+
+        for (path, install_path) in installs {
+            if let Some(directory) = qt_directory_of_path(&path) {
+                let install_path = install_path.display().to_string().replace('\\', "/");
+
+                // The install path ends with the binding-relative path.
+                let suffix = path[directory.len()..].trim_start_matches('/');
+
+                if let Some(root) = install_path.strip_suffix(suffix) {
+                    prefix = Some(PathBuf::from(root.trim_end_matches('/')));
+                    break;
+                }
+            }
+        }
+
+        if prefix.is_some() {
+            break;
+        }
+    }
+
+    Ok(QtDeployment {
+        needed_modules,
+        excluded_modules,
+        prefix,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        python_packaging::{
+            location::{AbstractResourceLocation, ConcreteResourceLocation},
+            resource::{PythonExtensionModule, PythonPackageResource},
+        },
+        tugger_file_manifest::FileData,
+    };
+
+    #[test]
+    fn test_needed_qt_modules() {
+        assert_eq!(
+            needed_qt_modules("PySide6", vec!["os", "PyQt6.QtSql"]),
+            BTreeSet::from(["QtCore".to_string()])
+        );
+
+        assert_eq!(
+            needed_qt_modules("PySide6", vec!["PySide6", "PySide6.QtWidgets.QWidget"]),
+            ["QtCore", "QtDBus", "QtGui", "QtWidgets"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<BTreeSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_qt_module_for_file() {
+        for (path, module) in [
+            ("QtWidgets", Some("QtWidgets")),
+            ("QtWidgets.abi3.so", Some("QtWidgets")),
+            ("QtWidgets.pyi", Some("QtWidgets")),
+            ("Qt/lib/libQt6WebEngineCore.so.6", Some("QtWebEngineCore")),
+            ("Qt6Sql.dll", Some("QtSql")),
+            ("Qt6/bin/Qt6Sql.dll", Some("QtSql")),
+            ("Qt/lib/QtSvg.framework/Versions/A/QtSvg", Some("QtSvg")),
+            ("Qt/libexec/QtWebEngineProcess", Some("QtWebEngineCore")),
+            (
+                "Qt/resources/qtwebengine_resources.pak",
+                Some("QtWebEngineCore"),
+            ),
+            ("Qt/plugins/platforms/libqxcb.so", Some("QtGui")),
+            ("plugins/sqldrivers/qsqlite.dll", Some("QtSql")),
+            (
+                "Qt/plugins/wayland-shell-integration/libxdg.so",
+                Some("QtGui"),
+            ),
+            ("Qt/plugins/unknown/libfoo.so", None),
+            ("Qt/qml/QtQuick/Controls/qmldir", Some("QtQml")),
+            ("Qt/translations/qtbase_de.qm", None),
+            ("Qt/lib/libQt6Unknown.so.6", None),
+            ("__init__.py", None),
+            ("scripts", None),
+        ] {
+            assert_eq!(qt_module_for_file(path), module, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_qt_conf() {
+        assert_eq!(
+            qt_conf(Path::new("lib/PySide6/Qt")),
+            "[Paths]\nPrefix = lib/PySide6/Qt\nPlugins = plugins\nQmlImports = qml\nQml2Imports = qml\n"
+        );
+    }
+
+    #[test]
+    fn test_deploy_qt_binding() -> Result<()> {
+        let locations = vec![
+            AbstractResourceLocation::InMemory,
+            AbstractResourceLocation::RelativePath,
+        ];
+        let mut collector = PythonResourceCollector::new(locations.clone(), locations, false, true);
+
+        for name in ["QtCore", "QtGui", "QtWidgets", "QtSql"] {
+            collector.add_python_extension_module(
+                &PythonExtensionModule {
+                    name: format!("PySide6.{}", name),
+                    init_fn: Some(format!("PyInit_{}", name)),
+                    extension_file_suffix: ".abi3.so".to_string(),
+                    shared_library: Some(FileData::Memory(vec![42])),
+                    object_file_data: vec![],
+                    is_package: false,
+                    link_libraries: vec![],
+                    is_stdlib: false,
+                    builtin_default: false,
+                    required: false,
+                    variant: None,
+                    license: None,
+                },
+                &ConcreteResourceLocation::RelativePath("lib".to_string()),
+            )?;
+        }
+
+        for name in [
+            "Qt/lib/libQt6Core.so.6",
+            "Qt/lib/libQt6Sql.so.6",
+            "Qt/plugins/platforms/libqxcb.so",
+            "Qt/plugins/sqldrivers/libqsqlite.so",
+            "Qt/translations/qtbase_de.qm",
+        ] {
+            collector.add_python_package_resource(
+                &PythonPackageResource {
+                    leaf_package: "PySide6".to_string(),
+                    relative_name: name.to_string(),
+                    data: FileData::Memory(vec![42]),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &ConcreteResourceLocation::RelativePath("lib".to_string()),
+            )?;
+        }
+
+        let imports = BTreeSet::from(["PySide6.QtWidgets".to_string()]);
+        let deployment = deploy_qt_binding(&mut collector, "PySide6", &imports)?;
+
+        assert_eq!(
+            deployment.excluded_modules,
+            BTreeSet::from(["QtSql".to_string()])
+        );
+        assert_eq!(deployment.prefix, Some(PathBuf::from("lib/PySide6/Qt")));
+
+        assert!(collector
+            .iter_resources()
+            .all(|(name, _)| name != "PySide6.QtSql"));
+        assert!(collector
+            .iter_resources()
+            .any(|(name, _)| name == "PySide6.QtWidgets"));
+
+        let (_, package) = collector
+            .iter_resources()
+            .find(|(name, _)| *name == "PySide6")
+            .unwrap();
+        assert_eq!(
+            package
+                .relative_path_package_resources
+                .as_ref()
+                .unwrap()
+                .keys()
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Qt/lib/libQt6Core.so.6",
+                "Qt/plugins/platforms/libqxcb.so",
+                "Qt/translations/qtbase_de.qm",
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,700 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Compile resources from their source forms into their run-time forms.
+
+Applications often ship assets that are authored in one format and loaded
+in another, like gettext message catalogs (`.po` to `.mo`) or Qt
+translations (`.ts` to `.qm`). Resource compilers perform this
+transformation as resources are collected, so no separate build step is
+needed.
+
+Compiled output is cached by the content of the source, so unchanged assets
+aren't compiled again.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    duct::cmd,
+    python_packaging::resource::{PythonPackageResource, PythonResource},
+    sha2::Digest,
+    std::{borrow::Cow, collections::BTreeMap, path::PathBuf},
+    tugger_file_manifest::{File, FileData, FileEntry},
+};
+
+/// Names of available resource compilers.
+pub const RESOURCE_COMPILERS: &[&str] = &["gettext", "qt"];
+
+/// Transforms a resource from its source form into its run-time form.
+pub trait ResourceCompiler {
+    /// Name of this compiler, as used in configuration.
+    fn name(&self) -> &'static str;
+
+    /// Filename extension of files this compiler consumes.
+    fn source_extension(&self) -> &'static str;
+
+    /// Filename extension of files this compiler produces.
+    fn output_extension(&self) -> &'static str;
+
+    /// Identifies the implementation producing output.
+    ///
+    /// Cached output is only reused if this value matches.
+    fn cache_key(&self) -> Result<String>;
+
+    /// Compile the content of a source file.
+    ///
+    /// `path` is the path of the resource, for error messages.
+    fn compile(&self, path: &str, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Construct a resource compiler from its name.
+pub fn resource_compiler(name: &str) -> Result<Box<dyn ResourceCompiler>> {
+    match name {
+        "gettext" => Ok(Box::new(GettextCompiler)),
+        "qt" => Ok(Box::new(QtLinguistCompiler)),
+        _ => Err(anyhow!(
+            "unknown resource compiler: {}; must be one of {}",
+            name,
+            RESOURCE_COMPILERS.join(", ")
+        )),
+    }
+}
+
+/// Compiles gettext `.po` message catalogs to `.mo` files.
+///
+/// Like `msgfmt`, fuzzy and untranslated messages are omitted.
+pub struct GettextCompiler;
+
+impl ResourceCompiler for GettextCompiler {
+    fn name(&self) -> &'static str {
+        "gettext"
+    }
+
+    fn source_extension(&self) -> &'static str {
+        "po"
+    }
+
+    fn output_extension(&self) -> &'static str {
+        "mo"
+    }
+
+    fn cache_key(&self) -> Result<String> {
+        Ok("1".to_string())
+    }
+
+    fn compile(&self, path: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let messages = parse_po(data).with_context(|| format!("parsing {}", path))?;
+
+        Ok(write_mo(&messages))
+    }
+}
+
+/// Compiles Qt Linguist `.ts` translations to `.qm` files.
+///
+/// This runs Qt's `lrelease` tool, which must be in `PATH`.
+pub struct QtLinguistCompiler;
+
+impl QtLinguistCompiler {
+    fn lrelease(&self) -> Result<PathBuf> {
+        const TOOLS: &[&str] = &[
+            "lrelease",
+            "lrelease-qt6",
+            "lrelease-qt5",
+            "pyside6-lrelease",
+            "pyside2-lrelease",
+        ];
+
+        TOOLS
+            .iter()
+            .find_map(|tool| which::which(tool).ok())
+            .ok_or_else(|| {
+                anyhow!(
+                    "unable to find {} in PATH; it is required to compile Qt translations",
+                    TOOLS.join(" or ")
+                )
+            })
+    }
+}
+
+impl ResourceCompiler for QtLinguistCompiler {
+    fn name(&self) -> &'static str {
+        "qt"
+    }
+
+    fn source_extension(&self) -> &'static str {
+        "ts"
+    }
+
+    fn output_extension(&self) -> &'static str {
+        "qm"
+    }
+
+    fn cache_key(&self) -> Result<String> {
+        Ok(format!("1:{}", self.lrelease()?.display()))
+    }
+
+    fn compile(&self, path: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let lrelease = self.lrelease()?;
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-lrelease")
+            .tempdir()?;
+        let source_path = temp_dir.path().join("source.ts");
+        let dest_path = temp_dir.path().join("output.qm");
+        std::fs::write(&source_path, data)?;
+
+        let output = cmd(
+            &lrelease,
+            &[
+                "-silent".into(),
+                source_path.into_os_string(),
+                "-qm".into(),
+                dest_path.clone().into_os_string(),
+            ],
+        )
+        .stderr_to_stdout()
+        .stdout_capture()
+        .unchecked()
+        .run()?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "error compiling {} with {}: {}",
+                path,
+                lrelease.display(),
+                String::from_utf8_lossy(&output.stdout)
+            ));
+        }
+
+        std::fs::read(&dest_path).with_context(|| format!("reading output of {}", path))
+    }
+}
+
+/// Applies resource compilers to resources.
+pub struct ResourceCompilers {
+    compilers: Vec<Box<dyn ResourceCompiler>>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl ResourceCompilers {
+    /// Construct an instance from compiler names.
+    ///
+    /// Compiled output is cached in `cache_dir` if defined.
+    pub fn new(names: &[String], cache_dir: Option<PathBuf>) -> Result<Self> {
+        Ok(Self {
+            compilers: names
+                .iter()
+                .map(|name| resource_compiler(name))
+                .collect::<Result<Vec<_>>>()?,
+            cache_dir,
+        })
+    }
+
+    /// Resolve the compiler for a path, if any.
+    fn compiler_for_path(&self, path: &str) -> Option<&dyn ResourceCompiler> {
+        self.compilers
+            .iter()
+            .find(|compiler| {
+                path.strip_suffix(compiler.source_extension())
+                    .map(|stem| stem.ends_with('.'))
+                    .unwrap_or(false)
+            })
+            .map(|compiler| compiler.as_ref())
+    }
+
+    /// Compile data, using cached output if available.
+    fn compile_data(
+        &self,
+        compiler: &dyn ResourceCompiler,
+        path: &str,
+        data: &FileData,
+    ) -> Result<Vec<u8>> {
+        let data = data
+            .resolve_content()
+            .with_context(|| format!("reading {}", path))?;
+
+        let cache_path = if let Some(cache_dir) = &self.cache_dir {
+            let mut hasher = sha2::Sha256::new();
+            hasher.update(compiler.name().as_bytes());
+            hasher.update(b"\0");
+            hasher.update(compiler.cache_key()?.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(&data);
+
+            let path = cache_dir.join(compiler.name()).join(format!(
+                "{}.{}",
+                hex::encode(hasher.finalize()),
+                compiler.output_extension()
+            ));
+
+            if let Ok(output) = std::fs::read(&path) {
+                return Ok(output);
+            }
+
+            Some(path)
+        } else {
+            None
+        };
+
+        let output = compiler.compile(path, &data)?;
+
+        if let Some(cache_path) = cache_path {
+            let parent = cache_path.parent().expect("cache paths have a parent");
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+
+            let temp_path = cache_path.with_extension("tmp");
+            std::fs::write(&temp_path, &output)
+                .with_context(|| format!("writing {}", temp_path.display()))?;
+            std::fs::rename(&temp_path, &cache_path)
+                .with_context(|| format!("renaming {}", temp_path.display()))?;
+        }
+
+        Ok(output)
+    }
+
+    /// Compile a resource.
+    ///
+    /// Returns `None` if no compiler applies to the resource. Otherwise
+    /// returns a resource holding the compiled output, whose name has the
+    /// extension of the compiler's output.
+    pub fn compile<'a>(&self, resource: &PythonResource<'a>) -> Result<Option<PythonResource<'a>>> {
+        if self.compilers.is_empty() {
+            return Ok(None);
+        }
+
+        match resource {
+            PythonResource::PackageResource(r) => {
+                let compiler = match self.compiler_for_path(&r.relative_name) {
+                    Some(compiler) => compiler,
+                    None => return Ok(None),
+                };
+
+                let data = self.compile_data(compiler, &r.symbolic_name(), &r.data)?;
+
+                Ok(Some(PythonResource::PackageResource(Cow::Owned(
+                    PythonPackageResource {
+                        relative_name: replace_extension(&r.relative_name, compiler),
+                        data: FileData::Memory(data),
+                        ..r.clone().into_owned()
+                    },
+                ))))
+            }
+            PythonResource::File(f) => {
+                let path = f.path_string();
+                let compiler = match self.compiler_for_path(&path) {
+                    Some(compiler) => compiler,
+                    None => return Ok(None),
+                };
+
+                let data = self.compile_data(compiler, &path, f.entry().file_data())?;
+
+                Ok(Some(PythonResource::File(Cow::Owned(File::new(
+                    replace_extension(&path, compiler),
+                    FileEntry::new_from_data(data, f.entry().is_executable()),
+                )))))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+fn replace_extension(path: &str, compiler: &dyn ResourceCompiler) -> String {
+    format!(
+        "{}{}",
+        &path[..path.len() - compiler.source_extension().len()],
+        compiler.output_extension()
+    )
+}
+
+/// Decode the content of a quoted PO string, without the quotes.
+fn unescape_po_string(s: &str) -> Result<Vec<u8>> {
+    let mut res = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            res.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        let c = chars
+            .next()
+            .ok_or_else(|| anyhow!("string ends with an escape"))?;
+        match c {
+            'n' => res.push(b'\n'),
+            't' => res.push(b'\t'),
+            'r' => res.push(b'\r'),
+            'a' => res.push(0x07),
+            'b' => res.push(0x08),
+            'f' => res.push(0x0c),
+            'v' => res.push(0x0b),
+            '\\' | '"' | '\'' | '?' => res.push(c as u8),
+            'x' => {
+                let mut value = 0u32;
+                let mut digits = 0;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    value = value * 16 + digit;
+                    digits += 1;
+                    chars.next();
+                }
+                if digits == 0 || value > 0xff {
+                    return Err(anyhow!("invalid hexadecimal escape"));
+                }
+                res.push(value as u8);
+            }
+            '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                if value > 0xff {
+                    return Err(anyhow!("invalid octal escape"));
+                }
+                res.push(value as u8);
+            }
+            c => return Err(anyhow!("invalid escape sequence: \\{}", c)),
+        }
+    }
+
+    Ok(res)
+}
+
+/// A field of a PO entry.
+#[derive(Clone, Copy)]
+enum PoField {
+    Context,
+    Id,
+    IdPlural,
+    Str(usize),
+}
+
+#[derive(Default)]
+struct PoEntry {
+    fuzzy: bool,
+    msgctxt: Option<Vec<u8>>,
+    msgid: Option<Vec<u8>>,
+    msgid_plural: Option<Vec<u8>>,
+    msgstr: BTreeMap<usize, Vec<u8>>,
+}
+
+impl PoEntry {
+    fn field_mut(&mut self, field: PoField) -> &mut Vec<u8> {
+        match field {
+            PoField::Context => self.msgctxt.get_or_insert_with(Vec::new),
+            PoField::Id => self.msgid.get_or_insert_with(Vec::new),
+            PoField::IdPlural => self.msgid_plural.get_or_insert_with(Vec::new),
+            PoField::Str(index) => self.msgstr.entry(index).or_default(),
+        }
+    }
+
+    /// Key and value of this entry in a `.mo` file, if it is included.
+    fn into_message(self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let msgid = self.msgid?;
+
+        // The header (the message with an empty ID) is kept even if fuzzy.
+        if (self.fuzzy && !msgid.is_empty()) || self.msgstr.values().all(|s| s.is_empty()) {
+            return None;
+        }
+
+        let mut key = vec![];
+        if let Some(context) = self.msgctxt {
+            key.extend(context);
+            key.push(0x04);
+        }
+        key.extend(msgid);
+        if let Some(plural) = self.msgid_plural {
+            key.push(0);
+            key.extend(plural);
+        }
+
+        let value = self.msgstr.into_values().collect::<Vec<_>>().join(&0);
+
+        Some((key, value))
+    }
+}
+
+/// Parse a PO file into messages keyed by their `.mo` key.
+fn parse_po(data: &[u8]) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| anyhow!("only UTF-8 encoded message catalogs are supported"))?;
+
+    let mut messages = BTreeMap::new();
+    let mut entry = PoEntry::default();
+    // The field continuation strings are appended to.
+    let mut field = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let lineno = i + 1;
+
+        let parse_string = |s: &str| -> Result<Vec<u8>> {
+            let inner = s
+                .trim()
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .ok_or_else(|| anyhow!("line {}: expected a quoted string", lineno))?;
+
+            unescape_po_string(inner).with_context(|| format!("line {}", lineno))
+        };
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('"') {
+            let s = parse_string(line)?;
+            match field {
+                Some(field) => entry.field_mut(field).extend(s),
+                None => return Err(anyhow!("line {}: string outside of a message", lineno)),
+            }
+            continue;
+        }
+
+        // Comments and `msgctxt` start a new entry, as does `msgid` if the
+        // current entry has one.
+        let starts_entry = line.starts_with('#')
+            || line.starts_with("msgctxt")
+            || (line.starts_with("msgid ") && entry.msgid.is_some());
+        if starts_entry && !entry.msgstr.is_empty() {
+            if let Some((key, value)) = std::mem::take(&mut entry).into_message() {
+                messages.insert(key, value);
+            }
+        }
+
+        if line.starts_with('#') {
+            if let Some(flags) = line.strip_prefix("#,") {
+                if flags.split(',').any(|flag| flag.trim() == "fuzzy") {
+                    entry.fuzzy = true;
+                }
+            }
+
+            // Other comments and obsolete messages are ignored.
+            field = None;
+            continue;
+        }
+
+        let (keyword, value) = line
+            .split_once(|c: char| c.is_whitespace())
+            .ok_or_else(|| anyhow!("line {}: expected a keyword and string", lineno))?;
+
+        let new_field = match keyword {
+            "msgctxt" => PoField::Context,
+            "msgid" => PoField::Id,
+            "msgid_plural" => PoField::IdPlural,
+            "msgstr" => PoField::Str(0),
+            keyword => PoField::Str(
+                keyword
+                    .strip_prefix("msgstr[")
+                    .and_then(|s| s.strip_suffix(']'))
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .ok_or_else(|| anyhow!("line {}: unknown keyword {}", lineno, keyword))?,
+            ),
+        };
+
+        *entry.field_mut(new_field) = parse_string(value)?;
+        field = Some(new_field);
+    }
+
+    if let Some((key, value)) = entry.into_message() {
+        messages.insert(key, value);
+    }
+
+    Ok(messages)
+}
+
+/// Write messages in the gettext `.mo` format.
+///
+/// Messages are sorted by key, which the map guarantees. No hash table is
+/// written; readers fall back to a binary search.
+fn write_mo(messages: &BTreeMap<Vec<u8>, Vec<u8>>) -> Vec<u8> {
+    const HEADER_SIZE: usize = 28;
+
+    let count = messages.len();
+    let keys_offset = HEADER_SIZE;
+    let values_offset = keys_offset + count * 8;
+    let mut data_offset = values_offset + count * 8;
+
+    let mut res = vec![];
+    for value in [
+        0x950412de,
+        0,
+        count,
+        keys_offset,
+        values_offset,
+        // Hash table size and offset.
+        0,
+        data_offset,
+    ] {
+        res.extend((value as u32).to_le_bytes());
+    }
+
+    let mut tables = vec![];
+    let mut strings = vec![];
+    for strings_of_table in [
+        messages.keys().collect::<Vec<_>>(),
+        messages.values().collect::<Vec<_>>(),
+    ] {
+        for s in strings_of_table {
+            tables.extend((s.len() as u32).to_le_bytes());
+            tables.extend((data_offset as u32).to_le_bytes());
+            strings.extend(s);
+            strings.push(0);
+            data_offset += s.len() + 1;
+        }
+    }
+
+    res.extend(tables);
+    res.extend(strings);
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read a `.mo` file into (key, value) pairs.
+    fn read_mo(data: &[u8]) -> Vec<(String, String)> {
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        let string_at = |table: usize, i: usize| {
+            let length = u32_at(table + i * 8);
+            let offset = u32_at(table + i * 8 + 4);
+            assert_eq!(data[offset + length], 0);
+            String::from_utf8(data[offset..offset + length].to_vec()).unwrap()
+        };
+
+        assert_eq!(u32_at(0), 0x950412de);
+        let count = u32_at(8);
+
+        (0..count)
+            .map(|i| (string_at(u32_at(12), i), string_at(u32_at(16), i)))
+            .collect()
+    }
+
+    const PO: &str = r#"# Translations for app.
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+#: app.py:10
+msgid "Hello"
+msgstr "Hallo"
+
+#, fuzzy
+msgid "Goodbye"
+msgstr "Tschüss"
+
+msgid "Untranslated"
+msgstr ""
+
+msgctxt "menu"
+msgid "Open"
+msgstr "Öffnen"
+
+msgid "%d file"
+msgid_plural "%d files"
+msgstr[0] "%d Datei"
+msgstr[1] "%d "
+"Dateien"
+
+msgid "Tab\there"
+msgstr "Tab\there \"quoted\" \101"
+
+#~ msgid "Obsolete"
+#~ msgstr "Veraltet"
+"#;
+
+    #[test]
+    fn test_gettext() -> Result<()> {
+        let mo = GettextCompiler.compile("app.po", PO.as_bytes())?;
+
+        assert_eq!(
+            read_mo(&mo),
+            vec![
+                (
+                    "".to_string(),
+                    "Content-Type: text/plain; charset=UTF-8\nPlural-Forms: nplurals=2; plural=(n != 1);\n"
+                        .to_string()
+                ),
+                ("%d file\0%d files".to_string(), "%d Datei\0%d Dateien".to_string()),
+                ("Hello".to_string(), "Hallo".to_string()),
+                ("Tab\there".to_string(), "Tab\there \"quoted\" A".to_string()),
+                ("menu\x04Open".to_string(), "Öffnen".to_string()),
+            ]
+        );
+
+        assert!(GettextCompiler
+            .compile("bad.po", b"msgid \"unterminated\nmsgstr \"\"")
+            .is_err());
+        assert!(GettextCompiler.compile("bad.po", b"msgfoo \"x\"").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_resources() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache_dir = temp_dir.path().join("cache");
+
+        assert!(ResourceCompilers::new(&["unknown".to_string()], None).is_err());
+
+        let compilers = ResourceCompilers::new(&["gettext".to_string()], Some(cache_dir.clone()))?;
+
+        let resource = PythonResource::from(PythonPackageResource {
+            leaf_package: "app".to_string(),
+            relative_name: "locale/de/LC_MESSAGES/app.po".to_string(),
+            data: FileData::Memory(PO.as_bytes().to_vec()),
+            is_stdlib: false,
+            is_test: false,
+        });
+
+        let compiled = match compilers.compile(&resource)? {
+            Some(PythonResource::PackageResource(r)) => r.into_owned(),
+            _ => panic!("expected a compiled package resource"),
+        };
+        assert_eq!(compiled.relative_name, "locale/de/LC_MESSAGES/app.mo");
+        assert_eq!(read_mo(&compiled.data.resolve_content()?).len(), 5);
+
+        // Output is cached by content.
+        let cached = std::fs::read_dir(cache_dir.join("gettext"))?.collect::<Vec<_>>();
+        assert_eq!(cached.len(), 1);
+        let cached_path = cached[0].as_ref().unwrap().path();
+        std::fs::write(&cached_path, b"cached")?;
+        match compilers.compile(&resource)? {
+            Some(PythonResource::PackageResource(r)) => {
+                assert_eq!(r.data.resolve_content()?, b"cached".to_vec())
+            }
+            _ => panic!("expected a compiled package resource"),
+        }
+
+        let file = PythonResource::from(File::new(
+            "share/app.po",
+            FileEntry::new_from_data(PO.as_bytes().to_vec(), false),
+        ));
+        match compilers.compile(&file)? {
+            Some(PythonResource::File(f)) => assert_eq!(f.path_string(), "share/app.mo"),
+            _ => panic!("expected a compiled file"),
+        }
+
+        let other = PythonResource::from(File::new(
+            "share/app.pot",
+            FileEntry::new_from_data(vec![], false),
+        ));
+        assert!(compilers.compile(&other)?.is_none());
+
+        Ok(())
+    }
+}
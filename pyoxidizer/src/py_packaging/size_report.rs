@@ -0,0 +1,373 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Attribute the size of built executables to their contents.
+
+A size report is a tree of named byte counts. The root is the executable.
+Its children attribute bytes to embedded Python resources (grouped by
+top-level package), to the sections of the binary and to remaining file
+overhead. On ELF binaries with a symbol table, executable sections are
+further broken down into code from the Python distribution, Rust code and
+other native code.
+
+The JSON form of the tree is suitable for rendering as a flame graph.
+*/
+
+use {
+    super::embedded_metadata::{find_packed_resources, resource_data_sizes},
+    anyhow::{anyhow, Result},
+    python_packed_resources::{load_resources, packed_resources_length},
+    serde::Serialize,
+    std::{collections::BTreeMap, fmt::Write, ops::Range},
+};
+
+/// A node in a size report tree.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SizeNode {
+    /// Name of the entity.
+    pub name: String,
+
+    /// Size in bytes, including children.
+    pub size: u64,
+
+    /// Entities contributing to the size.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SizeNode>,
+}
+
+impl SizeNode {
+    /// Construct a leaf node.
+    pub fn new(name: impl ToString, size: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+            children: vec![],
+        }
+    }
+
+    /// Construct a node whose size is the sum of its children.
+    ///
+    /// Children are sorted by descending size.
+    pub fn from_children(name: impl ToString, mut children: Vec<SizeNode>) -> Self {
+        children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+
+        Self {
+            name: name.to_string(),
+            size: children.iter().map(|c| c.size).sum(),
+            children,
+        }
+    }
+}
+
+/// Category of native code a symbol name belongs to.
+fn symbol_category(name: &str) -> &'static str {
+    if name.starts_with("_ZN") || name.starts_with("_R") {
+        "rust"
+    } else if name.starts_with("Py") || name.starts_with("_Py") {
+        "python-distribution"
+    } else {
+        "other"
+    }
+}
+
+/// A region of the file occupied by a section.
+struct Section {
+    name: String,
+    range: Range<u64>,
+    /// Symbol bytes in this section, keyed by category.
+    symbols: BTreeMap<&'static str, u64>,
+}
+
+/// Resolve sections of a binary.
+fn binary_sections(data: &[u8]) -> Vec<Section> {
+    let mut sections = vec![];
+
+    match goblin::Object::parse(data) {
+        Ok(goblin::Object::Elf(elf)) => {
+            for (index, header) in elf.section_headers.iter().enumerate() {
+                if header.sh_type == goblin::elf::section_header::SHT_NOBITS || header.sh_size == 0
+                {
+                    continue;
+                }
+
+                let mut symbols = BTreeMap::new();
+
+                if header.is_executable() {
+                    for sym in elf.syms.iter().filter(|sym| sym.st_shndx == index) {
+                        let name = elf.strtab.get_at(sym.st_name).unwrap_or("");
+                        *symbols.entry(symbol_category(name)).or_default() += sym.st_size;
+                    }
+                }
+
+                sections.push(Section {
+                    name: elf
+                        .shdr_strtab
+                        .get_at(header.sh_name)
+                        .unwrap_or("(unnamed)")
+                        .to_string(),
+                    range: header.sh_offset..header.sh_offset + header.sh_size,
+                    symbols,
+                });
+            }
+        }
+        Ok(goblin::Object::PE(pe)) => {
+            for section in &pe.sections {
+                let start = section.pointer_to_raw_data as u64;
+
+                sections.push(Section {
+                    name: section.name().unwrap_or("(unnamed)").to_string(),
+                    range: start..start + section.size_of_raw_data as u64,
+                    symbols: BTreeMap::new(),
+                });
+            }
+        }
+        Ok(goblin::Object::Mach(goblin::mach::Mach::Binary(macho))) => {
+            for segment in &macho.segments {
+                for (section, _) in segment.sections().unwrap_or_default() {
+                    // Zero fill sections don't occupy the file.
+                    if section.offset == 0 {
+                        continue;
+                    }
+
+                    let start = section.offset as u64;
+
+                    sections.push(Section {
+                        name: format!(
+                            "{},{}",
+                            section.segname().unwrap_or("?"),
+                            section.name().unwrap_or("?")
+                        ),
+                        range: start..start + section.size,
+                        symbols: BTreeMap::new(),
+                    });
+                }
+            }
+        }
+        _ => {}
+    }
+
+    sections
+}
+
+/// Compute a size report for executable data.
+///
+/// `name` is the name of the root node, typically the executable's filename.
+pub fn size_report(name: &str, data: &[u8]) -> Result<SizeNode> {
+    let file_size = data.len() as u64;
+
+    // Resources embedded in the binary, by top-level package.
+    let mut packages: BTreeMap<String, Vec<SizeNode>> = BTreeMap::new();
+    let mut resource_ranges = vec![];
+    let mut resources_overhead = 0;
+
+    for blob in find_packed_resources(data) {
+        let offset = blob.as_ptr() as u64 - data.as_ptr() as u64;
+        let mut blob_data_size = 0;
+
+        for resource in load_resources(blob).map_err(|e| anyhow!("{}", e))? {
+            let resource = resource.map_err(|e| anyhow!("{}", e))?;
+            let size = resource_data_sizes(&resource).values().sum::<usize>() as u64;
+            blob_data_size += size;
+
+            let package = resource
+                .name
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            packages
+                .entry(package)
+                .or_default()
+                .push(SizeNode::new(&resource.name, size));
+        }
+
+        // Only data declaring its length lets us attribute index overhead.
+        let length = packed_resources_length(blob)
+            .map(|l| l as u64)
+            .unwrap_or(blob_data_size);
+        resources_overhead += length.saturating_sub(blob_data_size);
+        resource_ranges.push(offset..offset + length);
+    }
+
+    let mut children = vec![];
+
+    if !resource_ranges.is_empty() {
+        let mut resource_children = packages
+            .into_iter()
+            .map(|(package, resources)| SizeNode::from_children(package, resources))
+            .collect::<Vec<_>>();
+        resource_children.push(SizeNode::new(
+            "(packed resources index)",
+            resources_overhead,
+        ));
+
+        children.push(SizeNode::from_children(
+            "python-resources",
+            resource_children,
+        ));
+    }
+
+    // Section sizes, excluding embedded resources.
+    let mut section_children = vec![];
+    for section in binary_sections(data) {
+        let overlap = resource_ranges
+            .iter()
+            .map(|r| {
+                r.end
+                    .min(section.range.end)
+                    .saturating_sub(r.start.max(section.range.start))
+            })
+            .sum::<u64>();
+        let size = (section.range.end - section.range.start).saturating_sub(overlap);
+
+        if section.symbols.is_empty() {
+            section_children.push(SizeNode::new(section.name, size));
+        } else {
+            let attributed = section.symbols.values().sum::<u64>().min(size);
+            let mut symbol_children = section
+                .symbols
+                .into_iter()
+                .map(|(category, size)| SizeNode::new(category, size))
+                .collect::<Vec<_>>();
+            symbol_children.push(SizeNode::new("(unattributed)", size - attributed));
+
+            let mut node = SizeNode::from_children(section.name, symbol_children);
+            // Symbol sizes can exceed the section size if symbols overlap.
+            node.size = size;
+            section_children.push(node);
+        }
+    }
+
+    if !section_children.is_empty() {
+        children.push(SizeNode::from_children("sections", section_children));
+    }
+
+    let attributed = children.iter().map(|c| c.size).sum::<u64>();
+    children.push(SizeNode::new(
+        "(headers and other)",
+        file_size.saturating_sub(attributed),
+    ));
+
+    let mut root = SizeNode::from_children(name, children);
+    root.size = file_size;
+
+    Ok(root)
+}
+
+/// Render a size report as a text table.
+///
+/// Nodes up to `depth` levels beneath the root are shown.
+pub fn size_report_text(root: &SizeNode, depth: usize) -> String {
+    fn visit(s: &mut String, node: &SizeNode, total: u64, level: usize, depth: usize) {
+        let percent = if total == 0 {
+            0.0
+        } else {
+            node.size as f64 * 100.0 / total as f64
+        };
+
+        let _ = writeln!(
+            s,
+            "{:>12}  {:>6.2}%  {}{}",
+            node.size,
+            percent,
+            "  ".repeat(level),
+            node.name
+        );
+
+        if level < depth {
+            for child in &node.children {
+                visit(s, child, total, level + 1, depth);
+            }
+        }
+    }
+
+    let mut s = String::new();
+    let _ = writeln!(s, "{:>12}  {:>7}  Name", "Bytes", "Percent");
+    visit(&mut s, root, root.size, 0, depth);
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        python_packed_resources::{write_packed_resources_v4, Resource},
+        std::borrow::Cow,
+    };
+
+    #[test]
+    fn test_symbol_category() {
+        assert_eq!(symbol_category("_ZN4core3fmt5write17h"), "rust");
+        assert_eq!(symbol_category("PyObject_GetAttr"), "python-distribution");
+        assert_eq!(
+            symbol_category("_PyEval_EvalFrameDefault"),
+            "python-distribution"
+        );
+        assert_eq!(symbol_category("memcpy"), "other");
+    }
+
+    #[test]
+    fn test_size_report_resources() -> Result<()> {
+        let resources = vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                is_python_package: true,
+                in_memory_bytecode: Some(Cow::from(vec![0u8; 100])),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("foo.bar"),
+                is_python_module: true,
+                in_memory_source: Some(Cow::from(vec![0u8; 50])),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("baz"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(vec![0u8; 10])),
+                ..Resource::default()
+            },
+        ];
+
+        let mut data = b"not a binary".to_vec();
+        write_packed_resources_v4(&resources, &mut data, None)?;
+        data.extend(b"trailer");
+
+        let report = size_report("app", &data)?;
+
+        assert_eq!(report.name, "app");
+        assert_eq!(report.size, data.len() as u64);
+        assert_eq!(report.children[0].name, "python-resources");
+
+        let python = &report.children[0];
+        let package = |name: &str| python.children.iter().find(|c| c.name == name).unwrap();
+        assert_eq!(
+            package("foo"),
+            &SizeNode {
+                name: "foo".to_string(),
+                size: 150,
+                children: vec![SizeNode::new("foo", 100), SizeNode::new("foo.bar", 50)],
+            }
+        );
+        assert_eq!(package("baz").size, 10);
+        assert!(package("(packed resources index)").size > 0);
+
+        // Everything is attributed to something.
+        assert_eq!(
+            report.children.iter().map(|c| c.size).sum::<u64>(),
+            report.size
+        );
+
+        let text = size_report_text(&report, 2);
+        assert!(text
+            .lines()
+            .any(|l| l.trim_start().starts_with("150 ") && l.ends_with("    foo")));
+        assert!(!text.contains("foo.bar"));
+
+        Ok(())
+    }
+}
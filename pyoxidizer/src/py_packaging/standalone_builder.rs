@@ -5,21 +5,30 @@
 use {
     super::{
         binary::{
-            pyembed_licenses, LibpythonLinkMode, PackedResourcesLoadMode, PythonBinaryBuilder,
-            ResourceAddCollectionContextCallback, WindowsRuntimeDllsMode,
+            pyembed_licenses, DunderFilePolicy, LibpythonLinkMode, PackedResourcesLoadMode,
+            PythonBinaryBuilder, ResourceAddCollectionContextCallback, WindowsRuntimeDllsMode,
         },
+        cargo_options::CargoBuildOptions,
+        conda::{conda_packages, CondaPayload},
         config::{PyembedPackedResourcesSource, PyembedPythonInterpreterConfig},
         distribution::{AppleSdkInfo, BinaryLibpythonLinkMode, PythonDistribution},
         embedding::{
             EmbeddedPythonContext, LibpythonLinkSettings, LinkSharedLibraryPath,
-            LinkStaticLibraryData, LinkingAnnotation,
+            LinkStaticLibraryData, LinkingAnnotation, EXTRACT_ARCHIVE_FILENAME,
+            SPLASH_SCREEN_IMAGE_FILENAME,
         },
         filtering::{filter_btreemap, resolve_resource_names_from_files},
+        hooks::{builtin_packaging_hooks, PackagingHook, PACKAGING_HOOKS_VERSION},
         libpython::link_libpython,
         packaging_tool::{
             find_resources, pip_download, pip_install, read_virtualenv, setup_py_install,
+            PipIndexSettings,
         },
+        post_processing::BinaryPostProcessor,
+        qt::{deploy_qt_binding, qt_conf},
         standalone_distribution::StandaloneDistribution,
+        wheel_resolver::download_wheels,
+        windows_resources::WindowsResources,
     },
     crate::environment::Environment,
     anyhow::{anyhow, Context, Result},
@@ -40,7 +49,9 @@ use {
             AddResourceAction, PrePackagedResource, PythonResourceAddCollectionContext,
             PythonResourceCollector,
         },
+        wheel::WheelArchive,
     },
+    sha2::{Digest, Sha256},
     slog::warn,
     std::{
         collections::{BTreeMap, BTreeSet, HashMap},
@@ -53,6 +64,19 @@ use {
     tugger_windows::{find_visual_cpp_redistributable, VcRedistributablePlatform},
 };
 
+/// Modules built into the interpreter core, which aren't packaged resources.
+const CORE_BUILTIN_MODULES: &[&str] = &[
+    "_frozen_importlib",
+    "_frozen_importlib_external",
+    "_imp",
+    "_thread",
+    "_warnings",
+    "_weakref",
+    "builtins",
+    "marshal",
+    "sys",
+];
+
 /// Libraries that we should not link against on Linux.
 static LINUX_IGNORE_LIBRARIES: Lazy<Vec<&'static str>> = Lazy::new(|| vec!["dl", "m"]);
 
@@ -127,6 +151,42 @@ pub struct StandalonePythonExecutableBuilder {
 
     /// Describes how Windows runtime DLLs should be handled during builds.
     windows_runtime_dlls_mode: WindowsRuntimeDllsMode,
+
+    /// Resources to embed in Windows executables.
+    windows_resources: WindowsResources,
+
+    /// Operations to perform on the built binary.
+    binary_post_processor: Option<BinaryPostProcessor>,
+
+    /// Filesystem relative path to move packages referencing `__file__` to.
+    dunder_file_packages_path: Option<String>,
+
+    /// Policies for in-memory modules referencing `__file__`, keyed by package name.
+    dunder_file_policies: BTreeMap<String, DunderFilePolicy>,
+
+    /// Modules imported in ways that can't be seen, which must be packaged.
+    hidden_imports: BTreeSet<String>,
+
+    /// Version of the built-in packaging hooks to apply.
+    packaging_hooks_version: u32,
+
+    /// Packaging hooks added to the built-in ones, by package name.
+    packaging_hooks: BTreeMap<String, PackagingHook>,
+
+    /// Newest glibc version the built binary may require symbols from.
+    glibc_version_ceiling: Option<String>,
+
+    /// Whether to write a size report next to the built binary.
+    write_size_report: bool,
+
+    /// Options controlling the `cargo build` of the Rust project building the binary.
+    cargo_build_options: CargoBuildOptions,
+
+    /// Whether to vendor crates of the Rust project building the binary.
+    vendor_crates: bool,
+
+    /// Whether to embed files installed next to the binary in it.
+    self_extracting: bool,
 }
 
 impl StandalonePythonExecutableBuilder {
@@ -185,6 +245,12 @@ impl StandalonePythonExecutableBuilder {
         if let Some(fallback) = packaging_policy.resources_location_fallback() {
             allowed_locations.push(AbstractResourceLocation::from(fallback));
         }
+        // Resources of GPU stacks are always added to the filesystem.
+        if packaging_policy.gpu_stack_compat()
+            && !allowed_locations.contains(&AbstractResourceLocation::RelativePath)
+        {
+            allowed_locations.push(AbstractResourceLocation::RelativePath);
+        }
 
         let mut allowed_extension_module_locations = vec![];
 
@@ -225,6 +291,18 @@ impl StandalonePythonExecutableBuilder {
             windows_subsystem: "console".to_string(),
             tcl_files_path: None,
             windows_runtime_dlls_mode: WindowsRuntimeDllsMode::WhenPresent,
+            windows_resources: WindowsResources::default(),
+            binary_post_processor: None,
+            dunder_file_packages_path: None,
+            dunder_file_policies: BTreeMap::new(),
+            hidden_imports: BTreeSet::new(),
+            packaging_hooks_version: PACKAGING_HOOKS_VERSION,
+            packaging_hooks: BTreeMap::new(),
+            glibc_version_ceiling: None,
+            write_size_report: false,
+            cargo_build_options: CargoBuildOptions::default(),
+            vendor_crates: false,
+            self_extracting: false,
         });
 
         builder.add_distribution_core_state()?;
@@ -376,7 +454,7 @@ impl StandalonePythonExecutableBuilder {
     }
 
     /// Resolves Windows runtime DLLs file needed for this binary given current settings.
-    fn resolve_windows_runtime_dll_files(&self) -> Result<FileManifest> {
+    fn resolve_windows_runtime_dll_files(&self, logger: &slog::Logger) -> Result<FileManifest> {
         let mut manifest = FileManifest::default();
 
         // If we require Windows CRT DLLs and we're told to install them, do that.
@@ -385,7 +463,7 @@ impl StandalonePythonExecutableBuilder {
                 self.windows_runtime_dlls_mode(),
                 WindowsRuntimeDllsMode::WhenPresent | WindowsRuntimeDllsMode::Always
             ) {
-                match find_visual_cpp_redistributable(&version, platform) {
+                match find_visual_cpp_redistributable(logger, &version, platform) {
                     Ok(paths) => {
                         for path in paths {
                             let file_name = PathBuf::from(
@@ -414,6 +492,271 @@ impl StandalonePythonExecutableBuilder {
 
         Ok(manifest)
     }
+
+    /// Move files to install next to the binary into a zstd compressed tar archive.
+    ///
+    /// The libpython shared library is left in place, as it is loaded before
+    /// the archive can be extracted.
+    ///
+    /// Files are checked for secret values before they are compressed.
+    fn create_extract_archive(
+        &self,
+        env: &Environment,
+        extra_files: &mut FileManifest,
+    ) -> Result<Vec<u8>> {
+        let mut retained = BTreeSet::new();
+        if self.link_mode == LibpythonLinkMode::Dynamic {
+            if let Some(p) = &self.target_distribution.libpython_shared_library {
+                retained.insert(PathBuf::from(p.file_name().unwrap()));
+                retained.insert(PathBuf::from("python3.dll"));
+            }
+        }
+
+        let paths = extra_files
+            .iter_entries()
+            .map(|(path, _)| path.clone())
+            .filter(|path| !retained.contains(path))
+            .collect::<Vec<_>>();
+
+        let mut builder = tar::Builder::new(vec![]);
+
+        for path in paths {
+            let entry = extra_files.remove(&path).unwrap();
+
+            let mut header = tar::Header::new_gnu();
+            header.set_mtime(0);
+            header.set_uid(0);
+            header.set_gid(0);
+
+            if let Some(target) = entry.link_target() {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                builder.append_link(&mut header, &path, target)?;
+            } else if let Some(target) = entry.hardlink_target() {
+                header.set_entry_type(tar::EntryType::Link);
+                header.set_mode(0o644);
+                header.set_size(0);
+                builder.append_link(&mut header, &path, target)?;
+            } else {
+                let data = entry
+                    .resolve_content()
+                    .with_context(|| format!("resolving content of {}", path.display()))?;
+                env.ensure_no_secrets(&path.display().to_string(), &data)?;
+                header.set_mode(entry.mode());
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, &path, data.as_slice())?;
+            }
+        }
+
+        Ok(zstd::stream::encode_all(
+            builder.into_inner()?.as_slice(),
+            10,
+        )?)
+    }
+
+    /// Ensure nothing requires dynamic linking.
+    ///
+    /// Binaries targeting musl libc are fully static and can't load shared
+    /// libraries. So any dependency on a shared library is an error.
+    fn validate_static_linking(&self, resources_collector: &PythonResourceCollector) -> Result<()> {
+        let mut offenders = vec![];
+
+        for lib in &self.core_build_context.dynamic_libraries {
+            offenders.push(format!("Python distribution links shared library {}", lib));
+        }
+
+        for (name, context) in &self.extension_build_contexts {
+            for lib in &context.dynamic_libraries {
+                offenders.push(format!(
+                    "extension module {} links shared library {}",
+                    name, lib
+                ));
+            }
+        }
+
+        for (name, resource) in resources_collector.iter_resources() {
+            if resource.is_shared_library {
+                offenders.push(format!("shared library {} is packaged", name));
+            }
+            if resource.in_memory_extension_module_shared_library.is_some()
+                || resource
+                    .relative_path_extension_module_shared_library
+                    .is_some()
+            {
+                offenders.push(format!("extension module {} is a shared library", name));
+            }
+        }
+
+        if offenders.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} executables are statically linked but dynamic dependencies were found:\n  {}\n\
+                 remove the packages providing them, install versions of them that can be \
+                 built as built-in extension modules, or target a glibc Linux triple instead",
+                self.target_triple,
+                offenders.join("\n  ")
+            ))
+        }
+    }
+
+    /// Ensure a module imported in ways that can't be seen is packaged.
+    ///
+    /// A missing module is added with its sub-modules from the Python
+    /// distribution, regardless of the packaging policy. `importer` names
+    /// what imports the module.
+    fn add_hidden_import(
+        &self,
+        logger: &slog::Logger,
+        resources_collector: &mut PythonResourceCollector,
+        module: &str,
+        importer: &str,
+    ) -> Result<()> {
+        if resources_collector
+            .iter_resources()
+            .any(|(name, resource)| name == module && resource.is_python_resource())
+        {
+            return Ok(());
+        }
+
+        let mut found = false;
+
+        for resource in self.target_distribution.python_resources() {
+            if let PythonResource::ModuleSource(source) = &resource {
+                if source.is_test
+                    || !(source.name == module || source.name.starts_with(&format!("{}.", module)))
+                {
+                    continue;
+                }
+
+                let mut add_context = self
+                    .packaging_policy
+                    .derive_add_collection_context(&resource);
+                add_context.include = true;
+
+                resources_collector.add_python_module_source_with_context(source, &add_context)?;
+                found = true;
+            }
+        }
+
+        if found {
+            warn!(logger, "adding {} because {} imports it", module, importer);
+        } else {
+            warn!(
+                logger,
+                "warning: {} imports {} but it isn't packaged", importer, module
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Warn about modules imported dynamically which aren't packaged.
+    ///
+    /// Source code of modules outside the standard library is scanned for
+    /// calls to `__import__()` and `importlib.import_module()` with literal
+    /// module names.
+    fn warn_missing_dynamic_imports(
+        &self,
+        logger: &slog::Logger,
+        resources_collector: &PythonResourceCollector,
+        compiler: &mut BytecodeCompiler,
+    ) -> Result<()> {
+        let packaged = resources_collector
+            .iter_resources()
+            .filter(|(_, resource)| resource.is_python_resource())
+            .map(|(name, _)| name.as_str())
+            .collect::<BTreeSet<_>>();
+
+        for (name, resource) in resources_collector.iter_resources() {
+            if !resource.is_module || self.target_distribution.py_modules.contains_key(name) {
+                continue;
+            }
+
+            let source = match resource.module_source() {
+                Some(source) => source.resolve_content()?,
+                None => continue,
+            };
+
+            let imports = match compiler.find_dynamic_imports(&source, name, resource.is_package) {
+                Ok(imports) => imports,
+                Err(err) => {
+                    warn!(logger, "unable to analyze imports of {}: {}", name, err);
+                    continue;
+                }
+            };
+
+            for import in imports {
+                if !packaged.contains(import.as_str())
+                    && !CORE_BUILTIN_MODULES.contains(&import.as_str())
+                {
+                    warn!(
+                        logger,
+                        "warning: {} dynamically imports {} but it isn't packaged; \
+                         declare it via add_hidden_imports() if it is needed",
+                        name,
+                        import
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find modules imported by `import` statements of packaged modules.
+    ///
+    /// Modules of the Python distribution and of `exclude_packages` aren't
+    /// scanned. Modules declared via `add_hidden_imports()` are included.
+    fn find_imports(
+        &self,
+        logger: &slog::Logger,
+        resources_collector: &PythonResourceCollector,
+        compiler: &mut BytecodeCompiler,
+        exclude_packages: &BTreeSet<String>,
+    ) -> Result<BTreeSet<String>> {
+        let mut res = self.hidden_imports.clone();
+
+        for (name, resource) in resources_collector.iter_resources() {
+            if !resource.is_module
+                || self.target_distribution.py_modules.contains_key(name)
+                || exclude_packages.contains(name.split('.').next().unwrap_or_default())
+            {
+                continue;
+            }
+
+            let source = match resource.module_source() {
+                Some(source) => source.resolve_content()?,
+                None => continue,
+            };
+
+            match compiler.find_imports(&source, name, resource.is_package) {
+                Ok(imports) => res.extend(imports),
+                Err(err) => {
+                    warn!(logger, "unable to analyze imports of {}: {}", name, err);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// The `__file__` policy applying to a module and the package it was set for.
+    ///
+    /// The policy of the innermost package containing the module applies.
+    fn dunder_file_policy(&self, module: &str) -> Option<(&str, DunderFilePolicy)> {
+        self.dunder_file_policies
+            .iter()
+            .filter(|(package, _)| {
+                module == package.as_str()
+                    || module
+                        .strip_prefix(package.as_str())
+                        .map_or(false, |rest| rest.starts_with('.'))
+            })
+            .max_by_key(|(package, _)| package.len())
+            .map(|(package, policy)| (package.as_str(), *policy))
+    }
 }
 
 impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
@@ -421,10 +764,24 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Arc::new(self.clone())
     }
 
+    fn clone_box(&self) -> Box<dyn PythonBinaryBuilder> {
+        Box::new(self.clone())
+    }
+
     fn name(&self) -> String {
         self.exe_name.clone()
     }
 
+    fn set_name(&mut self, name: &str) {
+        self.exe_name = name.to_string();
+    }
+
+    fn set_run_command(&mut self, code: &str) {
+        self.config.config.run_command = Some(code.to_string());
+        self.config.config.run_filename = None;
+        self.config.config.run_module = None;
+    }
+
     fn libpython_link_mode(&self) -> LibpythonLinkMode {
         self.link_mode
     }
@@ -486,17 +843,21 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
     fn set_tcl_files_path(&mut self, value: Option<String>) {
         self.tcl_files_path = value;
 
-        self.config.tcl_library = if let Some(path) = &self.tcl_files_path {
-            Some(
-                PathBuf::from("$ORIGIN").join(path).join(
-                    self.target_distribution
-                        .tcl_library_path_directory()
-                        .expect("should have a tcl library path directory"),
-                ),
-            )
-        } else {
-            None
-        };
+        let root = self
+            .tcl_files_path
+            .as_ref()
+            .map(|path| PathBuf::from("$ORIGIN").join(path));
+
+        self.config.tcl_library = root.as_ref().and_then(|root| {
+            self.target_distribution
+                .tcl_library_path_directory()
+                .map(|dir| root.join(dir))
+        });
+        self.config.tk_library = root.as_ref().and_then(|root| {
+            self.target_distribution
+                .tk_library_path_directory()
+                .map(|dir| root.join(dir))
+        });
     }
 
     fn windows_subsystem(&self) -> &str {
@@ -509,6 +870,116 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Ok(())
     }
 
+    fn windows_resources(&self) -> &WindowsResources {
+        &self.windows_resources
+    }
+
+    fn set_windows_resources(&mut self, value: WindowsResources) {
+        self.windows_resources = value;
+    }
+
+    fn binary_post_processor(&self) -> Option<&BinaryPostProcessor> {
+        self.binary_post_processor.as_ref()
+    }
+
+    fn set_binary_post_processor(&mut self, value: Option<BinaryPostProcessor>) {
+        self.binary_post_processor = value;
+    }
+
+    fn dunder_file_packages_path(&self) -> &Option<String> {
+        &self.dunder_file_packages_path
+    }
+
+    fn set_dunder_file_packages_path(&mut self, value: Option<String>) {
+        self.dunder_file_packages_path = value;
+    }
+
+    fn dunder_file_policies(&self) -> &BTreeMap<String, DunderFilePolicy> {
+        &self.dunder_file_policies
+    }
+
+    fn set_dunder_file_policy(&mut self, package: String, policy: DunderFilePolicy) {
+        self.dunder_file_policies.insert(package, policy);
+    }
+
+    fn hidden_imports(&self) -> &BTreeSet<String> {
+        &self.hidden_imports
+    }
+
+    fn add_hidden_imports(&mut self, names: Vec<String>) {
+        self.hidden_imports.extend(names);
+    }
+
+    fn runtime_metadata(&self) -> &BTreeMap<String, String> {
+        &self.config.runtime_metadata
+    }
+
+    fn set_runtime_metadata(&mut self, values: BTreeMap<String, String>) {
+        self.config.runtime_metadata.extend(values);
+    }
+
+    fn packaging_hooks_version(&self) -> u32 {
+        self.packaging_hooks_version
+    }
+
+    fn set_packaging_hooks_version(&mut self, version: u32) {
+        self.packaging_hooks_version = version;
+    }
+
+    fn packaging_hooks(&self) -> BTreeMap<String, PackagingHook> {
+        let mut hooks = builtin_packaging_hooks(self.packaging_hooks_version)
+            .into_iter()
+            .map(|hook| (hook.package.clone(), hook))
+            .collect::<BTreeMap<_, _>>();
+        hooks.extend(self.packaging_hooks.clone());
+
+        hooks
+    }
+
+    fn add_packaging_hook(&mut self, hook: PackagingHook) {
+        self.packaging_hooks.insert(hook.package.clone(), hook);
+    }
+
+    fn glibc_version_ceiling(&self) -> &Option<String> {
+        &self.glibc_version_ceiling
+    }
+
+    fn set_glibc_version_ceiling(&mut self, value: Option<String>) {
+        self.glibc_version_ceiling = value;
+    }
+
+    fn write_size_report(&self) -> bool {
+        self.write_size_report
+    }
+
+    fn set_write_size_report(&mut self, value: bool) {
+        self.write_size_report = value;
+    }
+
+    fn cargo_build_options(&self) -> &CargoBuildOptions {
+        &self.cargo_build_options
+    }
+
+    fn set_cargo_build_options(&mut self, value: CargoBuildOptions) {
+        self.cargo_build_options = value;
+    }
+
+    fn vendor_crates(&self) -> bool {
+        self.vendor_crates
+    }
+
+    fn set_vendor_crates(&mut self, value: bool) {
+        self.vendor_crates = value;
+    }
+
+    fn self_extracting(&self) -> bool {
+        self.self_extracting
+    }
+
+    fn set_self_extracting(&mut self, value: bool) {
+        self.self_extracting = value;
+    }
+
     fn packed_resources_load_mode(&self) -> &PackedResourcesLoadMode {
         &self.resources_load_mode
     }
@@ -540,6 +1011,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         logger: &slog::Logger,
         verbose: bool,
         args: &[String],
+        extra_envs: &HashMap<String, String>,
     ) -> Result<Vec<PythonResource>> {
         let resources = pip_download(
             logger,
@@ -548,6 +1020,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             self.python_packaging_policy(),
             verbose,
             args,
+            extra_envs,
         )
         .context("calling pip download")?;
 
@@ -557,6 +1030,52 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Ok(resources)
     }
 
+    fn resolve_wheels(
+        &mut self,
+        logger: &slog::Logger,
+        args: &[String],
+        index_settings: &PipIndexSettings,
+        wheels_dir: &Path,
+    ) -> Result<Vec<PythonResource>> {
+        let resources = download_wheels(
+            logger,
+            &*self.target_distribution,
+            self.python_packaging_policy(),
+            args,
+            index_settings,
+            wheels_dir,
+        )
+        .context("resolving wheels")?;
+
+        self.index_package_license_info_from_resources(&resources)
+            .context("indexing package license metadata")?;
+
+        Ok(resources)
+    }
+
+    fn conda_packages(
+        &mut self,
+        logger: &slog::Logger,
+        specs: &[String],
+        channels: &[String],
+        cache_dir: &Path,
+    ) -> Result<CondaPayload> {
+        let payload = conda_packages(
+            logger,
+            &*self.target_distribution,
+            self.python_packaging_policy(),
+            specs,
+            channels,
+            cache_dir,
+        )
+        .context("obtaining conda packages")?;
+
+        self.index_package_license_info_from_resources(&payload.resources)
+            .context("indexing package license metadata")?;
+
+        Ok(payload)
+    }
+
     fn pip_install(
         &mut self,
         logger: &slog::Logger,
@@ -628,6 +1147,47 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         Ok(resources)
     }
 
+    fn read_bundled_pip(&mut self, _logger: &slog::Logger) -> Result<Vec<PythonResource>> {
+        let wheel = self
+            .target_distribution
+            .python_resources()
+            .into_iter()
+            .find_map(|resource| match resource {
+                PythonResource::PackageResource(resource)
+                    if resource.leaf_package == "ensurepip"
+                        && resource.relative_name.starts_with("_bundled/pip-")
+                        && resource.relative_name.ends_with(".whl") =>
+                {
+                    Some(resource)
+                }
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Python distribution does not bundle a pip wheel"))?;
+
+        let basename = wheel.relative_name.trim_start_matches("_bundled/");
+        let archive = WheelArchive::from_reader(
+            std::io::Cursor::new(wheel.data.resolve_content()?),
+            basename,
+        )
+        .with_context(|| format!("reading {}", basename))?;
+
+        let resources = archive
+            .python_resources(
+                self.target_distribution.cache_tag(),
+                &self.target_distribution.python_module_suffixes()?,
+                false,
+                true,
+            )?
+            .into_iter()
+            .filter(|resource| resource.is_in_packages(&["pip".to_string()]))
+            .collect::<Vec<_>>();
+
+        self.index_package_license_info_from_resources(&resources)
+            .context("indexing package license metadata")?;
+
+        Ok(resources)
+    }
+
     fn setup_py_install(
         &mut self,
         logger: &slog::Logger,
@@ -721,7 +1281,8 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                         source.top_level_package(),
                         core_component
                             .spdx_expression()
-                            .ok_or_else(|| anyhow!("should have resolved SPDX expression"))?.as_ref(),
+                            .ok_or_else(|| anyhow!("should have resolved SPDX expression"))?
+                            .as_ref(),
                     )?;
                     component.set_flavor(ComponentFlavor::PythonPackage);
 
@@ -743,11 +1304,16 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         module: &PythonModuleSource,
         add_context: Option<PythonResourceAddCollectionContext>,
     ) -> Result<Vec<AddResourceAction>> {
-        let add_context = add_context.unwrap_or_else(|| {
+        let mut add_context = add_context.unwrap_or_else(|| {
             self.packaging_policy
                 .derive_add_collection_context(&module.into())
         });
 
+        // Coverage mode and debugger support compile modules from source at run-time.
+        if (self.config.coverage_mode || self.config.debugger_support) && !module.is_stdlib {
+            add_context.store_source = true;
+        }
+
         self.resources_collector
             .add_python_module_source_with_context(module, &add_context)
     }
@@ -872,16 +1438,200 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         self.config.allocator_backend == MemoryAllocatorBackend::Snmalloc
     }
 
+    fn requires_crash_reporting(&self) -> bool {
+        self.config.crash_reports_directory.is_some()
+    }
+
+    fn requires_updater(&self) -> bool {
+        self.config.update_feed_url.is_some()
+    }
+
+    fn requires_daemon(&self) -> bool {
+        self.config.daemonize
+            || self.config.pid_file.is_some()
+            || self.config.stdout_path.is_some()
+            || self.config.stderr_path.is_some()
+    }
+
+    fn requires_single_instance(&self) -> bool {
+        self.config.single_instance.is_some()
+    }
+
+    fn requires_splash_screen(&self) -> bool {
+        self.config.splash_screen_image.is_some()
+    }
+
+    fn requires_output_capture(&self) -> bool {
+        self.config.requires_output_capture()
+    }
+
+    fn requires_logging_bridge(&self) -> bool {
+        self.config.logging_bridge
+    }
+
+    fn requires_runtime_config(&self) -> bool {
+        !self.config.runtime_config_overrides.is_empty()
+    }
+
     fn to_embedded_python_context(
         &self,
         logger: &slog::Logger,
         env: &Environment,
         opt_level: &str,
     ) -> Result<EmbeddedPythonContext> {
+        let mut resources_collector = self.resources_collector.clone();
+
+        let top_level_names = resources_collector.all_top_level_module_names();
+        let hooks = self
+            .packaging_hooks()
+            .into_values()
+            .filter(|hook| top_level_names.contains(&hook.package))
+            .collect::<Vec<_>>();
+
+        for hook in &hooks {
+            warn!(logger, "applying packaging hook for {}", hook.package);
+
+            for module in &hook.hidden_imports {
+                self.add_hidden_import(logger, &mut resources_collector, module, &hook.package)?;
+            }
+        }
+
+        for module in &self.hidden_imports {
+            self.add_hidden_import(logger, &mut resources_collector, module, &self.exe_name)?;
+        }
+
+        let filesystem_packages = hooks
+            .iter()
+            .filter(|hook| hook.filesystem_resources)
+            .map(|hook| hook.package.clone())
+            .collect::<BTreeSet<_>>();
+
+        if !filesystem_packages.is_empty() {
+            if resources_collector
+                .allowed_locations()
+                .contains(&AbstractResourceLocation::RelativePath)
+            {
+                let prefix = self.dunder_file_packages_path.as_deref().unwrap_or("lib");
+
+                for name in resources_collector.relocate_packages_to_relative_path(
+                    &filesystem_packages,
+                    prefix,
+                    self.cache_tag(),
+                )? {
+                    warn!(
+                        logger,
+                        "moving {} to {} because its packaging hook requires it", name, prefix
+                    );
+                }
+            } else {
+                warn!(
+                    logger,
+                    "packaging hooks of {:?} install resources on the filesystem but the \
+                     packaging policy doesn't allow filesystem-relative resources",
+                    filesystem_packages
+                );
+            }
+        }
+
+        let qt_bindings = hooks
+            .iter()
+            .filter(|hook| hook.qt_deployment)
+            .map(|hook| hook.package.clone())
+            .collect::<BTreeSet<_>>();
+
+        let mut qt_conf_data = None;
+
+        if !qt_bindings.is_empty() {
+            let temp_dir = tempfile::TempDir::new()?;
+            let mut compiler = BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?;
+            let imports =
+                self.find_imports(logger, &resources_collector, &mut compiler, &qt_bindings)?;
+
+            for binding in &qt_bindings {
+                let deployment = deploy_qt_binding(&mut resources_collector, binding, &imports)?;
+
+                warn!(
+                    logger,
+                    "{} Qt modules used: {:?}", binding, deployment.needed_modules
+                );
+                if !deployment.excluded_modules.is_empty() {
+                    warn!(
+                        logger,
+                        "excluding unused Qt modules of {}: {:?}",
+                        binding,
+                        deployment.excluded_modules
+                    );
+                }
+
+                match deployment.prefix {
+                    Some(prefix) if qt_conf_data.is_none() => {
+                        qt_conf_data = Some(qt_conf(&prefix));
+                    }
+                    Some(_) => {
+                        warn!(
+                            logger,
+                            "multiple Qt bindings are packaged; qt.conf only locates the files of one"
+                        );
+                    }
+                    None => {
+                        warn!(
+                            logger,
+                            "Qt plugins of {} aren't installed on the filesystem; Qt may fail to \
+                             load them",
+                            binding
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(prefix) = &self.dunder_file_packages_path {
+            let packages = resources_collector
+                .find_dunder_file()?
+                .iter()
+                .map(|name| name.split('.').next().unwrap_or_default().to_string())
+                .collect::<BTreeSet<_>>();
+
+            for name in resources_collector.relocate_packages_to_relative_path(
+                &packages,
+                prefix,
+                self.cache_tag(),
+            )? {
+                warn!(
+                    logger,
+                    "moving {} to {} because its package references __file__", name, prefix
+                );
+            }
+        }
+
+        if self.target_triple.contains("linux-musl") {
+            self.validate_static_linking(&resources_collector)?;
+        }
+
+        let mut dunder_file_packages = BTreeSet::new();
+        let mut dunder_file_errors = vec![];
         let mut file_seen = false;
-        for module in self.resources_collector.find_dunder_file()? {
-            file_seen = true;
-            warn!(logger, "warning: {} contains __file__", module);
+        for module in resources_collector.find_dunder_file()? {
+            match self.dunder_file_policy(&module) {
+                Some((package, DunderFilePolicy::Extract)) => {
+                    dunder_file_packages.insert(package.to_string());
+                }
+                Some((package, DunderFilePolicy::Error)) => {
+                    dunder_file_errors.push(format!("{} (package {})", module, package));
+                }
+                _ => {
+                    file_seen = true;
+                    warn!(logger, "warning: {} contains __file__", module);
+                }
+            }
+        }
+
+        if !dunder_file_errors.is_empty() {
+            return Err(anyhow!(
+                "modules imported from memory reference __file__:\n  {}\n\
+                 install their packages to the filesystem or use the extract policy for them",
+                dunder_file_errors.join("\n  ")
+            ));
         }
 
         if file_seen {
@@ -896,7 +1646,7 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             );
         }
 
-        let license_report = self.resources_collector.generate_license_report()?;
+        let license_report = resources_collector.generate_license_report()?;
         if license_report.no_license_packages.is_empty() {
             warn!(logger, "All Python packages have license metadata");
         } else {
@@ -933,7 +1683,10 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         let compiled_resources = {
             let temp_dir = tempfile::TempDir::new()?;
             let mut compiler = BytecodeCompiler::new(self.host_python_exe_path(), temp_dir.path())?;
-            self.resources_collector.compile_resources(&mut compiler)?
+            self.warn_missing_dynamic_imports(logger, &resources_collector, &mut compiler)?;
+            resources_collector
+                .compile_resources(&mut compiler)?
+                .into_owned()
         };
 
         let mut pending_resources = vec![];
@@ -941,6 +1694,30 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
         let mut extra_files = compiled_resources.extra_files_manifest()?;
 
         let mut config = self.config.clone();
+        let mut shared_resources_file = None;
+
+        // Modules of these packages get a __file__ in the directory package
+        // data is extracted to at run-time.
+        config.dunder_file_packages.extend(dunder_file_packages);
+
+        for hook in &hooks {
+            config.runtime_hooks.extend(hook.runtime_hooks.clone());
+        }
+
+        // Shared libraries installed next to the executable are searched by
+        // ctypes.util.find_library() and for DLL dependencies.
+        for prefix in resources_collector
+            .iter_resources()
+            .filter_map(|(_, resource)| resource.relative_path_shared_library.as_ref())
+            .map(|(prefix, _, _)| prefix)
+            .collect::<BTreeSet<_>>()
+        {
+            let path = PathBuf::from("$ORIGIN").join(prefix);
+
+            if !config.shared_library_search_paths.contains(&path) {
+                config.shared_library_search_paths.push(path);
+            }
+        }
 
         match &self.resources_load_mode {
             PackedResourcesLoadMode::None => {}
@@ -960,6 +1737,19 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
                     .context("serializing packed resources")?;
                 extra_files.add_file_entry(Path::new(path), buffer)?;
 
+                config
+                    .packed_resources
+                    .push(PyembedPackedResourcesSource::MemoryMappedPath(
+                        PathBuf::from("$ORIGIN").join(path),
+                    ));
+            }
+            PackedResourcesLoadMode::SharedPathMemoryMapped(path) => {
+                let mut buffer = vec![];
+                compiled_resources
+                    .write_packed_resources(&mut buffer)
+                    .context("serializing packed resources")?;
+                shared_resources_file = Some((PathBuf::from(path), buffer));
+
                 config
                     .packed_resources
                     .push(PyembedPackedResourcesSource::MemoryMappedPath(
@@ -998,8 +1788,56 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             }
         }
 
+        // Qt looks for qt.conf in the directory of the executable.
+        if let Some(data) = qt_conf_data {
+            extra_files.add_file_entry(Path::new("qt.conf"), data.into_bytes())?;
+        }
+
+        if self.self_extracting && shared_resources_file.is_some() {
+            return Err(anyhow!(
+                "self-extracting executables can't load resources from a shared file"
+            ));
+        }
+
+        // Files are extracted to a per-user cache directory at run-time. The
+        // directory name is derived from the archive content so upgraded
+        // applications don't use stale files. Computing it here saves hashing
+        // the archive on every run.
+        let extract_archive = if self.self_extracting && !extra_files.is_empty() {
+            let data = self.create_extract_archive(env, &mut extra_files)?;
+            let digest = hex::encode(&Sha256::digest(&data)[0..8]);
+
+            config.extract_archive = Some(PathBuf::from(EXTRACT_ARCHIVE_FILENAME));
+            config.extract_directory = Some(PathBuf::from(&self.exe_name).join(digest));
+
+            Some((PathBuf::from(EXTRACT_ARCHIVE_FILENAME), data))
+        } else {
+            None
+        };
+
+        // The image is copied next to the generated config so `include_bytes!`
+        // doesn't depend on the directory the build runs in.
+        let splash_screen_image = if let Some(path) = &self.config.splash_screen_image {
+            let data = std::fs::read(path)
+                .with_context(|| format!("reading splash screen image {}", path.display()))?;
+
+            // Only uncompressed 24 and 32 bits per pixel bitmaps can be shown.
+            if !data.starts_with(b"BM") || !matches!(data.get(28), Some(24) | Some(32)) {
+                return Err(anyhow!(
+                    "splash screen image {} must be a BMP file with 24 or 32 bits per pixel",
+                    path.display()
+                ));
+            }
+
+            config.splash_screen_image = Some(PathBuf::from(SPLASH_SCREEN_IMAGE_FILENAME));
+
+            Some((PathBuf::from(SPLASH_SCREEN_IMAGE_FILENAME), data))
+        } else {
+            None
+        };
+
         // Install Windows runtime DLLs if told to do so.
-        extra_files.add_manifest(&self.resolve_windows_runtime_dll_files()?)?;
+        extra_files.add_manifest(&self.resolve_windows_runtime_dll_files(logger)?)?;
 
         let python_implementation = if self
             .target_distribution
@@ -1068,6 +1906,9 @@ impl PythonBinaryBuilder for StandalonePythonExecutableBuilder {
             link_settings,
             pending_resources,
             extra_files,
+            shared_resources_file,
+            extract_archive,
+            splash_screen_image,
             host_triple: self.host_triple.clone(),
             target_triple: self.target_triple.clone(),
             python_implementation,
@@ -1089,7 +1930,10 @@ pub mod tests {
             testutil::*,
         },
         once_cell::sync::Lazy,
-        python_packaging::{location::ConcreteResourceLocation, policy::ExtensionModuleFilter},
+        python_packaging::{
+            location::ConcreteResourceLocation, policy::ExtensionModuleFilter,
+            resource::SharedLibrary,
+        },
         std::ops::DerefMut,
         tugger_licensing::LicensedComponents,
     };
@@ -1384,6 +2228,213 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_self_extracting() -> Result<()> {
+        let logger = get_logger()?;
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let mut exe = options.new_builder()?;
+        exe.resources_load_mode =
+            PackedResourcesLoadMode::BinaryRelativePathMemoryMapped("resources".into());
+        exe.set_self_extracting(true);
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+
+        assert!(!embedded.extra_files.has_path(Path::new("resources")));
+        assert_eq!(
+            embedded.config.extract_archive,
+            Some(PathBuf::from(EXTRACT_ARCHIVE_FILENAME))
+        );
+        assert!(embedded
+            .config
+            .extract_directory
+            .as_ref()
+            .unwrap()
+            .starts_with(&exe.exe_name));
+
+        let (path, data) = embedded
+            .extract_archive
+            .as_ref()
+            .expect("extract archive should be defined");
+        assert_eq!(path, &PathBuf::from(EXTRACT_ARCHIVE_FILENAME));
+
+        let data = zstd::stream::decode_all(data.as_slice())?;
+        let mut archive = tar::Archive::new(data.as_slice());
+        assert!(archive
+            .entries()?
+            .any(|entry| entry.unwrap().path().unwrap() == Path::new("resources")));
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        embedded.write_files(temp_dir.path())?;
+        assert!(temp_dir.path().join(EXTRACT_ARCHIVE_FILENAME).exists());
+
+        exe.resources_load_mode =
+            PackedResourcesLoadMode::SharedPathMemoryMapped("../lib/resources".into());
+        assert!(exe
+            .to_embedded_python_context(&logger, &get_env()?, "0")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_file_resources() -> Result<()> {
+        let logger = get_logger()?;
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let mut exe = options.new_builder()?;
+        exe.resources_load_mode =
+            PackedResourcesLoadMode::SharedPathMemoryMapped("../lib/resources".into());
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+
+        assert_eq!(
+            &embedded.config.packed_resources,
+            &vec![PyembedPackedResourcesSource::MemoryMappedPath(
+                "$ORIGIN/../lib/resources".into()
+            )],
+            "load mode should have mapped to MemoryMappedPath"
+        );
+
+        let (path, data) = embedded
+            .shared_resources_file
+            .expect("shared resources file should be defined");
+        assert_eq!(path, PathBuf::from("../lib/resources"));
+        assert!(!data.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_library_search_paths() -> Result<()> {
+        let logger = get_logger()?;
+        let options = StandalonePythonExecutableBuilderOptions {
+            resources_location: Some(ConcreteResourceLocation::RelativePath("lib".to_string())),
+            ..Default::default()
+        };
+        let mut exe = options.new_builder()?;
+
+        exe.resources_collector.add_shared_library(
+            &SharedLibrary {
+                name: "foo".to_string(),
+                data: FileData::Memory(vec![42]),
+                filename: Some(PathBuf::from("libfoo.so")),
+            },
+            &ConcreteResourceLocation::RelativePath("lib".to_string()),
+        )?;
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+
+        assert_eq!(
+            embedded.config.shared_library_search_paths,
+            vec![PathBuf::from("$ORIGIN/lib")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dunder_file_policy() -> Result<()> {
+        let logger = get_logger()?;
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let mut exe = options.new_builder()?;
+
+        exe.resources_collector.add_python_module_source(
+            &PythonModuleSource {
+                name: "foo.bar".to_string(),
+                source: FileData::Memory(b"print(__file__)".to_vec()),
+                is_package: false,
+                cache_tag: exe.cache_tag().to_string(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+        assert!(embedded.config.dunder_file_packages.is_empty());
+
+        exe.set_dunder_file_policy("foo".to_string(), DunderFilePolicy::Extract);
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+        assert_eq!(
+            embedded.config.dunder_file_packages,
+            vec!["foo".to_string()]
+        );
+
+        exe.set_dunder_file_policy("foo.bar".to_string(), DunderFilePolicy::Error);
+        let err = exe
+            .to_embedded_python_context(&logger, &get_env()?, "0")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("foo.bar (package foo.bar)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hidden_imports() -> Result<()> {
+        let logger = get_logger()?;
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let mut exe = options.new_builder()?;
+
+        exe.add_hidden_imports(vec!["csv".to_string(), "does_not_exist".to_string()]);
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+        let resources = &embedded.pending_resources[0].0.resources;
+        assert!(resources.contains_key("csv"));
+        assert!(!resources.contains_key("does_not_exist"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packaging_hooks() -> Result<()> {
+        let logger = get_logger()?;
+        let options = StandalonePythonExecutableBuilderOptions::default();
+        let mut exe = options.new_builder()?;
+
+        for name in ["jinja2", "foo"] {
+            exe.resources_collector.add_python_module_source(
+                &PythonModuleSource {
+                    name: name.to_string(),
+                    source: FileData::Memory(vec![]),
+                    is_package: true,
+                    cache_tag: exe.cache_tag().to_string(),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &ConcreteResourceLocation::InMemory,
+            )?;
+        }
+
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+        assert_eq!(
+            embedded
+                .config
+                .runtime_hooks
+                .iter()
+                .map(|(module, _)| module.as_str())
+                .collect::<Vec<_>>(),
+            vec!["jinja2.loaders"]
+        );
+
+        exe.set_packaging_hooks_version(0);
+        exe.add_packaging_hook(PackagingHook {
+            package: "foo".to_string(),
+            runtime_hooks: [("foo".to_string(), "module.x = 1".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        });
+        let embedded = exe.to_embedded_python_context(&logger, &get_env()?, "0")?;
+        assert_eq!(
+            embedded.config.runtime_hooks,
+            vec![("foo".to_string(), "module.x = 1".to_string())]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_minimal_extensions_present() -> Result<()> {
         let options = StandalonePythonExecutableBuilderOptions::default();
@@ -1975,6 +3026,39 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_linux_musl_validate_static_linking() -> Result<()> {
+        let options = StandalonePythonExecutableBuilderOptions {
+            target_triple: "x86_64-unknown-linux-musl".to_string(),
+            extension_module_filter: Some(ExtensionModuleFilter::All),
+            libpython_link_mode: BinaryLibpythonLinkMode::Static,
+            ..StandalonePythonExecutableBuilderOptions::default()
+        };
+
+        let mut builder = options.new_builder()?;
+
+        // The distribution itself is fully static.
+        builder.validate_static_linking(&builder.resources_collector)?;
+
+        builder.extension_build_contexts.insert(
+            "_foo".to_string(),
+            LibPythonBuildContext {
+                dynamic_libraries: ["foo".to_string()].iter().cloned().collect(),
+                ..LibPythonBuildContext::default()
+            },
+        );
+
+        let err = builder
+            .validate_static_linking(&builder.resources_collector)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with(
+            "x86_64-unknown-linux-musl executables are statically linked but dynamic dependencies were found:\n  extension module _foo links shared library foo\n"
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_macos_distribution_extensions() -> Result<()> {
         for target_triple in MACOS_TARGET_TRIPLES.iter() {
@@ -3076,6 +4160,8 @@ pub mod tests {
 
     #[test]
     fn test_install_windows_runtime_dlls() -> Result<()> {
+        let logger = get_logger()?;
+
         for dist in get_all_standalone_distributions()? {
             let host_distribution = get_host_distribution_from_target(&dist)?;
 
@@ -3092,7 +4178,7 @@ pub mod tests {
 
             // In Never mode, the set of extra files should always be empty.
             builder.set_windows_runtime_dlls_mode(WindowsRuntimeDllsMode::Never);
-            let manifest = builder.resolve_windows_runtime_dll_files()?;
+            let manifest = builder.resolve_windows_runtime_dll_files(&logger)?;
             assert!(
                 manifest.is_empty(),
                 "target triple: {}",
@@ -3105,9 +4191,9 @@ pub mod tests {
 
             if let Some((version, platform)) = builder.vc_runtime_requirements() {
                 let can_locate_runtime =
-                    find_visual_cpp_redistributable(&version, platform).is_ok();
+                    find_visual_cpp_redistributable(&logger, &version, platform).is_ok();
 
-                let manifest = builder.resolve_windows_runtime_dll_files()?;
+                let manifest = builder.resolve_windows_runtime_dll_files(&logger)?;
 
                 if can_locate_runtime {
                     assert!(
@@ -3124,7 +4210,9 @@ pub mod tests {
                 }
             } else {
                 assert!(
-                    builder.resolve_windows_runtime_dll_files()?.is_empty(),
+                    builder
+                        .resolve_windows_runtime_dll_files(&logger)?
+                        .is_empty(),
                     "target triple: {}",
                     dist.target_triple()
                 );
@@ -3135,9 +4223,9 @@ pub mod tests {
 
             if let Some((version, platform)) = builder.vc_runtime_requirements() {
                 let can_locate_runtime =
-                    find_visual_cpp_redistributable(&version, platform).is_ok();
+                    find_visual_cpp_redistributable(&logger, &version, platform).is_ok();
 
-                let res = builder.resolve_windows_runtime_dll_files();
+                let res = builder.resolve_windows_runtime_dll_files(&logger);
 
                 if can_locate_runtime {
                     assert!(!res?.is_empty(), "target triple: {}", dist.target_triple());
@@ -3146,7 +4234,9 @@ pub mod tests {
                 }
             } else {
                 assert!(
-                    builder.resolve_windows_runtime_dll_files()?.is_empty(),
+                    builder
+                        .resolve_windows_runtime_dll_files(&logger)?
+                        .is_empty(),
                     "target triple: {}",
                     dist.target_triple()
                 );
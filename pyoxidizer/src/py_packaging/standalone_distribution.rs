@@ -1108,6 +1108,22 @@ impl StandaloneDistribution {
         Ok((python_paths, extra_envs))
     }
 
+    /// Resolve the tcl library directory for a library, e.g. `tk8.6` for `tk`.
+    ///
+    /// Looks for a directory named after the library followed by a dotted
+    /// version in the tcl library directories of the distribution.
+    fn versioned_tcl_directory(&self, library: &str) -> Option<String> {
+        self.tcl_library_paths.as_ref()?.iter().find_map(|path| {
+            let version = path.strip_prefix(library)?;
+
+            if version.contains('.') && version.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                Some(path.clone())
+            } else {
+                None
+            }
+        })
+    }
+
     /// Whether the distribution is capable of loading filed-based Python extension modules.
     pub fn is_extension_module_file_loadable(&self) -> bool {
         self.extension_module_loading
@@ -1444,7 +1460,14 @@ impl PythonDistribution for StandaloneDistribution {
 
     fn tcl_library_path_directory(&self) -> Option<String> {
         // TODO this should probably be exposed from the JSON metadata.
-        Some("tcl8.6".to_string())
+        Some(
+            self.versioned_tcl_directory("tcl")
+                .unwrap_or_else(|| "tcl8.6".to_string()),
+        )
+    }
+
+    fn tk_library_path_directory(&self) -> Option<String> {
+        self.versioned_tcl_directory("tk")
     }
 }
 
@@ -1497,6 +1520,7 @@ pub mod tests {
                 assert!(tcl_files.is_empty());
             } else {
                 assert!(!tcl_files.is_empty());
+                assert!(dist.tk_library_path_directory().is_some());
             }
         }
 
@@ -0,0 +1,402 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Resources embedded in Windows executables.
+
+Built Rust projects compile a resource script (`.rc` file) into Windows
+executables. It embeds the application manifest, which declares settings
+like the requested privilege level and DPI awareness. It can also define
+the executable's icon and its version information (the `VERSIONINFO`
+resource), which Windows shows in file properties and the task manager.
+*/
+
+use {
+    anyhow::{anyhow, Result},
+    std::{collections::BTreeMap, path::PathBuf},
+};
+
+/// Windows versions that can be declared supported in an application manifest.
+///
+/// Values are the `supportedOS` IDs of the versions. Windows 11 uses the ID
+/// of Windows 10.
+pub const WINDOWS_SUPPORTED_OS: &[(&str, &str)] = &[
+    ("vista", "{e2011457-1546-43c5-a5fe-008deee3d3f0}"),
+    ("7", "{35138b9a-5d96-4fbd-8e2d-a2440225f93a}"),
+    ("8", "{4a2f28e3-53b9-4441-ba9c-d69d4a4a6e38}"),
+    ("8.1", "{1f676c76-80e1-4239-95bb-83d0f6d0da78}"),
+    ("10", "{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}"),
+];
+
+/// Resolve the `supportedOS` ID of a Windows version.
+pub fn windows_supported_os_id(name: &str) -> Result<&'static str> {
+    WINDOWS_SUPPORTED_OS
+        .iter()
+        .find(|(os, _)| *os == name)
+        .map(|(_, id)| *id)
+        .ok_or_else(|| {
+            anyhow!(
+                "{} is not a known Windows version; must be one of {}",
+                name,
+                WINDOWS_SUPPORTED_OS
+                    .iter()
+                    .map(|(os, _)| *os)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// The privilege level an executable requests when it is run.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowsExecutionLevel {
+    /// Run with the privileges of the parent process.
+    AsInvoker,
+
+    /// Run with the highest privileges the user can obtain.
+    HighestAvailable,
+
+    /// Run as administrator, prompting for elevation.
+    RequireAdministrator,
+}
+
+impl ToString for WindowsExecutionLevel {
+    fn to_string(&self) -> String {
+        match self {
+            Self::AsInvoker => "as-invoker",
+            Self::HighestAvailable => "highest-available",
+            Self::RequireAdministrator => "require-administrator",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<&str> for WindowsExecutionLevel {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "as-invoker" => Ok(Self::AsInvoker),
+            "highest-available" => Ok(Self::HighestAvailable),
+            "require-administrator" => Ok(Self::RequireAdministrator),
+            _ => Err(format!(
+                "{} is not a valid execution level; must be 'as-invoker', 'highest-available', or 'require-administrator'",
+                value
+            )),
+        }
+    }
+}
+
+impl WindowsExecutionLevel {
+    /// The value of the manifest's `requestedExecutionLevel` element.
+    pub fn manifest_value(&self) -> &'static str {
+        match self {
+            Self::AsInvoker => "asInvoker",
+            Self::HighestAvailable => "highestAvailable",
+            Self::RequireAdministrator => "requireAdministrator",
+        }
+    }
+}
+
+/// How an executable handles displays with different DPI.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowsDpiAwareness {
+    /// The executable is scaled by Windows.
+    Unaware,
+
+    /// The executable scales itself for the DPI of the primary display.
+    System,
+
+    /// The executable scales itself when moved between displays.
+    PerMonitor,
+
+    /// Like `PerMonitor`, with scaling of non-client areas and dialogs by
+    /// Windows 10 1703 and newer.
+    PerMonitorV2,
+}
+
+impl ToString for WindowsDpiAwareness {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Unaware => "unaware",
+            Self::System => "system",
+            Self::PerMonitor => "per-monitor",
+            Self::PerMonitorV2 => "per-monitor-v2",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<&str> for WindowsDpiAwareness {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "unaware" => Ok(Self::Unaware),
+            "system" => Ok(Self::System),
+            "per-monitor" => Ok(Self::PerMonitor),
+            "per-monitor-v2" => Ok(Self::PerMonitorV2),
+            _ => Err(format!(
+                "{} is not a valid DPI awareness; must be 'unaware', 'system', 'per-monitor', or 'per-monitor-v2'",
+                value
+            )),
+        }
+    }
+}
+
+impl WindowsDpiAwareness {
+    /// The value of the manifest's `dpiAware` element.
+    ///
+    /// This is honored by Windows versions before Windows 10 1607.
+    pub fn dpi_aware(&self) -> &'static str {
+        match self {
+            Self::Unaware => "false",
+            Self::System => "true",
+            Self::PerMonitor | Self::PerMonitorV2 => "true/pm",
+        }
+    }
+
+    /// The value of the manifest's `dpiAwareness` element.
+    pub fn dpi_awareness(&self) -> &'static str {
+        match self {
+            Self::Unaware => "unaware",
+            Self::System => "system",
+            Self::PerMonitor => "PerMonitor",
+            Self::PerMonitorV2 => "PerMonitorV2,PerMonitor",
+        }
+    }
+}
+
+/// Settings of the application manifest of a Windows executable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WindowsManifest {
+    /// Path to a manifest file to embed verbatim.
+    ///
+    /// Other settings are ignored if defined.
+    pub path: Option<PathBuf>,
+
+    /// The privilege level to request when run.
+    pub execution_level: Option<WindowsExecutionLevel>,
+
+    /// How the executable handles displays with different DPI.
+    pub dpi_awareness: Option<WindowsDpiAwareness>,
+
+    /// Whether the executable can use paths longer than `MAX_PATH`.
+    pub long_path_aware: bool,
+
+    /// Windows versions the executable declares support for.
+    ///
+    /// Values are keys of [WINDOWS_SUPPORTED_OS].
+    pub supported_os: Vec<String>,
+}
+
+impl Default for WindowsManifest {
+    fn default() -> Self {
+        Self {
+            path: None,
+            execution_level: None,
+            dpi_awareness: None,
+            long_path_aware: true,
+            supported_os: vec![],
+        }
+    }
+}
+
+/// Parse a version string into the 4 components of a `VERSIONINFO` version.
+///
+/// Versions have 1 to 4 numeric components separated by `.`. Missing
+/// components are 0.
+pub fn parse_windows_version(s: &str) -> Result<[u16; 4]> {
+    let parts = s.split('.').collect::<Vec<_>>();
+
+    if parts.len() > 4 {
+        return Err(anyhow!(
+            "version {} has more than 4 components; expected a version like 1.2.3.4",
+            s
+        ));
+    }
+
+    let mut version = [0; 4];
+    for (i, part) in parts.iter().enumerate() {
+        version[i] = part.parse::<u16>().map_err(|_| {
+            anyhow!(
+                "version component {} of {} is not an integer between 0 and 65535",
+                part,
+                s
+            )
+        })?;
+    }
+
+    Ok(version)
+}
+
+/// Escape a string for use in a resource script string literal.
+pub fn rc_string_literal(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\"\""))
+}
+
+/// Version information for a Windows executable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WindowsVersionInfo {
+    /// Binary file version.
+    pub file_version: [u16; 4],
+
+    /// Binary product version.
+    pub product_version: [u16; 4],
+
+    /// Strings in the `StringFileInfo` block.
+    pub strings: BTreeMap<String, String>,
+}
+
+/// Resources to embed in a Windows executable.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WindowsResources {
+    /// Path to an `.ico` file to use as the executable's icon.
+    pub icon_path: Option<PathBuf>,
+
+    /// The file version. e.g. `1.2.3.4`.
+    pub file_version: Option<String>,
+
+    /// The product version. Defaults to the file version.
+    pub product_version: Option<String>,
+
+    /// Strings to define in the version information.
+    ///
+    /// e.g. `CompanyName` or `ProductName`.
+    pub version_strings: BTreeMap<String, String>,
+
+    /// The application manifest.
+    pub manifest: WindowsManifest,
+}
+
+impl WindowsResources {
+    /// Resolve the version information for an executable.
+    ///
+    /// Returns `None` if no version information is defined.
+    pub fn version_info(&self, program_name: &str) -> Result<Option<WindowsVersionInfo>> {
+        if self.file_version.is_none()
+            && self.product_version.is_none()
+            && self.version_strings.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let file_version = self.file_version.as_deref().unwrap_or("0");
+        let product_version = self.product_version.as_deref().unwrap_or(file_version);
+
+        let mut strings = self.version_strings.clone();
+        for (key, value) in [
+            ("FileVersion", file_version.to_string()),
+            ("ProductVersion", product_version.to_string()),
+            ("OriginalFilename", format!("{}.exe", program_name)),
+            ("InternalName", program_name.to_string()),
+        ] {
+            strings.entry(key.to_string()).or_insert(value);
+        }
+
+        Ok(Some(WindowsVersionInfo {
+            file_version: parse_windows_version(file_version)?,
+            product_version: parse_windows_version(product_version)?,
+            strings,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_supported_os_id() -> Result<()> {
+        assert_eq!(
+            windows_supported_os_id("10")?,
+            "{8e0f7a12-bfb3-4fe8-b9a5-48fd50a15a9a}"
+        );
+        assert!(windows_supported_os_id("xp").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_enums() {
+        for level in [
+            WindowsExecutionLevel::AsInvoker,
+            WindowsExecutionLevel::HighestAvailable,
+            WindowsExecutionLevel::RequireAdministrator,
+        ] {
+            assert_eq!(
+                WindowsExecutionLevel::try_from(level.to_string().as_str()),
+                Ok(level)
+            );
+        }
+        assert!(WindowsExecutionLevel::try_from("asInvoker").is_err());
+
+        for awareness in [
+            WindowsDpiAwareness::Unaware,
+            WindowsDpiAwareness::System,
+            WindowsDpiAwareness::PerMonitor,
+            WindowsDpiAwareness::PerMonitorV2,
+        ] {
+            assert_eq!(
+                WindowsDpiAwareness::try_from(awareness.to_string().as_str()),
+                Ok(awareness)
+            );
+        }
+        assert!(WindowsDpiAwareness::try_from("high").is_err());
+    }
+
+    #[test]
+    fn test_parse_windows_version() -> Result<()> {
+        assert_eq!(parse_windows_version("1")?, [1, 0, 0, 0]);
+        assert_eq!(parse_windows_version("1.2.3")?, [1, 2, 3, 0]);
+        assert_eq!(parse_windows_version("1.2.3.4")?, [1, 2, 3, 4]);
+        assert!(parse_windows_version("1.2.3.4.5").is_err());
+        assert!(parse_windows_version("1.2.3-beta").is_err());
+        assert!(parse_windows_version("70000").is_err());
+        assert!(parse_windows_version("").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rc_string_literal() {
+        assert_eq!(rc_string_literal("Example"), "\"Example\"");
+        assert_eq!(
+            rc_string_literal("\"Quoted\" C:\\path"),
+            "\"\"\"Quoted\"\" C:\\\\path\""
+        );
+    }
+
+    #[test]
+    fn test_version_info() -> Result<()> {
+        let mut resources = WindowsResources::default();
+        assert!(resources.version_info("myapp")?.is_none());
+
+        resources.icon_path = Some(PathBuf::from("myapp.ico"));
+        assert!(resources.version_info("myapp")?.is_none());
+
+        resources.file_version = Some("1.2.3".to_string());
+        resources
+            .version_strings
+            .insert("InternalName".to_string(), "app".to_string());
+
+        let info = resources.version_info("myapp")?.unwrap();
+        assert_eq!(info.file_version, [1, 2, 3, 0]);
+        assert_eq!(info.product_version, [1, 2, 3, 0]);
+        assert_eq!(
+            info.strings,
+            BTreeMap::from([
+                ("FileVersion".to_string(), "1.2.3".to_string()),
+                ("InternalName".to_string(), "app".to_string()),
+                ("OriginalFilename".to_string(), "myapp.exe".to_string()),
+                ("ProductVersion".to_string(), "1.2.3".to_string()),
+            ])
+        );
+
+        resources.product_version = Some("invalid".to_string());
+        assert!(resources.version_info("myapp").is_err());
+
+        Ok(())
+    }
+}
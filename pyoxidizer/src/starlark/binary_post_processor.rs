@@ -0,0 +1,271 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    super::util::ToValue,
+    crate::py_packaging::post_processing::{BinaryPostProcessor, ElfNote},
+    starlark::{
+        starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+        starlark_signature_extraction, starlark_signatures,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            Mutable, TypedValue, Value, ValueResult,
+        },
+    },
+    starlark_dialect_build_targets::{required_list_arg, ToOptional},
+    std::{
+        path::PathBuf,
+        sync::{Arc, Mutex, MutexGuard},
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct BinaryPostProcessorValue {
+    inner: Arc<Mutex<BinaryPostProcessor>>,
+}
+
+impl BinaryPostProcessorValue {
+    pub fn new(inner: BinaryPostProcessor) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    pub fn inner(&self, label: &str) -> Result<MutexGuard<BinaryPostProcessor>, ValueError> {
+        self.inner.try_lock().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "BINARY_POST_PROCESSOR",
+                message: format!("unable to obtain lock: {}", e),
+                label: label.to_string(),
+            })
+        })
+    }
+}
+
+impl TypedValue for BinaryPostProcessorValue {
+    type Holder = Mutable<BinaryPostProcessorValue>;
+    const TYPE: &'static str = "BinaryPostProcessor";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn to_str(&self) -> String {
+        format!("BinaryPostProcessor<{:#?}>", self.inner)
+    }
+
+    fn to_repr(&self) -> String {
+        self.to_str()
+    }
+
+    fn get_attr(&self, attribute: &str) -> ValueResult {
+        let inner = self.inner(&format!("BinaryPostProcessor.{}", attribute))?;
+
+        let v = match attribute {
+            "elf_build_id" => Value::from(inner.elf_build_id),
+            "strip" => Value::from(inner.strip),
+            "upx" => Value::from(inner.upx),
+            "upx_args" => Value::from(inner.upx_args.clone()),
+            "windows_file_version" => inner.windows_file_version.to_value(),
+            "windows_icon_path" => inner.windows_icon_path.to_value(),
+            "windows_product_version" => inner.windows_product_version.to_value(),
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::GetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn has_attr(&self, attribute: &str) -> Result<bool, ValueError> {
+        Ok(matches!(
+            attribute,
+            "elf_build_id"
+                | "strip"
+                | "upx"
+                | "upx_args"
+                | "windows_file_version"
+                | "windows_icon_path"
+                | "windows_product_version"
+        ))
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        let mut inner = self.inner(&format!("BinaryPostProcessor.{}", attribute))?;
+
+        match attribute {
+            "elf_build_id" => {
+                inner.elf_build_id = value.to_bool();
+            }
+            "strip" => {
+                inner.strip = value.to_bool();
+            }
+            "upx" => {
+                inner.upx = value.to_bool();
+            }
+            "upx_args" => {
+                required_list_arg(attribute, "string", &value)?;
+                inner.upx_args = value.iter()?.iter().map(|x| x.to_string()).collect();
+            }
+            "windows_file_version" => {
+                inner.windows_file_version = value.to_optional();
+            }
+            "windows_icon_path" => {
+                let path: Option<String> = value.to_optional();
+                inner.windows_icon_path = path.map(PathBuf::from);
+            }
+            "windows_product_version" => {
+                inner.windows_product_version = value.to_optional();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_owned(),
+                    right: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Starlark methods.
+impl BinaryPostProcessorValue {
+    fn starlark_add_elf_note(
+        &mut self,
+        section: String,
+        owner: String,
+        content: String,
+        note_type: i64,
+    ) -> ValueResult {
+        const LABEL: &str = "BinaryPostProcessor.add_elf_note()";
+
+        if !section.starts_with(".note") {
+            return Err(ValueError::from(RuntimeError {
+                code: "BINARY_POST_PROCESSOR",
+                message: format!("section {} does not begin with .note", section),
+                label: LABEL.to_string(),
+            }));
+        }
+
+        let note_type = u32::try_from(note_type).map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "BINARY_POST_PROCESSOR",
+                message: format!("invalid note type {}: {}", note_type, e),
+                label: LABEL.to_string(),
+            })
+        })?;
+
+        self.inner(LABEL)?.elf_notes.push(ElfNote {
+            section,
+            owner,
+            note_type,
+            desc: content.into_bytes(),
+        });
+
+        Ok(Value::from(NoneType::None))
+    }
+
+    fn starlark_set_windows_version_string(&mut self, key: String, value: String) -> ValueResult {
+        self.inner("BinaryPostProcessor.set_windows_version_string()")?
+            .windows_version_strings
+            .insert(key, value);
+
+        Ok(Value::from(NoneType::None))
+    }
+}
+
+starlark_module! { binary_post_processor_module =>
+    #[allow(non_snake_case)]
+    BinaryPostProcessor(env _env) {
+        Ok(Value::new(BinaryPostProcessorValue::new(BinaryPostProcessor::default())))
+    }
+
+    BinaryPostProcessor.add_elf_note(
+        this,
+        section: String,
+        owner: String,
+        content: String,
+        note_type: i64 = 1
+    ) {
+        let mut this = this.downcast_mut::<BinaryPostProcessorValue>().unwrap().unwrap();
+        this.starlark_add_elf_note(section, owner, content, note_type)
+    }
+
+    BinaryPostProcessor.set_windows_version_string(this, key: String, value: String) {
+        let mut this = this.downcast_mut::<BinaryPostProcessorValue>().unwrap().unwrap();
+        this.starlark_set_windows_version_string(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::super::testutil::*, super::*, anyhow::Result};
+
+    #[test]
+    fn test_default() -> Result<()> {
+        let value = starlark_ok("BinaryPostProcessor()");
+        assert_eq!(value.get_type(), "BinaryPostProcessor");
+
+        let processor = value.downcast_ref::<BinaryPostProcessorValue>().unwrap();
+        assert_eq!(
+            *processor.inner("ignored").unwrap(),
+            BinaryPostProcessor::default()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attributes() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        env.eval("p = BinaryPostProcessor()")?;
+
+        let value = env.eval("p.strip = True; p.strip")?;
+        assert!(value.to_bool());
+
+        let value = env.eval("p.upx_args = ['--best']; p.upx_args")?;
+        assert_eq!(value.to_repr(), "[\"--best\"]");
+
+        let value = env.eval("p.windows_icon_path")?;
+        assert_eq!(value.get_type(), "NoneType");
+
+        let value = env.eval("p.windows_icon_path = 'app.ico'; p.windows_icon_path")?;
+        assert_eq!(value.to_string(), "app.ico");
+
+        let value = env.eval("p.windows_file_version = '1.0.0.0'; p.windows_file_version")?;
+        assert_eq!(value.to_string(), "1.0.0.0");
+
+        env.eval("p.set_windows_version_string('CompanyName', 'Example')")?;
+        env.eval("p.add_elf_note('.note.myapp', 'MyApp', 'v1')")?;
+        assert!(env
+            .eval("p.add_elf_note('.comment', 'MyApp', 'v1')")
+            .is_err());
+
+        let value = env.eval("p")?;
+        let processor = value.downcast_ref::<BinaryPostProcessorValue>().unwrap();
+        let inner = processor.inner("ignored").unwrap();
+        assert_eq!(
+            inner.windows_version_strings.get("CompanyName"),
+            Some(&"Example".to_string())
+        );
+        assert_eq!(
+            inner.elf_notes,
+            vec![ElfNote {
+                section: ".note.myapp".to_string(),
+                owner: "MyApp".to_string(),
+                note_type: 1,
+                desc: b"v1".to_vec(),
+            }]
+        );
+
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
+    super::testing::PipMock,
     crate::py_packaging::distribution::DistributionCache,
     anyhow::{Context, Result},
     starlark::{
@@ -50,6 +51,9 @@ pub struct PyOxidizerEnvironmentContext {
     /// Whether we are building a debug or release binary.
     pub build_release: bool,
 
+    /// Name of the build profile, e.g. `debug`, `release` or a custom name.
+    pub build_profile: String,
+
     /// Optimization level when building binaries.
     pub build_opt_level: String,
 
@@ -61,6 +65,12 @@ pub struct PyOxidizerEnvironmentContext {
 
     /// Extra variables to inject into Starlark environment.
     extra_vars: HashMap<String, Option<String>>,
+
+    /// Whether the environment is evaluating config tests.
+    pub test_mode: bool,
+
+    /// Mocked `pip` invocations, keyed by arguments.
+    pub pip_mocks: HashMap<Vec<String>, PipMock>,
 }
 
 impl PyOxidizerEnvironmentContext {
@@ -73,6 +83,7 @@ impl PyOxidizerEnvironmentContext {
         build_host_triple: &str,
         build_target_triple: &str,
         build_release: bool,
+        build_profile: &str,
         build_opt_level: &str,
         distribution_cache: Option<Arc<DistributionCache>>,
         extra_vars: HashMap<String, Option<String>>,
@@ -102,9 +113,12 @@ impl PyOxidizerEnvironmentContext {
             build_host_triple: build_host_triple.to_string(),
             build_target_triple: build_target_triple.to_string(),
             build_release,
+            build_profile: build_profile.to_string(),
             build_opt_level: build_opt_level.to_string(),
             distribution_cache,
             extra_vars,
+            test_mode: false,
+            pip_mocks: HashMap::new(),
         })
     }
 
@@ -112,6 +126,10 @@ impl PyOxidizerEnvironmentContext {
         &self.env
     }
 
+    pub fn env_mut(&mut self) -> &mut crate::environment::Environment {
+        &mut self.env
+    }
+
     pub fn logger(&self) -> &slog::Logger {
         &self.logger
     }
@@ -141,6 +159,20 @@ impl PyOxidizerEnvironmentContext {
 
         Ok(context.target_build_path(target))
     }
+
+    /// Directory holding the Rust project generated to build an executable.
+    pub fn rust_project_path(
+        &self,
+        type_values: &TypeValues,
+        exe_name: &str,
+    ) -> Result<PathBuf, ValueError> {
+        Ok(self
+            .build_path(type_values)?
+            .join(&self.build_target_triple)
+            .join(&self.build_profile)
+            .join("rust-projects")
+            .join(exe_name))
+    }
 }
 
 impl TypedValue for PyOxidizerEnvironmentContext {
@@ -185,11 +217,18 @@ pub fn register_starlark_dialect(
 ) -> Result<(), EnvironmentError> {
     starlark_dialect_build_targets::register_starlark_dialect(env, type_values)?;
     tugger::starlark::register_starlark_dialect(env, type_values)?;
+    super::binary_post_processor::binary_post_processor_module(env, type_values);
     super::file_resource::file_resource_env(env, type_values);
     super::python_distribution::python_distribution_module(env, type_values);
     super::python_embedded_resources::python_embedded_resources_module(env, type_values);
     super::python_executable::python_executable_env(env, type_values);
     super::python_packaging_policy::python_packaging_policy_module(env, type_values);
+    super::rust_toolchain::rust_toolchain_module(env, type_values);
+    super::testing::testing_module(env, type_values);
+
+    for plugin in super::plugin::registered_plugins() {
+        plugin.register_starlark_dialect(env, type_values)?;
+    }
 
     Ok(())
 }
@@ -209,15 +248,16 @@ pub fn populate_environment(
 
     build_targets_context.build_script_mode = build_script_mode;
 
+    // Each profile has its own build path so artifacts of profiles don't clobber
+    // each other.
     build_targets_context.set_target_build_path_prefix(Some(
-        PathBuf::from(&context.build_target_triple).join(if context.build_release {
-            "release"
-        } else {
-            "debug"
-        }),
+        PathBuf::from(&context.build_target_triple).join(&context.build_profile),
     ));
 
-    let tugger_context = TuggerContext::new(context.logger.clone());
+    let mut tugger_context = TuggerContext::new(context.logger.clone());
+    tugger_context.http_cache_dir = Some(context.env().http_cache_dir());
+    tugger_context.reproducible = context.env().reproducible();
+    tugger_context.secrets = context.env().secrets().clone();
 
     starlark_dialect_build_targets::populate_environment(env, type_values, build_targets_context)?;
     tugger::starlark::populate_environment(env, type_values, tugger_context)?;
@@ -245,6 +285,11 @@ pub fn populate_environment(
         "BUILD_TARGET_TRIPLE",
         Value::from(context.build_target_triple.clone()),
     )?;
+    env.set("BUILD_PROFILE", Value::from(context.build_profile.clone()))?;
+
+    for plugin in super::plugin::registered_plugins() {
+        plugin.populate_environment(env, type_values, &context)?;
+    }
 
     env.set("CONTEXT", Value::new(context))?;
 
@@ -252,7 +297,13 @@ pub fn populate_environment(
     // available via the type object API. This is a bit hacky. But it allows
     // Rust code with only access to the TypeValues dictionary to retrieve
     // these globals.
-    for f in &["CONTEXT", "CWD", "CONFIG_PATH", "BUILD_TARGET_TRIPLE"] {
+    for f in &[
+        "CONTEXT",
+        "CWD",
+        "CONFIG_PATH",
+        "BUILD_TARGET_TRIPLE",
+        "BUILD_PROFILE",
+    ] {
         type_values.add_type_value(PyOxidizerContext::TYPE, f, env.get(f)?);
     }
 
@@ -276,6 +327,12 @@ pub mod tests {
         assert_eq!(target.to_str(), default_target_triple());
     }
 
+    #[test]
+    fn test_build_profile() {
+        let profile = starlark_ok("BUILD_PROFILE");
+        assert_eq!(profile.to_str(), "debug");
+    }
+
     #[test]
     fn test_print() {
         starlark_ok("print('hello, world')");
@@ -40,12 +40,14 @@ pub struct EvaluationContextBuilder {
     config_path: PathBuf,
     build_target_triple: String,
     release: bool,
+    build_profile: Option<String>,
     verbose: bool,
     resolve_targets: Option<Vec<String>>,
     build_script_mode: bool,
     build_opt_level: String,
     distribution_cache: Option<Arc<DistributionCache>>,
     extra_vars: HashMap<String, Option<String>>,
+    test_mode: bool,
 }
 
 impl EvaluationContextBuilder {
@@ -61,12 +63,14 @@ impl EvaluationContextBuilder {
             config_path: config_path.as_ref().to_path_buf(),
             build_target_triple: build_target_triple.to_string(),
             release: false,
+            build_profile: None,
             verbose: false,
             resolve_targets: None,
             build_script_mode: false,
             build_opt_level: "0".to_string(),
             distribution_cache: None,
             extra_vars: HashMap::new(),
+            test_mode: false,
         }
     }
 
@@ -93,6 +97,15 @@ impl EvaluationContextBuilder {
         self
     }
 
+    /// Set the name of the build profile.
+    ///
+    /// Defaults to `release` or `debug`, depending on [Self::release].
+    #[must_use]
+    pub fn build_profile(mut self, value: impl ToString) -> Self {
+        self.build_profile = Some(value.to_string());
+        self
+    }
+
     #[must_use]
     pub fn verbose(mut self, value: bool) -> Self {
         self.verbose = value;
@@ -141,6 +154,13 @@ impl EvaluationContextBuilder {
         self.extra_vars = extra_vars;
         self
     }
+
+    /// Whether to evaluate config tests, enabling mocks.
+    #[must_use]
+    pub fn test_mode(mut self, value: bool) -> Self {
+        self.test_mode = value;
+        self
+    }
 }
 
 /// Interface to evaluate Starlark configuration files.
@@ -166,7 +186,15 @@ impl TryFrom<EvaluationContextBuilder> for EvaluationContext {
 
 impl EvaluationContext {
     pub fn from_builder(builder: EvaluationContextBuilder) -> Result<Self> {
-        let context = PyOxidizerEnvironmentContext::new(
+        let build_profile = builder.build_profile.unwrap_or_else(|| {
+            if builder.release {
+                "release".to_string()
+            } else {
+                "debug".to_string()
+            }
+        });
+
+        let mut context = PyOxidizerEnvironmentContext::new(
             &builder.env,
             builder.logger,
             builder.verbose,
@@ -174,10 +202,12 @@ impl EvaluationContext {
             default_target_triple(),
             &builder.build_target_triple,
             builder.release,
+            &build_profile,
             &builder.build_opt_level,
             builder.distribution_cache,
             builder.extra_vars,
         )?;
+        context.test_mode = builder.test_mode;
 
         let (mut parent_env, mut type_values) = starlark::stdlib::global_environment();
 
@@ -327,6 +357,19 @@ impl EvaluationContext {
         pyoxidizer_context.build_path(&self.type_values)
     }
 
+    /// Set the directory build artifacts are written to.
+    ///
+    /// This overrides any path set by an evaluated config file.
+    pub fn set_build_path(&mut self, path: &Path) -> Result<()> {
+        let raw_context = self.build_targets_context_value()?;
+        let mut context = raw_context
+            .downcast_mut::<EnvironmentContext>()
+            .map_err(|_| anyhow!("unable to obtain mutable build targets context"))?
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        context.set_build_path(path)
+    }
+
     pub fn target_build_path(&self, target: &str) -> Result<PathBuf> {
         let context_value = self.build_targets_context_value()?;
         let context = context_value.downcast_ref::<EnvironmentContext>().unwrap();
@@ -366,6 +409,22 @@ impl EvaluationContext {
         Ok(context.targets_to_resolve())
     }
 
+    /// Obtain the value a target resolved to.
+    ///
+    /// Returns `None` if the target exists but wasn't resolved.
+    pub fn resolved_target_value(&self, target: &str) -> Result<Option<Value>> {
+        let raw_context = self.build_targets_context_value()?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or_else(|| anyhow!("context has incorrect type"))?;
+
+        Ok(context
+            .get_target(target)
+            .ok_or_else(|| anyhow!("target {} does not exist", target))?
+            .resolved_value
+            .clone())
+    }
+
     pub fn build_resolved_target(&mut self, target: &str) -> Result<ResolvedTarget> {
         let mut call_stack = CallStack::default();
 
@@ -490,4 +549,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn build_profile() -> Result<()> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("pyoxidizer-test")
+            .tempdir()?;
+        let env = get_env()?;
+        let logger = get_logger()?;
+
+        let config_path = temp_dir.path().join("pyoxidizer.bzl");
+        std::fs::write(&config_path, "")?;
+
+        for (release, profile, expected) in [
+            (false, None, "debug"),
+            (true, None, "release"),
+            (true, Some("staging"), "staging"),
+        ] {
+            let mut builder = EvaluationContextBuilder::new(
+                &env,
+                logger.clone(),
+                config_path.clone(),
+                default_target_triple().to_string(),
+            )
+            .release(release);
+
+            if let Some(profile) = profile {
+                builder = builder.build_profile(profile);
+            }
+
+            let context = builder.into_context()?;
+
+            assert_eq!(
+                context.get_var("BUILD_PROFILE").unwrap().to_string(),
+                expected
+            );
+            assert!(context.target_build_path("exe")?.ends_with(
+                PathBuf::from(default_target_triple())
+                    .join(expected)
+                    .join("exe")
+            ));
+        }
+
+        Ok(())
+    }
 }
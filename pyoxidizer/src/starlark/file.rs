@@ -128,6 +128,17 @@ impl TypedValue for FileValue {
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        self.set_attr_add_collection_context(attribute, value)
+        if attribute == "path" {
+            let path = {
+                let mut inner = self.inner(&format!("File.{}", attribute))?;
+                inner.file = File::new(value.to_string(), inner.file.entry().clone());
+                inner.file.path_string()
+            };
+            self.path = path;
+
+            Ok(())
+        } else {
+            self.set_attr_add_collection_context(attribute, value)
+        }
     }
 }
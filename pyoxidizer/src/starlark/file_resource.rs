@@ -12,7 +12,7 @@ use {
         python_package_resource::PythonPackageResourceValue,
     },
     crate::{
-        project_building::build_python_executable,
+        project_building::{build_python_executable, build_python_executable_in_dir},
         py_packaging::{binary::PythonBinaryBuilder, resource::AddToFileManifest},
     },
     anyhow::{anyhow, Context, Result},
@@ -29,14 +29,46 @@ use {
             starlark_signature_extraction, starlark_signatures,
         },
     },
-    std::{ops::DerefMut, path::Path},
+    std::{
+        ops::DerefMut,
+        path::{Component, Path, PathBuf},
+    },
     tugger::starlark::file_manifest::FileManifestValue,
     tugger_file_manifest::{FileEntry, FileManifest},
 };
 
+/// Resolve the path of a file relative to a directory in a manifest.
+///
+/// `..` components are resolved lexically. An error is returned if the
+/// path escapes the root of the manifest.
+pub fn resolve_manifest_relative_path(dir: &Path, path: &Path) -> Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+
+    for component in dir.join(path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return Err(anyhow!(
+                        "{} escapes the root of the manifest",
+                        dir.join(path).display()
+                    ));
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(anyhow!("{} is not a relative path", path.display()));
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn file_manifest_add_python_executable(
     manifest: &mut FileManifestValue,
+    project_path: Option<&Path>,
     env: &crate::environment::Environment,
     logger: &slog::Logger,
     prefix: &str,
@@ -47,8 +79,21 @@ pub fn file_manifest_add_python_executable(
 ) -> Result<()> {
     const LABEL: &str = "FileManifest.add_python_executable()";
 
-    let build = build_python_executable(env, logger, &exe.name(), exe, target, opt_level, release)
-        .context("building Python executable")?;
+    let build = if let Some(project_path) = project_path {
+        build_python_executable_in_dir(
+            env,
+            logger,
+            &exe.name(),
+            exe,
+            project_path,
+            target,
+            opt_level,
+            release,
+        )
+    } else {
+        build_python_executable(env, logger, &exe.name(), exe, target, opt_level, release)
+    }
+    .context("building Python executable")?;
 
     let content = FileEntry::new_from_data(build.exe_data.clone(), true);
 
@@ -69,6 +114,31 @@ pub fn file_manifest_add_python_executable(
     }
 
     inner.add_manifest(&extra_files)?;
+
+    // Executables sharing a packed resources file must agree on its content.
+    if let Some((path, data)) = &build.binary_data.shared_resources_file {
+        let manifest_path = resolve_manifest_relative_path(Path::new(use_prefix), path)?;
+
+        if let Some(existing) = inner.get(&manifest_path) {
+            if existing.file_data().resolve_content()? != *data {
+                return Err(anyhow!(
+                    "{} already exists with different content; executables sharing a packed resources file must have identical resources",
+                    manifest_path.display()
+                ));
+            }
+        } else {
+            warn!(
+                logger,
+                "adding shared packed resources {}",
+                manifest_path.display()
+            );
+            inner.add_file_entry(
+                &manifest_path,
+                FileEntry::new_from_data(data.clone(), false),
+            )?;
+        }
+    }
+
     drop(inner);
 
     // Make the last added Python executable the default run target.
@@ -396,4 +466,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_manifest_relative_path() -> Result<()> {
+        assert_eq!(
+            resolve_manifest_relative_path(Path::new("bin"), Path::new("../lib/resources"))?,
+            PathBuf::from("lib/resources")
+        );
+        assert_eq!(
+            resolve_manifest_relative_path(Path::new(""), Path::new("./resources"))?,
+            PathBuf::from("resources")
+        );
+        assert!(
+            resolve_manifest_relative_path(Path::new("bin"), Path::new("../../resources")).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_shared_resources() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+        add_exe(&mut env)?;
+
+        env.eval("exe2 = dist.to_python_executable('testapp2')")?;
+        for exe in ["exe", "exe2"] {
+            env.eval(&format!(
+                "{}.packed_resources_load_mode = 'shared-memory-mapped:../lib/packed-resources'",
+                exe
+            ))?;
+        }
+
+        let m = FileManifestValue::new_from_args().unwrap();
+        env.set_var("m", m).unwrap();
+
+        env.eval("m.add_python_resource('bin', exe)")?;
+        env.eval("m.add_python_resource('bin', exe2)")?;
+        env.eval("m.install('myapp-shared')")?;
+
+        let dest_path = env.build_path().unwrap().join("myapp-shared");
+        assert!(dest_path.join("lib").join("packed-resources").exists());
+        assert!(!dest_path.join("bin").join("packed-resources").exists());
+
+        Ok(())
+    }
 }
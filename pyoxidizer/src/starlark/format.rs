@@ -0,0 +1,244 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Formatting and syntax checking of Starlark source code.
+
+Formatting is intentionally conservative: it only normalizes whitespace
+(line endings, trailing whitespace, tab indentation, and blank lines)
+and never touches the content of string literals, so it can't change
+the meaning of a configuration file.
+*/
+
+use {
+    codemap::CodeMap,
+    starlark::syntax::dialect::Dialect,
+    std::{
+        collections::HashSet,
+        ops::Range,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// The kind of a lexical segment of Starlark source code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SegmentKind {
+    Code,
+    String,
+    Comment,
+}
+
+fn push_segment(
+    segments: &mut Vec<(SegmentKind, Range<usize>)>,
+    kind: SegmentKind,
+    range: Range<usize>,
+) {
+    if !range.is_empty() {
+        segments.push((kind, range));
+    }
+}
+
+/// Split source code into code, string literal, and comment segments.
+///
+/// Returned ranges are byte offsets into `source` and cover all of it.
+/// Unterminated string literals extend to the end of the line (or the
+/// source for triple quoted strings).
+pub fn segments(source: &str) -> Vec<(SegmentKind, Range<usize>)> {
+    let bytes = source.as_bytes();
+    let mut segments = vec![];
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'#' => {
+                let end = source[i..].find('\n').map_or(bytes.len(), |p| i + p);
+                push_segment(&mut segments, SegmentKind::Code, start..i);
+                push_segment(&mut segments, SegmentKind::Comment, i..end);
+                i = end;
+                start = end;
+            }
+            quote @ (b'\'' | b'"') => {
+                let delimiter = [quote; 3];
+                let triple = bytes[i..].starts_with(&delimiter);
+                let mut end = i + if triple { 3 } else { 1 };
+
+                while end < bytes.len() {
+                    match bytes[end] {
+                        b'\\' => {
+                            end += 1 + source[end + 1..].chars().next().map_or(0, char::len_utf8);
+                        }
+                        b'\n' if !triple => break,
+                        c if c == quote && !triple => {
+                            end += 1;
+                            break;
+                        }
+                        _ if triple && bytes[end..].starts_with(&delimiter) => {
+                            end += 3;
+                            break;
+                        }
+                        _ => end += 1,
+                    }
+                }
+
+                let end = end.min(bytes.len());
+                push_segment(&mut segments, SegmentKind::Code, start..i);
+                push_segment(&mut segments, SegmentKind::String, i..end);
+                i = end;
+                start = end;
+            }
+            _ => i += 1,
+        }
+    }
+
+    push_segment(&mut segments, SegmentKind::Code, start..bytes.len());
+
+    segments
+}
+
+/// Format Starlark source code.
+pub fn format_source(source: &str) -> String {
+    let source = source.replace("\r\n", "\n");
+
+    // Newlines within string literals are part of the string's value. So
+    // lines adjacent to them must be preserved.
+    let string_newlines = segments(&source)
+        .into_iter()
+        .filter(|(kind, _)| *kind == SegmentKind::String)
+        .flat_map(|(_, range)| range)
+        .filter(|i| source.as_bytes()[*i] == b'\n')
+        .collect::<HashSet<_>>();
+
+    let mut lines: Vec<String> = vec![];
+    let mut offset = 0;
+    let mut starts_in_string = false;
+    let mut blank_lines = 0;
+
+    for line in source.split('\n') {
+        let newline = offset + line.len();
+        let ends_in_string = string_newlines.contains(&newline);
+        offset = newline + 1;
+
+        let mut formatted = line.to_string();
+
+        if !starts_in_string {
+            let indent_len = line.len() - line.trim_start_matches(|c| c == ' ' || c == '\t').len();
+            formatted = format!(
+                "{}{}",
+                line[..indent_len].replace('\t', "    "),
+                &line[indent_len..]
+            );
+        }
+
+        if !ends_in_string {
+            formatted.truncate(formatted.trim_end().len());
+        }
+
+        if formatted.is_empty() && !starts_in_string {
+            blank_lines += 1;
+
+            // Drop leading blank lines and runs of more than 2 blank lines.
+            if lines.is_empty() || blank_lines > 2 {
+                starts_in_string = ends_in_string;
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+
+        lines.push(formatted);
+        starts_in_string = ends_in_string;
+    }
+
+    while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    if lines.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", lines.join("\n"))
+    }
+}
+
+/// A syntax error in Starlark source code.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+
+    /// 0-based start and end (line, column) of the error, if known.
+    pub span: Option<((usize, usize), (usize, usize))>,
+}
+
+/// Verify Starlark source code parses.
+pub fn check_syntax(filename: &str, source: &str) -> Result<(), SyntaxError> {
+    let map = Arc::new(Mutex::new(CodeMap::new()));
+
+    starlark::syntax::parser::parse(&map, filename, source, Dialect::Bzl)
+        .map(|_| ())
+        .map_err(|diagnostic| {
+            let span = diagnostic.spans.first().map(|label| {
+                let location = map.lock().unwrap().look_up_span(label.span);
+
+                (
+                    (location.begin.line, location.begin.column),
+                    (location.end.line, location.end.column),
+                )
+            });
+
+            SyntaxError {
+                message: diagnostic.message,
+                span,
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments() {
+        let source = "x = 'a#b' # comment\ny = \"\"\"multi\n'line\"\"\"\nz = \"\\\"\"";
+
+        assert_eq!(
+            segments(source)
+                .into_iter()
+                .map(|(kind, range)| (kind, &source[range]))
+                .collect::<Vec<_>>(),
+            vec![
+                (SegmentKind::Code, "x = "),
+                (SegmentKind::String, "'a#b'"),
+                (SegmentKind::Code, " "),
+                (SegmentKind::Comment, "# comment"),
+                (SegmentKind::Code, "\ny = "),
+                (SegmentKind::String, "\"\"\"multi\n'line\"\"\""),
+                (SegmentKind::Code, "\nz = "),
+                (SegmentKind::String, "\"\\\"\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_source() {
+        assert_eq!(
+            format_source("\n\ndef foo():  \r\n\treturn 1\t\n\n\n\n\nfoo()\n\n"),
+            "def foo():\n    return 1\n\n\nfoo()\n"
+        );
+
+        // Whitespace in string literals is preserved.
+        let source = "x = \"\"\"a  \n\tb\n\n\n\nc\"\"\"\n";
+        assert_eq!(format_source(source), source);
+
+        assert_eq!(format_source("\n \n"), "");
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        assert!(check_syntax("test.bzl", "def foo():\n    return 1\n").is_ok());
+
+        let err = check_syntax("test.bzl", "def foo(:\n").unwrap_err();
+        assert_eq!(err.span.map(|((line, _), _)| line), Some(0));
+    }
+}
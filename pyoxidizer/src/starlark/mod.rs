@@ -8,10 +8,13 @@ The `starlark` module and related sub-modules define the
 define Oxidized Python binaries.
 */
 
+pub mod binary_post_processor;
 pub mod env;
 pub mod eval;
 pub mod file;
 pub mod file_resource;
+pub mod format;
+pub mod plugin;
 pub mod python_distribution;
 pub mod python_embedded_resources;
 pub mod python_executable;
@@ -22,6 +25,9 @@ pub mod python_package_distribution_resource;
 pub mod python_package_resource;
 pub mod python_packaging_policy;
 pub mod python_resource;
+pub mod rust_toolchain;
+pub mod symbols;
+pub mod testing;
 #[cfg(test)]
 mod testutil;
 pub mod util;
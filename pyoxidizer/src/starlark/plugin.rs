@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Plugins extending the Starlark dialect.
+
+Plugins allow other crates to contribute types, functions, and variables
+to the Starlark environment without modifying PyOxidizer. A plugin
+implements [StarlarkPlugin] and is registered with [register_plugin]
+before any configuration file is evaluated. Typically this is done by a
+binary which depends on this library and the plugin crates, registers
+the plugins, and then runs PyOxidizer's command line interface:
+
+```ignore
+fn main() {
+    pyoxidizerlib::starlark::plugin::register_plugin(my_plugin::Plugin::default())
+        .expect("registering plugin");
+
+    std::process::exit(match pyoxidizerlib::cli::run_cli() {
+        Ok(_) => 0,
+        Err(e) => {
+            println!("error: {}", e);
+            1
+        }
+    });
+}
+```
+
+Functions defined by plugins can obtain PyOxidizer's state for the
+evaluation via [super::env::get_context] and the build targets state via
+[starlark_dialect_build_targets::get_context_value]. Types defining a
+`build(target)` method returning a
+[starlark_dialect_build_targets::ResolvedTargetValue] can be returned by
+registered targets and built like built-in types.
+*/
+
+use {
+    super::env::PyOxidizerEnvironmentContext,
+    anyhow::{anyhow, Result},
+    once_cell::sync::Lazy,
+    starlark::environment::{Environment, EnvironmentError, TypeValues},
+    std::sync::{Arc, RwLock},
+};
+
+/// A provider of symbols for the Starlark dialect.
+pub trait StarlarkPlugin: Send + Sync {
+    /// Name of the plugin.
+    ///
+    /// Must be unique among registered plugins.
+    fn name(&self) -> &str;
+
+    /// Register types and functions provided by this plugin.
+    ///
+    /// This is called for every new Starlark environment, after PyOxidizer's
+    /// own types and functions are registered. So plugins can't replace
+    /// built-in symbols.
+    fn register_starlark_dialect(
+        &self,
+        env: &mut Environment,
+        type_values: &mut TypeValues,
+    ) -> Result<(), EnvironmentError>;
+
+    /// Define global variables provided by this plugin.
+    ///
+    /// This is called after PyOxidizer's own global variables are defined.
+    fn populate_environment(
+        &self,
+        _env: &mut Environment,
+        _type_values: &mut TypeValues,
+        _context: &PyOxidizerEnvironmentContext,
+    ) -> Result<(), EnvironmentError> {
+        Ok(())
+    }
+
+    /// reStructuredText documentation of symbols provided by this plugin.
+    ///
+    /// Symbols documented with `py:*` directives are offered by
+    /// `pyoxidizer lsp` for completion and hover and are treated as known
+    /// attributes.
+    fn docs(&self) -> &[&str] {
+        &[]
+    }
+}
+
+static PLUGINS: Lazy<RwLock<Vec<Arc<dyn StarlarkPlugin>>>> = Lazy::new(|| RwLock::new(vec![]));
+
+/// Register a plugin.
+///
+/// The plugin is used by all Starlark environments created afterwards.
+pub fn register_plugin(plugin: impl StarlarkPlugin + 'static) -> Result<()> {
+    let mut plugins = PLUGINS.write().unwrap();
+
+    if plugins.iter().any(|p| p.name() == plugin.name()) {
+        return Err(anyhow!("plugin {} is already registered", plugin.name()));
+    }
+
+    plugins.push(Arc::new(plugin));
+
+    Ok(())
+}
+
+/// Obtain registered plugins, in order of registration.
+pub fn registered_plugins() -> Vec<Arc<dyn StarlarkPlugin>> {
+    PLUGINS.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        starlark::{
+            values::Value,
+            {
+                starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+                starlark_signature_extraction, starlark_signatures,
+            },
+        },
+    };
+
+    starlark_module! { test_plugin_module =>
+        test_plugin_greeting(name: String) {
+            Ok(Value::from(format!("hello, {}", name)))
+        }
+    }
+
+    struct TestPlugin;
+
+    impl StarlarkPlugin for TestPlugin {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn register_starlark_dialect(
+            &self,
+            env: &mut Environment,
+            type_values: &mut TypeValues,
+        ) -> Result<(), EnvironmentError> {
+            test_plugin_module(env, type_values);
+
+            Ok(())
+        }
+
+        fn populate_environment(
+            &self,
+            env: &mut Environment,
+            _type_values: &mut TypeValues,
+            context: &PyOxidizerEnvironmentContext,
+        ) -> Result<(), EnvironmentError> {
+            env.set(
+                "TEST_PLUGIN_TARGET",
+                Value::from(context.build_target_triple.clone()),
+            )
+        }
+    }
+
+    #[test]
+    fn test_plugin() -> anyhow::Result<()> {
+        register_plugin(TestPlugin)?;
+        assert!(register_plugin(TestPlugin).is_err());
+
+        let value = starlark_ok("test_plugin_greeting('world')");
+        assert_eq!(value.to_str(), "hello, world");
+
+        let value = starlark_ok("TEST_PLUGIN_TARGET");
+        assert_eq!(value.to_str(), crate::environment::default_target_triple());
+
+        Ok(())
+    }
+}
@@ -4,15 +4,17 @@
 
 use {
     super::util::ToValue,
-    crate::py_packaging::config::PyembedPythonInterpreterConfig,
+    crate::py_packaging::config::{PyembedPythonInterpreterConfig, RUNTIME_CONFIG_SETTINGS},
     python_packaging::{
         interpreter::{
-            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, MemoryAllocatorBackend,
-            MultiprocessingStartMethod, PythonInterpreterProfile, TerminfoResolution,
+            Allocator, BytesWarning, CheckHashPycsMode, CoerceCLocale, EnvironmentMutation,
+            MemoryAllocatorBackend, MultiprocessingStartMethod, PythonInterpreterProfile,
+            TerminfoResolution, WorkingDirectoryPolicy,
         },
         resource::BytecodeOptimizationLevel,
     },
     starlark::values::{
+        dict::Dictionary,
         error::{
             RuntimeError, UnsupportedOperation, ValueError, INCORRECT_PARAMETER_TYPE_ERROR_CODE,
         },
@@ -21,8 +23,10 @@ use {
     },
     starlark_dialect_build_targets::{ToOptional, TryToOptional},
     std::{
+        path::PathBuf,
         str::FromStr,
         sync::{Arc, Mutex, MutexGuard},
+        time::Duration,
     },
 };
 
@@ -108,6 +112,167 @@ fn bytecode_optimization_level_try_to_optional(
     }
 }
 
+/// Starlark types of values that can be assigned to an attribute.
+///
+/// Values of other types are rejected instead of being coerced. e.g. assigning
+/// the string `"false"` to a `bool` attribute is an error rather than `True`.
+fn attribute_value_types(attribute: &str) -> &'static [&'static str] {
+    match attribute {
+        "allocator_raw"
+        | "allocator_mem"
+        | "allocator_obj"
+        | "allocator_pymalloc_arena"
+        | "allocator_debug"
+        | "set_missing_path_configuration"
+        | "oxidized_importer"
+        | "filesystem_importer"
+        | "argvb"
+        | "multiprocessing_auto_dispatch"
+        | "sys_frozen"
+        | "sys_meipass"
+        | "allow_runtime_path_overrides"
+        | "daemonize"
+        | "run_pytest"
+        | "coverage_mode"
+        | "debugger_support"
+        | "emulate_python_cli"
+        | "single_instance_forward_args"
+        | "logging_bridge" => &["bool"],
+        "configure_locale"
+        | "coerce_c_locale_warn"
+        | "development_mode"
+        | "isolated"
+        | "legacy_windows_fs_encoding"
+        | "parse_argv"
+        | "use_environment"
+        | "utf8_mode"
+        | "buffered_stdio"
+        | "configure_c_stdio"
+        | "dump_refs"
+        | "fault_handler"
+        | "import_time"
+        | "inspect"
+        | "install_signal_handlers"
+        | "interactive"
+        | "legacy_windows_stdio"
+        | "malloc_stats"
+        | "parser_debug"
+        | "pathconfig_warnings"
+        | "quiet"
+        | "show_ref_count"
+        | "site_import"
+        | "skip_first_source_line"
+        | "tracemalloc"
+        | "user_site_directory"
+        | "verbose"
+        | "write_bytecode" => &["bool", "NoneType"],
+        "config_profile"
+        | "allocator_backend"
+        | "multiprocessing_start_method"
+        | "terminfo_resolution"
+        | "working_directory" => &["string"],
+        "allocator"
+        | "coerce_c_locale"
+        | "bytes_warning"
+        | "check_hash_pycs_mode"
+        | "base_exec_prefix"
+        | "base_executable"
+        | "base_prefix"
+        | "exec_prefix"
+        | "executable"
+        | "filesystem_encoding"
+        | "filesystem_errors"
+        | "home"
+        | "prefix"
+        | "program_name"
+        | "pycache_prefix"
+        | "python_path_env"
+        | "run_command"
+        | "run_filename"
+        | "run_module"
+        | "stdio_encoding"
+        | "stdio_errors"
+        | "tcl_library"
+        | "tk_library"
+        | "write_modules_directory_env"
+        | "fault_handler_path"
+        | "crash_reports_directory"
+        | "crash_reports_upload_url"
+        | "update_feed_url"
+        | "update_channel"
+        | "update_public_key"
+        | "update_current_version"
+        | "pid_file"
+        | "stdout_path"
+        | "stderr_path"
+        | "user_site_packages"
+        | "dunder_file_root"
+        | "optimization_level_env"
+        | "app_name"
+        | "app_author"
+        | "single_instance"
+        | "splash_screen_image"
+        | "stdout_capture_path"
+        | "stderr_capture_path" => &["string", "NoneType"],
+        "hash_seed"
+        | "optimization_level"
+        | "tracemalloc_frames"
+        | "splash_screen_timeout"
+        | "capture_max_size" => &["int", "NoneType"],
+        "capture_backups" => &["int"],
+        "module_search_paths"
+        | "warn_options"
+        | "x_options"
+        | "shared_library_search_paths"
+        | "dunder_file_packages"
+        | "environment"
+        | "runtime_config_overrides" => &["list", "NoneType"],
+        "runtime_hooks" => &["dict", "NoneType"],
+        _ => &[],
+    }
+}
+
+/// Ensure a value can be assigned to an attribute.
+fn check_attribute_value(attribute: &str, value: &Value) -> Result<(), ValueError> {
+    let error = |message: String| {
+        ValueError::from(RuntimeError {
+            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+            message,
+            label: format!("PythonInterpreterConfig.{}", attribute),
+        })
+    };
+
+    let types = attribute_value_types(attribute);
+
+    if !types.is_empty() && !types.contains(&value.get_type()) {
+        return Err(error(format!(
+            "expected {}; got {}",
+            types.join(" or "),
+            value.get_type()
+        )));
+    }
+
+    // Lists hold strings and dicts map strings to strings.
+    if value.get_type() == "list" || value.get_type() == "dict" {
+        for key in &value.iter()? {
+            let mut items = vec![key.clone()];
+            if value.get_type() == "dict" {
+                items.push(value.at(key)?);
+            }
+
+            if let Some(item) = items.iter().find(|item| item.get_type() != "string") {
+                return Err(error(format!(
+                    "expected {} of strings; got {}",
+                    value.get_type(),
+                    item.get_type()
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct PythonInterpreterConfigValue {
     pub inner: Arc<Mutex<PyembedPythonInterpreterConfig>>,
@@ -225,7 +390,70 @@ impl TypedValue for PythonInterpreterConfigValue {
             "sys_frozen" => Value::from(inner.sys_frozen),
             "sys_meipass" => Value::from(inner.sys_meipass),
             "terminfo_resolution" => inner.terminfo_resolution.to_value(),
+            "tcl_library" => inner.tcl_library.to_value(),
+            "tk_library" => inner.tk_library.to_value(),
             "write_modules_directory_env" => inner.write_modules_directory_env.to_value(),
+            "allow_runtime_path_overrides" => Value::from(inner.allow_runtime_path_overrides),
+            "fault_handler_path" => inner.fault_handler_path.to_value(),
+            "tracemalloc_frames" => match inner.tracemalloc_frames {
+                Some(value) => Value::from(value as i64),
+                None => Value::from(NoneType::None),
+            },
+            "crash_reports_directory" => inner.crash_reports_directory.to_value(),
+            "crash_reports_upload_url" => inner.crash_reports_upload_url.to_value(),
+            "update_feed_url" => inner.update_feed_url.to_value(),
+            "update_channel" => inner.update_channel.to_value(),
+            "update_public_key" => inner.update_public_key.to_value(),
+            "update_current_version" => inner.update_current_version.to_value(),
+            "daemonize" => Value::from(inner.daemonize),
+            "pid_file" => inner.pid_file.to_value(),
+            "stdout_path" => inner.stdout_path.to_value(),
+            "stderr_path" => inner.stderr_path.to_value(),
+            "user_site_packages" => inner.user_site_packages.to_value(),
+            "run_pytest" => Value::from(inner.run_pytest),
+            "coverage_mode" => Value::from(inner.coverage_mode),
+            "debugger_support" => Value::from(inner.debugger_support),
+            "emulate_python_cli" => Value::from(inner.emulate_python_cli),
+            "shared_library_search_paths" => inner.shared_library_search_paths.to_value(),
+            "dunder_file_root" => inner.dunder_file_root.to_value(),
+            "optimization_level_env" => inner.optimization_level_env.to_value(),
+            "set_missing_path_configuration" => Value::from(inner.set_missing_path_configuration),
+            "dunder_file_packages" => Value::from(inner.dunder_file_packages.clone()),
+            "environment" => Value::from(
+                inner
+                    .environment
+                    .iter()
+                    .map(|mutation| mutation.to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            "working_directory" => Value::from(inner.working_directory.to_string()),
+            "app_name" => inner.app_name.to_value(),
+            "app_author" => inner.app_author.to_value(),
+            "single_instance" => inner.single_instance.to_value(),
+            "single_instance_forward_args" => Value::from(inner.single_instance_forward_args),
+            "splash_screen_image" => inner.splash_screen_image.to_value(),
+            "splash_screen_timeout" => match inner.splash_screen_timeout {
+                Some(value) => Value::from(value.as_millis() as i64),
+                None => Value::from(NoneType::None),
+            },
+            "stdout_capture_path" => inner.stdout_capture_path.to_value(),
+            "stderr_capture_path" => inner.stderr_capture_path.to_value(),
+            "capture_max_size" => match inner.capture_max_size {
+                Some(value) => Value::from(value as i64),
+                None => Value::from(NoneType::None),
+            },
+            "capture_backups" => Value::from(inner.capture_backups as i64),
+            "logging_bridge" => Value::from(inner.logging_bridge),
+            "runtime_config_overrides" => Value::from(inner.runtime_config_overrides.clone()),
+            "runtime_hooks" => {
+                let mut hooks = Dictionary::default();
+
+                for (module, code) in &inner.runtime_hooks {
+                    hooks.insert(Value::from(module.as_str()), Value::from(code.as_str()))?;
+                }
+
+                Value::try_from(hooks.get_content().clone())?
+            }
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::GetAttr(attr.to_string()),
@@ -310,11 +538,53 @@ impl TypedValue for PythonInterpreterConfigValue {
                 | "sys_frozen"
                 | "sys_meipass"
                 | "terminfo_resolution"
+                | "tcl_library"
+                | "tk_library"
                 | "write_modules_directory_env"
+                | "allow_runtime_path_overrides"
+                | "fault_handler_path"
+                | "tracemalloc_frames"
+                | "crash_reports_directory"
+                | "crash_reports_upload_url"
+                | "update_feed_url"
+                | "update_channel"
+                | "update_public_key"
+                | "update_current_version"
+                | "daemonize"
+                | "pid_file"
+                | "stdout_path"
+                | "stderr_path"
+                | "user_site_packages"
+                | "run_pytest"
+                | "coverage_mode"
+                | "debugger_support"
+                | "emulate_python_cli"
+                | "shared_library_search_paths"
+                | "dunder_file_root"
+                | "optimization_level_env"
+                | "set_missing_path_configuration"
+                | "dunder_file_packages"
+                | "runtime_hooks"
+                | "environment"
+                | "working_directory"
+                | "app_name"
+                | "app_author"
+                | "single_instance"
+                | "single_instance_forward_args"
+                | "splash_screen_image"
+                | "splash_screen_timeout"
+                | "stdout_capture_path"
+                | "stderr_capture_path"
+                | "capture_max_size"
+                | "capture_backups"
+                | "logging_bridge"
+                | "runtime_config_overrides"
         ))
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        check_attribute_value(attribute, &value)?;
+
         let mut inner = self.inner(&format!("PythonInterpreterConfig.{}", attribute))?;
 
         match attribute {
@@ -538,7 +808,7 @@ impl TypedValue for PythonInterpreterConfigValue {
                 inner.config.user_site_directory = value.to_optional();
             }
             "verbose" => {
-                inner.config.configure_locale = value.to_optional();
+                inner.config.verbose = value.to_optional();
             }
             "warn_options" => {
                 inner.config.warn_options = value.try_to_optional()?;
@@ -614,9 +884,208 @@ impl TypedValue for PythonInterpreterConfigValue {
                         })
                     })?;
             }
+            "tcl_library" => {
+                inner.tcl_library = value.to_optional();
+            }
+            "tk_library" => {
+                inner.tk_library = value.to_optional();
+            }
             "write_modules_directory_env" => {
                 inner.write_modules_directory_env = value.to_optional();
             }
+            "allow_runtime_path_overrides" => {
+                inner.allow_runtime_path_overrides = value.to_bool();
+            }
+            "fault_handler_path" => {
+                inner.fault_handler_path = value.to_optional();
+            }
+            "tracemalloc_frames" => {
+                let frames: Option<i64> = value.try_to_optional()?;
+                inner.tracemalloc_frames = frames.map(u32::try_from).transpose().map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!("invalid number of frames: {}", e),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })
+                })?;
+            }
+            "crash_reports_directory" => {
+                inner.crash_reports_directory = value.to_optional();
+            }
+            "crash_reports_upload_url" => {
+                inner.crash_reports_upload_url = value.to_optional();
+            }
+            "update_feed_url" => {
+                inner.update_feed_url = value.to_optional();
+            }
+            "update_channel" => {
+                inner.update_channel = value.to_optional();
+            }
+            "update_public_key" => {
+                inner.update_public_key = value.to_optional();
+            }
+            "update_current_version" => {
+                inner.update_current_version = value.to_optional();
+            }
+            "daemonize" => {
+                inner.daemonize = value.to_bool();
+            }
+            "pid_file" => {
+                inner.pid_file = value.to_optional();
+            }
+            "stdout_path" => {
+                inner.stdout_path = value.to_optional();
+            }
+            "stderr_path" => {
+                inner.stderr_path = value.to_optional();
+            }
+            "user_site_packages" => {
+                inner.user_site_packages = value.to_optional();
+            }
+            "run_pytest" => {
+                inner.run_pytest = value.to_bool();
+            }
+            "coverage_mode" => {
+                inner.coverage_mode = value.to_bool();
+            }
+            "debugger_support" => {
+                inner.debugger_support = value.to_bool();
+            }
+            "emulate_python_cli" => {
+                inner.emulate_python_cli = value.to_bool();
+            }
+            "shared_library_search_paths" => {
+                let paths: Option<Vec<PathBuf>> = value.try_to_optional()?;
+                inner.shared_library_search_paths = paths.unwrap_or_default();
+            }
+            "dunder_file_root" => {
+                inner.dunder_file_root = value.to_optional();
+            }
+            "optimization_level_env" => {
+                inner.optimization_level_env = value.to_optional();
+            }
+            "set_missing_path_configuration" => {
+                inner.set_missing_path_configuration = value.to_bool();
+            }
+            "dunder_file_packages" => {
+                let packages: Option<Vec<String>> = value.try_to_optional()?;
+                inner.dunder_file_packages = packages.unwrap_or_default();
+            }
+            "runtime_hooks" => {
+                let mut hooks = vec![];
+
+                if value.get_type() == "dict" {
+                    for module in &value.iter()? {
+                        let code = value.at(module.clone())?.to_string();
+                        hooks.push((module.to_string(), code));
+                    }
+                }
+
+                inner.runtime_hooks = hooks;
+            }
+            "environment" => {
+                let mutations: Option<Vec<String>> = value.try_to_optional()?;
+                inner.environment = mutations
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(EnvironmentMutation::try_from)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
+            "working_directory" => {
+                inner.working_directory =
+                    WorkingDirectoryPolicy::try_from(value.to_string().as_str()).map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: e,
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+            }
+            "app_name" => {
+                inner.app_name = value.to_optional();
+            }
+            "app_author" => {
+                inner.app_author = value.to_optional();
+            }
+            "single_instance" => {
+                inner.single_instance = value.to_optional();
+            }
+            "single_instance_forward_args" => {
+                inner.single_instance_forward_args = value.to_bool();
+            }
+            "splash_screen_image" => {
+                inner.splash_screen_image = value.to_optional();
+            }
+            "splash_screen_timeout" => {
+                let millis: Option<i64> = value.try_to_optional()?;
+                inner.splash_screen_timeout = millis
+                    .map(u64::try_from)
+                    .transpose()
+                    .map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                            message: format!("invalid splash screen timeout: {}", e),
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?
+                    .map(Duration::from_millis);
+            }
+            "stdout_capture_path" => {
+                inner.stdout_capture_path = value.to_optional();
+            }
+            "stderr_capture_path" => {
+                inner.stderr_capture_path = value.to_optional();
+            }
+            "capture_max_size" => {
+                let size: Option<i64> = value.try_to_optional()?;
+                inner.capture_max_size = size.map(u64::try_from).transpose().map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!("invalid capture size: {}", e),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })
+                })?;
+            }
+            "capture_backups" => {
+                inner.capture_backups = usize::try_from(value.to_int()?).map_err(|e| {
+                    ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!("invalid number of backups: {}", e),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    })
+                })?;
+            }
+            "logging_bridge" => {
+                inner.logging_bridge = value.to_bool();
+            }
+            "runtime_config_overrides" => {
+                let settings: Option<Vec<String>> = value.try_to_optional()?;
+                let settings = settings.unwrap_or_default();
+
+                if let Some(setting) = settings
+                    .iter()
+                    .find(|setting| !RUNTIME_CONFIG_SETTINGS.contains(&setting.as_str()))
+                {
+                    return Err(ValueError::from(RuntimeError {
+                        code: INCORRECT_PARAMETER_TYPE_ERROR_CODE,
+                        message: format!(
+                            "{} can't be overridden at run-time; expected one of {}",
+                            setting,
+                            RUNTIME_CONFIG_SETTINGS.join(", ")
+                        ),
+                        label: format!("{}.{}", Self::TYPE, attribute),
+                    }));
+                }
+
+                inner.runtime_config_overrides = settings;
+            }
             attr => {
                 return Err(ValueError::OperationNotSupported {
                     op: UnsupportedOperation::SetAttr(attr.to_string()),
@@ -1164,6 +1633,10 @@ mod tests {
 
         eval_assert(&mut env, "config.verbose == None")?;
 
+        env.eval("config.verbose = True")?;
+        eval_assert(&mut env, "config.verbose == True")?;
+        eval_assert(&mut env, "config.configure_locale == True")?;
+
         Ok(())
     }
 
@@ -1377,6 +1850,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_tcl_tk_library() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.tcl_library == None")?;
+        eval_assert(&mut env, "config.tk_library == None")?;
+
+        env.eval("config.tcl_library = '$ORIGIN/lib/tcl8.6'")?;
+        env.eval("config.tk_library = '$ORIGIN/lib/tk8.6'")?;
+        eval_assert(&mut env, "config.tcl_library == '$ORIGIN/lib/tcl8.6'")?;
+        eval_assert(&mut env, "config.tk_library == '$ORIGIN/lib/tk8.6'")?;
+
+        env.eval("config.tk_library = None")?;
+        eval_assert(&mut env, "config.tk_library == None")?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_write_modules_directory_env() -> Result<()> {
         let mut env = get_env()?;
@@ -1385,4 +1876,447 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_allow_runtime_path_overrides() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.allow_runtime_path_overrides == False")?;
+
+        env.eval("config.allow_runtime_path_overrides = True")?;
+        eval_assert(&mut env, "config.allow_runtime_path_overrides == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fault_handler_path() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.fault_handler_path == None")?;
+
+        env.eval("config.fault_handler_path = '$ORIGIN/faults.log'")?;
+        eval_assert(
+            &mut env,
+            "config.fault_handler_path == '$ORIGIN/faults.log'",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracemalloc_frames() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.tracemalloc_frames == None")?;
+
+        env.eval("config.tracemalloc_frames = 25")?;
+        eval_assert(&mut env, "config.tracemalloc_frames == 25")?;
+
+        env.eval("config.tracemalloc_frames = None")?;
+        eval_assert(&mut env, "config.tracemalloc_frames == None")?;
+
+        assert!(env.eval("config.tracemalloc_frames = -1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crash_reports() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.crash_reports_directory == None")?;
+        eval_assert(&mut env, "config.crash_reports_upload_url == None")?;
+
+        env.eval("config.crash_reports_directory = '$ORIGIN/crashes'")?;
+        eval_assert(
+            &mut env,
+            "config.crash_reports_directory == '$ORIGIN/crashes'",
+        )?;
+
+        env.eval("config.crash_reports_upload_url = 'https://example.com/crashes'")?;
+        eval_assert(
+            &mut env,
+            "config.crash_reports_upload_url == 'https://example.com/crashes'",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_updater() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.update_feed_url == None")?;
+        eval_assert(&mut env, "config.update_channel == None")?;
+        eval_assert(&mut env, "config.update_public_key == None")?;
+        eval_assert(&mut env, "config.update_current_version == None")?;
+
+        env.eval("config.update_feed_url = 'https://example.com/updates.json'")?;
+        eval_assert(
+            &mut env,
+            "config.update_feed_url == 'https://example.com/updates.json'",
+        )?;
+
+        env.eval("config.update_channel = 'beta'")?;
+        eval_assert(&mut env, "config.update_channel == 'beta'")?;
+
+        env.eval("config.update_current_version = '1.0.0'")?;
+        eval_assert(&mut env, "config.update_current_version == '1.0.0'")?;
+
+        assert!(env.eval("config.update_public_key = 42").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_daemon() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.daemonize == False")?;
+        eval_assert(&mut env, "config.pid_file == None")?;
+        eval_assert(&mut env, "config.stdout_path == None")?;
+        eval_assert(&mut env, "config.stderr_path == None")?;
+
+        env.eval("config.daemonize = True")?;
+        eval_assert(&mut env, "config.daemonize == True")?;
+
+        env.eval("config.pid_file = '/run/myapp.pid'")?;
+        eval_assert(&mut env, "config.pid_file == '/run/myapp.pid'")?;
+
+        env.eval("config.stdout_path = '$ORIGIN/myapp.log'")?;
+        env.eval("config.stderr_path = '$ORIGIN/myapp.log'")?;
+        eval_assert(&mut env, "config.stdout_path == '$ORIGIN/myapp.log'")?;
+        eval_assert(&mut env, "config.stderr_path == '$ORIGIN/myapp.log'")?;
+
+        assert!(env.eval("config.daemonize = None").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_site_packages() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.user_site_packages == None")?;
+
+        env.eval("config.user_site_packages = 'myapp/site-packages'")?;
+        eval_assert(
+            &mut env,
+            "config.user_site_packages == 'myapp/site-packages'",
+        )?;
+
+        env.eval("config.user_site_packages = None")?;
+        eval_assert(&mut env, "config.user_site_packages == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_pytest() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.run_pytest == False")?;
+
+        env.eval("config.run_pytest = True")?;
+        eval_assert(&mut env, "config.run_pytest == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage_mode() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.coverage_mode == False")?;
+
+        env.eval("config.coverage_mode = True")?;
+        eval_assert(&mut env, "config.coverage_mode == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debugger_support() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.debugger_support == False")?;
+
+        env.eval("config.debugger_support = True")?;
+        eval_assert(&mut env, "config.debugger_support == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_emulate_python_cli() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.emulate_python_cli == False")?;
+
+        env.eval("config.emulate_python_cli = True")?;
+        eval_assert(&mut env, "config.emulate_python_cli == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_library_search_paths() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.shared_library_search_paths == []")?;
+
+        env.eval("config.shared_library_search_paths = ['$ORIGIN/lib']")?;
+        eval_assert(
+            &mut env,
+            "config.shared_library_search_paths == ['$ORIGIN/lib']",
+        )?;
+
+        env.eval("config.shared_library_search_paths = None")?;
+        eval_assert(&mut env, "config.shared_library_search_paths == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dunder_file_root() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.dunder_file_root == None")?;
+
+        env.eval("config.dunder_file_root = '$ORIGIN/data'")?;
+        eval_assert(&mut env, "config.dunder_file_root == '$ORIGIN/data'")?;
+
+        env.eval("config.dunder_file_root = None")?;
+        eval_assert(&mut env, "config.dunder_file_root == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimization_level_env() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.optimization_level_env == None")?;
+
+        env.eval("config.optimization_level_env = 'MYAPP_OPTIMIZE'")?;
+        eval_assert(
+            &mut env,
+            "config.optimization_level_env == 'MYAPP_OPTIMIZE'",
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_missing_path_configuration() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.set_missing_path_configuration == True")?;
+
+        env.eval("config.set_missing_path_configuration = False")?;
+        eval_assert(&mut env, "config.set_missing_path_configuration == False")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dunder_file_packages() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.dunder_file_packages == []")?;
+
+        env.eval("config.dunder_file_packages = ['foo', 'bar']")?;
+        eval_assert(&mut env, "config.dunder_file_packages == ['foo', 'bar']")?;
+
+        env.eval("config.dunder_file_packages = None")?;
+        eval_assert(&mut env, "config.dunder_file_packages == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_hooks() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.runtime_hooks == {}")?;
+
+        env.eval("config.runtime_hooks = {'foo': 'module.x = 1'}")?;
+        eval_assert(&mut env, "config.runtime_hooks == {'foo': 'module.x = 1'}")?;
+
+        assert!(env.eval("config.runtime_hooks = {'foo': 1}").is_err());
+
+        env.eval("config.runtime_hooks = None")?;
+        eval_assert(&mut env, "config.runtime_hooks == {}")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_environment() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.environment == []")?;
+
+        env.eval("config.environment = ['set:FOO=bar', 'prepend:PATH=$ORIGIN/bin']")?;
+        eval_assert(
+            &mut env,
+            "config.environment == ['set:FOO=bar', 'prepend:PATH=$ORIGIN/bin']",
+        )?;
+
+        assert!(env.eval("config.environment = ['FOO=bar']").is_err());
+
+        env.eval("config.environment = None")?;
+        eval_assert(&mut env, "config.environment == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_working_directory() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.working_directory == 'unchanged'")?;
+
+        env.eval("config.working_directory = 'exe'")?;
+        eval_assert(&mut env, "config.working_directory == 'exe'")?;
+
+        env.eval("config.working_directory = 'user-data'")?;
+        eval_assert(&mut env, "config.working_directory == 'user-data'")?;
+
+        assert!(env.eval("config.working_directory = 'home'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_app_metadata() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.app_name == None")?;
+        eval_assert(&mut env, "config.app_author == None")?;
+
+        env.eval("config.app_name = 'myapp'")?;
+        env.eval("config.app_author = 'ACME'")?;
+        eval_assert(&mut env, "config.app_name == 'myapp'")?;
+        eval_assert(&mut env, "config.app_author == 'ACME'")?;
+
+        env.eval("config.app_name = None")?;
+        eval_assert(&mut env, "config.app_name == None")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_instance() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.single_instance == None")?;
+        eval_assert(&mut env, "config.single_instance_forward_args == False")?;
+
+        env.eval("config.single_instance = 'myapp'")?;
+        env.eval("config.single_instance_forward_args = True")?;
+        eval_assert(&mut env, "config.single_instance == 'myapp'")?;
+        eval_assert(&mut env, "config.single_instance_forward_args == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_splash_screen() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.splash_screen_image == None")?;
+        eval_assert(&mut env, "config.splash_screen_timeout == None")?;
+
+        env.eval("config.splash_screen_image = 'splash.bmp'")?;
+        env.eval("config.splash_screen_timeout = 5000")?;
+        eval_assert(&mut env, "config.splash_screen_image == 'splash.bmp'")?;
+        eval_assert(&mut env, "config.splash_screen_timeout == 5000")?;
+
+        assert!(env.eval("config.splash_screen_timeout = -1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_capture() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.stdout_capture_path == None")?;
+        eval_assert(&mut env, "config.stderr_capture_path == None")?;
+        eval_assert(&mut env, "config.capture_max_size == None")?;
+        eval_assert(&mut env, "config.capture_backups == 0")?;
+
+        env.eval("config.stdout_capture_path = 'myapp.log'")?;
+        env.eval("config.stderr_capture_path = 'myapp.log'")?;
+        env.eval("config.capture_max_size = 1048576")?;
+        env.eval("config.capture_backups = 3")?;
+        eval_assert(&mut env, "config.stdout_capture_path == 'myapp.log'")?;
+        eval_assert(&mut env, "config.stderr_capture_path == 'myapp.log'")?;
+        eval_assert(&mut env, "config.capture_max_size == 1048576")?;
+        eval_assert(&mut env, "config.capture_backups == 3")?;
+
+        assert!(env.eval("config.capture_max_size = -1").is_err());
+        assert!(env.eval("config.capture_backups = -1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_logging_bridge() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.logging_bridge == False")?;
+        env.eval("config.logging_bridge = True")?;
+        eval_assert(&mut env, "config.logging_bridge == True")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_config_overrides() -> Result<()> {
+        let mut env = get_env()?;
+
+        eval_assert(&mut env, "config.runtime_config_overrides == []")?;
+
+        env.eval("config.runtime_config_overrides = ['optimization_level', 'verbose']")?;
+        eval_assert(
+            &mut env,
+            "config.runtime_config_overrides == ['optimization_level', 'verbose']",
+        )?;
+
+        assert!(env
+            .eval("config.runtime_config_overrides = ['packed_resources']")
+            .is_err());
+
+        env.eval("config.runtime_config_overrides = None")?;
+        eval_assert(&mut env, "config.runtime_config_overrides == []")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_attribute_types() -> Result<()> {
+        let mut env = get_env()?;
+
+        assert!(env.eval("config.oxidized_importer = 'false'").is_err());
+        assert!(env.eval("config.oxidized_importer = None").is_err());
+        assert!(env.eval("config.isolated = 1").is_err());
+        assert!(env.eval("config.run_command = True").is_err());
+        assert!(env.eval("config.config_profile = None").is_err());
+        assert!(env.eval("config.hash_seed = '42'").is_err());
+        assert!(env.eval("config.x_options = 'foo'").is_err());
+        assert!(env.eval("config.x_options = ['foo', 1]").is_err());
+
+        // Failed assignments leave the attribute unchanged.
+        eval_assert(&mut env, "config.oxidized_importer == True")?;
+
+        env.eval("config.isolated = None")?;
+        env.eval("config.hash_seed = 42")?;
+        env.eval("config.x_options = ['foo']")?;
+        eval_assert(&mut env, "config.x_options == ['foo']")?;
+
+        Ok(())
+    }
 }
@@ -13,6 +13,7 @@ use {
         {Mutable, TypedValue, Value, ValueResult},
     },
     std::sync::{Arc, Mutex, MutexGuard},
+    tugger_file_manifest::FileData,
 };
 
 #[derive(Debug)]
@@ -154,6 +155,20 @@ impl TypedValue for PythonModuleSourceValue {
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
         if self.add_collection_context_attrs().contains(&attribute) {
             self.set_attr_add_collection_context(attribute, value)
+        } else if attribute == "name" {
+            let name = value.to_string();
+            self.inner(&format!("PythonModuleSource.{}", attribute))?
+                .m
+                .name = name.clone();
+            self.name = name;
+
+            Ok(())
+        } else if attribute == "source" {
+            self.inner(&format!("PythonModuleSource.{}", attribute))?
+                .m
+                .source = FileData::Memory(value.to_string().into_bytes());
+
+            Ok(())
         } else {
             Err(ValueError::OperationNotSupported {
                 op: UnsupportedOperation::SetAttr(attribute.to_string()),
@@ -145,6 +145,26 @@ impl TypedValue for PythonPackageResourceValue {
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        self.set_attr_add_collection_context(attribute, value)
+        match attribute {
+            "package" => {
+                let package = value.to_string();
+                self.inner(&format!("PythonPackageResource.{}", attribute))?
+                    .r
+                    .leaf_package = package.clone();
+                self.leaf_package = package;
+
+                Ok(())
+            }
+            "name" => {
+                let name = value.to_string();
+                self.inner(&format!("PythonPackageResource.{}", attribute))?
+                    .r
+                    .relative_name = name.clone();
+                self.relative_name = name;
+
+                Ok(())
+            }
+            _ => self.set_attr_add_collection_context(attribute, value),
+        }
     }
 }
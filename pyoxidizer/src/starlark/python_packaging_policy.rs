@@ -4,6 +4,7 @@
 
 use {
     super::python_resource::ResourceCollectionContext,
+    crate::py_packaging::resource_compilers::resource_compiler,
     linked_hash_map::LinkedHashMap,
     python_packaging::{
         location::ConcreteResourceLocation,
@@ -20,7 +21,7 @@ use {
             Mutable, TypedValue, Value, ValueResult,
         },
     },
-    starlark_dialect_build_targets::required_type_arg,
+    starlark_dialect_build_targets::{required_list_arg, required_type_arg},
     std::{
         ops::Deref,
         sync::{Arc, Mutex, MutexGuard},
@@ -33,6 +34,9 @@ pub struct PythonPackagingPolicyValue {
 
     /// Starlark functions to influence PythonResourceAddCollectionContext creation.
     derive_context_callbacks: Vec<Value>,
+
+    /// Starlark functions transforming resources added to executables.
+    transform_callbacks: Vec<Value>,
 }
 
 impl PythonPackagingPolicyValue {
@@ -40,6 +44,7 @@ impl PythonPackagingPolicyValue {
         Self {
             inner: Arc::new(Mutex::new(inner)),
             derive_context_callbacks: vec![],
+            transform_callbacks: vec![],
         }
     }
 
@@ -103,6 +108,52 @@ impl PythonPackagingPolicyValue {
 
         Ok(Value::from(NoneType::None))
     }
+
+    /// Run registered transform functions on a resource.
+    ///
+    /// Each function receives the value returned by the previous one. Returns
+    /// `None` if a function dropped the resource by returning `None`.
+    pub fn transform_resource(
+        &self,
+        label: &str,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        value: Value,
+    ) -> Result<Option<Value>, ValueError> {
+        let mut value = value;
+
+        for func in &self.transform_callbacks {
+            value = func.call(
+                call_stack,
+                type_values,
+                vec![Value::new(self.clone()), value],
+                LinkedHashMap::new(),
+                None,
+                None,
+            )?;
+
+            match value.get_type() {
+                "NoneType" => return Ok(None),
+                "File"
+                | "PythonExtensionModule"
+                | "PythonModuleSource"
+                | "PythonPackageDistributionResource"
+                | "PythonPackageResource" => {}
+                t => {
+                    return Err(ValueError::from(RuntimeError {
+                        code: "PYTHON_PACKAGING_POLICY",
+                        message: format!(
+                            "resource transform functions must return a resource or None; got {}",
+                            t
+                        ),
+                        label: label.to_string(),
+                    }))
+                }
+            }
+        }
+
+        Ok(Some(value))
+    }
 }
 
 impl TypedValue for PythonPackagingPolicyValue {
@@ -112,7 +163,12 @@ impl TypedValue for PythonPackagingPolicyValue {
     fn values_for_descendant_check_and_freeze<'a>(
         &'a self,
     ) -> Box<dyn Iterator<Item = Value> + 'a> {
-        Box::new(self.derive_context_callbacks.iter().cloned())
+        Box::new(
+            self.derive_context_callbacks
+                .iter()
+                .chain(self.transform_callbacks.iter())
+                .cloned(),
+        )
     }
 
     fn get_attr(&self, attribute: &str) -> ValueResult {
@@ -129,6 +185,7 @@ impl TypedValue for PythonPackagingPolicyValue {
             "extension_module_filter" => Value::from(inner.extension_module_filter().as_ref()),
             "file_scanner_classify_files" => Value::from(inner.file_scanner_classify_files()),
             "file_scanner_emit_files" => Value::from(inner.file_scanner_emit_files()),
+            "gpu_stack_compat" => Value::from(inner.gpu_stack_compat()),
             "include_distribution_sources" => Value::from(inner.include_distribution_sources()),
             "include_distribution_resources" => Value::from(inner.include_distribution_resources()),
             "include_classified_resources" => Value::from(inner.include_classified_resources()),
@@ -140,6 +197,7 @@ impl TypedValue for PythonPackagingPolicyValue {
             "preferred_extension_module_variants" => {
                 Value::try_from(inner.preferred_extension_module_variants().clone())?
             }
+            "resource_compilers" => Value::from(inner.resource_compilers().to_vec()),
             "resources_location" => Value::from(inner.resources_location().to_string()),
             "resources_location_fallback" => match inner.resources_location_fallback() {
                 Some(location) => Value::from(location.to_string()),
@@ -168,6 +226,7 @@ impl TypedValue for PythonPackagingPolicyValue {
                 | "extension_module_filter"
                 | "file_scanner_classify_files"
                 | "file_scanner_emit_files"
+                | "gpu_stack_compat"
                 | "include_distribution_sources"
                 | "include_distribution_resources"
                 | "include_classified_resources"
@@ -175,6 +234,7 @@ impl TypedValue for PythonPackagingPolicyValue {
                 | "include_non_distribution_sources"
                 | "include_test"
                 | "preferred_extension_module_variants"
+                | "resource_compilers"
                 | "resources_location"
                 | "resources_location_fallback"
         ))
@@ -217,6 +277,9 @@ impl TypedValue for PythonPackagingPolicyValue {
             "file_scanner_emit_files" => {
                 inner.set_file_scanner_emit_files(value.to_bool());
             }
+            "gpu_stack_compat" => {
+                inner.set_gpu_stack_compat(value.to_bool());
+            }
             "include_classified_resources" => {
                 inner.set_include_classified_resources(value.to_bool());
             }
@@ -235,6 +298,27 @@ impl TypedValue for PythonPackagingPolicyValue {
             "include_test" => {
                 inner.set_include_test(value.to_bool());
             }
+            "resource_compilers" => {
+                required_list_arg(attribute, "string", &value)?;
+
+                let names = value
+                    .iter()?
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>();
+
+                for name in &names {
+                    resource_compiler(name).map_err(|e| {
+                        ValueError::from(RuntimeError {
+                            code: "PYOXIDIZER_BUILD",
+                            message: e.to_string(),
+                            label: format!("{}.{}", Self::TYPE, attribute),
+                        })
+                    })?;
+                }
+
+                inner.set_resource_compilers(names);
+            }
             "resources_location" => {
                 inner.set_resources_location(
                     ConcreteResourceLocation::try_from(value.to_string().as_str()).map_err(
@@ -290,6 +374,13 @@ impl TypedValue for PythonPackagingPolicyValue {
 
 // Starlark methods.
 impl PythonPackagingPolicyValue {
+    fn starlark_register_all_optimize_levels_package(&mut self, package: String) -> ValueResult {
+        self.inner("PythonPackagingPolicy.register_all_optimize_levels_package()")?
+            .register_all_optimize_levels_package(&package);
+
+        Ok(Value::from(NoneType::None))
+    }
+
     fn starlark_register_resource_callback(&mut self, func: &Value) -> ValueResult {
         required_type_arg("func", "function", func)?;
 
@@ -298,6 +389,14 @@ impl PythonPackagingPolicyValue {
         Ok(Value::from(NoneType::None))
     }
 
+    fn starlark_register_resource_transform(&mut self, func: &Value) -> ValueResult {
+        required_type_arg("func", "function", func)?;
+
+        self.transform_callbacks.push(func.clone());
+
+        Ok(Value::from(NoneType::None))
+    }
+
     #[allow(clippy::unnecessary_wraps)]
     fn starlark_set_preferred_extension_module_variant(
         &mut self,
@@ -328,11 +427,21 @@ impl PythonPackagingPolicyValue {
 }
 
 starlark_module! { python_packaging_policy_module =>
+    PythonPackagingPolicy.register_all_optimize_levels_package(this, package: String) {
+        let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
+        this.starlark_register_all_optimize_levels_package(package)
+    }
+
     PythonPackagingPolicy.register_resource_callback(this, func) {
         let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
         this.starlark_register_resource_callback(&func)
     }
 
+    PythonPackagingPolicy.register_resource_transform(this, func) {
+        let mut this = this.downcast_mut::<PythonPackagingPolicyValue>().unwrap().unwrap();
+        this.starlark_register_resource_transform(&func)
+    }
+
     PythonPackagingPolicy.set_preferred_extension_module_variant(
         this,
         name: String,
@@ -487,6 +596,13 @@ mod tests {
         let value = env.eval("policy.include_test = True; policy.include_test")?;
         assert!(value.to_bool());
 
+        let value = env.eval("policy.gpu_stack_compat")?;
+        assert_eq!(value.get_type(), "bool");
+        assert!(!value.to_bool());
+
+        let value = env.eval("policy.gpu_stack_compat = True; policy.gpu_stack_compat")?;
+        assert!(value.to_bool());
+
         let value = env.eval("policy.resources_location")?;
         assert_eq!(value.get_type(), "string");
         assert_eq!(value.to_string(), "in-memory");
@@ -597,6 +713,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_register_all_optimize_levels_package() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+        env.eval("policy.register_all_optimize_levels_package('foo')")?;
+
+        let policy_value = env.eval("policy")?;
+        let policy = policy_value
+            .downcast_ref::<PythonPackagingPolicyValue>()
+            .unwrap();
+        assert!(policy
+            .inner("ignored")
+            .unwrap()
+            .all_optimize_levels_packages()
+            .contains("foo"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_register_resource_callback() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -620,6 +757,117 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_register_resource_transform() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        let exe_value = env.eval(indoc! {r#"
+            dist = default_python_distribution()
+            policy = dist.make_python_packaging_policy()
+
+            def drop_tests(policy, resource):
+                if type(resource) == "PythonModuleSource" and ".tests" in resource.name:
+                    return None
+
+                return resource
+
+            def stamp(policy, resource):
+                if type(resource) == "PythonModuleSource" and resource.name == "foo":
+                    resource.name = "bar"
+                    resource.source = resource.source + "\nBUILD = 42\n"
+                    resource.add_source = True
+
+                return resource
+
+            policy.register_resource_transform(drop_tests)
+            policy.register_resource_transform(stamp)
+
+            exe = dist.to_python_executable(
+                name = "myapp",
+                packaging_policy = policy
+            )
+
+            exe.add_python_resources([
+                exe.make_python_module_source("foo", "x = 1"),
+                exe.make_python_module_source("foo.tests", "", is_package = True),
+            ])
+
+            exe
+        "#})?;
+
+        let exe = exe_value.downcast_ref::<PythonExecutableValue>().unwrap();
+        let inner = exe.inner("ignored").unwrap();
+
+        assert!(!inner
+            .iter_resources()
+            .any(|(name, _)| name == "foo" || name == "foo.tests"));
+
+        let (_, resource) = inner
+            .iter_resources()
+            .find(|(name, _)| *name == "bar")
+            .unwrap();
+        assert_eq!(
+            resource
+                .in_memory_source
+                .as_ref()
+                .unwrap()
+                .resolve_content()?,
+            b"x = 1\nBUILD = 42\n".to_vec()
+        );
+
+        drop(inner);
+
+        env.eval(indoc! {r#"
+            def invalid(policy, resource):
+                return 42
+
+            policy = dist.make_python_packaging_policy()
+            policy.register_resource_transform(invalid)
+            exe = dist.to_python_executable(name = "myapp", packaging_policy = policy)
+        "#})?;
+        assert!(env
+            .eval("exe.add_python_resource(exe.make_python_module_source('foo', ''))")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_compilers() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let package_dir = temp_dir.path().join("app");
+        let messages_dir = package_dir.join("locale").join("de").join("LC_MESSAGES");
+        std::fs::create_dir_all(&messages_dir)?;
+        std::fs::write(package_dir.join("__init__.py"), "")?;
+        std::fs::write(
+            messages_dir.join("app.po"),
+            "msgid \"Hello\"\nmsgstr \"Hallo\"\n",
+        )?;
+
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("dist = default_python_distribution()")?;
+        env.eval("policy = dist.make_python_packaging_policy()")?;
+
+        assert_eq!(env.eval("policy.resource_compilers")?.to_string(), "[]");
+        assert!(env.eval("policy.resource_compilers = ['sass']").is_err());
+
+        env.eval("policy.resource_compilers = ['gettext']")?;
+        assert_eq!(
+            env.eval("policy.resource_compilers")?.to_string(),
+            "[\"gettext\"]"
+        );
+
+        env.eval("exe = dist.to_python_executable(name = 'myapp', packaging_policy = policy)")?;
+        let names = env.eval(&format!(
+            "[r.name for r in exe.read_package_root({:?}, ['app']) if type(r) == 'PythonPackageResource']",
+            temp_dir.path().display().to_string()
+        ))?;
+        assert_eq!(names.to_string(), "[\"locale/de/LC_MESSAGES/app.mo\"]");
+
+        Ok(())
+    }
+
     #[test]
     fn test_set_resource_handling_mode() -> Result<()> {
         let mut env = test_evaluation_context_builder()?.into_context()?;
@@ -4,12 +4,15 @@
 
 use {
     super::{
-        file::FileValue, python_extension_module::PythonExtensionModuleValue,
+        env::{get_context, PyOxidizerEnvironmentContext},
+        file::FileValue,
+        python_extension_module::PythonExtensionModuleValue,
         python_module_source::PythonModuleSourceValue,
         python_package_distribution_resource::PythonPackageDistributionResourceValue,
         python_package_resource::PythonPackageResourceValue,
         python_packaging_policy::PythonPackagingPolicyValue,
     },
+    crate::py_packaging::resource_compilers::ResourceCompilers,
     python_packaging::{
         location::ConcreteResourceLocation, resource::PythonResource,
         resource_collection::PythonResourceAddCollectionContext,
@@ -253,6 +256,41 @@ pub fn is_resource_starlark_compatible(resource: &PythonResource) -> bool {
     }
 }
 
+/// Apply the resource compilers of a policy to a resource.
+///
+/// Returns `None` if no compiler applies to the resource.
+fn compile_resource<'a>(
+    label: &str,
+    type_values: &TypeValues,
+    resource: &PythonResource<'a>,
+    policy: &PythonPackagingPolicyValue,
+) -> Result<Option<PythonResource<'a>>, ValueError> {
+    let names = policy.inner(label)?.resource_compilers().to_vec();
+    if names.is_empty() {
+        return Ok(None);
+    }
+
+    let pyoxidizer_context_value = get_context(type_values)?;
+    let pyoxidizer_context = pyoxidizer_context_value
+        .downcast_ref::<PyOxidizerEnvironmentContext>()
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    let cache_dir = pyoxidizer_context
+        .env()
+        .cache_dir()
+        .join("compiled-resources");
+
+    ResourceCompilers::new(&names, Some(cache_dir))
+        .and_then(|compilers| compilers.compile(resource))
+        .map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "PYOXIDIZER_BUILD",
+                message: format!("{:?}", e),
+                label: label.to_string(),
+            })
+        })
+}
+
 pub fn python_resource_to_value(
     label: &str,
     type_values: &TypeValues,
@@ -260,6 +298,9 @@ pub fn python_resource_to_value(
     resource: &PythonResource,
     policy: &PythonPackagingPolicyValue,
 ) -> ValueResult {
+    let compiled = compile_resource(label, type_values, resource, policy)?;
+    let resource = compiled.as_ref().unwrap_or(resource);
+
     match resource {
         PythonResource::ModuleSource(sm) => {
             let mut m = PythonModuleSourceValue::new(sm.clone().into_owned());
@@ -0,0 +1,100 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Functionality for pinning the Rust toolchain used to build.
+
+Like Python distributions, the managed Rust toolchain is downloaded by
+PyOxidizer itself. Configuration files can pin its version so every machine
+building a project uses the same compiler and linker.
+*/
+
+use {
+    super::env::{get_context, PyOxidizerEnvironmentContext},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            none::NoneType,
+            Value, ValueResult,
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+};
+
+/// set_rust_toolchain(version)
+fn starlark_set_rust_toolchain(type_values: &TypeValues, version: String) -> ValueResult {
+    let pyoxidizer_context_value = get_context(type_values)?;
+    let mut pyoxidizer_context = pyoxidizer_context_value
+        .downcast_mut::<PyOxidizerEnvironmentContext>()?
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    pyoxidizer_context
+        .env_mut()
+        .set_rust_toolchain_version(&version)
+        .map_err(|e| {
+            ValueError::from(RuntimeError {
+                code: "PYOXIDIZER_RUST",
+                message: format!("{:?}", e),
+                label: "set_rust_toolchain()".to_string(),
+            })
+        })?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+starlark_module! { rust_toolchain_module =>
+    set_rust_toolchain(env env, version: String) {
+        starlark_set_rust_toolchain(env, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{environment::RUST_TOOLCHAIN_VERSION, starlark::testutil::*},
+        anyhow::Result,
+    };
+
+    fn toolchain_version(env: &crate::starlark::eval::EvaluationContext) -> Result<String> {
+        let pyoxidizer_context_value = env.pyoxidizer_context_value().unwrap();
+        let pyoxidizer_context = pyoxidizer_context_value
+            .downcast_ref::<PyOxidizerEnvironmentContext>()
+            .unwrap();
+
+        Ok(pyoxidizer_context
+            .env()
+            .rust_toolchain_version()
+            .to_string())
+    }
+
+    #[test]
+    fn test_set_rust_toolchain() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        assert_eq!(toolchain_version(&env)?, RUST_TOOLCHAIN_VERSION);
+
+        env.eval("set_rust_toolchain('1.58.1')")?;
+        assert_eq!(toolchain_version(&env)?, "1.58.1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_rust_toolchain_invalid() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        assert!(env.eval("set_rust_toolchain('stable')").is_err());
+        assert!(env.eval("set_rust_toolchain('1.58')").is_err());
+        assert!(env.eval("set_rust_toolchain('1.59.0-beta.1')").is_err());
+        assert!(env.eval("set_rust_toolchain('1.40.0')").is_err());
+        assert_eq!(toolchain_version(&env)?, RUST_TOOLCHAIN_VERSION);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,504 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Symbols defined by the Starlark dialect.
+
+The canonical description of every type, function, and attribute is the
+reStructuredText documentation, which carries typed signatures in its
+`py:*` directives. Rather than maintaining a second copy of this
+information, symbols are derived from the documentation compiled into
+the binary.
+*/
+
+use once_cell::sync::Lazy;
+
+/// Documentation describing PyOxidizer's Starlark dialect.
+const PYOXIDIZER_DOCS: &[&str] = &[
+    include_str!("../../docs/pyoxidizer_config_global_state.rst"),
+    include_str!("../../docs/pyoxidizer_config_globals.rst"),
+    include_str!("../../docs/pyoxidizer_config_target_management.rst"),
+    include_str!("../../docs/pyoxidizer_config_testing.rst"),
+    include_str!("../../docs/pyoxidizer_config_tugger_extensions.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_binary_post_processor.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_file.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_distribution.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_embedded_resources.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_executable.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_extension_module.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_interpreter_config.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_module_source.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_package_distribution_resource.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_package_resource.rst"),
+    include_str!("../../docs/pyoxidizer_config_type_python_packaging_policy.rst"),
+];
+
+/// Global functions and constants built into the Starlark language.
+pub const STARLARK_BUILTINS: &[&str] = &[
+    "False",
+    "None",
+    "True",
+    "all",
+    "any",
+    "bool",
+    "dict",
+    "dir",
+    "enumerate",
+    "fail",
+    "getattr",
+    "hasattr",
+    "hash",
+    "int",
+    "len",
+    "list",
+    "load",
+    "max",
+    "min",
+    "print",
+    "range",
+    "repr",
+    "reversed",
+    "sorted",
+    "str",
+    "tuple",
+    "type",
+    "zip",
+];
+
+/// Methods of the `string`, `list`, and `dict` types built into Starlark.
+pub const STARLARK_BUILTIN_METHODS: &[&str] = &[
+    "append",
+    "capitalize",
+    "clear",
+    "codepoints",
+    "count",
+    "elems",
+    "endswith",
+    "extend",
+    "find",
+    "format",
+    "get",
+    "index",
+    "insert",
+    "isalnum",
+    "isalpha",
+    "isdigit",
+    "islower",
+    "isspace",
+    "istitle",
+    "isupper",
+    "items",
+    "join",
+    "keys",
+    "lower",
+    "lstrip",
+    "partition",
+    "pop",
+    "popitem",
+    "remove",
+    "replace",
+    "rfind",
+    "rindex",
+    "rpartition",
+    "rsplit",
+    "rstrip",
+    "setdefault",
+    "split",
+    "splitlines",
+    "startswith",
+    "strip",
+    "title",
+    "update",
+    "upper",
+    "values",
+];
+
+/// The kind of a documented symbol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SymbolKind {
+    Type,
+    Function,
+    Constant,
+    Method,
+    Attribute,
+}
+
+/// A documented symbol in the Starlark dialect.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Symbol {
+    /// Name of the symbol.
+    ///
+    /// Attribute names may end with `*` to denote a family of attributes.
+    pub name: String,
+
+    pub kind: SymbolKind,
+
+    /// Name of the type this symbol is a member of.
+    pub owner: Option<String>,
+
+    /// Signature as written in the documentation. e.g. `foo(bar: str) -> str`.
+    pub signature: String,
+
+    /// Documentation text.
+    pub doc: String,
+}
+
+impl Symbol {
+    /// Whether this member symbol provides an attribute of a given name.
+    pub fn matches_attribute(&self, name: &str) -> bool {
+        if let Some(prefix) = self.name.strip_suffix('*') {
+            name.starts_with(prefix)
+        } else {
+            self.name == name
+        }
+    }
+
+    /// Render documentation of this symbol as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let signature = if let Some(owner) = &self.owner {
+            format!("{}.{}", owner, self.signature)
+        } else {
+            self.signature.clone()
+        };
+
+        if self.doc.is_empty() {
+            format!("```python\n{}\n```", signature)
+        } else {
+            format!("```python\n{}\n```\n\n{}", signature, self.doc)
+        }
+    }
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Join indented documentation lines into paragraphs.
+fn collect_doc(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| indent_of(l))
+        .min()
+        .unwrap_or(0);
+
+    let mut doc = String::new();
+    let mut blank = false;
+
+    for line in lines {
+        if line.trim().is_empty() {
+            blank = !doc.is_empty();
+            continue;
+        }
+
+        if blank {
+            doc.push_str("\n\n");
+        } else if !doc.is_empty() {
+            doc.push('\n');
+        }
+        blank = false;
+
+        doc.push_str(line[indent..].trim_end());
+    }
+
+    doc
+}
+
+/// Parse symbols from `py:*` directives in reStructuredText.
+fn parse_directives(rst: &str, symbols: &mut Vec<Symbol>) {
+    let lines = rst.lines().collect::<Vec<_>>();
+    let mut current_class: Option<(String, usize)> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let indent = indent_of(line);
+
+        if let Some((_, class_indent)) = &current_class {
+            if !line.trim().is_empty() && indent <= *class_indent {
+                current_class = None;
+            }
+        }
+
+        let (directive, value) = match line
+            .trim()
+            .strip_prefix(".. py:")
+            .and_then(|s| s.split_once("::"))
+        {
+            Some((directive, value)) => (directive, value.trim()),
+            None => continue,
+        };
+
+        let kind = match directive {
+            "class" => SymbolKind::Type,
+            "function" => SymbolKind::Function,
+            "method" => SymbolKind::Method,
+            "attribute" => SymbolKind::Attribute,
+            _ => continue,
+        };
+
+        // The body ends at the first nested directive or dedent.
+        let body = lines[i + 1..]
+            .iter()
+            .take_while(|l| l.trim().is_empty() || indent_of(l) > indent)
+            .take_while(|l| !l.trim().starts_with(".. py:"))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let name = value.split('(').next().unwrap_or_default().trim();
+        let name = name.rsplit('.').next().unwrap_or(name).to_string();
+
+        let owner = match kind {
+            SymbolKind::Type => {
+                current_class = Some((name.clone(), indent));
+                None
+            }
+            _ => current_class.as_ref().map(|(class, _)| class.clone()),
+        };
+
+        let kind = match (kind, &owner) {
+            (SymbolKind::Method, None) => SymbolKind::Function,
+            (kind, _) => kind,
+        };
+
+        symbols.push(Symbol {
+            name,
+            kind,
+            owner,
+            signature: value.to_string(),
+            doc: collect_doc(&body),
+        });
+    }
+}
+
+/// Parse symbols from section titles like ``` ``register_target()`` ```.
+///
+/// Some symbols are documented by sections rather than directives. Arguments
+/// are recovered from the definition list in the section body.
+fn parse_sections(rst: &str, symbols: &mut Vec<Symbol>) {
+    let lines = rst.lines().collect::<Vec<_>>();
+
+    let is_underline = |line: &str| {
+        line.len() > 2 && (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-'))
+    };
+
+    for i in 0..lines.len().saturating_sub(1) {
+        if !is_underline(lines[i + 1]) || (i > 0 && is_underline(lines[i - 1])) {
+            continue;
+        }
+
+        let title = match lines[i]
+            .strip_prefix("``")
+            .and_then(|s| s.strip_suffix("``"))
+        {
+            Some(title) => title,
+            None => continue,
+        };
+
+        let body = lines[i + 2..]
+            .iter()
+            .enumerate()
+            .take_while(|(j, l)| {
+                !l.starts_with(".. _")
+                    && !lines
+                        .get(i + 3 + j)
+                        .map(|next| is_underline(next))
+                        .unwrap_or(false)
+            })
+            .map(|(_, l)| *l)
+            .collect::<Vec<_>>();
+
+        let (path, kind) = if let Some(path) = title.strip_suffix("()") {
+            (path, SymbolKind::Function)
+        } else if title
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        {
+            (title, SymbolKind::Constant)
+        } else {
+            continue;
+        };
+
+        let (owner, name) = match path.split_once('.') {
+            Some((owner, name)) => (Some(owner.to_string()), name.to_string()),
+            None => (None, path.to_string()),
+        };
+
+        if symbols.iter().any(|s| s.name == name && s.owner == owner) {
+            continue;
+        }
+
+        let signature = if kind == SymbolKind::Function {
+            let args = body
+                .iter()
+                .filter_map(|l| l.strip_prefix("``").and_then(|l| l.strip_suffix("``")))
+                .filter(|arg| arg.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+                .collect::<Vec<_>>();
+
+            format!("{}({})", name, args.join(", "))
+        } else {
+            name.clone()
+        };
+
+        symbols.push(Symbol {
+            name,
+            kind: match (kind, &owner) {
+                (SymbolKind::Function, Some(_)) => SymbolKind::Method,
+                (kind, _) => kind,
+            },
+            owner,
+            signature,
+            doc: collect_doc(&body),
+        });
+    }
+}
+
+/// Parse the symbols documented in reStructuredText.
+pub fn parse_rst_symbols(rst: &str) -> Vec<Symbol> {
+    let mut symbols = vec![];
+    parse_directives(rst, &mut symbols);
+    parse_sections(rst, &mut symbols);
+
+    symbols
+}
+
+static SYMBOLS: Lazy<Vec<Symbol>> = Lazy::new(|| {
+    let mut symbols = PYOXIDIZER_DOCS
+        .iter()
+        .chain(tugger::starlark::STARLARK_DOCS.iter())
+        .flat_map(|rst| parse_rst_symbols(rst))
+        .collect::<Vec<_>>();
+
+    for plugin in super::plugin::registered_plugins() {
+        symbols.extend(plugin.docs().iter().flat_map(|rst| parse_rst_symbols(rst)));
+    }
+
+    symbols
+});
+
+/// All symbols documented by the Starlark dialect.
+///
+/// This includes symbols documented by plugins registered before first use.
+pub fn dialect_symbols() -> &'static [Symbol] {
+    &SYMBOLS
+}
+
+/// Documented global symbols with a given name.
+pub fn find_global(name: &str) -> Vec<&'static Symbol> {
+    dialect_symbols()
+        .iter()
+        .filter(|s| s.owner.is_none() && s.name == name)
+        .collect()
+}
+
+/// Documented type members providing an attribute of a given name.
+pub fn find_members(name: &str) -> Vec<&'static Symbol> {
+    dialect_symbols()
+        .iter()
+        .filter(|s| s.owner.is_some() && s.matches_attribute(name))
+        .collect()
+}
+
+/// Whether an attribute name is provided by any known type.
+pub fn is_known_attribute(name: &str) -> bool {
+    STARLARK_BUILTIN_METHODS.contains(&name) || !find_members(name).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RST: &str = "\
+.. py:currentmodule:: starlark_pyoxidizer
+
+.. py:class:: Thing
+
+    Represents a thing.
+
+    .. py:attribute:: name
+
+        (``string``)
+
+        Name of the thing.
+
+    .. py:method:: frob(count: int = 1) -> Thing
+
+        Frob the thing.
+
+        Returns a new thing.
+
+    .. py:attribute:: add_*
+
+        See elsewhere.
+
+.. py:function:: make_thing(name: str) -> Thing
+
+    Create a thing.
+
+.. _config_register_thing:
+
+``register_thing()``
+====================
+
+Registers a thing.
+
+``name``
+   (``string``) The name.
+
+``thing``
+   (``Thing``) The thing.
+
+``THING_COUNT``
+---------------
+
+Number of things.
+";
+
+    #[test]
+    fn test_parse_rst_symbols() {
+        let symbols = parse_rst_symbols(RST);
+
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|s| (s.name.as_str(), s.kind, s.owner.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Thing", SymbolKind::Type, None),
+                ("name", SymbolKind::Attribute, Some("Thing")),
+                ("frob", SymbolKind::Method, Some("Thing")),
+                ("add_*", SymbolKind::Attribute, Some("Thing")),
+                ("make_thing", SymbolKind::Function, None),
+                ("register_thing", SymbolKind::Function, None),
+                ("THING_COUNT", SymbolKind::Constant, None),
+            ]
+        );
+
+        assert_eq!(symbols[0].doc, "Represents a thing.");
+        assert_eq!(symbols[2].signature, "frob(count: int = 1) -> Thing");
+        assert_eq!(symbols[2].doc, "Frob the thing.\n\nReturns a new thing.");
+        assert_eq!(symbols[5].signature, "register_thing(name, thing)");
+        assert!(symbols[5].doc.starts_with("Registers a thing."));
+        assert_eq!(symbols[6].doc, "Number of things.");
+
+        assert!(symbols[3].matches_attribute("add_include"));
+        assert!(!symbols[1].matches_attribute("names"));
+    }
+
+    #[test]
+    fn test_dialect_symbols() {
+        assert!(!find_global("default_python_distribution").is_empty());
+        assert!(!find_global("register_target").is_empty());
+        assert!(!find_global("FileManifest").is_empty());
+        assert!(!find_global("BUILD_TARGET_TRIPLE").is_empty());
+        assert!(!find_global("BUILD_PROFILE").is_empty());
+
+        assert_eq!(
+            find_members("to_file_manifest")[0].owner.as_deref(),
+            Some("PythonExecutable")
+        );
+        assert!(is_known_attribute("add_python_resource"));
+        assert!(is_known_attribute("bytecode_optimize_level_zero"));
+        assert!(is_known_attribute("startswith"));
+        assert!(!is_known_attribute("not_a_real_attribute"));
+    }
+}
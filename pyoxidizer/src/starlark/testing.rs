@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Functionality for testing Starlark configuration files.
+
+Tests are Starlark functions whose names begin with `test_`. They are
+defined in a file evaluated after the configuration file and use the
+assertion functions defined here. Operations that would invoke `pip`
+can be replaced by mocks, so tests don't require network access or
+running Python.
+*/
+
+use {
+    super::env::{get_context, PyOxidizerEnvironmentContext},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            none::NoneType,
+            Value, ValueResult,
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{optional_str_arg, required_list_arg},
+    std::path::PathBuf,
+};
+
+/// Resources to return in place of running `pip`.
+#[derive(Clone, Debug)]
+pub struct PipMock {
+    /// Directory holding installed packages, as would be produced by `pip`.
+    pub path: PathBuf,
+
+    /// Names of packages to read from `path`.
+    pub packages: Vec<String>,
+}
+
+/// Find the names of test functions defined in Starlark source code.
+pub fn find_test_functions(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter_map(|line| line.strip_prefix("def "))
+        .filter_map(|line| line.split_once('('))
+        .map(|(name, _)| name.trim())
+        .filter(|name| name.starts_with("test_"))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn assertion_error(message: String, label: &str) -> ValueError {
+    ValueError::from(RuntimeError {
+        code: "PYOXIDIZER_ASSERTION",
+        message,
+        label: label.to_string(),
+    })
+}
+
+/// assert_eq(left, right, msg=None)
+fn starlark_assert_eq(left: &Value, right: &Value, msg: &Value) -> ValueResult {
+    let msg = optional_str_arg("msg", msg)?;
+
+    if left.equals(right)? {
+        Ok(Value::new(NoneType::None))
+    } else {
+        Err(assertion_error(
+            format!(
+                "{}{} != {}",
+                msg.map(|m| format!("{}: ", m)).unwrap_or_default(),
+                left.to_repr(),
+                right.to_repr()
+            ),
+            "assert_eq()",
+        ))
+    }
+}
+
+/// assert_true(value, msg=None)
+fn starlark_assert_true(value: &Value, msg: &Value) -> ValueResult {
+    let msg = optional_str_arg("msg", msg)?;
+
+    if value.to_bool() {
+        Ok(Value::new(NoneType::None))
+    } else {
+        Err(assertion_error(
+            msg.unwrap_or_else(|| format!("{} is not true", value.to_repr())),
+            "assert_true()",
+        ))
+    }
+}
+
+/// mock_pip(args, path, packages)
+fn starlark_mock_pip(
+    type_values: &TypeValues,
+    args: &Value,
+    path: String,
+    packages: &Value,
+) -> ValueResult {
+    required_list_arg("args", "string", args)?;
+    required_list_arg("packages", "string", packages)?;
+
+    let args = args
+        .iter()?
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    let packages = packages
+        .iter()?
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+
+    let pyoxidizer_context_value = get_context(type_values)?;
+    let mut pyoxidizer_context = pyoxidizer_context_value
+        .downcast_mut::<PyOxidizerEnvironmentContext>()?
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    if !pyoxidizer_context.test_mode {
+        return Err(ValueError::from(RuntimeError {
+            code: "PYOXIDIZER_TESTING",
+            message: "mock_pip() can only be called by `pyoxidizer test-config`".to_string(),
+            label: "mock_pip()".to_string(),
+        }));
+    }
+
+    let path = pyoxidizer_context.cwd.join(path);
+    pyoxidizer_context
+        .pip_mocks
+        .insert(args, PipMock { path, packages });
+
+    Ok(Value::new(NoneType::None))
+}
+
+starlark_module! { testing_module =>
+    assert_eq(left, right, msg=NoneType::None) {
+        starlark_assert_eq(&left, &right, &msg)
+    }
+
+    assert_true(value, msg=NoneType::None) {
+        starlark_assert_true(&value, &msg)
+    }
+
+    mock_pip(env env, args, path: String, packages) {
+        starlark_mock_pip(env, &args, path, &packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result};
+
+    #[test]
+    fn test_find_test_functions() {
+        assert_eq!(
+            find_test_functions(
+                "def make_exe():\n    pass\n\ndef test_exe():\n    pass\n\ndef test_policy( ):\n    pass\n"
+            ),
+            vec!["test_exe", "test_policy"]
+        );
+    }
+
+    #[test]
+    fn test_assertions() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        env.eval("assert_eq(1 + 1, 2)")?;
+        env.eval("assert_eq(['a'], ['a'], msg = 'lists')")?;
+        assert!(env.eval("assert_eq(1, 2)").is_err());
+
+        env.eval("assert_true(len('a') == 1)")?;
+        assert!(env.eval("assert_true([], msg = 'empty')").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_pip_requires_test_mode() -> Result<()> {
+        let mut env = test_evaluation_context_builder()?.into_context()?;
+
+        assert!(env
+            .eval("mock_pip(['foo'], 'site-packages', ['foo'])")
+            .is_err());
+
+        let mut env = test_evaluation_context_builder()?
+            .test_mode(true)
+            .into_context()?;
+
+        env.eval("mock_pip(['foo'], 'site-packages', ['foo'])")?;
+
+        Ok(())
+    }
+}
@@ -56,6 +56,16 @@ impl ToValue for Option<Vec<String>> {
     }
 }
 
+impl ToValue for Vec<PathBuf> {
+    fn to_value(&self) -> Value {
+        Value::from(
+            self.iter()
+                .map(|x| format!("{}", x.display()))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
 impl ToValue for Option<Vec<PathBuf>> {
     fn to_value(&self) -> Value {
         match self {
@@ -0,0 +1,299 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Produce update patches between builds of an application.
+
+An update patch transforms one build of a target into a newer build of the
+same target. Files that changed are stored as zstd frames compressed using
+the old file as a dictionary, so bytes shared with the old build cost
+almost nothing to transfer.
+
+The format is documented in [python_packaging::update_manifest]. Patches
+are applied by pyembed's `update_patch` module.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    python_packaging::update_manifest::{
+        render_update_manifest, UpdateEntry, UpdateOperation, UPDATE_MANIFEST_FILENAME,
+    },
+    sha2::Digest,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        path::{Path, PathBuf},
+    },
+};
+
+/// zstd compression level used for patches and added files.
+const COMPRESSION_LEVEL: i32 = 19;
+
+/// Largest zstd window log to use.
+///
+/// Windows of 1 GiB are the largest supported on 32-bit platforms.
+const MAX_WINDOW_LOG: u32 = 30;
+
+/// Describes an update patch that was written.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UpdatePatchSummary {
+    /// Files identical in both builds.
+    pub unchanged: usize,
+
+    /// Files stored in full.
+    pub added: usize,
+
+    /// Files stored as patches against the old build.
+    pub patched: usize,
+
+    /// Files only in the old build.
+    pub removed: usize,
+
+    /// Total size of files in the new build.
+    pub new_size: u64,
+
+    /// Total size of the update patch.
+    pub patch_size: u64,
+}
+
+/// Hex SHA-256 of data.
+fn sha256_hex(data: &[u8]) -> String {
+    sha2::Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Find files in a build directory, keyed by `/` delimited relative path.
+fn build_files(root: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    let mut files = BTreeMap::new();
+
+    for entry in walkdir::WalkDir::new(root) {
+        let entry = entry?;
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(root)?;
+        let key = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        files.insert(key, entry.path().to_path_buf());
+    }
+
+    Ok(files)
+}
+
+/// Compress data with zstd using another file's content as a dictionary.
+///
+/// Long distance matching and a window covering both inputs allow matches
+/// anywhere in the old content, as with `zstd --patch-from`.
+pub fn encode_patch(old: &[u8], new: &[u8]) -> Result<Vec<u8>> {
+    let largest = old.len().max(new.len()).max(1) as u64;
+    let window_log = (64 - (largest - 1).leading_zeros()).clamp(10, MAX_WINDOW_LOG);
+
+    let mut encoder = zstd::stream::Encoder::with_dictionary(vec![], COMPRESSION_LEVEL, old)?;
+    encoder.long_distance_matching(true)?;
+    encoder.window_log(window_log)?;
+    encoder.write_all(new)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Write an update patch transforming `old_dir` into `new_dir` to `out_dir`.
+///
+/// `out_dir` must not exist.
+pub fn create_update_patch(
+    old_dir: &Path,
+    new_dir: &Path,
+    out_dir: &Path,
+) -> Result<UpdatePatchSummary> {
+    if out_dir.exists() {
+        return Err(anyhow!("{} already exists", out_dir.display()));
+    }
+
+    let old_files = build_files(old_dir)?;
+    let new_files = build_files(new_dir)?;
+
+    if new_files.is_empty() {
+        return Err(anyhow!("no files found in {}", new_dir.display()));
+    }
+
+    let data_dir = out_dir.join("data");
+    std::fs::create_dir_all(&data_dir)
+        .with_context(|| format!("creating {}", data_dir.display()))?;
+
+    let mut summary = UpdatePatchSummary::default();
+    let mut entries = vec![];
+
+    let write_data = |path: &str, extension: &str, data: &[u8]| -> Result<u64> {
+        let dest = data_dir.join(format!("{}.{}", path, extension));
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating {}", parent.display()))?;
+        }
+        std::fs::write(&dest, data).with_context(|| format!("writing {}", dest.display()))?;
+
+        Ok(data.len() as u64)
+    };
+
+    let paths = old_files
+        .keys()
+        .chain(new_files.keys())
+        .collect::<BTreeSet<_>>();
+
+    for path in paths {
+        let old = old_files
+            .get(path)
+            .map(|p| std::fs::read(p).with_context(|| format!("reading {}", p.display())))
+            .transpose()?;
+        let old_sha256 = old.as_deref().map(sha256_hex);
+
+        let new_path = if let Some(p) = new_files.get(path) {
+            p
+        } else {
+            summary.removed += 1;
+            entries.push(UpdateEntry {
+                operation: UpdateOperation::Remove,
+                old_sha256,
+                new_sha256: None,
+                new_size: None,
+                executable: false,
+                path: path.to_string(),
+            });
+            continue;
+        };
+
+        let new =
+            std::fs::read(new_path).with_context(|| format!("reading {}", new_path.display()))?;
+        let new_sha256 = sha256_hex(&new);
+        let executable = is_executable(&std::fs::metadata(new_path)?);
+        summary.new_size += new.len() as u64;
+
+        // A patch against old content that has nothing in common with the new
+        // content is about as large as compressing the new content alone, so
+        // existing files are always patched.
+        let operation = if old_sha256.as_deref() == Some(new_sha256.as_str()) {
+            summary.unchanged += 1;
+            UpdateOperation::Unchanged
+        } else if let Some(old) = &old {
+            summary.patched += 1;
+            summary.patch_size += write_data(path, "zstpatch", &encode_patch(old, &new)?)?;
+            UpdateOperation::Patch
+        } else {
+            summary.added += 1;
+            summary.patch_size += write_data(
+                path,
+                "zst",
+                &zstd::stream::encode_all(new.as_slice(), COMPRESSION_LEVEL)?,
+            )?;
+            UpdateOperation::Add
+        };
+
+        entries.push(UpdateEntry {
+            operation,
+            old_sha256,
+            new_sha256: Some(new_sha256),
+            new_size: Some(new.len() as u64),
+            executable,
+            path: path.to_string(),
+        });
+    }
+
+    let manifest = render_update_manifest(&entries);
+    let manifest_path = out_dir.join(UPDATE_MANIFEST_FILENAME);
+    std::fs::write(&manifest_path, &manifest)
+        .with_context(|| format!("writing {}", manifest_path.display()))?;
+    summary.patch_size += manifest.len() as u64;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, python_packaging::update_manifest::UPDATE_MANIFEST_HEADER};
+
+    #[test]
+    fn test_encode_patch() -> Result<()> {
+        let old = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect::<Vec<_>>();
+        let mut new = old.clone();
+        new[50_000..50_010].copy_from_slice(b"0123456789");
+
+        let patch = encode_patch(&old, &new)?;
+        let compressed = zstd::stream::encode_all(new.as_slice(), COMPRESSION_LEVEL)?;
+
+        assert!(patch.len() < 1000);
+        assert!(patch.len() < compressed.len());
+
+        let mut decoder = zstd::stream::Decoder::with_dictionary(patch.as_slice(), &old)?;
+        decoder.window_log_max(MAX_WINDOW_LOG)?;
+        let mut decoded = vec![];
+        std::io::Read::read_to_end(&mut decoder, &mut decoded)?;
+        assert_eq!(decoded, new);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_update_patch() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let old_dir = temp_dir.path().join("old");
+        let new_dir = temp_dir.path().join("new");
+        let out_dir = temp_dir.path().join("patch");
+
+        let exe = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect::<Vec<_>>();
+
+        std::fs::create_dir_all(old_dir.join("lib"))?;
+        std::fs::create_dir_all(new_dir.join("lib"))?;
+        std::fs::write(old_dir.join("app"), &exe)?;
+        std::fs::write(new_dir.join("app"), [exe.as_slice(), b"v2"].concat())?;
+        std::fs::write(old_dir.join("lib/same.txt"), b"same")?;
+        std::fs::write(new_dir.join("lib/same.txt"), b"same")?;
+        std::fs::write(old_dir.join("removed.txt"), b"removed")?;
+        std::fs::write(new_dir.join("lib/new.txt"), b"new")?;
+
+        let summary = create_update_patch(&old_dir, &new_dir, &out_dir)?;
+
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(summary.patched, 1);
+        assert_eq!(summary.added, 1);
+        assert_eq!(summary.removed, 1);
+        assert!(summary.patch_size < summary.new_size / 10);
+
+        let manifest = std::fs::read_to_string(out_dir.join(UPDATE_MANIFEST_FILENAME))?;
+        let lines = manifest.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], UPDATE_MANIFEST_HEADER);
+        assert!(lines[1].starts_with("patch ") && lines[1].ends_with(" app"));
+        assert!(lines[2].starts_with("add - ") && lines[2].ends_with(" 3 - lib/new.txt"));
+        assert!(lines[3].starts_with("unchanged "));
+        assert!(lines[4].starts_with("remove ") && lines[4].ends_with(" - - - removed.txt"));
+
+        assert!(out_dir.join("data/app.zstpatch").exists());
+        assert!(out_dir.join("data/lib/new.txt.zst").exists());
+
+        // Refuse to overwrite an existing patch.
+        assert!(create_update_patch(&old_dir, &new_dir, &out_dir).is_err());
+
+        Ok(())
+    }
+}
@@ -24,7 +24,9 @@ use {
         python_resources::{
             pyobject_to_resource, ModuleFlavor, OxidizedResource, PythonResourcesState,
         },
+        resource_descriptor::{find_resources, OxidizedResourceDescriptor, ResourceFilter},
         resource_reader::OxidizedResourceReader,
+        resource_scanning::interpreter_module_suffixes,
         OXIDIZED_IMPORTER_NAME_STR,
     },
     pyo3::{
@@ -35,7 +37,10 @@ use {
         AsPyPointer, FromPyPointer, PyGCProtocol, PyNativeType, PyTraverseError, PyVisit,
     },
     python_packaging::resource::BytecodeOptimizationLevel,
-    std::sync::Arc,
+    std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
 };
 
 #[cfg(windows)]
@@ -241,6 +246,33 @@ fn load_dynamic_library(
     }
 }
 
+/// Register the source of a module with `linecache`.
+///
+/// A lazy entry calling `finder.get_source()` is registered, so source is only
+/// resolved when requested. Existing entries are kept.
+fn register_linecache_source(
+    py: Python,
+    finder: &PyAny,
+    filename: &Path,
+    name: &str,
+) -> PyResult<()> {
+    let cache = py
+        .import("linecache")?
+        .getattr("cache")?
+        .cast_as::<PyDict>()?;
+    let filename = filename.display().to_string();
+
+    if cache.get_item(&filename).is_none() {
+        let get_lines = py
+            .import("functools")?
+            .getattr("partial")?
+            .call1((finder.getattr("get_source")?, name))?;
+        cache.set_item(filename, (get_lines,))?;
+    }
+
+    Ok(())
+}
+
 /// Holds state for the custom MetaPathFinder.
 pub struct ImporterState {
     /// `imp` Python module.
@@ -271,6 +303,22 @@ pub struct ImporterState {
     pub(crate) multiprocessing_set_start_method: Option<String>,
     /// Whether to automatically register ourself with `pkg_resources` when it is imported.
     pub(crate) pkg_resources_import_auto_register: bool,
+    /// Directory `install_to_user_site()` installs packages to.
+    ///
+    /// If `None`, installing packages at run-time is disabled.
+    pub(crate) user_site_packages: Option<PathBuf>,
+    /// Whether to import modules in a way compatible with `coverage.py`.
+    ///
+    /// Modules having source are compiled from source, so code objects have the
+    /// path of the source file as their filename. Modules whose source is in
+    /// memory get a synthetic path as `__file__`.
+    pub(crate) coverage_mode: bool,
+    /// Whether to support debuggers like `pdb` and `debugpy`.
+    ///
+    /// Like [Self::coverage_mode], code objects get the path of the source file
+    /// as their filename. In addition, the source of modules imported from memory
+    /// is registered with `linecache`.
+    pub(crate) debugger_support: bool,
     /// Holds state about importable resources.
     ///
     /// This field is a PyCapsule and is a glorified wrapper around
@@ -389,6 +437,9 @@ impl ImporterState {
             multiprocessing_set_start_method: None,
             // TODO value should come from config.
             pkg_resources_import_auto_register: true,
+            user_site_packages: None,
+            coverage_mode: false,
+            debugger_support: false,
             resources_state: capsule,
         })
     }
@@ -448,6 +499,29 @@ impl ImporterState {
     pub fn set_multiprocessing_set_start_method(&mut self, value: Option<String>) {
         self.multiprocessing_set_start_method = value;
     }
+
+    /// Set the directory `install_to_user_site()` installs packages to.
+    #[allow(unused)]
+    pub fn set_user_site_packages(&mut self, value: Option<PathBuf>) {
+        self.user_site_packages = value;
+    }
+
+    /// Set whether to import modules in a way compatible with `coverage.py`.
+    #[allow(unused)]
+    pub fn set_coverage_mode(&mut self, value: bool) {
+        self.coverage_mode = value;
+    }
+
+    /// Set whether to support debuggers like `pdb` and `debugpy`.
+    #[allow(unused)]
+    pub fn set_debugger_support(&mut self, value: bool) {
+        self.debugger_support = value;
+    }
+
+    /// Whether code objects get the path of the source file as their filename.
+    pub(crate) fn source_filenames(&self) -> bool {
+        self.coverage_mode || self.debugger_support
+    }
 }
 
 impl Drop for ImporterState {
@@ -543,7 +617,9 @@ impl OxidizedFinder {
                 py,
                 finder.state.module_spec_type.clone_ref(py).into_ref(py),
                 slf,
+                path,
                 finder.state.optimize_level,
+                finder.state.source_filenames(),
             ),
             ModuleFlavor::Builtin => {
                 // BuiltinImporter.find_spec() always returns None if `path` is defined.
@@ -659,12 +735,32 @@ impl OxidizedFinder {
         if let Some(bytecode) = entry.resolve_bytecode(
             py,
             state.optimize_level,
+            state.source_filenames(),
             state.decode_source.as_ref(py),
             state.io_module.as_ref(py),
         )? {
             let code = state.marshal_loads.call(py, (bytecode,), None)?;
             let dict = module.getattr("__dict__")?;
 
+            // Paths derived from a synthetic `__file__` must resolve to package data.
+            state
+                .get_resources_state()
+                .extract_package_data(&key)
+                .map_err(|e| {
+                    PyImportError::new_err((
+                        format!("error extracting data of package of {}: {}", key, e),
+                        key.clone(),
+                    ))
+                })?;
+
+            // Debuggers obtain source from `linecache` by filename alone. Source
+            // of modules imported from memory can't be read from the filesystem.
+            if state.debugger_support && entry.is_source_in_memory() {
+                if let Some(path) = entry.source_path() {
+                    register_linecache_source(py, slf, &path, &key)?;
+                }
+            }
+
             state
                 .call_with_frames_removed
                 .call(py, (&state.exec_fn, code, dict), None)
@@ -748,6 +844,7 @@ impl OxidizedFinder {
         if let Some(bytecode) = module.resolve_bytecode(
             py,
             state.optimize_level,
+            state.source_filenames(),
             state.decode_source.as_ref(py),
             state.io_module.as_ref(py),
         )? {
@@ -810,7 +907,7 @@ impl OxidizedFinder {
             .ok_or_else(|| make_error("unknown module"))?;
 
         module
-            .resolve_origin(slf.py())
+            .resolve_origin(slf.py(), state.source_filenames())
             .map_err(|_| make_error("unable to resolve origin"))?
             .ok_or_else(|| make_error("no origin"))
     }
@@ -907,10 +1004,24 @@ impl OxidizedFinder {
 
     // Additional methods provided for convenience.
 
-    /// OxidizedFinder.__new__(relative_path_origin=None))
+    /// OxidizedFinder.__new__(relative_path_origin=None, coverage_mode=False,
+    /// debugger_support=False, dunder_file_root=None, dunder_file_packages=None))
     #[new]
-    #[args(relative_path_origin = "None")]
-    fn new(py: Python, relative_path_origin: Option<&PyAny>) -> PyResult<Self> {
+    #[args(
+        relative_path_origin = "None",
+        coverage_mode = "false",
+        debugger_support = "false",
+        dunder_file_root = "None",
+        dunder_file_packages = "None"
+    )]
+    fn new(
+        py: Python,
+        relative_path_origin: Option<&PyAny>,
+        coverage_mode: bool,
+        debugger_support: bool,
+        dunder_file_root: Option<&PyAny>,
+        dunder_file_packages: Option<Vec<String>>,
+    ) -> PyResult<Self> {
         // We need to obtain an ImporterState instance. This requires handles on a
         // few items...
 
@@ -926,13 +1037,25 @@ impl OxidizedFinder {
             resources_state.set_origin(pyobject_to_pathbuf(py, py_origin)?);
         }
 
+        match (dunder_file_root, dunder_file_packages) {
+            (Some(root), packages) => resources_state.set_dunder_file_packages(
+                pyobject_to_pathbuf(py, root)?,
+                packages.unwrap_or_default(),
+            ),
+            (None, Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "dunder_file_packages requires dunder_file_root",
+                ))
+            }
+            (None, None) => {}
+        }
+
+        let mut state = ImporterState::new(py, m, bootstrap_module, resources_state)?;
+        state.set_coverage_mode(coverage_mode);
+        state.set_debugger_support(debugger_support);
+
         Ok(OxidizedFinder {
-            state: Arc::new(ImporterState::new(
-                py,
-                m,
-                bootstrap_module,
-                resources_state,
-            )?),
+            state: Arc::new(state),
         })
     }
 
@@ -968,6 +1091,24 @@ impl OxidizedFinder {
         Ok(self.state.pkg_resources_import_auto_register)
     }
 
+    #[getter]
+    fn user_site_packages<'p>(&self, py: Python<'p>) -> Option<&'p PyAny> {
+        self.state
+            .user_site_packages
+            .as_ref()
+            .map(|path| path.as_path().into_py(py).into_ref(py))
+    }
+
+    #[getter]
+    fn coverage_mode(&self) -> bool {
+        self.state.coverage_mode
+    }
+
+    #[getter]
+    fn debugger_support(&self) -> bool {
+        self.state.debugger_support
+    }
+
     fn path_hook(slf: &PyCell<Self>, path: &PyAny) -> PyResult<OxidizedPathEntryFinder> {
         Self::path_hook_inner(slf, path).map_err(|inner| {
             let err = PyImportError::new_err("error running OxidizedFinder.path_hook");
@@ -1004,6 +1145,18 @@ impl OxidizedFinder {
         Ok(())
     }
 
+    fn index_directory(&self, py: Python, path: &PyAny) -> PyResult<()> {
+        let path = pyobject_to_pathbuf(py, path)?;
+        let (cache_tag, suffixes) = interpreter_module_suffixes(py)?;
+
+        self.state
+            .get_resources_state_mut()
+            .index_directory(&path, &cache_tag, &suffixes)
+            .map_err(PyValueError::new_err)?;
+
+        Ok(())
+    }
+
     fn index_interpreter_builtins(&self) -> PyResult<()> {
         self.state
             .get_resources_state_mut()
@@ -1037,6 +1190,18 @@ impl OxidizedFinder {
         resources_state.resources_as_py_list(py)
     }
 
+    #[args(package = "None", kind = "None", location = "None")]
+    fn find_resources(
+        &self,
+        package: Option<String>,
+        kind: Option<String>,
+        location: Option<String>,
+    ) -> PyResult<Vec<OxidizedResourceDescriptor>> {
+        let filter = ResourceFilter::new(package, kind, location)?;
+
+        Ok(find_resources(self.state.clone(), &filter))
+    }
+
     fn add_resource(&self, resource: &OxidizedResource) -> PyResult<()> {
         let resources_state = self.state.get_resources_state_mut();
 
@@ -17,8 +17,10 @@ mod pkg_resources;
 mod python_resource_collector;
 mod python_resource_types;
 mod python_resources;
+mod resource_descriptor;
 mod resource_reader;
 mod resource_scanning;
+mod user_site;
 #[cfg(feature = "zipimport")]
 #[allow(clippy::needless_option_as_deref)]
 mod zip_import;
@@ -186,6 +188,7 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
 
     crate::pkg_resources::init_module(m)?;
     crate::resource_scanning::init_module(m)?;
+    crate::user_site::init_module(m)?;
 
     m.add_function(wrap_pyfunction!(decode_source, m)?)?;
     m.add_function(wrap_pyfunction!(register_pkg_resources, m)?)?;
@@ -193,6 +196,7 @@ fn module_init(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<crate::package_metadata::OxidizedDistribution>()?;
     m.add_class::<OxidizedFinder>()?;
     m.add_class::<OxidizedResource>()?;
+    m.add_class::<crate::resource_descriptor::OxidizedResourceDescriptor>()?;
     m.add_class::<crate::python_resource_collector::OxidizedResourceCollector>()?;
     m.add_class::<OxidizedResourceReader>()?;
     m.add_class::<OxidizedPathEntryFinder>()?;
@@ -4,12 +4,13 @@
 
 use {
     crate::{
+        conversion::path_to_pathlib_path,
         importer::ImporterState,
         pkg_resources::create_oxidized_pkg_resources_provider,
         python_resources::{name_at_package_hierarchy, name_within_package_hierarchy},
     },
     pyo3::{
-        exceptions::{PyIOError, PyNotImplementedError, PyValueError},
+        exceptions::{PyIOError, PyValueError},
         prelude::*,
         types::{PyBytes, PyDict, PyList, PyString, PyTuple, PyType},
     },
@@ -34,6 +35,24 @@ fn discover_resolvers(py: Python) -> PyResult<&PyList> {
     Ok(PyList::new(py, resolvers))
 }
 
+// Emulates importlib.metadata.Prepared.normalize().
+fn normalize_distribution_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+
+    // Runs of `-`, `_` and `.` are replaced by a single `_`.
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('_') {
+                normalized.push('_');
+            }
+        } else {
+            normalized.extend(c.to_lowercase());
+        }
+    }
+
+    normalized
+}
+
 /// A importlib.metadata.Distribution allowing access to package distribution data.
 #[pyclass(module = "oxidized_importer")]
 pub(crate) struct OxidizedDistribution {
@@ -174,6 +193,26 @@ impl OxidizedDistribution {
         email.getattr("message_from_bytes")?.call((data,), None)
     }
 
+    #[getter]
+    fn name<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let metadata = self.metadata(py)?;
+
+        metadata.get_item("Name")
+    }
+
+    /// The name of the distribution normalized for comparisons.
+    ///
+    /// `importlib.metadata.entry_points()` uses this to deduplicate
+    /// distributions.
+    #[getter(_normalized_name)]
+    fn normalized_name(&self, py: Python) -> PyResult<String> {
+        let name = self.name(py)?.extract::<Option<String>>()?;
+
+        Ok(normalize_distribution_name(
+            name.as_deref().unwrap_or(&self.package),
+        ))
+    }
+
     #[getter]
     fn version<'p>(self_: PyRef<Self>, py: Python<'p>) -> PyResult<&'p PyAny> {
         let metadata = self_.metadata(py)?;
@@ -182,19 +221,114 @@ impl OxidizedDistribution {
     }
 
     #[getter]
-    fn entry_points<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+    fn entry_points<'p>(self_: PyRef<Self>, py: Python<'p>) -> PyResult<&'p PyAny> {
         let importlib_metadata = py.import("importlib.metadata")?;
 
-        let entry_point = importlib_metadata.getattr("EntryPoint")?;
+        let text = self_.read_text(py, "entry_points.txt".into())?;
 
-        let text = self.read_text(py, "entry_points.txt".into())?;
+        // Python 3.10+ binds entry points to their distribution.
+        if let Ok(entry_points) = importlib_metadata.getattr("EntryPoints") {
+            if entry_points.hasattr("_from_text_for")? {
+                return entry_points.call_method("_from_text_for", (text, self_), None);
+            }
+        }
+
+        let entry_point = importlib_metadata.getattr("EntryPoint")?;
 
         entry_point.call_method("_from_text", (text,), None)
     }
 
+    /// Return the files of this distribution.
+    ///
+    /// Files are read from `RECORD` or, for egg-info distributions,
+    /// `SOURCES.txt`. If neither exists, `RECORD` is synthesized from the
+    /// resources of the distribution.
     #[getter]
-    fn files(&self) -> PyResult<()> {
-        Err(PyNotImplementedError::new_err(()))
+    fn files<'p>(self_: PyRef<Self>, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let record = self_.read_text(py, "RECORD".into())?;
+        let sources = self_.read_text(py, "SOURCES.txt".into())?;
+
+        let lines = if !record.is_none() {
+            record
+                .call_method0("splitlines")?
+                .extract::<Vec<String>>()?
+        } else if !sources.is_none() {
+            sources
+                .call_method0("splitlines")?
+                .extract::<Vec<String>>()?
+                .into_iter()
+                .map(|line| format!("\"{}\"", line))
+                .collect::<Vec<_>>()
+        } else {
+            let metadata = self_.metadata(py)?;
+            let name = metadata
+                .get_item("Name")?
+                .extract::<Option<String>>()?
+                .unwrap_or_else(|| self_.package.clone());
+            let version = metadata
+                .get_item("Version")?
+                .extract::<Option<String>>()?
+                .unwrap_or_default();
+
+            let suffix = if self_.read_text(py, "METADATA".into())?.is_none() {
+                "egg-info"
+            } else {
+                "dist-info"
+            };
+            let dist_info = format!("{}-{}.{}", name.replace('-', "_"), version, suffix);
+
+            self_
+                .state
+                .get_resources_state()
+                .synthesize_package_distribution_record(&self_.package, &dist_info)
+                .map_err(|e| PyIOError::new_err(format!("error synthesizing RECORD: {}", e)))?
+        };
+
+        let importlib_metadata = py.import("importlib.metadata")?;
+        let package_path = importlib_metadata.getattr("PackagePath")?;
+        let file_hash = importlib_metadata.getattr("FileHash")?;
+        let reader = py.import("csv")?.getattr("reader")?.call((lines,), None)?;
+
+        let dist: PyObject = self_.into_py(py);
+        let files = PyList::empty(py);
+
+        for row in reader.iter()? {
+            let mut fields = row?.extract::<Vec<String>>()?.into_iter();
+
+            let path = match fields.next() {
+                Some(path) => package_path.call((path,), None)?,
+                None => continue,
+            };
+
+            let hash = match fields.next().filter(|hash| !hash.is_empty()) {
+                Some(hash) => file_hash.call((hash,), None)?,
+                None => py.None().into_ref(py),
+            };
+            let size = fields
+                .next()
+                .filter(|size| !size.is_empty())
+                .map(|size| size.parse::<u64>())
+                .transpose()
+                .map_err(|e| PyValueError::new_err(format!("invalid file size: {}", e)))?;
+
+            path.setattr("hash", hash)?;
+            path.setattr("size", size)?;
+            path.setattr("dist", &dist)?;
+
+            files.append(path)?;
+        }
+
+        Ok(files.into())
+    }
+
+    /// Resolve the path of a file of this distribution.
+    ///
+    /// Paths are relative to the directory filesystem-relative resources are
+    /// installed in. Files imported from memory don't exist there.
+    fn locate_file<'p>(&self, py: Python<'p>, path: &PyAny) -> PyResult<&'p PyAny> {
+        let origin = path_to_pathlib_path(py, self.state.get_resources_state().origin())?;
+
+        origin.call_method("joinpath", (path,), None)
     }
 
     #[getter]
@@ -242,12 +376,8 @@ pub(crate) fn find_distributions<'p>(
         .package_distribution_names(|match_name| {
             if let Some(name) = name {
                 // Python normalizes the name. We do the same.
-                let name = name.to_string();
-                let name = name.to_lowercase().replace('-', "_");
-
-                let match_name = match_name.to_lowercase().replace('-', "_");
-
-                match_name == name
+                normalize_distribution_name(&name.to_string())
+                    == normalize_distribution_name(match_name)
             } else {
                 true
             }
@@ -10,7 +10,7 @@ use {
     crate::conversion::{
         path_to_pathlib_path, pyobject_optional_resources_map_to_owned_bytes,
         pyobject_optional_resources_map_to_pathbuf, pyobject_to_owned_bytes_optional,
-        pyobject_to_pathbuf_optional,
+        pyobject_to_pathbuf, pyobject_to_pathbuf_optional,
     },
     anyhow::Result,
     pyo3::{
@@ -22,20 +22,48 @@ use {
         types::{PyBytes, PyDict, PyList, PyString, PyTuple},
         PyObjectProtocol,
     },
-    python_packaging::resource::BytecodeOptimizationLevel,
+    python_packaging::{
+        filesystem_scanning::find_python_resources,
+        module_util::PythonModuleSuffixes,
+        resource::{BytecodeOptimizationLevel, PythonResource},
+    },
     python_packed_resources::Resource,
     std::{
         borrow::Cow,
         cell::RefCell,
-        collections::{hash_map::Entry, BTreeSet, HashMap},
+        collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap},
         ffi::CStr,
         os::raw::c_int,
         path::{Path, PathBuf},
+        sync::Mutex,
     },
 };
 
 const ENOENT: c_int = 2;
 
+/// Optimization levels whose bytecode can be served at an optimization level.
+///
+/// Bytecode of the level itself comes first. Modules lacking it are served
+/// bytecode of the closest lower level, which only differs by keeping
+/// asserts or docstrings. So packages can be packaged at all levels while
+/// others are packaged at level 0 only.
+fn serving_optimize_levels(
+    optimize_level: BytecodeOptimizationLevel,
+) -> &'static [BytecodeOptimizationLevel] {
+    match optimize_level {
+        BytecodeOptimizationLevel::Zero => &[BytecodeOptimizationLevel::Zero],
+        BytecodeOptimizationLevel::One => &[
+            BytecodeOptimizationLevel::One,
+            BytecodeOptimizationLevel::Zero,
+        ],
+        BytecodeOptimizationLevel::Two => &[
+            BytecodeOptimizationLevel::Two,
+            BytecodeOptimizationLevel::One,
+            BytecodeOptimizationLevel::Zero,
+        ],
+    }
+}
+
 /// Determines whether an entry represents an importable Python module.
 ///
 /// Should only be called on module flavors.
@@ -45,17 +73,22 @@ where
 {
     entry.in_memory_source.is_some()
         || entry.relative_path_module_source.is_some()
-        || match optimize_level {
-            BytecodeOptimizationLevel::Zero => {
-                entry.in_memory_bytecode.is_some() || entry.relative_path_module_bytecode.is_some()
-            }
-            BytecodeOptimizationLevel::One => {
-                entry.in_memory_bytecode_opt1.is_some() || entry.in_memory_bytecode_opt1.is_some()
-            }
-            BytecodeOptimizationLevel::Two => {
-                entry.in_memory_bytecode_opt2.is_some() || entry.in_memory_bytecode_opt2.is_some()
-            }
-        }
+        || serving_optimize_levels(optimize_level)
+            .iter()
+            .any(|level| match level {
+                BytecodeOptimizationLevel::Zero => {
+                    entry.in_memory_bytecode.is_some()
+                        || entry.relative_path_module_bytecode.is_some()
+                }
+                BytecodeOptimizationLevel::One => {
+                    entry.in_memory_bytecode_opt1.is_some()
+                        || entry.relative_path_module_bytecode_opt1.is_some()
+                }
+                BytecodeOptimizationLevel::Two => {
+                    entry.in_memory_bytecode_opt2.is_some()
+                        || entry.relative_path_module_bytecode_opt2.is_some()
+                }
+            })
 }
 
 /// Whether a resource name matches a package target.
@@ -95,6 +128,27 @@ pub(crate) fn name_within_package_hierarchy(fullname: &str, package_target: Opti
     }
 }
 
+/// Write a file unless it already has the given content.
+///
+/// Content is written to a temporary file which is then renamed, so
+/// concurrent readers never observe partially written files.
+fn write_if_changed(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if std::fs::read(path).map_or(false, |existing| existing == data) {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, path)
+}
+
 /// Describes the type of an importable Python module.
 #[derive(Debug, PartialEq)]
 pub enum ModuleFlavor {
@@ -121,6 +175,11 @@ where
     /// Path from which relative paths should be interpreted.
     origin: &'a Path,
 
+    /// Directory holding the extracted data of this module's package.
+    ///
+    /// Set if in-memory modules of the package get a synthetic `__file__`.
+    data_root: Option<&'a Path>,
+
     /// The type of importable module.
     pub flavor: ModuleFlavor,
     /// Whether this module is a package.
@@ -174,18 +233,78 @@ impl<'a> ImportablePythonModule<'a, u8> {
     /// (e.g. the case of frozen modules).
     ///
     /// The returned `PyObject` will be an instance of `memoryview`.
+    ///
+    /// If `source_filenames` is set, bytecode is compiled from source, if available,
+    /// so the filename of code objects is [Self::source_path()].
+    ///
+    /// Bytecode at `optimize_level` is preferred. Without it, bytecode is
+    /// compiled from source, if available, or bytecode of the closest lower
+    /// optimization level is returned.
     pub fn resolve_bytecode(
         &mut self,
         py: Python,
         optimize_level: BytecodeOptimizationLevel,
+        source_filenames: bool,
         decode_source: &PyAny,
         io_module: &PyModule,
     ) -> PyResult<Option<Py<PyAny>>> {
-        if let Some(data) = match optimize_level {
+        if source_filenames {
+            if let Some(path) = self.source_path() {
+                return self.compile_source(
+                    py,
+                    &path.display().to_string(),
+                    decode_source,
+                    io_module,
+                );
+            }
+        }
+
+        if self.has_bytecode(optimize_level) {
+            return self.bytecode_at_level(py, optimize_level);
+        }
+
+        if let Some(code) =
+            self.compile_source(py, &self.resource.name, decode_source, io_module)?
+        {
+            return Ok(Some(code));
+        }
+
+        for level in serving_optimize_levels(optimize_level).iter().skip(1) {
+            if self.has_bytecode(*level) {
+                return self.bytecode_at_level(py, *level);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether bytecode at an optimization level is available.
+    fn has_bytecode(&self, optimize_level: BytecodeOptimizationLevel) -> bool {
+        self.in_memory_bytecode(optimize_level).is_some()
+            || self.bytecode_path(optimize_level).is_some()
+    }
+
+    /// In-memory bytecode at an optimization level.
+    fn in_memory_bytecode(
+        &self,
+        optimize_level: BytecodeOptimizationLevel,
+    ) -> &'a Option<Cow<'a, [u8]>> {
+        match optimize_level {
             BytecodeOptimizationLevel::Zero => &self.resource.in_memory_bytecode,
             BytecodeOptimizationLevel::One => &self.resource.in_memory_bytecode_opt1,
             BytecodeOptimizationLevel::Two => &self.resource.in_memory_bytecode_opt2,
-        } {
+        }
+    }
+
+    /// Resolve bytecode at an optimization level.
+    ///
+    /// Returns `Ok(None)` if there is no bytecode at this level.
+    fn bytecode_at_level(
+        &self,
+        py: Python,
+        optimize_level: BytecodeOptimizationLevel,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        if let Some(data) = self.in_memory_bytecode(optimize_level) {
             let ptr = unsafe {
                 pyffi::PyMemoryView_FromMemory(
                     data.as_ptr() as _,
@@ -221,13 +340,29 @@ impl<'a> ImportablePythonModule<'a, u8> {
 
             // First 16 bytes of .pyc files are a header.
             Ok(Some(PyBytes::new(py, &bytecode[16..]).into_py(py)))
-        } else if let Some(source) = self.resolve_source(py, decode_source, io_module)? {
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Compile the source code behind this module to bytecode.
+    ///
+    /// `filename` becomes the filename of the compiled code objects. Returns
+    /// `Ok(None)` if there is no source.
+    fn compile_source(
+        &self,
+        py: Python,
+        filename: &str,
+        decode_source: &PyAny,
+        io_module: &PyModule,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        if let Some(source) = self.resolve_source(py, decode_source, io_module)? {
             let builtins = py.import("builtins")?;
             let marshal = py.import("marshal")?;
 
             let code = builtins
                 .getattr("compile")?
-                .call((source, self.resource.name.as_ref(), "exec"), None)?;
+                .call((source, filename, "exec"), None)?;
             let bytecode = marshal.getattr("dumps")?.call((code,), None)?;
 
             Ok(Some(bytecode.into_py(py)))
@@ -242,7 +377,9 @@ impl<'a> ImportablePythonModule<'a, u8> {
         py: Python,
         module_spec_type: &'p PyAny,
         loader: &PyAny,
+        path: &PyAny,
         optimize_level: BytecodeOptimizationLevel,
+        source_filenames: bool,
     ) -> PyResult<&'p PyAny> {
         let name = PyString::new(py, &self.resource.name);
 
@@ -251,9 +388,13 @@ impl<'a> ImportablePythonModule<'a, u8> {
 
         // If we pass `origin=` and set `spec.has_location = True`, `__file__`
         // will be set on the module. This is appropriate for modules backed by
-        // the filesystem.
+        // the filesystem. Namespace packages don't have a location.
 
-        let origin = self.resolve_origin(py)?;
+        let origin = if self.resource.is_python_namespace_package {
+            None
+        } else {
+            self.resolve_origin(py, source_filenames)?
+        };
         if let Some(origin) = &origin {
             kwargs.set_item("origin", origin)?;
         }
@@ -314,21 +455,118 @@ impl<'a> ImportablePythonModule<'a, u8> {
                 locations.push(path.into_py(py).into_ref(py));
             }
 
+            // Portions of namespace packages can also exist on the filesystem,
+            // e.g. installed by other distributions. Searching them allows
+            // importing sub-modules that aren't packaged in memory.
+            if self.resource.is_python_namespace_package {
+                for portion in self.namespace_portions(py, path)? {
+                    locations.push(portion.into_py(py).into_ref(py));
+                }
+            }
+
             spec.setattr("submodule_search_locations", locations)?;
         }
 
         Ok(spec)
     }
 
+    /// Find the portions of a namespace package on the filesystem.
+    ///
+    /// `path` is the search path given to `find_spec()`: the `__path__` of
+    /// the parent package, or `None` to search `sys.path`. Directories named
+    /// after the package in the search path are portions of it. Entries which
+    /// aren't paths are ignored, like `PathFinder` does.
+    fn namespace_portions(&self, py: Python, path: &PyAny) -> PyResult<Vec<PathBuf>> {
+        let search_path = if path.is_none() {
+            py.import("sys")?.getattr("path")?
+        } else {
+            path
+        };
+
+        let leaf = match self.resource.name.rsplit_once('.') {
+            Some((_, leaf)) => leaf,
+            None => &self.resource.name,
+        };
+
+        let mut portions = vec![];
+        for entry in search_path.iter()? {
+            let entry = match pyobject_to_pathbuf(py, entry?) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            let candidate = entry.join(leaf);
+            if candidate.is_dir() && !portions.contains(&candidate) {
+                portions.push(candidate);
+            }
+        }
+
+        Ok(portions)
+    }
+
     /// Resolve the value of a `ModuleSpec` origin.
     ///
     /// The value gets turned into `__file__`
-    pub fn resolve_origin<'p>(&self, py: Python<'p>) -> PyResult<Option<&'p PyAny>> {
-        Ok(if let Some(path) = self.origin_path() {
-            Some(path.into_py(py).into_ref(py))
+    ///
+    /// If `source_filenames` is set, modules whose source is in memory have
+    /// [Self::source_path()] as their origin. In-memory modules of packages
+    /// having a data root have a path in it as their origin.
+    pub fn resolve_origin<'p>(
+        &self,
+        py: Python<'p>,
+        source_filenames: bool,
+    ) -> PyResult<Option<&'p PyAny>> {
+        let path = match self.origin_path() {
+            Some(path) => Some(path),
+            None if self.flavor == ModuleFlavor::SourceBytecode => match self.data_root {
+                Some(data_root) => Some(self.module_file_path(data_root)),
+                None if source_filenames => self.source_path(),
+                None => None,
+            },
+            None => None,
+        };
+
+        Ok(path.map(|path| path.into_py(py).into_ref(py)))
+    }
+
+    /// Whether the source code behind this module is in memory.
+    pub fn is_source_in_memory(&self) -> bool {
+        self.resource.in_memory_source.is_some()
+            && self.resource.relative_path_module_source.is_none()
+    }
+
+    /// Obtain the path of the source code behind this module.
+    ///
+    /// If the source is in memory, the path is constructed from the current
+    /// executable and module name, like `__path__` of packages. e.g.
+    /// `/path/to/myapp/foo/bar.py`. This path likely doesn't exist. If the
+    /// package of the module has a data root, the path is in it instead.
+    ///
+    /// Returns `None` if there is no source.
+    pub fn source_path(&self) -> Option<PathBuf> {
+        if let Some(relative_path) = &self.resource.relative_path_module_source {
+            Some(self.origin.join(relative_path))
+        } else if self.resource.in_memory_source.is_some() {
+            Some(self.module_file_path(self.data_root.unwrap_or(self.current_exe)))
         } else {
             None
-        })
+        }
+    }
+
+    /// Construct the path of this module's file beneath a directory.
+    ///
+    /// e.g. `<base>/foo/bar.py` or `<base>/foo/__init__.py`.
+    fn module_file_path(&self, base: &Path) -> PathBuf {
+        let mut path = base.to_path_buf();
+        path.extend(self.resource.name.split('.'));
+
+        if self.is_package {
+            path.push("__init__.py");
+        } else {
+            path.set_extension("py");
+        }
+
+        path
     }
 
     /// Resolve the value of a `ModuleSpec` `cached` attribute.
@@ -428,6 +666,15 @@ where
 
     /// Holds memory mapped file instances that resources data came from.
     backing_mmaps: Vec<memmap2::Mmap>,
+
+    /// Directory to extract data of packages in `dunder_file_packages` to.
+    dunder_file_root: Option<PathBuf>,
+
+    /// Packages whose in-memory modules get a synthetic `__file__`.
+    dunder_file_packages: BTreeSet<String>,
+
+    /// Packages whose data has been extracted by this process.
+    extracted_packages: Mutex<BTreeSet<String>>,
 }
 
 impl<'a> Default for PythonResourcesState<'a, u8> {
@@ -438,6 +685,9 @@ impl<'a> Default for PythonResourcesState<'a, u8> {
             resources: HashMap::new(),
             backing_py_objects: vec![],
             backing_mmaps: vec![],
+            dunder_file_root: None,
+            dunder_file_packages: BTreeSet::new(),
+            extracted_packages: Mutex::new(BTreeSet::new()),
         }
     }
 }
@@ -478,12 +728,89 @@ impl<'a> PythonResourcesState<'a, u8> {
         self.origin = path;
     }
 
+    /// Obtain the indexed resources, keyed by name.
+    pub fn resources(&self) -> &HashMap<Cow<'a, str>, Resource<'a, u8>> {
+        &self.resources
+    }
+
+    /// Give in-memory modules of `packages` a synthetic `__file__` in `root`.
+    ///
+    /// In-memory resources of these packages are extracted to `root` when a
+    /// module of the package is imported, so paths derived from `__file__`
+    /// resolve to them.
+    pub fn set_dunder_file_packages(
+        &mut self,
+        root: PathBuf,
+        packages: impl IntoIterator<Item = String>,
+    ) {
+        self.dunder_file_root = Some(root);
+        self.dunder_file_packages = packages.into_iter().collect();
+    }
+
+    /// Resolve the entry of `dunder_file_packages` a module belongs to.
+    fn dunder_file_package(&self, name: &str) -> Option<&str> {
+        self.dunder_file_packages
+            .iter()
+            .find(|package| {
+                name == package.as_str()
+                    || (name.starts_with(package.as_str())
+                        && name[package.len()..].starts_with('.'))
+            })
+            .map(|package| package.as_str())
+    }
+
+    /// Extract in-memory resources of the package of a module to the `__file__` root.
+    ///
+    /// Does nothing if the module's package doesn't get a synthetic `__file__`
+    /// or its resources have already been extracted by this process. Files
+    /// having the expected content are left untouched.
+    pub fn extract_package_data(&self, name: &str) -> std::io::Result<()> {
+        let (root, package) = match (&self.dunder_file_root, self.dunder_file_package(name)) {
+            (Some(root), Some(package)) => (root, package),
+            _ => return Ok(()),
+        };
+
+        let mut extracted = self
+            .extracted_packages
+            .lock()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, "lock poisoned"))?;
+
+        if extracted.contains(package) {
+            return Ok(());
+        }
+
+        for resource in self.resources.values() {
+            if self.dunder_file_package(&resource.name) != Some(package) {
+                continue;
+            }
+
+            if let Some(resources) = &resource.in_memory_package_resources {
+                let mut directory = root.clone();
+                directory.extend(resource.name.split('.'));
+
+                for (key, data) in resources {
+                    write_if_changed(&directory.join(key.as_ref()), data)?;
+                }
+            }
+        }
+
+        extracted.insert(package.to_string());
+
+        Ok(())
+    }
+
     /// Load resources by parsing a blob.
     ///
     /// If an existing entry exists, the new entry will be merged into it. Set fields
     /// on the incoming entry will overwrite fields on the existing entry.
     ///
     /// If an entry doesn't exist, the resource will be inserted as-is.
+    ///
+    /// Data in all format versions from
+    /// [python_packed_resources::MINIMUM_SUPPORTED_VERSION] to
+    /// [python_packed_resources::MAXIMUM_SUPPORTED_VERSION] is accepted. Data in a
+    /// newer version, e.g. produced by a newer PyOxidizer, is rejected with an
+    /// error saying so before anything is indexed.
     pub fn index_data(&mut self, data: &'a [u8]) -> Result<(), &'static str> {
         let resources = python_packed_resources::load_resources(data)?;
 
@@ -538,6 +865,104 @@ impl<'a> PythonResourcesState<'a, u8> {
         Ok(())
     }
 
+    /// Load resources by scanning a filesystem directory.
+    ///
+    /// The directory is treated like a `site-packages` directory. Resources
+    /// are indexed with absolute paths to their files. Bytecode files are
+    /// ignored: modules are compiled from source when imported.
+    ///
+    /// Only resources not yet indexed are added. So resources indexed from
+    /// other sources take precedence over the directory's content.
+    pub fn index_directory(
+        &mut self,
+        path: &Path,
+        cache_tag: &str,
+        suffixes: &PythonModuleSuffixes,
+    ) -> Result<(), String> {
+        fn resource_entry<'r, 'a>(
+            resources: &'r mut BTreeMap<String, Resource<'a, u8>>,
+            name: &str,
+        ) -> &'r mut Resource<'a, u8> {
+            resources
+                .entry(name.to_string())
+                .or_insert_with(|| Resource {
+                    name: Cow::Owned(name.to_string()),
+                    ..Resource::default()
+                })
+        }
+
+        let mut resources = BTreeMap::new();
+
+        for resource in find_python_resources(path, cache_tag, suffixes, false, true) {
+            let resource = resource.map_err(|e| format!("error scanning filesystem: {}", e))?;
+
+            match resource {
+                PythonResource::ModuleSource(module) => {
+                    if let Some(source_path) = module.source.backing_path() {
+                        let entry = resource_entry(&mut resources, &module.name);
+
+                        entry.is_python_module = true;
+                        entry.is_python_package |= module.is_package;
+                        entry.relative_path_module_source =
+                            Some(Cow::Owned(source_path.to_path_buf()));
+                    }
+                }
+                PythonResource::ExtensionModule(module) => {
+                    if let Some(library_path) = module
+                        .shared_library
+                        .as_ref()
+                        .and_then(|data| data.backing_path())
+                    {
+                        let entry = resource_entry(&mut resources, &module.name);
+
+                        entry.is_python_extension_module = true;
+                        entry.is_python_package |= module.is_package;
+                        entry.relative_path_extension_module_shared_library =
+                            Some(Cow::Owned(library_path.to_path_buf()));
+                    }
+                }
+                PythonResource::PackageResource(resource) => {
+                    if let Some(resource_path) = resource.data.backing_path() {
+                        let entry = resource_entry(&mut resources, &resource.leaf_package);
+
+                        entry
+                            .relative_path_package_resources
+                            .get_or_insert_with(HashMap::new)
+                            .insert(
+                                Cow::Owned(resource.relative_name.clone()),
+                                Cow::Owned(resource_path.to_path_buf()),
+                            );
+                    }
+                }
+                PythonResource::PackageDistributionResource(resource) => {
+                    if let Some(resource_path) = resource.data.backing_path() {
+                        let entry = resource_entry(&mut resources, &resource.package);
+
+                        // A distribution resource makes the entity a package.
+                        entry.is_python_module = true;
+                        entry.is_python_package = true;
+                        entry
+                            .relative_path_distribution_resources
+                            .get_or_insert_with(HashMap::new)
+                            .insert(
+                                Cow::Owned(resource.name.clone()),
+                                Cow::Owned(resource_path.to_path_buf()),
+                            );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (name, resource) in resources {
+            if let Entry::Vacant(vacant) = self.resources.entry(Cow::Owned(name)) {
+                vacant.insert(resource);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Load `builtin` modules from the Python interpreter.
     pub fn index_interpreter_builtin_extension_modules(&mut self) -> Result<(), &'static str> {
         for i in 0.. {
@@ -699,6 +1124,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                data_root: None,
                 flavor: ModuleFlavor::Builtin,
                 is_package: resource.is_python_package,
             })
@@ -707,6 +1133,7 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                data_root: None,
                 flavor: ModuleFlavor::Frozen,
                 is_package: resource.is_python_package,
             })
@@ -715,15 +1142,22 @@ impl<'a> PythonResourcesState<'a, u8> {
                 resource,
                 current_exe: &self.current_exe,
                 origin: &self.origin,
+                data_root: None,
                 flavor: ModuleFlavor::Extension,
                 is_package: resource.is_python_package,
             })
         } else if resource.is_python_module {
-            if is_module_importable(resource, optimize_level) {
+            // Namespace packages don't need code: they import as empty modules.
+            if is_module_importable(resource, optimize_level)
+                || resource.is_python_namespace_package
+            {
                 Some(ImportablePythonModule {
                     resource,
                     current_exe: &self.current_exe,
                     origin: &self.origin,
+                    data_root: self
+                        .dunder_file_package(name)
+                        .and(self.dunder_file_root.as_deref()),
                     flavor: ModuleFlavor::SourceBytecode,
                     is_package: resource.is_python_package,
                 })
@@ -1135,6 +1569,103 @@ impl<'a> PythonResourcesState<'a, u8> {
         }
     }
 
+    /// Synthesize the lines of the `RECORD` file of a package distribution.
+    ///
+    /// `RECORD` lists the files of a distribution. It is synthesized for
+    /// distributions packaged without one from the resources known to us:
+    /// the distribution resources, which are placed in `dist_info`, and the
+    /// modules and resources of the packages the distribution provides.
+    /// These are the packages in `top_level.txt`, or the package named like
+    /// the distribution. Hashes and sizes of files aren't known and are
+    /// left empty.
+    pub fn synthesize_package_distribution_record(
+        &self,
+        package: &str,
+        dist_info: &str,
+    ) -> Result<Vec<String>> {
+        let entry = match self.resources.get(package) {
+            Some(entry) => entry,
+            None => return Ok(vec![]),
+        };
+
+        let mut paths = BTreeSet::new();
+
+        if let Some(resources) = &entry.in_memory_distribution_resources {
+            for name in resources.keys() {
+                paths.insert(format!("{}/{}", dist_info, name));
+            }
+        }
+        if let Some(resources) = &entry.relative_path_distribution_resources {
+            for name in resources.keys() {
+                paths.insert(format!("{}/{}", dist_info, name));
+            }
+        }
+
+        let top_level =
+            match self.resolve_package_distribution_resource(package, "top_level.txt")? {
+                Some(data) => String::from_utf8_lossy(&data)
+                    .lines()
+                    .map(|line| line.trim().to_string())
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>(),
+                None => vec![package.to_lowercase().replace('-', "_")],
+            };
+
+        for resource in self.resources.values() {
+            let name = resource.name.as_ref();
+
+            if !top_level
+                .iter()
+                .any(|top| name == top || name_within_package_hierarchy(name, Some(top.as_str())))
+            {
+                continue;
+            }
+
+            let module_path = name.replace('.', "/");
+
+            if resource.is_python_extension_module {
+                if let Some(path) = &resource.relative_path_extension_module_shared_library {
+                    if let Some(file_name) = path.file_name() {
+                        let parent = match module_path.rsplit_once('/') {
+                            Some((parent, _)) => format!("{}/", parent),
+                            None => "".to_string(),
+                        };
+                        paths.insert(format!("{}{}", parent, file_name.to_string_lossy()));
+                    }
+                }
+            } else if resource.is_python_module {
+                paths.insert(if resource.is_python_package {
+                    format!("{}/__init__.py", module_path)
+                } else {
+                    format!("{}.py", module_path)
+                });
+            }
+
+            if let Some(resources) = &resource.in_memory_package_resources {
+                for name in resources.keys() {
+                    paths.insert(format!("{}/{}", module_path, name));
+                }
+            }
+            if let Some(resources) = &resource.relative_path_package_resources {
+                for name in resources.keys() {
+                    paths.insert(format!("{}/{}", module_path, name));
+                }
+            }
+        }
+
+        // Lines are CSV records of path, hash and size.
+        Ok(paths
+            .into_iter()
+            .map(|path| {
+                if path.contains(',') || path.contains('"') {
+                    format!("\"{}\",,", path.replace('"', "\"\""))
+                } else {
+                    format!("{},,", path)
+                }
+            })
+            .collect::<Vec<_>>())
+    }
+
     /// Whether a package distribution resource name is a directory.
     pub fn package_distribution_resource_name_is_directory(
         &self,
@@ -1273,7 +1804,7 @@ impl<'a> PythonResourcesState<'a, u8> {
 
         let mut buffer = Vec::new();
 
-        python_packed_resources::write_packed_resources_v3(&resources, &mut buffer, None)?;
+        python_packed_resources::write_packed_resources_v4(&resources, &mut buffer, None)?;
 
         Ok(buffer)
     }
@@ -0,0 +1,401 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Enumeration of indexed resources.
+
+Resources indexed by a finder describe modules, package resources,
+distribution resources, shared libraries and files. Each is exposed to
+Python as a descriptor naming its kind and location, so applications can
+discover plugins, list assets or diagnose packaging at run-time without
+knowing about the packed resources data structure.
+*/
+
+use {
+    crate::{conversion::path_to_pathlib_path, importer::ImporterState},
+    pyo3::{
+        exceptions::{PyIOError, PyValueError},
+        prelude::*,
+        types::PyBytes,
+        PyObjectProtocol,
+    },
+    python_packed_resources::Resource,
+    std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+    },
+};
+
+/// Kinds of resources.
+const RESOURCE_KINDS: &[&str] = &[
+    "module",
+    "extension-module",
+    "package-resource",
+    "distribution-resource",
+    "shared-library",
+    "file",
+];
+
+/// Locations of resources.
+const RESOURCE_LOCATIONS: &[&str] = &["in-memory", "filesystem-relative", "builtin", "frozen"];
+
+/// Describes a resource indexed by a finder.
+#[pyclass(module = "oxidized_importer")]
+pub(crate) struct OxidizedResourceDescriptor {
+    state: Arc<ImporterState>,
+    /// Name of the indexed resource holding this resource.
+    entry: String,
+    kind: &'static str,
+    location: &'static str,
+    name: String,
+    package: Option<String>,
+    is_package: bool,
+    path: Option<PathBuf>,
+}
+
+impl OxidizedResourceDescriptor {
+    /// Read the data of an in-memory resource.
+    fn in_memory_data(&self) -> Option<Vec<u8>> {
+        let resource = self
+            .state
+            .get_resources_state()
+            .resources()
+            .get(self.entry.as_str())?;
+
+        let data = match self.kind {
+            "module" => resource.in_memory_source.as_ref(),
+            "extension-module" => resource.in_memory_extension_module_shared_library.as_ref(),
+            "package-resource" => resource
+                .in_memory_package_resources
+                .as_ref()
+                .and_then(|resources| resources.get(self.name.as_str())),
+            "distribution-resource" => resource
+                .in_memory_distribution_resources
+                .as_ref()
+                .and_then(|resources| resources.get(self.name.as_str())),
+            "shared-library" => resource.in_memory_shared_library.as_ref(),
+            "file" => resource.file_data_embedded.as_ref(),
+            _ => None,
+        };
+
+        data.map(|data| data.to_vec())
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for OxidizedResourceDescriptor {
+    fn __repr__(&self) -> String {
+        format!(
+            "<OxidizedResourceDescriptor kind=\"{}\" location=\"{}\" name=\"{}\">",
+            self.kind, self.location, self.name
+        )
+    }
+}
+
+#[pymethods]
+impl OxidizedResourceDescriptor {
+    /// The kind of resource.
+    #[getter]
+    fn kind(&self) -> &str {
+        self.kind
+    }
+
+    /// Where the resource is loaded from.
+    #[getter]
+    fn location(&self) -> &str {
+        self.location
+    }
+
+    /// Module name, name of the resource within its package or filename.
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The package the resource belongs to.
+    ///
+    /// This is the package containing a module, or `None` for top-level
+    /// modules, shared libraries and files.
+    #[getter]
+    fn package(&self) -> Option<&str> {
+        self.package.as_deref()
+    }
+
+    /// Whether the resource is a module that is a package.
+    #[getter]
+    fn is_package(&self) -> bool {
+        self.is_package
+    }
+
+    /// Filesystem path of the resource, if it is filesystem-relative.
+    #[getter]
+    fn path<'p>(&self, py: Python<'p>) -> PyResult<Option<&'p PyAny>> {
+        self.path
+            .as_ref()
+            .map(|path| path_to_pathlib_path(py, path))
+            .transpose()
+    }
+
+    /// Read the data of the resource.
+    ///
+    /// Modules yield their source code. Returns `None` if the resource has
+    /// no data available, e.g. modules without source or built-in modules.
+    fn read<'p>(&self, py: Python<'p>) -> PyResult<Option<&'p PyBytes>> {
+        let data = if let Some(path) = &self.path {
+            Some(std::fs::read(path).map_err(|e| {
+                PyIOError::new_err(format!("error reading {}: {}", path.display(), e))
+            })?)
+        } else {
+            self.in_memory_data()
+        };
+
+        Ok(data.map(|data| PyBytes::new(py, &data)))
+    }
+}
+
+/// Criteria to select resources by.
+pub(crate) struct ResourceFilter {
+    package: Option<String>,
+    kind: Option<String>,
+    location: Option<String>,
+}
+
+impl ResourceFilter {
+    /// Construct an instance from optional criteria.
+    ///
+    /// Errors if `kind` or `location` aren't known values.
+    pub(crate) fn new(
+        package: Option<String>,
+        kind: Option<String>,
+        location: Option<String>,
+    ) -> PyResult<Self> {
+        if let Some(kind) = &kind {
+            if !RESOURCE_KINDS.contains(&kind.as_str()) {
+                return Err(PyValueError::new_err(format!(
+                    "unknown resource kind: {}; expected one of {}",
+                    kind,
+                    RESOURCE_KINDS.join(", ")
+                )));
+            }
+        }
+
+        if let Some(location) = &location {
+            if !RESOURCE_LOCATIONS.contains(&location.as_str()) {
+                return Err(PyValueError::new_err(format!(
+                    "unknown resource location: {}; expected one of {}",
+                    location,
+                    RESOURCE_LOCATIONS.join(", ")
+                )));
+            }
+        }
+
+        Ok(Self {
+            package,
+            kind,
+            location,
+        })
+    }
+
+    /// Whether a resource matches the criteria.
+    ///
+    /// `package` is the package a module is or the package owning a resource.
+    fn matches(&self, kind: &str, location: &str, package: Option<&str>) -> bool {
+        let package_matches = match (&self.package, package) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(wanted), Some(package)) => {
+                package == wanted || package.starts_with(&format!("{}.", wanted))
+            }
+        };
+
+        package_matches
+            && self.kind.as_deref().map_or(true, |wanted| wanted == kind)
+            && self
+                .location
+                .as_deref()
+                .map_or(true, |wanted| wanted == location)
+    }
+}
+
+/// The package containing a module.
+fn parent_package(name: &str) -> Option<String> {
+    name.rsplit_once('.').map(|(parent, _)| parent.to_string())
+}
+
+/// Find indexed resources matching a filter.
+///
+/// Resources are ordered by the name of the indexed resource holding them,
+/// then by kind and name.
+pub(crate) fn find_resources(
+    state: Arc<ImporterState>,
+    filter: &ResourceFilter,
+) -> Vec<OxidizedResourceDescriptor> {
+    let resources_state = state.get_resources_state();
+    let origin = resources_state.origin();
+
+    let mut entries = resources_state.resources().values().collect::<Vec<_>>();
+    entries.sort_by_key(|r| &r.name);
+
+    let mut descriptors = vec![];
+
+    for resource in entries {
+        let mut found = describe_resource(resource, origin);
+        found.sort_by(|a, b| (a.0, &a.2).cmp(&(b.0, &b.2)));
+
+        for (kind, location, name, package, is_package, path) in found {
+            let match_package = if kind == "module" || kind == "extension-module" {
+                Some(name.as_str())
+            } else {
+                package.as_deref()
+            };
+
+            if !filter.matches(kind, location, match_package) {
+                continue;
+            }
+
+            descriptors.push(OxidizedResourceDescriptor {
+                state: state.clone(),
+                entry: resource.name.to_string(),
+                kind,
+                location,
+                name,
+                package,
+                is_package,
+                path,
+            });
+        }
+    }
+
+    descriptors
+}
+
+type Description = (
+    &'static str,
+    &'static str,
+    String,
+    Option<String>,
+    bool,
+    Option<PathBuf>,
+);
+
+/// Describe the resources held by an indexed resource.
+fn describe_resource(resource: &Resource<u8>, origin: &Path) -> Vec<Description> {
+    let name = resource.name.to_string();
+    let mut found = vec![];
+
+    let module_location = if resource.is_python_builtin_extension_module {
+        Some(("extension-module", "builtin", None))
+    } else if resource.is_python_frozen_module {
+        Some(("module", "frozen", None))
+    } else if resource.is_python_extension_module {
+        if let Some(path) = &resource.relative_path_extension_module_shared_library {
+            Some((
+                "extension-module",
+                "filesystem-relative",
+                Some(origin.join(path)),
+            ))
+        } else {
+            Some(("extension-module", "in-memory", None))
+        }
+    } else if resource.is_python_module {
+        if let Some(path) = &resource.relative_path_module_source {
+            Some(("module", "filesystem-relative", Some(origin.join(path))))
+        } else if resource.in_memory_source.is_none()
+            && resource.in_memory_bytecode.is_none()
+            && resource.in_memory_bytecode_opt1.is_none()
+            && resource.in_memory_bytecode_opt2.is_none()
+            && resource.relative_path_module_bytecode.is_some()
+        {
+            Some((
+                "module",
+                "filesystem-relative",
+                resource
+                    .relative_path_module_bytecode
+                    .as_ref()
+                    .map(|path| origin.join(path)),
+            ))
+        } else {
+            Some(("module", "in-memory", None))
+        }
+    } else {
+        None
+    };
+
+    if let Some((kind, location, path)) = module_location {
+        found.push((
+            kind,
+            location,
+            name.clone(),
+            parent_package(&name),
+            resource.is_python_package,
+            path,
+        ));
+    }
+
+    for (kind, in_memory, relative_path) in [
+        (
+            "package-resource",
+            &resource.in_memory_package_resources,
+            &resource.relative_path_package_resources,
+        ),
+        (
+            "distribution-resource",
+            &resource.in_memory_distribution_resources,
+            &resource.relative_path_distribution_resources,
+        ),
+    ] {
+        if let Some(resources) = in_memory {
+            for key in resources.keys() {
+                found.push((
+                    kind,
+                    "in-memory",
+                    key.to_string(),
+                    Some(name.clone()),
+                    false,
+                    None,
+                ));
+            }
+        }
+        if let Some(resources) = relative_path {
+            for (key, path) in resources {
+                found.push((
+                    kind,
+                    "filesystem-relative",
+                    key.to_string(),
+                    Some(name.clone()),
+                    false,
+                    Some(origin.join(path)),
+                ));
+            }
+        }
+    }
+
+    if resource.is_shared_library && resource.in_memory_shared_library.is_some() {
+        found.push((
+            "shared-library",
+            "in-memory",
+            name.clone(),
+            None,
+            false,
+            None,
+        ));
+    }
+
+    if resource.is_utf8_filename_data {
+        if resource.file_data_embedded.is_some() {
+            found.push(("file", "in-memory", name.clone(), None, false, None));
+        }
+        if let Some(path) = &resource.file_data_utf8_relative_path {
+            found.push((
+                "file",
+                "filesystem-relative",
+                name,
+                None,
+                false,
+                Some(origin.join(path.as_ref())),
+            ));
+        }
+    }
+
+    found
+}
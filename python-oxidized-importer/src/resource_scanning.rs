@@ -19,18 +19,8 @@ use {
     },
 };
 
-/// Scans a filesystem path for Python resources and turns them into Python types.
-#[pyfunction]
-pub(crate) fn find_resources_in_path<'p>(py: Python<'p>, path: &PyAny) -> PyResult<&'p PyList> {
-    let path = pyobject_to_pathbuf(py, path)?;
-
-    if !path.is_dir() {
-        return Err(PyValueError::new_err(format!(
-            "path is not a directory: {}",
-            path.display()
-        )));
-    }
-
+/// Resolve the bytecode cache tag and module suffixes of the running interpreter.
+pub(crate) fn interpreter_module_suffixes(py: Python) -> PyResult<(String, PythonModuleSuffixes)> {
     let sys_module = py.import("sys")?;
     let implementation = sys_module.getattr("implementation")?;
     let cache_tag = implementation.getattr("cache_tag")?.extract::<String>()?;
@@ -61,6 +51,23 @@ pub(crate) fn find_resources_in_path<'p>(py: Python<'p>, path: &PyAny) -> PyResu
         extension,
     };
 
+    Ok((cache_tag, suffixes))
+}
+
+/// Scans a filesystem path for Python resources and turns them into Python types.
+#[pyfunction]
+pub(crate) fn find_resources_in_path<'p>(py: Python<'p>, path: &PyAny) -> PyResult<&'p PyList> {
+    let path = pyobject_to_pathbuf(py, path)?;
+
+    if !path.is_dir() {
+        return Err(PyValueError::new_err(format!(
+            "path is not a directory: {}",
+            path.display()
+        )));
+    }
+
+    let (cache_tag, suffixes) = interpreter_module_suffixes(py)?;
+
     let mut res: Vec<Py<PyAny>> = Vec::new();
 
     let iter = find_python_resources(&path, &cache_tag, &suffixes, false, true);
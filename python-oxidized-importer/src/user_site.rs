@@ -0,0 +1,83 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Functionality for installing packages at run-time. */
+
+use {
+    crate::importer::OxidizedFinder,
+    pyo3::{
+        exceptions::{PyOSError, PyRuntimeError, PyValueError},
+        prelude::*,
+    },
+};
+
+/// Arguments to `pip install` preceding the install directory.
+const PIP_INSTALL_ARGS: &[&str] = &[
+    "install",
+    "--disable-pip-version-check",
+    "--no-warn-script-location",
+    // Building packages from source requires a Python installation.
+    "--only-binary",
+    ":all:",
+    "--upgrade",
+    "--target",
+];
+
+/// Install packages to the user site-packages directory of the `OxidizedFinder`.
+///
+/// Packages are installed by running the `pip` packaged with the application.
+/// The directory is then indexed by the finder, making installed packages
+/// importable.
+#[pyfunction]
+pub(crate) fn install_to_user_site(py: Python, requirements: Vec<String>) -> PyResult<()> {
+    let meta_path = py.import("sys")?.getattr("meta_path")?;
+
+    let mut target = None;
+    for finder in meta_path.iter()? {
+        let finder = finder?;
+
+        if let Ok(cell) = finder.cast_as::<PyCell<OxidizedFinder>>() {
+            if let Some(path) = &cell.borrow().state.user_site_packages {
+                target = Some((finder, path.clone()));
+                break;
+            }
+        }
+    }
+
+    let (finder, path) = target.ok_or_else(|| {
+        PyValueError::new_err(
+            "no OxidizedFinder with a user site-packages directory found on sys.meta_path",
+        )
+    })?;
+
+    std::fs::create_dir_all(&path)
+        .map_err(|e| PyOSError::new_err(format!("error creating {}: {}", path.display(), e)))?;
+
+    let mut args = PIP_INSTALL_ARGS
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>();
+    args.push(path.display().to_string());
+    args.extend(requirements);
+
+    let pip_main = py.import("pip._internal.cli.main")?.getattr("main")?;
+    let status = pip_main.call1((args,))?.extract::<i64>()?;
+
+    if status != 0 {
+        return Err(PyRuntimeError::new_err(format!(
+            "pip install exited with status {}",
+            status
+        )));
+    }
+
+    finder.call_method1("index_directory", (path.display().to_string(),))?;
+
+    Ok(())
+}
+
+pub(crate) fn init_module(m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(install_to_user_site, m)?)?;
+
+    Ok(())
+}
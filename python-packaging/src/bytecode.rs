@@ -159,56 +159,126 @@ impl PythonBytecodeCompiler for BytecodeCompiler {
         stdin.write_all(source).context("writing source code")?;
         stdin.flush().context("flushing")?;
 
-        let mut code_s = String::new();
-        reader
-            .read_line(&mut code_s)
-            .context("reading result code")?;
-        let code_s = code_s.trim_end();
-        let code = code_s.parse::<u8>().unwrap();
-
-        match code {
-            0 => {
-                let mut len_s = String::new();
-                reader
-                    .read_line(&mut len_s)
-                    .context("reading output size line")?;
-
-                let len_s = len_s.trim_end();
-                let bytecode_len = len_s.parse::<u64>().unwrap();
-
-                let mut bytecode: Vec<u8> = Vec::new();
-                reader
-                    .take(bytecode_len)
-                    .read_to_end(&mut bytecode)
-                    .context("reading bytecode result")?;
-
-                Ok(bytecode)
-            }
-            1 => {
-                let mut len_s = String::new();
-                reader
-                    .read_line(&mut len_s)
-                    .context("reading error string length line")?;
-
-                let len_s = len_s.trim_end();
-                let error_len = len_s.parse::<u64>().unwrap();
-
-                let mut error_data = vec![];
-                reader
-                    .take(error_len)
-                    .read_to_end(&mut error_data)
-                    .context("reading error message")?;
-
-                Err(anyhow!(
-                    "compiling error: {}",
-                    String::from_utf8(error_data)?
-                ))
-            }
-            _ => Err(anyhow!(
-                "unexpected result code from compile command: {}",
-                code
-            )),
+        read_result(&mut reader, "compile", "compiling")
+    }
+}
+
+impl BytecodeCompiler {
+    /// Find names of modules imported dynamically by Python source code.
+    ///
+    /// Source code is parsed and calls to `__import__()` and `import_module()`
+    /// with a literal module name are collected. Relative names passed to
+    /// `import_module()` are resolved if the package they are relative to
+    /// is a literal, `__name__`, or `__package__`.
+    pub fn find_dynamic_imports(
+        &mut self,
+        source: &[u8],
+        name: &str,
+        is_package: bool,
+    ) -> Result<Vec<String>> {
+        self.analyze_imports("find_dynamic_imports", source, name, is_package)
+    }
+
+    /// Find names of modules imported by `import` statements of Python source code.
+    ///
+    /// For `from X import Y`, both `X` and `X.Y` are returned since `Y` may
+    /// be a module. Relative imports are resolved.
+    pub fn find_imports(
+        &mut self,
+        source: &[u8],
+        name: &str,
+        is_package: bool,
+    ) -> Result<Vec<String>> {
+        self.analyze_imports("find_imports", source, name, is_package)
+    }
+
+    fn analyze_imports(
+        &mut self,
+        command: &str,
+        source: &[u8],
+        name: &str,
+        is_package: bool,
+    ) -> Result<Vec<String>> {
+        let stdin = self.command.stdin.as_mut().expect("failed to get stdin");
+        let stdout = self.command.stdout.as_mut().expect("failed to get stdout");
+
+        let mut reader = BufReader::new(stdout);
+
+        stdin
+            .write_all(format!("{}\n", command).as_bytes())
+            .with_context(|| format!("writing {} command", command))?;
+        stdin
+            .write_all(format!("{}\n{}\n", name.len(), source.len()).as_bytes())
+            .context("writing name and source code lengths")?;
+        stdin
+            .write_all(if is_package { b"1\n" } else { b"0\n" })
+            .context("writing package flag")?;
+        stdin.write_all(name.as_bytes()).context("writing name")?;
+        stdin.write_all(source).context("writing source code")?;
+        stdin.flush().context("flushing")?;
+
+        let data = read_result(&mut reader, command, "analyzing imports")?;
+
+        Ok(String::from_utf8(data)?
+            .lines()
+            .map(|x| x.to_string())
+            .collect())
+    }
+}
+
+/// Read the result of a command sent to the bytecode compiler process.
+fn read_result(reader: &mut impl BufRead, command: &str, action: &str) -> Result<Vec<u8>> {
+    let mut code_s = String::new();
+    reader
+        .read_line(&mut code_s)
+        .context("reading result code")?;
+    let code_s = code_s.trim_end();
+    let code = code_s.parse::<u8>().unwrap();
+
+    match code {
+        0 => {
+            let mut len_s = String::new();
+            reader
+                .read_line(&mut len_s)
+                .context("reading output size line")?;
+
+            let len_s = len_s.trim_end();
+            let output_len = len_s.parse::<u64>().unwrap();
+
+            let mut output: Vec<u8> = Vec::new();
+            reader
+                .take(output_len)
+                .read_to_end(&mut output)
+                .with_context(|| format!("reading {} result", command))?;
+
+            Ok(output)
         }
+        1 => {
+            let mut len_s = String::new();
+            reader
+                .read_line(&mut len_s)
+                .context("reading error string length line")?;
+
+            let len_s = len_s.trim_end();
+            let error_len = len_s.parse::<u64>().unwrap();
+
+            let mut error_data = vec![];
+            reader
+                .take(error_len)
+                .read_to_end(&mut error_data)
+                .context("reading error message")?;
+
+            Err(anyhow!(
+                "{} error: {}",
+                action,
+                String::from_utf8(error_data)?
+            ))
+        }
+        _ => Err(anyhow!(
+            "unexpected result code from {} command: {}",
+            command,
+            code
+        )),
     }
 }
 
@@ -7,6 +7,7 @@
 # When invoked, we start a server that listens for commands. We then
 # react to those commands and send results to the caller.
 
+import ast
 import importlib._bootstrap_external
 import importlib.util
 import marshal
@@ -25,6 +26,115 @@ stdin = sys.__stdin__.buffer
 stdout = sys.__stdout__.buffer
 
 
+def decode_source(source):
+    """Decode source code bytes to str.
+
+    Returns the bytes the str was decoded from and the str.
+    """
+    # Default source encoding is UTF-8. But per PEP 263, the first or second
+    # line of source can match a regular expression to define a custom
+    # encoding. We need to detect custom encodings and use it to decode
+    # the passed bytes to str.
+    encoding = "utf-8"
+
+    for line in source.splitlines()[0:2]:
+        m = RE_CODING.match(line)
+        if m:
+            encoding = m.group(1).decode("ascii")
+            break
+
+    # Someone has set us up the BOM! According to PEP 263 the file should
+    # be interpreted as UTF-8.
+    if source.startswith(b"\xef\xbb\xbf"):
+        encoding = "utf-8"
+        source = source[3:]
+
+    return source, source.decode(encoding)
+
+
+def find_dynamic_imports(source, name, is_package):
+    """Find names of modules imported via __import__() or import_module().
+
+    Only calls with a literal module name are considered. Relative names are
+    resolved if the package is a literal, __name__, or __package__.
+    """
+    package = name if is_package else name.rpartition(".")[0]
+    imports = set()
+
+    for node in ast.walk(ast.parse(source, name)):
+        if not isinstance(node, ast.Call) or not node.args:
+            continue
+
+        if isinstance(node.func, ast.Name):
+            func = node.func.id
+        elif isinstance(node.func, ast.Attribute):
+            func = node.func.attr
+        else:
+            continue
+
+        arg = node.args[0]
+        if func not in ("__import__", "import_module") or not (
+            isinstance(arg, ast.Constant) and isinstance(arg.value, str)
+        ):
+            continue
+
+        module = arg.value
+
+        if func == "import_module" and module.startswith("."):
+            anchor = node.args[1] if len(node.args) > 1 else None
+            if isinstance(anchor, ast.Constant) and isinstance(anchor.value, str):
+                anchor = anchor.value
+            elif isinstance(anchor, ast.Name) and anchor.id == "__name__":
+                anchor = name
+            elif isinstance(anchor, ast.Name) and anchor.id == "__package__":
+                anchor = package
+            else:
+                continue
+
+            try:
+                module = importlib.util.resolve_name(module, anchor)
+            except ImportError:
+                continue
+
+        if module and not module.startswith("."):
+            imports.add(module)
+
+    return sorted(imports)
+
+
+def find_imports(source, name, is_package):
+    """Find names of modules imported via import statements.
+
+    For ``from X import Y``, both ``X`` and ``X.Y`` are returned since ``Y``
+    may be a module. Relative imports are resolved.
+    """
+    package = name if is_package else name.rpartition(".")[0]
+    imports = set()
+
+    for node in ast.walk(ast.parse(source, name)):
+        if isinstance(node, ast.Import):
+            imports.update(alias.name for alias in node.names)
+        elif isinstance(node, ast.ImportFrom):
+            module = node.module or ""
+
+            if node.level:
+                try:
+                    module = importlib.util.resolve_name(
+                        "." * node.level + module, package
+                    )
+                except (ImportError, ValueError):
+                    continue
+
+            imports.add(module)
+            imports.update(
+                "%s.%s" % (module, alias.name)
+                for alias in node.names
+                if alias.name != "*"
+            )
+
+    return sorted(imports)
+
+
 while True:
     command = stdin.readline().rstrip()
 
@@ -49,26 +159,7 @@ while True:
 
             name = os.fsdecode(name)
 
-            # Default source encoding is UTF-8. But per PEP 263, the first or second
-            # line of source can match a regular expression to define a custom
-            # encoding. We need to detect custom encodings and use it to decode
-            # the passed bytes to str.
-            encoding = "utf-8"
-
-            for line in source.splitlines()[0:2]:
-                m = RE_CODING.match(line)
-                if m:
-                    encoding = m.group(1).decode("ascii")
-                    break
-
-            # Someone has set us up the BOM! According to PEP 263 the file should
-            # be interpreted as UTF-8.
-            if source.startswith(b"\xef\xbb\xbf"):
-                encoding = "utf-8"
-                source = source[3:]
-
-            source_bytes = source
-            source = source.decode(encoding)
+            source_bytes, source = decode_source(source)
 
             code = compile(source, name, "exec", optimize=optimize_level)
             bytecode = marshal.dumps(code)
@@ -98,6 +189,33 @@ while True:
             stdout.write(b"%d\n" % len(error_bytes))
             stdout.write(error_bytes)
 
+        stdout.flush()
+    elif command in (b"find_dynamic_imports", b"find_imports"):
+        if command == b"find_dynamic_imports":
+            finder = find_dynamic_imports
+        else:
+            finder = find_imports
+
+        try:
+            name_len = int(stdin.readline().rstrip())
+            source_len = int(stdin.readline().rstrip())
+            is_package = stdin.readline().rstrip() == b"1"
+
+            name = os.fsdecode(stdin.read(name_len))
+            _, source = decode_source(stdin.read(source_len))
+
+            out = "\n".join(finder(source, name, is_package))
+            out = out.encode("utf-8")
+
+            stdout.write(b"0\n")
+            stdout.write(b"%d\n" % len(out))
+            stdout.write(out)
+        except Exception as e:
+            stdout.write(b"1\n")
+            error_bytes = str(e).encode("utf-8", errors="replace")
+            stdout.write(b"%d\n" % len(error_bytes))
+            stdout.write(error_bytes)
+
         stdout.flush()
     else:
         raise Exception("invalid command: %s" % command)
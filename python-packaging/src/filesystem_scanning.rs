@@ -26,20 +26,6 @@ use {
     tugger_file_manifest::{File, FileData, FileEntry, FileManifest},
 };
 
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-
-#[cfg(unix)]
-fn is_executable(metadata: &std::fs::Metadata) -> bool {
-    let permissions = metadata.permissions();
-    permissions.mode() & 0o111 != 0
-}
-
-#[cfg(windows)]
-fn is_executable(_metadata: &std::fs::Metadata) -> bool {
-    false
-}
-
 pub fn walk_tree_files(path: &Path) -> Box<dyn Iterator<Item = walkdir::DirEntry>> {
     let res = walkdir::WalkDir::new(path).sort_by(|a, b| a.file_name().cmp(b.file_name()));
 
@@ -181,16 +167,12 @@ impl<'a> PythonResourceIterator<'a> {
         })
     }
 
-    fn resolve_is_executable(&self, path: &Path) -> bool {
+    /// Resolve the file entry of a path, preserving its permissions.
+    fn resolve_file_entry(&self, path: &Path) -> FileEntry {
         match self.path_content_overrides.get(path) {
-            Some(file) => file.is_executable(),
-            None => {
-                if let Ok(metadata) = path.metadata() {
-                    is_executable(&metadata)
-                } else {
-                    false
-                }
-            }
+            Some(file) => FileEntry::new_from_data(file.file_data().clone(), file.is_executable()),
+            None => FileEntry::try_from(path)
+                .unwrap_or_else(|_| FileEntry::new_from_data(path.to_path_buf(), false)),
         }
     }
 
@@ -541,13 +523,7 @@ impl<'a> Iterator for PythonResourceIterator<'a> {
                     .expect("unable to strip path prefix")
                     .to_path_buf();
 
-                let f = File::new(
-                    rel_path,
-                    FileEntry::new_from_data(
-                        self.resolve_file_data(&self.paths[0].path),
-                        self.resolve_is_executable(&self.paths[0].path),
-                    ),
-                );
+                let f = File::new(rel_path, self.resolve_file_entry(&self.paths[0].path));
 
                 return Some(Ok(f.into()));
             }
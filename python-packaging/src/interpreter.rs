@@ -484,6 +484,155 @@ impl TryFrom<String> for MultiprocessingStartMethod {
     }
 }
 
+/// Defines a change to an environment variable made before the interpreter is initialized.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialization", serde(try_from = "String", into = "String"))]
+pub enum EnvironmentMutation {
+    /// Set a variable to a value.
+    Set(String, String),
+    /// Remove a variable.
+    Unset(String),
+    /// Add a path before the entries of a path list variable, like `PATH`.
+    Prepend(String, String),
+    /// Add a path after the entries of a path list variable, like `PATH`.
+    Append(String, String),
+}
+
+impl EnvironmentMutation {
+    /// The name of the variable being changed.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Set(name, _)
+            | Self::Unset(name)
+            | Self::Prepend(name, _)
+            | Self::Append(name, _) => name,
+        }
+    }
+}
+
+impl ToString for EnvironmentMutation {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Set(name, value) => format!("set:{}={}", name, value),
+            Self::Unset(name) => format!("unset:{}", name),
+            Self::Prepend(name, value) => format!("prepend:{}={}", name, value),
+            Self::Append(name, value) => format!("append:{}={}", name, value),
+        }
+    }
+}
+
+impl From<EnvironmentMutation> for String {
+    fn from(v: EnvironmentMutation) -> Self {
+        v.to_string()
+    }
+}
+
+impl TryFrom<&str> for EnvironmentMutation {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let invalid = || {
+            format!(
+                "{} is not a valid environment mutation; expected set:NAME=VALUE, unset:NAME, prepend:NAME=VALUE or append:NAME=VALUE",
+                value
+            )
+        };
+
+        let (action, rest) = value.split_once(':').ok_or_else(invalid)?;
+
+        if action == "unset" {
+            return if rest.is_empty() || rest.contains('=') {
+                Err(invalid())
+            } else {
+                Ok(Self::Unset(rest.to_string()))
+            };
+        }
+
+        let (name, value) = rest
+            .split_once('=')
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(invalid)?;
+        let (name, value) = (name.to_string(), value.to_string());
+
+        match action {
+            "set" => Ok(Self::Set(name, value)),
+            "prepend" => Ok(Self::Prepend(name, value)),
+            "append" => Ok(Self::Append(name, value)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for EnvironmentMutation {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+/// Defines the working directory of the process when the interpreter is initialized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serialization", serde(try_from = "String", into = "String"))]
+pub enum WorkingDirectoryPolicy {
+    /// Keep the working directory the process was started in.
+    Unchanged,
+    /// Use the directory holding the executable.
+    ExecutableDirectory,
+    /// Use a directory named after the executable in the directory holding
+    /// per-user application data.
+    UserDataDirectory,
+}
+
+impl Default for WorkingDirectoryPolicy {
+    fn default() -> Self {
+        Self::Unchanged
+    }
+}
+
+impl ToString for WorkingDirectoryPolicy {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::ExecutableDirectory => "exe",
+            Self::UserDataDirectory => "user-data",
+        }
+        .to_string()
+    }
+}
+
+impl From<WorkingDirectoryPolicy> for String {
+    fn from(v: WorkingDirectoryPolicy) -> Self {
+        v.to_string()
+    }
+}
+
+impl TryFrom<&str> for WorkingDirectoryPolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "unchanged" => Ok(Self::Unchanged),
+            "exe" => Ok(Self::ExecutableDirectory),
+            "user-data" => Ok(Self::UserDataDirectory),
+            _ => Err(format!(
+                "{} is not a valid working directory policy; expected unchanged, exe or user-data",
+                value
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for WorkingDirectoryPolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
 /// Holds configuration of a Python interpreter.
 ///
 /// This struct holds fields that are exposed by `PyPreConfig` and
@@ -18,13 +18,17 @@ pub mod module_util;
 pub mod package_metadata;
 pub mod policy;
 pub mod python_source;
+pub mod requirement;
 pub mod resource;
 pub mod resource_collection;
 #[cfg(test)]
 mod testutil;
+pub mod update_manifest;
+pub mod version;
 #[cfg(feature = "wheel")]
 pub mod wheel;
 #[cfg(feature = "wheel")]
 pub mod wheel_builder;
+pub mod wheel_tags;
 #[cfg(feature = "zip")]
 pub mod zip_app_builder;
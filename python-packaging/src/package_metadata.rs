@@ -5,7 +5,7 @@
 /*! Working with Python package metadata (i.e. .pkg-info directories) */
 
 use {
-    anyhow::{Context, Result},
+    anyhow::{anyhow, Context, Result},
     mailparse::parse_mail,
 };
 
@@ -62,6 +62,107 @@ impl PythonPackageMetadata {
     }
 }
 
+/// An entry point defined in an `entry_points.txt` file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntryPoint {
+    /// Group the entry point belongs to. e.g. `console_scripts`.
+    pub group: String,
+
+    /// Name of the entry point.
+    pub name: String,
+
+    /// Module providing the entry point.
+    pub module: String,
+
+    /// Dotted path of the object within the module, if any.
+    pub attr: Option<String>,
+
+    /// Extras required by the entry point.
+    pub extras: Vec<String>,
+}
+
+impl EntryPoint {
+    /// Parse the value of an entry point. e.g. `pkg.cli:main [extra]`.
+    pub fn from_value(group: &str, name: &str, value: &str) -> Result<Self> {
+        let (reference, extras) = match value.split_once('[') {
+            Some((reference, extras)) => (
+                reference,
+                extras
+                    .strip_suffix(']')
+                    .ok_or_else(|| anyhow!("unterminated extras in entry point: {}", value))?
+                    .split(',')
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty())
+                    .collect(),
+            ),
+            None => (value, vec![]),
+        };
+
+        let (module, attr) = match reference.split_once(':') {
+            Some((module, attr)) => (module.trim(), Some(attr.trim().to_string())),
+            None => (reference.trim(), None),
+        };
+
+        if module.is_empty() || attr.as_deref() == Some("") {
+            return Err(anyhow!("invalid entry point: {} = {}", name, value));
+        }
+
+        Ok(Self {
+            group: group.to_string(),
+            name: name.to_string(),
+            module: module.to_string(),
+            attr,
+            extras,
+        })
+    }
+
+    /// Parse the content of an `entry_points.txt` file.
+    pub fn parse_entry_points(data: &str) -> Result<Vec<Self>> {
+        let mut group = None;
+        let mut entry_points = vec![];
+
+        for line in data.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                group = Some(name.trim().to_string());
+                continue;
+            }
+
+            let group = group
+                .as_ref()
+                .ok_or_else(|| anyhow!("entry point outside of a group: {}", line))?;
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid entry point line: {}", line))?;
+
+            entry_points.push(Self::from_value(group, name.trim(), value.trim())?);
+        }
+
+        Ok(entry_points)
+    }
+
+    /// Python code calling the object this entry point references.
+    ///
+    /// Like scripts generated by installers, the process exits with the
+    /// value returned by the call. Entry points not referencing an object
+    /// within their module can't be called.
+    pub fn run_code(&self) -> Result<String> {
+        let attr = self
+            .attr
+            .as_ref()
+            .ok_or_else(|| anyhow!("entry point {} doesn't reference a callable", self.name))?;
+        let name = attr.split('.').next().unwrap_or_default();
+
+        Ok(format!(
+            "import sys\nfrom {} import {}\nsys.exit({}())\n",
+            self.module, name, attr
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,4 +200,45 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_entry_points() -> Result<()> {
+        let entry_points = EntryPoint::parse_entry_points(concat!(
+            "[console_scripts]\n",
+            "black = black:patched_main\n",
+            "blackd = blackd:patched_main [d]\n",
+            "\n",
+            "# comment\n",
+            "[gui_scripts]\n",
+            "viewer=viewer.app:App.run\n",
+            "[myapp.plugins]\n",
+            "default = myapp.plugins.default\n",
+        ))?;
+
+        assert_eq!(entry_points.len(), 4);
+        assert_eq!(
+            entry_points[1],
+            EntryPoint {
+                group: "console_scripts".to_string(),
+                name: "blackd".to_string(),
+                module: "blackd".to_string(),
+                attr: Some("patched_main".to_string()),
+                extras: vec!["d".to_string()],
+            }
+        );
+        assert_eq!(entry_points[2].group, "gui_scripts");
+        assert_eq!(entry_points[2].attr.as_deref(), Some("App.run"));
+        assert_eq!(entry_points[3].attr, None);
+
+        assert!(EntryPoint::parse_entry_points("black = black:main\n").is_err());
+        assert!(EntryPoint::from_value("console_scripts", "x", "x:").is_err());
+
+        assert_eq!(
+            entry_points[2].run_code()?,
+            "import sys\nfrom viewer.app import App\nsys.exit(App.run())\n"
+        );
+        assert!(entry_points[3].run_code().is_err());
+
+        Ok(())
+    }
 }
@@ -89,6 +89,27 @@ impl AsRef<str> for ResourceHandlingMode {
     }
 }
 
+/// Top-level packages of GPU and machine learning stacks.
+///
+/// These packages ship large extension modules and shared libraries which
+/// locate each other relative to their paths on the filesystem.
+pub const GPU_STACK_PACKAGES: &[&str] = &[
+    "cupy",
+    "cupy_backends",
+    "jax",
+    "jaxlib",
+    "keras",
+    "nvidia",
+    "onnxruntime",
+    "tensorflow",
+    "tensorrt",
+    "torch",
+    "torchaudio",
+    "torchvision",
+    "triton",
+    "xformers",
+];
+
 /// Defines how Python resources should be packaged.
 #[derive(Clone, Debug, PartialEq)]
 pub struct PythonPackagingPolicy {
@@ -175,6 +196,22 @@ pub struct PythonPackagingPolicy {
 
     /// Python modules for which bytecode should not be generated by default.
     no_bytecode_modules: HashSet<String>,
+
+    /// Python packages for which bytecode is generated at every optimization level.
+    all_optimize_levels_packages: HashSet<String>,
+
+    /// Names of compilers to apply to collected resources.
+    ///
+    /// Compilers transform source forms of assets, like gettext `.po` files,
+    /// into the forms used at run-time.
+    resource_compilers: Vec<String>,
+
+    /// Whether resources of GPU and machine learning stacks get special handling.
+    ///
+    /// Resources of packages in `GPU_STACK_PACKAGES` are added to the filesystem,
+    /// preserving their layout within the package, with their source code and
+    /// bytecode at optimization level 0 only.
+    gpu_stack_compat: bool,
 }
 
 impl Default for PythonPackagingPolicy {
@@ -199,6 +236,9 @@ impl Default for PythonPackagingPolicy {
             bytecode_optimize_level_one: false,
             bytecode_optimize_level_two: false,
             no_bytecode_modules: HashSet::new(),
+            all_optimize_levels_packages: HashSet::new(),
+            resource_compilers: vec![],
+            gpu_stack_compat: false,
         }
     }
 }
@@ -432,6 +472,90 @@ impl PythonPackagingPolicy {
         self.no_bytecode_modules.insert(name.to_string());
     }
 
+    /// Python packages registered to generate bytecode at every optimization level.
+    pub fn all_optimize_levels_packages(&self) -> &HashSet<String> {
+        &self.all_optimize_levels_packages
+    }
+
+    /// Register a Python package whose modules get bytecode at every optimization level.
+    ///
+    /// When source modules in the package or its sub-packages are added, their
+    /// default settings for adding bytecode at optimization levels 0, 1 and 2
+    /// are all true. This allows the interpreter's optimization level to be chosen
+    /// at run-time without rebuilding. Modules registered with
+    /// `register_no_bytecode_module()` still don't get bytecode.
+    pub fn register_all_optimize_levels_package(&mut self, name: &str) {
+        self.all_optimize_levels_packages.insert(name.to_string());
+    }
+
+    /// Names of compilers to apply to collected resources.
+    pub fn resource_compilers(&self) -> &[String] {
+        &self.resource_compilers
+    }
+
+    /// Set the names of compilers to apply to collected resources.
+    pub fn set_resource_compilers(&mut self, compilers: Vec<String>) {
+        self.resource_compilers = compilers;
+    }
+
+    /// Whether resources of GPU and machine learning stacks get special handling.
+    pub fn gpu_stack_compat(&self) -> bool {
+        self.gpu_stack_compat
+    }
+
+    /// Set whether resources of GPU and machine learning stacks get special handling.
+    ///
+    /// When enabled, resources of packages in `GPU_STACK_PACKAGES` are added to
+    /// a filesystem-relative location regardless of the resources location.
+    /// Python modules keep their source code and only get bytecode at
+    /// optimization level 0, as optimizations like stripping docstrings break
+    /// the lazy loaders of these packages.
+    pub fn set_gpu_stack_compat(&mut self, value: bool) {
+        self.gpu_stack_compat = value;
+    }
+
+    /// Whether a resource belongs to a package of a GPU or machine learning stack.
+    fn is_gpu_stack_resource(&self, resource: &PythonResource) -> bool {
+        let name = match resource {
+            PythonResource::ModuleSource(module) => module.name.clone(),
+            PythonResource::ModuleBytecodeRequest(module) => module.name.clone(),
+            PythonResource::ModuleBytecode(module) => module.name.clone(),
+            PythonResource::PackageResource(resource) => resource.leaf_package.clone(),
+            PythonResource::ExtensionModule(module) => module.name.clone(),
+            PythonResource::File(file) => file.path_string().replace('\\', "/"),
+            _ => return false,
+        };
+
+        let top_level = name.split(&['.', '/'][..]).next().unwrap_or_default();
+
+        GPU_STACK_PACKAGES.contains(&top_level)
+    }
+
+    /// The prefix of filesystem-relative resources.
+    ///
+    /// This is the prefix of the resources location or its fallback if either
+    /// is filesystem-relative, or `lib` otherwise.
+    fn relative_path_prefix(&self) -> String {
+        [
+            Some(&self.resources_location),
+            self.resources_location_fallback.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|location| match location {
+            ConcreteResourceLocation::RelativePath(prefix) => Some(prefix.clone()),
+            ConcreteResourceLocation::InMemory => None,
+        })
+        .unwrap_or_else(|| "lib".to_string())
+    }
+
+    /// Whether a module is in a package registered to get every optimization level.
+    fn is_all_optimize_levels_module(&self, name: &str) -> bool {
+        self.all_optimize_levels_packages
+            .iter()
+            .any(|package| name == package || name.starts_with(&format!("{}.", package)))
+    }
+
     /// Derive a `PythonResourceAddCollectionContext` for a resource using current settings.
     ///
     /// The returned object essentially says how the resource should be added
@@ -460,6 +584,8 @@ impl PythonPackagingPolicy {
             PythonResource::ModuleSource(module) => {
                 if self.no_bytecode_modules.contains(&*module.name) {
                     false
+                } else if self.is_all_optimize_levels_module(&module.name) {
+                    true
                 } else {
                     self.bytecode_optimize_level_zero
                 }
@@ -470,6 +596,8 @@ impl PythonPackagingPolicy {
             PythonResource::ModuleSource(module) => {
                 if self.no_bytecode_modules.contains(&*module.name) {
                     false
+                } else if self.is_all_optimize_levels_module(&module.name) {
+                    true
                 } else {
                     self.bytecode_optimize_level_one
                 }
@@ -480,6 +608,8 @@ impl PythonPackagingPolicy {
             PythonResource::ModuleSource(module) => {
                 if self.no_bytecode_modules.contains(&*module.name) {
                     false
+                } else if self.is_all_optimize_levels_module(&module.name) {
+                    true
                 } else {
                     self.bytecode_optimize_level_two
                 }
@@ -487,6 +617,20 @@ impl PythonPackagingPolicy {
             _ => self.bytecode_optimize_level_two,
         };
 
+        if self.gpu_stack_compat && self.is_gpu_stack_resource(resource) {
+            // These packages load shared libraries via paths relative to their
+            // files and inspect the source code of their modules.
+            return PythonResourceAddCollectionContext {
+                include,
+                location: ConcreteResourceLocation::RelativePath(self.relative_path_prefix()),
+                location_fallback: None,
+                store_source: matches!(resource, PythonResource::ModuleSource(_)),
+                optimize_level_zero,
+                optimize_level_one: false,
+                optimize_level_two: false,
+            };
+        }
+
         PythonResourceAddCollectionContext {
             include,
             location,
@@ -680,7 +824,11 @@ impl PythonPackagingPolicy {
 
 #[cfg(test)]
 mod tests {
-    use {super::*, tugger_file_manifest::File};
+    use {
+        super::*,
+        crate::resource::PythonModuleSource,
+        tugger_file_manifest::{File, FileData},
+    };
 
     #[test]
     fn test_add_collection_context_file() -> Result<()> {
@@ -700,4 +848,97 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_add_collection_context_all_optimize_levels() -> Result<()> {
+        let mut policy = PythonPackagingPolicy::default();
+        policy.register_all_optimize_levels_package("foo");
+        policy.register_no_bytecode_module("foo.nobytecode");
+
+        let module = |name: &str| -> PythonResource {
+            PythonModuleSource {
+                name: name.to_string(),
+                source: FileData::Memory(vec![]),
+                is_package: false,
+                cache_tag: "cpython-39".to_string(),
+                is_stdlib: false,
+                is_test: false,
+            }
+            .into()
+        };
+
+        for name in ["foo", "foo.bar"] {
+            let add_context = policy.derive_add_collection_context(&module(name));
+            assert!(add_context.optimize_level_zero);
+            assert!(add_context.optimize_level_one);
+            assert!(add_context.optimize_level_two);
+        }
+
+        let add_context = policy.derive_add_collection_context(&module("foobar"));
+        assert!(add_context.optimize_level_zero);
+        assert!(!add_context.optimize_level_one);
+        assert!(!add_context.optimize_level_two);
+
+        let add_context = policy.derive_add_collection_context(&module("foo.nobytecode"));
+        assert!(!add_context.optimize_level_zero);
+        assert!(!add_context.optimize_level_one);
+        assert!(!add_context.optimize_level_two);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_collection_context_gpu_stack_compat() -> Result<()> {
+        let mut policy = PythonPackagingPolicy {
+            include_file_resources: true,
+            ..Default::default()
+        };
+        policy.set_bytecode_optimize_level_two(true);
+
+        let module: PythonResource = PythonModuleSource {
+            name: "torch.nn".to_string(),
+            source: FileData::Memory(vec![]),
+            is_package: true,
+            cache_tag: "cpython-39".to_string(),
+            is_stdlib: false,
+            is_test: false,
+        }
+        .into();
+        let file: PythonResource = File::new("torch/lib/libc10.so", vec![42]).into();
+
+        let add_context = policy.derive_add_collection_context(&module);
+        assert_eq!(add_context.location, ConcreteResourceLocation::InMemory);
+        assert!(add_context.optimize_level_two);
+
+        policy.set_gpu_stack_compat(true);
+
+        let add_context = policy.derive_add_collection_context(&module);
+        assert_eq!(
+            add_context.location,
+            ConcreteResourceLocation::RelativePath("lib".to_string())
+        );
+        assert_eq!(add_context.location_fallback, None);
+        assert!(add_context.store_source);
+        assert!(add_context.optimize_level_zero);
+        assert!(!add_context.optimize_level_one);
+        assert!(!add_context.optimize_level_two);
+
+        policy.set_resources_location_fallback(Some(ConcreteResourceLocation::RelativePath(
+            "prefix".to_string(),
+        )));
+
+        let add_context = policy.derive_add_collection_context(&file);
+        assert!(add_context.include);
+        assert_eq!(
+            add_context.location,
+            ConcreteResourceLocation::RelativePath("prefix".to_string())
+        );
+        assert!(!add_context.store_source);
+
+        let add_context =
+            policy.derive_add_collection_context(&File::new("torchx/foo", vec![42]).into());
+        assert_eq!(add_context.location, ConcreteResourceLocation::InMemory);
+
+        Ok(())
+    }
 }
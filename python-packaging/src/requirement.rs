@@ -0,0 +1,516 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Python package requirements and environment markers (PEP 508). */
+
+use {
+    crate::version::{Version, VersionSpecifier, VersionSpecifiers},
+    anyhow::{anyhow, Result},
+    std::{collections::BTreeSet, str::FromStr},
+};
+
+/// Normalize a package name for comparison, as defined by PEP 503.
+pub fn canonicalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut in_separator = false;
+
+    for c in name.trim().chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !in_separator {
+                normalized.push('-');
+            }
+            in_separator = true;
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+            in_separator = false;
+        }
+    }
+
+    normalized
+}
+
+/// Values of environment marker variables for a Python interpreter.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MarkerEnvironment {
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub os_name: String,
+    pub platform_machine: String,
+    pub platform_python_implementation: String,
+    pub platform_release: String,
+    pub platform_system: String,
+    pub platform_version: String,
+    pub python_full_version: String,
+    pub python_version: String,
+    pub sys_platform: String,
+}
+
+impl MarkerEnvironment {
+    /// Resolve the value of a marker variable.
+    ///
+    /// Legacy dotted variable names are accepted.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        Some(match name {
+            "implementation_name" => &self.implementation_name,
+            "implementation_version" => &self.implementation_version,
+            "os_name" | "os.name" => &self.os_name,
+            "platform_machine" | "platform.machine" => &self.platform_machine,
+            "platform_python_implementation"
+            | "platform.python_implementation"
+            | "python_implementation" => &self.platform_python_implementation,
+            "platform_release" => &self.platform_release,
+            "platform_system" => &self.platform_system,
+            "platform_version" | "platform.version" => &self.platform_version,
+            "python_full_version" => &self.python_full_version,
+            "python_version" => &self.python_version,
+            "sys_platform" | "sys.platform" => &self.sys_platform,
+            _ => return None,
+        })
+    }
+}
+
+/// A value in a marker expression.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+/// A comparison operator in a marker expression.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MarkerOperator {
+    /// A version comparison operator. e.g. `>=`.
+    Version(&'static str),
+    In,
+    NotIn,
+}
+
+/// An environment marker. e.g. `python_version < "3.8" and os_name == "nt"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Marker {
+    Expression {
+        lhs: MarkerValue,
+        op: MarkerOperator,
+        rhs: MarkerValue,
+    },
+    And(Box<Marker>, Box<Marker>),
+    Or(Box<Marker>, Box<Marker>),
+}
+
+impl Marker {
+    /// Evaluate the marker in an environment with a set of requested extras.
+    ///
+    /// Extras must be normalized with [canonicalize_name].
+    pub fn evaluate(&self, env: &MarkerEnvironment, extras: &BTreeSet<String>) -> Result<bool> {
+        match self {
+            Self::And(a, b) => Ok(a.evaluate(env, extras)? && b.evaluate(env, extras)?),
+            Self::Or(a, b) => Ok(a.evaluate(env, extras)? || b.evaluate(env, extras)?),
+            Self::Expression { lhs, op, rhs } => {
+                // `extra` compares against every requested extra.
+                let extra_literal = match (lhs, rhs) {
+                    (MarkerValue::Variable(v), MarkerValue::Literal(l))
+                    | (MarkerValue::Literal(l), MarkerValue::Variable(v))
+                        if v == "extra" =>
+                    {
+                        Some(l)
+                    }
+                    _ => None,
+                };
+
+                if let Some(literal) = extra_literal {
+                    let contains = extras.contains(&canonicalize_name(literal));
+
+                    return match op {
+                        MarkerOperator::Version("==") => Ok(contains),
+                        MarkerOperator::Version("!=") => Ok(!contains),
+                        _ => Err(anyhow!("unsupported operator for extra marker: {:?}", op)),
+                    };
+                }
+
+                let resolve = |value: &MarkerValue| -> Result<String> {
+                    match value {
+                        MarkerValue::Literal(s) => Ok(s.clone()),
+                        MarkerValue::Variable(name) => env
+                            .get(name)
+                            .map(|s| s.to_string())
+                            .ok_or_else(|| anyhow!("unknown marker variable: {}", name)),
+                    }
+                };
+
+                let lhs = resolve(lhs)?;
+                let rhs = resolve(rhs)?;
+
+                Ok(compare(&lhs, *op, &rhs))
+            }
+        }
+    }
+}
+
+/// Compare marker values.
+///
+/// Values are compared as versions if possible and as strings otherwise.
+fn compare(lhs: &str, op: MarkerOperator, rhs: &str) -> bool {
+    let op = match op {
+        MarkerOperator::In => return rhs.contains(lhs),
+        MarkerOperator::NotIn => return !rhs.contains(lhs),
+        MarkerOperator::Version(op) => op,
+    };
+
+    if let (Ok(spec), Ok(version)) = (
+        VersionSpecifier::from_str(&format!("{}{}", op, rhs)),
+        Version::from_str(lhs),
+    ) {
+        return spec.contains(&version);
+    }
+
+    match op {
+        "==" | "===" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        "<=" => lhs <= rhs,
+        ">" => lhs > rhs,
+        ">=" => lhs >= rhs,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Value(MarkerValue),
+    Operator(MarkerOperator),
+    And,
+    Or,
+    LeftParen,
+    RightParen,
+}
+
+fn tokenize_marker(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::LeftParen);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::RightParen);
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut literal = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, q)) if q == c => break,
+                    Some((_, other)) => literal.push(other),
+                    None => return Err(anyhow!("unterminated string in marker: {}", s)),
+                }
+            }
+            tokens.push(Token::Value(MarkerValue::Literal(literal)));
+        } else if matches!(c, '<' | '>' | '=' | '!' | '~') {
+            let mut op = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if matches!(c, '<' | '>' | '=' | '!' | '~') {
+                    op.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            let op = ["===", "==", "!=", "<=", ">=", "<", ">", "~="]
+                .into_iter()
+                .find(|candidate| *candidate == op)
+                .ok_or_else(|| anyhow!("invalid operator in marker: {}", op))?;
+            tokens.push(Token::Operator(MarkerOperator::Version(op)));
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                    end = i + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+
+            let token = match &s[start..end] {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "in" if tokens.last()
+                    == Some(&Token::Value(MarkerValue::Variable("not".to_string()))) =>
+                {
+                    tokens.pop();
+                    Token::Operator(MarkerOperator::NotIn)
+                }
+                "in" => Token::Operator(MarkerOperator::In),
+                name => Token::Value(MarkerValue::Variable(name.to_string())),
+            };
+            tokens.push(token);
+        } else {
+            return Err(anyhow!("unexpected character {:?} in marker: {}", c, s));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct MarkerParser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> MarkerParser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Marker> {
+        let mut marker = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            marker = Marker::Or(Box::new(marker), Box::new(self.parse_and()?));
+        }
+
+        Ok(marker)
+    }
+
+    fn parse_and(&mut self) -> Result<Marker> {
+        let mut marker = self.parse_atom()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            marker = Marker::And(Box::new(marker), Box::new(self.parse_atom()?));
+        }
+
+        Ok(marker)
+    }
+
+    fn parse_atom(&mut self) -> Result<Marker> {
+        match self.next().cloned() {
+            Some(Token::LeftParen) => {
+                let marker = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(marker),
+                    _ => Err(anyhow!("expected `)` in marker")),
+                }
+            }
+            Some(Token::Value(lhs)) => {
+                let op = match self.next() {
+                    Some(Token::Operator(op)) => *op,
+                    _ => return Err(anyhow!("expected operator after {:?} in marker", lhs)),
+                };
+                let rhs = match self.next() {
+                    Some(Token::Value(rhs)) => rhs.clone(),
+                    _ => return Err(anyhow!("expected value after {:?} in marker", op)),
+                };
+
+                Ok(Marker::Expression { lhs, op, rhs })
+            }
+            token => Err(anyhow!("unexpected {:?} in marker", token)),
+        }
+    }
+}
+
+impl FromStr for Marker {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize_marker(s)?;
+        let mut parser = MarkerParser {
+            tokens: &tokens,
+            position: 0,
+        };
+
+        let marker = parser.parse_or()?;
+        if parser.position != tokens.len() {
+            return Err(anyhow!("unexpected trailing content in marker: {}", s));
+        }
+
+        Ok(marker)
+    }
+}
+
+/// A dependency on a Python package. e.g. `requests[socks]>=2.0; python_version >= "3.6"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Requirement {
+    /// Name of the package, as written.
+    pub name: String,
+
+    /// Requested extras, normalized.
+    pub extras: BTreeSet<String>,
+
+    /// Versions that satisfy the requirement.
+    pub specifiers: VersionSpecifiers,
+
+    /// URL the package is obtained from, for `name @ url` requirements.
+    pub url: Option<String>,
+
+    /// Condition under which the requirement applies.
+    pub marker: Option<Marker>,
+}
+
+impl Requirement {
+    /// The normalized name of the required package.
+    pub fn canonical_name(&self) -> String {
+        canonicalize_name(&self.name)
+    }
+
+    /// Whether the requirement applies in an environment with a set of extras.
+    pub fn applies(&self, env: &MarkerEnvironment, extras: &BTreeSet<String>) -> Result<bool> {
+        match &self.marker {
+            Some(marker) => marker.evaluate(env, extras),
+            None => Ok(true),
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (requirement, marker) = match s.split_once(';') {
+            Some((requirement, marker)) => (requirement, Some(Marker::from_str(marker)?)),
+            None => (s, None),
+        };
+        let requirement = requirement.trim();
+
+        let name_end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(requirement.len());
+        let name = &requirement[..name_end];
+        if name.is_empty() {
+            return Err(anyhow!("invalid requirement: {}", s));
+        }
+
+        let mut rest = requirement[name_end..].trim_start();
+
+        let mut extras = BTreeSet::new();
+        if let Some(after) = rest.strip_prefix('[') {
+            let (inner, after) = after
+                .split_once(']')
+                .ok_or_else(|| anyhow!("unterminated extras in requirement: {}", s))?;
+            extras.extend(
+                inner
+                    .split(',')
+                    .map(|extra| extra.trim())
+                    .filter(|extra| !extra.is_empty())
+                    .map(canonicalize_name),
+            );
+            rest = after.trim_start();
+        }
+
+        let (specifiers, url) = if let Some(url) = rest.strip_prefix('@') {
+            (VersionSpecifiers::default(), Some(url.trim().to_string()))
+        } else {
+            let specifiers = rest
+                .strip_prefix('(')
+                .and_then(|inner| inner.strip_suffix(')'))
+                .unwrap_or(rest);
+
+            (VersionSpecifiers::from_str(specifiers)?, None)
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            extras,
+            specifiers,
+            url,
+            marker,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux_env() -> MarkerEnvironment {
+        MarkerEnvironment {
+            implementation_name: "cpython".to_string(),
+            implementation_version: "3.9.7".to_string(),
+            os_name: "posix".to_string(),
+            platform_machine: "x86_64".to_string(),
+            platform_python_implementation: "CPython".to_string(),
+            platform_system: "Linux".to_string(),
+            python_full_version: "3.9.7".to_string(),
+            python_version: "3.9".to_string(),
+            sys_platform: "linux".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn evaluate(marker: &str, extras: &[&str]) -> bool {
+        let extras = extras.iter().map(|s| s.to_string()).collect();
+        Marker::from_str(marker)
+            .unwrap()
+            .evaluate(&linux_env(), &extras)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_canonicalize_name() {
+        assert_eq!(canonicalize_name("Foo.Bar__baz"), "foo-bar-baz");
+        assert_eq!(canonicalize_name("zope.interface"), "zope-interface");
+    }
+
+    #[test]
+    fn test_parse_requirement() -> Result<()> {
+        let req =
+            Requirement::from_str("Requests[Socks, security] >=2.0,<3 ; python_version >= '3.6'")?;
+        assert_eq!(req.name, "Requests");
+        assert_eq!(req.canonical_name(), "requests");
+        assert_eq!(
+            req.extras.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            vec!["security", "socks"]
+        );
+        assert_eq!(req.specifiers.to_string(), ">=2.0,<3");
+        assert!(req.marker.is_some());
+
+        let req = Requirement::from_str("six (>=1.0)")?;
+        assert_eq!(req.specifiers.to_string(), ">=1.0");
+
+        let req = Requirement::from_str("pip @ https://example.com/pip.whl")?;
+        assert_eq!(req.url.as_deref(), Some("https://example.com/pip.whl"));
+
+        assert!(Requirement::from_str(">=1.0").is_err());
+        assert!(Requirement::from_str("foo; os_name ==").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_markers() {
+        assert!(evaluate("python_version >= '3.6'", &[]));
+        assert!(!evaluate("python_version < '3.8'", &[]));
+        assert!(evaluate(
+            "python_version > '3.10' or sys_platform == 'linux'",
+            &[]
+        ));
+        assert!(!evaluate(
+            "os_name == 'nt' and (python_version < '3' or platform_machine == 'x86_64')",
+            &[]
+        ));
+        assert!(evaluate("'linux' in sys_platform", &[]));
+        assert!(evaluate("platform_system not in 'Windows Darwin'", &[]));
+        assert!(evaluate("platform.python_implementation == 'CPython'", &[]));
+
+        assert!(!evaluate("extra == 'socks'", &[]));
+        assert!(evaluate("extra == 'Socks'", &["socks"]));
+        assert!(evaluate(
+            "python_version >= '3' and extra == \"socks\"",
+            &["socks", "test"]
+        ));
+
+        assert!(Marker::from_str("os_name = 'nt'").is_err());
+        assert!(Marker::from_str("(os_name == 'nt'").is_err());
+        assert!(Marker::from_str("os_name not 'nt'").is_err());
+    }
+}
@@ -12,6 +12,7 @@ use {
         libpython::LibPythonBuildContext,
         location::{AbstractResourceLocation, ConcreteResourceLocation},
         module_util::{packages_from_module_name, resolve_path_for_module},
+        package_metadata::EntryPoint,
         python_source::has_dunder_file,
         resource::{
             BytecodeOptimizationLevel, PythonExtensionModule, PythonModuleBytecode,
@@ -96,6 +97,58 @@ impl PrePackagedResource {
             || self.is_extension_module
     }
 
+    /// Obtain entry points declared by this resource's distribution resources.
+    ///
+    /// Entry points are read from an `entry_points.txt` distribution resource.
+    /// Returns an empty list if there is no such resource.
+    pub fn entry_points(&self) -> Result<Vec<EntryPoint>> {
+        let data = self
+            .in_memory_distribution_resources
+            .as_ref()
+            .and_then(|resources| resources.get("entry_points.txt"))
+            .or_else(|| {
+                self.relative_path_distribution_resources
+                    .as_ref()
+                    .and_then(|resources| resources.get("entry_points.txt"))
+                    .map(|(_, data)| data)
+            });
+
+        if let Some(data) = data {
+            let data = String::from_utf8(data.resolve_content()?)
+                .with_context(|| format!("decoding entry points of {}", self.name))?;
+
+            EntryPoint::parse_entry_points(&data)
+                .with_context(|| format!("parsing entry points of {}", self.name))
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// Obtain the source code of this module, if available.
+    ///
+    /// Source code is also available if bytecode is compiled from it.
+    pub fn module_source(&self) -> Option<&FileData> {
+        self.in_memory_source
+            .as_ref()
+            .or_else(|| self.relative_path_module_source.as_ref().map(|(_, s)| s))
+            .or_else(|| {
+                [
+                    self.in_memory_bytecode.as_ref(),
+                    self.in_memory_bytecode_opt1.as_ref(),
+                    self.in_memory_bytecode_opt2.as_ref(),
+                    self.relative_path_bytecode.as_ref().map(|(_, _, b)| b),
+                    self.relative_path_bytecode_opt1.as_ref().map(|(_, _, b)| b),
+                    self.relative_path_bytecode_opt2.as_ref().map(|(_, _, b)| b),
+                ]
+                .into_iter()
+                .flatten()
+                .find_map(|bytecode| match bytecode {
+                    PythonModuleBytecodeProvider::FromSource(source) => Some(source),
+                    PythonModuleBytecodeProvider::Provided(_) => None,
+                })
+            })
+    }
+
     /// Convert the instance to a `Resource`.
     ///
     /// This will compile bytecode from source code using the specified compiler.
@@ -421,6 +474,11 @@ impl PrePackagedResource {
 /// a particular field, we populate that field in all its parent
 /// packages. If a corresponding fields is already populated, we
 /// copy its data as well.
+///
+/// Parents not defined by a module are implicit namespace packages (PEP 420),
+/// e.g. a package whose portions are provided by multiple distributions. They
+/// are marked as such, so the importer can merge them with portions outside
+/// the collection.
 pub fn populate_parent_packages(
     resources: &mut BTreeMap<String, PrePackagedResource>,
 ) -> Result<()> {
@@ -444,6 +502,10 @@ pub fn populate_parent_packages(
                     ..PrePackagedResource::default()
                 });
 
+            if !entry.is_module {
+                entry.is_namespace_package = true;
+            }
+
             // Parents must be modules + packages by definition.
             entry.is_module = true;
             entry.is_package = true;
@@ -654,9 +716,21 @@ pub struct CompiledResourcesCollection<'a> {
 }
 
 impl<'a> CompiledResourcesCollection<'a> {
-    /// Write resources to packed resources data, version 1.
+    /// Obtain an instance not borrowing from the collection it was compiled from.
+    pub fn into_owned(self) -> CompiledResourcesCollection<'static> {
+        CompiledResourcesCollection {
+            resources: self
+                .resources
+                .into_iter()
+                .map(|(name, resource)| (name, resource.to_owned()))
+                .collect(),
+            extra_files: self.extra_files,
+        }
+    }
+
+    /// Write resources to packed resources data, version 4.
     pub fn write_packed_resources<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
-        python_packed_resources::write_packed_resources_v3(
+        python_packed_resources::write_packed_resources_v4(
             &self
                 .resources
                 .values()
@@ -809,6 +883,28 @@ impl PythonResourceCollector {
         Ok(())
     }
 
+    /// Apply a filter function on package resources in this collection.
+    ///
+    /// The filter receives the name of the package and the relative name of
+    /// each resource of it, in memory or filesystem-relative. If it returns
+    /// true, the resource will be preserved.
+    pub fn filter_package_resources_mut<F>(&mut self, filter: F) -> Result<()>
+    where
+        F: Fn(&str, &str) -> bool,
+    {
+        for (name, resource) in self.resources.iter_mut() {
+            if let Some(resources) = resource.in_memory_resources.as_mut() {
+                resources.retain(|key, _| filter(name, key));
+            }
+
+            if let Some(resources) = resource.relative_path_package_resources.as_mut() {
+                resources.retain(|key, _| filter(name, key));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Obtain an iterator over the resources in this collector.
     pub fn iter_resources(&self) -> impl Iterator<Item = (&String, &PrePackagedResource)> {
         Box::new(self.resources.iter())
@@ -1885,6 +1981,83 @@ impl PythonResourceCollector {
         Ok(res)
     }
 
+    /// Move in-memory resources of packages to a filesystem relative location.
+    ///
+    /// Module source, bytecode, and package resources of the named top-level
+    /// packages and their children are moved from memory to `prefix`. This is
+    /// useful for packages which don't work when imported from memory, such
+    /// as packages relying on `__file__`.
+    ///
+    /// Extension modules and distribution resources are not moved.
+    ///
+    /// Returns the names of resources that were moved.
+    pub fn relocate_packages_to_relative_path(
+        &mut self,
+        packages: &BTreeSet<String>,
+        prefix: &str,
+        cache_tag: &str,
+    ) -> Result<BTreeSet<String>> {
+        self.check_policy(AbstractResourceLocation::RelativePath)?;
+
+        let mut res = BTreeSet::new();
+
+        for (name, resource) in self.resources.iter_mut() {
+            let top_level = name.split('.').next().unwrap_or_default();
+
+            if !resource.is_python_resource() || !packages.contains(top_level) {
+                continue;
+            }
+
+            let mut moved = false;
+
+            if let Some(source) = resource.in_memory_source.take() {
+                resource.relative_path_module_source = Some((prefix.to_string(), source));
+                moved = true;
+            }
+
+            for (in_memory, relative_path) in [
+                (
+                    &mut resource.in_memory_bytecode,
+                    &mut resource.relative_path_bytecode,
+                ),
+                (
+                    &mut resource.in_memory_bytecode_opt1,
+                    &mut resource.relative_path_bytecode_opt1,
+                ),
+                (
+                    &mut resource.in_memory_bytecode_opt2,
+                    &mut resource.relative_path_bytecode_opt2,
+                ),
+            ] {
+                if let Some(bytecode) = in_memory.take() {
+                    *relative_path = Some((prefix.to_string(), cache_tag.to_string(), bytecode));
+                    moved = true;
+                }
+            }
+
+            if let Some(resources) = resource.in_memory_resources.take() {
+                let package_path = name
+                    .split('.')
+                    .fold(PathBuf::from(prefix), |path, part| path.join(part));
+
+                resource
+                    .relative_path_package_resources
+                    .get_or_insert_with(BTreeMap::new)
+                    .extend(resources.into_iter().map(|(key, data)| {
+                        let path = package_path.join(&key);
+                        (key, (path, data))
+                    }));
+                moved = true;
+            }
+
+            if moved {
+                res.insert(name.clone());
+            }
+        }
+
+        Ok(res)
+    }
+
     /// Compiles resources into a finalized collection.
     ///
     /// This will take all resources collected so far and convert them into
@@ -2744,6 +2917,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_entry_points() -> Result<()> {
+        let mut resources = BTreeMap::new();
+        resources.insert(
+            "entry_points.txt".to_string(),
+            (
+                PathBuf::from("lib/module-1.0.dist-info/entry_points.txt"),
+                FileData::Memory(b"[console_scripts]\nfoo = module.cli:main\n".to_vec()),
+            ),
+        );
+
+        let pre = PrePackagedResource {
+            is_module: true,
+            name: "module".to_string(),
+            relative_path_distribution_resources: Some(resources),
+            ..PrePackagedResource::default()
+        };
+
+        let entry_points = pre.entry_points()?;
+        assert_eq!(entry_points.len(), 1);
+        assert_eq!(entry_points[0].group, "console_scripts");
+        assert_eq!(entry_points[0].name, "foo");
+        assert_eq!(entry_points[0].module, "module.cli");
+        assert_eq!(entry_points[0].attr, Some("main".to_string()));
+
+        assert!(PrePackagedResource::default().entry_points()?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_resource_conversion_relative_path_shared_library() -> Result<()> {
         let mut compiler = FakeBytecodeCompiler { magic_number: 42 };
@@ -2805,6 +3008,7 @@ mod tests {
                 is_module: true,
                 name: "root.parent".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 in_memory_source: Some(FileData::Memory(vec![])),
                 ..PrePackagedResource::default()
             })
@@ -2815,6 +3019,7 @@ mod tests {
                 is_module: true,
                 name: "root".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 in_memory_source: Some(FileData::Memory(vec![])),
                 ..PrePackagedResource::default()
             })
@@ -2849,6 +3054,7 @@ mod tests {
                 is_module: true,
                 name: "root.parent".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 relative_path_module_source: Some(("prefix".to_string(), FileData::Memory(vec![]))),
                 ..PrePackagedResource::default()
             })
@@ -2859,6 +3065,7 @@ mod tests {
                 is_module: true,
                 name: "root".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 relative_path_module_source: Some(("prefix".to_string(), FileData::Memory(vec![]))),
                 ..PrePackagedResource::default()
             })
@@ -2892,6 +3099,7 @@ mod tests {
                 is_module: true,
                 name: "root.parent".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 in_memory_bytecode: Some(PythonModuleBytecodeProvider::FromSource(
                     FileData::Memory(vec![])
                 )),
@@ -2904,6 +3112,7 @@ mod tests {
                 is_module: true,
                 name: "root".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 in_memory_bytecode: Some(PythonModuleBytecodeProvider::FromSource(
                     FileData::Memory(vec![])
                 )),
@@ -2938,6 +3147,7 @@ mod tests {
                 is_module: true,
                 name: "foo".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 ..PrePackagedResource::default()
             })
         );
@@ -2971,6 +3181,7 @@ mod tests {
                 is_module: true,
                 name: "foo".to_string(),
                 is_package: true,
+                is_namespace_package: true,
                 ..PrePackagedResource::default()
             })
         );
@@ -2978,6 +3189,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_populate_parent_packages_namespace_package() -> Result<()> {
+        let mut h = BTreeMap::new();
+        for name in ["ns.portion", "ns.pkg", "ns.pkg.child"] {
+            h.insert(
+                name.to_string(),
+                PrePackagedResource {
+                    is_module: true,
+                    name: name.to_string(),
+                    in_memory_source: Some(FileData::Memory(vec![42])),
+                    is_package: name != "ns.pkg.child",
+                    ..PrePackagedResource::default()
+                },
+            );
+        }
+
+        populate_parent_packages(&mut h)?;
+
+        assert_eq!(h.len(), 4);
+        assert!(h.get("ns").unwrap().is_namespace_package);
+        assert!(!h.get("ns.pkg").unwrap().is_namespace_package);
+        assert_eq!(
+            h.get("ns.pkg").unwrap().in_memory_source,
+            Some(FileData::Memory(vec![42]))
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_add_in_memory_source_module() -> Result<()> {
         let mut r = PythonResourceCollector::new(
@@ -3072,6 +3312,7 @@ mod tests {
                 is_python_module: true,
                 name: Cow::Owned("root".to_string()),
                 is_python_package: true,
+                is_python_namespace_package: true,
                 in_memory_source: Some(Cow::Owned(vec![])),
                 ..Resource::default()
             })
@@ -3082,6 +3323,7 @@ mod tests {
                 is_python_module: true,
                 name: Cow::Owned("root.parent".to_string()),
                 is_python_package: true,
+                is_python_namespace_package: true,
                 in_memory_source: Some(Cow::Owned(vec![])),
                 ..Resource::default()
             })
@@ -3148,6 +3390,7 @@ mod tests {
                 is_python_module: true,
                 name: Cow::Owned("foo".to_string()),
                 is_python_package: true,
+                is_python_namespace_package: true,
                 relative_path_module_source: Some(Cow::Owned(PathBuf::from(
                     "prefix/foo/__init__.py"
                 ))),
@@ -3573,6 +3816,7 @@ mod tests {
                 is_python_module: true,
                 name: Cow::Owned("root".to_string()),
                 is_python_package: true,
+                is_python_namespace_package: true,
                 in_memory_bytecode_opt1: Some(Cow::Owned(b"bc1".to_vec())),
                 ..Resource::default()
             })
@@ -3583,6 +3827,7 @@ mod tests {
                 is_python_module: true,
                 name: Cow::Owned("root.parent".to_string()),
                 is_python_package: true,
+                is_python_namespace_package: true,
                 in_memory_bytecode_opt1: Some(Cow::Owned(b"bc1".to_vec())),
                 ..Resource::default()
             })
@@ -4405,6 +4650,7 @@ mod tests {
                 is_python_module: true,
                 name: Cow::Owned("foo".to_string()),
                 is_python_package: true,
+                is_python_namespace_package: true,
                 ..Resource::default()
             })
         );
@@ -4735,4 +4981,198 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_module_source() -> Result<()> {
+        let source = FileData::Memory(b"import foo".to_vec());
+
+        let resource = PrePackagedResource {
+            in_memory_bytecode: Some(PythonModuleBytecodeProvider::Provided(FileData::Memory(
+                vec![42],
+            ))),
+            ..Default::default()
+        };
+        assert_eq!(resource.module_source(), None);
+
+        let resource = PrePackagedResource {
+            relative_path_bytecode: Some((
+                "lib".to_string(),
+                DEFAULT_CACHE_TAG.to_string(),
+                PythonModuleBytecodeProvider::FromSource(source.clone()),
+            )),
+            ..Default::default()
+        };
+        assert_eq!(resource.module_source(), Some(&source));
+
+        let resource = PrePackagedResource {
+            in_memory_source: Some(source.clone()),
+            ..Default::default()
+        };
+        assert_eq!(resource.module_source(), Some(&source));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocate_packages_to_relative_path() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![AbstractResourceLocation::InMemory],
+            vec![],
+            false,
+            false,
+        );
+
+        r.add_python_module_source(
+            &PythonModuleSource {
+                name: "foo".to_string(),
+                source: FileData::Memory(vec![42]),
+                is_package: true,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+        r.add_python_module_bytecode_from_source(
+            &PythonModuleBytecodeFromSource {
+                name: "foo.bar".to_string(),
+                source: FileData::Memory(vec![42]),
+                optimize_level: BytecodeOptimizationLevel::One,
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+        r.add_python_package_resource(
+            &PythonPackageResource {
+                leaf_package: "foo".to_string(),
+                relative_name: "data.txt".to_string(),
+                data: FileData::Memory(vec![42]),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+        r.add_python_module_source(
+            &PythonModuleSource {
+                name: "foobar".to_string(),
+                source: FileData::Memory(vec![42]),
+                is_package: false,
+                cache_tag: DEFAULT_CACHE_TAG.to_string(),
+                is_stdlib: false,
+                is_test: false,
+            },
+            &ConcreteResourceLocation::InMemory,
+        )?;
+
+        let packages = BTreeSet::from(["foo".to_string()]);
+
+        // The collector must allow filesystem relative resources.
+        assert!(r
+            .relocate_packages_to_relative_path(&packages, "lib", DEFAULT_CACHE_TAG)
+            .is_err());
+
+        r.allowed_locations
+            .push(AbstractResourceLocation::RelativePath);
+
+        let moved = r.relocate_packages_to_relative_path(&packages, "lib", DEFAULT_CACHE_TAG)?;
+        assert_eq!(
+            moved,
+            BTreeSet::from(["foo".to_string(), "foo.bar".to_string()])
+        );
+
+        assert_eq!(
+            r.resources.get("foo"),
+            Some(&PrePackagedResource {
+                name: "foo".to_string(),
+                is_module: true,
+                is_package: true,
+                relative_path_module_source: Some(("lib".to_string(), FileData::Memory(vec![42]))),
+                relative_path_package_resources: Some(BTreeMap::from([(
+                    "data.txt".to_string(),
+                    (
+                        PathBuf::from("lib/foo/data.txt"),
+                        FileData::Memory(vec![42])
+                    )
+                )])),
+                ..PrePackagedResource::default()
+            })
+        );
+        assert_eq!(
+            r.resources.get("foo.bar"),
+            Some(&PrePackagedResource {
+                name: "foo.bar".to_string(),
+                is_module: true,
+                relative_path_bytecode_opt1: Some((
+                    "lib".to_string(),
+                    DEFAULT_CACHE_TAG.to_string(),
+                    PythonModuleBytecodeProvider::FromSource(FileData::Memory(vec![42]))
+                )),
+                ..PrePackagedResource::default()
+            })
+        );
+        assert!(r
+            .resources
+            .get("foobar")
+            .unwrap()
+            .in_memory_source
+            .is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_package_resources_mut() -> Result<()> {
+        let mut r = PythonResourceCollector::new(
+            vec![
+                AbstractResourceLocation::InMemory,
+                AbstractResourceLocation::RelativePath,
+            ],
+            vec![],
+            false,
+            false,
+        );
+
+        for (name, location) in [
+            ("keep.txt", ConcreteResourceLocation::InMemory),
+            ("drop.txt", ConcreteResourceLocation::InMemory),
+            (
+                "sub/drop.txt",
+                ConcreteResourceLocation::RelativePath("lib".to_string()),
+            ),
+        ] {
+            r.add_python_package_resource(
+                &PythonPackageResource {
+                    leaf_package: "foo".to_string(),
+                    relative_name: name.to_string(),
+                    data: FileData::Memory(vec![42]),
+                    is_stdlib: false,
+                    is_test: false,
+                },
+                &location,
+            )?;
+        }
+
+        r.filter_package_resources_mut(|package, name| {
+            assert_eq!(package, "foo");
+            !name.ends_with("drop.txt")
+        })?;
+
+        let resource = r.resources.get("foo").unwrap();
+        assert_eq!(
+            resource.in_memory_resources,
+            Some(BTreeMap::from([(
+                "keep.txt".to_string(),
+                FileData::Memory(vec![42])
+            )]))
+        );
+        assert_eq!(
+            resource.relative_path_package_resources,
+            Some(BTreeMap::new())
+        );
+
+        Ok(())
+    }
 }
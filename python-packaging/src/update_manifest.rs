@@ -0,0 +1,257 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Manifests of update patches produced by `pyoxidizer diff-build`.
+
+An update patch is a directory transforming one build of an application
+into a newer build. It holds an `update-manifest.txt` file describing every
+file of both builds plus a `data` directory holding file content.
+
+Each line of the manifest after the `# pyoxidizer update manifest v1`
+header has the form
+`<operation> <old sha256> <new sha256> <new size> <flags> <path>`.
+Unknown values are `-`. `flags` is `x` for executable files. `path` is
+relative and `/` delimited. Operations are:
+
+`unchanged`
+   The file is identical in both builds.
+`add`
+   The file is new. `data/<path>.zst` holds its zstd compressed content.
+`patch`
+   `data/<path>.zstpatch` holds the new content zstd compressed using the
+   old content as a dictionary.
+`remove`
+   The file doesn't exist in the new build.
+
+Patches are written by the `pyoxidizer` crate and applied by the `pyembed`
+crate. Both use the types in this module to read and write manifests.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    std::path::{Component, Path, PathBuf},
+};
+
+/// Filename of the manifest in an update patch directory.
+pub const UPDATE_MANIFEST_FILENAME: &str = "update-manifest.txt";
+
+/// First line of update manifests.
+pub const UPDATE_MANIFEST_HEADER: &str = "# pyoxidizer update manifest v1";
+
+/// An operation to perform on a file when applying an update patch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UpdateOperation {
+    Unchanged,
+    Add,
+    Patch,
+    Remove,
+}
+
+impl UpdateOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unchanged => "unchanged",
+            Self::Add => "add",
+            Self::Patch => "patch",
+            Self::Remove => "remove",
+        }
+    }
+}
+
+impl TryFrom<&str> for UpdateOperation {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "unchanged" => Ok(Self::Unchanged),
+            "add" => Ok(Self::Add),
+            "patch" => Ok(Self::Patch),
+            "remove" => Ok(Self::Remove),
+            _ => Err(anyhow!("unknown update operation: {}", value)),
+        }
+    }
+}
+
+/// A file in an update manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpdateEntry {
+    /// What to do with the file.
+    pub operation: UpdateOperation,
+
+    /// Hex SHA-256 of the file in the old build.
+    pub old_sha256: Option<String>,
+
+    /// Hex SHA-256 of the file in the new build.
+    pub new_sha256: Option<String>,
+
+    /// Size of the file in the new build.
+    pub new_size: Option<u64>,
+
+    /// Whether the file in the new build is executable.
+    pub executable: bool,
+
+    /// `/` delimited path of the file relative to the build directory.
+    pub path: String,
+}
+
+impl UpdateEntry {
+    /// Parse a manifest line.
+    pub fn from_manifest_line(line: &str) -> Result<Self> {
+        let parts = line.splitn(6, ' ').collect::<Vec<_>>();
+
+        if parts.len() != 6 {
+            return Err(anyhow!(
+                "update manifest entry is not of the form <operation> <old sha256> <new sha256> <new size> <flags> <path>: {}",
+                line
+            ));
+        }
+
+        let optional = |v: &str| {
+            if v == "-" {
+                None
+            } else {
+                Some(v.to_string())
+            }
+        };
+
+        Ok(Self {
+            operation: UpdateOperation::try_from(parts[0])?,
+            old_sha256: optional(parts[1]),
+            new_sha256: optional(parts[2]),
+            new_size: optional(parts[3])
+                .map(|v| v.parse::<u64>())
+                .transpose()
+                .with_context(|| format!("parsing size in update manifest entry: {}", line))?,
+            executable: parts[4].contains('x'),
+            path: parts[5].to_string(),
+        })
+    }
+
+    /// Serialize to a manifest line.
+    pub fn to_manifest_line(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.operation.as_str(),
+            self.old_sha256.as_deref().unwrap_or("-"),
+            self.new_sha256.as_deref().unwrap_or("-"),
+            self.new_size
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            if self.executable { "x" } else { "-" },
+            self.path
+        )
+    }
+
+    /// Resolve the path of the file relative to a directory.
+    ///
+    /// Errors if the path would escape the directory.
+    pub fn resolve_in(&self, dir: &Path) -> Result<PathBuf> {
+        let relative = Path::new(&self.path);
+
+        if self.path.is_empty()
+            || !relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(anyhow!("invalid path in update manifest: {}", self.path));
+        }
+
+        Ok(dir.join(relative))
+    }
+}
+
+/// Parse the content of an update manifest.
+pub fn parse_update_manifest(data: &str) -> Result<Vec<UpdateEntry>> {
+    let mut lines = data.lines();
+
+    if lines.next() != Some(UPDATE_MANIFEST_HEADER) {
+        return Err(anyhow!("update manifest lacks expected header"));
+    }
+
+    lines
+        .filter(|line| !line.is_empty())
+        .map(UpdateEntry::from_manifest_line)
+        .collect()
+}
+
+/// Serialize entries to the content of an update manifest.
+pub fn render_update_manifest<'a>(entries: impl IntoIterator<Item = &'a UpdateEntry>) -> String {
+    let mut manifest = format!("{}\n", UPDATE_MANIFEST_HEADER);
+
+    for entry in entries {
+        manifest.push_str(&entry.to_manifest_line());
+        manifest.push('\n');
+    }
+
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_line_roundtrip() -> Result<()> {
+        let line = "patch aa bb 42 x lib/my app.so";
+        let entry = UpdateEntry::from_manifest_line(line)?;
+
+        assert_eq!(entry.operation, UpdateOperation::Patch);
+        assert_eq!(entry.old_sha256.as_deref(), Some("aa"));
+        assert_eq!(entry.new_size, Some(42));
+        assert!(entry.executable);
+        assert_eq!(entry.path, "lib/my app.so");
+        assert_eq!(entry.to_manifest_line(), line);
+
+        let entry = UpdateEntry::from_manifest_line("remove aa - - - old.txt")?;
+        assert_eq!(entry.new_sha256, None);
+        assert_eq!(entry.to_manifest_line(), "remove aa - - - old.txt");
+
+        assert!(UpdateEntry::from_manifest_line("frobnicate - - - - foo").is_err());
+        assert!(parse_update_manifest("add - - - - foo").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() -> Result<()> {
+        let entries = vec![
+            UpdateEntry {
+                operation: UpdateOperation::Unchanged,
+                old_sha256: Some("aa".to_string()),
+                new_sha256: Some("aa".to_string()),
+                new_size: Some(4),
+                executable: true,
+                path: "app".to_string(),
+            },
+            UpdateEntry {
+                operation: UpdateOperation::Add,
+                old_sha256: None,
+                new_sha256: Some("bb".to_string()),
+                new_size: Some(0),
+                executable: false,
+                path: "lib/new file.txt".to_string(),
+            },
+            UpdateEntry {
+                operation: UpdateOperation::Remove,
+                old_sha256: Some("cc".to_string()),
+                new_sha256: None,
+                new_size: None,
+                executable: false,
+                path: "removed.txt".to_string(),
+            },
+        ];
+
+        let manifest = render_update_manifest(&entries);
+        assert!(manifest.starts_with(UPDATE_MANIFEST_HEADER));
+        assert_eq!(parse_update_manifest(&manifest)?, entries);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_escape() {
+        let entry = UpdateEntry::from_manifest_line("add - aa 1 - ../outside").unwrap();
+        assert!(entry.resolve_in(Path::new("/tmp")).is_err());
+    }
+}
@@ -0,0 +1,697 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Python package versions and version specifiers (PEP 440). */
+
+use {
+    anyhow::{anyhow, Result},
+    once_cell::sync::Lazy,
+    std::{cmp::Ordering, fmt::Display, str::FromStr},
+};
+
+static RE_VERSION: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(
+        r"(?ix)
+        ^\s*v?
+        (?:(?P<epoch>[0-9]+)!)?
+        (?P<release>[0-9]+(?:\.[0-9]+)*)
+        (?:
+            [-_.]?(?P<pre_l>alpha|a|beta|b|preview|pre|c|rc)
+            [-_.]?(?P<pre_n>[0-9]+)?
+        )?
+        (?:
+            (?:-(?P<post_n1>[0-9]+))
+            |
+            (?:[-_.]?(?P<post_l>post|rev|r)[-_.]?(?P<post_n2>[0-9]+)?)
+        )?
+        (?:[-_.]?(?P<dev_l>dev)[-_.]?(?P<dev_n>[0-9]+)?)?
+        (?:\+(?P<local>[a-z0-9]+(?:[-_.][a-z0-9]+)*))?
+        \s*$",
+    )
+    .unwrap()
+});
+
+/// Kind of a pre-release.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum PreRelease {
+    Alpha,
+    Beta,
+    ReleaseCandidate,
+}
+
+impl PreRelease {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Alpha => "a",
+            Self::Beta => "b",
+            Self::ReleaseCandidate => "rc",
+        }
+    }
+}
+
+/// A segment of a local version label.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LocalSegment {
+    Number(u64),
+    String(String),
+}
+
+impl Ord for LocalSegment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Numeric segments sort after alphanumeric segments.
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.cmp(b),
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            (Self::Number(_), Self::String(_)) => Ordering::Greater,
+            (Self::String(_), Self::Number(_)) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for LocalSegment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A Python package version, as defined by PEP 440.
+///
+/// Versions are compared by their meaning rather than their spelling: `1.0`
+/// is equal to `1.0.0`.
+#[derive(Clone, Debug)]
+pub struct Version {
+    pub epoch: u64,
+    pub release: Vec<u64>,
+    pub pre: Option<(PreRelease, u64)>,
+    pub post: Option<u64>,
+    pub dev: Option<u64>,
+    pub local: Vec<LocalSegment>,
+}
+
+impl Version {
+    /// Whether this is a pre-release or development release.
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some() || self.dev.is_some()
+    }
+
+    /// Whether this is a post-release.
+    pub fn is_postrelease(&self) -> bool {
+        self.post.is_some()
+    }
+
+    /// This version without its local version label.
+    pub fn public(&self) -> Self {
+        Self {
+            local: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// This version without pre, post, development and local parts.
+    pub fn base(&self) -> Self {
+        Self {
+            epoch: self.epoch,
+            release: self.release.clone(),
+            pre: None,
+            post: None,
+            dev: None,
+            local: vec![],
+        }
+    }
+
+    /// Release segments without trailing zeros, which don't affect ordering.
+    fn release_key(&self) -> &[u64] {
+        let len = self
+            .release
+            .iter()
+            .rposition(|v| *v != 0)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        &self.release[..len]
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let captures = RE_VERSION
+            .captures(s)
+            .ok_or_else(|| anyhow!("invalid version: {}", s))?;
+
+        let number = |name: &str| -> Result<Option<u64>> {
+            captures
+                .name(name)
+                .map(|m| {
+                    m.as_str()
+                        .parse::<u64>()
+                        .map_err(|e| anyhow!("invalid version {}: {}", s, e))
+                })
+                .transpose()
+        };
+
+        let release = captures["release"]
+            .split('.')
+            .map(|v| {
+                v.parse::<u64>()
+                    .map_err(|e| anyhow!("invalid version {}: {}", s, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pre = if let Some(label) = captures.name("pre_l") {
+            let kind = match label.as_str().to_lowercase().as_str() {
+                "a" | "alpha" => PreRelease::Alpha,
+                "b" | "beta" => PreRelease::Beta,
+                _ => PreRelease::ReleaseCandidate,
+            };
+
+            Some((kind, number("pre_n")?.unwrap_or(0)))
+        } else {
+            None
+        };
+
+        let post = if captures.name("post_n1").is_some() {
+            number("post_n1")?
+        } else if captures.name("post_l").is_some() {
+            Some(number("post_n2")?.unwrap_or(0))
+        } else {
+            None
+        };
+
+        let dev = if captures.name("dev_l").is_some() {
+            Some(number("dev_n")?.unwrap_or(0))
+        } else {
+            None
+        };
+
+        let local = captures
+            .name("local")
+            .map(|m| {
+                m.as_str()
+                    .split(['-', '_', '.'])
+                    .map(|segment| match segment.parse::<u64>() {
+                        Ok(v) => LocalSegment::Number(v),
+                        Err(_) => LocalSegment::String(segment.to_lowercase()),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            epoch: number("epoch")?.unwrap_or(0),
+            release,
+            pre,
+            post,
+            dev,
+            local,
+        })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.epoch != 0 {
+            write!(f, "{}!", self.epoch)?;
+        }
+
+        let release = self
+            .release
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+        f.write_str(&release)?;
+
+        if let Some((kind, number)) = &self.pre {
+            write!(f, "{}{}", kind.as_str(), number)?;
+        }
+        if let Some(post) = &self.post {
+            write!(f, ".post{}", post)?;
+        }
+        if let Some(dev) = &self.dev {
+            write!(f, ".dev{}", dev)?;
+        }
+
+        if !self.local.is_empty() {
+            let local = self
+                .local
+                .iter()
+                .map(|segment| match segment {
+                    LocalSegment::Number(v) => v.to_string(),
+                    LocalSegment::String(s) => s.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(".");
+            write!(f, "+{}", local)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pre-release component of the sort key of a version.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum PreKey {
+    /// Development releases without a pre-release sort before pre-releases.
+    DevOnly,
+    Pre(PreRelease, u64),
+    /// Final and post-releases sort after pre-releases.
+    None,
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn pre_key(v: &Version) -> PreKey {
+            match (&v.pre, &v.post, &v.dev) {
+                (None, None, Some(_)) => PreKey::DevOnly,
+                (Some((kind, number)), _, _) => PreKey::Pre(*kind, *number),
+                _ => PreKey::None,
+            }
+        }
+
+        // A missing development release sorts after any development release.
+        fn dev_key(v: &Version) -> (bool, u64) {
+            match v.dev {
+                Some(dev) => (false, dev),
+                None => (true, 0),
+            }
+        }
+
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| self.release_key().cmp(other.release_key()))
+            .then_with(|| pre_key(self).cmp(&pre_key(other)))
+            .then_with(|| self.post.cmp(&other.post))
+            .then_with(|| dev_key(self).cmp(&dev_key(other)))
+            .then_with(|| self.local.cmp(&other.local))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl std::hash::Hash for Version {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.epoch.hash(state);
+        self.release_key().hash(state);
+        self.pre.hash(state);
+        self.post.hash(state);
+        self.dev.hash(state);
+        self.local.hash(state);
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Operator of a version specifier.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessThanEqual,
+    GreaterThanEqual,
+    LessThan,
+    GreaterThan,
+    ArbitraryEqual,
+}
+
+impl Operator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Compatible => "~=",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::LessThanEqual => "<=",
+            Self::GreaterThanEqual => ">=",
+            Self::LessThan => "<",
+            Self::GreaterThan => ">",
+            Self::ArbitraryEqual => "===",
+        }
+    }
+}
+
+/// A single version specifier. e.g. `>=1.0`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct VersionSpecifier {
+    pub operator: Operator,
+
+    /// The version being compared against.
+    ///
+    /// For `===`, this is the literal string.
+    pub version: String,
+
+    /// Whether the version ends with `.*`.
+    pub wildcard: bool,
+}
+
+impl FromStr for VersionSpecifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+
+        // Longer operators must be matched first.
+        let operator = [
+            Operator::ArbitraryEqual,
+            Operator::Compatible,
+            Operator::Equal,
+            Operator::NotEqual,
+            Operator::LessThanEqual,
+            Operator::GreaterThanEqual,
+            Operator::LessThan,
+            Operator::GreaterThan,
+        ]
+        .into_iter()
+        .find(|op| s.starts_with(op.as_str()))
+        .ok_or_else(|| anyhow!("invalid version specifier: {}", s))?;
+
+        let version = s[operator.as_str().len()..].trim();
+
+        if operator == Operator::ArbitraryEqual {
+            return Ok(Self {
+                operator,
+                version: version.to_string(),
+                wildcard: false,
+            });
+        }
+
+        let (version, wildcard) = match version.strip_suffix(".*") {
+            Some(prefix) if matches!(operator, Operator::Equal | Operator::NotEqual) => {
+                (prefix, true)
+            }
+            Some(_) => return Err(anyhow!("wildcards are only allowed with == and !=: {}", s)),
+            None => (version, false),
+        };
+
+        let parsed = Version::from_str(version)?;
+
+        if operator == Operator::Compatible && parsed.release.len() < 2 {
+            return Err(anyhow!(
+                "~= requires a version with at least 2 release segments: {}",
+                s
+            ));
+        }
+
+        Ok(Self {
+            operator,
+            version: version.to_string(),
+            wildcard,
+        })
+    }
+}
+
+impl Display for VersionSpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.operator.as_str(),
+            self.version,
+            if self.wildcard { ".*" } else { "" }
+        )
+    }
+}
+
+impl VersionSpecifier {
+    /// The parsed version of the specifier.
+    fn parsed_version(&self) -> Option<Version> {
+        Version::from_str(&self.version).ok()
+    }
+
+    /// Whether the specifier explicitly references a pre-release.
+    pub fn references_prerelease(&self) -> bool {
+        self.parsed_version()
+            .map(|v| v.is_prerelease())
+            .unwrap_or(false)
+    }
+
+    /// Whether a version satisfies this specifier.
+    pub fn contains(&self, candidate: &Version) -> bool {
+        if self.operator == Operator::ArbitraryEqual {
+            return candidate.to_string().eq_ignore_ascii_case(&self.version);
+        }
+
+        let spec = match self.parsed_version() {
+            Some(v) => v,
+            None => return false,
+        };
+
+        match self.operator {
+            Operator::Equal if self.wildcard => prefix_matches(&spec, candidate),
+            Operator::NotEqual if self.wildcard => !prefix_matches(&spec, candidate),
+            Operator::Equal => equal(&spec, candidate),
+            Operator::NotEqual => !equal(&spec, candidate),
+            Operator::Compatible => {
+                let prefix = Version {
+                    release: spec.release[..spec.release.len() - 1].to_vec(),
+                    ..spec.base()
+                };
+
+                candidate.public() >= spec && prefix_matches(&prefix, candidate)
+            }
+            Operator::LessThanEqual => candidate.public() <= spec,
+            Operator::GreaterThanEqual => candidate.public() >= spec,
+            Operator::LessThan => {
+                candidate.public() < spec
+                    // Pre-releases of the specified version aren't less than it.
+                    && (spec.is_prerelease()
+                        || !candidate.is_prerelease()
+                        || candidate.base() != spec.base())
+            }
+            Operator::GreaterThan => {
+                candidate.public() > spec
+                    // Post-releases of the specified version aren't greater than it.
+                    && (spec.is_postrelease()
+                        || !candidate.is_postrelease()
+                        || candidate.base() != spec.base())
+            }
+            Operator::ArbitraryEqual => unreachable!(),
+        }
+    }
+}
+
+/// Whether versions are equal, ignoring the candidate's local label if the
+/// specifier has none.
+fn equal(spec: &Version, candidate: &Version) -> bool {
+    if spec.local.is_empty() {
+        candidate.public() == *spec
+    } else {
+        candidate == spec
+    }
+}
+
+/// Whether a candidate version matches a `==prefix.*` specifier.
+fn prefix_matches(prefix: &Version, candidate: &Version) -> bool {
+    if prefix.epoch != candidate.epoch {
+        return false;
+    }
+
+    let mut release = candidate.release.clone();
+    if release.len() < prefix.release.len() {
+        release.resize(prefix.release.len(), 0);
+    }
+
+    if release[..prefix.release.len()] != prefix.release[..] {
+        return false;
+    }
+
+    // Remaining components of the prefix must match exactly.
+    if prefix.pre.is_some() && prefix.pre != candidate.pre {
+        return false;
+    }
+    if prefix.post.is_some() && prefix.post != candidate.post {
+        return false;
+    }
+    if prefix.dev.is_some() && prefix.dev != candidate.dev {
+        return false;
+    }
+
+    true
+}
+
+/// A set of version specifiers, all of which must match. e.g. `>=1.0,<2`.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct VersionSpecifiers(pub Vec<VersionSpecifier>);
+
+impl FromStr for VersionSpecifiers {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        Ok(Self(
+            s.split(',')
+                .map(VersionSpecifier::from_str)
+                .collect::<Result<Vec<_>>>()?,
+        ))
+    }
+}
+
+impl Display for VersionSpecifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            &self
+                .0
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+}
+
+impl VersionSpecifiers {
+    /// Whether there are no specifiers.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any specifier explicitly references a pre-release.
+    pub fn references_prerelease(&self) -> bool {
+        self.0.iter().any(|s| s.references_prerelease())
+    }
+
+    /// Whether a version satisfies all specifiers.
+    ///
+    /// Pre-releases only satisfy specifiers if `prereleases` is true or a
+    /// specifier references a pre-release.
+    pub fn contains(&self, candidate: &Version, prereleases: bool) -> bool {
+        if candidate.is_prerelease() && !prereleases && !self.references_prerelease() {
+            return false;
+        }
+
+        self.0.iter().all(|s| s.contains(candidate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::from_str(s).unwrap()
+    }
+
+    fn contains(specs: &str, version: &str) -> bool {
+        VersionSpecifiers::from_str(specs)
+            .unwrap()
+            .contains(&v(version), false)
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            v("1!2.3rc4.post5.dev6+ubuntu.1"),
+            Version {
+                epoch: 1,
+                release: vec![2, 3],
+                pre: Some((PreRelease::ReleaseCandidate, 4)),
+                post: Some(5),
+                dev: Some(6),
+                local: vec![
+                    LocalSegment::String("ubuntu".to_string()),
+                    LocalSegment::Number(1)
+                ],
+            }
+        );
+
+        // Normalization.
+        assert_eq!(v("1.0-1"), v("1.0.post1"));
+        assert_eq!(v("1.0alpha"), v("1.0a0"));
+        assert_eq!(v("v1.0.PREVIEW2"), v("1.0rc2"));
+        assert_eq!(v("1.0-dev"), v("1.0.dev0"));
+        assert_eq!(v("1.0c1").to_string(), "1.0rc1");
+        assert_eq!(v("1.0.0-r2").to_string(), "1.0.0.post2");
+
+        assert!(Version::from_str("1.0foo").is_err());
+        assert!(Version::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        let ordered = [
+            "1.0.dev456",
+            "1.0a1",
+            "1.0a2.dev456",
+            "1.0a12.dev456",
+            "1.0a12",
+            "1.0b1.dev456",
+            "1.0b2",
+            "1.0b2.post345.dev456",
+            "1.0b2.post345",
+            "1.0rc1.dev456",
+            "1.0rc1",
+            "1.0",
+            "1.0+abc.5",
+            "1.0+abc.7",
+            "1.0+5",
+            "1.0.post456.dev34",
+            "1.0.post456",
+            "1.1.dev1",
+            "1!0.1",
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+
+        assert_eq!(v("1.0").cmp(&v("1.0.0")), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_specifiers() {
+        assert!(contains(">=1.0,<2", "1.5"));
+        assert!(!contains(">=1.0,<2", "2.0"));
+        assert!(!contains(">=1.0,<2", "2.0rc1"));
+        assert!(!contains("<2", "2.0.dev1"));
+        assert!(contains("<2rc1", "2.0b1"));
+
+        assert!(contains("==1.2.*", "1.2.7"));
+        assert!(contains("==1.2.*", "1.2"));
+        assert!(!contains("==1.2.*", "1.3"));
+        assert!(contains("!=1.2.*", "1.3"));
+
+        assert!(contains("==1.0", "1.0.0"));
+        assert!(contains("==1.0", "1.0+local"));
+        assert!(!contains("==1.0+a", "1.0+b"));
+        assert!(contains("!=1.0", "1.0.post1"));
+
+        assert!(contains("~=2.2", "2.9"));
+        assert!(!contains("~=2.2", "3.0"));
+        assert!(contains("~=1.4.5", "1.4.9"));
+        assert!(!contains("~=1.4.5", "1.5.0"));
+
+        assert!(!contains(">1.7", "1.7.post2"));
+        assert!(contains(">1.7.post1", "1.7.post2"));
+        assert!(!contains(">1.7", "1.7+local"));
+
+        assert!(contains("===1.0", "1.0"));
+        assert!(!contains("===1.0", "1.0.0"));
+
+        // Pre-releases are only included if requested.
+        assert!(!contains(">=1.0", "2.0b1"));
+        assert!(contains(">=1.0b1", "2.0b1"));
+        assert!(VersionSpecifiers::from_str(">=1.0")
+            .unwrap()
+            .contains(&v("2.0b1"), true));
+        assert!(contains("", "1.0"));
+
+        assert!(VersionSpecifiers::from_str("~=1").is_err());
+        assert!(VersionSpecifiers::from_str(">=1.*").is_err());
+        assert!(VersionSpecifiers::from_str("1.0").is_err());
+    }
+}
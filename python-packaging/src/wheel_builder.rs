@@ -13,13 +13,31 @@ use {
         io::{Seek, Write},
         path::{Path, PathBuf},
     },
-    tugger_file_manifest::{FileEntry, FileManifest},
+    tugger_file_manifest::{source_date_epoch, FileEntry, FileManifest},
 };
 
 /// Wheel filename component escape regular expression.
 static RE_FILENAME_ESCAPE: Lazy<regex::Regex> =
     Lazy::new(|| regex::Regex::new(r"[^\w\d.]+").unwrap());
 
+/// The earliest time representable in zip archives: 1980-01-01T00:00:00Z.
+const ZIP_EPOCH_SECONDS: i64 = 315532800;
+
+/// Resolve the default modified time of files in zip archives we produce.
+///
+/// Honors `SOURCE_DATE_EPOCH`, clamping it to the earliest time zip archives can
+/// represent. Otherwise the current time is used.
+pub(crate) fn default_modified_time() -> time::OffsetDateTime {
+    if let Some(epoch) = source_date_epoch() {
+        time::OffsetDateTime::from(epoch).max(
+            time::OffsetDateTime::from_unix_timestamp(ZIP_EPOCH_SECONDS)
+                .expect("zip epoch should be valid"),
+        )
+    } else {
+        time::OffsetDateTime::now_utc()
+    }
+}
+
 /// Define and build a Python wheel from raw components.
 ///
 /// Python wheels are glorified zip files with some special files
@@ -110,7 +128,8 @@ static RE_FILENAME_ESCAPE: Lazy<regex::Regex> =
 /// For separate builder instances, content can be made identical by calling
 /// [Self::set_modified_time] to set the modified time and using identical input
 /// settings/files. (The modified time of files in zip files defaults to the time
-/// when the builder instance was created, which is obviously not deterministic.)
+/// defined by the `SOURCE_DATE_EPOCH` environment variable or the time when the
+/// builder instance was created, which is obviously not deterministic.)
 ///
 /// # Validation
 ///
@@ -165,7 +184,7 @@ impl WheelBuilder {
             generator: "rust-python-packaging".to_string(),
             root_is_purelib: false,
             manifest: FileManifest::default(),
-            modified_time: time::OffsetDateTime::now_utc(),
+            modified_time: default_modified_time(),
         }
     }
 
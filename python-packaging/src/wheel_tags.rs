@@ -0,0 +1,348 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Wheel filenames and compatibility tags (PEP 425).
+
+Compatible tags are computed from a description of the target interpreter
+instead of by inspecting a running interpreter. This allows selecting
+wheels for platforms other than the current one.
+*/
+
+use {
+    crate::version::Version,
+    anyhow::{anyhow, Result},
+    std::{fmt::Display, str::FromStr},
+};
+
+/// A compatibility tag. e.g. `cp39-cp39-manylinux2014_x86_64`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WheelTag {
+    pub python: String,
+    pub abi: String,
+    pub platform: String,
+}
+
+impl WheelTag {
+    pub fn new(python: &str, abi: &str, platform: &str) -> Self {
+        Self {
+            python: python.to_string(),
+            abi: abi.to_string(),
+            platform: platform.to_string(),
+        }
+    }
+}
+
+impl Display for WheelTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.python, self.abi, self.platform)
+    }
+}
+
+/// The components of a wheel filename.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WheelFilename {
+    /// Distribution name, with `-` escaped as `_`.
+    pub distribution: String,
+    pub version: Version,
+    pub build: Option<String>,
+
+    /// Python tags. A filename may declare multiple, separated by `.`.
+    pub python_tags: Vec<String>,
+    pub abi_tags: Vec<String>,
+    pub platform_tags: Vec<String>,
+}
+
+impl FromStr for WheelFilename {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let stem = s
+            .strip_suffix(".whl")
+            .ok_or_else(|| anyhow!("not a wheel filename: {}", s))?;
+
+        let parts = stem.split('-').collect::<Vec<_>>();
+        let (distribution, version, build, tags) = match parts.as_slice() {
+            [distribution, version, python, abi, platform] => {
+                (distribution, version, None, [python, abi, platform])
+            }
+            [distribution, version, build, python, abi, platform] => {
+                if !build.starts_with(|c: char| c.is_ascii_digit()) {
+                    return Err(anyhow!("invalid build tag in wheel filename: {}", s));
+                }
+
+                (
+                    distribution,
+                    version,
+                    Some(build.to_string()),
+                    [python, abi, platform],
+                )
+            }
+            _ => return Err(anyhow!("invalid wheel filename: {}", s)),
+        };
+
+        let split = |tags: &str| tags.split('.').map(|t| t.to_string()).collect::<Vec<_>>();
+
+        Ok(Self {
+            distribution: distribution.to_string(),
+            version: Version::from_str(version)?,
+            build,
+            python_tags: split(tags[0]),
+            abi_tags: split(tags[1]),
+            platform_tags: split(tags[2]),
+        })
+    }
+}
+
+impl WheelFilename {
+    /// All tags the wheel is compatible with.
+    pub fn tags(&self) -> Vec<WheelTag> {
+        let mut tags = vec![];
+
+        for python in &self.python_tags {
+            for abi in &self.abi_tags {
+                for platform in &self.platform_tags {
+                    tags.push(WheelTag::new(python, abi, platform));
+                }
+            }
+        }
+
+        tags
+    }
+
+    /// The preference of the wheel given compatible tags, most preferred first.
+    ///
+    /// Returns the index of the most preferred tag the wheel supports, or
+    /// `None` if the wheel isn't compatible.
+    pub fn tag_priority(&self, compatible: &[WheelTag]) -> Option<usize> {
+        let tags = self.tags();
+
+        compatible.iter().position(|tag| tags.contains(tag))
+    }
+}
+
+/// Platform tags for Linux distributions using glibc, most preferred first.
+///
+/// `glibc_minor` is the minor version of the oldest glibc 2 supported by
+/// the target.
+pub fn manylinux_platforms(arch: &str, glibc_minor: u32) -> Vec<String> {
+    let mut platforms = vec![];
+
+    // manylinux tags only exist for glibc 2.5 and newer.
+    for minor in (5..=glibc_minor).rev() {
+        platforms.push(format!("manylinux_2_{}_{}", minor, arch));
+
+        let legacy = match minor {
+            17 => Some("manylinux2014"),
+            12 => Some("manylinux2010"),
+            5 => Some("manylinux1"),
+            _ => None,
+        };
+
+        // Legacy aliases only exist for the architectures they were defined for.
+        if let Some(legacy) = legacy {
+            if legacy == "manylinux2014" || matches!(arch, "x86_64" | "i686") {
+                platforms.push(format!("{}_{}", legacy, arch));
+            }
+        }
+    }
+
+    platforms.push(format!("linux_{}", arch));
+
+    platforms
+}
+
+/// Platform tags for macOS, most preferred first.
+///
+/// `(major, minor)` is the oldest macOS version supported by the target.
+pub fn macos_platforms(arch: &str, version: (u32, u32)) -> Vec<String> {
+    let formats: &[&str] = match arch {
+        "arm64" => &["arm64", "universal2"],
+        "x86_64" => &[
+            "x86_64",
+            "intel",
+            "fat64",
+            "fat32",
+            "universal2",
+            "universal",
+        ],
+        _ => &[],
+    };
+
+    let mut versions = vec![];
+
+    // Since macOS 11, only major versions are significant.
+    if version.0 >= 11 {
+        for major in (11..=version.0).rev() {
+            versions.push((major, 0));
+        }
+    }
+
+    // Apple Silicon was never supported by macOS 10.
+    if arch == "x86_64" {
+        let minor = if version.0 >= 11 { 16 } else { version.1 };
+
+        for minor in (4..=minor).rev() {
+            versions.push((10, minor));
+        }
+    }
+
+    let mut platforms = vec![];
+    for (major, minor) in versions {
+        for format in formats {
+            platforms.push(format!("macosx_{}_{}_{}", major, minor, format));
+        }
+    }
+
+    platforms
+}
+
+/// Tags compatible with a CPython interpreter, most preferred first.
+///
+/// `abi` is the interpreter's ABI tag. e.g. `cp39` or `cp37m`. `platforms`
+/// are compatible platform tags, most preferred first.
+///
+/// This mirrors the ordering used by pip.
+pub fn cpython_compatible_tags(
+    python_version: (u32, u32),
+    abi: &str,
+    platforms: &[String],
+) -> Vec<WheelTag> {
+    let (major, minor) = python_version;
+    let interpreter = format!("cp{}{}", major, minor);
+
+    let mut tags = vec![];
+
+    for platform in platforms {
+        tags.push(WheelTag::new(&interpreter, abi, platform));
+    }
+    for platform in platforms {
+        tags.push(WheelTag::new(&interpreter, "abi3", platform));
+    }
+    for platform in platforms {
+        tags.push(WheelTag::new(&interpreter, "none", platform));
+    }
+
+    // The stable ABI is forward compatible from Python 3.2.
+    for older in (2..minor).rev() {
+        for platform in platforms {
+            tags.push(WheelTag::new(
+                &format!("cp{}{}", major, older),
+                "abi3",
+                platform,
+            ));
+        }
+    }
+
+    let py_versions = std::iter::once(format!("py{}{}", major, minor))
+        .chain(std::iter::once(format!("py{}", major)))
+        .chain(
+            (0..minor)
+                .rev()
+                .map(|older| format!("py{}{}", major, older)),
+        )
+        .collect::<Vec<_>>();
+
+    for python in &py_versions {
+        for platform in platforms {
+            tags.push(WheelTag::new(python, "none", platform));
+        }
+    }
+
+    tags.push(WheelTag::new(&interpreter, "none", "any"));
+    for python in &py_versions {
+        tags.push(WheelTag::new(python, "none", "any"));
+    }
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wheel_filename() -> Result<()> {
+        let wheel = WheelFilename::from_str("six-1.16.0-py2.py3-none-any.whl")?;
+        assert_eq!(wheel.distribution, "six");
+        assert_eq!(wheel.version.to_string(), "1.16.0");
+        assert_eq!(wheel.build, None);
+        assert_eq!(
+            wheel.tags(),
+            vec![
+                WheelTag::new("py2", "none", "any"),
+                WheelTag::new("py3", "none", "any")
+            ]
+        );
+
+        let wheel = WheelFilename::from_str(
+            "numpy-1.21.2-1-cp39-cp39-manylinux_2_12_x86_64.manylinux2010_x86_64.whl",
+        )?;
+        assert_eq!(wheel.build.as_deref(), Some("1"));
+        assert_eq!(wheel.platform_tags.len(), 2);
+
+        assert!(WheelFilename::from_str("six-1.16.0.tar.gz").is_err());
+        assert!(WheelFilename::from_str("six-1.16.0-none-any.whl").is_err());
+        assert!(WheelFilename::from_str("six-1.16.0-x-py3-none-any.whl").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_manylinux_platforms() {
+        let platforms = manylinux_platforms("x86_64", 17);
+        assert_eq!(platforms[0], "manylinux_2_17_x86_64");
+        assert_eq!(platforms[1], "manylinux2014_x86_64");
+        assert!(platforms.contains(&"manylinux2010_x86_64".to_string()));
+        assert_eq!(platforms[platforms.len() - 2], "manylinux1_x86_64");
+        assert_eq!(platforms[platforms.len() - 1], "linux_x86_64");
+
+        let platforms = manylinux_platforms("aarch64", 17);
+        assert!(platforms.contains(&"manylinux2014_aarch64".to_string()));
+        assert!(!platforms.contains(&"manylinux1_aarch64".to_string()));
+    }
+
+    #[test]
+    fn test_macos_platforms() {
+        let platforms = macos_platforms("arm64", (11, 0));
+        assert_eq!(
+            platforms,
+            vec!["macosx_11_0_arm64", "macosx_11_0_universal2"]
+        );
+
+        let platforms = macos_platforms("x86_64", (10, 9));
+        assert_eq!(platforms[0], "macosx_10_9_x86_64");
+        assert!(platforms.contains(&"macosx_10_6_intel".to_string()));
+        assert!(!platforms.contains(&"macosx_10_10_x86_64".to_string()));
+        assert!(!platforms.contains(&"macosx_10_3_x86_64".to_string()));
+    }
+
+    #[test]
+    fn test_cpython_compatible_tags() -> Result<()> {
+        let tags = cpython_compatible_tags((3, 9), "cp39", &["win_amd64".to_string()]);
+
+        let position = |tag: &str| {
+            let parts = tag.split('-').collect::<Vec<_>>();
+            tags.iter()
+                .position(|t| *t == WheelTag::new(parts[0], parts[1], parts[2]))
+        };
+
+        assert_eq!(position("cp39-cp39-win_amd64"), Some(0));
+        assert!(position("cp39-abi3-win_amd64") < position("cp36-abi3-win_amd64"));
+        assert!(position("cp36-abi3-win_amd64") < position("py3-none-win_amd64"));
+        assert!(position("py3-none-win_amd64") < position("cp39-none-any"));
+        assert!(position("cp39-none-any") < position("py3-none-any"));
+        assert!(position("py3-none-any") < position("py30-none-any"));
+        assert_eq!(position("cp38-cp38-win_amd64"), None);
+        assert_eq!(position("cp39-cp39-win32"), None);
+
+        let universal = WheelFilename::from_str("six-1.16.0-py2.py3-none-any.whl")?;
+        let native = WheelFilename::from_str("foo-1.0-cp39-cp39-win_amd64.whl")?;
+        let linux = WheelFilename::from_str("foo-1.0-cp39-cp39-manylinux1_x86_64.whl")?;
+        assert!(native.tag_priority(&tags) < universal.tag_priority(&tags));
+        assert!(universal.tag_priority(&tags).is_some());
+        assert_eq!(linux.tag_priority(&tags), None);
+
+        Ok(())
+    }
+}
@@ -9,6 +9,7 @@ use {
         bytecode::{CompileMode, PythonBytecodeCompiler},
         module_util::resolve_path_for_module,
         resource::{BytecodeOptimizationLevel, PythonModuleBytecode, PythonModuleSource},
+        wheel_builder::default_modified_time,
     },
     anyhow::{anyhow, Context, Result},
     std::{
@@ -55,7 +56,7 @@ impl Default for ZipAppBuilder {
             interpreter: None,
             manifest: FileManifest::default(),
             compression_method: CompressionMethod::Stored,
-            modified_time: time::OffsetDateTime::now_utc(),
+            modified_time: default_modified_time(),
             compiler: None,
             optimize_level: BytecodeOptimizationLevel::Zero,
         }
@@ -0,0 +1,23 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    python_packed_resources::{load_resources, packed_resources_length},
+};
+
+// Parsing arbitrary data must return errors instead of panicking.
+fuzz_target!(|data: &[u8]| {
+    let _ = packed_resources_length(data);
+
+    if let Ok(resources) = load_resources(data) {
+        for resource in resources {
+            if resource.is_err() {
+                break;
+            }
+        }
+    }
+});
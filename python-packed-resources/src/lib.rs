@@ -31,8 +31,14 @@ mod serialization;
 mod writer;
 
 pub use crate::{
-    parser::{load_resources, ResourceParserIterator},
+    parser::{
+        load_resources, packed_resources_length, packed_resources_version, ParseError,
+        ResourceParserIterator,
+    },
     resource::Resource,
-    serialization::HEADER_V3,
-    writer::write_packed_resources_v3,
+    serialization::{
+        BlobInteriorPadding, GLOBAL_HEADER_V4_LENGTH, HEADER_MAGIC, HEADER_V3, HEADER_V4,
+        MAXIMUM_SUPPORTED_VERSION, MINIMUM_SUPPORTED_VERSION,
+    },
+    writer::{write_packed_resources_v3, write_packed_resources_v4},
 };
@@ -2,12 +2,21 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-/*! Parsing of packed resources data blobs. */
+/*! Parsing of packed resources data blobs.
+
+Data may come from untrusted sources, e.g. files next to an executable. So
+every length and count read from the data is checked against the data
+actually present and parsing failures are reported as [ParseError] instead
+of panicking.
+*/
 
 use {
     crate::{
         resource::Resource,
-        serialization::{BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3},
+        serialization::{
+            BlobInteriorPadding, BlobSectionField, ResourceField, GLOBAL_HEADER_V4_LENGTH,
+            HEADER_MAGIC, HEADER_V4, MAXIMUM_SUPPORTED_VERSION, MINIMUM_SUPPORTED_VERSION,
+        },
     },
     byteorder::{LittleEndian, ReadBytesExt},
     std::{borrow::Cow, collections::HashMap, io::Cursor, path::Path},
@@ -18,6 +27,67 @@ use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
 #[cfg(windows)]
 use {std::ffi::OsString, std::os::windows::ffi::OsStringExt, std::path::PathBuf};
 
+/// Length of the global header following the magic header in version 3.
+const GLOBAL_HEADER_V3_LENGTH: usize = 1 + 4 + 4 + 4;
+
+/// Field type holding the resource flavor in version 1.
+///
+/// Later versions express the flavor with individual fields.
+const LEGACY_FLAVOR_FIELD: u8 = 0x02;
+
+/// Error parsing packed resources data.
+///
+/// Errors hold static messages so they are cheap to construct: we once
+/// attempted to switch to anyhow for error handling and this decreased
+/// performance by ~15%.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// The data doesn't begin with a known header.
+    UnrecognizedFormat,
+
+    /// The data is in a format version newer than this crate can parse.
+    NewerVersion(u8),
+
+    /// The data ends before a value that should be present.
+    Truncated(&'static str),
+
+    /// A value isn't valid.
+    InvalidValue(&'static str),
+
+    /// Values in the data contradict each other.
+    Malformed(&'static str),
+}
+
+impl ParseError {
+    /// Describe the error.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::UnrecognizedFormat => "unrecognized file format",
+            Self::NewerVersion(_) => {
+                "resources produced by newer pyoxidizer: packed resources format version is \
+                 newer than the versions this parser supports"
+            }
+            Self::Truncated(message) | Self::InvalidValue(message) | Self::Malformed(message) => {
+                message
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for &'static str {
+    fn from(e: ParseError) -> Self {
+        e.message()
+    }
+}
+
 /// Represents a blob section in the blob index.
 #[derive(Debug)]
 struct BlobSection {
@@ -30,6 +100,8 @@ struct BlobSection {
 #[derive(Clone, Copy, Debug)]
 struct BlobSectionReadState {
     offset: usize,
+    /// Offset where the blob section ends.
+    end: usize,
     interior_padding: BlobInteriorPadding,
 }
 
@@ -40,16 +112,39 @@ struct BlobSectionReadState {
 pub struct ResourceParserIterator<'a> {
     done: bool,
     data: &'a [u8],
+    /// Reads the indices. Its data ends where the resources index ends.
     reader: Cursor<&'a [u8]>,
     blob_sections: [Option<BlobSectionReadState>; 256],
     claimed_resources_count: usize,
+    expected_resources_count: usize,
     read_resources_count: usize,
+    /// Version of the data format being parsed.
+    version: u8,
+    /// Offset where the current resource entry ends, if declared.
+    entry_end: Option<usize>,
 }
 
 impl<'a> ResourceParserIterator<'a> {
     /// The expected number of resources we will emit.
+    ///
+    /// This is the count advertised by the data, bounded by the number of
+    /// resources the resources index can hold. So it can be used to
+    /// preallocate.
     pub fn expected_resources_count(&self) -> usize {
-        self.claimed_resources_count
+        self.expected_resources_count
+    }
+
+    /// Bound a count of index elements by the remaining index data.
+    ///
+    /// Each element occupies at least `element_length` bytes of index.
+    fn bounded_count(&self, count: usize, element_length: usize) -> usize {
+        let remaining = self
+            .reader
+            .get_ref()
+            .len()
+            .saturating_sub(self.reader.position() as usize);
+
+        count.min(remaining / element_length)
     }
 
     /// Resolve a slice to an individual blob's data.
@@ -57,42 +152,82 @@ impl<'a> ResourceParserIterator<'a> {
     /// This accepts a reference to the original blobs payload, an array of
     /// current blob section offsets, the resource field being accessed, and the
     /// length of the blob and returns a slice to that blob.
-    fn resolve_blob_data(&mut self, resource_field: ResourceField, length: usize) -> &'a [u8] {
-        let mut state = self.blob_sections[resource_field as usize]
+    ///
+    /// An error is returned if the blob isn't within the bounds of its blob
+    /// section or of the data.
+    fn resolve_blob_data(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<&'a [u8], ParseError> {
+        let state = self.blob_sections[resource_field as usize]
             .as_mut()
-            .expect("blob state not found");
-
-        let blob = &self.data[state.offset..state.offset + length];
-
-        let increment = match &state.interior_padding {
-            BlobInteriorPadding::None => length,
-            BlobInteriorPadding::Null => length + 1,
+            .ok_or(ParseError::Malformed("blob state not found"))?;
+
+        let end = state
+            .offset
+            .checked_add(length)
+            .filter(|end| *end <= state.end)
+            .ok_or(ParseError::Truncated("blob data out of bounds"))?;
+        let blob = self
+            .data
+            .get(state.offset..end)
+            .ok_or(ParseError::Truncated("blob data out of bounds"))?;
+
+        state.offset = match &state.interior_padding {
+            BlobInteriorPadding::None => end,
+            BlobInteriorPadding::Null => end + 1,
         };
 
-        state.offset += increment;
+        Ok(blob)
+    }
 
-        blob
+    /// Resolve a blob holding a UTF-8 string.
+    fn resolve_str(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+        error: &'static str,
+    ) -> Result<&'a str, ParseError> {
+        std::str::from_utf8(self.resolve_blob_data(resource_field, length)?)
+            .map_err(|_| ParseError::InvalidValue(error))
     }
 
     #[cfg(unix)]
-    fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
-        let path_str = OsStr::from_bytes(self.resolve_blob_data(resource_field, length));
-        Cow::Borrowed(Path::new(path_str))
+    fn resolve_path(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, Path>, ParseError> {
+        let path_str = OsStr::from_bytes(self.resolve_blob_data(resource_field, length)?);
+        Ok(Cow::Borrowed(Path::new(path_str)))
     }
 
     #[cfg(windows)]
-    fn resolve_path(&mut self, resource_field: ResourceField, length: usize) -> Cow<'a, Path> {
-        let raw = self.resolve_blob_data(resource_field, length);
-        let raw = unsafe { std::slice::from_raw_parts(raw.as_ptr() as *const u16, raw.len() / 2) };
+    fn resolve_path(
+        &mut self,
+        resource_field: ResourceField,
+        length: usize,
+    ) -> Result<Cow<'a, Path>, ParseError> {
+        let raw = self.resolve_blob_data(resource_field, length)?;
+        if raw.len() % 2 != 0 {
+            return Err(ParseError::InvalidValue(
+                "path length isn't a multiple of 2",
+            ));
+        }
 
-        // There isn't an API that lets us get a OsStr from &[u16]. So we need to use
-        // owned types.
-        let path_string = OsString::from_wide(raw);
+        // Blob data isn't necessarily aligned for u16. And there isn't an API
+        // that lets us get a OsStr from &[u16]. So we need to use owned types.
+        let raw = raw
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>();
+        let path_string = OsString::from_wide(&raw);
 
-        Cow::Owned(PathBuf::from(path_string))
+        Ok(Cow::Owned(PathBuf::from(path_string)))
     }
 
-    fn parse_next(&mut self) -> Result<Option<Resource<'a, u8>>, &'static str> {
+    fn parse_next(&mut self) -> Result<Option<Resource<'a, u8>>, ParseError> {
         let mut current_resource = Resource::default();
         let mut current_resource_name = None;
 
@@ -100,16 +235,34 @@ impl<'a> ResourceParserIterator<'a> {
             let field_type = self
                 .reader
                 .read_u8()
-                .map_err(|_| "failed reading field type")?;
+                .map_err(|_| ParseError::Truncated("failed reading field type"))?;
+
+            if self.version == 1 && field_type == LEGACY_FLAVOR_FIELD {
+                let flavor = self
+                    .reader
+                    .read_u8()
+                    .map_err(|_| ParseError::Truncated("failed reading resource flavor"))?;
+                apply_legacy_flavor(&mut current_resource, flavor)?;
+                continue;
+            }
+
+            let field_type =
+                ResourceField::try_from(field_type).map_err(ParseError::InvalidValue)?;
 
-            let field_type = ResourceField::try_from(field_type)?;
+            if field_type.introduced_version() > self.version {
+                return Err(ParseError::InvalidValue(
+                    "field type not defined in format version",
+                ));
+            }
 
             match field_type {
                 ResourceField::EndOfIndex => {
                     self.done = true;
 
                     if self.read_resources_count != self.claimed_resources_count {
-                        return Err("mismatch between advertised index count and actual");
+                        return Err(ParseError::Malformed(
+                            "mismatch between advertised index count and actual",
+                        ));
                     }
 
                     return Ok(None);
@@ -118,26 +271,47 @@ impl<'a> ResourceParserIterator<'a> {
                     self.read_resources_count += 1;
                     current_resource = Resource::default();
                     current_resource_name = None;
+
+                    if self.version >= 4 {
+                        let l = self
+                            .reader
+                            .read_u32::<LittleEndian>()
+                            .map_err(|_| ParseError::Truncated("failed reading entry length"))?
+                            as usize;
+
+                        let end = (self.reader.position() as usize)
+                            .checked_add(l)
+                            .filter(|end| *end <= self.reader.get_ref().len())
+                            .ok_or(ParseError::Truncated("resource entry out of bounds"))?;
+
+                        self.entry_end = Some(end);
+                    }
                 }
                 ResourceField::EndOfEntry => {
+                    if let Some(end) = self.entry_end.take() {
+                        if self.reader.position() as usize != end {
+                            return Err(ParseError::Malformed(
+                                "mismatch between resource entry length and actual",
+                            ));
+                        }
+                    }
+
                     let res = if current_resource_name.is_some() {
                         Ok(Some(current_resource))
                     } else {
-                        Err("resource name field is required")
+                        Err(ParseError::Malformed("resource name field is required"))
                     };
 
                     return res;
                 }
                 ResourceField::Name => {
-                    let l = self
-                        .reader
-                        .read_u16::<LittleEndian>()
-                        .map_err(|_| "failed reading resource name length")?
-                        as usize;
+                    let l =
+                        self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                            ParseError::Truncated("failed reading resource name length")
+                        })? as usize;
 
-                    let name = unsafe {
-                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
-                    };
+                    let name =
+                        self.resolve_str(field_type, l, "resource name isn't valid UTF-8")?;
 
                     current_resource_name = Some(name);
                     current_resource.name = Cow::Borrowed(name);
@@ -152,82 +326,79 @@ impl<'a> ResourceParserIterator<'a> {
                     let l = self
                         .reader
                         .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading source length")?
+                        .map_err(|_| ParseError::Truncated("failed reading source length"))?
                         as usize;
 
                     current_resource.in_memory_source =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryBytecode => {
                     let l = self
                         .reader
                         .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading bytecode length")?
+                        .map_err(|_| ParseError::Truncated("failed reading bytecode length"))?
                         as usize;
 
                     current_resource.in_memory_bytecode =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryBytecodeOpt1 => {
                     let l = self
                         .reader
                         .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading bytecode length")?
+                        .map_err(|_| ParseError::Truncated("failed reading bytecode length"))?
                         as usize;
 
                     current_resource.in_memory_bytecode_opt1 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryBytecodeOpt2 => {
                     let l = self
                         .reader
                         .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading bytecode length")?
+                        .map_err(|_| ParseError::Truncated("failed reading bytecode length"))?
                         as usize;
 
                     current_resource.in_memory_bytecode_opt2 =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
                 ResourceField::InMemoryExtensionModuleSharedLibrary => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading extension module length")?
-                        as usize;
+                    let l = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated("failed reading extension module length")
+                    })? as usize;
 
                     current_resource.in_memory_extension_module_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
 
                 ResourceField::InMemoryResourcesData => {
                     let resource_count = self
                         .reader
                         .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading resources length")?
+                        .map_err(|_| ParseError::Truncated("failed reading resources length"))?
                         as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+                    let mut resources =
+                        HashMap::with_capacity(self.bounded_count(resource_count, 10));
 
                     for _ in 0..resource_count {
-                        let resource_name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading resource name")?
-                            as usize;
+                        let resource_name_length =
+                            self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                                ParseError::Truncated("failed reading resource name")
+                            })? as usize;
 
-                        let resource_name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
-                            )
-                        };
+                        let resource_name = self.resolve_str(
+                            field_type,
+                            resource_name_length,
+                            "package resource name isn't valid UTF-8",
+                        )?;
 
-                        let resource_length = self
-                            .reader
-                            .read_u64::<LittleEndian>()
-                            .map_err(|_| "failed reading resource length")?
-                            as usize;
+                        let resource_length =
+                            self.reader.read_u64::<LittleEndian>().map_err(|_| {
+                                ParseError::Truncated("failed reading resource length")
+                            })? as usize;
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                        let resource_data = self.resolve_blob_data(field_type, resource_length)?;
 
                         resources
                             .insert(Cow::Borrowed(resource_name), Cow::Borrowed(resource_data));
@@ -237,33 +408,32 @@ impl<'a> ResourceParserIterator<'a> {
                 }
 
                 ResourceField::InMemoryDistributionResource => {
-                    let resource_count = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading package distribution length")?
-                        as usize;
+                    let resource_count = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated("failed reading package distribution length")
+                    })? as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+                    let mut resources =
+                        HashMap::with_capacity(self.bounded_count(resource_count, 10));
 
                     for _ in 0..resource_count {
-                        let name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading distribution metadata name")?
-                            as usize;
+                        let name_length = self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                            ParseError::Truncated("failed reading distribution metadata name")
+                        })? as usize;
 
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
-                            )
-                        };
+                        let name = self.resolve_str(
+                            field_type,
+                            name_length,
+                            "distribution resource name isn't valid UTF-8",
+                        )?;
 
                         let resource_length =
                             self.reader.read_u64::<LittleEndian>().map_err(|_| {
-                                "failed reading package distribution resource length"
+                                ParseError::Truncated(
+                                    "failed reading package distribution resource length",
+                                )
                             })? as usize;
 
-                        let resource_data = self.resolve_blob_data(field_type, resource_length);
+                        let resource_data = self.resolve_blob_data(field_type, resource_length)?;
 
                         resources.insert(Cow::Borrowed(name), Cow::Borrowed(resource_data));
                     }
@@ -272,36 +442,35 @@ impl<'a> ResourceParserIterator<'a> {
                 }
 
                 ResourceField::InMemorySharedLibrary => {
-                    let l = self
-                        .reader
-                        .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading in-memory shared library length")?
-                        as usize;
+                    let l = self.reader.read_u64::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated("failed reading in-memory shared library length")
+                    })? as usize;
 
                     current_resource.in_memory_shared_library =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
 
                 ResourceField::SharedLibraryDependencyNames => {
-                    let names_count = self
-                        .reader
-                        .read_u16::<LittleEndian>()
-                        .map_err(|_| "failed reading shared library dependency names length")?
-                        as usize;
+                    let names_count = self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated(
+                            "failed reading shared library dependency names length",
+                        )
+                    })? as usize;
 
                     let mut names = Vec::new();
 
                     for _ in 0..names_count {
-                        let name_length =
-                            self.reader.read_u16::<LittleEndian>().map_err(|_| {
-                                "failed reading shared library dependency name length"
-                            })? as usize;
-
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
+                        let name_length = self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                            ParseError::Truncated(
+                                "failed reading shared library dependency name length",
                             )
-                        };
+                        })? as usize;
+
+                        let name = self.resolve_str(
+                            field_type,
+                            name_length,
+                            "shared library dependency name isn't valid UTF-8",
+                        )?;
 
                         names.push(Cow::Borrowed(name));
                     }
@@ -310,86 +479,88 @@ impl<'a> ResourceParserIterator<'a> {
                 }
 
                 ResourceField::RelativeFilesystemModuleSource => {
-                    let path_length = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading Python module relative path length")?
-                        as usize;
+                    let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated("failed reading Python module relative path length")
+                    })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_source = Some(path);
                 }
 
                 ResourceField::RelativeFilesystemModuleBytecode => {
-                    let path_length =
-                        self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                            "failed reading Python module bytecode relative path length"
-                        })? as usize;
+                    let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated(
+                            "failed reading Python module bytecode relative path length",
+                        )
+                    })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_bytecode = Some(path);
                 }
 
                 ResourceField::RelativeFilesystemModuleBytecodeOpt1 => {
                     let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading Python module bytecode opt 1 relative path length"
+                        ParseError::Truncated(
+                            "failed reading Python module bytecode opt 1 relative path length",
+                        )
                     })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_bytecode_opt1 = Some(path);
                 }
 
                 ResourceField::RelativeFilesystemModuleBytecodeOpt2 => {
                     let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading Python module bytecode opt 2 relative path length"
+                        ParseError::Truncated(
+                            "failed reading Python module bytecode opt 2 relative path length",
+                        )
                     })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_module_bytecode_opt2 = Some(path);
                 }
 
                 ResourceField::RelativeFilesystemExtensionModuleSharedLibrary => {
                     let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading Python extension module shared library relative path length"
+                        ParseError::Truncated("failed reading Python extension module shared library relative path length")
                     })? as usize;
 
-                    let path = self.resolve_path(field_type, path_length);
+                    let path = self.resolve_path(field_type, path_length)?;
 
                     current_resource.relative_path_extension_module_shared_library = Some(path);
                 }
 
                 ResourceField::RelativeFilesystemPackageResources => {
-                    let resource_count =
-                        self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                            "failed reading package resources relative path item count"
-                        })? as usize;
+                    let resource_count = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated(
+                            "failed reading package resources relative path item count",
+                        )
+                    })? as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+                    let mut resources =
+                        HashMap::with_capacity(self.bounded_count(resource_count, 6));
 
                     for _ in 0..resource_count {
-                        let resource_name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading resource name")?
-                            as usize;
+                        let resource_name_length =
+                            self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                                ParseError::Truncated("failed reading resource name")
+                            })? as usize;
 
-                        let resource_name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, resource_name_length),
-                            )
-                        };
+                        let resource_name = self.resolve_str(
+                            field_type,
+                            resource_name_length,
+                            "package resource name isn't valid UTF-8",
+                        )?;
 
-                        let path_length = self
-                            .reader
-                            .read_u32::<LittleEndian>()
-                            .map_err(|_| "failed reading resource path length")?
-                            as usize;
+                        let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                            ParseError::Truncated("failed reading resource path length")
+                        })? as usize;
 
-                        let path = self.resolve_path(field_type, path_length);
+                        let path = self.resolve_path(field_type, path_length)?;
 
                         resources.insert(Cow::Borrowed(resource_name), path);
                     }
@@ -399,31 +570,32 @@ impl<'a> ResourceParserIterator<'a> {
 
                 ResourceField::RelativeFilesystemDistributionResource => {
                     let resource_count = self.reader.read_u32::<LittleEndian>().map_err(|_| {
-                        "failed reading package distribution relative path item count"
+                        ParseError::Truncated(
+                            "failed reading package distribution relative path item count",
+                        )
                     })? as usize;
 
-                    let mut resources = HashMap::with_capacity(resource_count);
+                    let mut resources =
+                        HashMap::with_capacity(self.bounded_count(resource_count, 6));
 
                     for _ in 0..resource_count {
-                        let name_length = self
-                            .reader
-                            .read_u16::<LittleEndian>()
-                            .map_err(|_| "failed reading package distribution metadata name")?
-                            as usize;
-
-                        let name = unsafe {
-                            std::str::from_utf8_unchecked(
-                                self.resolve_blob_data(field_type, name_length),
+                        let name_length = self.reader.read_u16::<LittleEndian>().map_err(|_| {
+                            ParseError::Truncated(
+                                "failed reading package distribution metadata name",
                             )
-                        };
+                        })? as usize;
 
-                        let path_length = self
-                            .reader
-                            .read_u32::<LittleEndian>()
-                            .map_err(|_| "failed reading package distribution path length")?
-                            as usize;
+                        let name = self.resolve_str(
+                            field_type,
+                            name_length,
+                            "distribution resource name isn't valid UTF-8",
+                        )?;
+
+                        let path_length = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                            ParseError::Truncated("failed reading package distribution path length")
+                        })? as usize;
 
-                        let path = self.resolve_path(field_type, path_length);
+                        let path = self.resolve_path(field_type, path_length)?;
 
                         resources.insert(Cow::Borrowed(name), path);
                     }
@@ -460,26 +632,25 @@ impl<'a> ResourceParserIterator<'a> {
                 }
 
                 ResourceField::FileDataEmbedded => {
-                    let l = self
-                        .reader
-                        .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading embedded file data length")?
-                        as usize;
+                    let l = self.reader.read_u64::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated("failed reading embedded file data length")
+                    })? as usize;
 
                     current_resource.file_data_embedded =
-                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)));
+                        Some(Cow::Borrowed(self.resolve_blob_data(field_type, l)?));
                 }
 
                 ResourceField::FileDataUtf8RelativePath => {
-                    let l = self
-                        .reader
-                        .read_u32::<LittleEndian>()
-                        .map_err(|_| "failed reading file data relative path length")?
-                        as usize;
+                    let l = self.reader.read_u32::<LittleEndian>().map_err(|_| {
+                        ParseError::Truncated("failed reading file data relative path length")
+                    })? as usize;
 
-                    current_resource.file_data_utf8_relative_path = Some(Cow::Borrowed(unsafe {
-                        std::str::from_utf8_unchecked(self.resolve_blob_data(field_type, l))
-                    }));
+                    current_resource.file_data_utf8_relative_path =
+                        Some(Cow::Borrowed(self.resolve_str(
+                            field_type,
+                            l,
+                            "file data relative path isn't valid UTF-8",
+                        )?));
                 }
             }
         }
@@ -487,7 +658,7 @@ impl<'a> ResourceParserIterator<'a> {
 }
 
 impl<'a> Iterator for ResourceParserIterator<'a> {
-    type Item = Result<Resource<'a, u8>, &'static str>;
+    type Item = Result<Resource<'a, u8>, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.done {
@@ -496,63 +667,180 @@ impl<'a> Iterator for ResourceParserIterator<'a> {
 
         match self.parse_next() {
             Ok(res) => res.map(Ok),
-            Err(e) => Some(Err(e)),
+            Err(e) => {
+                // State is undefined after an error. So stop parsing.
+                self.done = true;
+                Some(Err(e))
+            }
         }
     }
 }
 
+/// Apply the version 1 resource flavor to a resource.
+fn apply_legacy_flavor(resource: &mut Resource<u8>, flavor: u8) -> Result<(), ParseError> {
+    match flavor {
+        0x00 => {}
+        0x01 => resource.is_python_module = true,
+        0x02 => resource.is_python_builtin_extension_module = true,
+        0x03 => resource.is_python_frozen_module = true,
+        0x04 => resource.is_python_extension_module = true,
+        0x05 => resource.is_shared_library = true,
+        _ => return Err(ParseError::InvalidValue("invalid resource flavor")),
+    }
+
+    Ok(())
+}
+
+/// Obtain the format version of a packed resources data structure.
+///
+/// Errors with [ParseError::NewerVersion] if the data was produced in a
+/// version newer than [MAXIMUM_SUPPORTED_VERSION], e.g. by a newer PyOxidizer.
+pub fn packed_resources_version(data: &[u8]) -> Result<u8, ParseError> {
+    if data.len() < HEADER_MAGIC.len() + 1 {
+        return Err(ParseError::Truncated("error reading 8 byte header"));
+    }
+
+    if &data[0..HEADER_MAGIC.len()] != HEADER_MAGIC {
+        return Err(ParseError::UnrecognizedFormat);
+    }
+
+    match data[HEADER_MAGIC.len()] {
+        version if version < MINIMUM_SUPPORTED_VERSION => Err(ParseError::UnrecognizedFormat),
+        version if version > MAXIMUM_SUPPORTED_VERSION => Err(ParseError::NewerVersion(version)),
+        version => Ok(version),
+    }
+}
+
 /// Parse a packed resources data structure.
 ///
 /// The data structure is parsed lazily via an iterator that emits reconstructed
 /// [Resource] instances.
 ///
-/// Performance note: we once attempted to switch to anyhow for error handling and
-/// this decreased performance by ~15%. Given the performance sensitivity of this
-/// code, we need to keep error handling primitive.
-pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
-    if data.len() < HEADER_V3.len() {
-        return Err("error reading 8 byte header");
-    }
+/// All versions from [MINIMUM_SUPPORTED_VERSION] to [MAXIMUM_SUPPORTED_VERSION]
+/// are parsed. Data in older versions is read as if it were in the current
+/// version: e.g. the version 1 resource flavor is converted to the fields
+/// replacing it.
+///
+/// Data following the data structure is ignored.
+pub fn load_resources<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, ParseError> {
+    let version = packed_resources_version(data)?;
 
-    let header = &data[0..8];
+    load_resources_indices(&data[HEADER_MAGIC.len() + 1..], version)
+}
 
-    if header == HEADER_V3 {
-        load_resources_v3(&data[8..])
-    } else {
-        Err("unrecognized file format")
+/// Obtain the length of a packed resources data structure from its beginning.
+///
+/// Version 4 data declares the length of all its parts in its global header.
+/// So the length of the data structure is known from its first
+/// `HEADER_V4.len() + GLOBAL_HEADER_V4_LENGTH` bytes, which allows reading it
+/// from a stream or finding its end in a larger buffer. Earlier versions
+/// don't declare the length of their blob data.
+pub fn packed_resources_length(data: &[u8]) -> Result<usize, ParseError> {
+    if packed_resources_version(data)? < 4 {
+        return Err(ParseError::InvalidValue(
+            "data before version 4 doesn't declare its length",
+        ));
     }
+
+    let mut reader = Cursor::new(&data[8..]);
+    let header = read_global_header(&mut reader, true)?;
+
+    header
+        .blob_data_length
+        .and_then(|l| header.index_end.checked_add(l))
+        .and_then(|l| l.checked_add(HEADER_V4.len()))
+        .ok_or(ParseError::Malformed("data length overflows"))
 }
 
-fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &'static str> {
-    let mut reader = Cursor::new(data);
+/// Describes the data following the magic header.
+struct GlobalHeader {
+    blob_section_count: u8,
+    blob_index_length: usize,
+    resources_count: usize,
+    resources_index_length: usize,
+    /// Length of all blob sections. Only declared by version 4.
+    blob_data_length: Option<usize>,
+    /// Offset of the blob index.
+    blob_index_start: usize,
+    /// Offset where the indices end and blob data starts.
+    index_end: usize,
+}
 
+fn read_global_header(
+    reader: &mut Cursor<&[u8]>,
+    version_4: bool,
+) -> Result<GlobalHeader, ParseError> {
     let blob_section_count = reader
         .read_u8()
-        .map_err(|_| "failed reading blob section count")?;
+        .map_err(|_| ParseError::Truncated("failed reading blob section count"))?;
     let blob_index_length = reader
         .read_u32::<LittleEndian>()
-        .map_err(|_| "failed reading blob index length")? as usize;
+        .map_err(|_| ParseError::Truncated("failed reading blob index length"))?
+        as usize;
     let resources_count = reader
         .read_u32::<LittleEndian>()
-        .map_err(|_| "failed reading resources count")? as usize;
+        .map_err(|_| ParseError::Truncated("failed reading resources count"))?
+        as usize;
     let resources_index_length = reader
         .read_u32::<LittleEndian>()
-        .map_err(|_| "failed reading resources index length")?
+        .map_err(|_| ParseError::Truncated("failed reading resources index length"))?
         as usize;
 
+    let (blob_data_length, blob_index_start) = if version_4 {
+        let l = reader
+            .read_u64::<LittleEndian>()
+            .map_err(|_| ParseError::Truncated("failed reading blob data length"))?;
+        let l = usize::try_from(l)
+            .map_err(|_| ParseError::InvalidValue("blob data length overflows"))?;
+
+        (Some(l), GLOBAL_HEADER_V4_LENGTH)
+    } else {
+        (None, GLOBAL_HEADER_V3_LENGTH)
+    };
+
+    let index_end = blob_index_start
+        .checked_add(blob_index_length)
+        .and_then(|l| l.checked_add(resources_index_length))
+        .ok_or(ParseError::Malformed("index length overflows"))?;
+
+    Ok(GlobalHeader {
+        blob_section_count,
+        blob_index_length,
+        resources_count,
+        resources_index_length,
+        blob_data_length,
+        blob_index_start,
+        index_end,
+    })
+}
+
+fn load_resources_indices<'a>(
+    data: &'a [u8],
+    version: u8,
+) -> Result<ResourceParserIterator<'a>, ParseError> {
+    let header = read_global_header(&mut Cursor::new(data), version >= 4)?;
+
+    // Reads of index data must not go beyond the indices.
+    let index_data = data
+        .get(0..header.index_end)
+        .ok_or(ParseError::Truncated("index data out of bounds"))?;
+    let mut reader = Cursor::new(index_data);
+    reader.set_position(header.blob_index_start as u64);
+
     let mut current_blob_field = None;
     let mut current_blob_raw_payload_length = None;
     let mut current_blob_interior_padding = None;
     let mut blob_entry_count = 0;
-    let mut blob_sections = Vec::with_capacity(blob_section_count as usize);
+    let mut blob_sections = Vec::with_capacity(header.blob_section_count as usize);
 
-    if blob_section_count != 0 || blob_index_length != 0 {
+    if header.blob_section_count != 0 || header.blob_index_length != 0 {
         loop {
             let field_type = reader
                 .read_u8()
-                .map_err(|_| "failed reading blob section field type")?;
+                .map_err(|_| ParseError::Truncated("failed reading blob section field type"))?;
 
-            let field_type = BlobSectionField::try_from(field_type)?;
+            let field_type =
+                BlobSectionField::try_from(field_type).map_err(ParseError::InvalidValue)?;
 
             match field_type {
                 BlobSectionField::EndOfIndex => break,
@@ -563,16 +851,14 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                     current_blob_interior_padding = None;
                 }
                 BlobSectionField::EndOfEntry => {
-                    if current_blob_field.is_none() {
-                        return Err("blob resource field is required");
-                    }
-                    if current_blob_raw_payload_length.is_none() {
-                        return Err("blob raw payload length is required");
-                    }
+                    let resource_field = current_blob_field
+                        .ok_or(ParseError::Malformed("blob resource field is required"))?;
+                    let raw_payload_length = current_blob_raw_payload_length
+                        .ok_or(ParseError::Malformed("blob raw payload length is required"))?;
 
                     blob_sections.push(BlobSection {
-                        resource_field: current_blob_field.unwrap(),
-                        raw_payload_length: current_blob_raw_payload_length.unwrap(),
+                        resource_field,
+                        raw_payload_length,
                         interior_padding: current_blob_interior_padding,
                     });
 
@@ -581,68 +867,112 @@ fn load_resources_v3<'a>(data: &'a [u8]) -> Result<ResourceParserIterator<'a>, &
                     current_blob_interior_padding = None;
                 }
                 BlobSectionField::ResourceFieldType => {
-                    let field = reader
-                        .read_u8()
-                        .map_err(|_| "failed reading blob resource field value")?;
+                    let field = reader.read_u8().map_err(|_| {
+                        ParseError::Truncated("failed reading blob resource field value")
+                    })?;
                     current_blob_field = Some(field);
                 }
                 BlobSectionField::RawPayloadLength => {
                     let l = reader
                         .read_u64::<LittleEndian>()
-                        .map_err(|_| "failed reading raw payload length")?;
-                    current_blob_raw_payload_length = Some(l as usize);
+                        .map_err(|_| ParseError::Truncated("failed reading raw payload length"))?;
+                    current_blob_raw_payload_length =
+                        Some(usize::try_from(l).map_err(|_| {
+                            ParseError::InvalidValue("raw payload length overflows")
+                        })?);
                 }
                 BlobSectionField::InteriorPadding => {
-                    let padding = reader
-                        .read_u8()
-                        .map_err(|_| "failed reading interior padding field value")?;
+                    let padding = reader.read_u8().map_err(|_| {
+                        ParseError::Truncated("failed reading interior padding field value")
+                    })?;
 
                     current_blob_interior_padding = Some(match padding {
                         0x01 => BlobInteriorPadding::None,
                         0x02 => BlobInteriorPadding::Null,
-                        _ => return Err("invalid value for interior padding field"),
+                        _ => {
+                            return Err(ParseError::InvalidValue(
+                                "invalid value for interior padding field",
+                            ))
+                        }
                     });
                 }
             }
         }
     }
 
-    if blob_entry_count != blob_section_count {
-        return Err("mismatch between blob sections count");
+    if blob_entry_count != header.blob_section_count {
+        return Err(ParseError::Malformed(
+            "mismatch between blob sections count",
+        ));
+    }
+
+    if reader.position() as usize != header.blob_index_start + header.blob_index_length {
+        return Err(ParseError::Malformed(
+            "mismatch between blob index length and actual",
+        ));
     }
 
     // Array indexing resource field to current payload offset within that section.
     let mut blob_offsets: [Option<BlobSectionReadState>; 256] = [None; 256];
 
     // Global payload offset where blobs data starts.
-    let blob_start_offset: usize =
-            // Global header.
-            1 + 4 + 4 + 4
-            + blob_index_length
-            + resources_index_length
-        ;
+    let blob_start_offset = header.index_end;
     // Current offset from start of blobs data.
-    let mut current_blob_offset = 0;
+    let mut current_blob_offset: usize = 0;
 
     for section in &blob_sections {
-        let section_start_offset = blob_start_offset + current_blob_offset;
+        let section_start_offset = blob_start_offset
+            .checked_add(current_blob_offset)
+            .ok_or(ParseError::Malformed("blob section offset overflows"))?;
+        let section_end_offset = section_start_offset
+            .checked_add(section.raw_payload_length)
+            .ok_or(ParseError::Malformed("blob section offset overflows"))?;
+
         blob_offsets[section.resource_field as usize] = Some(BlobSectionReadState {
             offset: section_start_offset,
+            end: section_end_offset,
             interior_padding: match section.interior_padding {
                 Some(padding) => padding,
                 None => BlobInteriorPadding::None,
             },
         });
-        current_blob_offset += section.raw_payload_length;
+        current_blob_offset = section_end_offset - blob_start_offset;
     }
 
+    // Version 4 declares the length of blob data. So truncated data is detected
+    // before any resource is read.
+    if let Some(blob_data_length) = header.blob_data_length {
+        if current_blob_offset != blob_data_length {
+            return Err(ParseError::Malformed(
+                "mismatch between blob data length and blob sections",
+            ));
+        }
+
+        // Can't overflow, as section offsets were checked above.
+        if blob_start_offset + blob_data_length > data.len() {
+            return Err(ParseError::Truncated("blob data out of bounds"));
+        }
+    }
+
+    // Every entry has at least start and end markers and a name field.
+    let minimum_entry_length = if version >= 4 {
+        1 + 4 + 3 + 1
+    } else {
+        1 + 3 + 1
+    };
+
     Ok(ResourceParserIterator {
-        done: resources_index_length == 0 || resources_count == 0,
+        done: header.resources_index_length == 0 || header.resources_count == 0,
         data,
         reader,
         blob_sections: blob_offsets,
-        claimed_resources_count: resources_count,
+        claimed_resources_count: header.resources_count,
+        expected_resources_count: header
+            .resources_count
+            .min(header.resources_index_length / minimum_entry_length),
         read_resources_count: 0,
+        version,
+        entry_end: None,
     })
 }
 
@@ -651,28 +981,59 @@ mod tests {
     use {
         super::*,
         crate::{
-            resource::Resource, serialization::BlobInteriorPadding,
-            writer::write_packed_resources_v3,
+            resource::Resource,
+            serialization::BlobInteriorPadding,
+            writer::{write_packed_resources_v3, write_packed_resources_v4},
         },
     };
 
+    /// Resources exercising blob sections with sub-elements.
+    fn v4_resources() -> Vec<Resource<'static, u8>> {
+        let mut resources = HashMap::new();
+        resources.insert(Cow::from("data.txt"), Cow::from(b"data".to_vec()));
+
+        vec![
+            Resource {
+                name: Cow::from("foo"),
+                is_python_module: true,
+                is_python_package: true,
+                in_memory_source: Some(Cow::from(b"import io".to_vec())),
+                in_memory_package_resources: Some(resources),
+                ..Resource::default()
+            },
+            Resource {
+                name: Cow::from("foo.bar"),
+                is_python_module: true,
+                in_memory_bytecode: Some(Cow::from(b"fake bytecode".to_vec())),
+                shared_library_dependency_names: Some(vec![Cow::from("libfoo.so")]),
+                ..Resource::default()
+            },
+        ]
+    }
+
     #[test]
     fn test_too_short_header() {
         let data = b"foo";
 
         let res = load_resources(data);
-        assert_eq!(res.err(), Some("error reading 8 byte header"));
+        assert_eq!(
+            res.err(),
+            Some(ParseError::Truncated("error reading 8 byte header"))
+        );
     }
 
     #[test]
     fn test_unrecognized_header() {
         let data = b"pyembed\x00";
         let res = load_resources(data);
-        assert_eq!(res.err(), Some("unrecognized file format"));
-
-        let data = b"pyembed\x04";
-        let res = load_resources(data);
-        assert_eq!(res.err(), Some("unrecognized file format"));
+        assert_eq!(res.err(), Some(ParseError::UnrecognizedFormat));
+
+        let data = b"pyembed\x05";
+        let err = load_resources(data).err().unwrap();
+        assert_eq!(err, ParseError::NewerVersion(5));
+        assert!(err
+            .message()
+            .starts_with("resources produced by newer pyoxidizer"));
     }
 
     #[test]
@@ -705,7 +1066,9 @@ mod tests {
         let mut res = load_resources(data).unwrap();
         assert_eq!(
             res.next(),
-            Some(Err("mismatch between advertised index count and actual"))
+            Some(Err(ParseError::Malformed(
+                "mismatch between advertised index count and actual"
+            )))
         );
         assert_eq!(res.next(), None);
     }
@@ -715,10 +1078,33 @@ mod tests {
         let data =
             b"pyembed\x03\x00\x01\x00\x00\x00\x01\x00\x00\x00\x03\x00\x00\x00\x00\x01\xff\x00";
         let mut res = load_resources(data).unwrap();
-        assert_eq!(res.next(), Some(Err("resource name field is required")));
+        assert_eq!(
+            res.next(),
+            Some(Err(ParseError::Malformed(
+                "resource name field is required"
+            )))
+        );
         assert_eq!(res.next(), None);
     }
 
+    #[test]
+    fn test_truncated_blob_data() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        data.truncate(data.len() - 1);
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err(ParseError::Truncated("blob data out of bounds")))
+        );
+    }
+
     #[test]
     fn test_just_resource_name() {
         let resource = Resource {
@@ -731,7 +1117,7 @@ mod tests {
 
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -762,7 +1148,7 @@ mod tests {
         write_packed_resources_v3(&[resource1, resource2], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 2);
@@ -808,7 +1194,7 @@ mod tests {
         .unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 2);
@@ -844,7 +1230,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -875,7 +1261,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -909,7 +1295,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -943,7 +1329,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -977,7 +1363,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1021,7 +1407,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1050,7 +1436,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1075,7 +1461,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1111,7 +1497,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1136,7 +1522,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1165,7 +1551,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1194,7 +1580,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1223,7 +1609,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1252,7 +1638,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1287,7 +1673,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1322,7 +1708,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1407,7 +1793,7 @@ mod tests {
         write_packed_resources_v3(&[resource], &mut data, None).unwrap();
         let resources = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources.len(), 1);
@@ -1538,9 +1924,269 @@ mod tests {
         write_packed_resources_v3(&resources, &mut data, None).unwrap();
         let loaded = load_resources(&data)
             .unwrap()
-            .collect::<Result<Vec<Resource<u8>>, &'static str>>()
+            .collect::<Result<Vec<Resource<u8>>, ParseError>>()
             .unwrap();
 
         assert_eq!(resources, loaded);
     }
+
+    #[test]
+    fn test_v4_roundtrip() {
+        for padding in [None, Some(BlobInteriorPadding::Null)] {
+            let resources = v4_resources();
+
+            let mut data = Vec::new();
+            write_packed_resources_v4(&resources, &mut data, padding).unwrap();
+            let loaded = load_resources(&data)
+                .unwrap()
+                .collect::<Result<Vec<Resource<u8>>, ParseError>>()
+                .unwrap();
+
+            assert_eq!(resources, loaded);
+        }
+    }
+
+    #[test]
+    fn test_v4_truncated_blob_data() {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&v4_resources(), &mut data, None).unwrap();
+        data.truncate(data.len() - 1);
+
+        // Unlike version 3, truncation is detected before reading resources.
+        assert_eq!(
+            load_resources(&data).err(),
+            Some(ParseError::Truncated("blob data out of bounds"))
+        );
+    }
+
+    #[test]
+    fn test_v4_entry_length_mismatch() {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&v4_resources(), &mut data, None).unwrap();
+
+        // The length of the first entry follows its start of entry marker, which
+        // follows the global header and the blob index.
+        let blob_index_length = u32::from_le_bytes([data[9], data[10], data[11], data[12]]);
+        let offset = HEADER_V4.len() + GLOBAL_HEADER_V4_LENGTH + blob_index_length as usize + 1;
+        data[offset] -= 1;
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err(ParseError::Malformed(
+                "mismatch between resource entry length and actual"
+            )))
+        );
+        assert_eq!(res.next(), None);
+    }
+
+    #[test]
+    fn test_invalid_utf8_name() {
+        let resource = Resource {
+            name: Cow::from("foo"),
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v4(&[resource], &mut data, None).unwrap();
+        let l = data.len();
+        data[l - 1] = 0xff;
+
+        let mut res = load_resources(&data).unwrap();
+        assert_eq!(
+            res.next(),
+            Some(Err(ParseError::InvalidValue(
+                "resource name isn't valid UTF-8"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_expected_resources_count_bounded() {
+        // Claims 0xffffffff resources in a 1 byte resources index.
+        let data = b"pyembed\x03\x00\x00\x00\x00\x00\xff\xff\xff\xff\x01\x00\x00\x00\x00";
+        let res = load_resources(data).unwrap();
+        assert_eq!(res.expected_resources_count(), 0);
+    }
+
+    #[test]
+    fn test_packed_resources_length() {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&v4_resources(), &mut data, None).unwrap();
+        let l = data.len();
+
+        assert_eq!(packed_resources_length(&data), Ok(l));
+        assert_eq!(
+            packed_resources_length(&data[0..HEADER_V4.len() + GLOBAL_HEADER_V4_LENGTH]),
+            Ok(l)
+        );
+
+        data.extend(b"trailing data");
+        assert_eq!(packed_resources_length(&data), Ok(l));
+
+        assert_eq!(
+            packed_resources_length(&data[0..HEADER_V4.len() + 1]),
+            Err(ParseError::Truncated("failed reading blob index length"))
+        );
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&v4_resources(), &mut data, None).unwrap();
+        assert_eq!(
+            packed_resources_length(&data),
+            Err(ParseError::InvalidValue(
+                "data before version 4 doesn't declare its length"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_corrupt_data_no_panic() {
+        for padding in [None, Some(BlobInteriorPadding::Null)] {
+            let mut v3 = Vec::new();
+            write_packed_resources_v3(&v4_resources(), &mut v3, padding).unwrap();
+            let mut v4 = Vec::new();
+            write_packed_resources_v4(&v4_resources(), &mut v4, padding).unwrap();
+
+            for data in [v3, v4] {
+                let parse = |data: &[u8]| {
+                    if let Ok(resources) = load_resources(data) {
+                        for resource in resources {
+                            if resource.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    let _ = packed_resources_length(data);
+                };
+
+                for l in 0..data.len() {
+                    parse(&data[0..l]);
+                }
+
+                for i in 0..data.len() {
+                    for value in [0x00, 0x01, 0x7f, 0xff] {
+                        let mut corrupt = data.clone();
+                        corrupt[i] = value;
+                        parse(&corrupt);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_resources_version() {
+        let mut data = Vec::new();
+        write_packed_resources_v4(&v4_resources(), &mut data, None).unwrap();
+        assert_eq!(packed_resources_version(&data), Ok(4));
+
+        assert_eq!(packed_resources_version(b"pyembed\x01"), Ok(1));
+        assert_eq!(
+            packed_resources_version(b"pyembed\x00"),
+            Err(ParseError::UnrecognizedFormat)
+        );
+        assert_eq!(
+            packed_resources_version(b"pyembed\xff"),
+            Err(ParseError::NewerVersion(0xff))
+        );
+        assert_eq!(
+            packed_resources_version(b"pyembed"),
+            Err(ParseError::Truncated("error reading 8 byte header"))
+        );
+    }
+
+    #[test]
+    fn test_version_2() {
+        let resources = vec![Resource {
+            name: Cow::from("foo"),
+            is_python_module: true,
+            in_memory_source: Some(Cow::from(b"import io".to_vec())),
+            ..Resource::default()
+        }];
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&resources, &mut data, None).unwrap();
+        data[7] = 2;
+
+        let parsed = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(parsed, resources);
+
+        // Fields introduced after version 2 are rejected.
+        let resource = Resource {
+            name: Cow::from("foo"),
+            is_utf8_filename_data: true,
+            ..Resource::default()
+        };
+
+        let mut data = Vec::new();
+        write_packed_resources_v3(&[resource], &mut data, None).unwrap();
+        data[7] = 2;
+
+        let mut resources = load_resources(&data).unwrap();
+        assert_eq!(
+            resources.next(),
+            Some(Err(ParseError::InvalidValue(
+                "field type not defined in format version"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_version_1_flavor() {
+        let mut data = b"pyembed\x01".to_vec();
+        // Blob section count.
+        data.push(1);
+        // Blob index length.
+        data.extend(14u32.to_le_bytes());
+        // Resources count.
+        data.push(1);
+        data.extend([0, 0, 0]);
+        // Resources index length.
+        data.extend(9u32.to_le_bytes());
+
+        // Blob index: a 3 byte blob section for resource names.
+        data.extend([0x01, 0x02, 0x03, 0x03]);
+        data.extend(3u64.to_le_bytes());
+        data.extend([0xff, 0x00]);
+
+        // Resources index: a resource with a name and the extension module flavor.
+        data.extend([0x01, 0x02, 0x04, 0x04, 0x03]);
+        data.extend(3u16.to_le_bytes());
+        data.extend([0xff, 0x00]);
+
+        data.extend(b"foo");
+
+        let resources = load_resources(&data)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            resources,
+            vec![Resource {
+                name: Cow::from("foo"),
+                is_python_package: true,
+                is_python_extension_module: true,
+                ..Resource::default()
+            }]
+        );
+
+        // The flavor field was removed in version 2.
+        data[7] = 2;
+        let mut resources = load_resources(&data).unwrap();
+        assert_eq!(
+            resources.next(),
+            Some(Err(ParseError::InvalidValue("invalid field type")))
+        );
+
+        data[7] = 1;
+        data[8 + 13 + 14 + 2] = 0x06;
+        let mut resources = load_resources(&data).unwrap();
+        assert_eq!(
+            resources.next(),
+            Some(Err(ParseError::InvalidValue("invalid resource flavor")))
+        );
+    }
 }
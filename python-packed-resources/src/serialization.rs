@@ -4,9 +4,29 @@
 
 /*! Declares the foundational data primitives inside packed resources data. */
 
-/// Header value for version 2 of resources payload.
+/// Magic beginning the header of all versions of resources payload.
+///
+/// The byte following it is the version.
+pub const HEADER_MAGIC: &[u8] = b"pyembed";
+
+/// Oldest version of resources payload that can be parsed.
+pub const MINIMUM_SUPPORTED_VERSION: u8 = 1;
+
+/// Newest version of resources payload that can be parsed.
+pub const MAXIMUM_SUPPORTED_VERSION: u8 = 4;
+
+/// Header value for version 3 of resources payload.
 pub const HEADER_V3: &[u8] = b"pyembed\x03";
 
+/// Header value for version 4 of resources payload.
+pub const HEADER_V4: &[u8] = b"pyembed\x04";
+
+/// Length of the global header following the magic header in version 4.
+///
+/// Holds the blob section count, the lengths of both indices, the resources
+/// count and the length of blob data.
+pub const GLOBAL_HEADER_V4_LENGTH: usize = 1 + 4 + 4 + 4 + 8;
+
 /// Defines interior padding mechanism between entries in blob sections.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BlobInteriorPadding {
@@ -107,6 +127,17 @@ pub enum ResourceField {
     FileDataUtf8RelativePath = 0x1e,
 }
 
+impl ResourceField {
+    /// The first version of the data format defining this field.
+    pub fn introduced_version(&self) -> u8 {
+        match u8::from(*self) {
+            0x16..=0x1a => 2,
+            0x1b..=0x1e => 3,
+            _ => 1,
+        }
+    }
+}
+
 impl From<ResourceField> for u8 {
     fn from(field: ResourceField) -> Self {
         match field {
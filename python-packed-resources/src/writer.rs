@@ -7,11 +7,17 @@
 use {
     crate::{
         resource::Resource,
-        serialization::{BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3},
+        serialization::{
+            BlobInteriorPadding, BlobSectionField, ResourceField, HEADER_V3, HEADER_V4,
+        },
     },
     anyhow::{anyhow, Context, Result},
     byteorder::{LittleEndian, WriteBytesExt},
-    std::{collections::BTreeMap, io::Write, path::Path},
+    std::{
+        collections::{BTreeMap, HashMap},
+        io::Write,
+        path::Path,
+    },
 };
 
 #[cfg(unix)]
@@ -47,6 +53,16 @@ fn path_to_bytes(p: &Path) -> Vec<u8> {
     }
 }
 
+/// Obtain the entries of a map sorted by key.
+///
+/// Map entries are serialized in this order so output is deterministic.
+fn sorted_entries<K: Ord, V, S>(map: &HashMap<K, V, S>) -> Vec<(&K, &V)> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    entries
+}
+
 #[derive(Debug)]
 struct BlobSection {
     resource_field: ResourceField,
@@ -266,6 +282,13 @@ where
         index
     }
 
+    /// Compute length of index entry for version 4 payload format.
+    ///
+    /// Entries are the same as in version 1, plus their length.
+    pub fn index_v4_length(&self) -> usize {
+        self.index_v1_length() + 4
+    }
+
     /// Compute the length of a field.
     ///
     /// Interior padding is not part of the returned length.
@@ -583,12 +606,33 @@ where
 
     /// Write the version 1 index entry for a resource instance.
     pub fn write_index_v1<W: Write>(&self, dest: &mut W) -> Result<()> {
+        self.write_index_entry(dest, false)
+    }
+
+    /// Write the version 4 index entry for a resource instance.
+    pub fn write_index_v4<W: Write>(&self, dest: &mut W) -> Result<()> {
+        self.write_index_entry(dest, true)
+    }
+
+    /// Write the index entry for a resource instance.
+    ///
+    /// If `framed`, the length of the entry following the start of entry
+    /// marker is written after the marker.
+    fn write_index_entry<W: Write>(&self, dest: &mut W, framed: bool) -> Result<()> {
         let name_len =
             u16::try_from(self.name.as_bytes().len()).context("converting name to u16")?;
 
         dest.write_u8(ResourceField::StartOfEntry.into())
             .context("writing start of index entry")?;
 
+        if framed {
+            // Everything following the start of entry marker.
+            let l = u32::try_from(self.index_v1_length() - 1)
+                .context("converting index entry length to u32")?;
+            dest.write_u32::<LittleEndian>(l)
+                .context("writing index entry length")?;
+        }
+
         dest.write_u8(ResourceField::Name.into())
             .context("writing resource name field")?;
 
@@ -658,7 +702,7 @@ where
             dest.write_u32::<LittleEndian>(l)
                 .context("writing in-memory resources data length")?;
 
-            for (name, value) in resources.iter() {
+            for (name, value) in sorted_entries(resources) {
                 let name_length = u16::try_from(name.as_bytes().len())
                     .context("converting resource name length to u16")?;
                 dest.write_u16::<LittleEndian>(name_length)
@@ -676,7 +720,7 @@ where
             dest.write_u32::<LittleEndian>(l)
                 .context("writing in-memory package distribution length")?;
 
-            for (name, value) in metadata {
+            for (name, value) in sorted_entries(metadata) {
                 let name_length = u16::try_from(name.as_bytes().len())
                     .context("converting distribution name length to u16")?;
                 dest.write_u16::<LittleEndian>(name_length)
@@ -764,7 +808,7 @@ where
             dest.write_u32::<LittleEndian>(l)
                 .context("writing relative path resources resources data length")?;
 
-            for (name, path) in resources.iter() {
+            for (name, path) in sorted_entries(resources) {
                 let name_length = u16::try_from(name.as_bytes().len())
                     .context("converting resource name length to u16")?;
                 let path_length = u32::try_from(path_bytes_length(path))
@@ -784,7 +828,7 @@ where
             dest.write_u32::<LittleEndian>(l)
                 .context("writing relative path distribution data length")?;
 
-            for (name, path) in metadata.iter() {
+            for (name, path) in sorted_entries(metadata) {
                 let name_length = u16::try_from(name.as_bytes().len())
                     .context("converting resource name length to u16")?;
                 let path_length = u32::try_from(path_bytes_length(path))
@@ -857,11 +901,32 @@ where
 }
 
 /// Write packed resources data, version 3.
-#[allow(clippy::cognitive_complexity)]
 pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
     resources: &[T],
     dest: &mut W,
     interior_padding: Option<BlobInteriorPadding>,
+) -> Result<()> {
+    write_packed_resources(resources, dest, interior_padding, false)
+}
+
+/// Write packed resources data, version 4.
+///
+/// Version 4 declares the length of blob data and of each resources index
+/// entry, so readers can detect truncated data and skip entries.
+pub fn write_packed_resources_v4<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    resources: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+) -> Result<()> {
+    write_packed_resources(resources, dest, interior_padding, true)
+}
+
+#[allow(clippy::cognitive_complexity)]
+fn write_packed_resources<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
+    resources: &[T],
+    dest: &mut W,
+    interior_padding: Option<BlobInteriorPadding>,
+    version_4: bool,
 ) -> Result<()> {
     let mut blob_sections = BTreeMap::new();
 
@@ -904,7 +969,11 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     for resource in resources {
         let resource = resource.as_ref();
-        resource_index_length += resource.index_v1_length();
+        resource_index_length += if version_4 {
+            resource.index_v4_length()
+        } else {
+            resource.index_v1_length()
+        };
 
         process_field(&mut blob_sections, resource, ResourceField::Name);
         process_field(&mut blob_sections, resource, ResourceField::InMemorySource);
@@ -1000,12 +1069,25 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
         blob_index_length += section.index_v1_length();
     }
 
-    dest.write_all(HEADER_V3)?;
+    dest.write_all(if version_4 { HEADER_V4 } else { HEADER_V3 })?;
 
     dest.write_u8(blob_section_count)?;
-    dest.write_u32::<LittleEndian>(blob_index_length as u32)?;
-    dest.write_u32::<LittleEndian>(resources.len() as u32)?;
-    dest.write_u32::<LittleEndian>(resource_index_length as u32)?;
+    dest.write_u32::<LittleEndian>(
+        u32::try_from(blob_index_length).context("converting blob index length to u32")?,
+    )?;
+    dest.write_u32::<LittleEndian>(
+        u32::try_from(resources.len()).context("converting resources count to u32")?,
+    )?;
+    dest.write_u32::<LittleEndian>(
+        u32::try_from(resource_index_length).context("converting resources index length to u32")?,
+    )?;
+    if version_4 {
+        let blob_data_length = blob_sections
+            .values()
+            .map(|section| section.raw_payload_length as u64)
+            .sum::<u64>();
+        dest.write_u64::<LittleEndian>(blob_data_length)?;
+    }
 
     // Write the blob index.
     for section in blob_sections.values() {
@@ -1015,7 +1097,11 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     // Write the resources index.
     for resource in resources {
-        resource.as_ref().write_index_v1(dest)?;
+        if version_4 {
+            resource.as_ref().write_index_v4(dest)?;
+        } else {
+            resource.as_ref().write_index_v1(dest)?;
+        }
     }
     dest.write_u8(ResourceField::EndOfIndex.into())?;
 
@@ -1062,7 +1148,7 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     for resource in resources {
         if let Some(resources) = &resource.as_ref().in_memory_package_resources {
-            for (key, value) in resources.iter() {
+            for (key, value) in sorted_entries(resources) {
                 dest.write_all(key.as_bytes())?;
                 add_interior_padding(dest)?;
                 dest.write_all(value)?;
@@ -1073,7 +1159,7 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     for resource in resources {
         if let Some(resources) = &resource.as_ref().in_memory_distribution_resources {
-            for (key, value) in resources {
+            for (key, value) in sorted_entries(resources) {
                 dest.write_all(key.as_bytes())?;
                 add_interior_padding(dest)?;
                 dest.write_all(value)?;
@@ -1138,7 +1224,7 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     for resource in resources {
         if let Some(resources) = &resource.as_ref().relative_path_package_resources {
-            for (key, path) in resources.iter() {
+            for (key, path) in sorted_entries(resources) {
                 dest.write_all(key.as_bytes())?;
                 add_interior_padding(dest)?;
                 dest.write_all(&path_to_bytes(path))?;
@@ -1149,7 +1235,7 @@ pub fn write_packed_resources_v3<'a, T: AsRef<Resource<'a, u8>>, W: Write>(
 
     for resource in resources {
         if let Some(resources) = &resource.as_ref().relative_path_distribution_resources {
-            for (key, path) in resources {
+            for (key, path) in sorted_entries(resources) {
                 dest.write_all(key.as_bytes())?;
                 add_interior_padding(dest)?;
                 dest.write_all(&path_to_bytes(path))?;
@@ -1243,4 +1329,85 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_write_resource_name_v4() -> Result<()> {
+        let mut data = Vec::new();
+        let resource = Resource {
+            name: Cow::Owned("foo".to_string()),
+            ..Resource::default()
+        };
+
+        write_packed_resources_v4(&[resource], &mut data, None)?;
+
+        let mut expected: Vec<u8> = b"pyembed\x04".to_vec();
+        // Number of blob sections.
+        expected.write_u8(1)?;
+        // Length of blob index.
+        expected.write_u32::<LittleEndian>(1 + 1 + 1 + 1 + 8 + 1 + 1)?;
+        // Number of resources.
+        expected.write_u32::<LittleEndian>(1)?;
+        // Length of index. Start of entry, entry length, resource name length field,
+        // resource name length, end of entry, end of index.
+        expected.write_u32::<LittleEndian>(1 + 4 + 1 + 2 + 1 + 1)?;
+        // Length of blob data.
+        expected.write_u64::<LittleEndian>(b"foo".len() as u64)?;
+        // Blobs index.
+        expected.write_u8(BlobSectionField::StartOfEntry.into())?;
+        expected.write_u8(BlobSectionField::ResourceFieldType.into())?;
+        expected.write_u8(ResourceField::Name.into())?;
+        expected.write_u8(BlobSectionField::RawPayloadLength.into())?;
+        expected.write_u64::<LittleEndian>(b"foo".len() as u64)?;
+        expected.write_u8(BlobSectionField::EndOfEntry.into())?;
+        expected.write_u8(BlobSectionField::EndOfIndex.into())?;
+        // Resource index.
+        expected.write_u8(ResourceField::StartOfEntry.into())?;
+        // Entry length. Resource name length field, resource name length, end of entry.
+        expected.write_u32::<LittleEndian>(1 + 2 + 1)?;
+        expected.write_u8(ResourceField::Name.into())?;
+        expected.write_u16::<LittleEndian>(b"foo".len() as u16)?;
+        expected.write_u8(ResourceField::EndOfEntry.into())?;
+        expected.write_u8(ResourceField::EndOfIndex.into())?;
+        expected.write_all(b"foo")?;
+
+        assert_eq!(data, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_package_resources_sorted() -> Result<()> {
+        let names = (0..32)
+            .map(|i| format!("resource{}", i))
+            .collect::<Vec<_>>();
+
+        let mut blobs = vec![];
+
+        for reverse in [false, true] {
+            let mut resources = HashMap::new();
+
+            let mut ordered = names.clone();
+            if reverse {
+                ordered.reverse();
+            }
+
+            for name in ordered {
+                resources.insert(Cow::Owned(name), Cow::Owned(b"data".to_vec()));
+            }
+
+            let resource = Resource {
+                name: Cow::Owned("foo".to_string()),
+                in_memory_package_resources: Some(resources),
+                ..Resource::default()
+            };
+
+            let mut data = Vec::new();
+            write_packed_resources_v3(&[resource], &mut data, None)?;
+            blobs.push(data);
+        }
+
+        assert_eq!(blobs[0], blobs[1]);
+
+        Ok(())
+    }
 }
@@ -52,6 +52,9 @@ static RELEASE_ORDER: Lazy<Vec<&'static str>> = Lazy::new(|| {
         "tugger-windows-codesign",
         "tugger-code-signing",
         "tugger-wix",
+        "tugger-msix",
+        "tugger-nsis",
+        "tugger-squirrel",
         "python-packed-resources",
         "python-packaging",
         "tugger",
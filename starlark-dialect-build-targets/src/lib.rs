@@ -8,7 +8,7 @@ use {
     anyhow::{anyhow, Result},
     linked_hash_map::LinkedHashMap,
     path_dedot::ParseDot,
-    slog::warn,
+    slog::{info, warn},
     starlark::{
         environment::{Environment, EnvironmentError, TypeValues},
         eval::call_stack::CallStack,
@@ -834,7 +834,8 @@ fn starlark_resolve_target(
             return Ok(v);
         }
 
-        warn!(&context.logger, "resolving target {}", target);
+        warn!(&context.logger, "resolving target {}", target;
+            "event" => "stage_start", "stage" => "resolve", "target" => &target);
 
         match context.get_target(&target) {
             Some(v) => Ok((*v).clone()),
@@ -880,6 +881,9 @@ fn starlark_resolve_target(
         target_entry.resolved_value = Some(res.clone());
     }
 
+    info!(context.logger(), "resolved target {}", target;
+        "event" => "stage_stop", "stage" => "resolve", "target" => &target);
+
     Ok(res)
 }
 
@@ -1016,7 +1020,7 @@ pub fn build_target(
     call_stack: &mut CallStack,
     target: &str,
 ) -> Result<ResolvedTarget> {
-    let resolved_value = {
+    let (logger, resolved_value) = {
         let context_value = get_context_value(type_values)
             .map_err(|_| anyhow!("unable to resolve context value"))?;
         let context = context_value
@@ -1037,9 +1041,13 @@ pub fn build_target(
             return Err(anyhow!("target {} is not resolved", target));
         };
 
-        v
+        (context.logger().clone(), v)
     };
 
+    info!(logger, "building target {}", target;
+        "event" => "stage_start", "stage" => "build", "target" => target);
+    let start = std::time::Instant::now();
+
     let build = type_values
         .get_type_value(&resolved_value, "build")
         .ok_or_else(|| anyhow!("{} does not implement build()", resolved_value.get_type()))?;
@@ -1068,6 +1076,21 @@ pub fn build_target(
 
     context.get_target_mut(target).unwrap().built_target = Some(resolved_target.inner.clone());
 
+    info!(logger, "built target {}", target;
+        "event" => "stage_stop",
+        "stage" => "build",
+        "target" => target,
+        "output_path" => %resolved_target.inner.output_path.display(),
+        "duration_ms" => start.elapsed().as_millis() as u64);
+
+    if let RunMode::Path { path } = &resolved_target.inner.run_mode {
+        info!(logger, "target {} produced {}", target, path.display();
+            "event" => "artifact",
+            "target" => target,
+            "path" => %path.display(),
+            "size" => std::fs::metadata(path).map(|m| m.len()).unwrap_or_default());
+    }
+
     Ok(resolved_target.inner.clone())
 }
 
@@ -4,8 +4,9 @@
 
 use {
     crate::UndefinedSymbol,
+    anyhow::{anyhow, Result},
     byteorder::ReadBytesExt,
-    std::{ffi::CStr, os::raw::c_char},
+    std::{collections::BTreeSet, ffi::CStr, os::raw::c_char},
 };
 
 #[repr(C)]
@@ -160,3 +161,110 @@ pub fn find_undefined_elf_symbols(buffer: &[u8], elf: &goblin::elf::Elf) -> Vec<
 
     res
 }
+
+/// Dynamic linking requirements of an ELF binary.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ElfDynamicRequirements {
+    /// The program interpreter (dynamic loader), if any.
+    pub interpreter: Option<String>,
+    /// Shared libraries the binary depends on, from `DT_NEEDED` entries.
+    pub libraries: Vec<String>,
+}
+
+impl ElfDynamicRequirements {
+    /// Whether the binary is statically linked.
+    pub fn is_static(&self) -> bool {
+        self.interpreter.is_none() && self.libraries.is_empty()
+    }
+}
+
+pub fn find_elf_dynamic_requirements(data: &[u8]) -> Result<ElfDynamicRequirements> {
+    let elf = goblin::elf::Elf::parse(data)?;
+
+    Ok(ElfDynamicRequirements {
+        interpreter: elf.interpreter.map(|s| s.to_string()),
+        libraries: elf.libraries.iter().map(|l| (*l).to_string()).collect(),
+    })
+}
+
+/// Find undefined symbols of an ELF binary requiring a version above a ceiling.
+///
+/// `namespace` is the prefix of symbol versions, e.g. `GLIBC` for versions
+/// like `GLIBC_2.17`. `ceiling` is a version without prefix, e.g. `2.17`.
+pub fn find_elf_symbols_above_version(
+    data: &[u8],
+    namespace: &str,
+    ceiling: &str,
+) -> Result<Vec<UndefinedSymbol>> {
+    let elf = goblin::elf::Elf::parse(data)?;
+    let ceiling = version_compare::Version::from(ceiling)
+        .ok_or_else(|| anyhow!("unable to parse version {}", ceiling))?;
+
+    let mut res = find_undefined_elf_symbols(data, &elf)
+        .into_iter()
+        .filter(|symbol| {
+            symbol
+                .version
+                .as_deref()
+                .and_then(|v| v.strip_prefix(namespace))
+                .and_then(|v| v.strip_prefix('_'))
+                .and_then(version_compare::Version::from)
+                .map(|v| v > ceiling)
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    res.sort();
+
+    Ok(res)
+}
+
+/// Undefined symbols referenced by an ELF relocatable object.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ObjectSymbolReferences {
+    /// Name of the archive member holding the object, if in an archive.
+    pub member: Option<String>,
+    /// Names of undefined symbols.
+    pub symbols: BTreeSet<String>,
+}
+
+fn find_object_undefined_symbols(data: &[u8]) -> Result<BTreeSet<String>> {
+    let elf = goblin::elf::Elf::parse(data)?;
+
+    Ok(elf
+        .syms
+        .iter()
+        .filter(|sym| {
+            sym.st_shndx == goblin::elf::section_header::SHN_UNDEF as usize && sym.st_name != 0
+        })
+        .filter_map(|sym| elf.strtab.get_at(sym.st_name).map(|s| s.to_string()))
+        .collect())
+}
+
+/// Find undefined symbols referenced by an ELF object file or archive.
+///
+/// Archive members which aren't ELF objects, such as the metadata in Rust
+/// `.rlib` files, are ignored.
+pub fn find_elf_object_references(data: &[u8]) -> Result<Vec<ObjectSymbolReferences>> {
+    if data.starts_with(b"\x7fELF") {
+        return Ok(vec![ObjectSymbolReferences {
+            member: None,
+            symbols: find_object_undefined_symbols(data)?,
+        }]);
+    }
+
+    let archive = goblin::archive::Archive::parse(data)?;
+    let mut res = vec![];
+
+    for member in archive.members() {
+        let member_data = archive.extract(member, data)?;
+
+        if member_data.starts_with(b"\x7fELF") {
+            res.push(ObjectSymbolReferences {
+                member: Some(member.to_string()),
+                symbols: find_object_undefined_symbols(member_data)?,
+            });
+        }
+    }
+
+    Ok(res)
+}
@@ -7,7 +7,10 @@
 mod audit;
 pub use audit::{analyze_data, analyze_elf_libraries, analyze_file};
 mod elf;
-pub use elf::find_undefined_elf_symbols;
+pub use elf::{
+    find_elf_dynamic_requirements, find_elf_object_references, find_elf_symbols_above_version,
+    find_undefined_elf_symbols, ElfDynamicRequirements, ObjectSymbolReferences,
+};
 mod linux_distro_versions;
 pub use linux_distro_versions::{
     find_minimum_distro_version, GCC_VERSIONS_BY_DISTRO, GLIBC_VERSIONS_BY_DISTRO,
@@ -76,12 +76,15 @@ use {
     thiserror::Error,
     tugger_file_manifest::{File, FileData, FileEntry},
     tugger_windows_codesign::{
-        CodeSigningCertificate, FileBasedCodeSigningCertificate, SystemStore,
+        AzureKeyVaultCodeSigningCertificate, CodeSigningCertificate,
+        FileBasedCodeSigningCertificate, KeyContainerCodeSigningCertificate, SystemStore,
     },
     x509_certificate::{CapturedX509Certificate, InMemorySigningKeyPair, X509CertificateError},
     yasna::ASN1Error,
 };
 
+pub use tugger_windows_codesign::AzureKeyVaultCredentials;
+
 /// URL of Apple's time-stamp protocol server.
 pub const APPLE_TIMESTAMP_URL: &str = "http://timestamp.apple.com/ts01";
 
@@ -614,6 +617,19 @@ pub enum SigningCertificate {
     /// The string defines the SHA-1 thumbprint of the certificate. You can find this
     /// in the `Details` tab of the certificate when viewed in `certmgr.msc`.
     WindowsStoreSha1Thumbprint(SystemStore, String),
+
+    /// A certificate stored in Azure Key Vault.
+    ///
+    /// The private key never leaves the vault. Signing is only supported for
+    /// Windows signables.
+    AzureKeyVault(AzureKeyVaultCodeSigningCertificate),
+
+    /// A certificate whose private key is in a key container of a cryptographic
+    /// service provider (CSP).
+    ///
+    /// This is how vendors typically expose hardware tokens and HSMs to Windows.
+    /// Signing is only supported for Windows signables.
+    KeyContainer(KeyContainerCodeSigningCertificate),
 }
 
 impl SigningCertificate {
@@ -692,6 +708,39 @@ impl SigningCertificate {
         ))
     }
 
+    /// Construct an instance referring to a certificate in Azure Key Vault.
+    ///
+    /// `vault_url` is the URL of the vault (e.g. `https://myvault.vault.azure.net`)
+    /// and `certificate_name` the name of the certificate within it.
+    pub fn azure_key_vault(
+        vault_url: impl ToString,
+        certificate_name: impl ToString,
+        credentials: AzureKeyVaultCredentials,
+    ) -> Self {
+        Self::AzureKeyVault(AzureKeyVaultCodeSigningCertificate::new(
+            vault_url,
+            certificate_name,
+            credentials,
+        ))
+    }
+
+    /// Construct an instance referring to a certificate whose key is in a CSP key container.
+    ///
+    /// `certificate_path` is the path to a file holding the public certificate.
+    /// `provider` is the name of the cryptographic service provider and
+    /// `key_container` the name of the key container holding the private key.
+    pub fn key_container(
+        certificate_path: impl AsRef<Path>,
+        provider: impl ToString,
+        key_container: impl ToString,
+    ) -> Self {
+        Self::KeyContainer(KeyContainerCodeSigningCertificate::new(
+            certificate_path,
+            provider,
+            key_container,
+        ))
+    }
+
     /// Attempt to convert this instance to a [CodeSigningCertificate] for use signing on Windows.
     pub fn to_windows_code_signing_certificate(
         &self,
@@ -704,6 +753,8 @@ impl SigningCertificate {
             Self::WindowsStoreSubject(store, subject) => {
                 Ok(CodeSigningCertificate::SubjectName(*store, subject.clone()))
             }
+            Self::AzureKeyVault(cert) => Ok(CodeSigningCertificate::AzureKeyVault(cert.clone())),
+            Self::KeyContainer(cert) => Ok(CodeSigningCertificate::KeyContainer(cert.clone())),
             Self::PfxFile(path, password, _, _) => {
                 let mut f = FileBasedCodeSigningCertificate::new(path);
                 f.set_password(password);
@@ -1035,6 +1086,9 @@ impl<'a> SignableSigner<'a> {
             | SigningCertificate::WindowsStoreAuto => {
                 return Err(SigningError::CertificateNotUsable("certificates in the Windows store are not supported for signing Apple primitives; try using a PFX file-based certificate instead".to_string()));
             }
+            SigningCertificate::AzureKeyVault(_) | SigningCertificate::KeyContainer(_) => {
+                return Err(SigningError::CertificateNotUsable("remote and hardware backed certificates are not supported for signing Apple primitives; try using a PFX file-based certificate instead".to_string()));
+            }
         };
 
         // Automatically register Apple CA certificates for convenience.
@@ -1407,6 +1461,19 @@ mod tests {
         SigningCertificate::from_pfx_data(&pfx_data, "password").unwrap();
     }
 
+    #[test]
+    fn azure_key_vault_windows_certificate() {
+        let cert = SigningCertificate::azure_key_vault(
+            "https://vault.vault.azure.net",
+            "cert",
+            AzureKeyVaultCredentials::ManagedIdentity,
+        );
+        assert!(matches!(
+            cert.to_windows_code_signing_certificate().unwrap(),
+            CodeSigningCertificate::AzureKeyVault(_)
+        ));
+    }
+
     #[test]
     fn windows_store_with_subject() {
         let cert = SigningCertificate::windows_store_with_subject("my", "test user").unwrap();
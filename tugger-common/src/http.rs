@@ -5,12 +5,312 @@
 use {
     anyhow::{anyhow, Context, Result},
     fs2::FileExt,
+    once_cell::sync::Lazy,
+    reqwest::StatusCode,
     sha2::Digest,
     slog::warn,
-    std::{fs::File, io::Read, path::Path},
+    std::{
+        collections::HashMap,
+        fs::File,
+        io::Read,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            RwLock,
+        },
+        time::Duration,
+    },
     url::Url,
 };
 
+/// Whether network access is disabled.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Disable or enable network access for the current process.
+///
+/// When disabled, fetching remote content that isn't available locally
+/// fails instead of touching the network.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+/// Whether network access is disabled for the current process.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Error if network access is disabled.
+///
+/// `url` is the URL that would have been fetched.
+pub fn ensure_online(url: &str) -> Result<()> {
+    if is_offline() {
+        Err(anyhow!(
+            "network access is disabled in offline mode; unable to fetch {}",
+            url
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Credentials sent with HTTP requests.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum HttpAuth {
+    /// Send an `Authorization: Bearer` header with the given token.
+    Bearer(String),
+    /// Use HTTP basic authentication.
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+impl HttpAuth {
+    /// Parse credentials from a `bearer:<token>` or `basic:<username>[:<password>]` string.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some(("bearer", token)) if !token.is_empty() => Ok(Self::Bearer(token.to_string())),
+            Some(("basic", credentials)) if !credentials.is_empty() => {
+                let (username, password) = match credentials.split_once(':') {
+                    Some((username, password)) => (username, Some(password.to_string())),
+                    None => (credentials, None),
+                };
+
+                Ok(Self::Basic {
+                    username: username.to_string(),
+                    password,
+                })
+            }
+            _ => Err(anyhow!(
+                "invalid HTTP credentials; expected bearer:<token> or basic:<username>:<password>"
+            )),
+        }
+    }
+
+    fn apply(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+    ) -> reqwest::blocking::RequestBuilder {
+        match self {
+            Self::Bearer(token) => request.bearer_auth(token),
+            Self::Basic { username, password } => request.basic_auth(username, password.as_ref()),
+        }
+    }
+}
+
+/// Configuration of the HTTP transport used to fetch remote content.
+///
+/// A process-wide instance is consulted by [get_http_client()] and [fetch_url()].
+/// It is initialized from environment variables (see [HttpClientConfig::from_env()])
+/// and can be replaced with [set_http_client_config()].
+#[derive(Clone, Debug)]
+pub struct HttpClientConfig {
+    /// How many times to retry requests failing with a transient error.
+    pub retries: u32,
+
+    /// Delay before the first retry. Doubles with each retry.
+    pub retry_backoff: Duration,
+
+    /// Upper bound for the delay between retries.
+    pub max_retry_backoff: Duration,
+
+    /// Timeout of individual requests, including reading the response body.
+    pub timeout: Option<Duration>,
+
+    /// Maximum number of idle connections kept open per host.
+    pub max_idle_connections_per_host: Option<usize>,
+
+    /// Proxy used for all requests, overriding the `*_PROXY` environment variables.
+    pub proxy: Option<String>,
+
+    /// Paths of PEM files with additional trusted root certificates.
+    pub ca_certificates: Vec<PathBuf>,
+
+    /// Credentials to send, keyed by host name.
+    pub credentials: HashMap<String, HttpAuth>,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            retry_backoff: Duration::from_millis(500),
+            max_retry_backoff: Duration::from_secs(30),
+            timeout: None,
+            max_idle_connections_per_host: None,
+            proxy: None,
+            ca_certificates: vec![],
+            credentials: HashMap::new(),
+        }
+    }
+}
+
+impl HttpClientConfig {
+    /// Construct an instance from environment variables.
+    ///
+    /// The following variables are recognized:
+    ///
+    /// `TUGGER_HTTP_RETRIES`
+    ///    Number of retries of failed requests.
+    /// `TUGGER_HTTP_RETRY_BACKOFF_MS`
+    ///    Delay before the first retry, in milliseconds.
+    /// `TUGGER_HTTP_TIMEOUT`
+    ///    Request timeout, in seconds.
+    /// `TUGGER_HTTP_MAX_IDLE_PER_HOST`
+    ///    Maximum number of idle connections per host.
+    /// `TUGGER_HTTP_CA_BUNDLE` (falling back to `SSL_CERT_FILE`)
+    ///    Paths of PEM files with trusted root certificates, separated like `PATH`.
+    /// `TUGGER_HTTP_CREDENTIALS`
+    ///    `;` delimited `<host>=<credentials>` pairs. See [HttpAuth::parse()].
+    ///
+    /// Proxies are configured through the `HTTP_PROXY`, `HTTPS_PROXY` and
+    /// `ALL_PROXY` variables, which are read when clients are constructed.
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.is_empty());
+
+        if let Some(value) = var("TUGGER_HTTP_RETRIES") {
+            config.retries = value
+                .parse()
+                .with_context(|| format!("parsing TUGGER_HTTP_RETRIES value {}", value))?;
+        }
+        if let Some(value) = var("TUGGER_HTTP_RETRY_BACKOFF_MS") {
+            config.retry_backoff = Duration::from_millis(value.parse().with_context(|| {
+                format!("parsing TUGGER_HTTP_RETRY_BACKOFF_MS value {}", value)
+            })?);
+        }
+        if let Some(value) = var("TUGGER_HTTP_TIMEOUT") {
+            config.timeout =
+                Some(Duration::from_secs(value.parse().with_context(|| {
+                    format!("parsing TUGGER_HTTP_TIMEOUT value {}", value)
+                })?));
+        }
+        if let Some(value) = var("TUGGER_HTTP_MAX_IDLE_PER_HOST") {
+            config.max_idle_connections_per_host = Some(value.parse().with_context(|| {
+                format!("parsing TUGGER_HTTP_MAX_IDLE_PER_HOST value {}", value)
+            })?);
+        }
+        if let Some(value) = var("TUGGER_HTTP_CA_BUNDLE").or_else(|| var("SSL_CERT_FILE")) {
+            config.ca_certificates = std::env::split_paths(&value).collect();
+        }
+        if let Some(value) = var("TUGGER_HTTP_CREDENTIALS") {
+            config.credentials = parse_credentials(&value)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Obtain the delay before a retry.
+    ///
+    /// `attempt` is the 0-based index of the retry.
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        self.retry_backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_retry_backoff)
+            .min(self.max_retry_backoff)
+    }
+
+    /// Obtain a builder for a blocking HTTP client honoring this configuration.
+    pub fn client_builder(&self) -> Result<reqwest::blocking::ClientBuilder> {
+        let mut builder = reqwest::blocking::ClientBuilder::new();
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(proxy).with_context(|| format!("parsing proxy {}", proxy))?,
+            );
+        } else {
+            for proxy in env_proxies() {
+                builder = builder.proxy(proxy);
+            }
+        }
+
+        for path in &self.ca_certificates {
+            let data = std::fs::read(path)
+                .with_context(|| format!("reading CA certificates from {}", path.display()))?;
+            let certificate = reqwest::Certificate::from_pem(&data)
+                .with_context(|| format!("parsing CA certificates from {}", path.display()))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max) = self.max_idle_connections_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Parse `;` delimited `<host>=<credentials>` pairs.
+fn parse_credentials(s: &str) -> Result<HashMap<String, HttpAuth>> {
+    s.split(';')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (host, auth) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("HTTP credentials entry missing host: {}", entry))?;
+
+            Ok((
+                host.to_lowercase(),
+                HttpAuth::parse(auth).with_context(|| format!("credentials for {}", host))?,
+            ))
+        })
+        .collect()
+}
+
+/// Resolve proxies defined by `*_PROXY` environment variables.
+fn env_proxies() -> Vec<reqwest::Proxy> {
+    let mut proxies = vec![];
+
+    for (key, value) in std::env::vars() {
+        let key = key.to_lowercase();
+        if key.ends_with("_proxy") {
+            let end = key.len() - "_proxy".len();
+            let schema = &key[..end];
+
+            if let Ok(url) = Url::parse(&value) {
+                if let Some(Ok(proxy)) = match schema {
+                    "http" => Some(reqwest::Proxy::http(url.as_str())),
+                    "https" => Some(reqwest::Proxy::https(url.as_str())),
+                    "all" => Some(reqwest::Proxy::all(url.as_str())),
+                    _ => None,
+                } {
+                    proxies.push(proxy);
+                }
+            }
+        }
+    }
+
+    proxies
+}
+
+/// The HTTP transport configuration of the current process.
+///
+/// Holds an error message if the environment defines an invalid configuration.
+static HTTP_CONFIG: Lazy<RwLock<std::result::Result<HttpClientConfig, String>>> =
+    Lazy::new(|| RwLock::new(HttpClientConfig::from_env().map_err(|e| format!("{:#}", e))));
+
+/// Replace the HTTP transport configuration of the current process.
+pub fn set_http_client_config(config: HttpClientConfig) {
+    *HTTP_CONFIG.write().unwrap() = Ok(config);
+}
+
+/// Obtain the HTTP transport configuration of the current process.
+///
+/// Errors if the environment variables read by [HttpClientConfig::from_env()]
+/// are invalid and no configuration was set with [set_http_client_config()].
+pub fn http_client_config() -> Result<HttpClientConfig> {
+    HTTP_CONFIG
+        .read()
+        .unwrap()
+        .clone()
+        .map_err(|e| anyhow!("invalid HTTP client configuration in environment: {}", e))
+}
+
 /// Defines remote content that can be downloaded securely.
 pub struct RemoteContent {
     /// Identifier used for configuring an override for the URL.
@@ -37,45 +337,114 @@ fn sha256_path<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
     Ok(hasher.finalize().to_vec())
 }
 
-/// Obtain an HTTP client, taking proxy environment variables into account.
-pub fn get_http_client() -> reqwest::Result<reqwest::blocking::Client> {
-    let mut builder = reqwest::blocking::ClientBuilder::new();
+/// Obtain an HTTP client honoring the configuration of the current process.
+///
+/// See [HttpClientConfig] for what can be configured.
+pub fn get_http_client() -> Result<reqwest::blocking::Client> {
+    Ok(http_client_config()?.client_builder()?.build()?)
+}
 
-    for (key, value) in std::env::vars() {
-        let key = key.to_lowercase();
-        if key.ends_with("_proxy") {
-            let end = key.len() - "_proxy".len();
-            let schema = &key[..end];
+/// Whether a response status indicates a transient failure worth retrying.
+///
+/// The asynchronous client of the `debian-packaging` crate has its own copy
+/// of this and [HttpClientConfig::retry_delay()]. Tests of both use the same
+/// cases.
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
 
-            if let Ok(url) = Url::parse(&value) {
-                if let Some(Ok(proxy)) = match schema {
-                    "http" => Some(reqwest::Proxy::http(url.as_str())),
-                    "https" => Some(reqwest::Proxy::https(url.as_str())),
-                    _ => None,
-                } {
-                    builder = builder.proxy(proxy);
+/// Fetch the content of a URL, returning `None` if it doesn't exist.
+///
+/// Requests go through a client obtained from [get_http_client()]. Failures to
+/// connect and responses with a 5xx, 408 or 429 status are retried with
+/// exponential backoff as configured.
+///
+/// Credentials in the URL's user info are sent as HTTP basic authentication.
+/// Otherwise credentials configured for the URL's host, if any, are sent.
+pub fn fetch_url_if_exists(url: &str) -> Result<Option<Vec<u8>>> {
+    ensure_online(url)?;
+
+    let config = http_client_config()?;
+    let client = config.client_builder()?.build()?;
+
+    let mut url = Url::parse(url).with_context(|| format!("parsing URL {}", url))?;
+    let auth = if !url.username().is_empty() || url.password().is_some() {
+        let auth = HttpAuth::Basic {
+            username: url.username().to_string(),
+            password: url.password().map(|s| s.to_string()),
+        };
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+
+        Some(auth)
+    } else {
+        url.host_str()
+            .and_then(|host| config.credentials.get(&host.to_lowercase()))
+            .cloned()
+    };
+
+    let mut attempt = 0;
+
+    loop {
+        let mut request = client.get(url.clone());
+        if let Some(auth) = &auth {
+            request = auth.apply(request);
+        }
+
+        let err = match request.send() {
+            Ok(response) if response.status() == StatusCode::NOT_FOUND => return Ok(None),
+            Ok(response) if is_retryable_status(response.status()) => {
+                anyhow!("HTTP {} fetching {}", response.status(), url)
+            }
+            Ok(response) => {
+                let response = response
+                    .error_for_status()
+                    .with_context(|| format!("fetching {}", url))?;
+
+                match response.bytes() {
+                    Ok(data) => return Ok(Some(data.to_vec())),
+                    Err(e) => anyhow!(e).context(format!("reading response from {}", url)),
                 }
             }
+            Err(e) if e.is_builder() || e.is_redirect() => {
+                return Err(anyhow!(e).context(format!("fetching {}", url)))
+            }
+            Err(e) => anyhow!(e).context(format!("fetching {}", url)),
+        };
+
+        if attempt >= config.retries {
+            return Err(err);
         }
+
+        std::thread::sleep(config.retry_delay(attempt));
+        attempt += 1;
     }
+}
 
-    builder.build()
+/// Fetch the content of a URL.
+///
+/// Like [fetch_url_if_exists()] but a missing URL is an error.
+pub fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    fetch_url_if_exists(url)?.ok_or_else(|| anyhow!("HTTP 404 fetching {}", url))
 }
 
 /// Fetch a URL and verify its SHA-256 matches expectations.
 pub fn download_and_verify(logger: &slog::Logger, entry: &RemoteContent) -> Result<Vec<u8>> {
-    let url = std::env::var(format!("{}_URL", &entry.name)).unwrap_or_else(|_err| entry.url.to_string());
+    let url =
+        std::env::var(format!("{}_URL", &entry.name)).unwrap_or_else(|_err| entry.url.to_string());
     warn!(logger, "downloading {}", url);
     let url = Url::parse(&url)?;
-    let client = get_http_client()?;
     let mut data: Vec<u8> = Vec::new();
     if url.scheme() == "file" {
-        let file_path = url.to_file_path().map_err(|_err: ()| anyhow!("bad url for {}: {}", entry.name, url))?;
+        let file_path = url
+            .to_file_path()
+            .map_err(|_err: ()| anyhow!("bad url for {}: {}", entry.name, url))?;
         let mut file = File::open(&file_path)?;
         file.read_to_end(&mut data)?;
     } else {
-        let mut response = client.get(url).send()?;
-        response.read_to_end(&mut data)?;
+        data = fetch_url(url.as_str())?;
     }
 
     let mut hasher = sha2::Sha256::new();
@@ -145,3 +514,84 @@ pub fn download_to_path<P: AsRef<Path>>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_auth() -> Result<()> {
+        assert_eq!(
+            HttpAuth::parse("bearer:secret")?,
+            HttpAuth::Bearer("secret".to_string())
+        );
+        assert_eq!(
+            HttpAuth::parse("basic:user:pass:word")?,
+            HttpAuth::Basic {
+                username: "user".to_string(),
+                password: Some("pass:word".to_string())
+            }
+        );
+        assert_eq!(
+            HttpAuth::parse("basic:user")?,
+            HttpAuth::Basic {
+                username: "user".to_string(),
+                password: None
+            }
+        );
+        assert!(HttpAuth::parse("bearer:").is_err());
+        assert!(HttpAuth::parse("digest:foo").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_credentials() -> Result<()> {
+        let credentials =
+            parse_credentials("Mirror.example.com=bearer:abc; pypi.example.com=basic:me:pw;")?;
+
+        assert_eq!(credentials.len(), 2);
+        assert_eq!(
+            credentials.get("mirror.example.com"),
+            Some(&HttpAuth::Bearer("abc".to_string()))
+        );
+        assert!(parse_credentials("bearer:abc").is_err());
+
+        Ok(())
+    }
+
+    // Keep in sync with the retry tests of debian-packaging's HTTP client.
+    #[test]
+    fn test_retry_delay() {
+        let config = HttpClientConfig {
+            retry_backoff: Duration::from_millis(100),
+            max_retry_backoff: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        assert_eq!(config.retry_delay(0), Duration::from_millis(100));
+        assert_eq!(config.retry_delay(3), Duration::from_millis(800));
+        assert_eq!(config.retry_delay(4), Duration::from_secs(1));
+        assert_eq!(config.retry_delay(64), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_retryable_status() {
+        for status in [500, 502, 503, 408, 429] {
+            assert!(is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 301, 403, 404] {
+            assert!(!is_retryable_status(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_bad_ca_bundle() {
+        let config = HttpClientConfig {
+            ca_certificates: vec![PathBuf::from("/nonexistent/ca.pem")],
+            ..Default::default()
+        };
+
+        assert!(config.client_builder().is_err());
+    }
+}
@@ -4,5 +4,6 @@
 
 pub mod glob;
 pub mod http;
+pub mod secrets;
 pub mod testutil;
 pub mod zipfile;
@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Detect secret values in build artifacts. */
+
+use {
+    anyhow::{anyhow, Result},
+    std::sync::{Arc, RwLock},
+};
+
+/// Secret values shorter than this many bytes aren't searched for in artifacts.
+///
+/// Short values occur by chance in binary data and would fail builds spuriously.
+pub const MIN_SECRET_LENGTH: usize = 8;
+
+#[derive(Debug, Default)]
+struct SecretsState {
+    values: Vec<String>,
+    embedding_allowed: bool,
+}
+
+/// A set of secret values that must not be embedded in build artifacts.
+///
+/// Clones share state, so secrets registered through one clone are
+/// detected by all of them.
+#[derive(Clone, Debug, Default)]
+pub struct Secrets {
+    state: Arc<RwLock<SecretsState>>,
+}
+
+impl Secrets {
+    /// Register a secret value.
+    ///
+    /// Values shorter than [MIN_SECRET_LENGTH] are ignored.
+    pub fn add(&self, value: &str) {
+        if value.len() < MIN_SECRET_LENGTH {
+            return;
+        }
+
+        let mut state = self.state.write().unwrap();
+        if !state.values.iter().any(|v| v == value) {
+            state.values.push(value.to_string());
+        }
+    }
+
+    /// Registered secret values.
+    pub fn values(&self) -> Vec<String> {
+        self.state.read().unwrap().values.clone()
+    }
+
+    /// Allow secret values to be embedded in build artifacts.
+    pub fn allow_embedding(&self) {
+        self.state.write().unwrap().embedding_allowed = true;
+    }
+
+    /// Whether secret values may be embedded in build artifacts.
+    pub fn embedding_allowed(&self) -> bool {
+        self.state.read().unwrap().embedding_allowed
+    }
+
+    /// Ensure artifact data doesn't contain a registered secret value.
+    ///
+    /// `artifact` describes the data in the error message. The secret itself
+    /// is never included in the error.
+    pub fn ensure_not_in(&self, artifact: &str, data: &[u8]) -> Result<()> {
+        let state = self.state.read().unwrap();
+
+        if state.embedding_allowed {
+            return Ok(());
+        }
+
+        if state.values.iter().any(|secret| {
+            data.windows(secret.len())
+                .any(|window| window == secret.as_bytes())
+        }) {
+            Err(anyhow!(
+                "{} contains the value of a secret variable; refusing to write it (use --allow-secret-embedding to override)",
+                artifact
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_not_in() -> Result<()> {
+        let secrets = Secrets::default();
+        secrets.add("hunter2hunter2");
+        secrets.add("short");
+        assert_eq!(secrets.values(), vec!["hunter2hunter2".to_string()]);
+
+        let shared = secrets.clone();
+        shared.add("password123");
+        assert_eq!(secrets.values().len(), 2);
+
+        secrets.ensure_not_in("clean", b"nothing to see; short")?;
+
+        let err = secrets
+            .ensure_not_in("leaky", b"prefix password123 suffix")
+            .unwrap_err();
+        assert!(err.to_string().starts_with("leaky contains"));
+        assert!(!err.to_string().contains("password123"));
+
+        shared.allow_embedding();
+        secrets.ensure_not_in("leaky", b"prefix password123 suffix")?;
+
+        Ok(())
+    }
+}
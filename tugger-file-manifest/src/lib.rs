@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     ffi::OsStr,
     io::Write,
     path::{Path, PathBuf},
@@ -51,6 +51,25 @@ pub const S_IFLNK: u32 = 0o120000;
 /// File mode indicating a socket.
 pub const S_IFSOCK: u32 = 0o140000;
 
+/// Parse a `SOURCE_DATE_EPOCH` value into a time.
+fn parse_source_date_epoch(value: &str) -> Option<std::time::SystemTime> {
+    let seconds = value.trim().parse::<u64>().ok()?;
+
+    Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds))
+}
+
+/// Resolve the time defined by the `SOURCE_DATE_EPOCH` environment variable.
+///
+/// See <https://reproducible-builds.org/specs/source-date-epoch/>. Tools
+/// producing archives should use this time in place of the current time so
+/// their output is deterministic.
+///
+/// Returns `None` if the variable isn't set or isn't a non-negative integer
+/// number of seconds.
+pub fn source_date_epoch() -> Option<std::time::SystemTime> {
+    parse_source_date_epoch(&std::env::var("SOURCE_DATE_EPOCH").ok()?)
+}
+
 #[cfg(unix)]
 pub fn is_executable(metadata: &std::fs::Metadata) -> bool {
     let permissions = metadata.permissions();
@@ -75,6 +94,62 @@ pub fn set_executable(_file: &mut std::fs::File) -> Result<(), std::io::Error> {
     Ok(())
 }
 
+/// Obtain the permission bits of a file, including setuid, setgid, and sticky bits.
+#[cfg(unix)]
+pub fn file_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(windows)]
+pub fn file_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Set the permission bits of a file.
+#[cfg(unix)]
+pub fn set_file_mode(file: &mut std::fs::File, mode: u32) -> Result<(), std::io::Error> {
+    let mut permissions = file.metadata()?.permissions();
+    permissions.set_mode(mode & 0o7777);
+    file.set_permissions(permissions)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn set_file_mode(_file: &mut std::fs::File, _mode: u32) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+/// Obtain the `(device, inode)` pair identifying a file on disk.
+#[cfg(unix)]
+fn file_identity(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(windows)]
+fn file_identity(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Whether two paths refer to the same file on disk.
+fn is_same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    matches!((file_identity(a), file_identity(b)), (Some(a), Some(b)) if a == b)
+}
+
+/// Obtain the number of hard links to a file.
+#[cfg(unix)]
+fn hard_link_count(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink()
+}
+
+#[cfg(windows)]
+fn hard_link_count(_metadata: &std::fs::Metadata) -> u64 {
+    1
+}
+
 #[cfg(unix)]
 pub fn create_symlink(
     path: impl AsRef<Path>,
@@ -173,8 +248,16 @@ pub struct FileEntry {
     /// Whether the file is executable.
     executable: bool,
 
+    /// Explicit permission bits for the file.
+    ///
+    /// If not set, permissions are derived from the executable bit.
+    mode: Option<u32>,
+
     /// Indicates that this file is a link pointing to the specified path.
     link: Option<PathBuf>,
+
+    /// Indicates that this file is a hard link to another path in the same manifest.
+    hardlink: Option<PathBuf>,
 }
 
 impl TryFrom<&Path> for FileEntry {
@@ -187,7 +270,9 @@ impl TryFrom<&Path> for FileEntry {
         Ok(Self {
             data: FileData::from(path),
             executable,
+            mode: file_mode(&metadata),
             link: None,
+            hardlink: None,
         })
     }
 }
@@ -211,7 +296,9 @@ impl From<Vec<u8>> for FileEntry {
         Self {
             data: data.into(),
             executable: false,
+            mode: None,
             link: None,
+            hardlink: None,
         }
     }
 }
@@ -221,7 +308,9 @@ impl From<&[u8]> for FileEntry {
         Self {
             data: data.into(),
             executable: false,
+            mode: None,
             link: None,
+            hardlink: None,
         }
     }
 }
@@ -232,7 +321,9 @@ impl FileEntry {
         Self {
             data: data.into(),
             executable,
+            mode: None,
             link: None,
+            hardlink: None,
         }
     }
 
@@ -241,7 +332,31 @@ impl FileEntry {
         Self {
             data: path.as_ref().into(),
             executable,
+            mode: None,
             link: None,
+            hardlink: None,
+        }
+    }
+
+    /// Construct a new instance representing a symlink to the specified target.
+    pub fn new_symlink(target: impl AsRef<Path>) -> Self {
+        Self {
+            data: vec![].into(),
+            executable: false,
+            mode: None,
+            link: Some(target.as_ref().to_path_buf()),
+            hardlink: None,
+        }
+    }
+
+    /// Construct a new instance representing a hard link to another manifest path.
+    pub fn new_hardlink(target: impl AsRef<Path>) -> Self {
+        Self {
+            data: vec![].into(),
+            executable: false,
+            mode: None,
+            link: None,
+            hardlink: Some(target.as_ref().to_path_buf()),
         }
     }
 
@@ -256,8 +371,41 @@ impl FileEntry {
     }
 
     /// Set whether the file is executable.
+    ///
+    /// If explicit permissions are defined, their execute bits are updated
+    /// accordingly.
     pub fn set_executable(&mut self, v: bool) {
         self.executable = v;
+
+        if let Some(mode) = self.mode {
+            self.mode = Some(if v {
+                mode | ((mode & 0o444) >> 2)
+            } else {
+                mode & !0o111
+            });
+        }
+    }
+
+    /// Obtain the permission bits of this file.
+    ///
+    /// If explicit permissions aren't defined, `0o755` is returned for
+    /// executable files and `0o644` for everything else.
+    pub fn mode(&self) -> u32 {
+        self.mode
+            .unwrap_or(if self.executable { 0o755 } else { 0o644 })
+    }
+
+    /// Obtain the explicitly defined permission bits of this file, if set.
+    pub fn explicit_mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Set explicit permission bits for this file.
+    ///
+    /// The executable bit is updated to reflect whether any execute bit is set.
+    pub fn set_mode(&mut self, mode: u32) {
+        self.mode = Some(mode & 0o7777);
+        self.executable = mode & 0o111 != 0;
     }
 
     /// Resolve the data constituting this file.
@@ -270,16 +418,31 @@ impl FileEntry {
         self.link.as_deref()
     }
 
+    /// Obtain the manifest path this entry is hard linked to, if this is a hard link.
+    pub fn hardlink_target(&self) -> Option<&Path> {
+        self.hardlink.as_deref()
+    }
+
+    /// Whether this entry is a symlink or hard link.
+    pub fn is_link(&self) -> bool {
+        self.link.is_some() || self.hardlink.is_some()
+    }
+
     /// Obtain a new instance guaranteed to have file data stored in memory.
     pub fn to_memory(&self) -> Result<Self, std::io::Error> {
         Ok(Self {
             data: self.data.to_memory()?,
             executable: self.executable,
+            mode: self.mode,
             link: self.link.clone(),
+            hardlink: self.hardlink.clone(),
         })
     }
 
     /// Write this file entry to the given destination path.
+    ///
+    /// Hard links can't be written in isolation, as their target is relative
+    /// to a [FileManifest]. Use [FileManifest::materialize_files()] instead.
     pub fn write_to_path(&self, dest_path: impl AsRef<Path>) -> Result<(), FileManifestError> {
         let dest_path = dest_path.as_ref();
         let parent = dest_path
@@ -288,12 +451,22 @@ impl FileEntry {
 
         std::fs::create_dir_all(parent)?;
 
-        if let Some(link) = &self.link {
+        if let Some(target) = &self.hardlink {
+            return Err(FileManifestError::UnresolvedHardlink(format!(
+                "{}",
+                target.display()
+            )));
+        } else if let Some(link) = &self.link {
+            if std::fs::symlink_metadata(dest_path).is_ok() {
+                std::fs::remove_file(dest_path)?;
+            }
             create_symlink(dest_path, link)?;
         } else {
             let mut fh = std::fs::File::create(&dest_path)?;
             fh.write_all(&self.resolve_content()?)?;
-            if self.executable {
+            if let Some(mode) = self.mode {
+                set_file_mode(&mut fh, mode)?;
+            } else if self.executable {
                 set_executable(&mut fh)?;
             }
         }
@@ -375,6 +548,7 @@ pub enum FileManifestError {
     IoError(std::io::Error),
     StripPrefix(std::path::StripPrefixError),
     LinkNotAllowed,
+    UnresolvedHardlink(String),
 }
 
 impl std::fmt::Display for FileManifestError {
@@ -390,6 +564,10 @@ impl std::fmt::Display for FileManifestError {
             Self::IoError(inner) => inner.fmt(f),
             Self::StripPrefix(inner) => inner.fmt(f),
             Self::LinkNotAllowed => f.write_str("links are not allowed on this FileManifest"),
+            Self::UnresolvedHardlink(path) => f.write_str(&format!(
+                "hard link target is not a regular file in the FileManifest: {}",
+                path
+            )),
         }
     }
 }
@@ -427,23 +605,80 @@ pub fn normalize_path(path: &Path) -> Result<PathBuf, FileManifestError> {
     Ok(PathBuf::from(path_s))
 }
 
+/// Describes how the content of a directory differs from a [FileManifest].
+///
+/// All paths are relative to the directory and use `/` as the separator.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FileManifestDiff {
+    /// Manifest paths that don't exist in the directory.
+    pub added: Vec<PathBuf>,
+
+    /// Manifest paths whose content, permissions, or link target differ.
+    pub changed: Vec<PathBuf>,
+
+    /// Files in the directory that aren't in the manifest.
+    pub removed: Vec<PathBuf>,
+}
+
+impl FileManifestDiff {
+    /// Whether the directory already reflects the manifest.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Collect the paths of all non-directory entries under a directory.
+///
+/// Symlinks to directories are not followed.
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut BTreeSet<PathBuf>,
+) -> Result<(), FileManifestError> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let rel_path = path.strip_prefix(root)?;
+            files.insert(PathBuf::from(
+                format!("{}", rel_path.display()).replace('\\', "/"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Represents a collection of files.
 ///
 /// Files are keyed by their path. The file content is abstract and can be
 /// backed by multiple sources.
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default)]
 pub struct FileManifest {
     files: BTreeMap<PathBuf, FileEntry>,
     /// Whether to allow storage of links.
     allow_links: bool,
+    /// Manifest paths of added filesystem files having multiple hard links.
+    ///
+    /// Keyed by the `(device, inode)` of the file on disk.
+    hardlink_sources: HashMap<(u64, u64), PathBuf>,
+}
+
+impl PartialEq for FileManifest {
+    fn eq(&self, other: &Self) -> bool {
+        self.files == other.files && self.allow_links == other.allow_links
+    }
 }
 
 impl FileManifest {
     /// Create a new instance that allows the storage of links.
     pub fn new_with_links() -> Self {
         Self {
-            files: BTreeMap::new(),
             allow_links: true,
+            ..Default::default()
         }
     }
 
@@ -469,8 +704,9 @@ impl FileManifest {
 
         let add_path = path.strip_prefix(strip_prefix)?;
 
-        self.files
-            .insert(normalize_path(add_path)?, FileEntry::try_from(path)?);
+        let add_path = normalize_path(add_path)?;
+        let entry = self.resolve_path_entry(path, &add_path)?;
+        self.files.insert(add_path, entry);
 
         Ok(())
     }
@@ -490,12 +726,55 @@ impl FileManifest {
 
         let add_path = path.strip_prefix(strip_prefix)?;
 
-        let entry = FileEntry::try_from(path)?.to_memory()?;
-        self.files.insert(normalize_path(add_path)?, entry);
+        let add_path = normalize_path(add_path)?;
+        let entry = self.resolve_path_entry(path, &add_path)?.to_memory()?;
+        self.files.insert(add_path, entry);
 
         Ok(())
     }
 
+    /// Resolve the [FileEntry] for a file on the filesystem.
+    ///
+    /// If this manifest allows links, symlinks are preserved as symlinks and
+    /// files hard linked to a file already in this manifest become hard links.
+    /// Otherwise links are followed and their content is stored.
+    ///
+    /// `manifest_path` is the path the entry will be stored under.
+    fn resolve_path_entry(
+        &mut self,
+        path: &Path,
+        manifest_path: &Path,
+    ) -> Result<FileEntry, FileManifestError> {
+        if self.allow_links {
+            let metadata = std::fs::symlink_metadata(path)?;
+
+            if metadata.file_type().is_symlink() {
+                return Ok(FileEntry::new_symlink(std::fs::read_link(path)?));
+            }
+
+            if hard_link_count(&metadata) > 1 {
+                if let Some(identity) = file_identity(&metadata) {
+                    if let Some(source) = self.hardlink_sources.get(&identity) {
+                        // The source path may since have been removed or replaced by a link.
+                        if source != manifest_path
+                            && self
+                                .files
+                                .get(source)
+                                .map_or(false, |entry| !entry.is_link())
+                        {
+                            return Ok(FileEntry::new_hardlink(source));
+                        }
+                    }
+
+                    self.hardlink_sources
+                        .insert(identity, manifest_path.to_path_buf());
+                }
+            }
+        }
+
+        Ok(FileEntry::try_from(path)?)
+    }
+
     /// Add a `FileEntry` to this manifest under the given path.
     ///
     /// The path cannot contain relative paths and must not be absolute.
@@ -506,7 +785,7 @@ impl FileManifest {
     ) -> Result<(), FileManifestError> {
         let entry = entry.into();
 
-        if entry.link.is_some() && !self.allow_links {
+        if entry.is_link() && !self.allow_links {
             return Err(FileManifestError::LinkNotAllowed);
         }
 
@@ -533,13 +812,39 @@ impl FileManifest {
         manifest_path: impl AsRef<Path>,
         link_target: impl AsRef<Path>,
     ) -> Result<(), FileManifestError> {
-        let entry = FileEntry {
-            data: vec![].into(),
-            executable: false,
-            link: Some(link_target.as_ref().to_path_buf()),
-        };
+        self.add_file_entry(manifest_path, FileEntry::new_symlink(link_target))
+    }
 
-        self.add_file_entry(manifest_path, entry)
+    /// Add a hard link to another file in this manifest.
+    ///
+    /// `target` is the manifest path of a regular file already in this manifest.
+    pub fn add_hardlink(
+        &mut self,
+        manifest_path: impl AsRef<Path>,
+        target: impl AsRef<Path>,
+    ) -> Result<(), FileManifestError> {
+        let target = normalize_path(target.as_ref())?;
+        self.hardlink_target_entry(&target)?;
+
+        self.add_file_entry(manifest_path, FileEntry::new_hardlink(target))
+    }
+
+    /// Resolve the entry a hard link points to.
+    ///
+    /// Errors if the target doesn't exist or isn't a regular file.
+    pub fn hardlink_target_entry(
+        &self,
+        target: impl AsRef<Path>,
+    ) -> Result<&FileEntry, FileManifestError> {
+        let target = target.as_ref();
+
+        match self.files.get(target) {
+            Some(entry) if !entry.is_link() => Ok(entry),
+            _ => Err(FileManifestError::UnresolvedHardlink(format!(
+                "{}",
+                target.display()
+            ))),
+        }
     }
 
     /// Merge the content of another manifest into this one.
@@ -657,26 +962,236 @@ impl FileManifest {
         res
     }
 
+    /// Obtain entries in the order they should be materialized.
+    ///
+    /// Hard links come after all other entries so their targets exist.
+    fn materialization_order(&self) -> impl Iterator<Item = (&PathBuf, &FileEntry)> {
+        self.files
+            .iter()
+            .filter(|(_, entry)| entry.hardlink.is_none())
+            .chain(
+                self.files
+                    .iter()
+                    .filter(|(_, entry)| entry.hardlink.is_some()),
+            )
+    }
+
+    /// Write a single entry of this manifest under the specified directory.
+    fn write_entry(
+        &self,
+        dest: &Path,
+        path: &Path,
+        entry: &FileEntry,
+    ) -> Result<PathBuf, FileManifestError> {
+        let dest_path = dest.join(path);
+
+        if let Some(target) = &entry.hardlink {
+            self.hardlink_target_entry(target)?;
+
+            let parent = dest_path
+                .parent()
+                .ok_or(FileManifestError::NoParentDirectory)?;
+            std::fs::create_dir_all(parent)?;
+
+            if std::fs::symlink_metadata(&dest_path).is_ok() {
+                std::fs::remove_file(&dest_path)?;
+            }
+
+            std::fs::hard_link(dest.join(target), &dest_path)?;
+        } else {
+            entry.write_to_path(&dest_path)?;
+        }
+
+        Ok(dest_path)
+    }
+
     /// Write files in this manifest to the specified path.
     ///
     /// Existing files will be replaced if they exist.
     pub fn materialize_files(
         &self,
         dest: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>, FileManifestError> {
+        self.materialize_files_with_progress(dest, |_, _, _| {})
+    }
+
+    /// Write files in this manifest to the specified path, reporting progress.
+    ///
+    /// `progress` is called after each file is written with the number of files
+    /// written so far, the total number of files, and the manifest path of the
+    /// written file.
+    pub fn materialize_files_with_progress(
+        &self,
+        dest: impl AsRef<Path>,
+        mut progress: impl FnMut(usize, usize, &Path),
     ) -> Result<Vec<PathBuf>, FileManifestError> {
         let mut dest_paths = vec![];
 
         let dest = dest.as_ref();
+        let total = self.files.len();
 
-        for (k, v) in self.iter_entries() {
-            let dest_path = dest.join(k);
-            v.write_to_path(&dest_path)?;
-            dest_paths.push(dest_path)
+        for (i, (k, v)) in self.materialization_order().enumerate() {
+            dest_paths.push(self.write_entry(dest, k, v)?);
+            progress(i + 1, total, k);
         }
 
         Ok(dest_paths)
     }
 
+    /// Whether a materialized file matches an entry in this manifest.
+    fn entry_matches_path(
+        &self,
+        root: &Path,
+        path: &Path,
+        entry: &FileEntry,
+    ) -> Result<bool, FileManifestError> {
+        let dest_path = root.join(path);
+        let metadata = std::fs::symlink_metadata(&dest_path)?;
+
+        if let Some(link) = &entry.link {
+            return Ok(
+                metadata.file_type().is_symlink() && &std::fs::read_link(&dest_path)? == link
+            );
+        }
+
+        if !metadata.is_file() {
+            return Ok(false);
+        }
+
+        if let Some(target) = &entry.hardlink {
+            return Ok(match std::fs::symlink_metadata(root.join(target)) {
+                Ok(target_metadata) => is_same_file(&metadata, &target_metadata),
+                Err(_) => false,
+            });
+        }
+
+        if let Some(mode) = entry.mode {
+            if matches!(file_mode(&metadata), Some(existing) if existing != mode) {
+                return Ok(false);
+            }
+        } else if cfg!(unix) && is_executable(&metadata) != entry.executable {
+            return Ok(false);
+        }
+
+        let data = entry.resolve_content()?;
+
+        if metadata.len() != data.len() as u64 {
+            return Ok(false);
+        }
+
+        Ok(std::fs::read(&dest_path)? == data)
+    }
+
+    /// Compute how the content of a directory differs from this manifest.
+    ///
+    /// This is a dry-run of materializing this manifest into a directory
+    /// that only contains the files in this manifest.
+    pub fn diff_against_path(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<FileManifestDiff, FileManifestError> {
+        let root = path.as_ref();
+
+        let mut existing = BTreeSet::new();
+        if root.is_dir() {
+            collect_files(root, root, &mut existing)?;
+        }
+
+        let mut diff = FileManifestDiff::default();
+
+        for (k, v) in &self.files {
+            if !existing.contains(k) {
+                diff.added.push(k.clone());
+            } else if !self.entry_matches_path(root, k, v)? {
+                diff.changed.push(k.clone());
+            }
+        }
+
+        // Writing a hard link target replaces its inode, so existing hard links
+        // to it would keep the old content. Rewrite them too.
+        let rewritten_targets = diff
+            .added
+            .iter()
+            .chain(diff.changed.iter())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        for (k, v) in &self.files {
+            if let Some(target) = &v.hardlink {
+                if rewritten_targets.contains(target) && !rewritten_targets.contains(k) {
+                    diff.changed.push(k.clone());
+                }
+            }
+        }
+
+        diff.changed.sort();
+
+        diff.removed = existing
+            .into_iter()
+            .filter(|p| !self.files.contains_key(p))
+            .collect();
+
+        Ok(diff)
+    }
+
+    /// Make the content of a directory reflect this manifest, only writing what changed.
+    ///
+    /// Files that are added or changed are written, files not in this manifest
+    /// are removed, and directories left empty by removals are deleted. Unchanged
+    /// files are not touched, making this a fast no-op if the directory is current.
+    ///
+    /// `progress` is called after each file is written with the number of files
+    /// written so far, the total number of files to write, and the manifest path
+    /// of the written file.
+    ///
+    /// Returns the differences that were applied.
+    pub fn materialize_files_incremental(
+        &self,
+        dest: impl AsRef<Path>,
+        mut progress: impl FnMut(usize, usize, &Path),
+    ) -> Result<FileManifestDiff, FileManifestError> {
+        let dest = dest.as_ref();
+        let diff = self.diff_against_path(dest)?;
+
+        for path in &diff.removed {
+            let dest_path = dest.join(path);
+            std::fs::remove_file(&dest_path)?;
+
+            // Prune directories that are now empty. remove_dir() fails on
+            // non-empty directories, which stops the walk.
+            for ancestor in dest_path.ancestors().skip(1) {
+                if ancestor == dest || std::fs::remove_dir(ancestor).is_err() {
+                    break;
+                }
+            }
+        }
+
+        let changed = diff.changed.iter().collect::<BTreeSet<_>>();
+        let total = diff.added.len() + diff.changed.len();
+        let mut written = 0;
+
+        for (k, v) in self.materialization_order() {
+            if changed.contains(k) {
+                // Remove the existing file so we don't write through links.
+                let dest_path = dest.join(k);
+
+                if std::fs::symlink_metadata(&dest_path)?.is_dir() {
+                    std::fs::remove_dir_all(&dest_path)?;
+                } else {
+                    std::fs::remove_file(&dest_path)?;
+                }
+            } else if !diff.added.contains(k) {
+                continue;
+            }
+
+            self.write_entry(dest, k, v)?;
+            written += 1;
+            progress(written, total, k);
+        }
+
+        Ok(diff)
+    }
+
     /// Calls `materialize_files()` but removes the destination directory if it exists.
     ///
     /// This ensures the content of the destination reflects exactly what's defined
@@ -710,6 +1225,16 @@ mod tests {
     #[cfg(unix)]
     use tempfile::TempDir;
 
+    #[test]
+    fn test_parse_source_date_epoch() {
+        assert_eq!(
+            parse_source_date_epoch("315532800\n"),
+            Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(315532800))
+        );
+        assert_eq!(parse_source_date_epoch("-1"), None);
+        assert_eq!(parse_source_date_epoch("yesterday"), None);
+    }
+
     #[cfg(unix)]
     fn temp_dir() -> std::io::Result<TempDir> {
         tempfile::Builder::new()
@@ -784,6 +1309,37 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn add_path_hardlinks() -> Result<(), FileManifestError> {
+        let td = temp_dir()?;
+        let source = td.path().join("source");
+        std::fs::create_dir(&source)?;
+        std::fs::write(source.join("a"), vec![42])?;
+        std::fs::hard_link(source.join("a"), source.join("b"))?;
+        std::fs::hard_link(source.join("a"), source.join("c"))?;
+
+        let mut m = FileManifest::new_with_links();
+        for name in ["a", "b", "c"] {
+            m.add_path(source.join(name), &source)?;
+        }
+
+        assert!(!m.get("a").unwrap().is_link());
+        assert_eq!(m.get("b").unwrap().hardlink_target(), Some(Path::new("a")));
+        assert_eq!(m.get("c").unwrap().hardlink_target(), Some(Path::new("a")));
+
+        // Re-adding the source path doesn't turn it into a link to itself.
+        m.add_path(source.join("a"), &source)?;
+        assert!(!m.get("a").unwrap().is_link());
+
+        let mut m = FileManifest::default();
+        m.add_path(source.join("a"), &source)?;
+        m.add_path(source.join("b"), &source)?;
+        assert!(!m.get("b").unwrap().is_link());
+
+        Ok(())
+    }
+
     #[cfg(unix)]
     #[test]
     fn symlink_unix() -> Result<(), FileManifestError> {
@@ -803,6 +1359,272 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn mode() {
+        let mut f = FileEntry::from(vec![42]);
+        assert_eq!(f.mode(), 0o644);
+        f.set_executable(true);
+        assert_eq!(f.mode(), 0o755);
+
+        f.set_mode(0o4750);
+        assert!(f.is_executable());
+        assert_eq!(f.mode(), 0o4750);
+
+        f.set_executable(false);
+        assert_eq!(f.mode(), 0o4640);
+        f.set_executable(true);
+        assert_eq!(f.mode(), 0o4750);
+    }
+
+    #[test]
+    fn add_hardlink() -> Result<(), FileManifestError> {
+        let mut m = FileManifest::new_with_links();
+        m.add_file_entry("lib/libfoo.so.1", vec![42])?;
+        m.add_hardlink("lib/libfoo.so", "lib/libfoo.so.1")?;
+
+        assert_eq!(
+            m.get("lib/libfoo.so").unwrap().hardlink_target(),
+            Some(Path::new("lib/libfoo.so.1"))
+        );
+
+        assert!(matches!(
+            m.add_hardlink("lib/libbar.so", "lib/libbar.so.1"),
+            Err(FileManifestError::UnresolvedHardlink(_))
+        ));
+        assert!(matches!(
+            m.add_hardlink("lib/libfoo.so.2", "lib/libfoo.so"),
+            Err(FileManifestError::UnresolvedHardlink(_))
+        ));
+
+        let mut m = FileManifest::default();
+        m.add_file_entry("foo", vec![42])?;
+        assert!(matches!(
+            m.add_hardlink("bar", "foo"),
+            Err(FileManifestError::LinkNotAllowed)
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn materialize_hardlink_and_mode_unix() -> Result<(), FileManifestError> {
+        use std::os::unix::fs::MetadataExt;
+
+        let mut m = FileManifest::new_with_links();
+        let mut f = FileEntry::from(vec![42]);
+        f.set_mode(0o750);
+        m.add_file_entry("lib/libfoo.so.1", f)?;
+        m.add_hardlink("libfoo.so", "lib/libfoo.so.1")?;
+
+        let td = temp_dir()?;
+        m.materialize_files(td.path())?;
+
+        let target = std::fs::metadata(td.path().join("lib/libfoo.so.1"))?;
+        let link = std::fs::metadata(td.path().join("libfoo.so"))?;
+
+        assert_eq!(target.permissions().mode() & 0o7777, 0o750);
+        assert_eq!(target.ino(), link.ino());
+        assert_eq!(target.nlink(), 2);
+
+        // Materializing again replaces existing files.
+        m.materialize_files(td.path())?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_path_links_unix() -> Result<(), FileManifestError> {
+        let td = temp_dir()?;
+        let source = td.path().join("source");
+        std::fs::create_dir(&source)?;
+
+        std::fs::write(source.join("libfoo.so.1.0"), b"data")?;
+        create_symlink(source.join("libfoo.so.1"), "libfoo.so.1.0")?;
+        std::fs::hard_link(source.join("libfoo.so.1.0"), source.join("libfoo-copy.so"))?;
+
+        let mut m = FileManifest::new_with_links();
+        m.add_path(source.join("libfoo.so.1.0"), &source)?;
+        m.add_path(source.join("libfoo.so.1"), &source)?;
+        m.add_path(source.join("libfoo-copy.so"), &source)?;
+
+        assert_eq!(
+            m.get("libfoo.so.1").unwrap().link_target(),
+            Some(Path::new("libfoo.so.1.0"))
+        );
+        assert_eq!(
+            m.get("libfoo-copy.so").unwrap().hardlink_target(),
+            Some(Path::new("libfoo.so.1.0"))
+        );
+        let mode = std::fs::metadata(source.join("libfoo.so.1.0"))?
+            .permissions()
+            .mode();
+        assert_eq!(
+            m.get("libfoo.so.1.0").unwrap().explicit_mode(),
+            Some(mode & 0o7777)
+        );
+
+        // Without link support, links are followed.
+        let mut m = FileManifest::default();
+        m.add_path(source.join("libfoo.so.1"), &source)?;
+        m.add_path(source.join("libfoo-copy.so"), &source)?;
+        assert!(!m.get("libfoo.so.1").unwrap().is_link());
+        assert!(!m.get("libfoo-copy.so").unwrap().is_link());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn materialize_files_with_progress() -> Result<(), FileManifestError> {
+        let mut m = FileManifest::default();
+        m.add_file_entry("foo", vec![42])?;
+        m.add_file_entry("dir/bar", vec![42])?;
+
+        let td = temp_dir()?;
+
+        let mut calls = vec![];
+        m.materialize_files_with_progress(td.path(), |i, total, path| {
+            calls.push((i, total, path.to_path_buf()));
+        })?;
+
+        assert_eq!(
+            calls,
+            vec![
+                (1, 2, PathBuf::from("dir/bar")),
+                (2, 2, PathBuf::from("foo"))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn diff_against_path() -> Result<(), FileManifestError> {
+        let mut m = FileManifest::new_with_links();
+        m.add_file_entry("foo", vec![42])?;
+        m.add_file_entry("bin/app", FileEntry::new_from_data(vec![42], true))?;
+        m.add_symlink("link", "foo")?;
+        m.add_hardlink("hardlink", "foo")?;
+
+        let td = temp_dir()?;
+        let dest = td.path().join("dest");
+
+        let diff = m.diff_against_path(&dest)?;
+        assert_eq!(diff.added.len(), 4);
+        assert!(diff.changed.is_empty());
+        assert!(diff.removed.is_empty());
+
+        m.materialize_files(&dest)?;
+        assert!(m.diff_against_path(&dest)?.is_empty());
+
+        std::fs::write(dest.join("foo"), b"changed")?;
+        std::fs::create_dir(dest.join("extra"))?;
+        std::fs::write(dest.join("extra/file"), b"extra")?;
+        std::fs::remove_file(dest.join("link"))?;
+        create_symlink(dest.join("link"), "bin/app")?;
+
+        let mut app = std::fs::File::open(dest.join("bin/app"))?;
+        set_file_mode(&mut app, 0o644)?;
+
+        let diff = m.diff_against_path(&dest)?;
+        assert!(diff.added.is_empty());
+        assert_eq!(
+            diff.changed,
+            vec![
+                PathBuf::from("bin/app"),
+                PathBuf::from("foo"),
+                PathBuf::from("hardlink"),
+                PathBuf::from("link")
+            ]
+        );
+        assert_eq!(diff.removed, vec![PathBuf::from("extra/file")]);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn materialize_files_incremental() -> Result<(), FileManifestError> {
+        let mut m = FileManifest::default();
+        m.add_file_entry("foo", vec![42])?;
+        m.add_file_entry("bar", vec![42])?;
+
+        let td = temp_dir()?;
+        m.materialize_files(td.path())?;
+
+        std::fs::write(td.path().join("foo"), b"changed")?;
+        std::fs::create_dir(td.path().join("extra"))?;
+        std::fs::write(td.path().join("extra/file"), b"extra")?;
+        m.add_file_entry("baz", vec![42])?;
+
+        let mut written = vec![];
+        let diff = m.materialize_files_incremental(td.path(), |_, _, path| {
+            written.push(path.to_path_buf());
+        })?;
+
+        assert_eq!(diff.added, vec![PathBuf::from("baz")]);
+        assert_eq!(diff.changed, vec![PathBuf::from("foo")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("extra/file")]);
+        assert_eq!(written, vec![PathBuf::from("baz"), PathBuf::from("foo")]);
+        assert!(!td.path().join("extra").exists());
+        assert_eq!(std::fs::read(td.path().join("foo"))?, vec![42]);
+
+        let diff = m.materialize_files_incremental(td.path(), |_, _, _| {
+            panic!("no files should be written");
+        })?;
+        assert!(diff.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn materialize_files_incremental_hardlink() -> Result<(), FileManifestError> {
+        let mut m = FileManifest::new_with_links();
+        m.add_file_entry("lib/libfoo.so.1", vec![42])?;
+        m.add_hardlink("lib/libfoo.so", "lib/libfoo.so.1")?;
+
+        let td = temp_dir()?;
+        m.materialize_files(td.path())?;
+
+        m.add_file_entry("lib/libfoo.so.1", vec![43, 43])?;
+
+        let mut written = vec![];
+        let diff = m.materialize_files_incremental(td.path(), |_, _, path| {
+            written.push(path.to_path_buf());
+        })?;
+
+        assert!(diff.added.is_empty());
+        assert_eq!(
+            diff.changed,
+            vec![
+                PathBuf::from("lib/libfoo.so"),
+                PathBuf::from("lib/libfoo.so.1")
+            ]
+        );
+        assert_eq!(
+            written,
+            vec![
+                PathBuf::from("lib/libfoo.so.1"),
+                PathBuf::from("lib/libfoo.so")
+            ]
+        );
+        assert_eq!(
+            std::fs::read(td.path().join("lib/libfoo.so"))?,
+            vec![43, 43]
+        );
+        assert!(is_same_file(
+            &std::fs::metadata(td.path().join("lib/libfoo.so"))?,
+            &std::fs::metadata(td.path().join("lib/libfoo.so.1"))?
+        ));
+        assert!(m.diff_against_path(td.path())?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_relative_directories() -> Result<(), FileManifestError> {
         let mut m = FileManifest::default();
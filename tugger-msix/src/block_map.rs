@@ -0,0 +1,150 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `AppxBlockMap.xml` generation. */
+
+use {
+    anyhow::Result,
+    sha2::Digest,
+    std::io::Write,
+    xml::{
+        common::XmlVersion,
+        writer::{EmitterConfig, EventWriter, XmlEvent},
+    },
+};
+
+const BLOCK_MAP_NAMESPACE: &str = "http://schemas.microsoft.com/appx/2010/blockmap";
+const HASH_METHOD: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+
+/// Size of blocks that files are split into.
+pub const BLOCK_SIZE: usize = 65536;
+
+/// Size of a ZIP local file header, excluding the filename and extra field.
+const LOCAL_FILE_HEADER_SIZE: u64 = 30;
+
+/// Describes a single file in a block map.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockMapFile {
+    /// Name of the file within the package, using `\` as the directory separator.
+    pub name: String,
+
+    /// Uncompressed size of the file.
+    pub size: u64,
+
+    /// Size of the ZIP local file header for this file.
+    pub lfh_size: u64,
+
+    /// Base64 encoded SHA-256 digests of each block of the file.
+    pub block_hashes: Vec<String>,
+}
+
+impl BlockMapFile {
+    /// Construct an instance from file content.
+    ///
+    /// `zip_name` is the name of the file as stored in the ZIP archive. It is
+    /// used to compute the size of the local file header. Files are assumed to
+    /// be stored without compression and without ZIP extra fields.
+    pub fn new(name: impl ToString, zip_name: &str, data: &[u8]) -> Self {
+        let block_hashes = data
+            .chunks(BLOCK_SIZE)
+            .map(|chunk| base64::encode(sha2::Sha256::digest(chunk)))
+            .collect::<Vec<_>>();
+
+        Self {
+            name: name.to_string(),
+            size: data.len() as u64,
+            lfh_size: LOCAL_FILE_HEADER_SIZE + zip_name.len() as u64,
+            block_hashes,
+        }
+    }
+}
+
+/// Represents an `AppxBlockMap.xml` file.
+#[derive(Clone, Debug, Default)]
+pub struct BlockMap {
+    files: Vec<BlockMapFile>,
+}
+
+impl BlockMap {
+    /// Register a file with the block map.
+    ///
+    /// Files must be registered in the order they appear in the package.
+    pub fn add_file(&mut self, file: BlockMapFile) {
+        self.files.push(file);
+    }
+
+    /// Obtain the files registered with this block map.
+    pub fn files(&self) -> &[BlockMapFile] {
+        &self.files
+    }
+
+    /// Serialize the block map to XML.
+    pub fn to_xml(&self) -> Result<Vec<u8>> {
+        let mut emitter_config = EmitterConfig::new();
+        emitter_config.perform_indent = true;
+
+        let mut emitter = emitter_config.create_writer(Vec::new());
+        self.write_xml(&mut emitter)?;
+
+        Ok(emitter.into_inner())
+    }
+
+    /// Write XML describing this block map.
+    pub fn write_xml<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<()> {
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("utf-8"),
+            standalone: None,
+        })?;
+
+        writer.write(
+            XmlEvent::start_element("BlockMap")
+                .default_ns(BLOCK_MAP_NAMESPACE)
+                .attr("HashMethod", HASH_METHOD),
+        )?;
+
+        for file in &self.files {
+            writer.write(
+                XmlEvent::start_element("File")
+                    .attr("Name", &file.name)
+                    .attr("Size", &file.size.to_string())
+                    .attr("LfhSize", &file.lfh_size.to_string()),
+            )?;
+
+            // Files are stored uncompressed, so block sizes are omitted.
+            for hash in &file.block_hashes {
+                writer.write(XmlEvent::start_element("Block").attr("Hash", hash))?;
+                writer.write(XmlEvent::end_element().name("Block"))?;
+            }
+
+            writer.write(XmlEvent::end_element().name("File"))?;
+        }
+
+        writer.write(XmlEvent::end_element().name("BlockMap"))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_hashes() {
+        let empty = BlockMapFile::new("empty.txt", "empty.txt", &[]);
+        assert_eq!(empty.size, 0);
+        assert_eq!(empty.lfh_size, 39);
+        assert!(empty.block_hashes.is_empty());
+
+        let data = vec![42u8; BLOCK_SIZE + 1];
+        let file = BlockMapFile::new("dir\\file.bin", "dir/file.bin", &data);
+        assert_eq!(file.size, BLOCK_SIZE as u64 + 1);
+        assert_eq!(file.block_hashes.len(), 2);
+        assert_eq!(
+            file.block_hashes[1],
+            base64::encode(sha2::Sha256::digest(&[42u8]))
+        );
+    }
+}
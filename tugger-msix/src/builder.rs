@@ -0,0 +1,403 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Interface for producing MSIX packages. */
+
+use {
+    crate::{AppxManifest, BlockMap, BlockMapFile},
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    std::{
+        collections::BTreeMap,
+        io::{Seek, Write},
+        path::Path,
+    },
+    tugger_file_manifest::FileManifest,
+    tugger_windows_codesign::SigntoolSign,
+    xml::{
+        common::XmlVersion,
+        writer::{EmitterConfig, XmlEvent},
+    },
+};
+
+const CONTENT_TYPES_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/package/2006/content-types";
+
+/// Filenames that are reserved for package metadata.
+const RESERVED_FILENAMES: &[&str] = &[
+    "AppxManifest.xml",
+    "AppxBlockMap.xml",
+    "AppxSignature.p7x",
+    "[Content_Types].xml",
+];
+
+/// A 1x1 transparent PNG.
+///
+/// Used in place of logos that the manifest references but which weren't
+/// provided.
+const PLACEHOLDER_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0a, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+/// Resolve the MIME type for a file extension.
+fn content_type_for_extension(extension: &str) -> &'static str {
+    match extension.to_lowercase().as_str() {
+        "dll" | "exe" | "pyd" => "application/x-msdownload",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "ico" => "image/vnd.microsoft.icon",
+        "txt" => "text/plain",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "zip" => "application/x-zip-compressed",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Convert a package path to the name stored in the ZIP archive.
+///
+/// MSIX packages follow Open Packaging Conventions, which require names to
+/// be percent-encoded.
+fn zip_name(path: &str) -> String {
+    let mut name = String::with_capacity(path.len());
+
+    for b in path.replace('\\', "/").bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=@".contains(&b) {
+            name.push(b as char);
+        } else {
+            name.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    name
+}
+
+/// Entity used to produce MSIX packages.
+///
+/// Instances are constructed from an [AppxManifest] describing the package.
+/// Files to install are registered via a [FileManifest].
+#[derive(Clone)]
+pub struct MsixBuilder {
+    manifest: AppxManifest,
+
+    /// Files to materialize in the package.
+    files: FileManifest,
+
+    /// Signtool settings to use to sign the package.
+    signtool_settings: Option<SigntoolSign>,
+}
+
+impl MsixBuilder {
+    /// Construct a new instance from an [AppxManifest].
+    pub fn new(manifest: AppxManifest) -> Self {
+        Self {
+            manifest,
+            files: FileManifest::default(),
+            signtool_settings: None,
+        }
+    }
+
+    /// Obtain the [AppxManifest] describing this package.
+    pub fn manifest(&self) -> &AppxManifest {
+        &self.manifest
+    }
+
+    /// Obtain a mutable reference to the [AppxManifest] describing this package.
+    pub fn manifest_mut(&mut self) -> &mut AppxManifest {
+        &mut self.manifest
+    }
+
+    /// Obtain the files that will be installed by this package.
+    pub fn files(&self) -> &FileManifest {
+        &self.files
+    }
+
+    /// Add files to the package via a [FileManifest].
+    pub fn add_files_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        self.files.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Register signtool signing settings to be used to sign the package.
+    ///
+    /// The publisher in the manifest must match the subject of the signing
+    /// certificate or Windows will refuse to install the package.
+    #[must_use]
+    pub fn sign_signtool(mut self, settings: SigntoolSign) -> Self {
+        self.signtool_settings = Some(settings);
+        self
+    }
+
+    /// Obtain the default filename to use for the generated package.
+    pub fn default_package_filename(&self) -> String {
+        format!(
+            "{}_{}_{}.msix",
+            self.manifest.identity_name,
+            self.manifest.version,
+            self.manifest.processor_architecture
+        )
+    }
+
+    /// Validate that the package can be built.
+    pub fn validate(&self) -> Result<()> {
+        self.manifest.validate()?;
+
+        for app in &self.manifest.applications {
+            if !self.files.has_path(app.executable.replace('\\', "/")) {
+                return Err(anyhow!(
+                    "{} is referenced by the manifest but not present in the package",
+                    app.executable
+                ));
+            }
+        }
+
+        for (path, _) in self.files.iter_entries() {
+            if let Some(name) = path.to_str() {
+                if RESERVED_FILENAMES.contains(&name) {
+                    return Err(anyhow!("{} is reserved for package metadata", name));
+                }
+            } else {
+                return Err(anyhow!("{} is not a valid package path", path.display()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the files to write to the package.
+    ///
+    /// Logos referenced by the manifest but not provided are replaced by a
+    /// transparent placeholder image.
+    fn resolve_files(&self) -> Result<FileManifest> {
+        let mut files = self.files.clone();
+
+        for path in self.manifest.referenced_paths() {
+            let path = path.replace('\\', "/");
+
+            if !files.has_path(&path) {
+                files.add_file_entry(&path, PLACEHOLDER_PNG)?;
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Write the unsigned package to a writer.
+    pub fn write_package<W: Write + Seek>(&self, writer: W) -> Result<()> {
+        self.validate()?;
+
+        let files = self.resolve_files()?;
+
+        let mut zf = zip::ZipWriter::new(writer);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+        let mut block_map = BlockMap::default();
+        let mut extensions = BTreeMap::new();
+        let mut overrides = vec![];
+
+        let mut add_file = |zf: &mut zip::ZipWriter<W>, path: &str, data: &[u8]| -> Result<()> {
+            let name = zip_name(path);
+
+            zf.start_file(name.clone(), options)?;
+            zf.write_all(data)?;
+
+            block_map.add_file(BlockMapFile::new(path.replace('/', "\\"), &name, data));
+
+            Ok(())
+        };
+
+        for (path, entry) in files.iter_entries() {
+            let path_s = path.display().to_string().replace('\\', "/");
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", path.display()))?;
+
+            add_file(&mut zf, &path_s, &data)?;
+
+            if let Some(extension) = path.extension() {
+                let extension = extension.to_string_lossy().to_lowercase();
+                let content_type = content_type_for_extension(&extension);
+                extensions.insert(extension, content_type);
+            } else {
+                overrides.push((
+                    format!("/{}", zip_name(&path_s)),
+                    "application/octet-stream",
+                ));
+            }
+        }
+
+        add_file(&mut zf, "AppxManifest.xml", &self.manifest.to_xml()?)?;
+
+        zf.start_file("AppxBlockMap.xml", options)?;
+        zf.write_all(&block_map.to_xml()?)?;
+
+        overrides.push((
+            "/AppxManifest.xml".to_string(),
+            "application/vnd.ms-appx.manifest+xml",
+        ));
+        overrides.push((
+            "/AppxBlockMap.xml".to_string(),
+            "application/vnd.ms-appx.blockmap+xml",
+        ));
+
+        zf.start_file("[Content_Types].xml", options)?;
+        zf.write_all(&content_types_xml(&extensions, &overrides)?)?;
+
+        zf.finish()?;
+
+        Ok(())
+    }
+
+    /// Build the package, writing it to the specified path.
+    ///
+    /// If signtool settings are registered, the package is signed after it is
+    /// written.
+    pub fn build(&self, logger: &slog::Logger, output_path: impl AsRef<Path>) -> Result<()> {
+        let output_path = output_path.as_ref();
+
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        warn!(logger, "writing MSIX package to {}", output_path.display());
+        let fh = std::fs::File::create(output_path)
+            .with_context(|| format!("creating {}", output_path.display()))?;
+        self.write_package(std::io::BufWriter::new(fh))?;
+
+        if let Some(settings) = &self.signtool_settings {
+            warn!(logger, "signing {}", output_path.display());
+            let mut settings = settings.clone_settings();
+            settings.sign_file(output_path);
+            settings.run(logger).context("signing MSIX package")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Produce the content of a `[Content_Types].xml` file.
+fn content_types_xml(
+    extensions: &BTreeMap<String, &str>,
+    overrides: &[(String, &str)],
+) -> Result<Vec<u8>> {
+    let mut emitter_config = EmitterConfig::new();
+    emitter_config.perform_indent = true;
+
+    let mut writer = emitter_config.create_writer(Vec::new());
+
+    writer.write(XmlEvent::StartDocument {
+        version: XmlVersion::Version10,
+        encoding: Some("utf-8"),
+        standalone: None,
+    })?;
+
+    writer.write(XmlEvent::start_element("Types").default_ns(CONTENT_TYPES_NAMESPACE))?;
+
+    for (extension, content_type) in extensions {
+        writer.write(
+            XmlEvent::start_element("Default")
+                .attr("Extension", extension)
+                .attr("ContentType", content_type),
+        )?;
+        writer.write(XmlEvent::end_element().name("Default"))?;
+    }
+
+    for (part_name, content_type) in overrides {
+        writer.write(
+            XmlEvent::start_element("Override")
+                .attr("PartName", part_name)
+                .attr("ContentType", content_type),
+        )?;
+        writer.write(XmlEvent::end_element().name("Override"))?;
+    }
+
+    writer.write(XmlEvent::end_element().name("Types"))?;
+
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::MsixApplication, std::io::Read, tugger_common::testutil::*};
+
+    fn test_builder() -> Result<MsixBuilder> {
+        let mut manifest = AppxManifest::new(
+            "Tugger.TestApp",
+            "CN=Tugger",
+            "1.0.0.0",
+            "Test App",
+            "Tugger",
+        );
+        manifest
+            .applications
+            .push(MsixApplication::new("App", "app.exe", "Test App"));
+
+        let mut builder = MsixBuilder::new(manifest);
+
+        let mut m = FileManifest::default();
+        m.add_file_entry("app.exe", vec![42])?;
+        m.add_file_entry("lib/foo bar.txt", vec![42; 70000])?;
+        m.add_file_entry("Assets/StoreLogo.png", vec![0])?;
+        builder.add_files_manifest(&m)?;
+
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_zip_name() {
+        assert_eq!(zip_name("foo/bar.txt"), "foo/bar.txt");
+        assert_eq!(zip_name("foo\\bar baz.txt"), "foo/bar%20baz.txt");
+    }
+
+    #[test]
+    fn test_missing_referenced_file() -> Result<()> {
+        let mut builder = test_builder()?;
+        builder
+            .manifest_mut()
+            .applications
+            .push(MsixApplication::new("Other", "other.exe", "Other"));
+
+        assert!(builder.validate().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_package() -> Result<()> {
+        let logger = get_logger()?;
+        let builder = test_builder()?;
+
+        let output_path = DEFAULT_TEMP_DIR
+            .path()
+            .join("test_write_package")
+            .join(builder.default_package_filename());
+        builder.build(&logger, &output_path)?;
+
+        let mut zf = zip::ZipArchive::new(std::fs::File::open(&output_path)?)?;
+
+        let names = zf.file_names().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert!(names.contains(&"lib/foo%20bar.txt".to_string()));
+        assert!(names.contains(&"AppxManifest.xml".to_string()));
+        assert!(names.contains(&"Assets/Square44x44Logo.png".to_string()));
+
+        let mut block_map = String::new();
+        zf.by_name("AppxBlockMap.xml")?
+            .read_to_string(&mut block_map)?;
+        assert!(block_map.contains("Name=\"lib\\foo bar.txt\" Size=\"70000\""));
+        assert!(!block_map.contains("Content_Types"));
+
+        let mut content_types = String::new();
+        zf.by_name("[Content_Types].xml")?
+            .read_to_string(&mut content_types)?;
+        assert!(content_types.contains("Extension=\"exe\""));
+
+        Ok(())
+    }
+}
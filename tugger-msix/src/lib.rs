@@ -0,0 +1,25 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Build MSIX / AppX packages.
+
+MSIX packages are ZIP archives holding application files plus a handful of
+special files:
+
+* `AppxManifest.xml` describes the package identity and its applications.
+* `AppxBlockMap.xml` holds cryptographic digests of every file in the
+  package, in 64 KiB blocks.
+* `[Content_Types].xml` maps file extensions to MIME types.
+* `AppxSignature.p7x` holds the package signature. This file is added by
+  `signtool.exe` when the package is signed.
+
+[MsixBuilder] is the main interface for producing packages.
+*/
+
+mod block_map;
+pub use block_map::*;
+mod builder;
+pub use builder::*;
+mod manifest;
+pub use manifest::*;
@@ -0,0 +1,316 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `AppxManifest.xml` generation. */
+
+use {
+    anyhow::{anyhow, Result},
+    std::io::Write,
+    xml::{
+        common::XmlVersion,
+        writer::{EmitterConfig, EventWriter, XmlEvent},
+    },
+};
+
+const FOUNDATION_NAMESPACE: &str =
+    "http://schemas.microsoft.com/appx/manifest/foundation/windows10";
+const UAP_NAMESPACE: &str = "http://schemas.microsoft.com/appx/manifest/uap/windows10";
+const RESCAP_NAMESPACE: &str =
+    "http://schemas.microsoft.com/appx/manifest/foundation/windows10/restrictedcapabilities";
+
+/// Describes an application within an MSIX package.
+///
+/// Each application is registered with the Start Menu.
+#[derive(Clone, Debug)]
+pub struct MsixApplication {
+    /// Identifier of the application within the package.
+    pub id: String,
+
+    /// Path of the executable to run, relative to the package root.
+    pub executable: String,
+
+    /// Display name of the application.
+    pub display_name: String,
+
+    /// Description of the application.
+    pub description: String,
+
+    /// Background color for tiles. e.g. `transparent` or `#464646`.
+    pub background_color: String,
+
+    /// Path within the package of the 150x150 tile logo.
+    pub square150x150_logo: String,
+
+    /// Path within the package of the 44x44 tile logo.
+    pub square44x44_logo: String,
+}
+
+impl MsixApplication {
+    /// Construct a new instance with mandatory fields.
+    ///
+    /// Logos default to the paths used by [AppxManifest::new].
+    pub fn new(id: impl ToString, executable: impl ToString, display_name: impl ToString) -> Self {
+        let display_name = display_name.to_string();
+
+        Self {
+            id: id.to_string(),
+            executable: executable.to_string(),
+            description: display_name.clone(),
+            display_name,
+            background_color: "transparent".to_string(),
+            square150x150_logo: "Assets\\Square150x150Logo.png".to_string(),
+            square44x44_logo: "Assets\\Square44x44Logo.png".to_string(),
+        }
+    }
+}
+
+/// Represents an `AppxManifest.xml` file.
+#[derive(Clone, Debug)]
+pub struct AppxManifest {
+    /// Unique name of the package. e.g. `MyCompany.MyApp`.
+    pub identity_name: String,
+
+    /// Distinguished name of the publisher. e.g. `CN=My Company`.
+    ///
+    /// Must match the subject of the certificate used to sign the package.
+    pub publisher: String,
+
+    /// Version of the package in `major.minor.build.revision` form.
+    pub version: String,
+
+    /// Processor architecture. e.g. `x64`, `x86`, `arm64`, or `neutral`.
+    pub processor_architecture: String,
+
+    /// Display name of the package.
+    pub display_name: String,
+
+    /// Display name of the publisher.
+    pub publisher_display_name: String,
+
+    /// Description of the package.
+    pub description: Option<String>,
+
+    /// Path within the package of the store logo.
+    pub logo: String,
+
+    /// Minimum Windows version the package can be installed on.
+    pub min_version: String,
+
+    /// Maximum Windows version the package was tested against.
+    pub max_version_tested: String,
+
+    /// Languages of package resources.
+    pub languages: Vec<String>,
+
+    /// Applications in the package.
+    pub applications: Vec<MsixApplication>,
+
+    /// Restricted capabilities requested by the package.
+    ///
+    /// `runFullTrust` is required for traditional desktop applications.
+    pub restricted_capabilities: Vec<String>,
+}
+
+impl AppxManifest {
+    /// Construct a new instance with mandatory fields.
+    pub fn new(
+        identity_name: impl ToString,
+        publisher: impl ToString,
+        version: impl ToString,
+        display_name: impl ToString,
+        publisher_display_name: impl ToString,
+    ) -> Self {
+        Self {
+            identity_name: identity_name.to_string(),
+            publisher: publisher.to_string(),
+            version: version.to_string(),
+            processor_architecture: "x64".to_string(),
+            display_name: display_name.to_string(),
+            publisher_display_name: publisher_display_name.to_string(),
+            description: None,
+            logo: "Assets\\StoreLogo.png".to_string(),
+            min_version: "10.0.17763.0".to_string(),
+            max_version_tested: "10.0.19041.0".to_string(),
+            languages: vec!["en-us".to_string()],
+            applications: vec![],
+            restricted_capabilities: vec!["runFullTrust".to_string()],
+        }
+    }
+
+    /// Paths within the package that the manifest references.
+    ///
+    /// These paths must exist in the package for it to be valid.
+    pub fn referenced_paths(&self) -> Vec<String> {
+        let mut paths = vec![self.logo.clone()];
+
+        for app in &self.applications {
+            paths.push(app.executable.clone());
+            paths.push(app.square150x150_logo.clone());
+            paths.push(app.square44x44_logo.clone());
+        }
+
+        paths
+    }
+
+    /// Validate that fields have sensible values.
+    pub fn validate(&self) -> Result<()> {
+        let parts = self.version.split('.').collect::<Vec<_>>();
+        if parts.len() != 4 || parts.iter().any(|p| p.parse::<u16>().is_err()) {
+            return Err(anyhow!(
+                "version {} is not of the form major.minor.build.revision",
+                self.version
+            ));
+        }
+
+        if !self.publisher.contains('=') {
+            return Err(anyhow!(
+                "publisher {} is not a distinguished name (e.g. CN=My Company)",
+                self.publisher
+            ));
+        }
+
+        if self.applications.is_empty() {
+            return Err(anyhow!("package must define at least 1 application"));
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the manifest to XML.
+    pub fn to_xml(&self) -> Result<Vec<u8>> {
+        let mut emitter_config = EmitterConfig::new();
+        emitter_config.perform_indent = true;
+
+        let mut emitter = emitter_config.create_writer(Vec::new());
+        self.write_xml(&mut emitter)?;
+
+        Ok(emitter.into_inner())
+    }
+
+    /// Write XML describing this manifest.
+    pub fn write_xml<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<()> {
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("utf-8"),
+            standalone: None,
+        })?;
+
+        writer.write(
+            XmlEvent::start_element("Package")
+                .default_ns(FOUNDATION_NAMESPACE)
+                .ns("uap", UAP_NAMESPACE)
+                .ns("rescap", RESCAP_NAMESPACE)
+                .attr("IgnorableNamespaces", "uap rescap"),
+        )?;
+
+        writer.write(
+            XmlEvent::start_element("Identity")
+                .attr("Name", &self.identity_name)
+                .attr("Publisher", &self.publisher)
+                .attr("Version", &self.version)
+                .attr("ProcessorArchitecture", &self.processor_architecture),
+        )?;
+        writer.write(XmlEvent::end_element().name("Identity"))?;
+
+        writer.write(XmlEvent::start_element("Properties"))?;
+        write_text_element(writer, "DisplayName", &self.display_name)?;
+        write_text_element(writer, "PublisherDisplayName", &self.publisher_display_name)?;
+        if let Some(description) = &self.description {
+            write_text_element(writer, "Description", description)?;
+        }
+        write_text_element(writer, "Logo", &self.logo)?;
+        writer.write(XmlEvent::end_element().name("Properties"))?;
+
+        writer.write(XmlEvent::start_element("Dependencies"))?;
+        writer.write(
+            XmlEvent::start_element("TargetDeviceFamily")
+                .attr("Name", "Windows.Desktop")
+                .attr("MinVersion", &self.min_version)
+                .attr("MaxVersionTested", &self.max_version_tested),
+        )?;
+        writer.write(XmlEvent::end_element().name("TargetDeviceFamily"))?;
+        writer.write(XmlEvent::end_element().name("Dependencies"))?;
+
+        writer.write(XmlEvent::start_element("Resources"))?;
+        for language in &self.languages {
+            writer.write(XmlEvent::start_element("Resource").attr("Language", language))?;
+            writer.write(XmlEvent::end_element().name("Resource"))?;
+        }
+        writer.write(XmlEvent::end_element().name("Resources"))?;
+
+        writer.write(XmlEvent::start_element("Applications"))?;
+        for app in &self.applications {
+            writer.write(
+                XmlEvent::start_element("Application")
+                    .attr("Id", &app.id)
+                    .attr("Executable", &app.executable)
+                    .attr("EntryPoint", "Windows.FullTrustApplication"),
+            )?;
+            writer.write(
+                XmlEvent::start_element("uap:VisualElements")
+                    .attr("DisplayName", &app.display_name)
+                    .attr("Description", &app.description)
+                    .attr("BackgroundColor", &app.background_color)
+                    .attr("Square150x150Logo", &app.square150x150_logo)
+                    .attr("Square44x44Logo", &app.square44x44_logo),
+            )?;
+            writer.write(XmlEvent::end_element().name("uap:VisualElements"))?;
+            writer.write(XmlEvent::end_element().name("Application"))?;
+        }
+        writer.write(XmlEvent::end_element().name("Applications"))?;
+
+        writer.write(XmlEvent::start_element("Capabilities"))?;
+        for capability in &self.restricted_capabilities {
+            writer.write(XmlEvent::start_element("rescap:Capability").attr("Name", capability))?;
+            writer.write(XmlEvent::end_element().name("rescap:Capability"))?;
+        }
+        writer.write(XmlEvent::end_element().name("Capabilities"))?;
+
+        writer.write(XmlEvent::end_element().name("Package"))?;
+
+        Ok(())
+    }
+}
+
+fn write_text_element<W: Write>(writer: &mut EventWriter<W>, name: &str, text: &str) -> Result<()> {
+    writer.write(XmlEvent::start_element(name))?;
+    writer.write(XmlEvent::Characters(text))?;
+    writer.write(XmlEvent::end_element().name(name))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_xml() -> Result<()> {
+        let mut manifest = AppxManifest::new(
+            "Tugger.TestApp",
+            "CN=Tugger",
+            "1.0.0.0",
+            "Test App",
+            "Tugger",
+        );
+        manifest
+            .applications
+            .push(MsixApplication::new("App", "app.exe", "Test App"));
+        manifest.validate()?;
+
+        let xml = String::from_utf8(manifest.to_xml()?)?;
+
+        assert!(xml.contains("<Identity Name=\"Tugger.TestApp\" Publisher=\"CN=Tugger\" Version=\"1.0.0.0\" ProcessorArchitecture=\"x64\" />"));
+        assert!(xml.contains("<uap:VisualElements DisplayName=\"Test App\""));
+        assert!(xml.contains("<rescap:Capability Name=\"runFullTrust\" />"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let manifest = AppxManifest::new("Tugger.TestApp", "CN=Tugger", "1.0", "Test", "Tugger");
+        assert!(manifest.validate().is_err());
+    }
+}
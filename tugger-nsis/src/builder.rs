@@ -0,0 +1,596 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Interface for producing NSIS installers. */
+
+use {
+    anyhow::{anyhow, Context, Result},
+    duct::cmd,
+    slog::warn,
+    std::{
+        fmt::{Display, Formatter},
+        io::{BufRead, BufReader},
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::FileManifest,
+    tugger_windows_codesign::SigntoolSign,
+};
+
+/// Filename of the uninstaller written to the installation directory.
+const UNINSTALLER_FILENAME: &str = "uninstall.exe";
+
+/// Filename of the generated NSIS script.
+const SCRIPT_FILENAME: &str = "installer.nsi";
+
+/// Locate `makensis`.
+///
+/// `PATH` is searched first. On Windows, the default NSIS installation
+/// directories under Program Files are searched as well.
+pub fn find_makensis() -> Result<PathBuf> {
+    let filename = if cfg!(target_family = "windows") {
+        "makensis.exe"
+    } else {
+        "makensis"
+    };
+
+    let paths = std::env::var_os("PATH").unwrap_or_default();
+
+    let mut candidates = std::env::split_paths(&paths)
+        .map(|p| p.join(filename))
+        .collect::<Vec<_>>();
+
+    if cfg!(target_family = "windows") {
+        for key in ["ProgramFiles(x86)", "ProgramFiles"] {
+            if let Some(root) = std::env::var_os(key) {
+                candidates.push(PathBuf::from(root).join("NSIS").join(filename));
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .find(|p| p.exists())
+        .ok_or_else(|| anyhow!("unable to locate {}", filename))
+}
+
+/// Escape a value for use in a quoted NSIS string.
+///
+/// NSIS expands variables starting with `$`, so literal values must have
+/// these escaped.
+fn escape(value: &str) -> String {
+    value
+        .replace('$', "$$")
+        .replace('"', "$\\\"")
+        .replace('\r', "$\\r")
+        .replace('\n', "$\\n")
+}
+
+/// Convert a relative install path to a path under `$INSTDIR`.
+fn install_path(path: &Path) -> String {
+    let path = escape(&path.display().to_string().replace('/', "\\"));
+
+    if path.is_empty() {
+        "$INSTDIR".to_string()
+    } else {
+        format!("$INSTDIR\\{}", path)
+    }
+}
+
+/// The privileges an installer requests.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExecutionLevel {
+    /// Install for all users. Requires administrator privileges.
+    Admin,
+
+    /// Install for the current user only.
+    User,
+}
+
+impl Default for ExecutionLevel {
+    fn default() -> Self {
+        Self::Admin
+    }
+}
+
+impl Display for ExecutionLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Admin => "admin",
+            Self::User => "user",
+        })
+    }
+}
+
+impl TryFrom<&str> for ExecutionLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "admin" => Ok(Self::Admin),
+            "user" => Ok(Self::User),
+            _ => Err(anyhow!(
+                "{} is not a valid execution level; use admin or user",
+                value
+            )),
+        }
+    }
+}
+
+/// A Start Menu shortcut created by an installer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NsisShortcut {
+    /// Name of the shortcut, as displayed in the Start Menu.
+    pub name: String,
+
+    /// Path of the file the shortcut points to, relative to the installation directory.
+    pub target: String,
+}
+
+/// Entity used to produce NSIS installers.
+///
+/// Instances hold metadata about the installed application and a
+/// [FileManifest] of files to install. A `.nsi` script is generated from
+/// these and compiled with `makensis`.
+#[derive(Clone)]
+pub struct NsisInstallerBuilder {
+    product_name: String,
+    product_version: String,
+    manufacturer: String,
+
+    /// Privileges requested by the installer.
+    execution_level: ExecutionLevel,
+
+    /// Default installation directory, in NSIS syntax.
+    install_dir: Option<String>,
+
+    /// Filename of the installer executable.
+    installer_filename: Option<String>,
+
+    /// Path to a text file with a license the user must accept.
+    license_path: Option<PathBuf>,
+
+    /// Path to an `.ico` file to use for the installer and uninstaller.
+    icon_path: Option<PathBuf>,
+
+    /// Start Menu shortcuts to create.
+    shortcuts: Vec<NsisShortcut>,
+
+    /// Files to install.
+    files: FileManifest,
+
+    /// Signtool settings to use to sign the installer.
+    signtool_settings: Option<SigntoolSign>,
+}
+
+impl NsisInstallerBuilder {
+    /// Construct a new instance with mandatory fields.
+    pub fn new(
+        product_name: impl ToString,
+        product_version: impl ToString,
+        manufacturer: impl ToString,
+    ) -> Self {
+        Self {
+            product_name: product_name.to_string(),
+            product_version: product_version.to_string(),
+            manufacturer: manufacturer.to_string(),
+            execution_level: ExecutionLevel::default(),
+            install_dir: None,
+            installer_filename: None,
+            license_path: None,
+            icon_path: None,
+            shortcuts: vec![],
+            files: FileManifest::default(),
+            signtool_settings: None,
+        }
+    }
+
+    /// Set the privileges requested by the installer.
+    ///
+    /// Per-user installers don't require elevation and default to installing
+    /// into the user's local application data directory.
+    #[must_use]
+    pub fn execution_level(mut self, level: ExecutionLevel) -> Self {
+        self.execution_level = level;
+        self
+    }
+
+    /// Set the default installation directory.
+    ///
+    /// The value is interpreted by NSIS, so variables such as
+    /// `$PROGRAMFILES64` may be used.
+    #[must_use]
+    pub fn install_dir(mut self, value: impl ToString) -> Self {
+        self.install_dir = Some(value.to_string());
+        self
+    }
+
+    /// Set the filename of the installer executable.
+    #[must_use]
+    pub fn installer_filename(mut self, value: impl ToString) -> Self {
+        self.installer_filename = Some(value.to_string());
+        self
+    }
+
+    /// Set the path to a text file containing a license the user must accept.
+    #[must_use]
+    pub fn license_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.license_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the path to an `.ico` file used by the installer and uninstaller.
+    #[must_use]
+    pub fn icon_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.icon_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Register signtool signing settings to be used to sign the installer.
+    #[must_use]
+    pub fn sign_signtool(mut self, settings: SigntoolSign) -> Self {
+        self.signtool_settings = Some(settings);
+        self
+    }
+
+    /// Add a Start Menu shortcut.
+    ///
+    /// `target` is relative to the installation directory.
+    pub fn add_shortcut(&mut self, name: impl ToString, target: impl ToString) {
+        self.shortcuts.push(NsisShortcut {
+            name: name.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    /// Obtain the files that will be installed.
+    pub fn files(&self) -> &FileManifest {
+        &self.files
+    }
+
+    /// Add files to install via a [FileManifest].
+    pub fn add_files_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        self.files.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Obtain the filename of the installer executable.
+    pub fn resolve_installer_filename(&self) -> String {
+        if let Some(filename) = &self.installer_filename {
+            filename.clone()
+        } else {
+            format!("{}-{}-setup.exe", self.product_name, self.product_version)
+        }
+    }
+
+    /// Obtain the default installation directory, in NSIS syntax.
+    pub fn resolve_install_dir(&self) -> String {
+        if let Some(dir) = &self.install_dir {
+            dir.clone()
+        } else {
+            match self.execution_level {
+                ExecutionLevel::Admin => format!("$PROGRAMFILES64\\{}", escape(&self.product_name)),
+                ExecutionLevel::User => {
+                    format!("$LOCALAPPDATA\\Programs\\{}", escape(&self.product_name))
+                }
+            }
+        }
+    }
+
+    /// Registry key holding the Add/Remove Programs registration.
+    fn uninstall_key(&self) -> String {
+        format!(
+            "Software\\Microsoft\\Windows\\CurrentVersion\\Uninstall\\{}",
+            escape(&self.product_name)
+        )
+    }
+
+    /// Validate that the installer can be built.
+    pub fn validate(&self) -> Result<()> {
+        if self.files.has_path(UNINSTALLER_FILENAME) {
+            return Err(anyhow!(
+                "{} is reserved for the uninstaller",
+                UNINSTALLER_FILENAME
+            ));
+        }
+
+        for shortcut in &self.shortcuts {
+            if !self.files.has_path(shortcut.target.replace('\\', "/")) {
+                return Err(anyhow!(
+                    "shortcut {} targets {}, which is not installed",
+                    shortcut.name,
+                    shortcut.target
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Produce the content of the `.nsi` script.
+    ///
+    /// `source_dir` is the directory holding materialized copies of the
+    /// files to install.
+    pub fn to_script(&self, source_dir: &Path) -> Result<String> {
+        let mut lines = vec![
+            "Unicode true".to_string(),
+            "!include \"MUI2.nsh\"".to_string(),
+            "".to_string(),
+            format!("Name \"{}\"", escape(&self.product_name)),
+            format!("OutFile \"{}\"", escape(&self.resolve_installer_filename())),
+            format!("InstallDir \"{}\"", self.resolve_install_dir()),
+            format!("RequestExecutionLevel {}", self.execution_level),
+            "SetCompressor /SOLID lzma".to_string(),
+            "".to_string(),
+        ];
+
+        if let Some(icon) = &self.icon_path {
+            let icon = escape(&icon.display().to_string());
+            lines.push(format!("!define MUI_ICON \"{}\"", icon));
+            lines.push(format!("!define MUI_UNICON \"{}\"", icon));
+        }
+
+        lines.push("!insertmacro MUI_PAGE_WELCOME".to_string());
+        if let Some(license) = &self.license_path {
+            lines.push(format!(
+                "!insertmacro MUI_PAGE_LICENSE \"{}\"",
+                escape(&license.display().to_string())
+            ));
+        }
+        lines.push("!insertmacro MUI_PAGE_DIRECTORY".to_string());
+        lines.push("!insertmacro MUI_PAGE_INSTFILES".to_string());
+        lines.push("!insertmacro MUI_PAGE_FINISH".to_string());
+        lines.push("!insertmacro MUI_UNPAGE_CONFIRM".to_string());
+        lines.push("!insertmacro MUI_UNPAGE_INSTFILES".to_string());
+        lines.push("!insertmacro MUI_LANGUAGE \"English\"".to_string());
+        lines.push("".to_string());
+
+        // Registry writes to SHCTX go to HKLM or HKCU depending on the shell context.
+        let shell_context = match self.execution_level {
+            ExecutionLevel::Admin => "all",
+            ExecutionLevel::User => "current",
+        };
+        for function in [".onInit", "un.onInit"] {
+            lines.push(format!("Function {}", function));
+            lines.push(format!("  SetShellVarContext {}", shell_context));
+            lines.push("FunctionEnd".to_string());
+            lines.push("".to_string());
+        }
+
+        let uninstaller = install_path(Path::new(UNINSTALLER_FILENAME));
+        let shortcuts_dir = format!("$SMPROGRAMS\\{}", escape(&self.product_name));
+        let uninstall_key = self.uninstall_key();
+
+        lines.push("Section \"Install\"".to_string());
+
+        for (directory, files) in self.files.entries_by_directory() {
+            if files.is_empty() {
+                continue;
+            }
+
+            lines.push(format!(
+                "  SetOutPath \"{}\"",
+                install_path(directory.unwrap_or_else(|| Path::new("")))
+            ));
+
+            for (path, _) in files.values() {
+                lines.push(format!(
+                    "  File \"{}\"",
+                    escape(&source_dir.join(path).display().to_string())
+                ));
+            }
+        }
+
+        lines.push("  SetOutPath \"$INSTDIR\"".to_string());
+        lines.push(format!("  WriteUninstaller \"{}\"", uninstaller));
+
+        if !self.shortcuts.is_empty() {
+            lines.push(format!("  CreateDirectory \"{}\"", shortcuts_dir));
+
+            for shortcut in &self.shortcuts {
+                lines.push(format!(
+                    "  CreateShortcut \"{}\\{}.lnk\" \"{}\"",
+                    shortcuts_dir,
+                    escape(&shortcut.name),
+                    install_path(Path::new(&shortcut.target))
+                ));
+            }
+        }
+
+        for (name, value) in [
+            ("DisplayName", escape(&self.product_name)),
+            ("DisplayVersion", escape(&self.product_version)),
+            ("Publisher", escape(&self.manufacturer)),
+            ("InstallLocation", "$INSTDIR".to_string()),
+            ("UninstallString", format!("$\\\"{}$\\\"", uninstaller)),
+        ] {
+            lines.push(format!(
+                "  WriteRegStr SHCTX \"{}\" \"{}\" \"{}\"",
+                uninstall_key, name, value
+            ));
+        }
+        for name in ["NoModify", "NoRepair"] {
+            lines.push(format!(
+                "  WriteRegDWORD SHCTX \"{}\" \"{}\" 1",
+                uninstall_key, name
+            ));
+        }
+
+        lines.push("SectionEnd".to_string());
+        lines.push("".to_string());
+
+        lines.push("Section \"Uninstall\"".to_string());
+
+        for (path, _) in self.files.iter_entries() {
+            lines.push(format!("  Delete \"{}\"", install_path(path)));
+        }
+        lines.push(format!("  Delete \"{}\"", uninstaller));
+
+        if !self.shortcuts.is_empty() {
+            for shortcut in &self.shortcuts {
+                lines.push(format!(
+                    "  Delete \"{}\\{}.lnk\"",
+                    shortcuts_dir,
+                    escape(&shortcut.name)
+                ));
+            }
+            lines.push(format!("  RMDir \"{}\"", shortcuts_dir));
+        }
+
+        // Remove the deepest directories first so parents are empty when removed.
+        for directory in self.files.relative_directories().iter().rev() {
+            lines.push(format!("  RMDir \"{}\"", install_path(directory)));
+        }
+        lines.push("  RMDir \"$INSTDIR\"".to_string());
+        lines.push(format!("  DeleteRegKey SHCTX \"{}\"", uninstall_key));
+
+        lines.push("SectionEnd".to_string());
+        lines.push("".to_string());
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Build the installer.
+    ///
+    /// Files to install and the `.nsi` script are written to `build_dir`
+    /// and `makensis` is invoked to produce the installer, which is also
+    /// written to `build_dir`. Returns the path of the installer.
+    ///
+    /// If signtool settings are registered, the installer is signed after
+    /// it is built.
+    pub fn build(&self, logger: &slog::Logger, build_dir: impl AsRef<Path>) -> Result<PathBuf> {
+        self.validate()?;
+
+        let build_dir = build_dir.as_ref();
+        std::fs::create_dir_all(build_dir)
+            .with_context(|| format!("creating {}", build_dir.display()))?;
+        let build_dir = build_dir.canonicalize()?;
+
+        let makensis = find_makensis()?;
+
+        let files_dir = build_dir.join("files");
+        warn!(logger, "materializing files to {}", files_dir.display());
+        self.files
+            .materialize_files_with_replace(&files_dir)
+            .context("materializing files to install")?;
+
+        let script_path = build_dir.join(SCRIPT_FILENAME);
+        std::fs::write(&script_path, self.to_script(&files_dir)?)
+            .with_context(|| format!("writing {}", script_path.display()))?;
+
+        warn!(logger, "running makensis for {}", script_path.display());
+
+        let command = cmd(makensis, vec!["-V2", SCRIPT_FILENAME])
+            .dir(&build_dir)
+            .stderr_to_stdout()
+            .reader()?;
+        {
+            let reader = BufReader::new(&command);
+            for line in reader.lines() {
+                warn!(logger, "{}", line?);
+            }
+        }
+
+        let output = command
+            .try_wait()?
+            .ok_or_else(|| anyhow!("unable to wait on command"))?;
+        if !output.status.success() {
+            return Err(anyhow!("error running makensis"));
+        }
+
+        let installer_path = build_dir.join(self.resolve_installer_filename());
+
+        if let Some(settings) = &self.signtool_settings {
+            warn!(logger, "signing {}", installer_path.display());
+            let mut settings = settings.clone_settings();
+            settings.sign_file(&installer_path);
+            settings.run(logger).context("signing NSIS installer")?;
+        }
+
+        Ok(installer_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_builder() -> Result<NsisInstallerBuilder> {
+        let mut builder = NsisInstallerBuilder::new("My App", "1.0", "Tugger $Corp");
+
+        let mut m = FileManifest::default();
+        m.add_file_entry("app.exe", vec![42])?;
+        m.add_file_entry("lib/foo/bar.txt", vec![42])?;
+        builder.add_files_manifest(&m)?;
+        builder.add_shortcut("My App", "app.exe");
+
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("foo"), "foo");
+        assert_eq!(escape("$foo \"bar\""), "$$foo $\\\"bar$\\\"");
+    }
+
+    #[test]
+    fn test_execution_level() -> Result<()> {
+        assert_eq!(ExecutionLevel::try_from("user")?, ExecutionLevel::User);
+        assert_eq!(ExecutionLevel::Admin.to_string(), "admin");
+        assert!(ExecutionLevel::try_from("system").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_script() -> Result<()> {
+        let builder = test_builder()?;
+        builder.validate()?;
+
+        let source_dir = PathBuf::from("files");
+        let script = builder.to_script(&source_dir)?;
+
+        assert!(script.contains("OutFile \"My App-1.0-setup.exe\""));
+        assert!(script.contains("InstallDir \"$PROGRAMFILES64\\My App\""));
+        assert!(script.contains("RequestExecutionLevel admin"));
+        assert!(script.contains("  SetOutPath \"$INSTDIR\\lib\\foo\""));
+        assert!(script.contains(&format!(
+            "  File \"{}\"",
+            source_dir.join("lib/foo/bar.txt").display()
+        )));
+        assert!(script.contains(
+            "  CreateShortcut \"$SMPROGRAMS\\My App\\My App.lnk\" \"$INSTDIR\\app.exe\""
+        ));
+        assert!(script.contains("\"Publisher\" \"Tugger $$Corp\""));
+        assert!(script.contains("  Delete \"$INSTDIR\\lib\\foo\\bar.txt\""));
+
+        // Child directories are removed before their parents.
+        let child = script.find("  RMDir \"$INSTDIR\\lib\\foo\"").unwrap();
+        let parent = script.find("  RMDir \"$INSTDIR\\lib\"").unwrap();
+        assert!(child < parent);
+
+        let script = builder
+            .execution_level(ExecutionLevel::User)
+            .to_script(&source_dir)?;
+        assert!(script.contains("InstallDir \"$LOCALAPPDATA\\Programs\\My App\""));
+        assert!(script.contains("  SetShellVarContext current"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let mut builder = test_builder()?;
+        builder.add_shortcut("Missing", "missing.exe");
+        assert!(builder.validate().is_err());
+
+        let mut builder = test_builder()?;
+        let mut m = FileManifest::default();
+        m.add_file_entry(UNINSTALLER_FILENAME, vec![42])?;
+        builder.add_files_manifest(&m)?;
+        assert!(builder.validate().is_err());
+
+        Ok(())
+    }
+}
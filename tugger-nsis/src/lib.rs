@@ -0,0 +1,15 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Build Windows installers with NSIS.
+
+[NSIS](https://nsis.sourceforge.io/) installers are produced by compiling
+a `.nsi` script with the `makensis` tool. [NsisInstallerBuilder] generates
+a script installing the content of a `FileManifest`, along with an
+uninstaller and an Add/Remove Programs registration, and then runs
+`makensis` to produce the installer executable.
+*/
+
+mod builder;
+pub use builder::*;
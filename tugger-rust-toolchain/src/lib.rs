@@ -27,7 +27,7 @@ use {
         io::{Cursor, Read},
         path::{Path, PathBuf},
     },
-    tugger_common::http::{download_and_verify, download_to_path, get_http_client},
+    tugger_common::http::{download_and_verify, download_to_path, fetch_url},
 };
 
 const URL_PREFIX: &str = "https://static.rust-lang.org/dist/";
@@ -46,12 +46,8 @@ pub fn fetch_channel_manifest(logger: &slog::Logger, channel: &str) -> Result<Ma
     let signature_url = format!("{}.asc", manifest_url);
     let sha256_url = format!("{}.sha256", manifest_url);
 
-    let client = get_http_client()?;
-
     warn!(logger, "fetching {}", sha256_url);
-    let mut response = client.get(&sha256_url).send()?;
-    let mut sha256_data = vec![];
-    response.read_to_end(&mut sha256_data)?;
+    let sha256_data = fetch_url(&sha256_url)?;
 
     let sha256_manifest = String::from_utf8(sha256_data)?;
     let manifest_digest_wanted = sha256_manifest
@@ -61,14 +57,10 @@ pub fn fetch_channel_manifest(logger: &slog::Logger, channel: &str) -> Result<Ma
         .to_string();
 
     warn!(logger, "fetching {}", manifest_url);
-    let mut response = client.get(&manifest_url).send()?;
-    let mut manifest_data = vec![];
-    response.read_to_end(&mut manifest_data)?;
+    let manifest_data = fetch_url(&manifest_url)?;
 
     warn!(logger, "fetching {}", signature_url);
-    let mut response = client.get(&signature_url).send()?;
-    let mut signature_data = vec![];
-    response.read_to_end(&mut signature_data)?;
+    let signature_data = fetch_url(&signature_url)?;
 
     let mut hasher = sha2::Sha256::new();
     hasher.update(&manifest_data);
@@ -253,8 +245,6 @@ pub fn install_rust_toolchain(
     install_root_dir: &Path,
     download_cache_dir: Option<&Path>,
 ) -> Result<InstalledToolchain> {
-    let manifest = fetch_channel_manifest(logger, toolchain).context("fetching manifest")?;
-
     // The actual install directory is composed of the toolchain name and the
     // host triple.
     let install_dir = install_root_dir.join(format!("{}-{}", toolchain, host_triple));
@@ -279,6 +269,10 @@ pub fn install_rust_toolchain(
         .with_context(|| format!("creating {}", lock_path.display()))?;
     lock.lock_exclusive().context("obtaining lock")?;
 
+    // The manifest is only fetched if something needs installing so an
+    // existing install works without network access.
+    let mut manifest = None;
+
     for (triple, package) in installs {
         if package_is_fresh(&install_dir, package, triple)? {
             warn!(
@@ -296,8 +290,18 @@ pub fn install_rust_toolchain(
                 triple,
                 install_dir.display()
             );
-            let archive =
-                resolve_package_archive(logger, &manifest, package, triple, download_cache_dir)?;
+            if manifest.is_none() {
+                manifest.replace(
+                    fetch_channel_manifest(logger, toolchain).context("fetching manifest")?,
+                );
+            }
+            let archive = resolve_package_archive(
+                logger,
+                manifest.as_ref().expect("manifest fetched above"),
+                package,
+                triple,
+                download_cache_dir,
+            )?;
             materialize_archive(&archive, package, triple, &install_dir)?;
         }
     }
@@ -11,7 +11,7 @@ use {
         io::{BufRead, BufReader},
         path::Path,
     },
-    tugger_file_manifest::{FileEntry, FileManifest},
+    tugger_file_manifest::FileManifest,
 };
 
 /// Represents an invocation of the `snapcraft` command.
@@ -48,7 +48,7 @@ impl<'a> SnapcraftBuilder<'a> {
         Self {
             snap,
             invocations: vec![],
-            install_files: FileManifest::default(),
+            install_files: FileManifest::new_with_links(),
         }
     }
 
@@ -92,16 +92,14 @@ impl<'a> SnapcraftBuilder<'a> {
 
     /// Mark a file as specified by a filesystem path as to be installed in the
     /// build environment.
+    ///
+    /// Symlinks are preserved rather than followed.
     pub fn install_file(
         mut self,
         path: impl AsRef<Path>,
         strip_prefix: impl AsRef<Path>,
     ) -> Result<Self> {
-        let rel_path = path.as_ref().strip_prefix(strip_prefix.as_ref())?;
-
-        let entry = FileEntry::try_from(path.as_ref())?;
-
-        self.install_files.add_file_entry(rel_path, entry)?;
+        self.install_files.add_path(path, strip_prefix)?;
 
         Ok(self)
     }
@@ -0,0 +1,427 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Interface for producing Squirrel release packages. */
+
+use {
+    crate::{ReleaseEntry, ReleasesFile},
+    anyhow::{anyhow, Context, Result},
+    slog::warn,
+    std::{
+        collections::BTreeSet,
+        io::{Seek, Write},
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::FileManifest,
+    xml::{
+        common::XmlVersion,
+        writer::{EmitterConfig, EventWriter, XmlEvent},
+    },
+};
+
+const NUSPEC_NAMESPACE: &str = "http://schemas.microsoft.com/packaging/2010/07/nuspec.xsd";
+const CONTENT_TYPES_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/package/2006/content-types";
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships";
+const MANIFEST_RELATIONSHIP_TYPE: &str = "http://schemas.microsoft.com/packaging/2010/07/manifest";
+
+/// Directory within packages holding application files.
+///
+/// Squirrel only installs files from this directory.
+const APPLICATION_DIRECTORY: &str = "lib/net45";
+
+/// Convert a package path to the name stored in the ZIP archive.
+///
+/// NuGet packages follow Open Packaging Conventions, which require names to
+/// be percent-encoded.
+fn zip_name(path: &str) -> String {
+    let mut name = String::with_capacity(path.len());
+
+    for b in path.replace('\\', "/").bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/!$&'()*+,;=@".contains(&b) {
+            name.push(b as char);
+        } else {
+            name.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    name
+}
+
+/// Entity used to produce Squirrel release packages.
+///
+/// Instances describe a single release of an application. Files to install
+/// are registered via a [FileManifest].
+#[derive(Clone)]
+pub struct SquirrelReleaseBuilder {
+    /// Identifier of the application. Used as the package name.
+    id: String,
+
+    /// Version of the release. Must be a semantic version.
+    version: String,
+
+    /// Authors of the application.
+    authors: String,
+
+    /// Display name of the application.
+    title: Option<String>,
+
+    /// Description of the application.
+    description: Option<String>,
+
+    /// Files to install.
+    files: FileManifest,
+}
+
+impl SquirrelReleaseBuilder {
+    /// Construct a new instance with mandatory fields.
+    pub fn new(id: impl ToString, version: impl ToString, authors: impl ToString) -> Self {
+        Self {
+            id: id.to_string(),
+            version: version.to_string(),
+            authors: authors.to_string(),
+            title: None,
+            description: None,
+            files: FileManifest::default(),
+        }
+    }
+
+    /// Set the display name of the application.
+    #[must_use]
+    pub fn title(mut self, value: impl ToString) -> Self {
+        self.title = Some(value.to_string());
+        self
+    }
+
+    /// Set the description of the application.
+    #[must_use]
+    pub fn description(mut self, value: impl ToString) -> Self {
+        self.description = Some(value.to_string());
+        self
+    }
+
+    /// Obtain the files that will be installed by this release.
+    pub fn files(&self) -> &FileManifest {
+        &self.files
+    }
+
+    /// Add files to the release via a [FileManifest].
+    pub fn add_files_manifest(&mut self, manifest: &FileManifest) -> Result<()> {
+        self.files.add_manifest(manifest)?;
+
+        Ok(())
+    }
+
+    /// Obtain the filename of the full package for this release.
+    pub fn package_filename(&self) -> String {
+        format!("{}-{}-full.nupkg", self.id, self.version)
+    }
+
+    /// Validate that the package can be built.
+    pub fn validate(&self) -> Result<()> {
+        if self.id.is_empty() || self.id.contains(char::is_whitespace) {
+            return Err(anyhow!("{:?} is not a valid package id", self.id));
+        }
+
+        // Squirrel parses versions from package filenames, so the version
+        // must not contain characters that would confuse that parsing.
+        let (release, prerelease) = match self.version.split_once('-') {
+            Some((release, prerelease)) => (release, Some(prerelease)),
+            None => (self.version.as_str(), None),
+        };
+
+        let prerelease_valid = match prerelease {
+            Some(p) => !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric() || c == '.'),
+            None => true,
+        };
+
+        let parts = release.split('.').collect::<Vec<_>>();
+        if parts.len() != 3 || parts.iter().any(|p| p.parse::<u64>().is_err()) || !prerelease_valid
+        {
+            return Err(anyhow!(
+                "version {} is not of the form major.minor.patch[-prerelease]",
+                self.version
+            ));
+        }
+
+        if self.files.is_empty() {
+            return Err(anyhow!("release must contain at least 1 file"));
+        }
+
+        Ok(())
+    }
+
+    /// Produce the content of the package's `.nuspec` file.
+    pub fn nuspec_xml(&self) -> Result<Vec<u8>> {
+        let mut emitter_config = EmitterConfig::new();
+        emitter_config.perform_indent = true;
+
+        let mut writer = emitter_config.create_writer(Vec::new());
+
+        writer.write(XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("utf-8"),
+            standalone: None,
+        })?;
+
+        writer.write(XmlEvent::start_element("package").default_ns(NUSPEC_NAMESPACE))?;
+        writer.write(XmlEvent::start_element("metadata"))?;
+        write_text_element(&mut writer, "id", &self.id)?;
+        write_text_element(&mut writer, "version", &self.version)?;
+        write_text_element(
+            &mut writer,
+            "title",
+            self.title.as_ref().unwrap_or(&self.id),
+        )?;
+        write_text_element(&mut writer, "authors", &self.authors)?;
+        write_text_element(
+            &mut writer,
+            "description",
+            self.description.as_ref().unwrap_or(&self.id),
+        )?;
+        writer.write(XmlEvent::end_element().name("metadata"))?;
+        writer.write(XmlEvent::end_element().name("package"))?;
+
+        Ok(writer.into_inner())
+    }
+
+    /// Write the package to a writer.
+    pub fn write_package<W: Write + Seek>(&self, writer: W) -> Result<()> {
+        self.validate()?;
+
+        let mut zf = zip::ZipWriter::new(writer);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+        let nuspec_path = format!("{}.nuspec", self.id);
+        zf.start_file(zip_name(&nuspec_path), options)?;
+        zf.write_all(&self.nuspec_xml()?)?;
+
+        let mut extensions = BTreeSet::new();
+        let mut overrides = vec![];
+
+        for (path, entry) in self.files.iter_entries() {
+            let path_s = format!(
+                "{}/{}",
+                APPLICATION_DIRECTORY,
+                path.display().to_string().replace('\\', "/")
+            );
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", path.display()))?;
+
+            zf.start_file(zip_name(&path_s), options)?;
+            zf.write_all(&data)?;
+
+            if let Some(extension) = path.extension() {
+                extensions.insert(extension.to_string_lossy().to_lowercase());
+            } else {
+                overrides.push(format!("/{}", zip_name(&path_s)));
+            }
+        }
+
+        zf.start_file("_rels/.rels", options)?;
+        zf.write_all(&relationships_xml(&nuspec_path)?)?;
+
+        zf.start_file("[Content_Types].xml", options)?;
+        zf.write_all(&content_types_xml(&extensions, &overrides)?)?;
+
+        zf.finish()?;
+
+        Ok(())
+    }
+
+    /// Build the release, writing it to a release directory.
+    ///
+    /// The full package for this release is written to `releases_dir` and
+    /// added to the `RELEASES` file in that directory. Existing entries in
+    /// the `RELEASES` file are preserved, so a release directory accumulates
+    /// releases over successive builds.
+    ///
+    /// Returns the path of the written package.
+    pub fn build(&self, logger: &slog::Logger, releases_dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let releases_dir = releases_dir.as_ref();
+        std::fs::create_dir_all(releases_dir)
+            .with_context(|| format!("creating {}", releases_dir.display()))?;
+
+        let package_filename = self.package_filename();
+        let package_path = releases_dir.join(&package_filename);
+
+        warn!(
+            logger,
+            "writing Squirrel package to {}",
+            package_path.display()
+        );
+        let mut data = std::io::Cursor::new(vec![]);
+        self.write_package(&mut data)?;
+        let data = data.into_inner();
+        std::fs::write(&package_path, &data)
+            .with_context(|| format!("writing {}", package_path.display()))?;
+
+        let releases_path = releases_dir.join("RELEASES");
+        let mut releases = ReleasesFile::from_path(&releases_path)?;
+        releases.add_entry(ReleaseEntry::from_data(package_filename, &data));
+
+        warn!(logger, "writing {}", releases_path.display());
+        std::fs::write(&releases_path, releases.to_string())
+            .with_context(|| format!("writing {}", releases_path.display()))?;
+
+        Ok(package_path)
+    }
+}
+
+fn write_text_element<W: Write>(writer: &mut EventWriter<W>, name: &str, text: &str) -> Result<()> {
+    writer.write(XmlEvent::start_element(name))?;
+    writer.write(XmlEvent::Characters(text))?;
+    writer.write(XmlEvent::end_element().name(name))?;
+
+    Ok(())
+}
+
+/// Produce the content of a `_rels/.rels` file pointing at the `.nuspec`.
+fn relationships_xml(nuspec_path: &str) -> Result<Vec<u8>> {
+    let mut emitter_config = EmitterConfig::new();
+    emitter_config.perform_indent = true;
+
+    let mut writer = emitter_config.create_writer(Vec::new());
+
+    writer.write(XmlEvent::StartDocument {
+        version: XmlVersion::Version10,
+        encoding: Some("utf-8"),
+        standalone: None,
+    })?;
+
+    let target = format!("/{}", zip_name(nuspec_path));
+
+    writer.write(XmlEvent::start_element("Relationships").default_ns(RELATIONSHIPS_NAMESPACE))?;
+    writer.write(
+        XmlEvent::start_element("Relationship")
+            .attr("Type", MANIFEST_RELATIONSHIP_TYPE)
+            .attr("Target", &target)
+            .attr("Id", "R0"),
+    )?;
+    writer.write(XmlEvent::end_element().name("Relationship"))?;
+    writer.write(XmlEvent::end_element().name("Relationships"))?;
+
+    Ok(writer.into_inner())
+}
+
+/// Produce the content of a `[Content_Types].xml` file.
+fn content_types_xml(extensions: &BTreeSet<String>, overrides: &[String]) -> Result<Vec<u8>> {
+    let mut emitter_config = EmitterConfig::new();
+    emitter_config.perform_indent = true;
+
+    let mut writer = emitter_config.create_writer(Vec::new());
+
+    writer.write(XmlEvent::StartDocument {
+        version: XmlVersion::Version10,
+        encoding: Some("utf-8"),
+        standalone: None,
+    })?;
+
+    writer.write(XmlEvent::start_element("Types").default_ns(CONTENT_TYPES_NAMESPACE))?;
+
+    let mut defaults = vec![
+        (
+            "rels".to_string(),
+            "application/vnd.openxmlformats-package.relationships+xml",
+        ),
+        ("nuspec".to_string(), "application/octet"),
+    ];
+    defaults.extend(
+        extensions
+            .iter()
+            .filter(|e| e.as_str() != "rels" && e.as_str() != "nuspec")
+            .map(|e| (e.clone(), "application/octet")),
+    );
+
+    for (extension, content_type) in defaults {
+        writer.write(
+            XmlEvent::start_element("Default")
+                .attr("Extension", &extension)
+                .attr("ContentType", content_type),
+        )?;
+        writer.write(XmlEvent::end_element().name("Default"))?;
+    }
+
+    for part_name in overrides {
+        writer.write(
+            XmlEvent::start_element("Override")
+                .attr("PartName", part_name)
+                .attr("ContentType", "application/octet"),
+        )?;
+        writer.write(XmlEvent::end_element().name("Override"))?;
+    }
+
+    writer.write(XmlEvent::end_element().name("Types"))?;
+
+    Ok(writer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::io::Read, tugger_common::testutil::*};
+
+    fn test_builder(version: &str) -> Result<SquirrelReleaseBuilder> {
+        let mut builder = SquirrelReleaseBuilder::new("MyApp", version, "Tugger");
+
+        let mut m = FileManifest::default();
+        m.add_file_entry("app.exe", vec![42])?;
+        m.add_file_entry("lib/foo bar.txt", vec![42])?;
+        builder.add_files_manifest(&m)?;
+
+        Ok(builder)
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        test_builder("1.0.0")?.validate()?;
+        test_builder("1.0.0-beta.1")?.validate()?;
+        assert!(test_builder("1.0")?.validate().is_err());
+        assert!(test_builder("1.0.0-")?.validate().is_err());
+        assert!(test_builder("1.0.0-beta_1")?.validate().is_err());
+        assert!(SquirrelReleaseBuilder::new("MyApp", "1.0.0", "Tugger")
+            .validate()
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build() -> Result<()> {
+        let logger = get_logger()?;
+        let releases_dir = DEFAULT_TEMP_DIR.path().join("squirrel-test-build");
+
+        let path = test_builder("1.0.0")?.build(&logger, &releases_dir)?;
+        assert_eq!(path, releases_dir.join("MyApp-1.0.0-full.nupkg"));
+
+        let mut zf = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+        let names = zf.file_names().map(|x| x.to_string()).collect::<Vec<_>>();
+        assert!(names.contains(&"MyApp.nuspec".to_string()));
+        assert!(names.contains(&"lib/net45/app.exe".to_string()));
+        assert!(names.contains(&"lib/net45/lib/foo%20bar.txt".to_string()));
+
+        let mut nuspec = String::new();
+        zf.by_name("MyApp.nuspec")?.read_to_string(&mut nuspec)?;
+        assert!(nuspec.contains("<version>1.0.0</version>"));
+
+        test_builder("1.1.0")?
+            .title("My App")
+            .build(&logger, &releases_dir)?;
+
+        let releases = ReleasesFile::from_path(releases_dir.join("RELEASES"))?;
+        let filenames = releases
+            .entries()
+            .iter()
+            .map(|e| e.filename.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            filenames,
+            vec!["MyApp-1.0.0-full.nupkg", "MyApp-1.1.0-full.nupkg"]
+        );
+
+        Ok(())
+    }
+}
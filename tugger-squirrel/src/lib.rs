@@ -0,0 +1,22 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Produce Squirrel auto-update release feeds.
+
+[Squirrel](https://github.com/Squirrel/Squirrel.Windows) applications
+update themselves from a *release feed*: a directory (typically served
+over HTTP) holding NuGet packages (`.nupkg` files) for each release plus
+a `RELEASES` file indexing them. Each line of the `RELEASES` file holds
+the SHA-1 digest, filename, and size of a package.
+
+[SquirrelReleaseBuilder] writes a full package for a release into a
+release directory and adds it to that directory's `RELEASES` file. Entries
+for previous releases are preserved, so clients running any earlier
+release can discover and apply the update.
+*/
+
+mod builder;
+pub use builder::*;
+mod releases;
+pub use releases::*;
@@ -0,0 +1,176 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! `RELEASES` file handling. */
+
+use {
+    anyhow::{anyhow, Context, Result},
+    sha1::{Digest, Sha1},
+    std::{
+        fmt::{Display, Formatter},
+        path::Path,
+    },
+};
+
+/// An entry in a `RELEASES` file, describing a single package.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReleaseEntry {
+    /// Upper case hex encoded SHA-1 digest of the package.
+    pub sha1: String,
+
+    /// Filename of the package. e.g. `MyApp-1.0.0-full.nupkg`.
+    pub filename: String,
+
+    /// Size of the package in bytes.
+    pub size: u64,
+}
+
+impl ReleaseEntry {
+    /// Construct an instance describing a package with the given content.
+    pub fn from_data(filename: impl ToString, data: &[u8]) -> Self {
+        Self {
+            sha1: hex::encode_upper(Sha1::digest(data)),
+            filename: filename.to_string(),
+            size: data.len() as u64,
+        }
+    }
+
+    /// Whether this entry describes a delta package.
+    pub fn is_delta(&self) -> bool {
+        self.filename.ends_with("-delta.nupkg")
+    }
+}
+
+impl Display for ReleaseEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.sha1, self.filename, self.size)
+    }
+}
+
+impl TryFrom<&str> for ReleaseEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let parts = value.split_whitespace().collect::<Vec<_>>();
+
+        if parts.len() != 3 {
+            return Err(anyhow!(
+                "RELEASES entry is not of the form <sha1> <filename> <size>: {}",
+                value
+            ));
+        }
+
+        Ok(Self {
+            sha1: parts[0].to_string(),
+            filename: parts[1].to_string(),
+            size: parts[2]
+                .parse::<u64>()
+                .with_context(|| format!("parsing size of RELEASES entry: {}", value))?,
+        })
+    }
+}
+
+/// Represents a `RELEASES` file, which indexes the packages in a release feed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReleasesFile {
+    entries: Vec<ReleaseEntry>,
+}
+
+impl ReleasesFile {
+    /// Parse the content of a `RELEASES` file.
+    pub fn parse(data: &str) -> Result<Self> {
+        let entries = data
+            .trim_start_matches('\u{feff}')
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(ReleaseEntry::try_from)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// Read a `RELEASES` file from a path.
+    ///
+    /// An empty instance is returned if the path doesn't exist.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let data = std::fs::read_to_string(path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            Self::parse(&data)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Obtain the entries in this file.
+    pub fn entries(&self) -> &[ReleaseEntry] {
+        &self.entries
+    }
+
+    /// Add an entry to this file.
+    ///
+    /// An existing entry for the same filename is replaced.
+    pub fn add_entry(&mut self, entry: ReleaseEntry) {
+        if let Some(existing) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.filename == entry.filename)
+        {
+            *existing = entry;
+        } else {
+            self.entries.push(entry);
+        }
+    }
+}
+
+impl Display for ReleasesFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry() -> Result<()> {
+        let entry = ReleaseEntry::from_data("App-1.0.0-full.nupkg", b"foo");
+        assert_eq!(entry.sha1, "0BEEC7B5EA3F0FDBC95D0DD47F3C5BC275DA8A33");
+        assert_eq!(entry.size, 3);
+        assert!(!entry.is_delta());
+
+        assert_eq!(ReleaseEntry::try_from(entry.to_string().as_str())?, entry);
+        assert!(ReleaseEntry::try_from("abc App-1.0.0-full.nupkg").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_releases_file() -> Result<()> {
+        let mut releases = ReleasesFile::parse(
+            "\u{feff}94689FEDE03FED7AB59C24337673A27837F0C3EC App-1.0.0-full.nupkg 1004502\r\n\r\n",
+        )?;
+        assert_eq!(releases.entries().len(), 1);
+
+        releases.add_entry(ReleaseEntry::from_data("App-1.1.0-full.nupkg", b"foo"));
+        releases.add_entry(ReleaseEntry::from_data("App-1.1.0-full.nupkg", b"bar"));
+        assert_eq!(releases.entries().len(), 2);
+        assert_eq!(
+            releases.entries()[1].sha1,
+            hex::encode_upper(Sha1::digest(b"bar"))
+        );
+
+        assert_eq!(ReleasesFile::parse(&releases.to_string())?, releases);
+
+        Ok(())
+    }
+}
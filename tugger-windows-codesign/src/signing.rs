@@ -49,6 +49,113 @@ impl FileBasedCodeSigningCertificate {
     }
 }
 
+/// Describes how to authenticate against Azure Key Vault.
+#[derive(Clone, Debug)]
+pub enum AzureKeyVaultCredentials {
+    /// Authenticate as an Azure Active Directory application using a client secret.
+    ClientSecret {
+        /// The Azure Active Directory tenant ID.
+        tenant_id: String,
+        /// The application / client ID.
+        client_id: String,
+        /// The client secret.
+        client_secret: String,
+    },
+
+    /// Authenticate using the managed identity of the current machine.
+    ///
+    /// This is typically used on Azure hosted build agents.
+    ManagedIdentity,
+}
+
+/// Represents a code signing certificate stored in Azure Key Vault.
+///
+/// The private key of these certificates never leaves the vault. Signing
+/// is performed by `AzureSignTool`, which asks Key Vault to sign digests
+/// on our behalf.
+#[derive(Clone, Debug)]
+pub struct AzureKeyVaultCodeSigningCertificate {
+    /// URL of the Key Vault. e.g. `https://myvault.vault.azure.net`.
+    vault_url: String,
+    /// Name of the certificate in the vault.
+    certificate_name: String,
+    /// Credentials used to access the vault.
+    credentials: AzureKeyVaultCredentials,
+}
+
+impl AzureKeyVaultCodeSigningCertificate {
+    /// Construct an instance from a vault URL, certificate name, and credentials.
+    pub fn new(
+        vault_url: impl ToString,
+        certificate_name: impl ToString,
+        credentials: AzureKeyVaultCredentials,
+    ) -> Self {
+        Self {
+            vault_url: vault_url.to_string(),
+            certificate_name: certificate_name.to_string(),
+            credentials,
+        }
+    }
+
+    pub fn vault_url(&self) -> &str {
+        &self.vault_url
+    }
+
+    pub fn certificate_name(&self) -> &str {
+        &self.certificate_name
+    }
+
+    pub fn credentials(&self) -> &AzureKeyVaultCredentials {
+        &self.credentials
+    }
+}
+
+/// Represents a code signing certificate whose private key is in a key container.
+///
+/// The key container belongs to a cryptographic service provider (CSP), which
+/// is how vendors typically expose hardware tokens and HSMs to Windows signing
+/// tools. The public certificate is read from a file and the private key is
+/// addressed via the provider and a key container name. This corresponds to
+/// the `/f`, `/csp` and `/kc` flags of `signtool`.
+#[derive(Clone, Debug)]
+pub struct KeyContainerCodeSigningCertificate {
+    /// Path to a file holding the public certificate.
+    certificate_path: PathBuf,
+    /// Name of the cryptographic service provider.
+    provider: String,
+    /// Name of the key container holding the private key.
+    key_container: String,
+}
+
+impl KeyContainerCodeSigningCertificate {
+    /// Construct an instance from a public certificate path, provider, and key container.
+    ///
+    /// No validation is done that the path exists or that the provider is installed.
+    pub fn new(
+        certificate_path: impl AsRef<Path>,
+        provider: impl ToString,
+        key_container: impl ToString,
+    ) -> Self {
+        Self {
+            certificate_path: certificate_path.as_ref().to_path_buf(),
+            provider: provider.to_string(),
+            key_container: key_container.to_string(),
+        }
+    }
+
+    pub fn certificate_path(&self) -> &Path {
+        &self.certificate_path
+    }
+
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    pub fn key_container(&self) -> &str {
+        &self.key_container
+    }
+}
+
 /// Represents a code signing certificate used to sign binaries on Windows.
 ///
 /// This only represents the location of the certificate. It is possible
@@ -69,6 +176,15 @@ pub enum CodeSigningCertificate {
     /// This is the most reliable way to specify a certificate in the Windows
     /// certificate store because thumbprints should be unique.
     Sha1Thumbprint(SystemStore, String),
+
+    /// A certificate stored in Azure Key Vault.
+    ///
+    /// Signing is performed via `AzureSignTool` instead of `signtool`.
+    AzureKeyVault(AzureKeyVaultCodeSigningCertificate),
+
+    /// A certificate whose private key is in a key container of a cryptographic
+    /// service provider.
+    KeyContainer(KeyContainerCodeSigningCertificate),
 }
 
 impl From<FileBasedCodeSigningCertificate> for CodeSigningCertificate {
@@ -77,6 +193,18 @@ impl From<FileBasedCodeSigningCertificate> for CodeSigningCertificate {
     }
 }
 
+impl From<AzureKeyVaultCodeSigningCertificate> for CodeSigningCertificate {
+    fn from(v: AzureKeyVaultCodeSigningCertificate) -> Self {
+        Self::AzureKeyVault(v)
+    }
+}
+
+impl From<KeyContainerCodeSigningCertificate> for CodeSigningCertificate {
+    fn from(v: KeyContainerCodeSigningCertificate) -> Self {
+        Self::KeyContainer(v)
+    }
+}
+
 /// Create parameters for a self-signed x509 certificate suitable for code signing on Windows.
 ///
 /// The self-signed certificate mimics what the powershell
@@ -146,6 +274,12 @@ pub fn certificate_to_pfx(
 /// MSI file magic.
 const CFB_MAGIC_NUMBER: [u8; 8] = [0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
 
+/// ZIP local file header magic.
+const ZIP_MAGIC_NUMBER: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
+/// File extensions of ZIP based package formats that signtool can sign.
+const SIGNABLE_PACKAGE_EXTENSIONS: &[&str] = &["appx", "appxbundle", "msix", "msixbundle"];
+
 /// Whether the bytes passed in look like a file header for a format that is signable.
 ///
 /// The passed buffer must be at least 16 bytes long.
@@ -165,7 +299,11 @@ pub fn is_signable_binary_header(data: &[u8]) -> bool {
 
 /// Determine whether a given filesystem path is signable.
 ///
-/// This effectively answers whether the given path is a PE or MSI.
+/// This effectively answers whether the given path is a PE, MSI, or MSIX / AppX
+/// package.
+///
+/// Since MSIX / AppX packages are ZIP archives, they are identified by their
+/// file extension in addition to their content.
 pub fn is_file_signable(path: impl AsRef<Path>) -> Result<bool> {
     let path = path.as_ref();
 
@@ -177,7 +315,14 @@ pub fn is_file_signable(path: impl AsRef<Path>) -> Result<bool> {
     let mut buffer: [u8; 16] = [0; 16];
     fh.read_exact(&mut buffer)?;
 
-    Ok(is_signable_binary_header(&buffer))
+    let is_package = path
+        .extension()
+        .map(|ext| {
+            SIGNABLE_PACKAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+        })
+        .unwrap_or(false);
+
+    Ok(is_signable_binary_header(&buffer) || (is_package && buffer[0..4] == ZIP_MAGIC_NUMBER))
 }
 
 #[cfg(test)]
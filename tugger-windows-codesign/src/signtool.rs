@@ -5,7 +5,9 @@
 /*! Interface to `signtool.exe`. */
 
 use {
-    crate::signing::CodeSigningCertificate,
+    crate::signing::{
+        AzureKeyVaultCodeSigningCertificate, AzureKeyVaultCredentials, CodeSigningCertificate,
+    },
     anyhow::{anyhow, Context, Result},
     slog::warn,
     std::{
@@ -53,6 +55,25 @@ pub fn find_signtool() -> Result<PathBuf> {
     Err(anyhow!("finding signtool.exe only supported on Windows"))
 }
 
+/// Locate `AzureSignTool` by searching `PATH`.
+///
+/// `AzureSignTool` is typically installed as a .NET global tool via
+/// `dotnet tool install --global AzureSignTool`.
+pub fn find_azuresigntool() -> Result<PathBuf> {
+    let filename = if cfg!(target_family = "windows") {
+        "AzureSignTool.exe"
+    } else {
+        "azuresigntool"
+    };
+
+    let paths = std::env::var_os("PATH").unwrap_or_default();
+
+    std::env::split_paths(&paths)
+        .map(|p| p.join(filename))
+        .find(|p| p.exists())
+        .ok_or_else(|| anyhow!("unable to locate {} on PATH", filename))
+}
+
 /// Represents an invocation of `signtool.exe sign` to sign some files.
 #[derive(Clone, Debug)]
 pub struct SigntoolSign {
@@ -149,10 +170,10 @@ impl SigntoolSign {
         self
     }
 
-    /// Run `signtool sign` with requested options.
-    pub fn run(&self, logger: &slog::Logger) -> Result<()> {
-        let signtool = find_signtool().context("locating signtool.exe")?;
-
+    /// Resolve the arguments to pass to `signtool`.
+    ///
+    /// Errors if the certificate can't be used with `signtool`.
+    fn signtool_args(&self) -> Result<Vec<String>> {
         let mut args = vec!["sign".to_string()];
 
         if self.verbose {
@@ -187,6 +208,19 @@ impl SigntoolSign {
                 args.push("/sha1".to_string());
                 args.push(sha1.to_string());
             }
+            CodeSigningCertificate::KeyContainer(token) => {
+                args.push("/f".to_string());
+                args.push(token.certificate_path().display().to_string());
+                args.push("/csp".to_string());
+                args.push(token.provider().to_string());
+                args.push("/kc".to_string());
+                args.push(token.key_container().to_string());
+            }
+            CodeSigningCertificate::AzureKeyVault(_) => {
+                return Err(anyhow!(
+                    "Azure Key Vault certificates can only be signed with AzureSignTool"
+                ));
+            }
         }
 
         if let Some(description) = &self.description {
@@ -216,10 +250,105 @@ impl SigntoolSign {
 
         args.extend(self.sign_files.iter().map(|p| p.display().to_string()));
 
-        let command = duct::cmd(signtool, args)
+        Ok(args)
+    }
+
+    /// Resolve the arguments to pass to `AzureSignTool`.
+    ///
+    /// Credentials aren't passed as arguments, which other processes could
+    /// see. See [Self::azure_sign_tool_env()].
+    fn azure_sign_tool_args(&self, cert: &AzureKeyVaultCodeSigningCertificate) -> Vec<String> {
+        let mut args = vec![
+            "sign".to_string(),
+            "--azure-key-vault-url".to_string(),
+            cert.vault_url().to_string(),
+            "--azure-key-vault-certificate".to_string(),
+            cert.certificate_name().to_string(),
+            // Managed identity authentication uses the Azure SDK's default
+            // credential chain, which reads client secrets from the environment.
+            "--azure-key-vault-managed-identity".to_string(),
+        ];
+
+        if self.verbose || self.debug {
+            args.push("--verbose".to_string());
+        }
+
+        if let Some(description) = &self.description {
+            args.push("--description".to_string());
+            args.push(description.to_string());
+        }
+
+        args.push("--file-digest".to_string());
+        args.push(self.file_digest_algorithm.clone());
+
+        if let Some(server) = &self.timestamp_server {
+            match server {
+                TimestampServer::Simple(url) => {
+                    args.push("--timestamp-authenticode".to_string());
+                    args.push(url.to_string());
+                }
+                TimestampServer::Rfc3161(url, algorithm) => {
+                    args.push("--timestamp-rfc3161".to_string());
+                    args.push(url.to_string());
+                    args.push("--timestamp-digest".to_string());
+                    args.push(algorithm.to_string());
+                }
+            }
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+
+        args.extend(self.sign_files.iter().map(|p| p.display().to_string()));
+
+        args
+    }
+
+    /// Resolve the environment variables to set for `AzureSignTool`.
+    ///
+    /// Client secret credentials are passed via the `AZURE_TENANT_ID`,
+    /// `AZURE_CLIENT_ID` and `AZURE_CLIENT_SECRET` variables read by the
+    /// Azure SDK.
+    fn azure_sign_tool_env(cert: &AzureKeyVaultCodeSigningCertificate) -> Vec<(&str, String)> {
+        match cert.credentials() {
+            AzureKeyVaultCredentials::ClientSecret {
+                tenant_id,
+                client_id,
+                client_secret,
+            } => vec![
+                ("AZURE_TENANT_ID", tenant_id.to_string()),
+                ("AZURE_CLIENT_ID", client_id.to_string()),
+                ("AZURE_CLIENT_SECRET", client_secret.to_string()),
+            ],
+            AzureKeyVaultCredentials::ManagedIdentity => vec![],
+        }
+    }
+
+    /// Run `signtool sign` with requested options.
+    ///
+    /// Certificates stored in Azure Key Vault are routed to `AzureSignTool`,
+    /// which accepts equivalent options.
+    pub fn run(&self, logger: &slog::Logger) -> Result<()> {
+        let (program, args, env) = match &self.certificate {
+            CodeSigningCertificate::AzureKeyVault(cert) => (
+                find_azuresigntool().context("locating AzureSignTool")?,
+                self.azure_sign_tool_args(cert),
+                Self::azure_sign_tool_env(cert),
+            ),
+            _ => (
+                find_signtool().context("locating signtool.exe")?,
+                self.signtool_args()?,
+                vec![],
+            ),
+        };
+
+        let command = env
+            .into_iter()
+            .fold(duct::cmd(&program, args), |command, (key, value)| {
+                command.env(key, value)
+            })
             .stderr_to_stdout()
             .reader()
-            .context("running signtool")?;
+            .with_context(|| format!("running {}", program.display()))?;
         {
             let reader = BufReader::new(&command);
             for line in reader.lines() {
@@ -233,7 +362,7 @@ impl SigntoolSign {
         if output.status.success() {
             Ok(())
         } else {
-            Err(anyhow!("error running signtool"))
+            Err(anyhow!("error running {}", program.display()))
         }
     }
 }
@@ -244,7 +373,10 @@ mod tests {
         super::*,
         crate::{
             create_self_signed_code_signing_certificate,
-            signing::{certificate_to_pfx, FileBasedCodeSigningCertificate},
+            signing::{
+                certificate_to_pfx, FileBasedCodeSigningCertificate,
+                KeyContainerCodeSigningCertificate,
+            },
         },
         tugger_common::testutil::*,
     };
@@ -298,4 +430,96 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_key_container_args() {
+        let cert = KeyContainerCodeSigningCertificate::new(
+            "cert.cer",
+            "eToken Base Cryptographic Provider",
+            "container",
+        );
+
+        let mut sign = SigntoolSign::new(cert.into());
+        sign.sign_file("app.exe");
+
+        assert_eq!(
+            sign.signtool_args().unwrap(),
+            vec![
+                "sign",
+                "/f",
+                "cert.cer",
+                "/csp",
+                "eToken Base Cryptographic Provider",
+                "/kc",
+                "container",
+                "/fd",
+                "SHA256",
+                "app.exe"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_azure_key_vault_args() {
+        let cert = AzureKeyVaultCodeSigningCertificate::new(
+            "https://vault.vault.azure.net",
+            "cert",
+            AzureKeyVaultCredentials::ManagedIdentity,
+        );
+
+        let mut sign = SigntoolSign::new(cert.clone().into());
+        sign.timestamp_server(TimestampServer::Rfc3161(
+            "http://timestamp.example.com".to_string(),
+            "SHA256".to_string(),
+        ));
+        sign.sign_file("app.exe");
+
+        assert_eq!(
+            sign.azure_sign_tool_args(&cert),
+            vec![
+                "sign",
+                "--azure-key-vault-url",
+                "https://vault.vault.azure.net",
+                "--azure-key-vault-certificate",
+                "cert",
+                "--azure-key-vault-managed-identity",
+                "--file-digest",
+                "SHA256",
+                "--timestamp-rfc3161",
+                "http://timestamp.example.com",
+                "--timestamp-digest",
+                "SHA256",
+                "app.exe"
+            ]
+        );
+        assert!(sign.signtool_args().is_err());
+    }
+
+    #[test]
+    fn test_azure_key_vault_client_secret() {
+        let cert = AzureKeyVaultCodeSigningCertificate::new(
+            "https://vault.vault.azure.net",
+            "cert",
+            AzureKeyVaultCredentials::ClientSecret {
+                tenant_id: "tenant".to_string(),
+                client_id: "client".to_string(),
+                client_secret: "secret".to_string(),
+            },
+        );
+
+        let mut sign = SigntoolSign::new(cert.clone().into());
+        sign.sign_file("app.exe");
+
+        let args = sign.azure_sign_tool_args(&cert);
+        assert!(args.contains(&"--azure-key-vault-managed-identity".to_string()));
+        assert!(!args.contains(&"secret".to_string()));
+        assert_eq!(
+            SigntoolSign::azure_sign_tool_env(&cert),
+            vec![
+                ("AZURE_TENANT_ID", "tenant".to_string()),
+                ("AZURE_CLIENT_ID", "client".to_string()),
+                ("AZURE_CLIENT_SECRET", "secret".to_string()),
+            ]
+        );
+    }
 }
@@ -11,8 +11,14 @@ pub use sdk::target_arch_to_windows_sdk_platform_path;
 mod util;
 mod vc_redistributable;
 pub use vc_redistributable::{
+    download_visual_cpp_redistributable, extract_visual_cpp_redistributable_dlls,
     find_visual_cpp_redistributable, VcRedistributablePlatform, VC_REDIST_ARM64, VC_REDIST_X64,
     VC_REDIST_X86,
 };
+mod visual_studio;
+pub use visual_studio::{
+    find_visual_studio_installation, find_visual_studio_installations, VisualStudioDiscovery,
+    VisualStudioInstallation,
+};
 mod vswhere;
 pub use vswhere::find_vswhere;
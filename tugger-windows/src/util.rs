@@ -38,3 +38,93 @@ pub fn get_known_folder_path(
         }
     }
 }
+
+/// Read the string values of a registry key under `HKEY_LOCAL_MACHINE`.
+///
+/// The 32-bit registry view is used. Returns `(name, value)` pairs. Errors
+/// (e.g. the key not existing) result in an empty list.
+#[cfg(windows)]
+pub fn registry_string_values(subkey: &str) -> Vec<(String, String)> {
+    use {
+        std::{ffi::OsStr, os::windows::ffi::OsStrExt},
+        winapi::{
+            shared::{minwindef::HKEY, winerror::ERROR_SUCCESS},
+            um::{
+                winnt::{KEY_READ, KEY_WOW64_32KEY, REG_SZ},
+                winreg::{RegCloseKey, RegEnumValueW, RegOpenKeyExW, HKEY_LOCAL_MACHINE},
+            },
+        },
+    };
+
+    struct Key(HKEY);
+
+    impl Drop for Key {
+        fn drop(&mut self) {
+            unsafe {
+                RegCloseKey(self.0);
+            }
+        }
+    }
+
+    let subkey = OsStr::new(subkey)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect::<Vec<_>>();
+
+    let mut res = vec![];
+
+    unsafe {
+        let mut key = Key(std::ptr::null_mut());
+        if RegOpenKeyExW(
+            HKEY_LOCAL_MACHINE,
+            subkey.as_ptr(),
+            0,
+            KEY_READ | KEY_WOW64_32KEY,
+            &mut key.0,
+        ) != ERROR_SUCCESS as i32
+        {
+            return res;
+        }
+
+        for index in 0.. {
+            let mut name = [0u16; 256];
+            let mut name_len = name.len() as u32;
+            let mut data = [0u16; 1024];
+            let mut data_len = (data.len() * 2) as u32;
+            let mut value_type = 0;
+
+            if RegEnumValueW(
+                key.0,
+                index,
+                name.as_mut_ptr(),
+                &mut name_len,
+                std::ptr::null_mut(),
+                &mut value_type,
+                data.as_mut_ptr() as *mut u8,
+                &mut data_len,
+            ) != ERROR_SUCCESS as i32
+            {
+                break;
+            }
+
+            if value_type != REG_SZ {
+                continue;
+            }
+
+            // Data length is in bytes and may include a trailing NULL.
+            let data = &data[..data_len as usize / 2];
+            let data = data.split(|c| *c == 0).next().unwrap_or(data);
+
+            res.push((
+                std::ffi::OsString::from_wide(&name[..name_len as usize])
+                    .to_string_lossy()
+                    .to_string(),
+                std::ffi::OsString::from_wide(data)
+                    .to_string_lossy()
+                    .to_string(),
+            ));
+        }
+    }
+
+    res
+}
@@ -3,17 +3,19 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    anyhow::{anyhow, Result},
+    anyhow::{anyhow, Context, Result},
     once_cell::sync::Lazy,
     std::{
+        collections::BTreeMap,
         fmt::{Display, Formatter},
-        path::PathBuf,
+        io::{Cursor, Read},
+        path::{Path, PathBuf},
     },
-    tugger_common::http::RemoteContent,
+    tugger_common::http::{download_to_path, RemoteContent},
 };
 
 #[cfg(windows)]
-use {crate::find_vswhere, std::collections::BTreeMap};
+use crate::{find_visual_studio_installations, find_vswhere};
 
 // Latest versions of the VC++ Redistributable can be found at
 // https://support.microsoft.com/en-us/help/2977003/the-latest-supported-visual-c-downloads.
@@ -85,11 +87,50 @@ impl TryFrom<&str> for VcRedistributablePlatform {
 ///
 /// The returned paths should have names like `vcruntime140.dll`. Some installs
 /// have multiple DLLs.
+///
+/// `vswhere.exe` is used to find a Visual Studio installation having the
+/// redistributable component. If that fails, all installations found by
+/// [find_visual_studio_installations] are searched.
 #[cfg(windows)]
 pub fn find_visual_cpp_redistributable(
+    logger: &slog::Logger,
     redist_version: &str,
     platform: VcRedistributablePlatform,
 ) -> Result<Vec<PathBuf>> {
+    let mut install_paths = vec![];
+
+    match vswhere_redistributable_install_path(redist_version) {
+        Ok(path) => install_paths.push(path),
+        Err(e) => slog::info!(
+            logger,
+            "vswhere could not locate Visual C++ Redistributable {}: {}",
+            redist_version,
+            e
+        ),
+    }
+
+    for installation in find_visual_studio_installations() {
+        if !install_paths.contains(&installation.install_path) {
+            install_paths.push(installation.install_path);
+        }
+    }
+
+    for install_path in install_paths {
+        if let Ok(paths) = find_visual_cpp_redistributable_in_installation(
+            &install_path,
+            redist_version,
+            &platform,
+        ) {
+            return Ok(paths);
+        }
+    }
+
+    Err(anyhow!("unable to find install VC++ Redistributable"))
+}
+
+/// Use `vswhere.exe` to find an installation having the redistributable component.
+#[cfg(windows)]
+fn vswhere_redistributable_install_path(redist_version: &str) -> Result<PathBuf> {
     let vswhere_exe = find_vswhere()?;
 
     let cmd = duct::cmd(
@@ -109,12 +150,20 @@ pub fn find_visual_cpp_redistributable(
     .stderr_capture()
     .run()?;
 
-    let install_path = PathBuf::from(
+    Ok(PathBuf::from(
         String::from_utf8(cmd.stdout)?
             .strip_suffix("\r\n")
             .ok_or_else(|| anyhow!("unable to strip string"))?,
-    );
+    ))
+}
 
+/// Find the Visual C++ Redistributable DLLs in a Visual Studio installation.
+#[cfg(windows)]
+fn find_visual_cpp_redistributable_in_installation(
+    install_path: &Path,
+    redist_version: &str,
+    platform: &VcRedistributablePlatform,
+) -> Result<Vec<PathBuf>> {
     // This gets us the path to the Visual Studio installation root. The vcruntimeXXX.dll
     // files are under a path like: VC\Redist\MSVC\<version>\<arch>\Microsoft.VCXXX.CRT\vcruntimeXXX.dll.
 
@@ -158,15 +207,243 @@ pub fn find_visual_cpp_redistributable(
         .1)
 }
 
+/// Find the paths to the Visual C++ Redistributable DLLs.
+///
+/// Outside of Windows, there is no Visual Studio installation to find the
+/// files in. Instead, the pinned Visual C++ Redistributable installer is
+/// downloaded to the user's cache directory and the DLLs are extracted from it.
+/// See [download_visual_cpp_redistributable].
 #[cfg(unix)]
 pub fn find_visual_cpp_redistributable(
-    _version: &str,
-    _platform: VcRedistributablePlatform,
+    logger: &slog::Logger,
+    redist_version: &str,
+    platform: VcRedistributablePlatform,
 ) -> Result<Vec<PathBuf>> {
-    // TODO we could potentially reference these files at a URL and download them or something.
-    Err(anyhow!(
-        "Finding the Visual C++ Redistributable is not supported outside of Windows"
-    ))
+    let cache_dir = dirs::cache_dir()
+        .ok_or_else(|| anyhow!("unable to resolve cache directory"))?
+        .join("tugger")
+        .join("vc_redistributable");
+
+    download_visual_cpp_redistributable(logger, redist_version, platform, &cache_dir)
+}
+
+/// Download the Visual C++ Redistributable and extract its runtime DLLs.
+///
+/// The pinned, SHA-256 verified installer for `platform` (e.g. [VC_REDIST_X64])
+/// is downloaded to `cache_dir` and the `vcruntime*.dll` files it contains are
+/// extracted to a directory next to it. Only `redist_version` `14` is supported.
+///
+/// This works on any host operating system.
+pub fn download_visual_cpp_redistributable(
+    logger: &slog::Logger,
+    redist_version: &str,
+    platform: VcRedistributablePlatform,
+    cache_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    if redist_version != "14" {
+        return Err(anyhow!(
+            "downloading Visual C++ Redistributable version {} is not supported; only 14 is",
+            redist_version
+        ));
+    }
+
+    let entry = match platform {
+        VcRedistributablePlatform::X86 => &*VC_REDIST_X86,
+        VcRedistributablePlatform::X64 => &*VC_REDIST_X64,
+        VcRedistributablePlatform::Arm64 => &*VC_REDIST_ARM64,
+    };
+
+    let installer_path = cache_dir.join(format!("vc_redist.{}.exe", platform));
+    download_to_path(logger, entry, &installer_path)
+        .context("downloading Visual C++ Redistributable")?;
+
+    let installer_data = std::fs::read(&installer_path)
+        .with_context(|| format!("reading {}", installer_path.display()))?;
+
+    // Key the extraction directory by content so changes to the pinned
+    // installer don't reuse stale files.
+    let dest_dir = cache_dir.join(format!(
+        "{}-{}-{}",
+        redist_version,
+        platform,
+        &entry.sha256[0..12]
+    ));
+
+    extract_visual_cpp_redistributable_dlls(&installer_data, &dest_dir)
+}
+
+/// Magic number of the `.wixburn` PE section.
+const BURN_SECTION_MAGIC: u32 = 0x00f1_4300;
+
+/// Magic number of OLE compound documents, such as MSI files.
+const OLE_MAGIC: [u8; 8] = [0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+
+/// Magic number of cabinet files.
+const CAB_MAGIC: &[u8] = b"MSCF";
+
+/// Obtain the containers attached to a WiX Burn bundle executable.
+///
+/// The `.wixburn` PE section records the size of the executable stub and the
+/// sizes of the cabinet containers appended after it.
+fn burn_bundle_containers(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let pe = goblin::pe::PE::parse(data).context("parsing PE file")?;
+
+    let section = pe
+        .sections
+        .iter()
+        .find(|section| matches!(section.name(), Ok(".wixburn")))
+        .ok_or_else(|| anyhow!("no .wixburn section; not a WiX Burn bundle"))?;
+
+    let start = section.pointer_to_raw_data as usize;
+    let header = data
+        .get(start..start + section.size_of_raw_data as usize)
+        .ok_or_else(|| anyhow!(".wixburn section data out of bounds"))?;
+
+    let read_u32 = |offset: usize| -> Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(
+            header
+                .get(offset..offset + 4)
+                .ok_or_else(|| anyhow!("truncated .wixburn section"))?,
+        );
+        Ok(u32::from_le_bytes(buf))
+    };
+
+    if read_u32(0)? != BURN_SECTION_MAGIC {
+        return Err(anyhow!("bad .wixburn section magic"));
+    }
+
+    // Offset 8 holds the 16 byte bundle GUID.
+    let stub_size = read_u32(24)? as usize;
+    let format = read_u32(40)?;
+    if format != 1 {
+        return Err(anyhow!("unsupported Burn container format {}", format));
+    }
+
+    let mut containers = vec![];
+    let mut offset = stub_size;
+
+    for i in 0..read_u32(44)? as usize {
+        let size = read_u32(48 + i * 4)? as usize;
+        containers.push(
+            data.get(offset..offset + size)
+                .ok_or_else(|| anyhow!("Burn container {} out of bounds", i))?,
+        );
+        offset += size;
+    }
+
+    Ok(containers)
+}
+
+/// Read all files from a cabinet.
+fn read_cabinet_files(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut cabinet = cab::Cabinet::new(Cursor::new(data)).context("parsing cabinet")?;
+
+    let names = cabinet
+        .folder_entries()
+        .flat_map(|folder| folder.file_entries())
+        .map(|file| file.name().to_string())
+        .collect::<Vec<_>>();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let mut data = vec![];
+            cabinet
+                .read_file(&name)
+                .with_context(|| format!("reading {} from cabinet", name))?
+                .read_to_end(&mut data)?;
+
+            Ok((name, data))
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+/// Extract the `vcruntime*.dll` files from a Visual C++ Redistributable installer.
+///
+/// The installer is a WiX Burn bundle containing MSI packages. The MSI `File`
+/// tables map the names of files in cabinets to installed filenames.
+///
+/// Extracted files are written to `dest_dir`, whose paths are returned.
+pub fn extract_visual_cpp_redistributable_dlls(
+    installer_data: &[u8],
+    dest_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let mut msis = vec![];
+    let mut cabs = vec![];
+
+    for container in burn_bundle_containers(installer_data)? {
+        for (_, data) in read_cabinet_files(container)? {
+            if data.starts_with(&OLE_MAGIC) {
+                msis.push(data);
+            } else if data.starts_with(CAB_MAGIC) {
+                cabs.push(data);
+            }
+        }
+    }
+
+    // File table key -> installed filename.
+    let mut wanted = BTreeMap::new();
+
+    for msi_data in msis {
+        let mut package = msi::Package::open(Cursor::new(msi_data)).context("opening MSI")?;
+
+        if !package.has_table("File") {
+            continue;
+        }
+
+        for row in package.select_rows(msi::Select::table("File"))? {
+            let (key, filename) = match (row["File"].as_str(), row["FileName"].as_str()) {
+                (Some(key), Some(filename)) => (key, filename),
+                _ => continue,
+            };
+
+            // Values are either `long` or `SHORT|long`.
+            let filename = filename.rsplit('|').next().unwrap_or(filename);
+            let lower = filename.to_lowercase();
+
+            if lower.starts_with("vcruntime") && lower.ends_with(".dll") {
+                wanted.insert(key.to_string(), filename.to_string());
+            }
+        }
+
+        // Cabinets may also be embedded in the MSI as streams.
+        let stream_names = package.streams().collect::<Vec<_>>();
+        for name in stream_names {
+            let mut data = vec![];
+            package.read_stream(&name)?.read_to_end(&mut data)?;
+
+            if data.starts_with(CAB_MAGIC) {
+                cabs.push(data);
+            }
+        }
+    }
+
+    std::fs::create_dir_all(dest_dir)
+        .with_context(|| format!("creating directory {}", dest_dir.display()))?;
+
+    let mut paths = vec![];
+
+    for cab_data in cabs {
+        for (name, data) in read_cabinet_files(&cab_data)? {
+            if let Some(filename) = wanted.get(&name) {
+                let path = dest_dir.join(filename);
+                std::fs::write(&path, &data)
+                    .with_context(|| format!("writing {}", path.display()))?;
+                paths.push(path);
+            }
+        }
+    }
+
+    if paths.is_empty() {
+        Err(anyhow!(
+            "unable to find vcruntime DLLs in Visual C++ Redistributable installer"
+        ))
+    } else {
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
 }
 
 #[cfg(test)]
@@ -200,7 +477,45 @@ mod tests {
     }
 
     #[test]
-    fn test_find_visual_cpp_redistributable_14() {
+    fn test_download_visual_cpp_redistributable() -> Result<()> {
+        let logger = get_logger()?;
+
+        let paths = download_visual_cpp_redistributable(
+            &logger,
+            "14",
+            VcRedistributablePlatform::X64,
+            &DEFAULT_DOWNLOAD_DIR.join("vc_redistributable"),
+        )?;
+
+        assert!(paths
+            .iter()
+            .any(|p| p.file_name().unwrap() == "vcruntime140.dll"));
+        assert!(paths.iter().all(|p| p.exists()));
+
+        assert!(download_visual_cpp_redistributable(
+            &logger,
+            "12",
+            VcRedistributablePlatform::X64,
+            &DEFAULT_DOWNLOAD_DIR.join("vc_redistributable"),
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_not_burn_bundle() {
+        assert!(extract_visual_cpp_redistributable_dlls(
+            b"not an executable",
+            &DEFAULT_TEMP_DIR.path().join("not-burn")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_find_visual_cpp_redistributable_14() -> Result<()> {
+        let logger = get_logger()?;
+
         let platforms = vec![
             VcRedistributablePlatform::X86,
             VcRedistributablePlatform::X64,
@@ -208,15 +523,20 @@ mod tests {
         ];
 
         for platform in platforms {
-            let res = find_visual_cpp_redistributable("14", platform);
+            let res = find_visual_cpp_redistributable(&logger, "14", platform);
 
             if cfg!(windows) {
                 if res.is_ok() {
                     println!("found vcruntime files: {:?}", res.unwrap());
                 }
             } else {
-                assert!(res.is_err());
+                let paths = res.unwrap();
+                assert!(paths
+                    .iter()
+                    .any(|p| p.file_name().unwrap() == "vcruntime140.dll"));
             }
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,376 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Discovery of Visual Studio installations. */
+
+use {
+    anyhow::{anyhow, Result},
+    std::path::{Path, PathBuf},
+};
+
+#[cfg(windows)]
+use crate::{find_vswhere, util::get_known_folder_path, util::registry_string_values};
+
+/// Years of Visual Studio releases installed in well-known directories.
+#[cfg(any(windows, test))]
+const VISUAL_STUDIO_YEARS: &[&str] = &["2022", "2019", "2017"];
+
+/// Editions of Visual Studio, in order of preference.
+#[cfg(any(windows, test))]
+const VISUAL_STUDIO_EDITIONS: &[&str] = &[
+    "Enterprise",
+    "Professional",
+    "Community",
+    "BuildTools",
+    "Preview",
+];
+
+/// How a Visual Studio installation was discovered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VisualStudioDiscovery {
+    /// Reported by `vswhere.exe`.
+    Vswhere,
+
+    /// Derived from `VSINSTALLDIR` or `VCToolsInstallDir` environment variables.
+    ///
+    /// These are defined in Visual Studio developer command prompts.
+    Environment,
+
+    /// Registered in the `SxS` registry keys.
+    Registry,
+
+    /// Found in a well-known installation directory.
+    ///
+    /// This finds Build Tools installs, which aren't always registered with
+    /// the other mechanisms.
+    Directory,
+}
+
+/// Describes an installation of Visual Studio or Visual Studio Build Tools.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VisualStudioInstallation {
+    /// Root directory of the installation.
+    pub install_path: PathBuf,
+
+    /// Version of the installation, if known. e.g. `16.0` or `17.4.33103.184`.
+    pub version: Option<String>,
+
+    /// Path to the `VC\Tools\MSVC\<version>` directory, if present.
+    pub vc_tools_path: Option<PathBuf>,
+
+    /// How the installation was discovered.
+    pub discovery: VisualStudioDiscovery,
+}
+
+impl VisualStudioInstallation {
+    /// Construct an instance from an installation root directory.
+    ///
+    /// The path to the default MSVC tools is resolved automatically.
+    pub fn from_install_path(
+        install_path: impl AsRef<Path>,
+        version: Option<String>,
+        discovery: VisualStudioDiscovery,
+    ) -> Self {
+        let install_path = install_path.as_ref().to_path_buf();
+        let vc_tools_path = resolve_vc_tools_path(&install_path);
+
+        Self {
+            install_path,
+            version,
+            vc_tools_path,
+            discovery,
+        }
+    }
+}
+
+/// Resolve the `VC\Tools\MSVC\<version>` directory of an installation.
+///
+/// The default version recorded by the installation is preferred. Otherwise
+/// the highest version present is used.
+fn resolve_vc_tools_path(install_path: &Path) -> Option<PathBuf> {
+    let msvc_path = install_path.join("VC").join("Tools").join("MSVC");
+
+    let default_version_path = install_path
+        .join("VC")
+        .join("Auxiliary")
+        .join("Build")
+        .join("Microsoft.VCToolsVersion.default.txt");
+
+    if let Ok(version) = std::fs::read_to_string(&default_version_path) {
+        let path = msvc_path.join(version.trim());
+        if path.is_dir() {
+            return Some(path);
+        }
+    }
+
+    let mut versions = std::fs::read_dir(&msvc_path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+
+    // Versions are of the form `14.29.30133`. Compare numerically.
+    versions.sort_by_key(|path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    });
+
+    versions.pop()
+}
+
+/// Resolve an installation from Visual Studio developer environment variables.
+///
+/// `get_var` resolves the value of an environment variable.
+fn installation_from_environment<F>(get_var: F) -> Option<VisualStudioInstallation>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let version = get_var("VisualStudioVersion");
+
+    if let Some(install_dir) = get_var("VSINSTALLDIR") {
+        let mut installation = VisualStudioInstallation::from_install_path(
+            install_dir.trim_end_matches('\\'),
+            version,
+            VisualStudioDiscovery::Environment,
+        );
+
+        if let Some(tools_dir) = get_var("VCToolsInstallDir") {
+            installation.vc_tools_path = Some(PathBuf::from(tools_dir.trim_end_matches('\\')));
+        }
+
+        return Some(installation);
+    }
+
+    // VCToolsInstallDir is <root>\VC\Tools\MSVC\<version>\.
+    let tools_path = PathBuf::from(get_var("VCToolsInstallDir")?.trim_end_matches('\\'));
+    let install_path = tools_path.ancestors().nth(4)?.to_path_buf();
+
+    Some(VisualStudioInstallation {
+        install_path,
+        version,
+        vc_tools_path: Some(tools_path),
+        discovery: VisualStudioDiscovery::Environment,
+    })
+}
+
+/// Find installations in well-known directories under the given roots.
+///
+/// Roots are Program Files directories. Installations live at
+/// `<root>\Microsoft Visual Studio\<year>\<edition>`.
+#[cfg(any(windows, test))]
+fn installations_in_directories(roots: &[PathBuf]) -> Vec<VisualStudioInstallation> {
+    let mut res = vec![];
+
+    for year in VISUAL_STUDIO_YEARS {
+        for root in roots {
+            for edition in VISUAL_STUDIO_EDITIONS {
+                let path = root
+                    .join("Microsoft Visual Studio")
+                    .join(year)
+                    .join(edition);
+
+                if path.join("VC").is_dir() {
+                    res.push(VisualStudioInstallation::from_install_path(
+                        path,
+                        None,
+                        VisualStudioDiscovery::Directory,
+                    ));
+                }
+            }
+        }
+    }
+
+    res
+}
+
+#[cfg(windows)]
+fn vswhere_installation() -> Result<VisualStudioInstallation> {
+    let vswhere_exe = find_vswhere()?;
+
+    let query = |property: &str| -> Result<String> {
+        let cmd = duct::cmd(
+            &vswhere_exe,
+            vec!["-products", "*", "-latest", "-property", property, "-utf8"],
+        )
+        .stdout_capture()
+        .stderr_capture()
+        .run()?;
+
+        Ok(String::from_utf8(cmd.stdout)?.trim().to_string())
+    };
+
+    let install_path = query("installationPath")?;
+    if install_path.is_empty() {
+        return Err(anyhow!("vswhere.exe did not find any installations"));
+    }
+
+    let version = query("installationVersion").ok().filter(|v| !v.is_empty());
+
+    Ok(VisualStudioInstallation::from_install_path(
+        install_path,
+        version,
+        VisualStudioDiscovery::Vswhere,
+    ))
+}
+
+#[cfg(windows)]
+fn registry_installations() -> Vec<VisualStudioInstallation> {
+    // Values are named by version (e.g. `15.0`) and hold the install path.
+    let mut values = registry_string_values(r"SOFTWARE\Microsoft\VisualStudio\SxS\VS7");
+
+    // Prefer newer versions.
+    values.sort_by(|(a, _), (b, _)| {
+        let parse = |v: &str| v.parse::<f64>().unwrap_or(0.0);
+        parse(b)
+            .partial_cmp(&parse(a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    values
+        .into_iter()
+        .map(|(version, path)| {
+            VisualStudioInstallation::from_install_path(
+                path.trim_end_matches('\\'),
+                Some(version),
+                VisualStudioDiscovery::Registry,
+            )
+        })
+        .filter(|installation| installation.install_path.is_dir())
+        .collect()
+}
+
+#[cfg(windows)]
+fn program_files_roots() -> Vec<PathBuf> {
+    [
+        winapi::um::knownfolders::FOLDERID_ProgramFilesX86,
+        winapi::um::knownfolders::FOLDERID_ProgramFiles,
+    ]
+    .iter()
+    .filter_map(|id| get_known_folder_path(id).ok())
+    .collect()
+}
+
+/// Find installations of Visual Studio and Visual Studio Build Tools.
+///
+/// Multiple discovery mechanisms are consulted, in order:
+///
+/// 1. `vswhere.exe`.
+/// 2. The `VSINSTALLDIR` and `VCToolsInstallDir` environment variables.
+/// 3. The `SxS` registry keys.
+/// 4. Well-known installation directories.
+///
+/// Installations are returned in that order, without duplicates. Outside of
+/// Windows, only environment variables are consulted.
+pub fn find_visual_studio_installations() -> Vec<VisualStudioInstallation> {
+    let mut candidates = vec![];
+
+    #[cfg(windows)]
+    {
+        if let Ok(installation) = vswhere_installation() {
+            candidates.push(installation);
+        }
+    }
+
+    if let Some(installation) = installation_from_environment(|key| std::env::var(key).ok()) {
+        candidates.push(installation);
+    }
+
+    #[cfg(windows)]
+    {
+        candidates.extend(registry_installations());
+        candidates.extend(installations_in_directories(&program_files_roots()));
+    }
+
+    let mut res: Vec<VisualStudioInstallation> = vec![];
+
+    for candidate in candidates {
+        if !res
+            .iter()
+            .any(|existing| existing.install_path == candidate.install_path)
+        {
+            res.push(candidate);
+        }
+    }
+
+    res
+}
+
+/// Find the preferred installation of Visual Studio or Visual Studio Build Tools.
+///
+/// See [find_visual_studio_installations] for how installations are found.
+pub fn find_visual_studio_installation() -> Result<VisualStudioInstallation> {
+    find_visual_studio_installations()
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("could not find a Visual Studio installation"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::HashMap, tugger_common::testutil::*};
+
+    #[test]
+    fn test_environment() {
+        let install_path = PathBuf::from("VS").join("2019").join("BuildTools");
+        let tools_path = install_path
+            .join("VC")
+            .join("Tools")
+            .join("MSVC")
+            .join("14.29.30133");
+
+        let mut vars = HashMap::new();
+        assert!(installation_from_environment(|k| vars.get(k).cloned()).is_none());
+
+        vars.insert("VCToolsInstallDir", tools_path.display().to_string());
+        let installation = installation_from_environment(|k| vars.get(k).cloned()).unwrap();
+        assert_eq!(installation.install_path, install_path);
+        assert_eq!(installation.vc_tools_path, Some(tools_path.clone()));
+        assert_eq!(installation.discovery, VisualStudioDiscovery::Environment);
+
+        vars.insert("VSINSTALLDIR", format!("{}\\", install_path.display()));
+        vars.insert("VisualStudioVersion", "16.0".to_string());
+        let installation = installation_from_environment(|k| vars.get(k).cloned()).unwrap();
+        assert_eq!(installation.install_path, install_path);
+        assert_eq!(installation.vc_tools_path, Some(tools_path));
+        assert_eq!(installation.version, Some("16.0".to_string()));
+    }
+
+    #[test]
+    fn test_directories() -> Result<()> {
+        let root = DEFAULT_TEMP_DIR.path().join("visual-studio-directories");
+        let install_path = root
+            .join("Microsoft Visual Studio")
+            .join("2019")
+            .join("BuildTools");
+        let msvc_path = install_path.join("VC").join("Tools").join("MSVC");
+        std::fs::create_dir_all(msvc_path.join("14.9.1"))?;
+        std::fs::create_dir_all(msvc_path.join("14.29.30133"))?;
+
+        let installations = installations_in_directories(&[root]);
+        assert_eq!(installations.len(), 1);
+        assert_eq!(installations[0].install_path, install_path);
+        assert_eq!(
+            installations[0].vc_tools_path,
+            Some(msvc_path.join("14.29.30133"))
+        );
+
+        let build_path = install_path.join("VC").join("Auxiliary").join("Build");
+        std::fs::create_dir_all(&build_path)?;
+        std::fs::write(
+            build_path.join("Microsoft.VCToolsVersion.default.txt"),
+            "14.9.1\r\n",
+        )?;
+        assert_eq!(
+            resolve_vc_tools_path(&install_path),
+            Some(msvc_path.join("14.9.1"))
+        );
+
+        Ok(())
+    }
+}
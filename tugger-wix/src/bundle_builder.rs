@@ -6,13 +6,70 @@ use {
     crate::*,
     anyhow::{anyhow, Context, Result},
     slog::warn,
-    std::{borrow::Cow, collections::BTreeMap, io::Write, ops::Deref, path::Path},
+    std::{
+        borrow::Cow,
+        collections::BTreeMap,
+        fmt::{Display, Formatter},
+        io::Write,
+        ops::Deref,
+        path::Path,
+    },
     tugger_common::http::download_to_path,
     tugger_windows::{VcRedistributablePlatform, VC_REDIST_ARM64, VC_REDIST_X64, VC_REDIST_X86},
     uuid::Uuid,
     xml::{common::XmlVersion, writer::XmlEvent, EmitterConfig, EventWriter},
 };
 
+/// How the Visual C++ Redistributable installer is delivered by a bundle.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VcRedistributableMode {
+    /// The redistributable installer is embedded in the bundle.
+    Embedded,
+
+    /// The redistributable installer is downloaded from Microsoft at install
+    /// time if it is needed.
+    Download,
+}
+
+impl Default for VcRedistributableMode {
+    fn default() -> Self {
+        Self::Embedded
+    }
+}
+
+impl Display for VcRedistributableMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Embedded => "embedded",
+            Self::Download => "download",
+        })
+    }
+}
+
+impl TryFrom<&str> for VcRedistributableMode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self, Self::Error> {
+        match value {
+            "embedded" => Ok(Self::Embedded),
+            "download" => Ok(Self::Download),
+            _ => Err(anyhow!(
+                "{} is not a valid VC++ Redistributable mode; use 'embedded' or 'download'",
+                value
+            )),
+        }
+    }
+}
+
+/// Represents a `<util:RegistrySearch>` storing a value in a bundle variable.
+#[derive(Clone, Debug)]
+struct RegistrySearch {
+    variable: String,
+    key: String,
+    value: String,
+    win64: bool,
+}
+
 /// Entity used to build a WiX bundle installer.
 ///
 /// Bundle installers have multiple components in them.
@@ -35,6 +92,9 @@ pub struct WiXBundleInstallerBuilder<'a> {
     /// Conditions that must be met to perform the install.
     conditions: Vec<(String, String)>,
 
+    /// Registry searches defining variables usable in conditions.
+    registry_searches: Vec<RegistrySearch>,
+
     /// Keys to define in the preprocessor when running candle.
     preprocess_parameters: BTreeMap<String, String>,
 
@@ -74,21 +134,46 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
             .push((message.to_string(), condition.to_string()));
     }
 
+    /// Chain the Visual C++ Redistributable installer for a platform.
+    ///
+    /// The installer is fetched to `download_path`. In
+    /// [VcRedistributableMode::Download] mode, the fetched installer is only used
+    /// to derive metadata and the bundle downloads it at install time.
+    ///
+    /// The installer only runs if the redistributable isn't already installed.
     pub fn add_vc_redistributable<P: AsRef<Path>>(
         &mut self,
         logger: &slog::Logger,
         platform: VcRedistributablePlatform,
+        mode: VcRedistributableMode,
         download_path: P,
     ) -> Result<()> {
-        let (entry, install_condition) = match platform {
-            VcRedistributablePlatform::X86 => (VC_REDIST_X86.deref(), "Not VersionNT64"),
-            VcRedistributablePlatform::X64 => (VC_REDIST_X64.deref(), "VersionNT64"),
+        let (entry, install_condition, win64) = match platform {
+            VcRedistributablePlatform::X86 => (VC_REDIST_X86.deref(), "Not VersionNT64", false),
+            VcRedistributablePlatform::X64 => (VC_REDIST_X64.deref(), "VersionNT64", true),
             VcRedistributablePlatform::Arm64 => {
                 // TODO define proper Arm64 install condition.
-                (VC_REDIST_ARM64.deref(), "VersionNT64 And Not VersionNT64")
+                (
+                    VC_REDIST_ARM64.deref(),
+                    "VersionNT64 And Not VersionNT64",
+                    true,
+                )
             }
         };
 
+        // The redistributable records its installation in the registry. 32-bit
+        // runtimes are recorded in the 32-bit registry view.
+        let detect_variable = format!("VCRedist{}Installed", platform.to_string().to_uppercase());
+        self.registry_searches.push(RegistrySearch {
+            variable: detect_variable.clone(),
+            key: format!(
+                "SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\{}",
+                platform
+            ),
+            value: "Installed".to_string(),
+            win64,
+        });
+
         let url = url::Url::parse(&entry.url)?;
         let filename = url
             .path_segments()
@@ -111,11 +196,24 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
                 id: Some(filename.clone().into()),
                 name: Some(filename.into()),
                 source_file: Some(dest_path.display().to_string().into()),
+                download_url: if mode == VcRedistributableMode::Download {
+                    Some(entry.url.clone().into())
+                } else {
+                    None
+                },
                 cache: Some("no".into()),
-                compressed: Some("yes".into()),
+                compressed: Some(
+                    if mode == VcRedistributableMode::Embedded {
+                        "yes"
+                    } else {
+                        "no"
+                    }
+                    .into(),
+                ),
                 per_machine: Some("yes".into()),
                 permanent: Some("yes".into()),
                 install_condition: Some(install_condition.into()),
+                detect_condition: Some(detect_variable.into()),
                 install_command: Some("/install /quiet /norestart".into()),
                 repair_command: Some("/repair /quiet /norestart".into()),
                 uninstall_command: Some("/uninstall /quiet /norestart".into()),
@@ -230,6 +328,19 @@ impl<'a> WiXBundleInstallerBuilder<'a> {
             writer.write(XmlEvent::end_element())?;
         }
 
+        for search in &self.registry_searches {
+            writer.write(
+                XmlEvent::start_element("util:RegistrySearch")
+                    .attr("Root", "HKLM")
+                    .attr("Key", &search.key)
+                    .attr("Value", &search.value)
+                    .attr("Variable", &search.variable)
+                    .attr("Result", "value")
+                    .attr("Win64", if search.win64 { "yes" } else { "no" }),
+            )?;
+            writer.write(XmlEvent::end_element().name("util:RegistrySearch"))?;
+        }
+
         writer.write(XmlEvent::start_element("Chain"))?;
 
         for element in &self.chain {
@@ -268,22 +379,60 @@ mod tests {
         bundle.add_vc_redistributable(
             &logger,
             VcRedistributablePlatform::X86,
+            VcRedistributableMode::Embedded,
             DEFAULT_DOWNLOAD_DIR.as_path(),
         )?;
         bundle.add_vc_redistributable(
             &logger,
             VcRedistributablePlatform::X64,
+            VcRedistributableMode::Embedded,
             DEFAULT_DOWNLOAD_DIR.as_path(),
         )?;
         bundle.add_vc_redistributable(
             &logger,
             VcRedistributablePlatform::Arm64,
+            VcRedistributableMode::Embedded,
             DEFAULT_DOWNLOAD_DIR.as_path(),
         )?;
 
         Ok(())
     }
 
+    #[test]
+    fn test_vc_redistributable_download_mode() -> Result<()> {
+        let logger = get_logger()?;
+
+        let mut bundle = WiXBundleInstallerBuilder::new(
+            "myapp".to_string(),
+            "0.1".to_string(),
+            "author".to_string(),
+        );
+
+        bundle.add_vc_redistributable(
+            &logger,
+            VcRedistributablePlatform::X64,
+            VcRedistributableMode::Download,
+            DEFAULT_DOWNLOAD_DIR.as_path(),
+        )?;
+
+        let mut emitter = EmitterConfig::new().create_writer(Vec::new());
+        bundle.write_xml(&mut emitter)?;
+        let xml = String::from_utf8(emitter.into_inner())?;
+
+        assert!(xml.contains(&format!("DownloadUrl=\"{}\"", VC_REDIST_X64.url)));
+        assert!(xml.contains("Compressed=\"no\""));
+        assert!(xml.contains("DetectCondition=\"VCRedistX64Installed\""));
+        assert!(xml.contains("<util:RegistrySearch Root=\"HKLM\" Key=\"SOFTWARE\\Microsoft\\VisualStudio\\14.0\\VC\\Runtimes\\x64\""));
+
+        assert_eq!(
+            VcRedistributableMode::try_from("download")?,
+            VcRedistributableMode::Download
+        );
+        assert!(VcRedistributableMode::try_from("web").is_err());
+
+        Ok(())
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_vc_redistributable_build() -> Result<()> {
@@ -299,11 +448,13 @@ mod tests {
         bundle.add_vc_redistributable(
             &logger,
             VcRedistributablePlatform::X86,
+            VcRedistributableMode::Embedded,
             DEFAULT_DOWNLOAD_DIR.as_path(),
         )?;
         bundle.add_vc_redistributable(
             &logger,
             VcRedistributablePlatform::X64,
+            VcRedistributableMode::Embedded,
             DEFAULT_DOWNLOAD_DIR.as_path(),
         )?;
 
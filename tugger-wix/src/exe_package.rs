@@ -53,6 +53,7 @@ pub struct ExePackage<'a> {
     pub id: Option<Cow<'a, str>>,
     pub name: Option<Cow<'a, str>>,
     pub source_file: Option<Cow<'a, str>>,
+    pub download_url: Option<Cow<'a, str>>,
     pub display_name: Option<Cow<'a, str>>,
     pub cache: Option<Cow<'a, str>>,
     pub compressed: Option<Cow<'a, str>>,
@@ -94,6 +95,12 @@ impl<'a> ExePackage<'a> {
             e
         };
 
+        let e = if let Some(value) = &self.download_url {
+            e.attr("DownloadUrl", value)
+        } else {
+            e
+        };
+
         let e = if let Some(value) = &self.display_name {
             e.attr("DisplayName", value)
         } else {
@@ -185,7 +185,8 @@ impl WiXInstallerBuilder {
         self.auto_sign_signtool_settings = Some(settings);
     }
 
-    fn stage_path(&self) -> PathBuf {
+    /// Directory [Self::install_files] are materialized in before building.
+    pub fn stage_path(&self) -> PathBuf {
         self.build_path.join("staged_files")
     }
 
@@ -3,10 +3,13 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::{WiXInstallerBuilder, WxsBuilder},
+    crate::{common::directory_to_id, WiXInstallerBuilder, WxsBuilder},
     anyhow::{anyhow, Result},
+    sha2::Digest,
     std::{
         borrow::Cow,
+        collections::BTreeMap,
+        fmt::{Display, Formatter},
         io::Write,
         path::{Path, PathBuf},
     },
@@ -20,6 +23,246 @@ use {
     },
 };
 
+/// Describes who an installer installs for.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum InstallScope {
+    /// Install for all users of the machine.
+    ///
+    /// Installation requires elevated privileges.
+    #[default]
+    PerMachine,
+
+    /// Install for the current user only.
+    ///
+    /// Files are installed to the per-user program files directory
+    /// (`%LOCALAPPDATA%\Programs`) and installation does not require
+    /// elevated privileges.
+    PerUser,
+}
+
+impl Display for InstallScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::PerMachine => "perMachine",
+            Self::PerUser => "perUser",
+        })
+    }
+}
+
+impl TryFrom<&str> for InstallScope {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self, Self::Error> {
+        match value {
+            "perMachine" => Ok(Self::PerMachine),
+            "perUser" => Ok(Self::PerUser),
+            _ => Err(anyhow!(
+                "{} is not a valid install scope; use 'perMachine' or 'perUser'",
+                value
+            )),
+        }
+    }
+}
+
+/// Describes how to react when a newer version of the product is installed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DowngradePolicy {
+    /// Refuse to install and display an error message.
+    #[default]
+    Disallow,
+
+    /// Remove the newer version and install the older one.
+    Allow,
+}
+
+impl Display for DowngradePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Disallow => "disallow",
+            Self::Allow => "allow",
+        })
+    }
+}
+
+impl TryFrom<&str> for DowngradePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self, Self::Error> {
+        match value {
+            "disallow" => Ok(Self::Disallow),
+            "allow" => Ok(Self::Allow),
+            _ => Err(anyhow!(
+                "{} is not a valid downgrade policy; use 'disallow' or 'allow'",
+                value
+            )),
+        }
+    }
+}
+
+/// A Start Menu shortcut to an installed file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Shortcut {
+    /// Name of the shortcut as displayed in the Start Menu.
+    pub name: String,
+
+    /// Path of the shortcut's target, relative to the installation directory.
+    pub target: PathBuf,
+
+    /// Description of the shortcut.
+    pub description: Option<String>,
+
+    /// Arguments to pass to the target.
+    pub arguments: Option<String>,
+}
+
+impl Shortcut {
+    pub fn new(name: impl ToString, target: impl AsRef<Path>) -> Self {
+        Self {
+            name: name.to_string(),
+            target: target.as_ref().to_path_buf(),
+            description: None,
+            arguments: None,
+        }
+    }
+}
+
+/// Associates a file extension with an installed executable.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileAssociation {
+    /// File extension, without the leading `.`.
+    pub extension: String,
+
+    /// Programmatic identifier to register. e.g. `MyApp.Document`.
+    pub prog_id: String,
+
+    /// Path of the executable to open files with, relative to the installation directory.
+    pub executable: PathBuf,
+
+    /// Description of the file type.
+    pub description: Option<String>,
+}
+
+impl FileAssociation {
+    pub fn new(extension: &str, prog_id: impl ToString, executable: impl AsRef<Path>) -> Self {
+        Self {
+            extension: extension.trim_start_matches('.').to_string(),
+            prog_id: prog_id.to_string(),
+            executable: executable.as_ref().to_path_buf(),
+            description: None,
+        }
+    }
+}
+
+/// Registers an installed executable as the handler of a URL protocol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UrlProtocol {
+    /// URL scheme to handle. e.g. `myapp` to handle `myapp://` URLs.
+    pub scheme: String,
+
+    /// Path of the executable to open URLs with, relative to the installation directory.
+    pub executable: PathBuf,
+
+    /// Description of the protocol.
+    pub description: Option<String>,
+}
+
+impl UrlProtocol {
+    pub fn new(scheme: impl ToString, executable: impl AsRef<Path>) -> Self {
+        Self {
+            scheme: scheme.to_string(),
+            executable: executable.as_ref().to_path_buf(),
+            description: None,
+        }
+    }
+}
+
+/// Describes when a Windows service is started.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ServiceStartType {
+    /// Start the service when the system boots.
+    #[default]
+    Auto,
+
+    /// Start the service when requested.
+    Demand,
+
+    /// The service can't be started.
+    Disabled,
+}
+
+impl Display for ServiceStartType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Demand => "demand",
+            Self::Disabled => "disabled",
+        })
+    }
+}
+
+impl TryFrom<&str> for ServiceStartType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> anyhow::Result<Self, Self::Error> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "demand" => Ok(Self::Demand),
+            "disabled" => Ok(Self::Disabled),
+            _ => Err(anyhow!(
+                "{} is not a valid service start type; use 'auto', 'demand', or 'disabled'",
+                value
+            )),
+        }
+    }
+}
+
+/// Registers an installed executable as a Windows service.
+///
+/// The service is installed and started by the installer and stopped and
+/// removed when the product is uninstalled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WindowsService {
+    /// Name of the service.
+    pub name: String,
+
+    /// Path of the service's executable, relative to the installation directory.
+    pub executable: PathBuf,
+
+    /// Name of the service as displayed in the Services console.
+    pub display_name: Option<String>,
+
+    /// Description of the service.
+    pub description: Option<String>,
+
+    /// Arguments to pass to the executable.
+    pub arguments: Option<String>,
+
+    /// When the service is started.
+    pub start_type: ServiceStartType,
+
+    /// Account to run the service as.
+    ///
+    /// Services run as `LocalSystem` if not set.
+    pub account: Option<String>,
+}
+
+impl WindowsService {
+    pub fn new(name: impl ToString, executable: impl AsRef<Path>) -> Self {
+        Self {
+            name: name.to_string(),
+            executable: executable.as_ref().to_path_buf(),
+            display_name: None,
+            description: None,
+            arguments: None,
+            start_type: ServiceStartType::default(),
+            account: None,
+        }
+    }
+}
+
+const DEFAULT_DOWNGRADE_ERROR_MESSAGE: &str =
+    "A newer version of [ProductName] is already installed. Setup will now exit.";
+
 /// Entity used to emit a simple `.wxs` for building an msi installer.
 ///
 /// Instances are constructed with mandatory fields, such as the
@@ -42,15 +285,31 @@ pub struct WiXSimpleMsiBuilder {
     package_languages: String,
     package_installer_version: String,
 
+    install_scope: InstallScope,
+    downgrade_policy: DowngradePolicy,
+    downgrade_error_message: Option<String>,
+    allow_same_version_upgrades: bool,
+
     /// Files to materialize in `Program Files`.
     program_files_manifest: FileManifest,
 
+    /// Whether to omit the feature adding the install directory to `PATH`.
+    no_path: bool,
+    shortcuts: Vec<Shortcut>,
+    file_associations: Vec<FileAssociation>,
+    url_protocols: Vec<UrlProtocol>,
+    services: Vec<WindowsService>,
+
     upgrade_code: Option<String>,
     package_keywords: Option<String>,
     package_description: Option<String>,
     license_source: Option<PathBuf>,
     product_icon: Option<PathBuf>,
     help_url: Option<String>,
+    about_url: Option<String>,
+    update_url: Option<String>,
+    contact: Option<String>,
+    comments: Option<String>,
     eula_rtf: Option<PathBuf>,
     /// Banner BMP image.
     ///
@@ -62,8 +321,17 @@ pub struct WiXSimpleMsiBuilder {
     /// Dimensions are 493 x 312.
     dialog_bmp: Option<PathBuf>,
 
+    /// Path of executable relative to install root to offer to launch after install.
+    launch_after_install: Option<PathBuf>,
+
+    /// Text of the checkbox to launch the application after install.
+    launch_checkbox_text: Option<String>,
+
     /// Signtool settings to use to auto sign binaries and the installer.
     auto_sign_signtool_settings: Option<SigntoolSign>,
+
+    /// Whether to derive product and package codes instead of having WiX generate them.
+    reproducible: bool,
 }
 
 impl WiXSimpleMsiBuilder {
@@ -100,14 +368,15 @@ impl WiXSimpleMsiBuilder {
     ///
     /// The latest installed version is always used.
     ///
-    /// As this requires `vswhere.exe` and a Visual Studio installation, this function
-    /// will always error outside of Windows.
+    /// Outside of Windows, the DLLs are instead extracted from a downloaded copy of
+    /// the Visual C++ Redistributable installer.
     pub fn add_visual_cpp_redistributable(
         &mut self,
+        logger: &slog::Logger,
         redist_version: &str,
         platform: VcRedistributablePlatform,
     ) -> Result<()> {
-        for path in find_visual_cpp_redistributable(redist_version, platform)? {
+        for path in find_visual_cpp_redistributable(logger, redist_version, platform)? {
             let parent = path
                 .parent()
                 .ok_or_else(|| anyhow!("unable to obtain parent path"))?;
@@ -117,6 +386,40 @@ impl WiXSimpleMsiBuilder {
         Ok(())
     }
 
+    /// Add a shortcut to the Start Menu.
+    ///
+    /// Shortcuts are installed in a Start Menu folder named after the product.
+    pub fn add_shortcut(&mut self, shortcut: Shortcut) {
+        self.shortcuts.push(shortcut);
+    }
+
+    /// Register a file extension to be opened by an installed executable.
+    pub fn add_file_association(&mut self, association: FileAssociation) {
+        self.file_associations.push(association);
+    }
+
+    /// Register an installed executable as the handler of a URL protocol.
+    pub fn add_url_protocol(&mut self, protocol: UrlProtocol) {
+        self.url_protocols.push(protocol);
+    }
+
+    /// Register an installed executable as a Windows service.
+    ///
+    /// The executable must be in the program files manifest when the
+    /// installer is built.
+    pub fn add_service(&mut self, service: WindowsService) {
+        self.services.push(service);
+    }
+
+    /// Set whether the installer offers to add the install directory to `PATH`.
+    ///
+    /// Defaults to true.
+    #[must_use]
+    pub fn add_to_path(mut self, value: bool) -> Self {
+        self.no_path = !value;
+        self
+    }
+
     /// Set the `<Product UpgradeCode` attribute value.
     ///
     /// If not called, a deterministic value will be derived from the product name.
@@ -161,6 +464,95 @@ impl WiXSimpleMsiBuilder {
         self
     }
 
+    /// Set the URL with general information about this application.
+    ///
+    /// This is displayed in the Add/Remove Programs entry.
+    #[must_use]
+    pub fn about_url(mut self, value: String) -> Self {
+        self.about_url = Some(value);
+        self
+    }
+
+    /// Set the URL with update information for this application.
+    ///
+    /// This is displayed in the Add/Remove Programs entry.
+    #[must_use]
+    pub fn update_url(mut self, value: String) -> Self {
+        self.update_url = Some(value);
+        self
+    }
+
+    /// Set the technical support contact for this application.
+    ///
+    /// This is displayed in the Add/Remove Programs entry.
+    #[must_use]
+    pub fn contact(mut self, value: String) -> Self {
+        self.contact = Some(value);
+        self
+    }
+
+    /// Set comments to display in the Add/Remove Programs entry.
+    #[must_use]
+    pub fn comments(mut self, value: String) -> Self {
+        self.comments = Some(value);
+        self
+    }
+
+    /// Set whether the installer installs for all users or the current user.
+    ///
+    /// Defaults to [InstallScope::PerMachine].
+    #[must_use]
+    pub fn install_scope(mut self, scope: InstallScope) -> Self {
+        self.install_scope = scope;
+        self
+    }
+
+    /// Set how to react when a newer version of the product is already installed.
+    ///
+    /// Defaults to [DowngradePolicy::Disallow].
+    #[must_use]
+    pub fn downgrade_policy(mut self, policy: DowngradePolicy) -> Self {
+        self.downgrade_policy = policy;
+        self
+    }
+
+    /// Set the error message displayed when a downgrade is refused.
+    #[must_use]
+    pub fn downgrade_error_message(mut self, value: String) -> Self {
+        self.downgrade_error_message = Some(value);
+        self
+    }
+
+    /// Set whether installing the same version over an existing install is an upgrade.
+    ///
+    /// By default, installing the same version results in 2 entries in
+    /// Add/Remove Programs.
+    #[must_use]
+    pub fn allow_same_version_upgrades(mut self, value: bool) -> Self {
+        self.allow_same_version_upgrades = value;
+        self
+    }
+
+    /// Offer to launch an installed executable when installation finishes.
+    ///
+    /// The path is relative to the installation directory. A checkbox is added
+    /// to the final installer dialog.
+    #[must_use]
+    pub fn launch_after_install<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.launch_after_install = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the text of the checkbox offering to launch the application.
+    ///
+    /// Only used if [Self::launch_after_install] is set. Defaults to
+    /// `Launch <product name>`.
+    #[must_use]
+    pub fn launch_checkbox_text(mut self, value: String) -> Self {
+        self.launch_checkbox_text = Some(value);
+        self
+    }
+
     /// Set the path to an rtf file containing the end user license agreement.
     #[must_use]
     pub fn eula_rtf_path<P: AsRef<Path>>(mut self, path: P) -> Self {
@@ -196,6 +588,19 @@ impl WiXSimpleMsiBuilder {
         self
     }
 
+    /// Set whether the generated `.wxs` is reproducible.
+    ///
+    /// By default, the `<Product Id` and `<Package Id` attributes are `*`, which
+    /// causes WiX to generate random GUIDs each time the installer is built. When
+    /// enabled, these GUIDs are derived from the target architecture and the
+    /// product name and version (product code) or a digest of the package's
+    /// content (package code) instead.
+    #[must_use]
+    pub fn reproducible(mut self, value: bool) -> Self {
+        self.reproducible = value;
+        self
+    }
+
     /// Add this instance to a `WiXInstallerBuilder`.
     pub fn add_to_installer_builder(&self, builder: &mut WiXInstallerBuilder) -> Result<()> {
         let mut emitter_config = EmitterConfig::new();
@@ -206,16 +611,34 @@ impl WiXSimpleMsiBuilder {
         let mut emitter = emitter_config.create_writer(writer);
         self.write_xml(&mut emitter)?;
 
-        builder.add_wxs(WxsBuilder::from_data(
-            Path::new("main.wxs"),
-            emitter.into_inner().into_inner()?,
-        ));
+        let mut wxs =
+            WxsBuilder::from_data(Path::new("main.wxs"), emitter.into_inner().into_inner()?);
+        wxs.set_preprocessor_parameter(
+            "StagedFilesDir".to_string(),
+            builder.stage_path().display().to_string(),
+        );
+        builder.add_wxs(wxs);
+
+        // Service executables are defined by the components registering the
+        // services. So they are staged without generating components for them.
+        let mut manifest = self.program_files_manifest.clone();
+        let mut service_files = FileManifest::default();
+        for service in &self.services {
+            if let Some(entry) = manifest.remove(&service.executable) {
+                service_files.add_file_entry(&service.executable, entry)?;
+            } else if !service_files.has_path(&service.executable) {
+                return Err(anyhow!(
+                    "executable of service {} not in program files: {}",
+                    service.name,
+                    service.executable.display()
+                ));
+            }
+        }
 
-        builder
-            .install_files_mut()
-            .add_manifest(&self.program_files_manifest)?;
+        builder.install_files_mut().add_manifest(&manifest)?;
         builder.set_install_files_root_directory_id("APPLICATIONFOLDER");
         builder.add_files_manifest_wxs()?;
+        builder.install_files_mut().add_manifest(&service_files)?;
 
         if let Some(settings) = &self.auto_sign_signtool_settings {
             builder.auto_sign_signtool(settings.clone_settings());
@@ -251,6 +674,51 @@ impl WiXSimpleMsiBuilder {
 
     /// Write XML describing this builder.
     pub fn write_xml<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<()> {
+        let package_digest = if self.reproducible {
+            Some(self.package_digest()?)
+        } else {
+            None
+        };
+
+        self.write_xml_with_package_digest(writer, package_digest.as_deref())
+    }
+
+    /// Compute a digest of the inputs of the package.
+    ///
+    /// This covers the `.wxs` with random product and package codes and the
+    /// paths, permissions and content of installed files.
+    fn package_digest(&self) -> Result<String> {
+        let mut xml = Vec::new();
+        self.write_xml_with_package_digest(
+            &mut EmitterConfig::new().create_writer(&mut xml),
+            None,
+        )?;
+
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(&xml);
+
+        for (path, entry) in self.program_files_manifest.iter_entries() {
+            let data = entry.resolve_content()?;
+
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update([0, entry.is_executable() as u8]);
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(&data);
+        }
+
+        Ok(hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect())
+    }
+
+    /// Write XML, with reproducible product and package codes if a package digest is given.
+    fn write_xml_with_package_digest<W: Write>(
+        &self,
+        writer: &mut EventWriter<W>,
+        package_digest: Option<&str>,
+    ) -> Result<()> {
         writer.write(XmlEvent::StartDocument {
             version: XmlVersion::Version10,
             encoding: Some("utf-8"),
@@ -286,13 +754,66 @@ impl WiXSimpleMsiBuilder {
             data: None,
         })?;
 
+        if !self.services.is_empty() && self.install_scope == InstallScope::PerUser {
+            return Err(anyhow!(
+                "Windows services require a per-machine install scope"
+            ));
+        }
+
+        // The architecture is only known when WiX runs. So reproducible codes
+        // are defined for every supported architecture.
+        let (product_code, package_code) = if let Some(digest) = package_digest {
+            for (i, arch) in ["x86", "x64", "arm64"].iter().enumerate() {
+                writer.write(XmlEvent::ProcessingInstruction {
+                    name: if i == 0 { "if" } else { "elseif" },
+                    data: Some(&format!("$(sys.BUILDARCH) = {}", arch)),
+                })?;
+                writer.write(XmlEvent::ProcessingInstruction {
+                    name: "define",
+                    data: Some(&format!(
+                        "ProductCode = \"{}\"",
+                        self.component_guid(&format!(
+                            "product_code.{}.{}",
+                            arch, self.product_version
+                        ))
+                    )),
+                })?;
+                writer.write(XmlEvent::ProcessingInstruction {
+                    name: "define",
+                    data: Some(&format!(
+                        "PackageCode = \"{}\"",
+                        self.component_guid(&format!("package_code.{}.{}", arch, digest))
+                    )),
+                })?;
+            }
+            writer.write(XmlEvent::ProcessingInstruction {
+                name: "else",
+                data: None,
+            })?;
+            writer.write(XmlEvent::ProcessingInstruction {
+                name: "error",
+                data: Some("Reproducible installers don't support $(sys.BUILDARCH)"),
+            })?;
+            writer.write(XmlEvent::ProcessingInstruction {
+                name: "endif",
+                data: None,
+            })?;
+
+            (
+                "$(var.ProductCode)".to_string(),
+                "$(var.PackageCode)".to_string(),
+            )
+        } else {
+            ("*".to_string(), "*".to_string())
+        };
+
         writer.write(
             XmlEvent::start_element("Wix").default_ns("http://schemas.microsoft.com/wix/2006/wi"),
         )?;
 
         writer.write(
             XmlEvent::start_element("Product")
-                .attr("Id", "*")
+                .attr("Id", &product_code)
                 .attr("Name", &self.product_name)
                 .attr("Version", &self.product_version)
                 .attr("Manufacturer", &self.product_manufacturer)
@@ -302,15 +823,22 @@ impl WiXSimpleMsiBuilder {
         )?;
 
         let package = XmlEvent::start_element("Package")
-            .attr("Id", "*")
+            .attr("Id", &package_code)
             .attr("Manufacturer", &self.product_manufacturer)
             .attr("InstallerVersion", &self.package_installer_version)
             .attr("Languages", &self.package_languages)
             .attr("Compressed", "yes")
-            .attr("InstallScope", "perMachine")
             .attr("SummaryCodepage", "1252")
             .attr("Platform", "$(sys.BUILDARCH)");
 
+        // Per-user installs use a dual-purpose package with ALLUSERS=2 and
+        // MSIINSTALLPERUSER=1 so program files are redirected to the per-user
+        // location. This is incompatible with InstallScope.
+        let package = match self.install_scope {
+            InstallScope::PerMachine => package.attr("InstallScope", "perMachine"),
+            InstallScope::PerUser => package.attr("InstallPrivileges", "limited"),
+        };
+
         let package = if let Some(keywords) = &self.package_keywords {
             package.attr("Keywords", keywords)
         } else {
@@ -325,14 +853,37 @@ impl WiXSimpleMsiBuilder {
         writer.write(package)?;
         writer.write(XmlEvent::end_element().name("Package"))?;
 
-        writer.write(
-            XmlEvent::start_element("MajorUpgrade")
-                .attr("Schedule", "afterInstallInitialize")
-                .attr(
-                    "DowngradeErrorMessage",
-                    "A newer version of [ProductName] is already installed. Setup will now exit.",
-                ),
-        )?;
+        if self.install_scope == InstallScope::PerUser {
+            for (id, value) in [("ALLUSERS", "2"), ("MSIINSTALLPERUSER", "1")] {
+                writer.write(
+                    XmlEvent::start_element("Property")
+                        .attr("Id", id)
+                        .attr("Value", value),
+                )?;
+                writer.write(XmlEvent::end_element().name("Property"))?;
+            }
+        }
+
+        let major_upgrade =
+            XmlEvent::start_element("MajorUpgrade").attr("Schedule", "afterInstallInitialize");
+
+        let major_upgrade = match self.downgrade_policy {
+            DowngradePolicy::Disallow => major_upgrade.attr(
+                "DowngradeErrorMessage",
+                self.downgrade_error_message
+                    .as_deref()
+                    .unwrap_or(DEFAULT_DOWNGRADE_ERROR_MESSAGE),
+            ),
+            DowngradePolicy::Allow => major_upgrade.attr("AllowDowngrades", "yes"),
+        };
+
+        let major_upgrade = if self.allow_same_version_upgrades {
+            major_upgrade.attr("AllowSameVersionUpgrades", "yes")
+        } else {
+            major_upgrade
+        };
+
+        writer.write(major_upgrade)?;
         writer.write(XmlEvent::end_element().name("MajorUpgrade"))?;
 
         writer.write(
@@ -367,25 +918,38 @@ impl WiXSimpleMsiBuilder {
                 .attr("Name", &self.product_name),
         )?;
 
-        writer.write(
-            XmlEvent::start_element("Component")
-                .attr("Id", "Path")
-                .attr("Guid", &self.path_component_guid())
-                .attr("Win64", "$(var.Win64)")
-                .attr("KeyPath", "yes"),
-        )?;
-        writer.write(
-            XmlEvent::start_element("Environment")
-                .attr("Id", "PATH")
-                .attr("Name", "PATH")
-                .attr("Value", "[APPLICATIONFOLDER]")
-                .attr("Permanent", "no")
-                .attr("Part", "last")
-                .attr("Action", "set")
-                .attr("System", "yes"),
-        )?;
-        writer.write(XmlEvent::end_element().name("Environment"))?;
-        writer.write(XmlEvent::end_element().name("Component"))?;
+        if !self.no_path {
+            writer.write(
+                XmlEvent::start_element("Component")
+                    .attr("Id", "Path")
+                    .attr("Guid", &self.component_guid("path_component"))
+                    .attr("Win64", "$(var.Win64)")
+                    .attr("KeyPath", "yes"),
+            )?;
+            writer.write(
+                XmlEvent::start_element("Environment")
+                    .attr("Id", "PATH")
+                    .attr("Name", "PATH")
+                    .attr("Value", "[APPLICATIONFOLDER]")
+                    .attr("Permanent", "no")
+                    .attr("Part", "last")
+                    .attr("Action", "set")
+                    .attr(
+                        "System",
+                        if self.install_scope == InstallScope::PerUser {
+                            "no"
+                        } else {
+                            "yes"
+                        },
+                    ),
+            )?;
+            writer.write(XmlEvent::end_element().name("Environment"))?;
+            writer.write(XmlEvent::end_element().name("Component"))?;
+        }
+
+        if !self.file_associations.is_empty() || !self.url_protocols.is_empty() {
+            self.write_registrations_component(writer)?;
+        }
 
         if let Some(license_source) = &self.license_source {
             writer.write(
@@ -410,8 +974,15 @@ impl WiXSimpleMsiBuilder {
 
         writer.write(XmlEvent::end_element().name("Directory"))?;
         writer.write(XmlEvent::end_element().name("Directory"))?;
+
+        if !self.shortcuts.is_empty() {
+            self.write_shortcuts_directory(writer)?;
+        }
+
         writer.write(XmlEvent::end_element().name("Directory"))?;
 
+        let service_components = self.write_service_components(writer)?;
+
         writer.write(
             XmlEvent::start_element("Feature")
                 .attr("Id", "MainProgram")
@@ -436,20 +1007,46 @@ impl WiXSimpleMsiBuilder {
             writer.write(XmlEvent::end_element().name("ComponentRef"))?;
         }
 
-        writer.write(
-            XmlEvent::start_element("Feature")
-                .attr("Id", "Environment")
-                .attr("Title", "PATH Environment Variable")
-                .attr(
-                    "Description",
-                    "Add the install location to the PATH system environment variable",
-                )
-                .attr("Level", "1")
-                .attr("Absent", "allow"),
-        )?;
-        writer.write(XmlEvent::start_element("ComponentRef").attr("Id", "Path"))?;
-        writer.write(XmlEvent::end_element().name("ComponentRef"))?;
-        writer.write(XmlEvent::end_element().name("Feature"))?;
+        if !self.file_associations.is_empty() || !self.url_protocols.is_empty() {
+            writer.write(XmlEvent::start_element("ComponentRef").attr("Id", "Registrations"))?;
+            writer.write(XmlEvent::end_element().name("ComponentRef"))?;
+        }
+
+        for id in &service_components {
+            writer.write(XmlEvent::start_element("ComponentRef").attr("Id", id))?;
+            writer.write(XmlEvent::end_element().name("ComponentRef"))?;
+        }
+
+        if !self.shortcuts.is_empty() {
+            writer.write(
+                XmlEvent::start_element("Feature")
+                    .attr("Id", "Shortcuts")
+                    .attr("Title", "Start Menu Shortcuts")
+                    .attr("Description", "Add shortcuts to the Start Menu")
+                    .attr("Level", "1")
+                    .attr("Absent", "allow"),
+            )?;
+            writer.write(XmlEvent::start_element("ComponentRef").attr("Id", "Shortcuts"))?;
+            writer.write(XmlEvent::end_element().name("ComponentRef"))?;
+            writer.write(XmlEvent::end_element().name("Feature"))?;
+        }
+
+        if !self.no_path {
+            writer.write(
+                XmlEvent::start_element("Feature")
+                    .attr("Id", "Environment")
+                    .attr("Title", "PATH Environment Variable")
+                    .attr(
+                        "Description",
+                        "Add the install location to the PATH system environment variable",
+                    )
+                    .attr("Level", "1")
+                    .attr("Absent", "allow"),
+            )?;
+            writer.write(XmlEvent::start_element("ComponentRef").attr("Id", "Path"))?;
+            writer.write(XmlEvent::end_element().name("ComponentRef"))?;
+            writer.write(XmlEvent::end_element().name("Feature"))?;
+        }
 
         writer.write(XmlEvent::end_element().name("Feature"))?;
 
@@ -477,13 +1074,57 @@ impl WiXSimpleMsiBuilder {
             writer.write(XmlEvent::end_element().name("Property"))?;
         }
 
-        if let Some(help_url) = &self.help_url {
+        for (id, value) in [
+            ("ARPHELPLINK", &self.help_url),
+            ("ARPURLINFOABOUT", &self.about_url),
+            ("ARPURLUPDATEINFO", &self.update_url),
+            ("ARPCONTACT", &self.contact),
+            ("ARPCOMMENTS", &self.comments),
+        ] {
+            if let Some(value) = value {
+                writer.write(
+                    XmlEvent::start_element("Property")
+                        .attr("Id", id)
+                        .attr("Value", value),
+                )?;
+                writer.write(XmlEvent::end_element().name("Property"))?;
+            }
+        }
+
+        if let Some(launch_path) = &self.launch_after_install {
+            let checkbox_text = if let Some(text) = &self.launch_checkbox_text {
+                Cow::Borrowed(text.as_str())
+            } else {
+                Cow::Owned(format!("Launch {}", self.product_name))
+            };
+
+            let target = install_path(launch_path);
+
+            for (id, value) in [
+                (
+                    "WIXUI_EXITDIALOGOPTIONALCHECKBOXTEXT",
+                    checkbox_text.as_ref(),
+                ),
+                ("WIXUI_EXITDIALOGOPTIONALCHECKBOX", "1"),
+                ("WixShellExecTarget", target.as_str()),
+            ] {
+                writer.write(
+                    XmlEvent::start_element("Property")
+                        .attr("Id", id)
+                        .attr("Value", value),
+                )?;
+                writer.write(XmlEvent::end_element().name("Property"))?;
+            }
+
+            // WixShellExec is provided by WixUtilExtension.
             writer.write(
-                XmlEvent::start_element("Property")
-                    .attr("Id", "ARPHELPLINK")
-                    .attr("Value", help_url),
+                XmlEvent::start_element("CustomAction")
+                    .attr("Id", "LaunchApplication")
+                    .attr("BinaryKey", "WixCA")
+                    .attr("DllEntry", "WixShellExec")
+                    .attr("Impersonate", "yes"),
             )?;
-            writer.write(XmlEvent::end_element().name("Property"))?;
+            writer.write(XmlEvent::end_element().name("CustomAction"))?;
         }
 
         writer.write(XmlEvent::start_element("UI"))?;
@@ -513,6 +1154,20 @@ impl WiXSimpleMsiBuilder {
             writer.write(XmlEvent::end_element().name("Publish"))?;
         }
 
+        if self.launch_after_install.is_some() {
+            writer.write(
+                XmlEvent::start_element("Publish")
+                    .attr("Dialog", "ExitDialog")
+                    .attr("Control", "Finish")
+                    .attr("Event", "DoAction")
+                    .attr("Value", "LaunchApplication"),
+            )?;
+            writer.write(XmlEvent::Characters(
+                "WIXUI_EXITDIALOGOPTIONALCHECKBOX = 1 and NOT Installed",
+            ))?;
+            writer.write(XmlEvent::end_element().name("Publish"))?;
+        }
+
         writer.write(XmlEvent::end_element().name("UI"))?;
 
         if let Some(eula_path) = &self.eula_rtf {
@@ -562,10 +1217,280 @@ impl WiXSimpleMsiBuilder {
         }
     }
 
-    fn path_component_guid(&self) -> String {
+    /// Write the Start Menu directory containing shortcuts.
+    fn write_shortcuts_directory<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<()> {
+        writer.write(XmlEvent::start_element("Directory").attr("Id", "ProgramMenuFolder"))?;
+        writer.write(
+            XmlEvent::start_element("Directory")
+                .attr("Id", "ApplicationProgramsFolder")
+                .attr("Name", &self.product_name),
+        )?;
+
+        writer.write(
+            XmlEvent::start_element("Component")
+                .attr("Id", "Shortcuts")
+                .attr("Guid", &self.component_guid("shortcuts_component")),
+        )?;
+
+        for (i, shortcut) in self.shortcuts.iter().enumerate() {
+            let id = format!("Shortcut{}", i);
+            let target = install_path(&shortcut.target);
+
+            let element = XmlEvent::start_element("Shortcut")
+                .attr("Id", &id)
+                .attr("Name", &shortcut.name)
+                .attr("Target", &target)
+                .attr("WorkingDirectory", "APPLICATIONFOLDER");
+
+            let element = if let Some(description) = &shortcut.description {
+                element.attr("Description", description)
+            } else {
+                element
+            };
+
+            let element = if let Some(arguments) = &shortcut.arguments {
+                element.attr("Arguments", arguments)
+            } else {
+                element
+            };
+
+            writer.write(element)?;
+            writer.write(XmlEvent::end_element().name("Shortcut"))?;
+        }
+
+        writer.write(
+            XmlEvent::start_element("RemoveFolder")
+                .attr("Id", "RemoveApplicationProgramsFolder")
+                .attr("On", "uninstall"),
+        )?;
+        writer.write(XmlEvent::end_element().name("RemoveFolder"))?;
+
+        // Shortcuts are installed to a per-user location, so the component
+        // key path must be a per-user registry value.
+        writer.write(
+            XmlEvent::start_element("RegistryValue")
+                .attr("Root", "HKCU")
+                .attr("Key", &self.registry_key())
+                .attr("Name", "shortcuts")
+                .attr("Type", "integer")
+                .attr("Value", "1")
+                .attr("KeyPath", "yes"),
+        )?;
+        writer.write(XmlEvent::end_element().name("RegistryValue"))?;
+
+        writer.write(XmlEvent::end_element().name("Component"))?;
+        writer.write(XmlEvent::end_element().name("Directory"))?;
+        writer.write(XmlEvent::end_element().name("Directory"))?;
+
+        Ok(())
+    }
+
+    /// Write the component registering file associations and URL protocols.
+    ///
+    /// Registry values are written to `HKMU`, which resolves to `HKLM` for
+    /// per-machine installs and `HKCU` for per-user installs.
+    fn write_registrations_component<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<()> {
+        writer.write(
+            XmlEvent::start_element("Component")
+                .attr("Id", "Registrations")
+                .attr("Guid", &self.component_guid("registrations_component"))
+                .attr("Win64", "$(var.Win64)"),
+        )?;
+
+        write_registry_value(
+            writer,
+            &self.registry_key(),
+            Some("registrations"),
+            "1",
+            true,
+        )?;
+
+        for association in &self.file_associations {
+            let command = format!("\"{}\" \"%1\"", install_path(&association.executable));
+            let prog_id_key = format!("Software\\Classes\\{}", association.prog_id);
+
+            write_registry_value(
+                writer,
+                &format!("Software\\Classes\\.{}", association.extension),
+                None,
+                &association.prog_id,
+                false,
+            )?;
+            if let Some(description) = &association.description {
+                write_registry_value(writer, &prog_id_key, None, description, false)?;
+            }
+            write_registry_value(
+                writer,
+                &format!("{}\\DefaultIcon", prog_id_key),
+                None,
+                &format!("{},0", install_path(&association.executable)),
+                false,
+            )?;
+            write_registry_value(
+                writer,
+                &format!("{}\\shell\\open\\command", prog_id_key),
+                None,
+                &command,
+                false,
+            )?;
+        }
+
+        for protocol in &self.url_protocols {
+            let command = format!("\"{}\" \"%1\"", install_path(&protocol.executable));
+            let key = format!("Software\\Classes\\{}", protocol.scheme);
+            let description = if let Some(description) = &protocol.description {
+                Cow::Borrowed(description.as_str())
+            } else {
+                Cow::Owned(format!("URL:{} Protocol", protocol.scheme))
+            };
+
+            write_registry_value(writer, &key, None, &description, false)?;
+            write_registry_value(writer, &key, Some("URL Protocol"), "", false)?;
+            write_registry_value(
+                writer,
+                &format!("{}\\shell\\open\\command", key),
+                None,
+                &command,
+                false,
+            )?;
+        }
+
+        writer.write(XmlEvent::end_element().name("Component"))?;
+
+        Ok(())
+    }
+
+    /// Write the components installing service executables and registering services.
+    ///
+    /// Windows Installer installs a service from the component whose key path is
+    /// the service's executable. So a component is written for each executable,
+    /// defining the file. Returns the IDs of the written components.
+    fn write_service_components<W: Write>(
+        &self,
+        writer: &mut EventWriter<W>,
+    ) -> Result<Vec<String>> {
+        let mut executables: BTreeMap<&Path, Vec<&WindowsService>> = BTreeMap::new();
+        for service in &self.services {
+            executables
+                .entry(service.executable.as_path())
+                .or_default()
+                .push(service);
+        }
+
+        let mut ids = vec![];
+
+        for (i, (executable, services)) in executables.into_iter().enumerate() {
+            let id = format!("Service{}", i);
+
+            let directory_id = match executable.parent() {
+                Some(parent) if parent != Path::new("") => directory_to_id(&self.id_prefix, parent),
+                _ => "APPLICATIONFOLDER".to_string(),
+            };
+
+            writer.write(
+                XmlEvent::start_element("Component")
+                    .attr("Id", &id)
+                    .attr(
+                        "Guid",
+                        &self
+                            .component_guid(&format!("service_component.{}", executable.display())),
+                    )
+                    .attr("Directory", &directory_id)
+                    .attr("Win64", "$(var.Win64)"),
+            )?;
+
+            writer.write(
+                XmlEvent::start_element("File")
+                    .attr("Id", &format!("ServiceFile{}", i))
+                    .attr(
+                        "Source",
+                        &format!(
+                            "$(var.StagedFilesDir)\\{}",
+                            executable.display().to_string().replace('/', "\\")
+                        ),
+                    )
+                    .attr("KeyPath", "yes"),
+            )?;
+            writer.write(XmlEvent::end_element().name("File"))?;
+
+            for (j, service) in services.into_iter().enumerate() {
+                let start_type = service.start_type.to_string();
+                let install_id = format!("ServiceInstall{}_{}", i, j);
+                let control_id = format!("ServiceControl{}_{}", i, j);
+
+                let element = XmlEvent::start_element("ServiceInstall")
+                    .attr("Id", &install_id)
+                    .attr("Name", &service.name)
+                    .attr("Type", "ownProcess")
+                    .attr("Start", &start_type)
+                    .attr("ErrorControl", "normal")
+                    .attr("Vital", "yes");
+
+                let element = if let Some(display_name) = &service.display_name {
+                    element.attr("DisplayName", display_name)
+                } else {
+                    element
+                };
+
+                let element = if let Some(description) = &service.description {
+                    element.attr("Description", description)
+                } else {
+                    element
+                };
+
+                let element = if let Some(arguments) = &service.arguments {
+                    element.attr("Arguments", arguments)
+                } else {
+                    element
+                };
+
+                let element = if let Some(account) = &service.account {
+                    element.attr("Account", account)
+                } else {
+                    element
+                };
+
+                writer.write(element)?;
+                writer.write(XmlEvent::end_element().name("ServiceInstall"))?;
+
+                let element = XmlEvent::start_element("ServiceControl")
+                    .attr("Id", &control_id)
+                    .attr("Name", &service.name)
+                    .attr("Stop", "both")
+                    .attr("Remove", "uninstall")
+                    .attr("Wait", "yes");
+
+                let element = if service.start_type == ServiceStartType::Auto {
+                    element.attr("Start", "install")
+                } else {
+                    element
+                };
+
+                writer.write(element)?;
+                writer.write(XmlEvent::end_element().name("ServiceControl"))?;
+            }
+
+            writer.write(XmlEvent::end_element().name("Component"))?;
+
+            ids.push(id);
+        }
+
+        Ok(ids)
+    }
+
+    /// Registry key used to hold key path values for this product.
+    fn registry_key(&self) -> String {
+        format!(
+            "Software\\{}\\{}",
+            self.product_manufacturer, self.product_name
+        )
+    }
+
+    /// Derive a stable component GUID from the product name.
+    fn component_guid(&self, component: &str) -> String {
         Uuid::new_v5(
             &Uuid::NAMESPACE_DNS,
-            format!("tugger.path_component.{}", self.product_name).as_bytes(),
+            format!("tugger.{}.{}", component, self.product_name).as_bytes(),
         )
         .to_hyphenated()
         .encode_upper(&mut Uuid::encode_buffer())
@@ -573,9 +1498,48 @@ impl WiXSimpleMsiBuilder {
     }
 }
 
+/// Resolve a path relative to the installation directory to a WiX formatted string.
+fn install_path(path: &Path) -> String {
+    format!(
+        "[APPLICATIONFOLDER]{}",
+        path.display().to_string().replace('/', "\\")
+    )
+}
+
+fn write_registry_value<W: Write>(
+    writer: &mut EventWriter<W>,
+    key: &str,
+    name: Option<&str>,
+    value: &str,
+    key_path: bool,
+) -> Result<()> {
+    let element = XmlEvent::start_element("RegistryValue")
+        .attr("Root", "HKMU")
+        .attr("Key", key)
+        .attr("Type", if key_path { "integer" } else { "string" })
+        .attr("Value", value);
+
+    let element = if let Some(name) = name {
+        element.attr("Name", name)
+    } else {
+        element
+    };
+
+    let element = if key_path {
+        element.attr("KeyPath", "yes")
+    } else {
+        element
+    };
+
+    writer.write(element)?;
+    writer.write(XmlEvent::end_element().name("RegistryValue"))?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use {super::*, tugger_common::testutil::*};
+    use {super::*, tugger_common::testutil::*, tugger_file_manifest::FileEntry};
 
     #[cfg(target_family = "windows")]
     use tugger_windows_codesign::{
@@ -600,6 +1564,203 @@ mod tests {
         Ok(())
     }
 
+    fn builder_xml(builder: &WiXSimpleMsiBuilder) -> Result<String> {
+        let mut emitter = EmitterConfig::new().create_writer(Vec::new());
+        builder.write_xml(&mut emitter)?;
+
+        Ok(String::from_utf8(emitter.into_inner())?)
+    }
+
+    #[test]
+    fn test_default_scope_and_upgrade() -> Result<()> {
+        let builder = WiXSimpleMsiBuilder::new("prefix", "myapp", "0.1", "author");
+        let xml = builder_xml(&builder)?;
+
+        assert!(xml.contains("InstallScope=\"perMachine\""));
+        assert!(xml.contains("System=\"yes\""));
+        assert!(xml.contains("DowngradeErrorMessage="));
+        assert!(!xml.contains("AllowDowngrades"));
+        assert!(!xml.contains("LaunchApplication"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reproducible() -> Result<()> {
+        let mut builder = WiXSimpleMsiBuilder::new("prefix", "myapp", "0.1", "author");
+        assert_eq!(builder_xml(&builder)?.matches("Id=\"*\"").count(), 2);
+
+        builder = builder.reproducible(true);
+        let xml = builder_xml(&builder)?;
+        assert!(!xml.contains("Id=\"*\""));
+        assert!(xml.contains("Id=\"$(var.ProductCode)\""));
+        assert!(xml.contains("Id=\"$(var.PackageCode)\""));
+        for arch in ["x86", "x64", "arm64"] {
+            assert!(xml.contains(&builder.component_guid(&format!("product_code.{}.0.1", arch))));
+        }
+        assert_eq!(xml, builder_xml(&builder)?);
+
+        // The package code changes with the package content, the product code doesn't.
+        let digest = builder.package_digest()?;
+        builder.add_program_files_manifest(&{
+            let mut manifest = FileManifest::default();
+            manifest.add_file_entry("app.exe", FileEntry::new_from_data(vec![42], true))?;
+            manifest
+        })?;
+        assert_ne!(builder.package_digest()?, digest);
+        assert!(builder_xml(&builder)?.contains(&builder.component_guid("product_code.x64.0.1")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_install_options() -> Result<()> {
+        let builder = WiXSimpleMsiBuilder::new("prefix", "myapp", "0.1", "author")
+            .install_scope(InstallScope::PerUser)
+            .downgrade_policy(DowngradePolicy::Allow)
+            .allow_same_version_upgrades(true)
+            .about_url("https://example.com/".to_string())
+            .contact("support@example.com".to_string())
+            .launch_after_install("bin/myapp.exe");
+        let xml = builder_xml(&builder)?;
+
+        assert!(!xml.contains("InstallScope="));
+        assert!(xml.contains("InstallPrivileges=\"limited\""));
+        assert!(xml.contains("<Property Id=\"MSIINSTALLPERUSER\" Value=\"1\""));
+        assert!(xml.contains("System=\"no\""));
+        assert!(xml.contains("AllowDowngrades=\"yes\""));
+        assert!(xml.contains("AllowSameVersionUpgrades=\"yes\""));
+        assert!(!xml.contains("DowngradeErrorMessage"));
+        assert!(xml.contains("<Property Id=\"ARPURLINFOABOUT\" Value=\"https://example.com/\""));
+        assert!(xml.contains("<Property Id=\"ARPCONTACT\" Value=\"support@example.com\""));
+        assert!(xml.contains("Value=\"Launch myapp\""));
+        assert!(xml.contains("Value=\"[APPLICATIONFOLDER]bin\\myapp.exe\""));
+        assert!(xml.contains("Dialog=\"ExitDialog\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_registrations() -> Result<()> {
+        let mut builder = WiXSimpleMsiBuilder::new("prefix", "myapp", "0.1", "author");
+
+        let mut shortcut = Shortcut::new("My App", "myapp.exe");
+        shortcut.description = Some("Runs My App".to_string());
+        builder.add_shortcut(shortcut);
+        builder.add_file_association(FileAssociation::new(".myd", "MyApp.Document", "myapp.exe"));
+        builder.add_url_protocol(UrlProtocol::new("myapp", "myapp.exe"));
+
+        let builder = builder.add_to_path(false);
+        let xml = builder_xml(&builder)?;
+
+        assert!(!xml.contains("<Environment"));
+        assert!(!xml.contains("Id=\"Environment\""));
+        assert!(xml.contains("<Directory Id=\"ProgramMenuFolder\">"));
+        assert!(xml.contains(
+            "<Shortcut Id=\"Shortcut0\" Name=\"My App\" Target=\"[APPLICATIONFOLDER]myapp.exe\""
+        ));
+        assert!(xml
+            .contains("Key=\"Software\\Classes\\.myd\" Type=\"string\" Value=\"MyApp.Document\""));
+        assert!(xml.contains(
+            "Key=\"Software\\Classes\\myapp\" Type=\"string\" Value=\"\" Name=\"URL Protocol\""
+        ));
+        assert!(xml.contains("Value=\"&quot;[APPLICATIONFOLDER]myapp.exe&quot; &quot;%1&quot;\""));
+        assert!(xml.contains("<ComponentRef Id=\"Registrations\""));
+        assert!(xml.contains("<ComponentRef Id=\"Shortcuts\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_services() -> Result<()> {
+        let mut builder = WiXSimpleMsiBuilder::new("prefix", "myapp", "0.1", "author");
+
+        let mut service = WindowsService::new("MyAppService", "bin/myapp.exe");
+        service.display_name = Some("My App".to_string());
+        service.arguments = Some("--service".to_string());
+        builder.add_service(service);
+
+        let mut service = WindowsService::new("MyAppWorker", "bin/myapp.exe");
+        service.start_type = ServiceStartType::Demand;
+        builder.add_service(service);
+
+        let xml = builder_xml(&builder)?;
+
+        assert!(xml.contains("<Component Id=\"Service0\""));
+        assert!(xml.contains("Directory=\"prefix.dir.bin\""));
+        assert!(xml.contains("Source=\"$(var.StagedFilesDir)\\bin\\myapp.exe\" KeyPath=\"yes\""));
+        assert!(xml.contains(
+            "<ServiceInstall Id=\"ServiceInstall0_0\" Name=\"MyAppService\" Type=\"ownProcess\" Start=\"auto\""
+        ));
+        assert!(xml.contains("DisplayName=\"My App\""));
+        assert!(xml.contains("Arguments=\"--service\""));
+        assert!(xml.contains("Start=\"demand\""));
+        assert!(xml.contains(
+            "<ServiceControl Id=\"ServiceControl0_0\" Name=\"MyAppService\" Stop=\"both\" Remove=\"uninstall\" Wait=\"yes\" Start=\"install\""
+        ));
+        assert!(xml.contains(
+            "<ServiceControl Id=\"ServiceControl0_1\" Name=\"MyAppWorker\" Stop=\"both\" Remove=\"uninstall\" Wait=\"yes\""
+        ));
+        assert_eq!(xml.matches("Start=\"install\"").count(), 1);
+        assert!(xml.contains("<ComponentRef Id=\"Service0\""));
+
+        let builder = builder.install_scope(InstallScope::PerUser);
+        assert!(builder_xml(&builder).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_services_installer_builder() -> Result<()> {
+        let mut builder = WiXSimpleMsiBuilder::new("prefix", "myapp", "0.1", "author");
+        builder.add_service(WindowsService::new("MyAppService", "myapp.exe"));
+
+        assert!(builder
+            .to_installer_builder("x64", DEFAULT_TEMP_DIR.path())
+            .is_err());
+
+        let mut manifest = FileManifest::default();
+        manifest.add_file_entry(
+            "myapp.exe",
+            tugger_file_manifest::FileEntry::new_from_data(vec![42], true),
+        )?;
+        builder.add_program_files_manifest(&manifest)?;
+
+        let installer = builder.to_installer_builder("x64", DEFAULT_TEMP_DIR.path())?;
+        assert!(installer.install_files().has_path("myapp.exe"));
+
+        let files_wxs = String::from_utf8(
+            installer
+                .wxs_files()
+                .get(installer.install_files_wxs_path())
+                .unwrap()
+                .data()
+                .to_vec(),
+        )?;
+        assert!(!files_wxs.contains("myapp.exe"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(
+            InstallScope::try_from("perUser").unwrap(),
+            InstallScope::PerUser
+        );
+        assert!(InstallScope::try_from("user").is_err());
+        assert_eq!(
+            DowngradePolicy::try_from("allow").unwrap(),
+            DowngradePolicy::Allow
+        );
+        assert!(DowngradePolicy::try_from("ignore").is_err());
+        assert_eq!(
+            ServiceStartType::try_from("demand").unwrap(),
+            ServiceStartType::Demand
+        );
+        assert!(ServiceStartType::try_from("manual").is_err());
+    }
+
     #[cfg(target_family = "windows")]
     #[test]
     fn test_simple_msi_builder_build() -> Result<()> {
@@ -648,13 +1809,15 @@ mod tests {
 
     #[test]
     fn test_add_visual_cpp_redistributable() -> Result<()> {
-        if find_visual_cpp_redistributable("14", VcRedistributablePlatform::X64).is_err() {
+        let logger = get_logger()?;
+
+        if find_visual_cpp_redistributable(&logger, "14", VcRedistributablePlatform::X64).is_err() {
             eprintln!("skipping because VC++ redistributable files could not be located");
             return Ok(());
         }
 
         let mut builder = WiXSimpleMsiBuilder::new("prefix", "testapp", "0.1", "author");
-        builder.add_visual_cpp_redistributable("14", VcRedistributablePlatform::X64)?;
+        builder.add_visual_cpp_redistributable(&logger, "14", VcRedistributablePlatform::X64)?;
 
         assert!(builder
             .program_files_manifest
@@ -19,5 +19,8 @@ Rust crate and utility. It just happens to be developed alongside
 PyOxidizer.
 */
 
+pub mod oci;
+pub mod publish;
+pub mod service;
 pub mod starlark;
 pub mod tarball;
@@ -0,0 +1,1100 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Building of OCI container images.
+
+[OciImageBuilder] produces an image consisting of the layers of an optional
+base image plus a layer holding the files of a [FileManifest]. Images are
+written as an [OCI image layout](https://github.com/opencontainers/image-spec/blob/main/image-layout.md)
+directory and/or pushed to a registry implementing the
+[distribution API](https://github.com/opencontainers/distribution-spec).
+
+No container runtime is required. Base images are pulled from their registry
+directly.
+
+Registry credentials are read from the `TUGGER_REGISTRY_USERNAME` and
+`TUGGER_REGISTRY_PASSWORD` environment variables. Registries allowing
+anonymous access don't need them.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    serde::{Deserialize, Serialize},
+    sha2::Digest,
+    slog::warn,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        io::Write,
+        path::{Path, PathBuf},
+    },
+    tugger_file_manifest::FileManifest,
+};
+
+pub const MEDIA_TYPE_OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+pub const MEDIA_TYPE_OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+pub const MEDIA_TYPE_OCI_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+pub const MEDIA_TYPE_OCI_LAYER_GZIP: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+pub const MEDIA_TYPE_DOCKER_MANIFEST_LIST: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+pub const MEDIA_TYPE_DOCKER_MANIFEST: &str = "application/vnd.docker.distribution.manifest.v2+json";
+pub const MEDIA_TYPE_DOCKER_LAYER_GZIP: &str = "application/vnd.docker.image.rootfs.diff.tar.gzip";
+
+/// Environment variable holding the username to authenticate to registries with.
+pub const REGISTRY_USERNAME_ENV: &str = "TUGGER_REGISTRY_USERNAME";
+
+/// Environment variable holding the password to authenticate to registries with.
+pub const REGISTRY_PASSWORD_ENV: &str = "TUGGER_REGISTRY_PASSWORD";
+
+const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+
+/// Compute the `sha256:<hex>` digest of content.
+pub fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(sha2::Sha256::digest(data)))
+}
+
+/// Resolve the OCI architecture of a Rust target triple.
+///
+/// Returns `None` for architectures without an OCI equivalent.
+pub fn target_triple_to_oci_architecture(triple: &str) -> Option<&'static str> {
+    match triple.split('-').next()? {
+        "x86_64" => Some("amd64"),
+        "i686" => Some("386"),
+        "aarch64" => Some("arm64"),
+        "armv7" => Some("arm"),
+        "powerpc64le" => Some("ppc64le"),
+        "s390x" => Some("s390x"),
+        _ => None,
+    }
+}
+
+/// Describes content addressed by its digest.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Descriptor {
+    /// Construct an instance describing content.
+    pub fn new(media_type: impl ToString, data: &[u8]) -> Self {
+        Self {
+            media_type: media_type.to_string(),
+            digest: sha256_digest(data),
+            size: data.len() as u64,
+            platform: None,
+            annotations: BTreeMap::new(),
+        }
+    }
+}
+
+/// The platform an image runs on.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Platform {
+    pub architecture: String,
+    pub os: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+/// An image manifest.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageManifest {
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+}
+
+/// An image index, referencing manifests for multiple platforms.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub manifests: Vec<Descriptor>,
+}
+
+/// A reference to an image in a registry.
+///
+/// e.g. `gcr.io/distroless/cc-debian11:latest` or `python@sha256:...`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageReference {
+    /// Host (and port) of the registry.
+    pub registry: String,
+
+    /// Name of the repository in the registry.
+    pub repository: String,
+
+    /// Tag or digest of the image.
+    pub reference: String,
+}
+
+impl std::str::FromStr for ImageReference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, reference) = if let Some((name, digest)) = s.split_once('@') {
+            (name, digest.to_string())
+        } else {
+            match s.rsplit_once(':') {
+                // A colon after the last slash separates the tag. Otherwise it's a port.
+                Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+                _ => (s, "latest".to_string()),
+            }
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, rest))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), rest.to_string())
+            }
+            Some(_) => (DOCKER_HUB_REGISTRY.to_string(), name.to_string()),
+            None => (DOCKER_HUB_REGISTRY.to_string(), format!("library/{}", name)),
+        };
+
+        let registry = if registry == "docker.io" {
+            DOCKER_HUB_REGISTRY.to_string()
+        } else {
+            registry
+        };
+
+        if repository.is_empty() || reference.is_empty() {
+            return Err(anyhow!("invalid image reference: {}", s));
+        }
+
+        Ok(Self {
+            registry,
+            repository,
+            reference,
+        })
+    }
+}
+
+impl std::fmt::Display for ImageReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = if self.reference.contains(':') {
+            '@'
+        } else {
+            ':'
+        };
+
+        write!(
+            f,
+            "{}/{}{}{}",
+            self.registry, self.repository, separator, self.reference
+        )
+    }
+}
+
+/// Parse the parameters of a `WWW-Authenticate` challenge.
+fn parse_challenge(header: &str) -> Option<(String, BTreeMap<String, String>)> {
+    let (scheme, rest) = header.trim().split_once(' ').unwrap_or((header.trim(), ""));
+
+    let mut params = BTreeMap::new();
+    let mut rest = rest.trim();
+
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let value = value.trim_start();
+
+        let (value, remaining) = if let Some(value) = value.strip_prefix('"') {
+            let end = value.find('"')?;
+            (&value[..end], &value[end + 1..])
+        } else {
+            let end = value.find(',').unwrap_or(value.len());
+            (&value[..end], &value[end..])
+        };
+
+        params.insert(key.trim().to_lowercase(), value.to_string());
+        rest = remaining.trim_start_matches(&[',', ' '][..]);
+    }
+
+    Some((scheme.to_lowercase(), params))
+}
+
+/// A client for a repository in a registry implementing the distribution API.
+pub struct RegistryClient {
+    client: reqwest::blocking::Client,
+    base_url: String,
+    repository: String,
+    credentials: Option<(String, String)>,
+    authorization: Option<String>,
+}
+
+impl RegistryClient {
+    /// Construct an instance for the repository of an image reference.
+    ///
+    /// Credentials are read from environment variables.
+    pub fn new(image: &ImageReference) -> Result<Self> {
+        let credentials = match (
+            std::env::var(REGISTRY_USERNAME_ENV),
+            std::env::var(REGISTRY_PASSWORD_ENV),
+        ) {
+            (Ok(username), Ok(password)) => Some((username, password)),
+            _ => None,
+        };
+
+        // Local registries commonly don't use TLS.
+        let scheme = if image.registry.starts_with("localhost") {
+            "http"
+        } else {
+            "https"
+        };
+
+        let base_url = format!("{}://{}/v2/{}", scheme, image.registry, image.repository);
+        tugger_common::http::ensure_online(&base_url)?;
+
+        Ok(Self {
+            client: tugger_common::http::get_http_client()?,
+            base_url,
+            repository: image.repository.clone(),
+            credentials,
+            authorization: None,
+        })
+    }
+
+    /// Obtain an authorization header value answering a challenge.
+    fn authorize(&self, challenge: &str, push: bool) -> Result<String> {
+        let (scheme, params) = parse_challenge(challenge)
+            .ok_or_else(|| anyhow!("unable to parse authentication challenge: {}", challenge))?;
+
+        match scheme.as_str() {
+            "basic" => {
+                let (username, password) = self.credentials.as_ref().ok_or_else(|| {
+                    anyhow!(
+                        "registry requires credentials; define {} and {}",
+                        REGISTRY_USERNAME_ENV,
+                        REGISTRY_PASSWORD_ENV
+                    )
+                })?;
+
+                Ok(format!(
+                    "Basic {}",
+                    base64::encode(format!("{}:{}", username, password))
+                ))
+            }
+            "bearer" => {
+                let realm = params
+                    .get("realm")
+                    .ok_or_else(|| anyhow!("authentication challenge lacks realm"))?;
+
+                let mut url = url::Url::parse(realm)?;
+                {
+                    let mut query = url.query_pairs_mut();
+                    if let Some(service) = params.get("service") {
+                        query.append_pair("service", service);
+                    }
+                    let actions = if push { "pull,push" } else { "pull" };
+                    query.append_pair(
+                        "scope",
+                        &format!("repository:{}:{}", self.repository, actions),
+                    );
+                }
+
+                let mut request = self.client.get(url);
+                if let Some((username, password)) = &self.credentials {
+                    request = request.basic_auth(username, Some(password));
+                }
+
+                #[derive(Deserialize)]
+                struct TokenResponse {
+                    token: Option<String>,
+                    access_token: Option<String>,
+                }
+
+                let response: TokenResponse = request
+                    .send()
+                    .and_then(|res| res.error_for_status())
+                    .context("obtaining registry token")?
+                    .json()
+                    .context("parsing registry token response")?;
+
+                let token = response
+                    .token
+                    .or(response.access_token)
+                    .ok_or_else(|| anyhow!("registry token response lacks token"))?;
+
+                Ok(format!("Bearer {}", token))
+            }
+            _ => Err(anyhow!("unsupported authentication scheme: {}", scheme)),
+        }
+    }
+
+    /// Send a request, authenticating if the registry demands it.
+    fn send(
+        &mut self,
+        push: bool,
+        build: impl Fn(&reqwest::blocking::Client) -> reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response> {
+        let mut request = build(&self.client);
+        if let Some(authorization) = &self.authorization {
+            request = request.header("Authorization", authorization);
+        }
+
+        let response = request.send()?;
+
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let challenge = response
+            .headers()
+            .get("WWW-Authenticate")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| anyhow!("registry denied access without authentication challenge"))?
+            .to_string();
+
+        let authorization = self.authorize(&challenge, push)?;
+        let response = build(&self.client)
+            .header("Authorization", &authorization)
+            .send()?;
+        self.authorization = Some(authorization);
+
+        Ok(response)
+    }
+
+    /// Fetch a manifest by tag or digest.
+    ///
+    /// Returns the media type and content of the manifest.
+    pub fn get_manifest(&mut self, reference: &str) -> Result<(String, Vec<u8>)> {
+        let url = format!("{}/manifests/{}", self.base_url, reference);
+        let accept = [
+            MEDIA_TYPE_OCI_INDEX,
+            MEDIA_TYPE_OCI_MANIFEST,
+            MEDIA_TYPE_DOCKER_MANIFEST_LIST,
+            MEDIA_TYPE_DOCKER_MANIFEST,
+        ]
+        .join(", ");
+
+        let response = self
+            .send(false, |client| {
+                client.get(&url).header("Accept", accept.as_str())
+            })?
+            .error_for_status()
+            .with_context(|| format!("fetching manifest {}", reference))?;
+
+        let media_type = response
+            .headers()
+            .get("Content-Type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or(MEDIA_TYPE_OCI_MANIFEST)
+            .to_string();
+        let data = response.bytes()?.to_vec();
+
+        if reference.starts_with("sha256:") && sha256_digest(&data) != reference {
+            return Err(anyhow!("digest mismatch of manifest {}", reference));
+        }
+
+        Ok((media_type, data))
+    }
+
+    /// Fetch the image manifest for a platform.
+    ///
+    /// If the reference resolves to an index, the manifest for the platform is selected.
+    pub fn get_image_manifest(
+        &mut self,
+        reference: &str,
+        platform: &Platform,
+    ) -> Result<ImageManifest> {
+        let (media_type, data) = self.get_manifest(reference)?;
+
+        let data = if media_type == MEDIA_TYPE_OCI_INDEX
+            || media_type == MEDIA_TYPE_DOCKER_MANIFEST_LIST
+        {
+            let index: ImageIndex = serde_json::from_slice(&data).context("parsing image index")?;
+
+            let descriptor = index
+                .manifests
+                .iter()
+                .find(|d| {
+                    matches!(&d.platform, Some(p)
+                        if p.architecture == platform.architecture && p.os == platform.os
+                            && (platform.variant.is_none() || p.variant == platform.variant))
+                })
+                .ok_or_else(|| {
+                    anyhow!(
+                        "image {} isn't available for {}/{}",
+                        reference,
+                        platform.os,
+                        platform.architecture
+                    )
+                })?;
+
+            self.get_manifest(&descriptor.digest)?.1
+        } else {
+            data
+        };
+
+        serde_json::from_slice(&data).context("parsing image manifest")
+    }
+
+    /// Fetch a blob, verifying its digest.
+    pub fn get_blob(&mut self, digest: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/blobs/{}", self.base_url, digest);
+
+        let data = self
+            .send(false, |client| client.get(&url))?
+            .error_for_status()
+            .with_context(|| format!("fetching blob {}", digest))?
+            .bytes()?
+            .to_vec();
+
+        if sha256_digest(&data) != digest {
+            return Err(anyhow!("digest mismatch of blob {}", digest));
+        }
+
+        Ok(data)
+    }
+
+    /// Whether the repository has a blob.
+    pub fn has_blob(&mut self, digest: &str) -> Result<bool> {
+        let url = format!("{}/blobs/{}", self.base_url, digest);
+
+        let response = self.send(true, |client| client.head(&url))?;
+
+        Ok(response.status().is_success())
+    }
+
+    /// Upload a blob unless the repository has it already.
+    pub fn push_blob(&mut self, digest: &str, data: &[u8]) -> Result<()> {
+        if self.has_blob(digest)? {
+            return Ok(());
+        }
+
+        let url = format!("{}/blobs/uploads/", self.base_url);
+        let response = self
+            .send(true, |client| client.post(&url))?
+            .error_for_status()
+            .context("starting blob upload")?;
+
+        let location = response
+            .headers()
+            .get("Location")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| anyhow!("blob upload response lacks Location"))?;
+
+        let mut upload_url = url::Url::parse(&url)?.join(location)?;
+        upload_url.query_pairs_mut().append_pair("digest", digest);
+
+        let data = data.to_vec();
+        self.send(true, |client| {
+            client
+                .put(upload_url.clone())
+                .header("Content-Type", "application/octet-stream")
+                .body(data.clone())
+        })?
+        .error_for_status()
+        .with_context(|| format!("uploading blob {}", digest))?;
+
+        Ok(())
+    }
+
+    /// Upload a manifest under a tag or digest.
+    pub fn push_manifest(&mut self, reference: &str, media_type: &str, data: &[u8]) -> Result<()> {
+        let url = format!("{}/manifests/{}", self.base_url, reference);
+
+        let data = data.to_vec();
+        self.send(true, |client| {
+            client
+                .put(&url)
+                .header("Content-Type", media_type)
+                .body(data.clone())
+        })?
+        .error_for_status()
+        .with_context(|| format!("uploading manifest {}", reference))?;
+
+        Ok(())
+    }
+}
+
+/// Create a gzip compressed tar archive of the files in a manifest.
+///
+/// Files are placed relative to the root directory. Archives are reproducible:
+/// entries are sorted and have fixed ownership and modification times.
+///
+/// Returns the compressed archive and the digest of the uncompressed archive.
+pub fn create_layer(files: &FileManifest) -> Result<(Vec<u8>, String)> {
+    let mut builder = tar::Builder::new(vec![]);
+
+    let mtime = tugger_file_manifest::source_date_epoch()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let header = |entry_type: tar::EntryType, mode: u32, size: u64| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(size);
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header
+    };
+
+    let mut directories = BTreeSet::new();
+    for (path, _) in files.iter_entries() {
+        for ancestor in path.ancestors().skip(1) {
+            if !ancestor.as_os_str().is_empty() {
+                directories.insert(ancestor.to_path_buf());
+            }
+        }
+    }
+
+    for directory in &directories {
+        let mut header = header(tar::EntryType::Directory, 0o755, 0);
+        builder.append_data(&mut header, directory, std::io::empty())?;
+    }
+
+    for (path, entry) in files.iter_entries() {
+        if let Some(target) = entry.link_target() {
+            let mut header = header(tar::EntryType::Symlink, 0o777, 0);
+            builder.append_link(&mut header, path, target)?;
+        } else if let Some(target) = entry.hardlink_target() {
+            let mut header = header(tar::EntryType::Link, 0o644, 0);
+            builder.append_link(&mut header, path, target)?;
+        } else {
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", path.display()))?;
+            let mut header = header(tar::EntryType::Regular, entry.mode(), data.len() as u64);
+            builder.append_data(&mut header, path, std::io::Cursor::new(data))?;
+        }
+    }
+
+    let tar_data = builder.into_inner()?;
+    let diff_id = sha256_digest(&tar_data);
+
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&tar_data)?;
+
+    Ok((encoder.finish()?, diff_id))
+}
+
+/// A built image.
+#[derive(Clone, Debug)]
+pub struct OciImage {
+    /// Content of the image manifest.
+    pub manifest: Vec<u8>,
+
+    /// Content of the image configuration.
+    pub config: Vec<u8>,
+
+    /// Content of the layers, keyed by digest.
+    pub layers: BTreeMap<String, Vec<u8>>,
+}
+
+impl OciImage {
+    /// The digest of the image manifest.
+    pub fn digest(&self) -> String {
+        sha256_digest(&self.manifest)
+    }
+
+    /// Write the image as an OCI image layout directory.
+    ///
+    /// `tag` is recorded as the `org.opencontainers.image.ref.name` annotation.
+    /// Existing blobs in the directory are preserved, so multiple images can be
+    /// written to the same directory. The index only references the written image.
+    pub fn write_layout(&self, dest: &Path, tag: Option<&str>) -> Result<()> {
+        let blobs = dest.join("blobs").join("sha256");
+        std::fs::create_dir_all(&blobs).with_context(|| format!("creating {}", blobs.display()))?;
+
+        let write_blob = |data: &[u8]| -> Result<()> {
+            let digest = sha256_digest(data);
+            let path = blobs.join(digest.trim_start_matches("sha256:"));
+            std::fs::write(&path, data).with_context(|| format!("writing {}", path.display()))
+        };
+
+        for data in self.layers.values() {
+            write_blob(data)?;
+        }
+        write_blob(&self.config)?;
+        write_blob(&self.manifest)?;
+
+        let mut descriptor = Descriptor::new(MEDIA_TYPE_OCI_MANIFEST, &self.manifest);
+        if let Some(tag) = tag {
+            descriptor.annotations.insert(
+                "org.opencontainers.image.ref.name".to_string(),
+                tag.to_string(),
+            );
+        }
+
+        let index = ImageIndex {
+            schema_version: 2,
+            media_type: Some(MEDIA_TYPE_OCI_INDEX.to_string()),
+            manifests: vec![descriptor],
+        };
+
+        std::fs::write(
+            dest.join("oci-layout"),
+            serde_json::to_vec(&serde_json::json!({"imageLayoutVersion": "1.0.0"}))?,
+        )?;
+        std::fs::write(dest.join("index.json"), serde_json::to_vec_pretty(&index)?)?;
+
+        Ok(())
+    }
+
+    /// Push the image to a registry.
+    pub fn push(&self, logger: &slog::Logger, reference: &ImageReference) -> Result<()> {
+        let mut client = RegistryClient::new(reference)?;
+
+        for (digest, data) in &self.layers {
+            warn!(logger, "uploading layer {}", digest);
+            client.push_blob(digest, data)?;
+        }
+
+        client.push_blob(&sha256_digest(&self.config), &self.config)?;
+
+        warn!(logger, "uploading manifest to {}", reference);
+        client.push_manifest(
+            &reference.reference,
+            MEDIA_TYPE_OCI_MANIFEST,
+            &self.manifest,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Builds OCI images from files.
+#[derive(Clone, Debug)]
+pub struct OciImageBuilder {
+    /// Image to add files on top of. An empty image is used if not set.
+    pub base: Option<ImageReference>,
+
+    /// Platform of the image.
+    pub platform: Platform,
+
+    /// Files to add, relative to the root directory.
+    pub files: FileManifest,
+
+    /// Command run when a container starts.
+    pub entrypoint: Option<Vec<String>>,
+
+    /// Default arguments of the entrypoint.
+    pub cmd: Option<Vec<String>>,
+
+    /// Environment variables to define, in addition to those of the base image.
+    pub env: BTreeMap<String, String>,
+
+    /// Working directory of the entrypoint.
+    pub working_dir: Option<String>,
+
+    /// User to run the entrypoint as.
+    pub user: Option<String>,
+
+    /// Labels to add to the image.
+    pub labels: BTreeMap<String, String>,
+
+    /// Ports to expose. e.g. `8080/tcp`.
+    pub exposed_ports: Vec<String>,
+}
+
+impl Default for OciImageBuilder {
+    fn default() -> Self {
+        Self {
+            base: None,
+            platform: Platform {
+                architecture: "amd64".to_string(),
+                os: "linux".to_string(),
+                variant: None,
+            },
+            files: FileManifest::default(),
+            entrypoint: None,
+            cmd: None,
+            env: BTreeMap::new(),
+            working_dir: None,
+            user: None,
+            labels: BTreeMap::new(),
+            exposed_ports: vec![],
+        }
+    }
+}
+
+impl OciImageBuilder {
+    /// Resolve the image configuration given the configuration of the base image.
+    ///
+    /// Settings of this builder override or extend those of the base image.
+    pub fn image_config(
+        &self,
+        base: Option<serde_json::Value>,
+        diff_id: &str,
+    ) -> Result<serde_json::Value> {
+        use serde_json::{json, Value};
+
+        let mut config = base.unwrap_or_else(|| {
+            json!({
+                "architecture": self.platform.architecture,
+                "os": self.platform.os,
+                "config": {},
+                "rootfs": {"type": "layers", "diff_ids": []},
+            })
+        });
+
+        if let Some(epoch) = tugger_file_manifest::source_date_epoch() {
+            let created = time::OffsetDateTime::from(epoch)
+                .format(&time::format_description::well_known::Rfc3339)?;
+            config["created"] = created.into();
+        } else if let Some(object) = config.as_object_mut() {
+            // The base image's timestamp would be misleading.
+            object.remove("created");
+        }
+
+        let container = config
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("image configuration isn't an object"))?
+            .entry("config")
+            .or_insert_with(|| json!({}));
+        if container.is_null() {
+            *container = json!({});
+        }
+
+        if let Some(entrypoint) = &self.entrypoint {
+            container["Entrypoint"] = json!(entrypoint);
+            // The base image's arguments are meant for its own entrypoint.
+            container["Cmd"] = Value::Null;
+        }
+        if let Some(cmd) = &self.cmd {
+            container["Cmd"] = json!(cmd);
+        }
+        if let Some(dir) = &self.working_dir {
+            container["WorkingDir"] = json!(dir);
+        }
+        if let Some(user) = &self.user {
+            container["User"] = json!(user);
+        }
+
+        if !self.env.is_empty() {
+            let mut env = container["Env"]
+                .as_array()
+                .map(|v| {
+                    v.iter()
+                        .filter_map(|s| s.as_str())
+                        .filter(|s| {
+                            let key = s.split('=').next().unwrap_or_default();
+                            !self.env.contains_key(key)
+                        })
+                        .map(|s| s.to_string())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            env.extend(self.env.iter().map(|(k, v)| format!("{}={}", k, v)));
+            container["Env"] = json!(env);
+        }
+
+        if !self.labels.is_empty() {
+            if !container["Labels"].is_object() {
+                container["Labels"] = json!({});
+            }
+            for (k, v) in &self.labels {
+                container["Labels"][k] = json!(v);
+            }
+        }
+
+        if !self.exposed_ports.is_empty() {
+            if !container["ExposedPorts"].is_object() {
+                container["ExposedPorts"] = json!({});
+            }
+            for port in &self.exposed_ports {
+                let port = if port.contains('/') {
+                    port.clone()
+                } else {
+                    format!("{}/tcp", port)
+                };
+                container["ExposedPorts"][port] = json!({});
+            }
+        }
+
+        config["rootfs"]["diff_ids"]
+            .as_array_mut()
+            .ok_or_else(|| anyhow!("image configuration lacks rootfs.diff_ids"))?
+            .push(json!(diff_id));
+
+        if let Some(history) = config.get_mut("history").and_then(|v| v.as_array_mut()) {
+            history.push(json!({"created_by": "tugger"}));
+        }
+
+        Ok(config)
+    }
+
+    /// Build the image.
+    ///
+    /// The base image, if any, is pulled from its registry.
+    pub fn build(&self, logger: &slog::Logger) -> Result<OciImage> {
+        let mut layers = BTreeMap::new();
+        let mut descriptors = vec![];
+
+        let base_config = if let Some(base) = &self.base {
+            warn!(logger, "pulling base image {}", base);
+            let mut client = RegistryClient::new(base)?;
+
+            let manifest = client
+                .get_image_manifest(&base.reference, &self.platform)
+                .with_context(|| format!("resolving base image {}", base))?;
+
+            let config = client.get_blob(&manifest.config.digest)?;
+
+            for layer in manifest.layers {
+                warn!(logger, "fetching base image layer {}", layer.digest);
+                let data = client.get_blob(&layer.digest)?;
+
+                let media_type = if layer.media_type == MEDIA_TYPE_DOCKER_LAYER_GZIP {
+                    MEDIA_TYPE_OCI_LAYER_GZIP.to_string()
+                } else {
+                    layer.media_type.clone()
+                };
+
+                layers.insert(layer.digest.clone(), data);
+                descriptors.push(Descriptor {
+                    media_type,
+                    platform: None,
+                    ..layer
+                });
+            }
+
+            Some(serde_json::from_slice(&config).context("parsing base image configuration")?)
+        } else {
+            None
+        };
+
+        let (layer, diff_id) = create_layer(&self.files).context("creating image layer")?;
+        let descriptor = Descriptor::new(MEDIA_TYPE_OCI_LAYER_GZIP, &layer);
+        layers.insert(descriptor.digest.clone(), layer);
+        descriptors.push(descriptor);
+
+        let config = serde_json::to_vec(&self.image_config(base_config, &diff_id)?)?;
+
+        let manifest = ImageManifest {
+            schema_version: 2,
+            media_type: Some(MEDIA_TYPE_OCI_MANIFEST.to_string()),
+            config: Descriptor::new(MEDIA_TYPE_OCI_CONFIG, &config),
+            layers: descriptors,
+        };
+
+        Ok(OciImage {
+            manifest: serde_json::to_vec(&manifest)?,
+            config,
+            layers,
+        })
+    }
+
+    /// Build the image and write it as an OCI image layout directory.
+    ///
+    /// Returns the built image.
+    pub fn write_layout(
+        &self,
+        logger: &slog::Logger,
+        dest: impl Into<PathBuf>,
+        tag: Option<&str>,
+    ) -> Result<OciImage> {
+        let dest = dest.into();
+        let image = self.build(logger)?;
+
+        warn!(logger, "writing OCI image layout to {}", dest.display());
+        image.write_layout(&dest, tag)?;
+
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, tugger_file_manifest::FileEntry};
+
+    #[test]
+    fn test_parse_reference() -> Result<()> {
+        let r: ImageReference = "python".parse()?;
+        assert_eq!(r.registry, DOCKER_HUB_REGISTRY);
+        assert_eq!(r.repository, "library/python");
+        assert_eq!(r.reference, "latest");
+
+        let r: ImageReference = "gcr.io/distroless/cc-debian11:nonroot".parse()?;
+        assert_eq!(r.registry, "gcr.io");
+        assert_eq!(r.repository, "distroless/cc-debian11");
+        assert_eq!(r.reference, "nonroot");
+        assert_eq!(r.to_string(), "gcr.io/distroless/cc-debian11:nonroot");
+
+        let r: ImageReference = "localhost:5000/myapp".parse()?;
+        assert_eq!(r.registry, "localhost:5000");
+        assert_eq!(r.repository, "myapp");
+        assert_eq!(r.reference, "latest");
+
+        let r: ImageReference = "myorg/myapp@sha256:abcd".parse()?;
+        assert_eq!(r.registry, DOCKER_HUB_REGISTRY);
+        assert_eq!(r.repository, "myorg/myapp");
+        assert_eq!(r.reference, "sha256:abcd");
+        assert_eq!(
+            r.to_string(),
+            "registry-1.docker.io/myorg/myapp@sha256:abcd"
+        );
+
+        assert!("myapp:".parse::<ImageReference>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_triple_to_oci_architecture() {
+        assert_eq!(
+            target_triple_to_oci_architecture("x86_64-unknown-linux-musl"),
+            Some("amd64")
+        );
+        assert_eq!(
+            target_triple_to_oci_architecture("aarch64-unknown-linux-gnu"),
+            Some("arm64")
+        );
+        assert_eq!(target_triple_to_oci_architecture("wasm32-wasi"), None);
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/python:pull""#,
+        )
+        .unwrap();
+
+        assert_eq!(scheme, "bearer");
+        assert_eq!(params["realm"], "https://auth.docker.io/token");
+        assert_eq!(params["service"], "registry.docker.io");
+        assert_eq!(params["scope"], "repository:library/python:pull");
+
+        let (scheme, params) = parse_challenge(r#"Basic realm="Registry""#).unwrap();
+        assert_eq!(scheme, "basic");
+        assert_eq!(params["realm"], "Registry");
+    }
+
+    #[test]
+    fn test_create_layer() -> Result<()> {
+        let mut files = FileManifest::default();
+        files.add_file_entry("app/myapp", FileEntry::new_from_data(b"exe".to_vec(), true))?;
+        files.add_file_entry(
+            "app/lib/data.txt",
+            FileEntry::new_from_data(b"data".to_vec(), false),
+        )?;
+
+        let (layer, diff_id) = create_layer(&files)?;
+        let (layer2, diff_id2) = create_layer(&files)?;
+        assert_eq!(layer, layer2);
+        assert_eq!(diff_id, diff_id2);
+
+        let decoder = flate2::read::GzDecoder::new(std::io::Cursor::new(layer));
+        let mut archive = tar::Archive::new(decoder);
+
+        let entries = archive
+            .entries()?
+            .map(|entry| -> Result<(String, u32)> {
+                let entry = entry?;
+                Ok((entry.path()?.display().to_string(), entry.header().mode()?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            entries,
+            vec![
+                ("app".to_string(), 0o755),
+                ("app/lib".to_string(), 0o755),
+                ("app/lib/data.txt".to_string(), 0o644),
+                ("app/myapp".to_string(), 0o755),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_config() -> Result<()> {
+        let builder = OciImageBuilder {
+            entrypoint: Some(vec!["/app/myapp".to_string()]),
+            env: [("PYTHONUNBUFFERED".to_string(), "1".to_string())]
+                .into_iter()
+                .collect(),
+            exposed_ports: vec!["8080".to_string()],
+            ..Default::default()
+        };
+
+        let config = builder.image_config(None, "sha256:00")?;
+        assert_eq!(config["architecture"], "amd64");
+        assert_eq!(config["config"]["Entrypoint"][0], "/app/myapp");
+        assert_eq!(config["config"]["Env"][0], "PYTHONUNBUFFERED=1");
+        assert!(config["config"]["ExposedPorts"]["8080/tcp"].is_object());
+        assert_eq!(config["rootfs"]["diff_ids"][0], "sha256:00");
+
+        let base = serde_json::json!({
+            "architecture": "amd64",
+            "os": "linux",
+            "created": "2021-01-01T00:00:00Z",
+            "config": {
+                "Env": ["PATH=/usr/bin", "PYTHONUNBUFFERED=0"],
+                "Cmd": ["/bin/sh"],
+            },
+            "rootfs": {"type": "layers", "diff_ids": ["sha256:11"]},
+            "history": [{"created_by": "base"}],
+        });
+
+        let config = builder.image_config(Some(base), "sha256:00")?;
+        assert_eq!(
+            config["config"]["Env"],
+            serde_json::json!(["PATH=/usr/bin", "PYTHONUNBUFFERED=1"])
+        );
+        assert!(config["config"]["Cmd"].is_null());
+        assert_eq!(
+            config["rootfs"]["diff_ids"],
+            serde_json::json!(["sha256:11", "sha256:00"])
+        );
+        assert_eq!(config["history"].as_array().unwrap().len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_layout() -> Result<()> {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let temp_dir = tempfile::TempDir::new()?;
+
+        let mut builder = OciImageBuilder::default();
+        builder
+            .files
+            .add_file_entry("myapp", FileEntry::new_from_data(b"exe".to_vec(), true))?;
+        builder.entrypoint = Some(vec!["/myapp".to_string()]);
+
+        let image = builder.write_layout(&logger, temp_dir.path(), Some("1.0"))?;
+
+        let index: ImageIndex =
+            serde_json::from_slice(&std::fs::read(temp_dir.path().join("index.json"))?)?;
+        assert_eq!(index.manifests.len(), 1);
+        assert_eq!(index.manifests[0].digest, image.digest());
+        assert_eq!(
+            index.manifests[0].annotations["org.opencontainers.image.ref.name"],
+            "1.0"
+        );
+
+        let manifest: ImageManifest = serde_json::from_slice(&std::fs::read(
+            temp_dir
+                .path()
+                .join("blobs/sha256")
+                .join(image.digest().trim_start_matches("sha256:")),
+        )?)?;
+        assert_eq!(manifest.layers.len(), 1);
+        assert_eq!(manifest.config.media_type, MEDIA_TYPE_OCI_CONFIG);
+        assert!(temp_dir
+            .path()
+            .join("blobs/sha256")
+            .join(manifest.layers[0].digest.trim_start_matches("sha256:"))
+            .exists());
+        assert!(temp_dir.path().join("oci-layout").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,835 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Publishing of build artifacts.
+
+[S3Upload] uploads files to an Amazon S3 bucket (or a service implementing
+the S3 API) and can invalidate the uploaded paths in a CloudFront
+distribution serving the bucket. [GcsUpload] uploads files to a Google Cloud
+Storage bucket. [GitHubRelease] creates or updates a GitHub release and
+attaches files to it.
+
+Files are described by a [FileManifest]. The key of each uploaded object is
+the path of the file in the manifest, joined to an optional key prefix.
+*/
+
+use {
+    anyhow::{anyhow, Context, Result},
+    rusoto_cloudfront::{
+        CloudFront, CloudFrontClient, CreateInvalidationRequest, InvalidationBatch, Paths,
+    },
+    rusoto_core::Region,
+    rusoto_s3::{PutObjectRequest, S3Client, S3},
+    serde::Deserialize,
+    sha2::Digest,
+    slog::warn,
+    std::{collections::BTreeMap, path::Path, str::FromStr},
+    tugger_file_manifest::{FileEntry, FileManifest},
+};
+
+/// Environment variable holding the OAuth 2.0 access token for Google Cloud Storage.
+pub const GCS_ACCESS_TOKEN_ENV: &str = "GOOGLE_OAUTH_ACCESS_TOKEN";
+
+/// Guess the MIME content type of a file from its extension.
+pub fn guess_content_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_lowercase());
+
+    match extension.as_deref() {
+        Some("css") => "text/css",
+        Some("deb") => "application/vnd.debian.binary-package",
+        Some("dmg") => "application/x-apple-diskimage",
+        Some("exe") | Some("msi") | Some("dll") => "application/octet-stream",
+        Some("gz") | Some("tgz") => "application/gzip",
+        Some("htm") | Some("html") => "text/html",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("msix") | Some("zip") | Some("whl") => "application/zip",
+        Some("png") => "image/png",
+        Some("sha256") | Some("txt") | Some("asc") => "text/plain",
+        Some("svg") => "image/svg+xml",
+        Some("tar") => "application/x-tar",
+        Some("xml") => "application/xml",
+        Some("xz") => "application/x-xz",
+        Some("zst") => "application/zstd",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Add a file on the filesystem to a [FileManifest].
+///
+/// `name` is the path of the file in the manifest. It defaults to the file name
+/// of `path`.
+pub fn add_path_to_manifest(
+    manifest: &mut FileManifest,
+    path: impl AsRef<Path>,
+    name: Option<&str>,
+) -> Result<()> {
+    let path = path.as_ref();
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => path
+            .file_name()
+            .ok_or_else(|| anyhow!("unable to resolve file name of {}", path.display()))?
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    manifest.add_file_entry(
+        name.trim_start_matches('/'),
+        FileEntry::new_from_path(path, false),
+    )?;
+
+    Ok(())
+}
+
+/// An object to upload.
+#[derive(Clone, Debug)]
+pub struct UploadObject {
+    /// Key of the object in the bucket.
+    pub key: String,
+
+    /// Content of the object.
+    pub entry: FileEntry,
+
+    /// MIME type of the object.
+    pub content_type: String,
+}
+
+/// Settings shared by all upload destinations.
+#[derive(Clone, Debug, Default)]
+pub struct UploadSettings {
+    /// Files to upload.
+    pub files: FileManifest,
+
+    /// Prefix of the keys of uploaded objects.
+    ///
+    /// A `/` is inserted between the prefix and file paths if needed.
+    pub prefix: String,
+
+    /// MIME type of all uploaded objects.
+    ///
+    /// If not set, it is guessed from the file extension.
+    pub content_type: Option<String>,
+
+    /// Value of the `Cache-Control` header of uploaded objects.
+    pub cache_control: Option<String>,
+
+    /// Canned ACL applied to uploaded objects. e.g. `public-read`.
+    pub acl: Option<String>,
+}
+
+impl UploadSettings {
+    /// Add a file on the filesystem to be uploaded.
+    ///
+    /// `key` is the path of the object relative to the prefix. It defaults to the
+    /// file name of `path`.
+    pub fn add_path(&mut self, path: impl AsRef<Path>, key: Option<&str>) -> Result<()> {
+        add_path_to_manifest(&mut self.files, path, key)
+    }
+
+    /// Resolve the key of an object from the path of a file in the manifest.
+    pub fn object_key(&self, path: &Path) -> String {
+        let path = path.to_string_lossy().replace('\\', "/");
+
+        if self.prefix.is_empty() {
+            path
+        } else {
+            format!("{}/{}", self.prefix.trim_end_matches('/'), path)
+        }
+    }
+
+    /// Resolve the objects to upload.
+    ///
+    /// Symlinks in the manifest can't be represented in object storage and are rejected.
+    pub fn objects(&self) -> Result<Vec<UploadObject>> {
+        self.files
+            .iter_entries()
+            .map(|(path, entry)| {
+                if entry.is_link() {
+                    return Err(anyhow!(
+                        "cannot upload {}: links are not supported",
+                        path.display()
+                    ));
+                }
+
+                let key = self.object_key(path);
+                let content_type = self
+                    .content_type
+                    .clone()
+                    .unwrap_or_else(|| guess_content_type(&key).to_string());
+
+                Ok(UploadObject {
+                    key,
+                    entry: entry.clone(),
+                    content_type,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Run a future to completion on a new runtime.
+fn block_on<F: std::future::Future>(future: F) -> Result<F::Output> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("creating tokio runtime")?
+        .block_on(future))
+}
+
+/// Uploads files to an Amazon S3 bucket.
+///
+/// Credentials are resolved like the AWS CLI does: from the `AWS_ACCESS_KEY_ID`
+/// and `AWS_SECRET_ACCESS_KEY` environment variables, the shared credentials file
+/// or instance metadata.
+#[derive(Clone, Debug)]
+pub struct S3Upload {
+    /// Name of the bucket to upload to.
+    pub bucket: String,
+
+    /// AWS region of the bucket.
+    ///
+    /// If not set, `AWS_DEFAULT_REGION` or `AWS_REGION` is used, defaulting to
+    /// `us-east-1`.
+    pub region: Option<String>,
+
+    /// URL of a custom S3 compatible endpoint.
+    pub endpoint: Option<String>,
+
+    /// ID of a CloudFront distribution to invalidate uploaded paths in.
+    pub cloudfront_distribution_id: Option<String>,
+
+    /// Paths to invalidate in the CloudFront distribution.
+    ///
+    /// If empty, the paths of all uploaded objects are invalidated.
+    pub invalidation_paths: Vec<String>,
+
+    /// What to upload and how.
+    pub settings: UploadSettings,
+}
+
+impl S3Upload {
+    /// Construct an instance uploading to the given bucket.
+    pub fn new(bucket: impl ToString) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            region: None,
+            endpoint: None,
+            cloudfront_distribution_id: None,
+            invalidation_paths: vec![],
+            settings: UploadSettings::default(),
+        }
+    }
+
+    /// Resolve the [Region] to send requests to.
+    pub fn resolve_region(&self) -> Result<Region> {
+        match (&self.region, &self.endpoint) {
+            (region, Some(endpoint)) => Ok(Region::Custom {
+                name: region.clone().unwrap_or_else(|| "us-east-1".to_string()),
+                endpoint: endpoint.clone(),
+            }),
+            (Some(region), None) => {
+                Region::from_str(region).map_err(|_| anyhow!("unknown AWS region: {}", region))
+            }
+            (None, None) => Ok(Region::default()),
+        }
+    }
+
+    /// Resolve the paths to invalidate in CloudFront given uploaded objects.
+    pub fn resolve_invalidation_paths(&self, objects: &[UploadObject]) -> Vec<String> {
+        if self.invalidation_paths.is_empty() {
+            objects.iter().map(|o| format!("/{}", o.key)).collect()
+        } else {
+            self.invalidation_paths.clone()
+        }
+    }
+
+    /// Upload all files.
+    ///
+    /// Returns the keys of uploaded objects.
+    pub fn upload(&self, logger: &slog::Logger) -> Result<Vec<String>> {
+        let objects = self.settings.objects()?;
+        let region = self.resolve_region()?;
+
+        block_on(async {
+            let client = S3Client::new(region);
+
+            for object in &objects {
+                warn!(logger, "uploading s3://{}/{}", self.bucket, object.key);
+
+                let data = object
+                    .entry
+                    .resolve_content()
+                    .with_context(|| format!("resolving content of {}", object.key))?;
+
+                let req = PutObjectRequest {
+                    bucket: self.bucket.clone(),
+                    key: object.key.clone(),
+                    body: Some(data.into()),
+                    content_type: Some(object.content_type.clone()),
+                    cache_control: self.settings.cache_control.clone(),
+                    acl: self.settings.acl.clone(),
+                    ..Default::default()
+                };
+
+                client
+                    .put_object(req)
+                    .await
+                    .map_err(|e| anyhow!("S3 error: {:?}", e))
+                    .with_context(|| format!("uploading s3://{}/{}", self.bucket, object.key))?;
+            }
+
+            if let Some(distribution_id) = &self.cloudfront_distribution_id {
+                let paths = self.resolve_invalidation_paths(&objects);
+
+                if !paths.is_empty() {
+                    warn!(
+                        logger,
+                        "invalidating {} paths in CloudFront distribution {}",
+                        paths.len(),
+                        distribution_id
+                    );
+
+                    // CloudFront is a global service whose API is served from us-east-1.
+                    let client = CloudFrontClient::new(Region::UsEast1);
+
+                    let req = CreateInvalidationRequest {
+                        distribution_id: distribution_id.clone(),
+                        invalidation_batch: InvalidationBatch {
+                            caller_reference: format!(
+                                "tugger-{}",
+                                time::OffsetDateTime::now_utc().unix_timestamp_nanos()
+                            ),
+                            paths: Paths {
+                                quantity: paths.len() as i64,
+                                items: Some(paths),
+                            },
+                        },
+                    };
+
+                    client
+                        .create_invalidation(req)
+                        .await
+                        .map_err(|e| anyhow!("CloudFront error: {:?}", e))
+                        .with_context(|| {
+                            format!("invalidating CloudFront distribution {}", distribution_id)
+                        })?;
+                }
+            }
+
+            Ok::<_, anyhow::Error>(objects.into_iter().map(|o| o.key).collect())
+        })?
+    }
+}
+
+/// Uploads files to a Google Cloud Storage bucket.
+///
+/// Requests are authenticated with an OAuth 2.0 access token, e.g. as printed
+/// by `gcloud auth print-access-token`.
+#[derive(Clone, Debug)]
+pub struct GcsUpload {
+    /// Name of the bucket to upload to.
+    pub bucket: String,
+
+    /// Name of the environment variable holding the access token.
+    ///
+    /// Defaults to [GCS_ACCESS_TOKEN_ENV].
+    pub access_token_env: String,
+
+    /// What to upload and how.
+    pub settings: UploadSettings,
+}
+
+impl GcsUpload {
+    /// Construct an instance uploading to the given bucket.
+    pub fn new(bucket: impl ToString) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            access_token_env: GCS_ACCESS_TOKEN_ENV.to_string(),
+            settings: UploadSettings::default(),
+        }
+    }
+
+    /// Resolve the URL to upload an object to via the XML API.
+    pub fn object_url(&self, key: &str) -> Result<url::Url> {
+        let mut url = url::Url::parse("https://storage.googleapis.com/")?;
+
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("URL cannot be a base"))?
+            .pop_if_empty()
+            .push(&self.bucket)
+            .extend(key.split('/'));
+
+        Ok(url)
+    }
+
+    /// Upload all files.
+    ///
+    /// Returns the keys of uploaded objects.
+    pub fn upload(&self, logger: &slog::Logger) -> Result<Vec<String>> {
+        let objects = self.settings.objects()?;
+
+        let token = std::env::var(&self.access_token_env).map_err(|_| {
+            anyhow!(
+                "environment variable {} must define a Google Cloud access token",
+                self.access_token_env
+            )
+        })?;
+
+        let client = tugger_common::http::get_http_client()?;
+
+        for object in &objects {
+            warn!(logger, "uploading gs://{}/{}", self.bucket, object.key);
+
+            let url = self.object_url(&object.key)?;
+            tugger_common::http::ensure_online(url.as_str())?;
+
+            let data = object
+                .entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", object.key))?;
+
+            let mut request = client
+                .put(url)
+                .bearer_auth(&token)
+                .header("Content-Type", &object.content_type)
+                .body(data);
+
+            if let Some(value) = &self.settings.cache_control {
+                request = request.header("Cache-Control", value);
+            }
+            if let Some(value) = &self.settings.acl {
+                request = request.header("x-goog-acl", value);
+            }
+
+            request
+                .send()
+                .and_then(|res| res.error_for_status())
+                .with_context(|| format!("uploading gs://{}/{}", self.bucket, object.key))?;
+        }
+
+        Ok(objects.into_iter().map(|o| o.key).collect())
+    }
+}
+
+/// Default base URL of the GitHub REST API.
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Environment variable holding the GitHub token, by default.
+pub const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// Name of the release asset listing SHA-256 digests of other assets.
+pub const CHECKSUMS_ASSET_NAME: &str = "SHA256SUMS";
+
+#[derive(Debug, Deserialize)]
+struct GitHubAsset {
+    id: u64,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubReleaseResponse {
+    id: u64,
+    html_url: String,
+    upload_url: String,
+    #[serde(default)]
+    assets: Vec<GitHubAsset>,
+}
+
+/// Creates or updates a GitHub release and uploads files as its assets.
+///
+/// The release for [Self::tag] is updated if it exists. Otherwise it is created,
+/// along with the tag if it doesn't exist yet. Assets having the name of an
+/// uploaded file are replaced.
+#[derive(Clone, Debug)]
+pub struct GitHubRelease {
+    /// Owner of the repository.
+    pub owner: String,
+
+    /// Name of the repository.
+    pub repo: String,
+
+    /// Name of the tag of the release.
+    pub tag: String,
+
+    /// Title of the release. Defaults to the tag.
+    pub name: Option<String>,
+
+    /// Release notes.
+    pub body: Option<String>,
+
+    /// Commit or branch to create the tag from if it doesn't exist.
+    pub target_commitish: Option<String>,
+
+    /// Whether the release is a draft.
+    pub draft: bool,
+
+    /// Whether the release is a prerelease.
+    pub prerelease: bool,
+
+    /// Whether to upload a [CHECKSUMS_ASSET_NAME] asset with digests of all assets.
+    pub checksums: bool,
+
+    /// Base URL of the GitHub API. Differs for GitHub Enterprise Server.
+    pub api_url: String,
+
+    /// Name of the environment variable holding the token to authenticate with.
+    pub token_env: String,
+
+    /// Files to upload. Assets are named after the file name of their path.
+    pub files: FileManifest,
+}
+
+impl GitHubRelease {
+    /// Construct an instance for a release of a repository.
+    ///
+    /// `repository` is `<owner>/<repo>`.
+    pub fn new(repository: &str, tag: impl ToString) -> Result<Self> {
+        let (owner, repo) = repository
+            .split_once('/')
+            .filter(|(owner, repo)| !owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+            .ok_or_else(|| {
+                anyhow!(
+                    "repository must be of the form <owner>/<repo>; got {}",
+                    repository
+                )
+            })?;
+
+        Ok(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            tag: tag.to_string(),
+            name: None,
+            body: None,
+            target_commitish: None,
+            draft: false,
+            prerelease: false,
+            checksums: true,
+            api_url: GITHUB_API_URL.to_string(),
+            token_env: GITHUB_TOKEN_ENV.to_string(),
+            files: FileManifest::default(),
+        })
+    }
+
+    /// Resolve the assets to upload, keyed by name.
+    ///
+    /// Includes the checksums asset if enabled.
+    pub fn assets(&self) -> Result<BTreeMap<String, Vec<u8>>> {
+        let mut assets = BTreeMap::new();
+
+        for (path, entry) in self.files.iter_entries() {
+            if entry.is_link() {
+                return Err(anyhow!(
+                    "cannot upload {}: links are not supported",
+                    path.display()
+                ));
+            }
+
+            let name = path
+                .file_name()
+                .ok_or_else(|| anyhow!("unable to resolve file name of {}", path.display()))?
+                .to_string_lossy()
+                .to_string();
+
+            if name == CHECKSUMS_ASSET_NAME && self.checksums {
+                return Err(anyhow!(
+                    "{} conflicts with the generated checksums asset",
+                    path.display()
+                ));
+            }
+
+            let data = entry
+                .resolve_content()
+                .with_context(|| format!("resolving content of {}", path.display()))?;
+
+            if assets.insert(name.clone(), data).is_some() {
+                return Err(anyhow!("multiple files would be uploaded as {}", name));
+            }
+        }
+
+        if self.checksums && !assets.is_empty() {
+            let checksums = checksums_file(&assets);
+            assets.insert(CHECKSUMS_ASSET_NAME.to_string(), checksums.into_bytes());
+        }
+
+        Ok(assets)
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::blocking::Client,
+        method: reqwest::Method,
+        url: &str,
+        token: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        client
+            .request(method, url)
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("User-Agent", "tugger")
+    }
+
+    /// Create or update the release and upload assets.
+    ///
+    /// Returns the URL of the release.
+    pub fn publish(&self, logger: &slog::Logger) -> Result<String> {
+        use reqwest::Method;
+
+        let assets = self.assets()?;
+
+        let token = std::env::var(&self.token_env).map_err(|_| {
+            anyhow!(
+                "environment variable {} must define a GitHub token",
+                self.token_env
+            )
+        })?;
+
+        let api_url = self.api_url.trim_end_matches('/');
+        let releases_url = format!("{}/repos/{}/{}/releases", api_url, self.owner, self.repo);
+        tugger_common::http::ensure_online(&releases_url)?;
+
+        let client = tugger_common::http::get_http_client()?;
+
+        let existing = self
+            .request(
+                &client,
+                Method::GET,
+                &format!("{}/tags/{}", releases_url, self.tag),
+                &token,
+            )
+            .send()
+            .with_context(|| format!("looking up release {}", self.tag))?;
+
+        let mut payload = serde_json::json!({
+            "tag_name": self.tag,
+            "name": self.name.as_deref().unwrap_or(&self.tag),
+            "draft": self.draft,
+            "prerelease": self.prerelease,
+        });
+        if let Some(body) = &self.body {
+            payload["body"] = body.as_str().into();
+        }
+        if let Some(target) = &self.target_commitish {
+            payload["target_commitish"] = target.as_str().into();
+        }
+
+        let request = if existing.status() == reqwest::StatusCode::NOT_FOUND {
+            warn!(logger, "creating GitHub release {}", self.tag);
+            self.request(&client, Method::POST, &releases_url, &token)
+        } else {
+            let release: GitHubReleaseResponse = existing
+                .error_for_status()
+                .with_context(|| format!("looking up release {}", self.tag))?
+                .json()?;
+
+            warn!(logger, "updating GitHub release {}", self.tag);
+            self.request(
+                &client,
+                Method::PATCH,
+                &format!("{}/{}", releases_url, release.id),
+                &token,
+            )
+        };
+
+        let release: GitHubReleaseResponse = request
+            .json(&payload)
+            .send()
+            .and_then(|res| res.error_for_status())
+            .with_context(|| format!("saving release {}", self.tag))?
+            .json()?;
+
+        // upload_url is a URI template like `.../assets{?name,label}`.
+        let upload_url = release
+            .upload_url
+            .split('{')
+            .next()
+            .unwrap_or(&release.upload_url)
+            .to_string();
+
+        for (name, data) in assets {
+            if let Some(asset) = release.assets.iter().find(|a| a.name == name) {
+                warn!(logger, "deleting existing release asset {}", name);
+                self.request(
+                    &client,
+                    Method::DELETE,
+                    &format!("{}/assets/{}", releases_url, asset.id),
+                    &token,
+                )
+                .send()
+                .and_then(|res| res.error_for_status())
+                .with_context(|| format!("deleting release asset {}", name))?;
+            }
+
+            warn!(logger, "uploading release asset {}", name);
+            let mut url = url::Url::parse(&upload_url)?;
+            url.query_pairs_mut().append_pair("name", &name);
+
+            self.request(&client, Method::POST, url.as_str(), &token)
+                .header("Content-Type", guess_content_type(&name))
+                .body(data)
+                .send()
+                .and_then(|res| res.error_for_status())
+                .with_context(|| format!("uploading release asset {}", name))?;
+        }
+
+        Ok(release.html_url)
+    }
+}
+
+/// Render a file listing SHA-256 digests in the format of `sha256sum`.
+pub fn checksums_file(assets: &BTreeMap<String, Vec<u8>>) -> String {
+    assets
+        .iter()
+        .map(|(name, data)| format!("{}  {}\n", hex::encode(sha2::Sha256::digest(data)), name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_content_type() {
+        assert_eq!(
+            guess_content_type("dist/app-1.0.msi"),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            guess_content_type("dist/app-1.0.tar.GZ"),
+            "application/gzip"
+        );
+        assert_eq!(guess_content_type("index.html"), "text/html");
+        assert_eq!(
+            guess_content_type("v1.0/README"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_objects() -> Result<()> {
+        let mut settings = UploadSettings {
+            prefix: "releases/1.0/".to_string(),
+            cache_control: Some("max-age=300".to_string()),
+            ..Default::default()
+        };
+
+        settings
+            .files
+            .add_file_entry("app.zip", FileEntry::new_from_data(b"zip".to_vec(), false))?;
+        settings.files.add_file_entry(
+            "docs/index.html",
+            FileEntry::new_from_data(b"<html/>".to_vec(), false),
+        )?;
+
+        let objects = settings.objects()?;
+        assert_eq!(
+            objects
+                .iter()
+                .map(|o| (o.key.as_str(), o.content_type.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("releases/1.0/app.zip", "application/zip"),
+                ("releases/1.0/docs/index.html", "text/html"),
+            ]
+        );
+
+        settings.prefix = "".to_string();
+        settings.content_type = Some("application/x-custom".to_string());
+        let objects = settings.objects()?;
+        assert_eq!(objects[0].key, "app.zip");
+        assert_eq!(objects[0].content_type, "application/x-custom");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_s3_region_and_invalidation() -> Result<()> {
+        let mut upload = S3Upload::new("bucket");
+        upload.region = Some("eu-west-1".to_string());
+        assert_eq!(upload.resolve_region()?, Region::EuWest1);
+
+        upload.endpoint = Some("https://minio.example.com".to_string());
+        assert_eq!(
+            upload.resolve_region()?,
+            Region::Custom {
+                name: "eu-west-1".to_string(),
+                endpoint: "https://minio.example.com".to_string()
+            }
+        );
+
+        upload.endpoint = None;
+        upload.region = Some("nowhere-1".to_string());
+        assert!(upload.resolve_region().is_err());
+
+        upload
+            .settings
+            .files
+            .add_file_entry("a/b.txt", FileEntry::new_from_data(b"b".to_vec(), false))?;
+        let objects = upload.settings.objects()?;
+        assert_eq!(
+            upload.resolve_invalidation_paths(&objects),
+            vec!["/a/b.txt"]
+        );
+
+        upload.invalidation_paths = vec!["/*".to_string()];
+        assert_eq!(upload.resolve_invalidation_paths(&objects), vec!["/*"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gcs_object_url() -> Result<()> {
+        let upload = GcsUpload::new("my-bucket");
+
+        assert_eq!(
+            upload.object_url("releases/app 1.0.zip")?.as_str(),
+            "https://storage.googleapis.com/my-bucket/releases/app%201.0.zip"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_github_release() -> Result<()> {
+        assert!(GitHubRelease::new("indygreg", "v1.0").is_err());
+        assert!(GitHubRelease::new("indygreg/PyOxidizer/x", "v1.0").is_err());
+
+        let mut release = GitHubRelease::new("indygreg/PyOxidizer", "v1.0")?;
+        assert_eq!(release.owner, "indygreg");
+        assert_eq!(release.repo, "PyOxidizer");
+        assert!(release.assets()?.is_empty());
+
+        release.files.add_file_entry(
+            "dist/hello.txt",
+            FileEntry::new_from_data(b"hello".to_vec(), false),
+        )?;
+
+        let assets = release.assets()?;
+        assert_eq!(
+            assets.keys().collect::<Vec<_>>(),
+            vec!["SHA256SUMS", "hello.txt"]
+        );
+        assert_eq!(
+            String::from_utf8(assets["SHA256SUMS"].clone())?,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  hello.txt\n"
+        );
+
+        release.checksums = false;
+        assert_eq!(release.assets()?.len(), 1);
+
+        release.files.add_file_entry(
+            "other/hello.txt",
+            FileEntry::new_from_data(b"other".to_vec(), false),
+        )?;
+        assert!(release.assets().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*!
+Definitions of system services running installed applications.
+
+Services are registered with the service manager of the operating system
+when the package installing them is installed. Linux packages (deb and RPM)
+and macOS installer packages do this by running scripts at install and
+uninstall time. This module defines services and renders the files and
+scripts to register them.
+*/
+
+use {
+    anyhow::Result,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Quote an argument of a systemd command line.
+fn systemd_quote(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// A systemd service unit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SystemdUnit {
+    /// Name of the unit, without the `.service` suffix.
+    pub name: String,
+
+    /// Absolute path of the executable to run.
+    pub executable: PathBuf,
+
+    /// Arguments to pass to the executable.
+    pub arguments: Vec<String>,
+
+    /// Description of the service.
+    pub description: Option<String>,
+
+    /// User to run the service as.
+    pub user: Option<String>,
+
+    /// Group to run the service as.
+    pub group: Option<String>,
+
+    /// Working directory of the service.
+    pub working_directory: Option<PathBuf>,
+
+    /// Environment variables to define.
+    pub environment: BTreeMap<String, String>,
+
+    /// When to restart the service. e.g. `on-failure` or `always`.
+    pub restart: String,
+
+    /// Units to start after.
+    pub after: Vec<String>,
+
+    /// Target wanting the service when it is enabled.
+    pub wanted_by: String,
+}
+
+impl SystemdUnit {
+    pub fn new(name: impl ToString, executable: impl AsRef<Path>) -> Self {
+        Self {
+            name: name.to_string(),
+            executable: executable.as_ref().to_path_buf(),
+            arguments: vec![],
+            description: None,
+            user: None,
+            group: None,
+            working_directory: None,
+            environment: BTreeMap::new(),
+            restart: "on-failure".to_string(),
+            after: vec!["network.target".to_string()],
+            wanted_by: "multi-user.target".to_string(),
+        }
+    }
+
+    /// Filename of the unit file.
+    pub fn unit_filename(&self) -> String {
+        format!("{}.service", self.name)
+    }
+
+    /// Path the unit file is installed at, relative to the root directory.
+    pub fn install_path(&self) -> PathBuf {
+        PathBuf::from("lib/systemd/system").join(self.unit_filename())
+    }
+
+    /// Render the content of the unit file.
+    pub fn to_unit_file(&self) -> String {
+        let mut lines = vec!["[Unit]".to_string()];
+
+        if let Some(description) = &self.description {
+            lines.push(format!("Description={}", description));
+        }
+        if !self.after.is_empty() {
+            lines.push(format!("After={}", self.after.join(" ")));
+        }
+
+        lines.push(String::new());
+        lines.push("[Service]".to_string());
+        lines.push("Type=simple".to_string());
+        lines.push(format!(
+            "ExecStart={}",
+            std::iter::once(self.executable.display().to_string())
+                .chain(self.arguments.iter().cloned())
+                .map(|x| systemd_quote(&x))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ));
+
+        if let Some(path) = &self.working_directory {
+            lines.push(format!("WorkingDirectory={}", path.display()));
+        }
+        if let Some(user) = &self.user {
+            lines.push(format!("User={}", user));
+        }
+        if let Some(group) = &self.group {
+            lines.push(format!("Group={}", group));
+        }
+        for (key, value) in &self.environment {
+            lines.push(format!(
+                "Environment={}",
+                systemd_quote(&format!("{}={}", key, value))
+            ));
+        }
+        lines.push(format!("Restart={}", self.restart));
+
+        lines.push(String::new());
+        lines.push("[Install]".to_string());
+        lines.push(format!("WantedBy={}", self.wanted_by));
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Script enabling and (re)starting the service after installation.
+    ///
+    /// Suitable as a Debian `postinst` script and an RPM `%post` scriptlet.
+    pub fn install_script(&self) -> String {
+        format!(
+            "#!/bin/sh\n\
+             set -e\n\
+             \n\
+             if [ -d /run/systemd/system ]; then\n\
+             \x20   systemctl daemon-reload\n\
+             \x20   systemctl enable {unit}\n\
+             \x20   systemctl restart {unit}\n\
+             fi\n",
+            unit = self.unit_filename()
+        )
+    }
+
+    /// Script stopping and disabling the service before removal.
+    ///
+    /// Suitable as a Debian `prerm` script and an RPM `%preun` scriptlet.
+    /// The service is left alone when the package is upgraded.
+    pub fn uninstall_script(&self) -> String {
+        format!(
+            "#!/bin/sh\n\
+             set -e\n\
+             \n\
+             case \"$1\" in\n\
+             \x20   remove|0)\n\
+             \x20       if [ -d /run/systemd/system ]; then\n\
+             \x20           systemctl disable --now {unit} || true\n\
+             \x20       fi\n\
+             \x20       ;;\n\
+             esac\n",
+            unit = self.unit_filename()
+        )
+    }
+}
+
+/// A launchd job defined by a property list.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LaunchdPlist {
+    /// Label uniquely identifying the job. e.g. `com.example.myapp`.
+    pub label: String,
+
+    /// Absolute path of the executable to run.
+    pub program: PathBuf,
+
+    /// Arguments to pass to the executable.
+    pub arguments: Vec<String>,
+
+    /// Whether the job is a per-user agent instead of a system daemon.
+    pub user_agent: bool,
+
+    /// Whether to start the job when it is loaded.
+    pub run_at_load: bool,
+
+    /// Whether to keep the job running, restarting it when it exits.
+    pub keep_alive: bool,
+
+    /// User to run the job as.
+    pub user_name: Option<String>,
+
+    /// Working directory of the job.
+    pub working_directory: Option<PathBuf>,
+
+    /// Environment variables to define.
+    pub environment: BTreeMap<String, String>,
+
+    /// File to write the standard output of the job to.
+    pub standard_out_path: Option<PathBuf>,
+
+    /// File to write the standard error of the job to.
+    pub standard_error_path: Option<PathBuf>,
+}
+
+impl LaunchdPlist {
+    pub fn new(label: impl ToString, program: impl AsRef<Path>) -> Self {
+        Self {
+            label: label.to_string(),
+            program: program.as_ref().to_path_buf(),
+            arguments: vec![],
+            user_agent: false,
+            run_at_load: true,
+            keep_alive: false,
+            user_name: None,
+            working_directory: None,
+            environment: BTreeMap::new(),
+            standard_out_path: None,
+            standard_error_path: None,
+        }
+    }
+
+    /// Filename of the property list.
+    pub fn plist_filename(&self) -> String {
+        format!("{}.plist", self.label)
+    }
+
+    /// Path the property list is installed at, relative to the root directory.
+    pub fn install_path(&self) -> PathBuf {
+        PathBuf::from(if self.user_agent {
+            "Library/LaunchAgents"
+        } else {
+            "Library/LaunchDaemons"
+        })
+        .join(self.plist_filename())
+    }
+
+    /// Render the content of the property list.
+    pub fn to_plist_xml(&self) -> Result<Vec<u8>> {
+        let mut dict = plist::Dictionary::new();
+
+        dict.insert("Label".to_string(), self.label.clone().into());
+        dict.insert(
+            "ProgramArguments".to_string(),
+            plist::Value::Array(
+                std::iter::once(self.program.display().to_string())
+                    .chain(self.arguments.iter().cloned())
+                    .map(plist::Value::from)
+                    .collect(),
+            ),
+        );
+        dict.insert("RunAtLoad".to_string(), self.run_at_load.into());
+        dict.insert("KeepAlive".to_string(), self.keep_alive.into());
+
+        if let Some(user_name) = &self.user_name {
+            dict.insert("UserName".to_string(), user_name.clone().into());
+        }
+        if let Some(path) = &self.working_directory {
+            dict.insert(
+                "WorkingDirectory".to_string(),
+                path.display().to_string().into(),
+            );
+        }
+        if !self.environment.is_empty() {
+            dict.insert(
+                "EnvironmentVariables".to_string(),
+                plist::Value::Dictionary(
+                    self.environment
+                        .iter()
+                        .map(|(k, v)| (k.clone(), plist::Value::from(v.clone())))
+                        .collect(),
+                ),
+            );
+        }
+        if let Some(path) = &self.standard_out_path {
+            dict.insert(
+                "StandardOutPath".to_string(),
+                path.display().to_string().into(),
+            );
+        }
+        if let Some(path) = &self.standard_error_path {
+            dict.insert(
+                "StandardErrorPath".to_string(),
+                path.display().to_string().into(),
+            );
+        }
+
+        let mut data = vec![];
+        plist::Value::from(dict).to_writer_xml(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// The launchd domain the job is loaded in, as a shell expression.
+    fn domain(&self) -> &'static str {
+        if self.user_agent {
+            "gui/$(stat -f %u /dev/console)"
+        } else {
+            "system"
+        }
+    }
+
+    /// Script loading the job after installation.
+    ///
+    /// Suitable as the `postinstall` script of a macOS installer package.
+    /// Agents are loaded for the user logged in at the console.
+    pub fn install_script(&self) -> String {
+        format!(
+            "#!/bin/sh\n\
+             \n\
+             launchctl bootout {domain}/{label} 2>/dev/null || true\n\
+             launchctl bootstrap {domain} \"/{path}\"\n",
+            domain = self.domain(),
+            label = self.label,
+            path = self.install_path().display()
+        )
+    }
+
+    /// Script unloading the job and removing its property list.
+    pub fn uninstall_script(&self) -> String {
+        format!(
+            "#!/bin/sh\n\
+             \n\
+             launchctl bootout {domain}/{label} 2>/dev/null || true\n\
+             rm -f \"/{path}\"\n",
+            domain = self.domain(),
+            label = self.label,
+            path = self.install_path().display()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systemd_unit() {
+        let mut unit = SystemdUnit::new("myapp", "/opt/myapp/myapp");
+        unit.description = Some("My App".to_string());
+        unit.arguments = vec!["--serve".to_string(), "two words".to_string()];
+        unit.user = Some("myapp".to_string());
+        unit.environment
+            .insert("MYAPP_HOME".to_string(), "/var/lib/myapp".to_string());
+
+        assert_eq!(
+            unit.install_path(),
+            Path::new("lib/systemd/system/myapp.service")
+        );
+        assert_eq!(
+            unit.to_unit_file(),
+            "[Unit]\n\
+             Description=My App\n\
+             After=network.target\n\
+             \n\
+             [Service]\n\
+             Type=simple\n\
+             ExecStart=/opt/myapp/myapp --serve \"two words\"\n\
+             User=myapp\n\
+             Environment=MYAPP_HOME=/var/lib/myapp\n\
+             Restart=on-failure\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n"
+        );
+
+        assert!(unit
+            .install_script()
+            .contains("    systemctl enable myapp.service\n"));
+        assert!(unit
+            .uninstall_script()
+            .contains("        systemctl disable --now myapp.service || true\n"));
+    }
+
+    #[test]
+    fn test_launchd_plist() -> Result<()> {
+        let mut job = LaunchdPlist::new("com.example.myapp", "/Applications/MyApp/myapp");
+        job.arguments = vec!["--serve".to_string()];
+        job.keep_alive = true;
+
+        assert_eq!(
+            job.install_path(),
+            Path::new("Library/LaunchDaemons/com.example.myapp.plist")
+        );
+
+        let value = plist::Value::from_reader_xml(std::io::Cursor::new(job.to_plist_xml()?))?;
+        let dict = value.as_dictionary().unwrap();
+        assert_eq!(
+            dict.get("Label").and_then(|x| x.as_string()),
+            Some("com.example.myapp")
+        );
+        assert_eq!(
+            dict.get("ProgramArguments")
+                .and_then(|x| x.as_array())
+                .map(|x| x.iter().filter_map(|x| x.as_string()).collect::<Vec<_>>()),
+            Some(vec!["/Applications/MyApp/myapp", "--serve"])
+        );
+        assert_eq!(
+            dict.get("KeepAlive").and_then(|x| x.as_boolean()),
+            Some(true)
+        );
+        assert!(dict.get("UserName").is_none());
+
+        assert!(job.install_script().contains(
+            "launchctl bootstrap system \"/Library/LaunchDaemons/com.example.myapp.plist\"\n"
+        ));
+
+        job.user_agent = true;
+        assert!(job
+            .uninstall_script()
+            .contains("launchctl bootout gui/$(stat -f %u /dev/console)/com.example.myapp"));
+
+        Ok(())
+    }
+}
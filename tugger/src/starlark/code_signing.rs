@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::starlark::{get_context_value, TuggerContextValue},
+    crate::starlark::{ensure_no_secrets_in_manifest, get_context_value, TuggerContextValue},
     anyhow::{anyhow, Context, Result},
     linked_hash_map::LinkedHashMap,
     slog::{debug, info, warn},
@@ -20,15 +20,15 @@ use {
             starlark_signature_extraction, starlark_signatures,
         },
     },
-    starlark_dialect_build_targets::required_type_arg,
+    starlark_dialect_build_targets::{optional_str_arg, required_type_arg},
     std::{
         fmt::{Display, Formatter},
         path::{Path, PathBuf},
         sync::{Arc, Mutex},
     },
     tugger_code_signing::{
-        SignableCandidate, SignedOutput, Signer, SigningCertificate, SigningDestination,
-        SigningError,
+        AzureKeyVaultCredentials, SignableCandidate, SignedOutput, Signer, SigningCertificate,
+        SigningDestination, SigningError,
     },
     tugger_file_manifest::{FileEntry, FileManifest},
 };
@@ -67,6 +67,46 @@ impl Display for SigningRequest {
     }
 }
 
+/// Describes how to react to a failure to sign an entity.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SigningFailurePolicy {
+    /// Signing failures are fatal.
+    Error,
+
+    /// Signing failures are logged and the unsigned entity is used.
+    Warn,
+}
+
+impl Default for SigningFailurePolicy {
+    fn default() -> Self {
+        Self::Error
+    }
+}
+
+impl Display for SigningFailurePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+        })
+    }
+}
+
+impl TryFrom<&str> for SigningFailurePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            _ => Err(anyhow!(
+                "invalid signing failure policy: {}; must be one of error, warn",
+                value
+            )),
+        }
+    }
+}
+
 fn from_code_signing_error(err: SigningError, label: impl ToString) -> ValueError {
     ValueError::Runtime(RuntimeError {
         code: "TUGGER_CODE_SIGNING",
@@ -94,6 +134,9 @@ pub struct CodeSignerValue {
 
     /// Starlark functions to influence signing operations.
     signing_callback: Option<Value>,
+
+    /// How to react to signing failures.
+    failure_policy: SigningFailurePolicy,
 }
 
 impl TypedValue for CodeSignerValue {
@@ -110,6 +153,7 @@ impl From<SigningCertificate> for CodeSignerValue {
         Self {
             inner: Arc::new(Mutex::new(Signer::new(cert))),
             signing_callback: None,
+            failure_policy: SigningFailurePolicy::default(),
         }
     }
 }
@@ -157,6 +201,51 @@ impl CodeSignerValue {
         Ok(Value::new::<CodeSignerValue>(cert.into()))
     }
 
+    fn from_azure_key_vault(
+        vault_url: String,
+        certificate_name: String,
+        tenant_id: Value,
+        client_id: Value,
+        client_secret: Value,
+    ) -> ValueResult {
+        let tenant_id = optional_str_arg("tenant_id", &tenant_id)?;
+        let client_id = optional_str_arg("client_id", &client_id)?;
+        let client_secret = optional_str_arg("client_secret", &client_secret)?;
+
+        let credentials = match (tenant_id, client_id, client_secret) {
+            (Some(tenant_id), Some(client_id), Some(client_secret)) => {
+                AzureKeyVaultCredentials::ClientSecret {
+                    tenant_id,
+                    client_id,
+                    client_secret,
+                }
+            }
+            (None, None, None) => AzureKeyVaultCredentials::ManagedIdentity,
+            _ => {
+                return Err(ValueError::Runtime(RuntimeError {
+                    code: "TUGGER_CODE_SIGNING",
+                    message: "must specify all of tenant_id, client_id, and client_secret or no credentials to use a managed identity".to_string(),
+                    label: "code_signer_from_azure_key_vault()".to_string(),
+                }))
+            }
+        };
+
+        let cert = SigningCertificate::azure_key_vault(vault_url, certificate_name, credentials);
+
+        Ok(Value::new::<CodeSignerValue>(cert.into()))
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn from_key_container(
+        certificate_path: String,
+        provider: String,
+        key_container: String,
+    ) -> ValueResult {
+        let cert = SigningCertificate::key_container(certificate_path, provider, key_container);
+
+        Ok(Value::new::<CodeSignerValue>(cert.into()))
+    }
+
     #[allow(clippy::unnecessary_wraps)]
     fn from_windows_store_auto() -> ValueResult {
         Ok(Value::new::<CodeSignerValue>(
@@ -217,6 +306,14 @@ impl CodeSignerValue {
 
         Ok(Value::from(NoneType::None))
     }
+
+    fn set_failure_policy(&mut self, policy: String) -> ValueResult {
+        self.failure_policy = error_context("set_failure_policy()", || {
+            SigningFailurePolicy::try_from(policy.as_str())
+        })?;
+
+        Ok(Value::from(NoneType::None))
+    }
 }
 
 pub struct CodeSigningRequestValue {
@@ -379,6 +476,9 @@ pub struct SigningResponse {
     /// Index of the [Signer] that signed this response.
     pub signed_index: Option<usize>,
 
+    /// Index of the [Signer] that failed to sign and whose failure was ignored.
+    pub failed_index: Option<usize>,
+
     /// The output of a successful code signing operation.
     pub output: Option<SignedOutput>,
 }
@@ -494,13 +594,29 @@ pub fn handle_signable_event(
 
             // Skip actual code signing if we're in pretend mode. (This is meant for testing.)
             let output = if let Some(output) = request_context.pretend_output {
-                Ok(output)
+                output
             } else {
-                error_context(request_context.label, || {
-                    // TODO specify temp dir as build directory.
-                    Ok(signable_signer.sign(&logger, None, &destination)?)
-                })
-            }?;
+                // TODO specify temp dir as build directory.
+                match signable_signer.sign(&logger, None, &destination) {
+                    Ok(output) => output,
+                    Err(e) => match signer_value.failure_policy {
+                        SigningFailurePolicy::Error => {
+                            return Err(from_code_signing_error(e, request_context.label));
+                        }
+                        SigningFailurePolicy::Warn => {
+                            warn!(
+                                logger,
+                                "CodeSigner #{} failed to sign {}; continuing due to failure policy: {:?}",
+                                i,
+                                request,
+                                e
+                            );
+                            response.failed_index = Some(i);
+                            break;
+                        }
+                    },
+                }
+            };
 
             response.signed_index = Some(i);
             response.output = Some(output);
@@ -553,6 +669,8 @@ pub fn handle_file_manifest_signable_events(
     label: &'static str,
     action: SigningAction,
 ) -> Result<FileManifest> {
+    ensure_no_secrets_in_manifest(type_values, label, manifest).map_err(|e| anyhow!("{:?}", e))?;
+
     let mut new_manifest = FileManifest::default();
 
     for (path, entry) in manifest.iter_entries() {
@@ -606,6 +724,26 @@ starlark_module! { code_signing_module =>
         CodeSignerValue::from_windows_store_auto()
     }
 
+    code_signer_from_azure_key_vault(
+        vault_url: String,
+        certificate_name: String,
+        tenant_id = NoneType::None,
+        client_id = NoneType::None,
+        client_secret = NoneType::None
+    ) {
+        CodeSignerValue::from_azure_key_vault(
+            vault_url,
+            certificate_name,
+            tenant_id,
+            client_id,
+            client_secret,
+        )
+    }
+
+    code_signer_from_key_container(certificate_path: String, provider: String, key_container: String) {
+        CodeSignerValue::from_key_container(certificate_path, provider, key_container)
+    }
+
     CodeSigner.activate(env env, this) {
         let this = this.downcast_ref::<CodeSignerValue>().unwrap();
         this.activate(env)
@@ -630,6 +768,11 @@ starlark_module! { code_signing_module =>
         let mut this = this.downcast_mut::<CodeSignerValue>().unwrap().unwrap();
         this.set_signing_callback(func)
     }
+
+    CodeSigner.set_failure_policy(this, policy: String) {
+        let mut this = this.downcast_mut::<CodeSignerValue>().unwrap().unwrap();
+        this.set_failure_policy(policy)
+    }
 }
 
 #[cfg(test)]
@@ -653,6 +796,7 @@ mod tests {
         filename: PathBuf,
         path: Option<PathBuf>,
         candidate: SignableCandidate<'static>,
+        pretend: bool,
         response: Option<SigningResponse>,
     }
 
@@ -664,6 +808,7 @@ mod tests {
                 filename: PathBuf::from("test_filename"),
                 path: None,
                 candidate: SignableCandidate::Forced(Signable::MachOData(vec![])),
+                pretend: true,
                 response: None,
             }
         }
@@ -674,7 +819,9 @@ mod tests {
             let mut context =
                 SigningContext::new(self.label, self.action, &self.filename, &self.candidate);
             context.path = self.path.clone();
-            context.set_pretend_output(SignedOutput::Memory(vec![42]));
+            if self.pretend {
+                context.set_pretend_output(SignedOutput::Memory(vec![42]));
+            }
             context.set_signing_destination(SigningDestination::Memory);
 
             let response = handle_signable_event(type_values, call_stack, context)?;
@@ -780,6 +927,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn code_signer_from_azure_key_vault() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let signer =
+            env.eval("code_signer_from_azure_key_vault('https://vault.vault.azure.net', 'cert')")?;
+        assert_eq!(signer.get_type(), CodeSignerValue::TYPE);
+
+        env.eval("code_signer_from_azure_key_vault('https://vault.vault.azure.net', 'cert', tenant_id = 'tenant', client_id = 'client', client_secret = 'secret')")?;
+        assert!(env
+            .eval("code_signer_from_azure_key_vault('https://vault.vault.azure.net', 'cert', access_token = 'token')")
+            .is_err());
+        assert!(env
+            .eval("code_signer_from_azure_key_vault('https://vault.vault.azure.net', 'cert', tenant_id = 'tenant')")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn code_signer_from_key_container() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let signer = env.eval(
+            "code_signer_from_key_container('cert.cer', 'eToken Base Cryptographic Provider', 'container')",
+        )?;
+        assert_eq!(signer.get_type(), CodeSignerValue::TYPE);
+
+        Ok(())
+    }
+
     #[test]
     fn chain_issuer_certificates_pem_file() -> Result<()> {
         let mut env = StarlarkEnvironment::new()?;
@@ -918,4 +1096,73 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn set_failure_policy() -> Result<()> {
+        let mut env = env_with_pfx_signer()?;
+
+        {
+            let signer_value = env.eval("signer")?;
+            let signer = signer_value.downcast_ref::<CodeSignerValue>().unwrap();
+            assert_eq!(signer.failure_policy, SigningFailurePolicy::Error);
+        }
+
+        env.eval("signer.set_failure_policy('warn')")?;
+        {
+            let signer_value = env.eval("signer")?;
+            let signer = signer_value.downcast_ref::<CodeSignerValue>().unwrap();
+            assert_eq!(signer.failure_policy, SigningFailurePolicy::Warn);
+        }
+
+        assert!(env.eval("signer.set_failure_policy('ignore')").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn failure_policy_error() -> Result<()> {
+        let mut env = env_with_pfx_signer()?;
+
+        {
+            let event_value = env.eval("SIGNING_EVENT")?;
+            let mut event = event_value
+                .downcast_mut::<TestSigningEventValue>()
+                .unwrap()
+                .unwrap();
+            event.pretend = false;
+        }
+
+        env.eval("signer.activate()")?;
+        assert!(env.eval("SIGNING_EVENT.run()").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn failure_policy_warn() -> Result<()> {
+        let mut env = env_with_pfx_signer()?;
+
+        {
+            let event_value = env.eval("SIGNING_EVENT")?;
+            let mut event = event_value
+                .downcast_mut::<TestSigningEventValue>()
+                .unwrap()
+                .unwrap();
+            event.pretend = false;
+        }
+
+        env.eval("signer.set_failure_policy('warn')")?;
+        env.eval("signer.activate()")?;
+        env.eval("SIGNING_EVENT.run()")?;
+
+        let event_value = env.eval("SIGNING_EVENT")?;
+        let event = event_value.downcast_ref::<TestSigningEventValue>().unwrap();
+
+        let response = event.response.as_ref().unwrap();
+        assert_eq!(response.failed_index, Some(0));
+        assert!(response.signed_index.is_none());
+        assert!(response.output.is_none());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,575 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::file_manifest::FileManifestValue,
+    anyhow::{anyhow, Context},
+    slog::{info, warn},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_bool_arg, optional_dict_arg, optional_list_arg,
+        optional_str_arg, required_list_arg, EnvironmentContext,
+    },
+    std::{
+        collections::BTreeMap,
+        path::{Component, Path, PathBuf},
+        process::Command,
+        time::SystemTime,
+    },
+    tugger_common::glob::evaluate_glob,
+    tugger_file_manifest::FileManifest,
+};
+
+/// Environment variables passed through to commands not inheriting the environment.
+///
+/// These are needed to locate and run programs on common platforms.
+const ENV_PASSTHROUGH: &[&str] = &[
+    "COMSPEC",
+    "HOME",
+    "PATH",
+    "PATHEXT",
+    "SYSTEMROOT",
+    "TEMP",
+    "TMP",
+    "TMPDIR",
+];
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_COMMAND",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct CommandValue {
+    /// The program to run followed by its arguments.
+    pub args: Vec<String>,
+
+    /// Directory to run the command in.
+    pub cwd: PathBuf,
+
+    /// Environment variables to define for the command.
+    pub env: BTreeMap<String, String>,
+
+    /// Patterns of files the command reads, relative to `cwd`.
+    pub inputs: Vec<String>,
+
+    /// Patterns of files the command writes, relative to `cwd`.
+    pub outputs: Vec<String>,
+
+    /// Whether the command inherits the full environment of this process.
+    pub inherit_env: bool,
+
+    /// Files captured by running the command.
+    captured: Option<FileManifest>,
+}
+
+impl TypedValue for CommandValue {
+    type Holder = Mutable<CommandValue>;
+    const TYPE: &'static str = "Command";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Ensure a declared input or output pattern stays within the command's directory.
+fn validate_pattern(kind: &str, pattern: &str) -> anyhow::Result<()> {
+    let path = Path::new(pattern);
+
+    if pattern.is_empty()
+        || !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        Err(anyhow!(
+            "declared {} {} must be a relative path without ..",
+            kind,
+            pattern
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+impl CommandValue {
+    /// Obtain the most recent modification time of declared inputs.
+    ///
+    /// Errors if an input pattern doesn't match any files.
+    fn newest_input(&self) -> anyhow::Result<Option<SystemTime>> {
+        let mut newest = None;
+
+        for pattern in &self.inputs {
+            let paths = evaluate_glob(&self.cwd, pattern)?;
+
+            if paths.is_empty() {
+                return Err(anyhow!("declared input {} does not exist", pattern));
+            }
+
+            for path in paths {
+                let modified = std::fs::metadata(&path)?.modified()?;
+                newest = Some(newest.map_or(modified, |t: SystemTime| t.max(modified)));
+            }
+        }
+
+        Ok(newest)
+    }
+
+    /// Whether all declared outputs exist and are newer than all declared inputs.
+    fn outputs_up_to_date(&self, newest_input: SystemTime) -> anyhow::Result<bool> {
+        for pattern in &self.outputs {
+            let paths = evaluate_glob(&self.cwd, pattern)?;
+
+            if paths.is_empty() {
+                return Ok(false);
+            }
+
+            for path in paths {
+                if std::fs::metadata(&path)?.modified()? < newest_input {
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Run the command if its outputs are stale and capture its outputs.
+    ///
+    /// The command runs at most once per instance.
+    fn run(&mut self, logger: &slog::Logger) -> anyhow::Result<FileManifest> {
+        if let Some(manifest) = &self.captured {
+            return Ok(manifest.clone());
+        }
+
+        let (program, program_args) = self
+            .args
+            .split_first()
+            .ok_or_else(|| anyhow!("args must not be empty"))?;
+
+        let newest_input = self.newest_input()?;
+
+        // Without declared inputs we can't tell whether outputs are stale.
+        let up_to_date = match newest_input {
+            Some(newest_input) if !self.outputs.is_empty() => {
+                self.outputs_up_to_date(newest_input)?
+            }
+            _ => false,
+        };
+
+        if up_to_date {
+            warn!(
+                logger,
+                "outputs of {} are up to date; not running it",
+                self.args.join(" ")
+            );
+        } else {
+            // Remove outputs of previous runs so they can't be mistaken for
+            // outputs of this one.
+            for pattern in &self.outputs {
+                for path in evaluate_glob(&self.cwd, pattern)? {
+                    std::fs::remove_file(&path)
+                        .with_context(|| format!("removing {}", path.display()))?;
+                }
+            }
+
+            let mut command = Command::new(program);
+            command.args(program_args).current_dir(&self.cwd);
+
+            // This only limits what the command sees of our environment. It still
+            // has access to everything else the current user can access.
+            if !self.inherit_env {
+                command.env_clear();
+
+                for key in ENV_PASSTHROUGH {
+                    if let Some(value) = std::env::var_os(key) {
+                        command.env(key, value);
+                    }
+                }
+            }
+
+            command.envs(&self.env);
+
+            warn!(
+                logger,
+                "running {} in {}",
+                self.args.join(" "),
+                self.cwd.display()
+            );
+
+            let output = command
+                .output()
+                .with_context(|| format!("running {}", program))?;
+
+            for line in String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .chain(String::from_utf8_lossy(&output.stderr).lines())
+            {
+                info!(logger, "{}", line);
+            }
+
+            if !output.status.success() {
+                return Err(anyhow!("{} failed: {}", program, output.status));
+            }
+        }
+
+        // Capture outputs into memory so they survive subsequent runs.
+        let mut manifest = FileManifest::default();
+
+        for pattern in &self.outputs {
+            let paths = evaluate_glob(&self.cwd, pattern)?;
+
+            if paths.is_empty() {
+                return Err(anyhow!(
+                    "declared output {} was not produced by {}",
+                    pattern,
+                    program
+                ));
+            }
+
+            for path in paths {
+                manifest
+                    .add_path_memory(&path, &self.cwd)
+                    .with_context(|| format!("capturing output {}", path.display()))?;
+            }
+        }
+
+        self.captured = Some(manifest.clone());
+
+        Ok(manifest)
+    }
+}
+
+// Starlark functions.
+impl CommandValue {
+    /// run_command(args, cwd=None, env=None, inputs=None, outputs=None, inherit_env=False)
+    #[allow(clippy::too_many_arguments)]
+    fn new_from_args(
+        type_values: &TypeValues,
+        args: &Value,
+        cwd: &Value,
+        env: &Value,
+        inputs: &Value,
+        outputs: &Value,
+        inherit_env: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "run_command()";
+
+        required_list_arg("args", "string", args)?;
+        let cwd = optional_str_arg("cwd", cwd)?;
+        optional_dict_arg("env", "string", "string", env)?;
+        optional_list_arg("inputs", "string", inputs)?;
+        optional_list_arg("outputs", "string", outputs)?;
+        let inherit_env = optional_bool_arg("inherit_env", inherit_env)?.unwrap_or(false);
+
+        let args = args
+            .iter()?
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+
+        let env = match env.get_type() {
+            "dict" => env
+                .iter()?
+                .iter()
+                .map(|k| Ok((k.to_string(), env.at(k.clone())?.to_string())))
+                .collect::<Result<BTreeMap<_, _>, ValueError>>()?,
+            _ => BTreeMap::new(),
+        };
+
+        let patterns = |value: &Value| -> Result<Vec<String>, ValueError> {
+            Ok(match value.get_type() {
+                "list" => value.iter()?.iter().map(|x| x.to_string()).collect(),
+                _ => Vec::new(),
+            })
+        };
+        let inputs = patterns(inputs)?;
+        let outputs = patterns(outputs)?;
+
+        error_context(LABEL, || {
+            if args.is_empty() {
+                return Err(anyhow!("args must not be empty"));
+            }
+
+            for pattern in &inputs {
+                validate_pattern("input", pattern)?;
+            }
+            for pattern in &outputs {
+                validate_pattern("output", pattern)?;
+            }
+
+            Ok(())
+        })?;
+
+        let raw_context = get_context_value(type_values)?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        // Relative directories are relative to the config file.
+        let cwd = if let Some(cwd) = cwd {
+            context.cwd().join(cwd)
+        } else {
+            context.cwd().to_path_buf()
+        };
+
+        Ok(Value::new(CommandValue {
+            args,
+            cwd,
+            env,
+            inputs,
+            outputs,
+            inherit_env,
+            captured: None,
+        }))
+    }
+
+    /// Command.to_file_manifest()
+    fn resolve_file_manifest(&mut self, type_values: &TypeValues) -> ValueResult {
+        const LABEL: &str = "Command.to_file_manifest()";
+
+        let raw_context = get_context_value(type_values)?;
+        let context = raw_context
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let manifest = error_context(LABEL, || self.run(context.logger()))?;
+
+        FileManifestValue::new_from_manifest(manifest)
+    }
+}
+
+starlark_module! { command_module =>
+    run_command(
+        env type_values,
+        args,
+        cwd=NoneType::None,
+        env=NoneType::None,
+        inputs=NoneType::None,
+        outputs=NoneType::None,
+        inherit_env=NoneType::None
+    ) {
+        CommandValue::new_from_args(type_values, &args, &cwd, &env, &inputs, &outputs, &inherit_env)
+    }
+
+    Command.build(env env, call_stack cs, this, target: String) {
+        let manifest = {
+            let mut this = this.downcast_mut::<CommandValue>().unwrap().unwrap();
+            this.resolve_file_manifest(env)?
+        };
+
+        let manifest = manifest.downcast_ref::<FileManifestValue>().unwrap();
+        manifest.build(env, cs, target)
+    }
+
+    Command.to_file_manifest(env env, this) {
+        let mut this = this.downcast_mut::<CommandValue>().unwrap().unwrap();
+        this.resolve_file_manifest(env)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use {
+        super::*, crate::starlark::testutil::*, anyhow::Result,
+        starlark_dialect_build_targets::ResolvedTargetValue, tugger_common::testutil::*,
+    };
+
+    fn temp_dir_str(name: &str) -> Result<String> {
+        let path = DEFAULT_TEMP_DIR.path().join(name);
+        std::fs::create_dir_all(&path)?;
+
+        Ok(format!("{}", path.display()))
+    }
+
+    fn manifest_paths(value: &Value) -> Vec<String> {
+        let manifest = value.downcast_ref::<FileManifestValue>().unwrap();
+        let inner = manifest.inner("ignored").unwrap();
+
+        inner.iter_files().map(|f| f.path_string()).collect()
+    }
+
+    #[test]
+    fn run_command_outputs() -> Result<()> {
+        let cwd = temp_dir_str("run_command_outputs")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval(&format!(
+            "c = run_command(['sh', '-c', 'mkdir -p out && echo $FOO > out/a.txt && echo b > out/b.txt'], cwd = '{}', env = {{'FOO': 'foo'}}, outputs = ['out/*.txt'])",
+            cwd
+        ))?;
+
+        // Evaluating run_command() doesn't run the command.
+        assert!(!Path::new(&cwd).join("out").exists());
+
+        let value = env.eval("c.to_file_manifest()")?;
+        assert_eq!(manifest_paths(&value), vec!["out/a.txt", "out/b.txt"]);
+
+        let manifest = value.downcast_ref::<FileManifestValue>().unwrap();
+        assert_eq!(
+            manifest
+                .inner("ignored")
+                .unwrap()
+                .get("out/a.txt")
+                .unwrap()
+                .resolve_content()?,
+            b"foo\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_clears_stale_outputs() -> Result<()> {
+        let cwd = temp_dir_str("run_command_clears_stale_outputs")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        std::fs::create_dir_all(Path::new(&cwd).join("out"))?;
+        std::fs::write(Path::new(&cwd).join("out/stale.txt"), b"stale")?;
+
+        let value = env.eval(&format!(
+            "run_command(['sh', '-c', 'echo a > out/a.txt'], cwd = '{}', outputs = ['out/*.txt']).to_file_manifest()",
+            cwd
+        ))?;
+
+        assert_eq!(manifest_paths(&value), vec!["out/a.txt"]);
+        assert!(!Path::new(&cwd).join("out/stale.txt").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_inputs() -> Result<()> {
+        let cwd = temp_dir_str("run_command_inputs")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        let command = format!(
+            "run_command(['sh', '-c', 'cp in.txt out.txt && echo run >> runs.txt'], cwd = '{}', inputs = ['in.txt'], outputs = ['out.txt'])",
+            cwd
+        );
+
+        assert!(env
+            .eval(&format!("{}.to_file_manifest()", command))
+            .is_err());
+
+        std::fs::write(Path::new(&cwd).join("in.txt"), b"input")?;
+        env.eval(&format!("{}.to_file_manifest()", command))?;
+
+        // Outputs newer than inputs aren't rebuilt.
+        let value = env.eval(&format!("{}.to_file_manifest()", command))?;
+        assert_eq!(manifest_paths(&value), vec!["out.txt"]);
+        assert_eq!(
+            std::fs::read_to_string(Path::new(&cwd).join("runs.txt"))?,
+            "run\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_build() -> Result<()> {
+        let cwd = temp_dir_str("run_command_build")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval(&format!(
+            "c = run_command(['sh', '-c', 'echo built > out.txt'], cwd = '{}', outputs = ['out.txt'])",
+            cwd
+        ))?;
+
+        let resolved = env.eval("c.build('run_command_build')")?;
+        let resolved = resolved.downcast_ref::<ResolvedTargetValue>().unwrap();
+        assert_eq!(
+            std::fs::read(resolved.inner.output_path.join("out.txt"))?,
+            b"built\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_cleared_env() -> Result<()> {
+        let cwd = temp_dir_str("run_command_cleared_env")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        std::env::set_var("TUGGER_RUN_COMMAND_TEST", "leaked");
+
+        let value = env.eval(&format!(
+            "run_command(['sh', '-c', 'echo \"$TUGGER_RUN_COMMAND_TEST\" > env.txt'], cwd = '{}', outputs = ['env.txt']).to_file_manifest()",
+            cwd
+        ))?;
+        let manifest = value.downcast_ref::<FileManifestValue>().unwrap();
+        assert_eq!(
+            manifest
+                .inner("ignored")
+                .unwrap()
+                .get("env.txt")
+                .unwrap()
+                .resolve_content()?,
+            b"\n"
+        );
+
+        let value = env.eval(&format!(
+            "run_command(['sh', '-c', 'echo \"$TUGGER_RUN_COMMAND_TEST\" > env.txt'], cwd = '{}', outputs = ['env.txt'], inherit_env = True).to_file_manifest()",
+            cwd
+        ))?;
+        let manifest = value.downcast_ref::<FileManifestValue>().unwrap();
+        assert_eq!(
+            manifest
+                .inner("ignored")
+                .unwrap()
+                .get("env.txt")
+                .unwrap()
+                .resolve_content()?,
+            b"leaked\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_errors() -> Result<()> {
+        let cwd = temp_dir_str("run_command_errors")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        assert!(env.eval("run_command([])").is_err());
+        assert!(env
+            .eval("run_command(['true'], outputs = ['../x'])")
+            .is_err());
+        assert!(env
+            .eval("run_command(['true'], inputs = ['/etc/passwd'])")
+            .is_err());
+        assert!(env
+            .eval(&format!(
+                "run_command(['sh', '-c', 'exit 1'], cwd = '{}').to_file_manifest()",
+                cwd
+            ))
+            .is_err());
+        assert!(env
+            .eval(&format!(
+                "run_command(['sh', '-c', 'true'], cwd = '{}', outputs = ['missing.txt']).to_file_manifest()",
+                cwd
+            ))
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -5,10 +5,11 @@
 use {
     crate::starlark::{
         code_signing::{handle_signable_event, SigningAction, SigningContext},
+        ensure_no_secrets_in_manifest,
         file_content::{FileContentValue, FileContentWrapper},
     },
     anyhow::anyhow,
-    slog::warn,
+    slog::{info, warn},
     starlark::{
         environment::TypeValues,
         eval::call_stack::CallStack,
@@ -116,7 +117,7 @@ impl FileManifestValue {
         })
     }
 
-    fn build(
+    pub(crate) fn build(
         &self,
         type_values: &TypeValues,
         call_stack: &mut CallStack,
@@ -132,6 +133,7 @@ impl FileManifestValue {
         let output_path = context.target_build_path(&target);
 
         let inner = self.inner(LABEL)?;
+        ensure_no_secrets_in_manifest(type_values, LABEL, &inner)?;
 
         let installed_paths = error_context(LABEL, || {
             warn!(
@@ -139,9 +141,35 @@ impl FileManifestValue {
                 "installing files to {}",
                 output_path.display()
             );
-            inner
-                .materialize_files_with_replace(&output_path)
-                .map_err(anyhow::Error::new)
+
+            // Only write what changed since the previous build.
+            let diff = inner
+                .materialize_files_incremental(&output_path, |i, total, path| {
+                    info!(context.logger(), "[{}/{}] {}", i, total, path.display());
+                })
+                .map_err(anyhow::Error::new)?;
+
+            if diff.is_empty() {
+                warn!(context.logger(), "{} is up to date", output_path.display());
+            } else {
+                warn!(
+                    context.logger(),
+                    "{} files added, {} changed, {} removed",
+                    diff.added.len(),
+                    diff.changed.len(),
+                    diff.removed.len()
+                );
+                for path in &diff.removed {
+                    info!(context.logger(), "removed {}", path.display());
+                }
+            }
+
+            Ok(diff
+                .added
+                .iter()
+                .chain(diff.changed.iter())
+                .map(|path| output_path.join(path))
+                .collect::<Vec<_>>())
         })?;
 
         post_materialize_signing_checks(
@@ -306,6 +334,7 @@ impl FileManifestValue {
             .ok_or(ValueError::IncorrectParameterType)?;
 
         let inner = self.inner(LABEL)?;
+        ensure_no_secrets_in_manifest(type_values, LABEL, &inner)?;
 
         let installed_paths = error_context(LABEL, || {
             let dest_path = context.build_path().join(path);
@@ -428,8 +457,11 @@ starlark_module! { file_manifest_module =>
 #[cfg(test)]
 mod tests {
     use {
-        super::*, crate::starlark::testutil::*, anyhow::Result, tugger_common::testutil::*,
-        tugger_file_manifest::FileEntry,
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        tugger_common::testutil::*,
+        tugger_file_manifest::{file_mode, FileEntry},
     };
 
     #[test]
@@ -477,20 +509,51 @@ mod tests {
             parent.display().to_string().escape_default()
         ))?;
 
+        // Entries carry the permissions of the file they were read from.
+        let mut expected1: FileEntry = vec![42, 42].into();
+        if let Some(mode) = file_mode(&std::fs::metadata(&temp_file1)?) {
+            expected1.set_mode(mode);
+        }
+
         let manifest = manifest_value.downcast_ref::<FileManifestValue>().unwrap();
         {
             let inner = manifest.inner("ignored").unwrap();
             assert_eq!(inner.iter_files().count(), 2);
             assert_eq!(
                 inner.get("test_add_path_0"),
-                Some(&FileEntry::new_from_path(temp_file0, false))
+                Some(&FileEntry::try_from(temp_file0)?)
             );
-            assert_eq!(inner.get("test_add_path_1"), Some(&vec![42, 42].into()),);
+            assert_eq!(inner.get("test_add_path_1"), Some(&expected1));
         }
 
         Ok(())
     }
 
+    #[test]
+    fn install_secret() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        {
+            let context_value = crate::starlark::get_context_value(&env.type_values).unwrap();
+            let context = context_value
+                .downcast_ref::<crate::starlark::TuggerContextValue>()
+                .unwrap();
+            context.secrets.add("hunter2hunter2");
+        }
+
+        env.eval("c = FileContent(filename = 'file', content = 'password=hunter2hunter2')")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(c)")?;
+
+        let err = env.eval("m.install('install_secret')").unwrap_err();
+        assert!(str::contains(
+            &format!("{:?}", err),
+            "file contains the value of a secret variable"
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn add_file() -> Result<()> {
         let mut env = StarlarkEnvironment::new()?;
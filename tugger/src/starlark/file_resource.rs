@@ -4,6 +4,7 @@
 
 use {
     crate::starlark::file_manifest::FileManifestValue,
+    anyhow::anyhow,
     starlark::{
         environment::TypeValues,
         values::{
@@ -17,10 +18,10 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        get_context_value, optional_list_arg, optional_str_arg, required_list_arg,
-        EnvironmentContext,
+        get_context_value, optional_bool_arg, optional_list_arg, optional_str_arg,
+        required_list_arg, EnvironmentContext,
     },
-    std::collections::HashSet,
+    std::{collections::BTreeSet, path::PathBuf},
     tugger_common::glob::evaluate_glob,
     tugger_file_manifest::{FileEntry, FileManifest},
 };
@@ -38,16 +39,20 @@ where
     })
 }
 
-/// glob(include, exclude=None, relative_to=None)
+/// glob(include, exclude=None, strip_prefix=None, directory=None, flatten=False)
 fn starlark_glob(
     type_values: &TypeValues,
     include: &Value,
     exclude: &Value,
     strip_prefix: &Value,
+    directory: &Value,
+    flatten: &Value,
 ) -> ValueResult {
     required_list_arg("include", "string", include)?;
     optional_list_arg("exclude", "string", exclude)?;
     let strip_prefix = optional_str_arg("strip_prefix", strip_prefix)?;
+    let directory = optional_str_arg("directory", directory)?;
+    let flatten = optional_bool_arg("flatten", flatten)?.unwrap_or(false);
 
     let include = include
         .iter()?
@@ -66,7 +71,8 @@ fn starlark_glob(
         .ok_or(ValueError::IncorrectParameterType)?;
 
     let manifest = error_context("glob()", || {
-        let mut result = HashSet::new();
+        // Use an ordered set so results are deterministic.
+        let mut result = BTreeSet::new();
 
         // Evaluate all the includes first.
         for v in include {
@@ -82,18 +88,38 @@ fn starlark_glob(
             }
         }
 
+        // Relative prefixes are relative to the config file, like patterns.
+        let strip_prefix = strip_prefix.map(|prefix| context.cwd().join(prefix));
+
         let mut manifest = FileManifest::default();
 
         for path in result {
             let content = FileEntry::try_from(path.as_path())?;
 
-            let path = if let Some(prefix) = &strip_prefix {
+            let mut manifest_path = if flatten {
+                let filename = path
+                    .file_name()
+                    .ok_or_else(|| anyhow!("unable to resolve filename of {}", path.display()))?;
+
+                PathBuf::from(filename)
+            } else if let Some(prefix) = &strip_prefix {
                 path.strip_prefix(prefix)?.to_path_buf()
             } else {
                 path.to_path_buf()
             };
 
-            manifest.add_file_entry(&path, content)?;
+            if let Some(directory) = &directory {
+                manifest_path = PathBuf::from(directory).join(manifest_path);
+            }
+
+            if flatten && manifest.has_path(&manifest_path) {
+                return Err(anyhow!(
+                    "multiple files would be flattened to {}",
+                    manifest_path.display()
+                ));
+            }
+
+            manifest.add_file_entry(&manifest_path, content)?;
         }
 
         Ok(manifest)
@@ -103,8 +129,102 @@ fn starlark_glob(
 }
 
 starlark_module! { file_resource_module =>
-    glob(env env, include, exclude=NoneType::None, strip_prefix=NoneType::None) {
-        starlark_glob(env, &include, &exclude, &strip_prefix)
+    glob(
+        env env,
+        include,
+        exclude=NoneType::None,
+        strip_prefix=NoneType::None,
+        directory=NoneType::None,
+        flatten=NoneType::None
+    ) {
+        starlark_glob(env, &include, &exclude, &strip_prefix, &directory, &flatten)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result, tugger_common::testutil::*};
+
+    fn glob_fixture(name: &str) -> Result<String> {
+        let root = DEFAULT_TEMP_DIR.path().join(name);
+        std::fs::create_dir_all(root.join("assets/fonts"))?;
+        std::fs::write(root.join("assets/logo.png"), b"logo")?;
+        std::fs::write(root.join("assets/fonts/a.ttf"), b"a")?;
+        std::fs::write(root.join("assets/fonts/b.ttf"), b"b")?;
+        std::fs::write(root.join("assets/fonts/README"), b"readme")?;
 
+        // Normalize paths to work around string escaping.
+        Ok(format!("{}", root.display()).replace('\\', "/"))
+    }
+
+    fn manifest_paths(value: &Value) -> Vec<PathBuf> {
+        let manifest = value.downcast_ref::<FileManifestValue>().unwrap();
+        let inner = manifest.inner("ignored").unwrap();
+
+        inner.iter_files().map(|f| f.path().to_path_buf()).collect()
+    }
+
+    #[test]
+    fn glob_exclude_strip_prefix() -> Result<()> {
+        let root = glob_fixture("glob_exclude_strip_prefix")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        let value = env.eval(&format!(
+            "glob(include = ['{0}/assets/**/*'], exclude = ['{0}/assets/**/README'], strip_prefix = '{0}/')",
+            root
+        ))?;
+
+        assert_eq!(
+            manifest_paths(&value),
+            vec![
+                PathBuf::from("assets/fonts/a.ttf"),
+                PathBuf::from("assets/fonts/b.ttf"),
+                PathBuf::from("assets/logo.png"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn glob_directory_flatten() -> Result<()> {
+        let root = glob_fixture("glob_directory_flatten")?;
+        let mut env = StarlarkEnvironment::new()?;
+
+        let value = env.eval(&format!(
+            "glob(include = ['{0}/assets/fonts/*.ttf'], strip_prefix = '{0}/assets/', directory = 'share')",
+            root
+        ))?;
+        assert_eq!(
+            manifest_paths(&value),
+            vec![
+                PathBuf::from("share/fonts/a.ttf"),
+                PathBuf::from("share/fonts/b.ttf"),
+            ]
+        );
+
+        let value = env.eval(&format!(
+            "glob(include = ['{0}/assets/**/*.*'], flatten = True, directory = 'data')",
+            root
+        ))?;
+        assert_eq!(
+            manifest_paths(&value),
+            vec![
+                PathBuf::from("data/a.ttf"),
+                PathBuf::from("data/b.ttf"),
+                PathBuf::from("data/logo.png"),
+            ]
+        );
+
+        std::fs::create_dir_all(format!("{}/other", root))?;
+        std::fs::write(format!("{}/other/logo.png", root), b"other")?;
+        assert!(env
+            .eval(&format!(
+                "glob(include = ['{0}/**/logo.png'], flatten = True)",
+                root
+            ))
+            .is_err());
+
+        Ok(())
+    }
 }
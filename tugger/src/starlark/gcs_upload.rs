@@ -0,0 +1,129 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        publish::GcsUpload,
+        starlark::{
+            file_manifest::FileManifestValue,
+            publish::{
+                add_manifest_to_files, add_path_to_files, error_context, set_settings_attr,
+                upload_context, uploaded_target,
+            },
+        },
+    },
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::optional_str_arg,
+};
+
+#[derive(Clone, Debug)]
+pub struct GcsUploadValue {
+    pub inner: GcsUpload,
+}
+
+impl TypedValue for GcsUploadValue {
+    type Holder = Mutable<GcsUploadValue>;
+    const TYPE: &'static str = "GcsUpload";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        if set_settings_attr(&mut self.inner.settings, attribute, &value)? {
+            return Ok(());
+        }
+
+        match attribute {
+            "access_token_env" => {
+                self.inner.access_token_env = value.to_string();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl GcsUploadValue {
+    pub fn new_from_args(bucket: String, prefix: &Value) -> ValueResult {
+        let mut inner = GcsUpload::new(bucket);
+        inner.settings.prefix = optional_str_arg("prefix", prefix)?.unwrap_or_default();
+
+        Ok(Value::new(GcsUploadValue { inner }))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "GcsUpload.build()";
+
+        let (logger, output_path) = upload_context(type_values, LABEL, &target)?;
+
+        let keys = error_context(LABEL, || self.inner.upload(&logger))?;
+
+        uploaded_target(LABEL, output_path, keys)
+    }
+}
+
+starlark_module! { gcs_upload_module =>
+    #[allow(non_snake_case)]
+    GcsUpload(bucket: String, prefix = NoneType::None) {
+        GcsUploadValue::new_from_args(bucket, &prefix)
+    }
+
+    GcsUpload.add_path(env env, this, path: String, key = NoneType::None) {
+        let mut this = this.downcast_mut::<GcsUploadValue>().unwrap().unwrap();
+        add_path_to_files(env, &mut this.inner.settings.files, "GcsUpload.add_path()", path, "key", &key)
+    }
+
+    GcsUpload.add_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<GcsUploadValue>().unwrap().unwrap();
+        add_manifest_to_files(&mut this.inner.settings.files, "GcsUpload.add_manifest()", manifest)
+    }
+
+    GcsUpload.build(env env, this, target: String) {
+        let this = this.downcast_ref::<GcsUploadValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result};
+
+    #[test]
+    fn test_gcs_upload() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("upload = GcsUpload('releases')")?;
+        env.eval("upload.content_type = 'application/octet-stream'")?;
+        env.eval("upload.access_token_env = 'MY_TOKEN'")?;
+
+        let value = env.eval("upload")?;
+        let upload = value.downcast_ref::<GcsUploadValue>().unwrap();
+        assert_eq!(upload.inner.access_token_env, "MY_TOKEN");
+        assert_eq!(
+            upload.inner.settings.content_type,
+            Some("application/octet-stream".to_string())
+        );
+
+        Ok(())
+    }
+}
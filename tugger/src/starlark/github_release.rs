@@ -0,0 +1,152 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        publish::GitHubRelease,
+        starlark::{
+            file_manifest::FileManifestValue,
+            publish::{
+                add_manifest_to_files, add_path_to_files, error_context, upload_context,
+                uploaded_target,
+            },
+        },
+    },
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::ToOptional,
+};
+
+#[derive(Clone, Debug)]
+pub struct GitHubReleaseValue {
+    pub inner: GitHubRelease,
+}
+
+impl TypedValue for GitHubReleaseValue {
+    type Holder = Mutable<GitHubReleaseValue>;
+    const TYPE: &'static str = "GitHubRelease";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "api_url" => {
+                self.inner.api_url = value.to_string();
+            }
+            "body" => {
+                self.inner.body = value.to_optional();
+            }
+            "checksums" => {
+                self.inner.checksums = value.to_bool();
+            }
+            "draft" => {
+                self.inner.draft = value.to_bool();
+            }
+            "name" => {
+                self.inner.name = value.to_optional();
+            }
+            "prerelease" => {
+                self.inner.prerelease = value.to_bool();
+            }
+            "target_commitish" => {
+                self.inner.target_commitish = value.to_optional();
+            }
+            "token_env" => {
+                self.inner.token_env = value.to_string();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl GitHubReleaseValue {
+    pub fn new_from_args(repository: String, tag: String) -> ValueResult {
+        let inner = error_context("GitHubRelease()", || GitHubRelease::new(&repository, tag))?;
+
+        Ok(Value::new(GitHubReleaseValue { inner }))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "GitHubRelease.build()";
+
+        let (logger, output_path) = upload_context(type_values, LABEL, &target)?;
+
+        let url = error_context(LABEL, || self.inner.publish(&logger))?;
+
+        uploaded_target(LABEL, output_path, vec![url])
+    }
+}
+
+starlark_module! { github_release_module =>
+    #[allow(non_snake_case)]
+    GitHubRelease(repository: String, tag: String) {
+        GitHubReleaseValue::new_from_args(repository, tag)
+    }
+
+    GitHubRelease.add_path(env env, this, path: String, name = NoneType::None) {
+        let mut this = this.downcast_mut::<GitHubReleaseValue>().unwrap().unwrap();
+        add_path_to_files(env, &mut this.inner.files, "GitHubRelease.add_path()", path, "name", &name)
+    }
+
+    GitHubRelease.add_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<GitHubReleaseValue>().unwrap().unwrap();
+        add_manifest_to_files(&mut this.inner.files, "GitHubRelease.add_manifest()", manifest)
+    }
+
+    GitHubRelease.build(env env, this, target: String) {
+        let this = this.downcast_ref::<GitHubReleaseValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result};
+
+    #[test]
+    fn test_github_release() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        assert!(env.eval("GitHubRelease('PyOxidizer', 'v1.0')").is_err());
+
+        env.eval("release = GitHubRelease('indygreg/PyOxidizer', 'v1.0')")?;
+        env.eval("release.name = 'PyOxidizer 1.0'")?;
+        env.eval("release.body = 'Release notes'")?;
+        env.eval("release.prerelease = True")?;
+        env.eval("release.checksums = False")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'app.zip', content = 'dummy'))")?;
+        env.eval("release.add_manifest(m)")?;
+
+        let value = env.eval("release")?;
+        let release = value.downcast_ref::<GitHubReleaseValue>().unwrap();
+        assert_eq!(release.inner.owner, "indygreg");
+        assert_eq!(release.inner.name, Some("PyOxidizer 1.0".to_string()));
+        assert!(release.inner.prerelease);
+        assert!(!release.inner.draft);
+        assert_eq!(release.inner.assets()?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_content::FileContentWrapper, get_context_value, TuggerContextValue},
+    anyhow::{anyhow, Context},
+    slog::warn,
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            none::NoneType,
+            {Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{optional_bool_arg, optional_str_arg, EnvironmentContext},
+    tugger_common::http::{download_to_path, RemoteContent},
+    tugger_file_manifest::FileEntry,
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_HTTP",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+/// Resolve the filename to use for content at a URL.
+fn filename_from_url(url: &str) -> Option<String> {
+    let path = url.split(|c| c == '?' || c == '#').next()?;
+
+    // The scheme and host aren't file names.
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    let (_, path) = path.split_once('/')?;
+    let filename = path.rsplit('/').next()?;
+
+    if filename.is_empty() {
+        None
+    } else {
+        Some(filename.to_string())
+    }
+}
+
+/// http_fetch(url, sha256, filename=None, executable=False)
+fn starlark_http_fetch(
+    type_values: &TypeValues,
+    url: String,
+    sha256: String,
+    filename: &Value,
+    executable: &Value,
+) -> ValueResult {
+    const LABEL: &str = "http_fetch()";
+
+    let filename = optional_str_arg("filename", filename)?;
+    let executable = optional_bool_arg("executable", executable)?.unwrap_or(false);
+
+    let (logger, cache_dir, offline) = {
+        let tugger_context_raw = get_context_value(type_values)?;
+        let tugger_context = tugger_context_raw
+            .downcast_ref::<TuggerContextValue>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let cache_dir = if let Some(path) = &tugger_context.http_cache_dir {
+            path.clone()
+        } else {
+            let raw_context = starlark_dialect_build_targets::get_context_value(type_values)?;
+            let context = raw_context
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.build_path().join("http_cache")
+        };
+
+        (
+            tugger_context.logger.clone(),
+            cache_dir,
+            tugger_context.offline,
+        )
+    };
+
+    let content = error_context(LABEL, || {
+        let sha256 = sha256.to_lowercase();
+
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("{} is not a hex encoded SHA-256 digest", sha256));
+        }
+
+        let filename = if let Some(filename) = filename {
+            filename
+        } else {
+            filename_from_url(&url).ok_or_else(|| {
+                anyhow!(
+                    "unable to resolve filename from {}; specify filename explicitly",
+                    url
+                )
+            })?
+        };
+
+        // The cache is content addressed so the same content is only fetched once.
+        let cache_path = cache_dir.join(&sha256);
+
+        if offline && !cache_path.exists() {
+            return Err(anyhow!(
+                "{} is not cached and network access is disabled by offline mode",
+                url
+            ));
+        }
+
+        let entry = RemoteContent {
+            name: format!("HTTP_FETCH_{}", sha256.to_uppercase()),
+            url: url.clone(),
+            sha256: sha256.clone(),
+        };
+
+        if offline {
+            warn!(logger, "using cached {} in offline mode", url);
+        }
+
+        download_to_path(&logger, &entry, &cache_path)
+            .with_context(|| format!("fetching {}", url))?;
+
+        Ok(FileContentWrapper {
+            content: FileEntry::new_from_path(&cache_path, executable),
+            filename,
+        })
+    })?;
+
+    Ok(content.into())
+}
+
+starlark_module! { http_module =>
+    http_fetch(
+        env env,
+        url: String,
+        sha256: String,
+        filename = NoneType::None,
+        executable = NoneType::None
+    ) {
+        starlark_http_fetch(env, url, sha256, &filename, &executable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*, crate::starlark::file_content::FileContentValue, crate::starlark::testutil::*,
+        anyhow::Result, starlark::values::TypedValue, tugger_common::testutil::*,
+    };
+
+    const CONTENT: &[u8] = b"hello world";
+    const CONTENT_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    #[test]
+    fn test_filename_from_url() {
+        assert_eq!(
+            filename_from_url("https://example.com/fonts/font.ttf?download=1"),
+            Some("font.ttf".to_string())
+        );
+        assert_eq!(filename_from_url("https://example.com/fonts/"), None);
+        assert_eq!(filename_from_url("https://example.com"), None);
+    }
+
+    fn env_with_cache(name: &str, offline: bool) -> Result<(StarlarkEnvironment, String)> {
+        let root = DEFAULT_TEMP_DIR.path().join(name);
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(root.join("source.txt"), CONTENT)?;
+
+        let env = StarlarkEnvironment::new()?;
+
+        {
+            let context_value = get_context_value(&env.type_values).unwrap();
+            let mut context = context_value
+                .downcast_mut::<TuggerContextValue>()
+                .unwrap()
+                .unwrap();
+            context.http_cache_dir = Some(root.join("cache"));
+            context.offline = offline;
+        }
+
+        let url = format!(
+            "file:///{}",
+            format!("{}", root.join("source.txt").display())
+                .replace('\\', "/")
+                .trim_start_matches('/')
+        );
+
+        Ok((env, url))
+    }
+
+    #[test]
+    fn http_fetch() -> Result<()> {
+        let (mut env, url) = env_with_cache("http_fetch", false)?;
+
+        let value = env.eval(&format!("http_fetch('{}', '{}')", url, CONTENT_SHA256))?;
+        assert_eq!(value.get_type(), FileContentValue::TYPE);
+
+        let content = value.downcast_ref::<FileContentValue>().unwrap();
+        let inner = content.inner("ignored").unwrap();
+        assert_eq!(inner.filename, "source.txt");
+        assert_eq!(inner.content.resolve_content()?, CONTENT);
+
+        let value = env.eval(&format!(
+            "http_fetch('{}', '{}', filename = 'renamed.txt', executable = True)",
+            url, CONTENT_SHA256
+        ))?;
+        let content = value.downcast_ref::<FileContentValue>().unwrap();
+        let inner = content.inner("ignored").unwrap();
+        assert_eq!(inner.filename, "renamed.txt");
+        assert!(inner.content.is_executable());
+
+        Ok(())
+    }
+
+    #[test]
+    fn http_fetch_bad_digest() -> Result<()> {
+        let (mut env, url) = env_with_cache("http_fetch_bad_digest", false)?;
+
+        assert!(env
+            .eval(&format!("http_fetch('{}', '{}')", url, "0".repeat(64)))
+            .is_err());
+        assert!(env.eval(&format!("http_fetch('{}', 'abc')", url)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn http_fetch_offline() -> Result<()> {
+        let (mut env, url) = env_with_cache("http_fetch_offline", true)?;
+
+        assert!(env
+            .eval(&format!("http_fetch('{}', '{}')", url, CONTENT_SHA256))
+            .is_err());
+
+        let cache_dir = DEFAULT_TEMP_DIR.path().join("http_fetch_offline/cache");
+        std::fs::create_dir_all(&cache_dir)?;
+        std::fs::write(cache_dir.join(CONTENT_SHA256), CONTENT)?;
+
+        let value = env.eval(&format!("http_fetch('{}', '{}')", url, CONTENT_SHA256))?;
+        let content = value.downcast_ref::<FileContentValue>().unwrap();
+        assert_eq!(
+            content
+                .inner("ignored")
+                .unwrap()
+                .content
+                .resolve_content()?,
+            CONTENT
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        service::LaunchdPlist,
+        starlark::{
+            file_manifest::FileManifestValue,
+            service::{
+                add_file_to_manifest, error_context, file_content_value,
+                optional_str_dict_to_btreemap, optional_str_vec_to_vec,
+            },
+        },
+    },
+    starlark::{
+        values::{
+            error::{UnsupportedOperation, ValueError},
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::ToOptional,
+};
+
+#[derive(Clone, Debug)]
+pub struct LaunchdPlistValue {
+    pub inner: LaunchdPlist,
+}
+
+impl TypedValue for LaunchdPlistValue {
+    type Holder = Mutable<LaunchdPlistValue>;
+    const TYPE: &'static str = "LaunchdPlist";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "arguments" => {
+                self.inner.arguments = optional_str_vec_to_vec(value)?;
+            }
+            "environment" => {
+                self.inner.environment = optional_str_dict_to_btreemap(value)?;
+            }
+            "keep_alive" => {
+                self.inner.keep_alive = value.to_bool();
+            }
+            "run_at_load" => {
+                self.inner.run_at_load = value.to_bool();
+            }
+            "standard_error_path" => {
+                self.inner.standard_error_path = value.to_optional();
+            }
+            "standard_out_path" => {
+                self.inner.standard_out_path = value.to_optional();
+            }
+            "user_agent" => {
+                self.inner.user_agent = value.to_bool();
+            }
+            "user_name" => {
+                self.inner.user_name = value.to_optional();
+            }
+            "working_directory" => {
+                self.inner.working_directory = value.to_optional();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl LaunchdPlistValue {
+    fn add_to_manifest(&self, manifest: FileManifestValue) -> ValueResult {
+        const LABEL: &str = "LaunchdPlist.add_to_manifest()";
+
+        let data = error_context(LABEL, || self.inner.to_plist_xml())?;
+
+        add_file_to_manifest(LABEL, &manifest, self.inner.install_path(), data)
+    }
+
+    fn to_file_content(&self) -> ValueResult {
+        let data = error_context("LaunchdPlist.to_file_content()", || {
+            self.inner.to_plist_xml()
+        })?;
+
+        file_content_value(self.inner.plist_filename(), data, false)
+    }
+}
+
+starlark_module! { launchd_plist_module =>
+    #[allow(non_snake_case)]
+    LaunchdPlist(label: String, program: String) {
+        Ok(Value::new(LaunchdPlistValue {
+            inner: LaunchdPlist::new(label, program),
+        }))
+    }
+
+    LaunchdPlist.add_to_manifest(this, manifest: FileManifestValue) {
+        let this = this.downcast_ref::<LaunchdPlistValue>().unwrap();
+        this.add_to_manifest(manifest)
+    }
+
+    LaunchdPlist.install_script(this) {
+        let this = this.downcast_ref::<LaunchdPlistValue>().unwrap();
+        file_content_value("postinstall".to_string(), this.inner.install_script().into_bytes(), true)
+    }
+
+    LaunchdPlist.to_file_content(this) {
+        let this = this.downcast_ref::<LaunchdPlistValue>().unwrap();
+        this.to_file_content()
+    }
+
+    LaunchdPlist.uninstall_script(this) {
+        let this = this.downcast_ref::<LaunchdPlistValue>().unwrap();
+        file_content_value("uninstall".to_string(), this.inner.uninstall_script().into_bytes(), true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::testutil::*,
+        anyhow::Result,
+        std::path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn test_launchd_plist() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let job_value =
+            env.eval("job = LaunchdPlist('com.example.myapp', '/Applications/MyApp/myapp'); job")?;
+        assert_eq!(job_value.get_type(), "LaunchdPlist");
+
+        env.eval("job.arguments = ['--serve']")?;
+        env.eval("job.keep_alive = True")?;
+        env.eval("job.user_agent = True")?;
+        env.eval("job.standard_out_path = '/tmp/myapp.log'")?;
+
+        let job = job_value.downcast_ref::<LaunchdPlistValue>().unwrap();
+        let mut expected = LaunchdPlist::new("com.example.myapp", "/Applications/MyApp/myapp");
+        expected.arguments = vec!["--serve".to_string()];
+        expected.keep_alive = true;
+        expected.user_agent = true;
+        expected.standard_out_path = Some(PathBuf::from("/tmp/myapp.log"));
+        assert_eq!(job.inner, expected);
+
+        assert_eq!(
+            env.eval("job.to_file_content().filename")?.to_string(),
+            "com.example.myapp.plist"
+        );
+        assert_eq!(
+            env.eval("job.install_script().filename")?.to_string(),
+            "postinstall"
+        );
+
+        let manifest_value = env.eval("m = FileManifest(); job.add_to_manifest(m); m")?;
+        let manifest = manifest_value.downcast_ref::<FileManifestValue>().unwrap();
+        assert!(manifest
+            .inner("test")
+            .unwrap()
+            .has_path(Path::new("Library/LaunchAgents/com.example.myapp.plist")));
+
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@
 use {
     crate::starlark::{
         code_signing::{handle_signable_event, SigningAction, SigningContext},
+        ensure_no_secrets_in_manifest,
         file_content::FileContentValue,
         file_manifest::FileManifestValue,
     },
@@ -231,6 +232,8 @@ impl MacOsApplicationBundleBuilderValue {
         label: &'static str,
         dest_dir: &Path,
     ) -> Result<PathBuf, ValueError> {
+        ensure_no_secrets_in_manifest(type_values, label, self.inner.files())?;
+
         let (bundle_path, filename) = error_context(label, || {
             let bundle_path = self
                 .inner
@@ -10,12 +10,25 @@ Tugger.
 
 pub mod apple_universal_binary;
 pub mod code_signing;
+pub mod command;
 pub mod file_content;
 pub mod file_manifest;
 pub mod file_resource;
+pub mod gcs_upload;
+pub mod github_release;
+pub mod http;
+pub mod launchd_plist;
 pub mod macos_application_bundle_builder;
+pub mod msix_builder;
+pub mod nsis_installer;
+pub mod oci_image;
+pub mod publish;
 pub mod python_wheel_builder;
+pub mod s3_upload;
+pub mod service;
 pub mod snapcraft;
+pub mod squirrel_release_builder;
+pub mod systemd_unit;
 pub mod terminal;
 #[cfg(test)]
 mod testutil;
@@ -32,9 +45,50 @@ use {
             Mutable, TypedValue, Value, ValueResult,
         },
     },
-    std::ops::{Deref, DerefMut},
+    std::{
+        ops::{Deref, DerefMut},
+        path::PathBuf,
+    },
+    tugger_common::secrets::Secrets,
+    tugger_file_manifest::FileManifest,
 };
 
+/// reStructuredText documentation of the Starlark dialect.
+///
+/// Tools like editor integrations derive symbol information from these.
+pub const STARLARK_DOCS: &[&str] = &[
+    include_str!("../../docs/tugger_starlark_commands.rst"),
+    include_str!("../../docs/tugger_starlark_filesystem.rst"),
+    include_str!("../../docs/tugger_starlark_globals.rst"),
+    include_str!("../../docs/tugger_starlark_http.rst"),
+    include_str!("../../docs/tugger_starlark_terminal.rst"),
+    include_str!("../../docs/tugger_starlark_type_apple_universal_binary.rst"),
+    include_str!("../../docs/tugger_starlark_type_code_signer.rst"),
+    include_str!("../../docs/tugger_starlark_type_code_signing_request.rst"),
+    include_str!("../../docs/tugger_starlark_type_file_content.rst"),
+    include_str!("../../docs/tugger_starlark_type_file_manifest.rst"),
+    include_str!("../../docs/tugger_starlark_type_gcs_upload.rst"),
+    include_str!("../../docs/tugger_starlark_type_github_release.rst"),
+    include_str!("../../docs/tugger_starlark_type_launchd_plist.rst"),
+    include_str!("../../docs/tugger_starlark_type_macos_application_bundle_builder.rst"),
+    include_str!("../../docs/tugger_starlark_type_msix_builder.rst"),
+    include_str!("../../docs/tugger_starlark_type_nsis_installer.rst"),
+    include_str!("../../docs/tugger_starlark_type_oci_image.rst"),
+    include_str!("../../docs/tugger_starlark_type_python_wheel_builder.rst"),
+    include_str!("../../docs/tugger_starlark_type_resolved_target.rst"),
+    include_str!("../../docs/tugger_starlark_type_s3_upload.rst"),
+    include_str!("../../docs/tugger_starlark_type_snap.rst"),
+    include_str!("../../docs/tugger_starlark_type_snap_app.rst"),
+    include_str!("../../docs/tugger_starlark_type_snap_part.rst"),
+    include_str!("../../docs/tugger_starlark_type_snapcraft_builder.rst"),
+    include_str!("../../docs/tugger_starlark_type_squirrel_release_builder.rst"),
+    include_str!("../../docs/tugger_starlark_type_systemd_unit.rst"),
+    include_str!("../../docs/tugger_starlark_type_windows_service.rst"),
+    include_str!("../../docs/tugger_starlark_type_wix_bundle_builder.rst"),
+    include_str!("../../docs/tugger_starlark_type_wix_installer.rst"),
+    include_str!("../../docs/tugger_starlark_type_wix_msi_builder.rst"),
+];
+
 /// Holds global context for Tugger Starlark evaluation.
 pub struct TuggerContext {
     pub logger: slog::Logger,
@@ -45,6 +99,21 @@ pub struct TuggerContext {
     ///
     /// Setting to true causes [Self::can_prompt] to always return false.
     pub disable_interaction: bool,
+    /// Directory to cache content fetched over HTTP in.
+    ///
+    /// If not set, a directory in the build path is used.
+    pub http_cache_dir: Option<PathBuf>,
+    /// Whether network access is disabled.
+    ///
+    /// Defaults to true if the `TUGGER_OFFLINE` environment variable is set.
+    pub offline: bool,
+    /// Whether to produce reproducible build artifacts.
+    ///
+    /// When set, builders avoid embedding random values, such as generated
+    /// GUIDs, in their output.
+    pub reproducible: bool,
+    /// Secret values that must not be embedded in written artifacts.
+    pub secrets: Secrets,
 }
 
 impl TuggerContext {
@@ -57,6 +126,10 @@ impl TuggerContext {
             term_stderr: Term::stderr(),
             code_signers: vec![],
             disable_interaction: false,
+            http_cache_dir: None,
+            offline: std::env::var_os("TUGGER_OFFLINE").is_some(),
+            reproducible: false,
+            secrets: Secrets::default(),
         }
     }
 
@@ -122,6 +195,44 @@ pub fn get_context_value(type_values: &TypeValues) -> ValueResult {
         })
 }
 
+/// Ensure files about to be written don't contain a secret value.
+///
+/// `label` is the Starlark function writing the files. Links hold no content
+/// of their own and are skipped.
+pub fn ensure_no_secrets_in_manifest(
+    type_values: &TypeValues,
+    label: &str,
+    manifest: &FileManifest,
+) -> Result<(), ValueError> {
+    let secrets = {
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<TuggerContextValue>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        context.secrets.clone()
+    };
+
+    if secrets.embedding_allowed() || secrets.values().is_empty() {
+        return Ok(());
+    }
+
+    manifest
+        .iter_entries()
+        .filter(|(_, entry)| !entry.is_link())
+        .try_for_each(|(path, entry)| {
+            let data = entry.resolve_content()?;
+            secrets.ensure_not_in(&path.display().to_string(), &data)
+        })
+        .map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SECRETS",
+                message: format!("{:?}", e),
+                label: label.to_string(),
+            })
+        })
+}
+
 /// Registers Tugger's Starlark dialect.
 pub fn register_starlark_dialect(
     env: &mut Environment,
@@ -129,12 +240,24 @@ pub fn register_starlark_dialect(
 ) -> Result<(), EnvironmentError> {
     apple_universal_binary::apple_universal_binary_module(env, type_values);
     code_signing::code_signing_module(env, type_values);
+    command::command_module(env, type_values);
     file_content::file_content_module(env, type_values);
     file_manifest::file_manifest_module(env, type_values);
     file_resource::file_resource_module(env, type_values);
+    gcs_upload::gcs_upload_module(env, type_values);
+    github_release::github_release_module(env, type_values);
+    http::http_module(env, type_values);
+    launchd_plist::launchd_plist_module(env, type_values);
     macos_application_bundle_builder::macos_application_bundle_builder_module(env, type_values);
+    msix_builder::msix_builder_module(env, type_values);
+    nsis_installer::nsis_installer_module(env, type_values);
+    oci_image::oci_image_module(env, type_values);
     python_wheel_builder::python_wheel_builder_module(env, type_values);
+    s3_upload::s3_upload_module(env, type_values);
+    service::service_module(env, type_values);
     snapcraft::snapcraft_module(env, type_values);
+    squirrel_release_builder::squirrel_release_builder_module(env, type_values);
+    systemd_unit::systemd_unit_module(env, type_values);
     terminal::terminal_module(env, type_values);
     wix_bundle_builder::wix_bundle_builder_module(env, type_values);
     wix_installer::wix_installer_module(env, type_values);
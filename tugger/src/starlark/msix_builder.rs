@@ -0,0 +1,461 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{
+        code_signing::{
+            handle_file_manifest_signable_events, handle_signable_event, SigningAction,
+            SigningContext,
+        },
+        file_content::FileContentWrapper,
+        file_manifest::FileManifestValue,
+    },
+    anyhow::{anyhow, Context, Result},
+    starlark::{
+        environment::TypeValues,
+        eval::call_stack::CallStack,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
+    },
+    std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, MutexGuard},
+    },
+    tugger_code_signing::SigningDestination,
+    tugger_file_manifest::FileEntry,
+    tugger_msix::{AppxManifest, MsixApplication, MsixBuilder},
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_MSIX_BUILDER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+pub struct MsixBuilderWrapper {
+    pub builder: MsixBuilder,
+    /// Explicit filename to use for the built package.
+    pub package_filename: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct MsixBuilderValue {
+    inner: Arc<Mutex<MsixBuilderWrapper>>,
+}
+
+impl TypedValue for MsixBuilderValue {
+    type Holder = Mutable<MsixBuilderValue>;
+    const TYPE: &'static str = "MsixBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        let mut inner = self.inner(&format!("{}.{}", Self::TYPE, &attribute))?;
+
+        match attribute {
+            "arch" => {
+                inner.builder.manifest_mut().processor_architecture = value.to_string();
+            }
+            "description" => {
+                inner.builder.manifest_mut().description = Some(value.to_string());
+            }
+            "logo_path" => {
+                inner.builder.manifest_mut().logo = value.to_string();
+            }
+            "max_version_tested" => {
+                inner.builder.manifest_mut().max_version_tested = value.to_string();
+            }
+            "min_version" => {
+                inner.builder.manifest_mut().min_version = value.to_string();
+            }
+            "package_filename" => {
+                inner.package_filename = Some(value.to_string());
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MsixBuilderValue {
+    pub fn new_from_args(
+        identity_name: String,
+        publisher: String,
+        version: String,
+        display_name: String,
+        publisher_display_name: String,
+        arch: String,
+    ) -> ValueResult {
+        let mut manifest = AppxManifest::new(
+            identity_name,
+            publisher,
+            version,
+            display_name,
+            publisher_display_name,
+        );
+        manifest.processor_architecture = arch;
+
+        Ok(Value::new(MsixBuilderValue {
+            inner: Arc::new(Mutex::new(MsixBuilderWrapper {
+                builder: MsixBuilder::new(manifest),
+                package_filename: None,
+            })),
+        }))
+    }
+
+    pub fn inner(&self, label: &str) -> Result<MutexGuard<MsixBuilderWrapper>, ValueError> {
+        self.inner.try_lock().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_MSIX_BUILDER",
+                message: format!("error obtaining lock: {}", e),
+                label: label.to_string(),
+            })
+        })
+    }
+
+    pub fn add_application(
+        &mut self,
+        id: String,
+        executable: String,
+        display_name: String,
+        description: &Value,
+        background_color: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "MsixBuilder.add_application()";
+
+        let description = optional_str_arg("description", description)?;
+        let background_color = optional_str_arg("background_color", background_color)?;
+
+        let mut inner = self.inner(LABEL)?;
+
+        let mut app = MsixApplication::new(id, executable, display_name);
+        if let Some(description) = description {
+            app.description = description;
+        }
+        if let Some(color) = background_color {
+            app.background_color = color;
+        }
+
+        inner.builder.manifest_mut().applications.push(app);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_files_manifest(
+        &mut self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        manifest: FileManifestValue,
+    ) -> ValueResult {
+        const LABEL: &str = "MsixBuilder.add_files_manifest()";
+
+        let mut inner = self.inner(LABEL)?;
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let manifest = handle_file_manifest_signable_events(
+                type_values,
+                call_stack,
+                &manifest,
+                LABEL,
+                SigningAction::WindowsInstallerFileAdded,
+            )?;
+
+            inner
+                .builder
+                .add_files_manifest(&manifest)
+                .context("adding files manifest")
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn materialize(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        label: &'static str,
+        build_dir: &Path,
+    ) -> Result<PathBuf, ValueError> {
+        let logger = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.logger().clone()
+        };
+
+        let package_filename = self.package_filename(label)?;
+        let inner = self.inner(label)?;
+
+        let package_path = build_dir.join(&package_filename);
+
+        error_context(label, || {
+            inner
+                .builder
+                .build(&logger, &package_path)
+                .context("building MSIX package")
+        })?;
+
+        let candidate = package_path.as_path().into();
+        let mut context = SigningContext::new(
+            label,
+            SigningAction::WindowsInstallerCreation,
+            &package_filename,
+            &candidate,
+        );
+        context.set_path(&package_path);
+        context.set_signing_destination(SigningDestination::File(package_path.clone()));
+
+        handle_signable_event(type_values, call_stack, context)?;
+
+        Ok(package_path)
+    }
+
+    fn materialize_temp_dir(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        label: &'static str,
+    ) -> Result<(FileEntry, String), ValueError> {
+        let build_path = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.build_path().to_path_buf()
+        };
+
+        let dest_dir = error_context(label, || {
+            tempfile::Builder::new()
+                .prefix("msix-builder-")
+                .tempdir_in(&build_path)
+                .context("creating temp directory")
+        })?;
+
+        let package_path = self.materialize(type_values, call_stack, label, dest_dir.path())?;
+
+        let entry = FileEntry::new_from_path(&package_path, false);
+
+        let (entry, filename) = error_context(label, || {
+            let entry = entry
+                .to_memory()
+                .context("converting FileEntry to in-memory")?;
+
+            let filename = package_path
+                .file_name()
+                .ok_or_else(|| anyhow!("unable to resolve file name of generated package"))?;
+
+            Ok((entry, filename.to_string_lossy().to_string()))
+        })?;
+
+        Ok((entry, filename))
+    }
+
+    pub fn build(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        target: String,
+    ) -> ValueResult {
+        const LABEL: &str = "MsixBuilder.build()";
+
+        let dest_dir = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.target_build_path(&target)
+        };
+
+        let package_path = self.materialize(type_values, call_stack, LABEL, &dest_dir)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path { path: package_path },
+                output_path: dest_dir,
+            },
+        }))
+    }
+
+    pub fn package_filename(&self, label: &str) -> Result<String, ValueError> {
+        let inner = self.inner(label)?;
+
+        Ok(if let Some(filename) = &inner.package_filename {
+            filename.clone()
+        } else {
+            inner.builder.default_package_filename()
+        })
+    }
+
+    pub fn to_file_content(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+    ) -> ValueResult {
+        const LABEL: &str = "MsixBuilder.to_file_content()";
+
+        let (entry, filename) = self.materialize_temp_dir(type_values, call_stack, LABEL)?;
+
+        Ok(FileContentWrapper {
+            content: entry,
+            filename,
+        }
+        .into())
+    }
+
+    fn write_to_directory(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        path: String,
+    ) -> ValueResult {
+        const LABEL: &str = "MsixBuilder.write_to_directory()";
+
+        let dest_dir = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.resolve_path(path)
+        };
+
+        let (entry, filename) = self.materialize_temp_dir(type_values, call_stack, LABEL)?;
+
+        let package_path = dest_dir.join(&filename);
+
+        error_context(LABEL, || {
+            entry
+                .write_to_path(&package_path)
+                .with_context(|| format!("writing package to {}", package_path.display()))
+        })?;
+
+        Ok(Value::from(format!("{}", package_path.display())))
+    }
+}
+
+starlark_module! { msix_builder_module =>
+    #[allow(non_snake_case)]
+    MsixBuilder(
+        identity_name: String,
+        publisher: String,
+        version: String,
+        display_name: String,
+        publisher_display_name: String,
+        arch: String = "x64".to_string()
+    ) {
+        MsixBuilderValue::new_from_args(identity_name, publisher, version, display_name, publisher_display_name, arch)
+    }
+
+    MsixBuilder.add_application(
+        this,
+        id: String,
+        executable: String,
+        display_name: String,
+        description = NoneType::None,
+        background_color = NoneType::None
+    ) {
+        let mut this = this.downcast_mut::<MsixBuilderValue>().unwrap().unwrap();
+        this.add_application(id, executable, display_name, &description, &background_color)
+    }
+
+    MsixBuilder.add_files_manifest(env env, call_stack cs, this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<MsixBuilderValue>().unwrap().unwrap();
+        this.add_files_manifest(env, cs, manifest)
+    }
+
+    MsixBuilder.build(env env, call_stack cs, this, target: String) {
+        let this = this.downcast_ref::<MsixBuilderValue>().unwrap();
+        this.build(env, cs, target)
+    }
+
+    MsixBuilder.to_file_content(env env, call_stack cs, this) {
+        let this = this.downcast_ref::<MsixBuilderValue>().unwrap();
+        this.to_file_content(env, cs)
+    }
+
+    MsixBuilder.write_to_directory(env env, call_stack cs, this, path: String) {
+        let this = this.downcast_ref::<MsixBuilderValue>().unwrap();
+        this.write_to_directory(env, cs, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*};
+
+    #[test]
+    fn test_new() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let builder_value =
+            env.eval("MsixBuilder('Tugger.App', 'CN=Tugger', '1.0.0.0', 'App', 'Tugger')")?;
+        assert_eq!(builder_value.get_type(), "MsixBuilder");
+        let builder = builder_value.downcast_ref::<MsixBuilderValue>().unwrap();
+        assert_eq!(
+            builder
+                .inner
+                .lock()
+                .unwrap()
+                .builder
+                .manifest()
+                .processor_architecture,
+            "x64"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'app.exe', content = 'dummy'))")?;
+        env.eval("msix = MsixBuilder('Tugger.App', 'CN=Tugger', '1.0.0.0', 'App', 'Tugger')")?;
+        env.eval("msix.add_application('App', 'app.exe', 'App')")?;
+        env.eval("msix.add_files_manifest(m)")?;
+        env.eval("msix.package_filename = 'app.msix'")?;
+        env.eval("msix.build('test_build')")?;
+
+        let context_value = get_context_value(&env.type_values).unwrap();
+        let context = context_value.downcast_ref::<EnvironmentContext>().unwrap();
+
+        let package_path = context.target_build_path("test_build").join("app.msix");
+        assert!(package_path.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{
+        code_signing::{
+            handle_file_manifest_signable_events, handle_signable_event, SigningAction,
+            SigningContext,
+        },
+        file_content::FileContentWrapper,
+        file_manifest::FileManifestValue,
+    },
+    anyhow::{anyhow, Context, Result},
+    starlark::{
+        environment::TypeValues,
+        eval::call_stack::CallStack,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+    },
+    std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, MutexGuard},
+    },
+    tugger_code_signing::SigningDestination,
+    tugger_file_manifest::FileEntry,
+    tugger_nsis::{ExecutionLevel, NsisInstallerBuilder},
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_NSIS_INSTALLER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+#[derive(Clone)]
+pub struct NsisInstallerValue {
+    inner: Arc<Mutex<NsisInstallerBuilder>>,
+}
+
+impl TypedValue for NsisInstallerValue {
+    type Holder = Mutable<NsisInstallerValue>;
+    const TYPE: &'static str = "NsisInstaller";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        let label = format!("{}.{}", Self::TYPE, &attribute);
+        let mut inner = self.inner(&label)?;
+
+        match attribute {
+            "execution_level" => {
+                let level = error_context(&label, || {
+                    ExecutionLevel::try_from(value.to_string().as_str())
+                })?;
+                *inner = inner.clone().execution_level(level);
+            }
+            "icon_path" => {
+                *inner = inner.clone().icon_path(value.to_string());
+            }
+            "install_dir" => {
+                *inner = inner.clone().install_dir(value.to_string());
+            }
+            "installer_filename" => {
+                *inner = inner.clone().installer_filename(value.to_string());
+            }
+            "license_path" => {
+                *inner = inner.clone().license_path(value.to_string());
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl NsisInstallerValue {
+    pub fn new_from_args(
+        product_name: String,
+        product_version: String,
+        manufacturer: String,
+    ) -> ValueResult {
+        Ok(Value::new(NsisInstallerValue {
+            inner: Arc::new(Mutex::new(NsisInstallerBuilder::new(
+                product_name,
+                product_version,
+                manufacturer,
+            ))),
+        }))
+    }
+
+    pub fn inner(&self, label: &str) -> Result<MutexGuard<NsisInstallerBuilder>, ValueError> {
+        self.inner.try_lock().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_NSIS_INSTALLER",
+                message: format!("error obtaining lock: {}", e),
+                label: label.to_string(),
+            })
+        })
+    }
+
+    pub fn add_start_menu_shortcut(&mut self, name: String, target: String) -> ValueResult {
+        const LABEL: &str = "NsisInstaller.add_start_menu_shortcut()";
+
+        self.inner(LABEL)?.add_shortcut(name, target);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_files_manifest(
+        &mut self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        manifest: FileManifestValue,
+    ) -> ValueResult {
+        const LABEL: &str = "NsisInstaller.add_files_manifest()";
+
+        let mut inner = self.inner(LABEL)?;
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let manifest = handle_file_manifest_signable_events(
+                type_values,
+                call_stack,
+                &manifest,
+                LABEL,
+                SigningAction::WindowsInstallerFileAdded,
+            )?;
+
+            inner
+                .add_files_manifest(&manifest)
+                .context("adding files manifest")
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn materialize(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        label: &'static str,
+        build_dir: &Path,
+    ) -> Result<PathBuf, ValueError> {
+        let logger = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.logger().clone()
+        };
+
+        let inner = self.inner(label)?;
+
+        let installer_path = error_context(label, || {
+            inner
+                .build(&logger, build_dir)
+                .context("building NSIS installer")
+        })?;
+
+        let filename = inner.resolve_installer_filename();
+
+        let candidate = installer_path.as_path().into();
+        let mut context = SigningContext::new(
+            label,
+            SigningAction::WindowsInstallerCreation,
+            &filename,
+            &candidate,
+        );
+        context.set_path(&installer_path);
+        context.set_signing_destination(SigningDestination::File(installer_path.clone()));
+
+        handle_signable_event(type_values, call_stack, context)?;
+
+        Ok(installer_path)
+    }
+
+    fn materialize_temp_dir(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        label: &'static str,
+    ) -> Result<(FileEntry, String), ValueError> {
+        let build_path = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.build_path().to_path_buf()
+        };
+
+        let dest_dir = error_context(label, || {
+            tempfile::Builder::new()
+                .prefix("nsis-installer-")
+                .tempdir_in(&build_path)
+                .context("creating temp directory")
+        })?;
+
+        let installer_path = self.materialize(type_values, call_stack, label, dest_dir.path())?;
+
+        let entry = FileEntry::new_from_path(&installer_path, false);
+
+        let (entry, filename) = error_context(label, || {
+            let entry = entry
+                .to_memory()
+                .context("converting FileEntry to in-memory")?;
+
+            let filename = installer_path
+                .file_name()
+                .ok_or_else(|| anyhow!("unable to resolve file name of generated installer"))?;
+
+            Ok((entry, filename.to_string_lossy().to_string()))
+        })?;
+
+        Ok((entry, filename))
+    }
+
+    pub fn build(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        target: String,
+    ) -> ValueResult {
+        const LABEL: &str = "NsisInstaller.build()";
+
+        let dest_dir = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.target_build_path(&target)
+        };
+
+        let installer_path = self.materialize(type_values, call_stack, LABEL, &dest_dir)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path {
+                    path: installer_path,
+                },
+                output_path: dest_dir,
+            },
+        }))
+    }
+
+    pub fn to_file_content(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+    ) -> ValueResult {
+        const LABEL: &str = "NsisInstaller.to_file_content()";
+
+        let (entry, filename) = self.materialize_temp_dir(type_values, call_stack, LABEL)?;
+
+        Ok(FileContentWrapper {
+            content: entry,
+            filename,
+        }
+        .into())
+    }
+
+    fn write_to_directory(
+        &self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        path: String,
+    ) -> ValueResult {
+        const LABEL: &str = "NsisInstaller.write_to_directory()";
+
+        let dest_dir = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.resolve_path(path)
+        };
+
+        let (entry, filename) = self.materialize_temp_dir(type_values, call_stack, LABEL)?;
+
+        let installer_path = dest_dir.join(&filename);
+
+        error_context(LABEL, || {
+            entry
+                .write_to_path(&installer_path)
+                .with_context(|| format!("writing installer to {}", installer_path.display()))
+        })?;
+
+        Ok(Value::from(format!("{}", installer_path.display())))
+    }
+}
+
+starlark_module! { nsis_installer_module =>
+    #[allow(non_snake_case)]
+    NsisInstaller(product_name: String, product_version: String, manufacturer: String) {
+        NsisInstallerValue::new_from_args(product_name, product_version, manufacturer)
+    }
+
+    NsisInstaller.add_start_menu_shortcut(this, name: String, target: String) {
+        let mut this = this.downcast_mut::<NsisInstallerValue>().unwrap().unwrap();
+        this.add_start_menu_shortcut(name, target)
+    }
+
+    NsisInstaller.add_files_manifest(env env, call_stack cs, this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<NsisInstallerValue>().unwrap().unwrap();
+        this.add_files_manifest(env, cs, manifest)
+    }
+
+    NsisInstaller.build(env env, call_stack cs, this, target: String) {
+        let this = this.downcast_ref::<NsisInstallerValue>().unwrap();
+        this.build(env, cs, target)
+    }
+
+    NsisInstaller.to_file_content(env env, call_stack cs, this) {
+        let this = this.downcast_ref::<NsisInstallerValue>().unwrap();
+        this.to_file_content(env, cs)
+    }
+
+    NsisInstaller.write_to_directory(env env, call_stack cs, this, path: String) {
+        let this = this.downcast_ref::<NsisInstallerValue>().unwrap();
+        this.write_to_directory(env, cs, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*};
+
+    #[test]
+    fn test_new() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let installer_value = env.eval("NsisInstaller('App', '1.0', 'Tugger')")?;
+        assert_eq!(installer_value.get_type(), "NsisInstaller");
+        let installer = installer_value
+            .downcast_ref::<NsisInstallerValue>()
+            .unwrap();
+        assert_eq!(
+            installer.inner.lock().unwrap().resolve_installer_filename(),
+            "App-1.0-setup.exe"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_attributes() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("nsis = NsisInstaller('App', '1.0', 'Tugger')")?;
+        env.eval("nsis.execution_level = 'user'")?;
+        env.eval("nsis.installer_filename = 'setup.exe'")?;
+        assert!(env.eval("nsis.execution_level = 'system'").is_err());
+
+        let installer_value = env.eval("nsis")?;
+        let installer = installer_value
+            .downcast_ref::<NsisInstallerValue>()
+            .unwrap();
+        let inner = installer.inner.lock().unwrap();
+        assert_eq!(inner.resolve_installer_filename(), "setup.exe");
+        assert_eq!(inner.resolve_install_dir(), "$LOCALAPPDATA\\Programs\\App");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_files_manifest() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'app.exe', content = 'dummy'))")?;
+        env.eval("nsis = NsisInstaller('App', '1.0', 'Tugger')")?;
+        env.eval("nsis.add_files_manifest(m)")?;
+        env.eval("nsis.add_start_menu_shortcut('App', 'app.exe')")?;
+
+        let installer_value = env.eval("nsis")?;
+        let installer = installer_value
+            .downcast_ref::<NsisInstallerValue>()
+            .unwrap();
+        let inner = installer.inner.lock().unwrap();
+        assert!(inner.files().has_path("app.exe"));
+        inner.validate()?;
+
+        Ok(())
+    }
+}
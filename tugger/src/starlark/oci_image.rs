@@ -0,0 +1,297 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        oci::{ImageReference, OciImageBuilder},
+        starlark::{
+            ensure_no_secrets_in_manifest, file_manifest::FileManifestValue, TuggerContextValue,
+        },
+    },
+    anyhow::{anyhow, Context},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode, ToOptional, TryToOptional,
+    },
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap},
+        path::Path,
+    },
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_OCI_IMAGE",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+fn string_map(value: &Value) -> Result<BTreeMap<String, String>, ValueError> {
+    let v: Option<HashMap<Cow<'static, str>, Cow<'static, str>>> = value.try_to_optional()?;
+
+    Ok(v.unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect())
+}
+
+#[derive(Clone, Debug)]
+pub struct OciImageValue {
+    pub inner: OciImageBuilder,
+
+    /// Tag to record in the written image layout.
+    pub tag: Option<String>,
+
+    /// Image reference to push the built image to.
+    pub push_to: Option<String>,
+}
+
+impl TypedValue for OciImageValue {
+    type Holder = Mutable<OciImageValue>;
+    const TYPE: &'static str = "OCIImage";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "architecture" => {
+                self.inner.platform.architecture = value.to_string();
+            }
+            "cmd" => {
+                self.inner.cmd = value.try_to_optional()?;
+            }
+            "entrypoint" => {
+                self.inner.entrypoint = value.try_to_optional()?;
+            }
+            "env" => {
+                self.inner.env = string_map(&value)?;
+            }
+            "exposed_ports" => {
+                let v: Option<Vec<String>> = value.try_to_optional()?;
+                self.inner.exposed_ports = v.unwrap_or_default();
+            }
+            "labels" => {
+                self.inner.labels = string_map(&value)?;
+            }
+            "os" => {
+                self.inner.platform.os = value.to_string();
+            }
+            "push_to" => {
+                self.push_to = value.to_optional();
+            }
+            "tag" => {
+                self.tag = value.to_optional();
+            }
+            "user" => {
+                self.inner.user = value.to_optional();
+            }
+            "variant" => {
+                self.inner.platform.variant = value.to_optional();
+            }
+            "working_dir" => {
+                self.inner.working_dir = value.to_optional();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl OciImageValue {
+    pub fn new_from_args(base: &Value) -> ValueResult {
+        let base = optional_str_arg("base", base)?;
+
+        let base = error_context("OCIImage()", || match base.as_deref() {
+            None | Some("scratch") => Ok(None),
+            Some(base) => Ok(Some(base.parse::<ImageReference>()?)),
+        })?;
+
+        Ok(Value::new(OciImageValue {
+            inner: OciImageBuilder {
+                base,
+                ..Default::default()
+            },
+            tag: None,
+            push_to: None,
+        }))
+    }
+
+    pub fn add_manifest(&mut self, manifest: FileManifestValue, prefix: &Value) -> ValueResult {
+        const LABEL: &str = "OCIImage.add_manifest()";
+
+        let prefix = optional_str_arg("prefix", prefix)?;
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            // Image paths are absolute. But manifest paths are always relative.
+            let prefix = prefix.as_deref().unwrap_or("").trim_start_matches('/');
+
+            for (path, entry) in manifest.iter_entries() {
+                self.inner
+                    .files
+                    .add_file_entry(Path::new(prefix).join(path), entry.clone())
+                    .with_context(|| format!("adding {}", path.display()))?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "OCIImage.build()";
+
+        let push_to = if let Some(push_to) = &self.push_to {
+            let offline = {
+                let context_value = crate::starlark::get_context_value(type_values)?;
+                let context = context_value
+                    .downcast_ref::<TuggerContextValue>()
+                    .ok_or(ValueError::IncorrectParameterType)?;
+
+                context.offline
+            };
+
+            Some(error_context(LABEL, || {
+                if offline {
+                    Err(anyhow!("pushing images is not possible in offline mode"))
+                } else {
+                    push_to.parse::<ImageReference>()
+                }
+            })?)
+        } else {
+            None
+        };
+
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        let output_path = context.target_build_path(&target);
+
+        ensure_no_secrets_in_manifest(type_values, LABEL, &self.inner.files)?;
+
+        error_context(LABEL, || {
+            let image =
+                self.inner
+                    .write_layout(context.logger(), &output_path, self.tag.as_deref())?;
+
+            if let Some(reference) = push_to {
+                image.push(context.logger(), &reference)?;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::None,
+                output_path,
+            },
+        }))
+    }
+}
+
+starlark_module! { oci_image_module =>
+    #[allow(non_snake_case)]
+    OCIImage(base = NoneType::None) {
+        OciImageValue::new_from_args(&base)
+    }
+
+    OCIImage.add_manifest(this, manifest: FileManifestValue, prefix = NoneType::None) {
+        let mut this = this.downcast_mut::<OciImageValue>().unwrap().unwrap();
+        this.add_manifest(manifest, &prefix)
+    }
+
+    OCIImage.build(env env, this, target: String) {
+        let this = this.downcast_ref::<OciImageValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result};
+
+    #[test]
+    fn test_oci_image() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("image = OCIImage('gcr.io/distroless/cc-debian11')")?;
+        env.eval("image.architecture = 'arm64'")?;
+        env.eval("image.entrypoint = ['/app/myapp']")?;
+        env.eval("image.env = {'PYTHONUNBUFFERED': '1'}")?;
+        env.eval("image.exposed_ports = ['8080/tcp']")?;
+        env.eval("image.push_to = 'localhost:5000/myapp:1.0'")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'myapp', content = 'dummy'))")?;
+        env.eval("image.add_manifest(m, prefix = '/app')")?;
+
+        let value = env.eval("image")?;
+        let image = value.downcast_ref::<OciImageValue>().unwrap();
+        assert_eq!(
+            image.inner.base.as_ref().unwrap().repository,
+            "distroless/cc-debian11"
+        );
+        assert_eq!(image.inner.platform.architecture, "arm64");
+        assert_eq!(image.inner.entrypoint, Some(vec!["/app/myapp".to_string()]));
+        assert_eq!(image.inner.env["PYTHONUNBUFFERED"], "1");
+        assert_eq!(image.push_to, Some("localhost:5000/myapp:1.0".to_string()));
+        assert!(image.inner.files.has_path("app/myapp"));
+
+        assert!(env.eval("image.bogus = True").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_scratch() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("image = OCIImage('scratch')")?;
+        env.eval("image.tag = '1.0'")?;
+        env.eval("image.entrypoint = ['/myapp']")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'myapp', content = 'dummy'))")?;
+        env.eval("image.add_manifest(m)")?;
+
+        let resolved = env.eval("image.build('image')")?;
+        assert_eq!(resolved.get_type(), "ResolvedTarget");
+
+        let resolved = resolved.downcast_ref::<ResolvedTargetValue>().unwrap();
+        assert!(resolved.inner.output_path.join("index.json").exists());
+        assert!(resolved.inner.output_path.join("oci-layout").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/*! Functionality shared by the Starlark upload types. */
+
+use {
+    crate::{
+        publish::{add_path_to_manifest, UploadSettings},
+        starlark::{file_manifest::FileManifestValue, TuggerContextValue},
+    },
+    anyhow::{anyhow, Context},
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{RuntimeError, ValueError},
+            none::NoneType,
+            Value, ValueResult,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode, ToOptional,
+    },
+    std::path::PathBuf,
+    tugger_file_manifest::FileManifest,
+};
+
+pub(crate) fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_PUBLISH",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+/// Set an attribute shared by all upload types.
+///
+/// Returns false if the attribute isn't known.
+pub(crate) fn set_settings_attr(
+    settings: &mut UploadSettings,
+    attribute: &str,
+    value: &Value,
+) -> Result<bool, ValueError> {
+    match attribute {
+        "acl" => {
+            settings.acl = value.to_optional();
+        }
+        "cache_control" => {
+            settings.cache_control = value.to_optional();
+        }
+        "content_type" => {
+            settings.content_type = value.to_optional();
+        }
+        "prefix" => {
+            settings.prefix = value.to_optional().unwrap_or_default();
+        }
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+pub(crate) fn add_path_to_files(
+    type_values: &TypeValues,
+    files: &mut FileManifest,
+    label: &str,
+    path: String,
+    key_arg: &str,
+    key: &Value,
+) -> ValueResult {
+    let key = optional_str_arg(key_arg, key)?;
+
+    let path = {
+        let context_value = get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<EnvironmentContext>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        context.resolve_path(path)
+    };
+
+    error_context(label, || {
+        add_path_to_manifest(files, &path, key.as_deref())
+            .with_context(|| format!("adding {}", path.display()))
+    })?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+pub(crate) fn add_manifest_to_files(
+    files: &mut FileManifest,
+    label: &str,
+    manifest: FileManifestValue,
+) -> ValueResult {
+    let manifest = manifest.inner(label)?;
+
+    error_context(label, || {
+        files.add_manifest(&manifest).context("adding manifest")
+    })?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+/// Resolve the logger and build path of a target, erroring in offline mode.
+pub(crate) fn upload_context(
+    type_values: &TypeValues,
+    label: &str,
+    target: &str,
+) -> Result<(slog::Logger, PathBuf), ValueError> {
+    let offline = {
+        let context_value = crate::starlark::get_context_value(type_values)?;
+        let context = context_value
+            .downcast_ref::<TuggerContextValue>()
+            .ok_or(ValueError::IncorrectParameterType)?;
+
+        context.offline
+    };
+
+    error_context(label, || {
+        if offline {
+            Err(anyhow!("uploading is not possible in offline mode"))
+        } else {
+            Ok(())
+        }
+    })?;
+
+    let context_value = get_context_value(type_values)?;
+    let context = context_value
+        .downcast_ref::<EnvironmentContext>()
+        .ok_or(ValueError::IncorrectParameterType)?;
+
+    Ok((context.logger().clone(), context.target_build_path(target)))
+}
+
+pub(crate) fn uploaded_target(label: &str, output_path: PathBuf, keys: Vec<String>) -> ValueResult {
+    error_context(label, || {
+        std::fs::create_dir_all(&output_path)
+            .with_context(|| format!("creating {}", output_path.display()))?;
+
+        let mut listing = keys.join("\n");
+        listing.push('\n');
+
+        let path = output_path.join("uploaded.txt");
+        std::fs::write(&path, listing).with_context(|| format!("writing {}", path.display()))
+    })?;
+
+    Ok(Value::new(ResolvedTargetValue {
+        inner: ResolvedTarget {
+            run_mode: RunMode::None,
+            output_path,
+        },
+    }))
+}
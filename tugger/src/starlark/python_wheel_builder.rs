@@ -3,7 +3,10 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::starlark::file_content::{FileContentValue, FileContentWrapper},
+    crate::starlark::{
+        ensure_no_secrets_in_manifest,
+        file_content::{FileContentValue, FileContentWrapper},
+    },
     anyhow::{anyhow, Context},
     python_packaging::wheel_builder::WheelBuilder,
     slog::warn,
@@ -306,6 +309,9 @@ impl PythonWheelBuilderValue {
 
         let path = context.resolve_path(path);
 
+        let manifest = error_context(LABEL, || inner.build_file_manifest())?;
+        ensure_no_secrets_in_manifest(type_values, LABEL, &manifest)?;
+
         let wheel_path = error_context(LABEL, || {
             std::fs::create_dir_all(&path)
                 .with_context(|| format!("creating directory {}", path.display()))?;
@@ -330,6 +336,9 @@ impl PythonWheelBuilderValue {
 
         let output_directory = context.target_build_path(&target);
 
+        let manifest = error_context(LABEL, || inner.build_file_manifest())?;
+        ensure_no_secrets_in_manifest(type_values, LABEL, &manifest)?;
+
         let wheel_path = error_context(LABEL, || {
             warn!(
                 context.logger(),
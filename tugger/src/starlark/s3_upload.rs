@@ -0,0 +1,177 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        publish::S3Upload,
+        starlark::{
+            file_manifest::FileManifestValue,
+            publish::{
+                add_manifest_to_files, add_path_to_files, error_context, set_settings_attr,
+                upload_context, uploaded_target,
+            },
+        },
+    },
+    starlark::{
+        environment::TypeValues,
+        values::{
+            error::{UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{optional_str_arg, ToOptional, TryToOptional},
+};
+
+#[derive(Clone, Debug)]
+pub struct S3UploadValue {
+    pub inner: S3Upload,
+}
+
+impl TypedValue for S3UploadValue {
+    type Holder = Mutable<S3UploadValue>;
+    const TYPE: &'static str = "S3Upload";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        if set_settings_attr(&mut self.inner.settings, attribute, &value)? {
+            return Ok(());
+        }
+
+        match attribute {
+            "cloudfront_distribution_id" => {
+                self.inner.cloudfront_distribution_id = value.to_optional();
+            }
+            "endpoint" => {
+                self.inner.endpoint = value.to_optional();
+            }
+            "invalidation_paths" => {
+                let v: Option<Vec<String>> = value.try_to_optional()?;
+                self.inner.invalidation_paths = v.unwrap_or_default();
+            }
+            "region" => {
+                self.inner.region = value.to_optional();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl S3UploadValue {
+    pub fn new_from_args(bucket: String, prefix: &Value) -> ValueResult {
+        let mut inner = S3Upload::new(bucket);
+        inner.settings.prefix = optional_str_arg("prefix", prefix)?.unwrap_or_default();
+
+        Ok(Value::new(S3UploadValue { inner }))
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "S3Upload.build()";
+
+        let (logger, output_path) = upload_context(type_values, LABEL, &target)?;
+
+        let keys = error_context(LABEL, || self.inner.upload(&logger))?;
+
+        uploaded_target(LABEL, output_path, keys)
+    }
+}
+
+starlark_module! { s3_upload_module =>
+    #[allow(non_snake_case)]
+    S3Upload(bucket: String, prefix = NoneType::None) {
+        S3UploadValue::new_from_args(bucket, &prefix)
+    }
+
+    S3Upload.add_path(env env, this, path: String, key = NoneType::None) {
+        let mut this = this.downcast_mut::<S3UploadValue>().unwrap().unwrap();
+        add_path_to_files(env, &mut this.inner.settings.files, "S3Upload.add_path()", path, "key", &key)
+    }
+
+    S3Upload.add_manifest(this, manifest: FileManifestValue) {
+        let mut this = this.downcast_mut::<S3UploadValue>().unwrap().unwrap();
+        add_manifest_to_files(&mut this.inner.settings.files, "S3Upload.add_manifest()", manifest)
+    }
+
+    S3Upload.build(env env, this, target: String) {
+        let this = this.downcast_ref::<S3UploadValue>().unwrap();
+        this.build(env, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::{testutil::*, TuggerContextValue},
+        anyhow::Result,
+    };
+
+    #[test]
+    fn test_s3_upload() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("upload = S3Upload('releases', prefix = 'myapp/1.0')")?;
+        env.eval("upload.region = 'us-west-2'")?;
+        env.eval("upload.cache_control = 'max-age=60'")?;
+        env.eval("upload.acl = 'public-read'")?;
+        env.eval("upload.cloudfront_distribution_id = 'E123'")?;
+        env.eval("upload.invalidation_paths = ['/myapp/*']")?;
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'app.zip', content = 'dummy'))")?;
+        env.eval("upload.add_manifest(m)")?;
+
+        let value = env.eval("upload")?;
+        let upload = value.downcast_ref::<S3UploadValue>().unwrap();
+        assert_eq!(upload.inner.bucket, "releases");
+        assert_eq!(upload.inner.region, Some("us-west-2".to_string()));
+        assert_eq!(
+            upload.inner.cloudfront_distribution_id,
+            Some("E123".to_string())
+        );
+        assert_eq!(upload.inner.invalidation_paths, vec!["/myapp/*"]);
+        assert_eq!(upload.inner.settings.acl, Some("public-read".to_string()));
+
+        let objects = upload.inner.settings.objects()?;
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].key, "myapp/1.0/app.zip");
+
+        assert!(env.eval("upload.bogus = True").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_offline() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        {
+            let context_value = crate::starlark::get_context_value(&env.type_values).unwrap();
+            let mut context = context_value
+                .downcast_mut::<TuggerContextValue>()
+                .unwrap()
+                .unwrap();
+            context.offline = true;
+        }
+
+        env.eval("upload = S3Upload('releases')")?;
+        assert!(env.eval("upload.build('upload')").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{file_content::FileContentWrapper, file_manifest::FileManifestValue},
+    starlark::{
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{ToOptional, TryToOptional},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap},
+        path::PathBuf,
+    },
+    tugger_file_manifest::FileEntry,
+    tugger_wix::{ServiceStartType, WindowsService},
+};
+
+pub(crate) fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_SERVICE",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+pub(crate) fn optional_str_vec_to_vec(value: Value) -> Result<Vec<String>, ValueError> {
+    let v: Option<Vec<String>> = value.try_to_optional()?;
+
+    Ok(v.unwrap_or_default())
+}
+
+pub(crate) fn optional_str_dict_to_btreemap(
+    value: Value,
+) -> Result<BTreeMap<String, String>, ValueError> {
+    let v: Option<HashMap<Cow<'static, str>, Cow<'static, str>>> = value.try_to_optional()?;
+
+    Ok(v.unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect())
+}
+
+pub(crate) fn file_content_value(filename: String, data: Vec<u8>, executable: bool) -> ValueResult {
+    Ok(FileContentWrapper {
+        content: FileEntry::new_from_data(data, executable),
+        filename,
+    }
+    .into())
+}
+
+pub(crate) fn add_file_to_manifest(
+    label: &str,
+    manifest: &FileManifestValue,
+    path: PathBuf,
+    data: Vec<u8>,
+) -> ValueResult {
+    let mut inner = manifest.inner(label)?;
+
+    error_context(label, || {
+        inner
+            .add_file_entry(path, FileEntry::new_from_data(data, false))
+            .map_err(anyhow::Error::new)
+    })?;
+
+    Ok(Value::new(NoneType::None))
+}
+
+#[derive(Clone, Debug)]
+pub struct WindowsServiceValue {
+    pub inner: WindowsService,
+}
+
+impl TypedValue for WindowsServiceValue {
+    type Holder = Mutable<WindowsServiceValue>;
+    const TYPE: &'static str = "WindowsService";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "account" => {
+                self.inner.account = value.to_optional();
+            }
+            "arguments" => {
+                self.inner.arguments = value.to_optional();
+            }
+            "description" => {
+                self.inner.description = value.to_optional();
+            }
+            "display_name" => {
+                self.inner.display_name = value.to_optional();
+            }
+            "start_type" => {
+                self.inner.start_type = error_context("WindowsService.start_type", || {
+                    ServiceStartType::try_from(value.to_string().as_str())
+                })?;
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+starlark_module! { service_module =>
+    #[allow(non_snake_case)]
+    WindowsService(name: String, executable: String) {
+        Ok(Value::new(WindowsServiceValue {
+            inner: WindowsService::new(name, executable),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, anyhow::Result};
+
+    #[test]
+    fn test_windows_service() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let service_value = env.eval("service = WindowsService('MyApp', 'myapp.exe'); service")?;
+        assert_eq!(service_value.get_type(), "WindowsService");
+
+        env.eval("service.display_name = 'My App'")?;
+        env.eval("service.arguments = '--service'")?;
+        env.eval("service.start_type = 'demand'")?;
+        assert!(env.eval("service.start_type = 'manual'").is_err());
+
+        let service = service_value.downcast_ref::<WindowsServiceValue>().unwrap();
+        let mut expected = WindowsService::new("MyApp", "myapp.exe");
+        expected.display_name = Some("My App".to_string());
+        expected.arguments = Some("--service".to_string());
+        expected.start_type = ServiceStartType::Demand;
+        assert_eq!(service.inner, expected);
+
+        Ok(())
+    }
+}
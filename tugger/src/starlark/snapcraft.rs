@@ -3,7 +3,7 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use {
-    crate::starlark::file_manifest::FileManifestValue,
+    crate::starlark::{ensure_no_secrets_in_manifest, file_manifest::FileManifestValue},
     starlark::{
         environment::TypeValues,
         values::{
@@ -737,6 +737,12 @@ impl SnapcraftBuilderValue<'static> {
     }
 
     pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        ensure_no_secrets_in_manifest(
+            type_values,
+            "SnapcraftBuilder.build()",
+            self.inner.install_files(),
+        )?;
+
         let context_value = get_context_value(type_values)?;
         let context = context_value
             .downcast_ref::<EnvironmentContext>()
@@ -1070,7 +1076,10 @@ mod tests {
                 },
             ]
         );
-        assert_eq!(builder.inner.install_files(), &FileManifest::default());
+        assert_eq!(
+            builder.inner.install_files(),
+            &FileManifest::new_with_links()
+        );
 
         Ok(())
     }
@@ -0,0 +1,264 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::starlark::{
+        code_signing::{handle_file_manifest_signable_events, SigningAction},
+        file_manifest::FileManifestValue,
+    },
+    anyhow::{Context, Result},
+    starlark::{
+        environment::TypeValues,
+        eval::call_stack::CallStack,
+        values::{
+            error::{RuntimeError, UnsupportedOperation, ValueError},
+            none::NoneType,
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::{
+        get_context_value, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+    },
+    std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex, MutexGuard},
+    },
+    tugger_squirrel::SquirrelReleaseBuilder,
+};
+
+fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    f().map_err(|e| {
+        ValueError::Runtime(RuntimeError {
+            code: "TUGGER_SQUIRREL_RELEASE_BUILDER",
+            message: format!("{:?}", e),
+            label: label.to_string(),
+        })
+    })
+}
+
+#[derive(Clone)]
+pub struct SquirrelReleaseBuilderValue {
+    inner: Arc<Mutex<SquirrelReleaseBuilder>>,
+}
+
+impl TypedValue for SquirrelReleaseBuilderValue {
+    type Holder = Mutable<SquirrelReleaseBuilderValue>;
+    const TYPE: &'static str = "SquirrelReleaseBuilder";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        let mut inner = self.inner(&format!("{}.{}", Self::TYPE, &attribute))?;
+
+        match attribute {
+            "description" => {
+                *inner = inner.clone().description(value.to_string());
+            }
+            "title" => {
+                *inner = inner.clone().title(value.to_string());
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SquirrelReleaseBuilderValue {
+    pub fn new_from_args(id: String, version: String, authors: String) -> ValueResult {
+        Ok(Value::new(SquirrelReleaseBuilderValue {
+            inner: Arc::new(Mutex::new(SquirrelReleaseBuilder::new(
+                id, version, authors,
+            ))),
+        }))
+    }
+
+    pub fn inner(&self, label: &str) -> Result<MutexGuard<SquirrelReleaseBuilder>, ValueError> {
+        self.inner.try_lock().map_err(|e| {
+            ValueError::Runtime(RuntimeError {
+                code: "TUGGER_SQUIRREL_RELEASE_BUILDER",
+                message: format!("error obtaining lock: {}", e),
+                label: label.to_string(),
+            })
+        })
+    }
+
+    pub fn add_files_manifest(
+        &mut self,
+        type_values: &TypeValues,
+        call_stack: &mut CallStack,
+        manifest: FileManifestValue,
+    ) -> ValueResult {
+        const LABEL: &str = "SquirrelReleaseBuilder.add_files_manifest()";
+
+        let mut inner = self.inner(LABEL)?;
+        let manifest = manifest.inner(LABEL)?;
+
+        error_context(LABEL, || {
+            let manifest = handle_file_manifest_signable_events(
+                type_values,
+                call_stack,
+                &manifest,
+                LABEL,
+                SigningAction::WindowsInstallerFileAdded,
+            )?;
+
+            inner
+                .add_files_manifest(&manifest)
+                .context("adding files manifest")
+        })?;
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    fn materialize(
+        &self,
+        type_values: &TypeValues,
+        label: &'static str,
+        releases_dir: &Path,
+    ) -> Result<PathBuf, ValueError> {
+        let logger = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.logger().clone()
+        };
+
+        let inner = self.inner(label)?;
+
+        error_context(label, || {
+            inner
+                .build(&logger, releases_dir)
+                .context("building Squirrel release")
+        })
+    }
+
+    pub fn build(&self, type_values: &TypeValues, target: String) -> ValueResult {
+        const LABEL: &str = "SquirrelReleaseBuilder.build()";
+
+        let dest_dir = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.target_build_path(&target)
+        };
+
+        let package_path = self.materialize(type_values, LABEL, &dest_dir)?;
+
+        Ok(Value::new(ResolvedTargetValue {
+            inner: ResolvedTarget {
+                run_mode: RunMode::Path { path: package_path },
+                output_path: dest_dir,
+            },
+        }))
+    }
+
+    fn write_to_directory(&self, type_values: &TypeValues, path: String) -> ValueResult {
+        const LABEL: &str = "SquirrelReleaseBuilder.write_to_directory()";
+
+        let dest_dir = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.resolve_path(path)
+        };
+
+        let package_path = self.materialize(type_values, LABEL, &dest_dir)?;
+
+        Ok(Value::from(format!("{}", package_path.display())))
+    }
+}
+
+starlark_module! { squirrel_release_builder_module =>
+    #[allow(non_snake_case)]
+    SquirrelReleaseBuilder(id: String, version: String, authors: String) {
+        SquirrelReleaseBuilderValue::new_from_args(id, version, authors)
+    }
+
+    SquirrelReleaseBuilder.add_files_manifest(
+        env env,
+        call_stack cs,
+        this,
+        manifest: FileManifestValue
+    ) {
+        let mut this = this.downcast_mut::<SquirrelReleaseBuilderValue>().unwrap().unwrap();
+        this.add_files_manifest(env, cs, manifest)
+    }
+
+    SquirrelReleaseBuilder.build(env env, this, target: String) {
+        let this = this.downcast_ref::<SquirrelReleaseBuilderValue>().unwrap();
+        this.build(env, target)
+    }
+
+    SquirrelReleaseBuilder.write_to_directory(env env, this, path: String) {
+        let this = this.downcast_ref::<SquirrelReleaseBuilderValue>().unwrap();
+        this.write_to_directory(env, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::starlark::testutil::*, tugger_squirrel::ReleasesFile};
+
+    #[test]
+    fn test_new() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let builder_value = env.eval("SquirrelReleaseBuilder('MyApp', '1.0.0', 'Tugger')")?;
+        assert_eq!(builder_value.get_type(), "SquirrelReleaseBuilder");
+        let builder = builder_value
+            .downcast_ref::<SquirrelReleaseBuilderValue>()
+            .unwrap();
+        assert_eq!(
+            builder.inner.lock().unwrap().package_filename(),
+            "MyApp-1.0.0-full.nupkg"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("m = FileManifest()")?;
+        env.eval("m.add_file(FileContent(filename = 'app.exe', content = 'dummy'))")?;
+        env.eval("release = SquirrelReleaseBuilder('MyApp', '1.0.0', 'Tugger')")?;
+        env.eval("release.title = 'My App'")?;
+        env.eval("release.add_files_manifest(m)")?;
+        env.eval("release.build('test_build')")?;
+
+        let context_value = get_context_value(&env.type_values).unwrap();
+        let context = context_value.downcast_ref::<EnvironmentContext>().unwrap();
+
+        let build_path = context.target_build_path("test_build");
+        assert!(build_path.join("MyApp-1.0.0-full.nupkg").exists());
+
+        let releases = ReleasesFile::from_path(build_path.join("RELEASES"))?;
+        assert_eq!(releases.entries().len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use {
+    crate::{
+        service::SystemdUnit,
+        starlark::{
+            file_manifest::FileManifestValue,
+            service::{
+                add_file_to_manifest, file_content_value, optional_str_dict_to_btreemap,
+                optional_str_vec_to_vec,
+            },
+        },
+    },
+    starlark::{
+        values::{
+            error::{UnsupportedOperation, ValueError},
+            {Mutable, TypedValue, Value, ValueResult},
+        },
+        {
+            starlark_fun, starlark_module, starlark_parse_param_type, starlark_signature,
+            starlark_signature_extraction, starlark_signatures,
+        },
+    },
+    starlark_dialect_build_targets::ToOptional,
+};
+
+#[derive(Clone, Debug)]
+pub struct SystemdUnitValue {
+    pub inner: SystemdUnit,
+}
+
+impl TypedValue for SystemdUnitValue {
+    type Holder = Mutable<SystemdUnitValue>;
+    const TYPE: &'static str = "SystemdUnit";
+
+    fn values_for_descendant_check_and_freeze(&self) -> Box<dyn Iterator<Item = Value>> {
+        Box::new(std::iter::empty())
+    }
+
+    fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
+        match attribute {
+            "after" => {
+                self.inner.after = optional_str_vec_to_vec(value)?;
+            }
+            "arguments" => {
+                self.inner.arguments = optional_str_vec_to_vec(value)?;
+            }
+            "description" => {
+                self.inner.description = value.to_optional();
+            }
+            "environment" => {
+                self.inner.environment = optional_str_dict_to_btreemap(value)?;
+            }
+            "group" => {
+                self.inner.group = value.to_optional();
+            }
+            "restart" => {
+                self.inner.restart = value.to_string();
+            }
+            "user" => {
+                self.inner.user = value.to_optional();
+            }
+            "wanted_by" => {
+                self.inner.wanted_by = value.to_string();
+            }
+            "working_directory" => {
+                self.inner.working_directory = value.to_optional();
+            }
+            attr => {
+                return Err(ValueError::OperationNotSupported {
+                    op: UnsupportedOperation::SetAttr(attr.to_string()),
+                    left: Self::TYPE.to_string(),
+                    right: None,
+                })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SystemdUnitValue {
+    fn add_to_manifest(&self, manifest: FileManifestValue) -> ValueResult {
+        add_file_to_manifest(
+            "SystemdUnit.add_to_manifest()",
+            &manifest,
+            self.inner.install_path(),
+            self.inner.to_unit_file().into_bytes(),
+        )
+    }
+
+    fn to_file_content(&self) -> ValueResult {
+        file_content_value(
+            self.inner.unit_filename(),
+            self.inner.to_unit_file().into_bytes(),
+            false,
+        )
+    }
+}
+
+starlark_module! { systemd_unit_module =>
+    #[allow(non_snake_case)]
+    SystemdUnit(name: String, executable: String) {
+        Ok(Value::new(SystemdUnitValue {
+            inner: SystemdUnit::new(name, executable),
+        }))
+    }
+
+    SystemdUnit.add_to_manifest(this, manifest: FileManifestValue) {
+        let this = this.downcast_ref::<SystemdUnitValue>().unwrap();
+        this.add_to_manifest(manifest)
+    }
+
+    SystemdUnit.install_script(this) {
+        let this = this.downcast_ref::<SystemdUnitValue>().unwrap();
+        file_content_value("postinst".to_string(), this.inner.install_script().into_bytes(), true)
+    }
+
+    SystemdUnit.to_file_content(this) {
+        let this = this.downcast_ref::<SystemdUnitValue>().unwrap();
+        this.to_file_content()
+    }
+
+    SystemdUnit.uninstall_script(this) {
+        let this = this.downcast_ref::<SystemdUnitValue>().unwrap();
+        file_content_value("prerm".to_string(), this.inner.uninstall_script().into_bytes(), true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::starlark::{file_content::FileContentValue, testutil::*},
+        anyhow::Result,
+        std::path::Path,
+    };
+
+    #[test]
+    fn test_systemd_unit() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        let unit_value = env.eval("unit = SystemdUnit('myapp', '/opt/myapp/myapp'); unit")?;
+        assert_eq!(unit_value.get_type(), "SystemdUnit");
+
+        env.eval("unit.arguments = ['--serve']")?;
+        env.eval("unit.description = 'My App'")?;
+        env.eval("unit.environment = {'MYAPP_HOME': '/var/lib/myapp'}")?;
+        env.eval("unit.user = 'myapp'")?;
+        env.eval("unit.restart = 'always'")?;
+        assert!(env.eval("unit.unknown = True").is_err());
+
+        let unit = unit_value.downcast_ref::<SystemdUnitValue>().unwrap();
+        let mut expected = SystemdUnit::new("myapp", "/opt/myapp/myapp");
+        expected.arguments = vec!["--serve".to_string()];
+        expected.description = Some("My App".to_string());
+        expected
+            .environment
+            .insert("MYAPP_HOME".to_string(), "/var/lib/myapp".to_string());
+        expected.user = Some("myapp".to_string());
+        expected.restart = "always".to_string();
+        assert_eq!(unit.inner, expected);
+
+        let content = env.eval("unit.to_file_content()")?;
+        assert_eq!(content.get_type(), FileContentValue::TYPE);
+        assert_eq!(
+            env.eval("unit.to_file_content().filename")?.to_string(),
+            "myapp.service"
+        );
+        assert_eq!(
+            env.eval("unit.install_script().filename")?.to_string(),
+            "postinst"
+        );
+        assert!(env.eval("unit.uninstall_script().executable")?.to_bool());
+
+        let manifest_value = env.eval("m = FileManifest(); unit.add_to_manifest(m); m")?;
+        let manifest = manifest_value.downcast_ref::<FileManifestValue>().unwrap();
+        assert!(manifest
+            .inner("test")
+            .unwrap()
+            .has_path(Path::new("lib/systemd/system/myapp.service")));
+
+        Ok(())
+    }
+}
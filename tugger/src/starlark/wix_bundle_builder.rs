@@ -29,7 +29,7 @@ use {
     tugger_code_signing::SigningDestination,
     tugger_file_manifest::FileEntry,
     tugger_windows::VcRedistributablePlatform,
-    tugger_wix::{MsiPackage, WiXBundleInstallerBuilder},
+    tugger_wix::{MsiPackage, VcRedistributableMode, WiXBundleInstallerBuilder},
 };
 
 fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
@@ -86,11 +86,12 @@ impl<'a> WiXBundleBuilderValue<'a> {
         Ok(Value::new(NoneType::None))
     }
 
-    /// WiXBundleBuilder.add_vc_redistributable(platform)
+    /// WiXBundleBuilder.add_vc_redistributable(platform, mode="embedded")
     pub fn add_vc_redistributable(
         &mut self,
         type_values: &TypeValues,
         platform: String,
+        mode: String,
     ) -> ValueResult {
         let context_value = get_context_value(type_values)?;
         let context = context_value
@@ -100,9 +101,11 @@ impl<'a> WiXBundleBuilderValue<'a> {
         error_context("WiXBundleBuilder.add_vc_redistributable()", || {
             let platform = VcRedistributablePlatform::try_from(platform.as_str())
                 .context("obtaining VcRedistributablePlatform from str")?;
+            let mode = VcRedistributableMode::try_from(mode.as_str())
+                .context("obtaining VcRedistributableMode from str")?;
 
             self.inner
-                .add_vc_redistributable(context.logger(), platform, context.build_path())
+                .add_vc_redistributable(context.logger(), platform, mode, context.build_path())
                 .context("adding VC++ Redistributable to bundle builder")
         })?;
 
@@ -314,9 +317,14 @@ starlark_module! { wix_bundle_builder_module =>
         this.add_condition(condition, message)
     }
 
-    WiXBundleBuilder.add_vc_redistributable(env env, this, platform: String) {
+    WiXBundleBuilder.add_vc_redistributable(
+        env env,
+        this,
+        platform: String,
+        mode: String = "embedded".to_string()
+    ) {
         let mut this = this.downcast_mut::<WiXBundleBuilderValue>().unwrap().unwrap();
-        this.add_vc_redistributable(env, platform)
+        this.add_vc_redistributable(env, platform, mode)
     }
 
     WiXBundleBuilder.add_wix_msi_builder(
@@ -375,6 +383,18 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_add_vc_redistributable_invalid_mode() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("builder = WiXBundleBuilder('prefix', 'name', '0.1', 'manufacturer')")?;
+        assert!(env
+            .eval("builder.add_vc_redistributable('x64', mode = 'web')")
+            .is_err());
+
+        Ok(())
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_build() -> Result<()> {
@@ -10,6 +10,8 @@ use {
         },
         file_content::FileContentWrapper,
         file_manifest::FileManifestValue,
+        service::WindowsServiceValue,
+        TuggerContextValue,
     },
     anyhow::{anyhow, Context, Result},
     starlark::{
@@ -26,7 +28,8 @@ use {
         },
     },
     starlark_dialect_build_targets::{
-        get_context_value, EnvironmentContext, ResolvedTarget, ResolvedTargetValue, RunMode,
+        get_context_value, optional_str_arg, EnvironmentContext, ResolvedTarget,
+        ResolvedTargetValue, RunMode,
     },
     std::{
         path::{Path, PathBuf},
@@ -35,7 +38,9 @@ use {
     tugger_code_signing::SigningDestination,
     tugger_file_manifest::FileEntry,
     tugger_windows::VcRedistributablePlatform,
-    tugger_wix::WiXSimpleMsiBuilder,
+    tugger_wix::{
+        DowngradePolicy, FileAssociation, InstallScope, Shortcut, UrlProtocol, WiXSimpleMsiBuilder,
+    },
 };
 
 fn error_context<F, T>(label: &str, f: F) -> Result<T, ValueError>
@@ -73,24 +78,73 @@ impl TypedValue for WiXMsiBuilderValue {
     }
 
     fn set_attr(&mut self, attribute: &str, value: Value) -> Result<(), ValueError> {
-        let mut inner = self.inner(&format!("{}.{}", Self::TYPE, &attribute))?;
+        let label = format!("{}.{}", Self::TYPE, &attribute);
+        let mut inner = self.inner(&label)?;
 
         match attribute {
+            "about_url" => {
+                inner.builder = inner.builder.clone().about_url(value.to_string());
+            }
+            "allow_same_version_upgrades" => {
+                inner.builder = inner
+                    .builder
+                    .clone()
+                    .allow_same_version_upgrades(value.to_bool());
+            }
+            "add_to_path" => {
+                inner.builder = inner.builder.clone().add_to_path(value.to_bool());
+            }
             "arch" => {
                 inner.arch = value.to_string();
             }
             "banner_bmp_path" => {
                 inner.builder = inner.builder.clone().banner_bmp_path(value.to_string());
             }
+            "comments" => {
+                inner.builder = inner.builder.clone().comments(value.to_string());
+            }
+            "contact" => {
+                inner.builder = inner.builder.clone().contact(value.to_string());
+            }
             "dialog_bmp_path" => {
                 inner.builder = inner.builder.clone().dialog_bmp_path(value.to_string());
             }
+            "downgrade_error_message" => {
+                inner.builder = inner
+                    .builder
+                    .clone()
+                    .downgrade_error_message(value.to_string());
+            }
+            "downgrade_policy" => {
+                let policy = error_context(&label, || {
+                    DowngradePolicy::try_from(value.to_string().as_str())
+                })?;
+                inner.builder = inner.builder.clone().downgrade_policy(policy);
+            }
             "eula_rtf_path" => {
                 inner.builder = inner.builder.clone().eula_rtf_path(value.to_string());
             }
             "help_url" => {
                 inner.builder = inner.builder.clone().help_url(value.to_string());
             }
+            "install_scope" => {
+                let scope = error_context(&label, || {
+                    InstallScope::try_from(value.to_string().as_str())
+                })?;
+                inner.builder = inner.builder.clone().install_scope(scope);
+            }
+            "launch_after_install" => {
+                inner.builder = inner
+                    .builder
+                    .clone()
+                    .launch_after_install(value.to_string());
+            }
+            "launch_checkbox_text" => {
+                inner.builder = inner
+                    .builder
+                    .clone()
+                    .launch_checkbox_text(value.to_string());
+            }
             "license_path" => {
                 inner.builder = inner.builder.clone().license_path(value.to_string());
             }
@@ -106,6 +160,9 @@ impl TypedValue for WiXMsiBuilderValue {
             "product_icon_path" => {
                 inner.builder = inner.builder.clone().product_icon_path(value.to_string());
             }
+            "update_url" => {
+                inner.builder = inner.builder.clone().update_url(value.to_string());
+            }
             "upgrade_code" => {
                 inner.builder = inner.builder.clone().upgrade_code(value.to_string());
             }
@@ -185,13 +242,97 @@ impl WiXMsiBuilderValue {
         Ok(Value::new(NoneType::None))
     }
 
+    pub fn add_start_menu_shortcut(
+        &mut self,
+        name: String,
+        target: String,
+        description: &Value,
+        arguments: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "WiXMSIBuilder.add_start_menu_shortcut()";
+
+        let description = optional_str_arg("description", description)?;
+        let arguments = optional_str_arg("arguments", arguments)?;
+
+        let mut inner = self.inner(LABEL)?;
+
+        let mut shortcut = Shortcut::new(name, target);
+        shortcut.description = description;
+        shortcut.arguments = arguments;
+
+        inner.builder.add_shortcut(shortcut);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_file_association(
+        &mut self,
+        extension: String,
+        prog_id: String,
+        executable: String,
+        description: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "WiXMSIBuilder.add_file_association()";
+
+        let description = optional_str_arg("description", description)?;
+
+        let mut inner = self.inner(LABEL)?;
+
+        let mut association = FileAssociation::new(&extension, prog_id, executable);
+        association.description = description;
+
+        inner.builder.add_file_association(association);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_url_protocol(
+        &mut self,
+        scheme: String,
+        executable: String,
+        description: &Value,
+    ) -> ValueResult {
+        const LABEL: &str = "WiXMSIBuilder.add_url_protocol()";
+
+        let description = optional_str_arg("description", description)?;
+
+        let mut inner = self.inner(LABEL)?;
+
+        let mut protocol = UrlProtocol::new(scheme, executable);
+        protocol.description = description;
+
+        inner.builder.add_url_protocol(protocol);
+
+        Ok(Value::new(NoneType::None))
+    }
+
+    pub fn add_windows_service(&mut self, service: WindowsServiceValue) -> ValueResult {
+        const LABEL: &str = "WiXMSIBuilder.add_windows_service()";
+
+        let mut inner = self.inner(LABEL)?;
+
+        inner.builder.add_service(service.inner);
+
+        Ok(Value::new(NoneType::None))
+    }
+
     pub fn add_visual_cpp_redistributable(
         &mut self,
+        type_values: &TypeValues,
         redist_version: String,
         platform: String,
     ) -> ValueResult {
         const LABEL: &str = "WiXMSIBuilder.add_visual_cpp_redistributable()";
 
+        let logger = {
+            let context_value = get_context_value(type_values)?;
+            let context = context_value
+                .downcast_ref::<EnvironmentContext>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            context.logger().clone()
+        };
+
         let mut inner = self.inner(LABEL)?;
 
         error_context(LABEL, || {
@@ -200,7 +341,7 @@ impl WiXMsiBuilderValue {
 
             inner
                 .builder
-                .add_visual_cpp_redistributable(&redist_version, platform)
+                .add_visual_cpp_redistributable(&logger, &redist_version, platform)
                 .context("adding Visual C++ redistributable")
         })?;
 
@@ -223,12 +364,23 @@ impl WiXMsiBuilderValue {
             context.logger().clone()
         };
 
+        let reproducible = {
+            let tugger_context_value = crate::starlark::get_context_value(type_values)?;
+            let tugger_context = tugger_context_value
+                .downcast_ref::<TuggerContextValue>()
+                .ok_or(ValueError::IncorrectParameterType)?;
+
+            tugger_context.reproducible
+        };
+
         let msi_filename = self.msi_filename(label)?;
         let inner = self.inner(label)?;
 
         let msi_path = error_context(label, || {
             let builder = inner
                 .builder
+                .clone()
+                .reproducible(reproducible)
                 .to_installer_builder(&inner.arch, build_dir)
                 .context("converting WiXSimpleMSiBuilder to WiXInstallerBuilder")?;
 
@@ -393,18 +545,56 @@ starlark_module! { wix_msi_builder_module =>
         WiXMsiBuilderValue::new_from_args(id_prefix, product_name, product_version, product_manufacturer, arch)
     }
 
+    WiXMSIBuilder.add_file_association(
+        this,
+        extension: String,
+        prog_id: String,
+        executable: String,
+        description = NoneType::None
+    ) {
+        let mut this = this.downcast_mut::<WiXMsiBuilderValue>().unwrap().unwrap();
+        this.add_file_association(extension, prog_id, executable, &description)
+    }
+
     WiXMSIBuilder.add_program_files_manifest(env env, call_stack cs, this, manifest: FileManifestValue) {
         let mut this = this.downcast_mut::<WiXMsiBuilderValue>().unwrap().unwrap();
         this.add_program_files_manifest(env, cs, manifest)
     }
 
+    WiXMSIBuilder.add_start_menu_shortcut(
+        this,
+        name: String,
+        target: String,
+        description = NoneType::None,
+        arguments = NoneType::None
+    ) {
+        let mut this = this.downcast_mut::<WiXMsiBuilderValue>().unwrap().unwrap();
+        this.add_start_menu_shortcut(name, target, &description, &arguments)
+    }
+
+    WiXMSIBuilder.add_url_protocol(
+        this,
+        scheme: String,
+        executable: String,
+        description = NoneType::None
+    ) {
+        let mut this = this.downcast_mut::<WiXMsiBuilderValue>().unwrap().unwrap();
+        this.add_url_protocol(scheme, executable, &description)
+    }
+
     WiXMSIBuilder.add_visual_cpp_redistributable(
+        env env,
         this,
         redist_version: String,
         platform: String
     ) {
         let mut this = this.downcast_mut::<WiXMsiBuilderValue>().unwrap().unwrap();
-        this.add_visual_cpp_redistributable(redist_version, platform)
+        this.add_visual_cpp_redistributable(env, redist_version, platform)
+    }
+
+    WiXMSIBuilder.add_windows_service(this, service: WindowsServiceValue) {
+        let mut this = this.downcast_mut::<WiXMsiBuilderValue>().unwrap().unwrap();
+        this.add_windows_service(service)
     }
 
     WiXMSIBuilder.build(env env, call_stack cs, this, target: String) {
@@ -441,6 +631,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_set_install_options() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("msi = WiXMSIBuilder('prefix', 'name', '0.1', 'manufacturer')")?;
+        env.eval("msi.install_scope = 'perUser'")?;
+        env.eval("msi.downgrade_policy = 'allow'")?;
+        env.eval("msi.allow_same_version_upgrades = True")?;
+        env.eval("msi.about_url = 'https://example.com/'")?;
+        env.eval("msi.launch_after_install = 'name.exe'")?;
+
+        assert!(env.eval("msi.install_scope = 'everyone'").is_err());
+        assert!(env.eval("msi.downgrade_policy = 'ignore'").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_registrations() -> Result<()> {
+        let mut env = StarlarkEnvironment::new()?;
+
+        env.eval("msi = WiXMSIBuilder('prefix', 'name', '0.1', 'manufacturer')")?;
+        env.eval("msi.add_to_path = False")?;
+        env.eval("msi.add_start_menu_shortcut('Name', 'name.exe', description = 'Runs name')")?;
+        env.eval("msi.add_file_association('.nam', 'Name.Document', 'name.exe')")?;
+        env.eval("msi.add_url_protocol('name', 'name.exe', description = 'URL:Name')")?;
+        env.eval("service = WindowsService('NameService', 'name.exe')")?;
+        env.eval("service.start_type = 'demand'")?;
+        env.eval("msi.add_windows_service(service)")?;
+
+        Ok(())
+    }
+
     #[cfg(windows)]
     #[test]
     fn test_build() -> Result<()> {
@@ -482,8 +705,12 @@ mod tests {
 
     #[test]
     fn test_add_visual_cpp_redistributable() -> Result<()> {
-        if tugger_windows::find_visual_cpp_redistributable("14", VcRedistributablePlatform::X64)
-            .is_err()
+        if tugger_windows::find_visual_cpp_redistributable(
+            &tugger_common::testutil::get_logger()?,
+            "14",
+            VcRedistributablePlatform::X64,
+        )
+        .is_err()
         {
             eprintln!("skipping test because Visual C++ Redistributable files not found");
             return Ok(());